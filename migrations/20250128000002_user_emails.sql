@@ -0,0 +1,31 @@
+-- Additional email addresses per user
+CREATE TABLE IF NOT EXISTS user_emails (
+    id UUID PRIMARY KEY NOT NULL,
+    tenant_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    is_primary BOOLEAN NOT NULL DEFAULT FALSE,
+    verified BOOLEAN NOT NULL DEFAULT FALSE,
+    verified_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- An address can only belong to one user per tenant
+CREATE UNIQUE INDEX idx_user_emails_tenant_email ON user_emails(tenant_id, LOWER(email));
+
+-- Each user has at most one primary address
+CREATE UNIQUE INDEX idx_user_emails_primary ON user_emails(user_id) WHERE is_primary;
+
+CREATE INDEX idx_user_emails_user_id ON user_emails(user_id);
+
+ALTER TABLE user_emails ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_policy ON user_emails
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
+
+-- Existing login addresses become verified primary addresses
+INSERT INTO user_emails (id, tenant_id, user_id, email, is_primary, verified, verified_at, created_at)
+SELECT gen_random_uuid(), tenant_id, id, email, TRUE, TRUE, created_at, created_at
+FROM users;
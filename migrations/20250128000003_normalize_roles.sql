@@ -0,0 +1,80 @@
+-- Relational storage for roles and permissions
+CREATE TABLE IF NOT EXISTS roles (
+    id UUID PRIMARY KEY NOT NULL,
+    tenant_id UUID NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    role_type VARCHAR(50) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    UNIQUE(tenant_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS permissions (
+    id UUID PRIMARY KEY NOT NULL,
+    role_id UUID NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    action VARCHAR(50) NOT NULL,
+    resource VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE,
+    UNIQUE(role_id, action, resource)
+);
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id UUID NOT NULL,
+    role_id UUID NOT NULL,
+    tenant_id UUID NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    PRIMARY KEY (user_id, role_id),
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_permissions_role_id ON permissions(role_id);
+CREATE INDEX idx_user_roles_role_id ON user_roles(role_id);
+
+ALTER TABLE roles ENABLE ROW LEVEL SECURITY;
+ALTER TABLE user_roles ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_policy ON roles
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
+
+CREATE POLICY tenant_isolation_policy ON user_roles
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
+
+CREATE TRIGGER update_roles_updated_at
+    BEFORE UPDATE ON roles
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at();
+
+-- Migrate the JSON encoded roles stored on users.
+-- Every user used to carry its own copy of a role, so copies with the same
+-- name within a tenant are merged into one role holding the union of their
+-- permissions.
+INSERT INTO roles (id, tenant_id, name, role_type)
+SELECT DISTINCT ON (u.tenant_id, r.role->>'name')
+    (r.role->>'id')::uuid, u.tenant_id, r.role->>'name', LOWER(r.role->>'role_type')
+FROM users u
+CROSS JOIN LATERAL (SELECT role_json::jsonb AS role FROM unnest(u.roles) AS role_json) r
+ORDER BY u.tenant_id, r.role->>'name', u.created_at
+ON CONFLICT DO NOTHING;
+
+INSERT INTO permissions (id, role_id, name, action, resource)
+SELECT DISTINCT ON (ro.id, LOWER(p.permission->>'action'), p.permission->>'resource')
+    gen_random_uuid(), ro.id, p.permission->>'name', LOWER(p.permission->>'action'), p.permission->>'resource'
+FROM users u
+CROSS JOIN LATERAL (SELECT role_json::jsonb AS role FROM unnest(u.roles) AS role_json) r
+CROSS JOIN LATERAL jsonb_array_elements(r.role->'permissions') AS p(permission)
+JOIN roles ro ON ro.tenant_id = u.tenant_id AND ro.name = r.role->>'name'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO user_roles (user_id, role_id, tenant_id)
+SELECT DISTINCT u.id, ro.id, u.tenant_id
+FROM users u
+CROSS JOIN LATERAL (SELECT role_json::jsonb AS role FROM unnest(u.roles) AS role_json) r
+JOIN roles ro ON ro.tenant_id = u.tenant_id AND ro.name = r.role->>'name'
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE users DROP COLUMN roles;
@@ -0,0 +1,21 @@
+-- Asynchronously generated exports of the data stored about a user
+CREATE TABLE IF NOT EXISTS data_exports (
+    id UUID PRIMARY KEY NOT NULL,
+    tenant_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    requested_by UUID NOT NULL,
+    status VARCHAR(50) NOT NULL,
+    archive JSONB,
+    error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    completed_at TIMESTAMP WITH TIME ZONE,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_data_exports_user_id ON data_exports(user_id);
+
+ALTER TABLE data_exports ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_policy ON data_exports
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
@@ -0,0 +1,38 @@
+-- Right-to-erasure requests; personal data is anonymized once the grace period ended
+CREATE TABLE IF NOT EXISTS data_erasures (
+    id UUID PRIMARY KEY NOT NULL,
+    tenant_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    requested_by UUID NOT NULL,
+    status VARCHAR(50) NOT NULL,
+    error TEXT,
+    scheduled_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    cancelled_at TIMESTAMP WITH TIME ZONE,
+    completed_at TIMESTAMP WITH TIME ZONE,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_data_erasures_user_id ON data_erasures(user_id);
+CREATE INDEX idx_data_erasures_scheduled_at ON data_erasures(scheduled_at) WHERE status = 'pending';
+CREATE UNIQUE INDEX idx_data_erasures_pending ON data_erasures(user_id) WHERE status = 'pending';
+
+-- Proof that the data of a user was erased; holds no personal data
+CREATE TABLE IF NOT EXISTS user_tombstones (
+    user_id UUID PRIMARY KEY NOT NULL,
+    tenant_id UUID NOT NULL,
+    erasure_id UUID NOT NULL,
+    requested_by UUID NOT NULL,
+    erased_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+ALTER TABLE data_erasures ENABLE ROW LEVEL SECURITY;
+ALTER TABLE user_tombstones ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_policy ON data_erasures
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
+
+CREATE POLICY tenant_isolation_policy ON user_tombstones
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
@@ -0,0 +1,21 @@
+-- Owner-scoped permissions only grant access to resources the user owns
+ALTER TABLE permissions ADD COLUMN scope VARCHAR(50) NOT NULL DEFAULT 'all';
+
+-- Ownership of application resources by users or groups (tenant roles)
+CREATE TABLE IF NOT EXISTS resource_owners (
+    tenant_id UUID NOT NULL,
+    resource_type VARCHAR(255) NOT NULL,
+    resource_id VARCHAR(255) NOT NULL,
+    owner_type VARCHAR(50) NOT NULL,
+    owner_id UUID NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    PRIMARY KEY (tenant_id, resource_type, resource_id, owner_type, owner_id),
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_resource_owners_owner ON resource_owners(owner_type, owner_id);
+
+ALTER TABLE resource_owners ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_policy ON resource_owners
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
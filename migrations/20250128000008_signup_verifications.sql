@@ -0,0 +1,18 @@
+-- Pending public signups; tenant and admin stay inactive until the email is verified
+CREATE TABLE IF NOT EXISTS signup_verifications (
+    id UUID PRIMARY KEY NOT NULL,
+    tenant_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_signup_verifications_expires_at ON signup_verifications(expires_at);
+
+ALTER TABLE signup_verifications ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_policy ON signup_verifications
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
@@ -0,0 +1,24 @@
+-- Pending links between an SSO identity and an existing password user with the same email
+CREATE TABLE IF NOT EXISTS sso_link_requests (
+    id UUID PRIMARY KEY NOT NULL,
+    tenant_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    provider_id UUID NOT NULL,
+    external_id TEXT NOT NULL,
+    email TEXT NOT NULL,
+    code_hash VARCHAR(64),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (provider_id) REFERENCES sso_providers(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_sso_link_requests_user_id ON sso_link_requests(user_id);
+CREATE INDEX idx_sso_link_requests_expires_at ON sso_link_requests(expires_at);
+
+ALTER TABLE sso_link_requests ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_policy ON sso_link_requests
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
@@ -0,0 +1,20 @@
+-- Approval queue of self-signed-up tenants that are vetted manually before activation
+CREATE TABLE IF NOT EXISTS tenant_approvals (
+    id UUID PRIMARY KEY NOT NULL,
+    tenant_id UUID NOT NULL UNIQUE,
+    user_id UUID NOT NULL,
+    status VARCHAR(50) NOT NULL,
+    reason TEXT,
+    reviewed_by UUID,
+    reviewed_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_tenant_approvals_status ON tenant_approvals(status, created_at);
+
+ALTER TABLE tenant_approvals ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_policy ON tenant_approvals
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
@@ -0,0 +1,19 @@
+-- Roles inherit the permissions of their parent roles, e.g. Admin inherits User
+CREATE TABLE IF NOT EXISTS role_parents (
+    role_id UUID NOT NULL,
+    parent_id UUID NOT NULL,
+    tenant_id UUID NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    PRIMARY KEY (role_id, parent_id),
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE,
+    FOREIGN KEY (parent_id) REFERENCES roles(id) ON DELETE CASCADE,
+    CHECK (role_id <> parent_id)
+);
+
+CREATE INDEX idx_role_parents_parent_id ON role_parents(parent_id);
+
+ALTER TABLE role_parents ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_policy ON role_parents
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
@@ -0,0 +1,2 @@
+-- Deny permissions override the grants of all roles of a user
+ALTER TABLE permissions ADD COLUMN effect VARCHAR(50) NOT NULL DEFAULT 'allow';
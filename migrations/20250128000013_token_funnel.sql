@@ -0,0 +1,16 @@
+-- Issuance and completion counters of token based flows per tenant for funnel monitoring;
+-- no tenant foreign key so counters of discarded signups are kept
+CREATE TABLE IF NOT EXISTS token_funnel (
+    tenant_id UUID NOT NULL,
+    flow VARCHAR(50) NOT NULL,
+    issued BIGINT NOT NULL DEFAULT 0,
+    completed BIGINT NOT NULL DEFAULT 0,
+    expired BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    PRIMARY KEY (tenant_id, flow)
+);
+
+ALTER TABLE token_funnel ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_policy ON token_funnel
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
@@ -0,0 +1,2 @@
+-- Index for querying audit events of a tenant by action, e.g. denied authorization decisions
+CREATE INDEX IF NOT EXISTS idx_audit_log_tenant_action ON audit_log (tenant_id, action, created_at DESC);
@@ -0,0 +1,13 @@
+-- URL-safe vanity slugs of tenants, e.g. for login URLs like /t/acme
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS slug VARCHAR(63) UNIQUE;
+
+-- Previous slugs of renamed tenants, kept so old vanity URLs keep redirecting; slugs
+-- are resolved before the tenant is known, so the table is not tenant isolated
+CREATE TABLE IF NOT EXISTS tenant_slug_history (
+    slug VARCHAR(63) PRIMARY KEY NOT NULL,
+    tenant_id UUID NOT NULL,
+    replaced_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_tenant_slug_history_tenant ON tenant_slug_history(tenant_id);
@@ -0,0 +1,7 @@
+-- Per-provider validation controls for OIDC ID tokens
+ALTER TABLE sso_providers
+    ADD COLUMN IF NOT EXISTS allowed_algorithms TEXT[] DEFAULT '{}' NOT NULL,
+    ADD COLUMN IF NOT EXISTS issuer_exact_match BOOLEAN DEFAULT TRUE NOT NULL,
+    ADD COLUMN IF NOT EXISTS require_email_verified BOOLEAN DEFAULT TRUE NOT NULL,
+    ADD COLUMN IF NOT EXISTS allowed_hosted_domains TEXT[] DEFAULT '{}' NOT NULL,
+    ADD COLUMN IF NOT EXISTS allowed_tenant_ids TEXT[] DEFAULT '{}' NOT NULL;
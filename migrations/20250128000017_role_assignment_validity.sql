@@ -0,0 +1,8 @@
+-- Time-bound role assignments, e.g. temporary elevated access; assignments take
+-- effect at valid_from and lapse at valid_until, permanent if unset
+ALTER TABLE user_roles
+    ADD COLUMN IF NOT EXISTS valid_from TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    ADD COLUMN IF NOT EXISTS valid_until TIMESTAMP WITH TIME ZONE,
+    ADD CONSTRAINT user_roles_validity_check CHECK (valid_until IS NULL OR valid_until > valid_from);
+
+CREATE INDEX idx_user_roles_valid_until ON user_roles(valid_until) WHERE valid_until IS NOT NULL;
@@ -0,0 +1,15 @@
+-- Storage of the Postgres session store, for deployments without Redis.
+-- Sessions are looked up through the existing unique index on the token; the
+-- full session is kept as JSON next to the indexed columns.
+DELETE FROM sessions;
+
+ALTER TABLE sessions
+    ADD COLUMN IF NOT EXISTS tenant_id UUID NOT NULL,
+    ADD COLUMN IF NOT EXISTS data TEXT NOT NULL,
+    ALTER COLUMN token TYPE TEXT,
+    DROP CONSTRAINT IF EXISTS sessions_user_id_fkey,
+    ADD CONSTRAINT sessions_user_id_fkey FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    ADD CONSTRAINT sessions_tenant_id_fkey FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE;
+
+CREATE INDEX idx_sessions_tenant_user ON sessions(tenant_id, user_id);
+CREATE INDEX idx_sessions_expires_at ON sessions(expires_at);
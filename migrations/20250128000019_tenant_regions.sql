@@ -0,0 +1,5 @@
+-- Data residency region of tenants, e.g. eu; tenants without a region are kept
+-- in the home region. The tenant catalog itself stays in the home database
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS region VARCHAR(32);
+
+CREATE INDEX idx_tenants_region ON tenants(region);
@@ -0,0 +1,22 @@
+-- Long-term summaries of sessions for investigations, kept after the session
+-- store forgot them; no user foreign key so the history of deleted users is kept
+-- until it leaves the retention period
+CREATE TABLE IF NOT EXISTS session_archive (
+    id UUID PRIMARY KEY NOT NULL,
+    tenant_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    revoked_at TIMESTAMP WITH TIME ZONE,
+    ip_address TEXT,
+    user_agent TEXT,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_session_archive_tenant_user ON session_archive(tenant_id, user_id, created_at);
+CREATE INDEX idx_session_archive_ended_at ON session_archive((COALESCE(revoked_at, expires_at)));
+
+ALTER TABLE session_archive ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_policy ON session_archive
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
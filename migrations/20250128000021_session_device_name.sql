@@ -0,0 +1,2 @@
+-- Name the user gave the device a session was created on
+ALTER TABLE session_archive ADD COLUMN IF NOT EXISTS device_name TEXT;
@@ -0,0 +1,11 @@
+-- Token version per user of the Postgres session store; tokens issued with an
+-- older version are revoked, so bumping it logs the user out everywhere
+CREATE TABLE IF NOT EXISTS user_token_versions (
+    tenant_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    version BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    PRIMARY KEY (tenant_id, user_id),
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
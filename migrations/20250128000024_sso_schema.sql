@@ -0,0 +1,48 @@
+-- Schema of the SSO module: provider configuration for SAML, OIDC and WS-Federation,
+-- the identities users are linked to at providers, and the sessions of SSO logins
+ALTER TABLE sso_providers RENAME COLUMN active TO enabled;
+ALTER TABLE sso_providers
+    ADD COLUMN IF NOT EXISTS description TEXT,
+    ADD COLUMN IF NOT EXISTS metadata_xml TEXT,
+    ADD COLUMN IF NOT EXISTS entity_id TEXT,
+    ADD COLUMN IF NOT EXISTS assertion_consumer_service_url TEXT,
+    ADD COLUMN IF NOT EXISTS single_logout_url TEXT,
+    ADD COLUMN IF NOT EXISTS discovery_url TEXT,
+    ALTER COLUMN client_id DROP NOT NULL,
+    ALTER COLUMN client_secret DROP NOT NULL,
+    ALTER COLUMN created_at TYPE TIMESTAMP WITH TIME ZONE USING created_at AT TIME ZONE 'UTC',
+    ALTER COLUMN updated_at TYPE TIMESTAMP WITH TIME ZONE USING updated_at AT TIME ZONE 'UTC';
+
+ALTER TABLE sso_mappings RENAME TO sso_user_mappings;
+ALTER TABLE sso_user_mappings
+    ADD COLUMN IF NOT EXISTS email TEXT NOT NULL DEFAULT '',
+    ALTER COLUMN created_at TYPE TIMESTAMP WITH TIME ZONE USING created_at AT TIME ZONE 'UTC',
+    ALTER COLUMN updated_at TYPE TIMESTAMP WITH TIME ZONE USING updated_at AT TIME ZONE 'UTC';
+
+-- An identity at a provider is linked to one user
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sso_user_mappings_external_id
+    ON sso_user_mappings(provider_id, external_id);
+CREATE INDEX IF NOT EXISTS idx_sso_user_mappings_user_id ON sso_user_mappings(user_id);
+
+CREATE TABLE IF NOT EXISTS sso_sessions (
+    id UUID PRIMARY KEY NOT NULL,
+    user_id UUID NOT NULL,
+    tenant_id UUID NOT NULL,
+    provider_id UUID NOT NULL,
+    -- SAML session index and subject, matched by logout requests of the IdP
+    session_index TEXT,
+    name_id TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (provider_id) REFERENCES sso_providers(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_sso_sessions_provider_user ON sso_sessions(provider_id, user_id);
+CREATE INDEX idx_sso_sessions_expires_at ON sso_sessions(expires_at);
+
+ALTER TABLE sso_sessions ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_policy ON sso_sessions
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
@@ -0,0 +1,14 @@
+-- Unsolicited SAML responses are rejected unless a provider allows them
+ALTER TABLE sso_providers
+    ADD COLUMN allow_idp_initiated BOOLEAN DEFAULT FALSE NOT NULL;
+
+-- IDs of accepted SAML assertions, kept until they expire so they cannot be replayed
+CREATE TABLE sso_used_assertions (
+    provider_id UUID NOT NULL,
+    assertion_id TEXT NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    PRIMARY KEY (provider_id, assertion_id),
+    FOREIGN KEY (provider_id) REFERENCES sso_providers(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_sso_used_assertions_expires_at ON sso_used_assertions(expires_at);
@@ -0,0 +1,3 @@
+-- ID token of an OIDC login, sent as hint when the user logs out at the provider
+ALTER TABLE sso_sessions
+    ADD COLUMN id_token TEXT;
@@ -0,0 +1,7 @@
+-- Attributes each provider carries profile fields in
+ALTER TABLE sso_providers
+    ADD COLUMN attribute_mapping JSONB DEFAULT '{}' NOT NULL;
+
+-- Profile of an SSO user as last asserted by the provider
+ALTER TABLE sso_user_mappings
+    ADD COLUMN profile JSONB DEFAULT '{}' NOT NULL;
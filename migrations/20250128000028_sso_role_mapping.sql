@@ -0,0 +1,3 @@
+-- Local roles each provider grants to the members of IdP groups
+ALTER TABLE sso_providers
+    ADD COLUMN IF NOT EXISTS role_mapping JSONB DEFAULT '{}' NOT NULL;
@@ -0,0 +1,3 @@
+-- Redirect URIs each OIDC provider may return logins to, the first being the default
+ALTER TABLE sso_providers
+    ADD COLUMN IF NOT EXISTS redirect_uris TEXT[] DEFAULT '{}' NOT NULL;
@@ -0,0 +1,3 @@
+-- Reference to the client secret of an OIDC provider in an external secret store,
+-- kept instead of the secret itself
+ALTER TABLE sso_providers ADD COLUMN IF NOT EXISTS client_secret_ref TEXT;
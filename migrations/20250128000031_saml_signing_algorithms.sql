@@ -0,0 +1,6 @@
+-- Algorithms the SAML requests of the service provider are signed with, for IdPs
+-- rejecting the defaults
+ALTER TABLE sso_providers
+    ADD COLUMN IF NOT EXISTS saml_signature_algorithm TEXT NOT NULL DEFAULT 'rsa-sha256',
+    ADD COLUMN IF NOT EXISTS saml_digest_algorithm TEXT NOT NULL DEFAULT 'sha256',
+    ADD COLUMN IF NOT EXISTS saml_canonicalization TEXT NOT NULL DEFAULT 'exclusive-c14n';
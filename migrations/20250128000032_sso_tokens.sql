@@ -0,0 +1,28 @@
+-- OIDC providers whose access and refresh tokens are kept for calling the customer's APIs
+ALTER TABLE sso_providers
+    ADD COLUMN IF NOT EXISTS store_tokens BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN IF NOT EXISTS token_scopes TEXT[] DEFAULT '{}' NOT NULL;
+
+-- Encrypted tokens of the last OIDC login of a user at a provider
+CREATE TABLE IF NOT EXISTS sso_tokens (
+    provider_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    tenant_id UUID NOT NULL,
+    access_token TEXT NOT NULL,
+    refresh_token TEXT,
+    expires_at TIMESTAMP WITH TIME ZONE,
+    scopes TEXT[] DEFAULT '{}' NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    PRIMARY KEY (provider_id, user_id),
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (provider_id) REFERENCES sso_providers(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_sso_tokens_expires_at ON sso_tokens(expires_at);
+
+ALTER TABLE sso_tokens ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_policy ON sso_tokens
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
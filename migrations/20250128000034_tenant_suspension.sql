@@ -0,0 +1,3 @@
+-- Suspended tenants are kept with their data, but none of their users can access them
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS suspended_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS suspension_reason TEXT;
@@ -0,0 +1,14 @@
+-- Per-tenant settings, e.g. password policy overrides, session lifetimes and branding
+CREATE TABLE IF NOT EXISTS tenant_settings (
+    tenant_id UUID NOT NULL,
+    key TEXT NOT NULL,
+    value JSONB NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    PRIMARY KEY (tenant_id, key),
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+ALTER TABLE tenant_settings ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_policy ON tenant_settings
+    USING (tenant_id::text = COALESCE(current_setting('app.current_tenant', true), ''));
@@ -0,0 +1,19 @@
+-- Custom domains claimed by tenants; a domain routes to its tenant only once a DNS
+-- TXT challenge proved the tenant controls it
+CREATE TABLE IF NOT EXISTS tenant_domains (
+    tenant_id UUID NOT NULL,
+    domain TEXT NOT NULL,
+    token TEXT NOT NULL,
+    verified_at TIMESTAMP WITH TIME ZONE,
+    last_checked_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    PRIMARY KEY (tenant_id, domain),
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+-- Pending claims do not block other tenants, but a verified domain has a single owner
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tenant_domains_verified
+    ON tenant_domains(domain) WHERE verified_at IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_tenant_domains_pending
+    ON tenant_domains(last_checked_at) WHERE verified_at IS NULL;
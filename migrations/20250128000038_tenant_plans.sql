@@ -0,0 +1,8 @@
+-- Plans deciding which capabilities, e.g. SSO or SCIM, a tenant can use. Existing
+-- tenants are kept on the enterprise plan so they do not lose features; new tenants
+-- start on the free plan.
+ALTER TABLE tenants
+    ADD COLUMN IF NOT EXISTS plan VARCHAR(32) DEFAULT 'enterprise' NOT NULL
+        CHECK (plan IN ('free', 'pro', 'enterprise'));
+
+ALTER TABLE tenants ALTER COLUMN plan SET DEFAULT 'free';
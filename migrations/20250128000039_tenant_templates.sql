@@ -0,0 +1,11 @@
+-- Templates of the roles, settings and feature flags new tenants are created with
+CREATE TABLE IF NOT EXISTS tenant_templates (
+    id UUID PRIMARY KEY NOT NULL,
+    name VARCHAR(255) NOT NULL UNIQUE,
+    plan VARCHAR(32) DEFAULT 'free' NOT NULL CHECK (plan IN ('free', 'pro', 'enterprise')),
+    roles JSONB DEFAULT '[]' NOT NULL,
+    settings JSONB DEFAULT '{}' NOT NULL,
+    features JSONB DEFAULT '{}' NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL
+);
@@ -0,0 +1,37 @@
+-- Global identities of the people behind tenant users; users of different tenants
+-- sharing an email address are memberships of the same identity
+CREATE TABLE IF NOT EXISTS identities (
+    id UUID PRIMARY KEY NOT NULL,
+    email VARCHAR(255) NOT NULL UNIQUE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL
+);
+
+ALTER TABLE users ADD COLUMN identity_id UUID REFERENCES identities(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_users_identity_id ON users(identity_id);
+
+-- Links every user to the identity of their email, creating it on first use
+CREATE OR REPLACE FUNCTION link_user_identity()
+RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO identities (id, email)
+    VALUES (gen_random_uuid(), LOWER(NEW.email))
+    ON CONFLICT (email) DO NOTHING;
+    SELECT id INTO NEW.identity_id FROM identities WHERE email = LOWER(NEW.email);
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER link_users_identity
+    BEFORE INSERT OR UPDATE OF email ON users
+    FOR EACH ROW
+    EXECUTE FUNCTION link_user_identity();
+
+-- Link the existing users
+INSERT INTO identities (id, email)
+SELECT gen_random_uuid(), LOWER(email) FROM users GROUP BY LOWER(email)
+ON CONFLICT (email) DO NOTHING;
+
+UPDATE users SET identity_id = identities.id
+FROM identities
+WHERE identities.email = LOWER(users.email);
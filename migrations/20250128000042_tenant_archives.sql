@@ -0,0 +1,18 @@
+-- Manifests of tenants archived to cold storage; the tenant's rows are removed
+-- from the hot tables, so manifests do not reference them
+CREATE TABLE IF NOT EXISTS tenant_archives (
+    id UUID PRIMARY KEY NOT NULL,
+    tenant_id UUID NOT NULL,
+    tenant_name VARCHAR(255) NOT NULL,
+    tenant_domain VARCHAR(255) NOT NULL,
+    object_key TEXT NOT NULL,
+    checksum VARCHAR(64) NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    row_counts JSONB DEFAULT '{}'::jsonb NOT NULL,
+    archived_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    restored_at TIMESTAMP WITH TIME ZONE
+);
+
+-- A tenant has at most one archive it can be restored from
+CREATE UNIQUE INDEX idx_tenant_archives_unrestored
+    ON tenant_archives(tenant_id) WHERE restored_at IS NULL;
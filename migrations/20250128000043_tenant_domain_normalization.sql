@@ -0,0 +1,8 @@
+-- Tenant domains are stored normalized: lowercase, without surrounding whitespace
+-- or a trailing dot
+UPDATE tenants
+SET domain = lower(rtrim(btrim(domain), '.'))
+WHERE domain <> lower(rtrim(btrim(domain), '.'));
+
+-- Domains differing only in case or a trailing dot belong to the same tenant
+CREATE UNIQUE INDEX idx_tenants_domain_normalized ON tenants(lower(rtrim(domain, '.')));
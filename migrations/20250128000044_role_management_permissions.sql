@@ -0,0 +1,14 @@
+-- Managing custom roles requires permissions on the roles resource; tenant
+-- admins get them like the built-in Admin role of new tenants
+INSERT INTO permissions (id, role_id, name, action, resource)
+SELECT gen_random_uuid(), r.id, p.name, p.action, 'roles'
+FROM roles r
+CROSS JOIN (
+    VALUES
+        ('Create Role', 'create'),
+        ('Read Role', 'read'),
+        ('Update Role', 'update'),
+        ('Delete Role', 'delete')
+) AS p(name, action)
+WHERE r.role_type = 'admin'
+ON CONFLICT (role_id, action, resource) DO NOTHING;
@@ -0,0 +1,3 @@
+-- Daily request quotas of API keys, overall and per endpoint; an empty quota
+-- leaves a key unlimited
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS quota JSONB NOT NULL DEFAULT '{}';
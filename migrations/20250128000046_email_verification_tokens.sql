@@ -0,0 +1,11 @@
+-- Pending verification of an added email address; only the hash of the token
+-- mailed to the address is stored
+ALTER TABLE user_emails ADD COLUMN IF NOT EXISTS verification_token_hash VARCHAR(64);
+ALTER TABLE user_emails ADD COLUMN IF NOT EXISTS verification_expires_at TIMESTAMP WITH TIME ZONE;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_user_emails_verification_token
+    ON user_emails(verification_token_hash) WHERE verification_token_hash IS NOT NULL;
+
+-- Login addresses of users created since the backfill are verified like the backfilled ones
+UPDATE user_emails SET verified = TRUE, verified_at = created_at
+WHERE is_primary AND NOT verified;
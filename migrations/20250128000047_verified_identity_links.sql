@@ -0,0 +1,55 @@
+-- Users join the identity of their email only once its owner verified the address,
+-- e.g. through a mailed token or the signup verification; addresses set by tenant
+-- admins or directories must not grant access to other tenants' memberships
+CREATE OR REPLACE FUNCTION identity_of_email(address TEXT)
+RETURNS UUID AS $$
+DECLARE
+    identity UUID;
+BEGIN
+    INSERT INTO identities (id, email)
+    VALUES (gen_random_uuid(), LOWER(address))
+    ON CONFLICT (email) DO NOTHING;
+    SELECT id INTO identity FROM identities WHERE email = LOWER(address);
+    RETURN identity;
+END;
+$$ language 'plpgsql';
+
+-- Links a user to the identity of their login address if it is verified, unlinks them otherwise
+CREATE OR REPLACE FUNCTION link_user_identity()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM user_emails
+        WHERE user_id = NEW.id AND LOWER(email) = LOWER(NEW.email) AND verified
+    ) THEN
+        NEW.identity_id := identity_of_email(NEW.email);
+    ELSE
+        NEW.identity_id := NULL;
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+-- Links the user of a login address once the address is verified
+CREATE OR REPLACE FUNCTION link_verified_email_identity()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.verified THEN
+        UPDATE users SET identity_id = identity_of_email(NEW.email)
+        WHERE id = NEW.user_id AND LOWER(email) = LOWER(NEW.email);
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER link_verified_email_identity
+    AFTER INSERT OR UPDATE OF verified ON user_emails
+    FOR EACH ROW
+    EXECUTE FUNCTION link_verified_email_identity();
+
+-- Unlink the users whose login address is not verified
+UPDATE users SET identity_id = NULL
+WHERE identity_id IS NOT NULL AND NOT EXISTS (
+    SELECT 1 FROM user_emails e
+    WHERE e.user_id = users.id AND LOWER(e.email) = LOWER(users.email) AND e.verified
+);
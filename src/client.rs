@@ -0,0 +1,597 @@
+//! Typed HTTP client for the REST API, enabled with the `client` feature
+//!
+//! Requests and responses use the same models as the handlers, so services
+//! integrating with the framework stay in sync with the API.
+
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{
+    modules::{
+        identity::{
+            catalog::CatalogResource,
+            models::{
+                AccessTokenRequest, AccessTokenResponse, ActivityPage, ActivityQuery,
+                AddEmailRequest, ApiKeyRequest, ApiKeyResponse, CreatedApiKeyResponse,
+                EmailVerificationRequest, MembershipDiscoveryRequest, MembershipResponse,
+                PolicySimulation, PolicySimulationRequest, ResourceOwner, Role,
+                RoleAssignmentRequest, RoleRequest, SessionRefreshRequest, SessionRefreshResponse,
+                TenantSwitchRequest, TenantSwitchResponse, UserEmail, UserOverviewPage,
+                UserOverviewQuery, UserResponse, UserSessionResponse,
+            },
+        },
+        tenant::models::{
+            DomainCheckQuery, DomainCheckResponse, Tenant, TenantListQuery, TenantPage,
+            TenantRequest, TenantResponse,
+        },
+    },
+    shared::{
+        error::{Error, Result, ThrottleInfo},
+        types::{TenantId, UserId},
+    },
+};
+
+/// Default timeout of API requests
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Client of the REST API of a deployment
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    base_url: String,
+    token: Option<String>,
+    client: reqwest::Client,
+}
+
+impl ApiClient {
+    /// Creates a client for the API at `base_url`, e.g. `https://iam.example.com/api`
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: None,
+            client: reqwest::Client::builder()
+                .timeout(DEFAULT_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Sets the bearer token sent with every request, e.g. a personal access token
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Sets the underlying HTTP client, e.g. to configure proxies or timeouts
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Checks if the deployment is healthy
+    pub async fn health(&self) -> Result<()> {
+        self.send(self.request(Method::GET, "/health")).await?;
+        Ok(())
+    }
+
+    /// Creates a tenant
+    pub async fn create_tenant(&self, request: &TenantRequest) -> Result<TenantResponse> {
+        self.json(self.request(Method::POST, "/tenants").json(request))
+            .await
+    }
+
+    /// Checks if a domain is valid and available before creating a tenant with it
+    pub async fn check_tenant_domain(&self, domain: &str) -> Result<DomainCheckResponse> {
+        let query = DomainCheckQuery {
+            domain: domain.to_string(),
+        };
+        self.json(
+            self.request(Method::GET, "/tenants/check-domain")
+                .query(&query),
+        )
+        .await
+    }
+
+    /// Gets a tenant by ID
+    pub async fn get_tenant(&self, tenant_id: TenantId) -> Result<Tenant> {
+        self.json(self.request(Method::GET, &format!("/tenants/{}", tenant_id.0)))
+            .await
+    }
+
+    /// Updates a tenant
+    pub async fn update_tenant(
+        &self,
+        tenant_id: TenantId,
+        request: &TenantRequest,
+    ) -> Result<TenantResponse> {
+        self.json(
+            self.request(Method::PUT, &format!("/tenants/{}", tenant_id.0))
+                .json(request),
+        )
+        .await
+    }
+
+    /// Lists a page of tenants, optionally filtered and sorted
+    pub async fn list_tenants(&self, query: &TenantListQuery) -> Result<TenantPage> {
+        self.json(self.request(Method::GET, "/tenants").query(query))
+            .await
+    }
+
+    /// Resolves a tenant by its vanity slug, following redirects of previous slugs
+    pub async fn resolve_tenant_slug(&self, slug: &str) -> Result<TenantResponse> {
+        self.json(self.request(Method::GET, &format!("/t/{}", slug)))
+            .await
+    }
+
+    /// Lists the users of a tenant with login, session and MFA details
+    pub async fn list_users(
+        &self,
+        tenant_id: TenantId,
+        query: &UserOverviewQuery,
+    ) -> Result<UserOverviewPage> {
+        self.json(
+            self.request(Method::GET, &format!("/tenants/{}/users", tenant_id.0))
+                .query(query),
+        )
+        .await
+    }
+
+    /// Lists a page of a user's security relevant activity, newest first
+    pub async fn list_user_activity(
+        &self,
+        tenant_id: TenantId,
+        user_id: UserId,
+        query: &ActivityQuery,
+    ) -> Result<ActivityPage> {
+        self.json(
+            self.request(
+                Method::GET,
+                &format!("/tenants/{}/users/{}/activity", tenant_id.0, user_id.0),
+            )
+            .query(query),
+        )
+        .await
+    }
+
+    /// Deactivates a user and revokes their sessions
+    pub async fn deactivate_user(
+        &self,
+        tenant_id: TenantId,
+        user_id: UserId,
+    ) -> Result<UserResponse> {
+        self.json(self.request(
+            Method::POST,
+            &format!("/tenants/{}/users/{}/deactivate", tenant_id.0, user_id.0),
+        ))
+        .await
+    }
+
+    /// Reactivates a user
+    pub async fn activate_user(
+        &self,
+        tenant_id: TenantId,
+        user_id: UserId,
+    ) -> Result<UserResponse> {
+        self.json(self.request(
+            Method::POST,
+            &format!("/tenants/{}/users/{}/activate", tenant_id.0, user_id.0),
+        ))
+        .await
+    }
+
+    /// Revokes the sessions of all users of a tenant
+    pub async fn revoke_tenant_sessions(&self, tenant_id: TenantId) -> Result<()> {
+        self.send(self.request(
+            Method::DELETE,
+            &format!("/tenants/{}/sessions", tenant_id.0),
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Lists the API keys of a tenant
+    pub async fn list_api_keys(&self, tenant_id: TenantId) -> Result<Vec<ApiKeyResponse>> {
+        self.json(self.request(Method::GET, &format!("/tenants/{}/api-keys", tenant_id.0)))
+            .await
+    }
+
+    /// Mints an API key of a tenant
+    pub async fn create_api_key(
+        &self,
+        tenant_id: TenantId,
+        request: &ApiKeyRequest,
+    ) -> Result<CreatedApiKeyResponse> {
+        self.json(
+            self.request(Method::POST, &format!("/tenants/{}/api-keys", tenant_id.0))
+                .json(request),
+        )
+        .await
+    }
+
+    /// Revokes an API key of a tenant
+    pub async fn revoke_api_key(&self, tenant_id: TenantId, key_id: Uuid) -> Result<()> {
+        self.send(self.request(
+            Method::DELETE,
+            &format!("/tenants/{}/api-keys/{}", tenant_id.0, key_id),
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Assigns a role to a user
+    pub async fn assign_role(
+        &self,
+        tenant_id: TenantId,
+        user_id: UserId,
+        role_id: Uuid,
+    ) -> Result<UserResponse> {
+        self.json(self.request(
+            Method::POST,
+            &format!(
+                "/tenants/{}/users/{}/roles/{}",
+                tenant_id.0, user_id.0, role_id
+            ),
+        ))
+        .await
+    }
+
+    /// Assigns a role to a user for a validity window, e.g. temporary elevated access
+    pub async fn schedule_role(
+        &self,
+        tenant_id: TenantId,
+        user_id: UserId,
+        role_id: Uuid,
+        request: &RoleAssignmentRequest,
+    ) -> Result<UserResponse> {
+        self.json(
+            self.request(
+                Method::POST,
+                &format!(
+                    "/tenants/{}/users/{}/roles/{}",
+                    tenant_id.0, user_id.0, role_id
+                ),
+            )
+            .json(request),
+        )
+        .await
+    }
+
+    /// Removes a role from a user
+    pub async fn revoke_role(
+        &self,
+        tenant_id: TenantId,
+        user_id: UserId,
+        role_id: Uuid,
+    ) -> Result<UserResponse> {
+        self.json(self.request(
+            Method::DELETE,
+            &format!(
+                "/tenants/{}/users/{}/roles/{}",
+                tenant_id.0, user_id.0, role_id
+            ),
+        ))
+        .await
+    }
+
+    /// Lists the resources and actions permissions of custom roles may refer to
+    pub async fn list_permission_catalog(&self) -> Result<Vec<CatalogResource>> {
+        self.json(self.request(Method::GET, "/permissions")).await
+    }
+
+    /// Lists the roles of a tenant
+    pub async fn list_roles(&self, tenant_id: TenantId) -> Result<Vec<Role>> {
+        self.json(self.request(Method::GET, &format!("/tenants/{}/roles", tenant_id.0)))
+            .await
+    }
+
+    /// Gets a role of a tenant
+    pub async fn get_role(&self, tenant_id: TenantId, role_id: Uuid) -> Result<Role> {
+        self.json(self.request(
+            Method::GET,
+            &format!("/tenants/{}/roles/{}", tenant_id.0, role_id),
+        ))
+        .await
+    }
+
+    /// Creates a custom role
+    pub async fn create_role(&self, tenant_id: TenantId, request: &RoleRequest) -> Result<Role> {
+        self.json(
+            self.request(Method::POST, &format!("/tenants/{}/roles", tenant_id.0))
+                .json(request),
+        )
+        .await
+    }
+
+    /// Updates a custom role
+    pub async fn update_role(
+        &self,
+        tenant_id: TenantId,
+        role_id: Uuid,
+        request: &RoleRequest,
+    ) -> Result<Role> {
+        self.json(
+            self.request(
+                Method::PUT,
+                &format!("/tenants/{}/roles/{}", tenant_id.0, role_id),
+            )
+            .json(request),
+        )
+        .await
+    }
+
+    /// Deletes a custom role
+    pub async fn delete_role(&self, tenant_id: TenantId, role_id: Uuid) -> Result<()> {
+        self.send(self.request(
+            Method::DELETE,
+            &format!("/tenants/{}/roles/{}", tenant_id.0, role_id),
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Simulates a permission check for a user of a tenant, explaining its outcome
+    pub async fn simulate_policy(
+        &self,
+        tenant_id: TenantId,
+        request: &PolicySimulationRequest,
+    ) -> Result<PolicySimulation> {
+        self.json(
+            self.request(
+                Method::POST,
+                &format!("/tenants/{}/policy/simulate", tenant_id.0),
+            )
+            .json(request),
+        )
+        .await
+    }
+
+    /// Lists the owners of a resource
+    pub async fn list_resource_owners(
+        &self,
+        tenant_id: TenantId,
+        resource_type: &str,
+        resource_id: &str,
+    ) -> Result<Vec<ResourceOwner>> {
+        self.json(self.request(
+            Method::GET,
+            &format!(
+                "/tenants/{}/resources/{}/{}/owners",
+                tenant_id.0, resource_type, resource_id
+            ),
+        ))
+        .await
+    }
+
+    /// Registers an owner of a resource, returning all owners of the resource
+    pub async fn add_resource_owner(&self, owner: &ResourceOwner) -> Result<Vec<ResourceOwner>> {
+        self.json(self.request(Method::POST, &owner_path(owner)))
+            .await
+    }
+
+    /// Removes an owner of a resource
+    pub async fn remove_resource_owner(&self, owner: &ResourceOwner) -> Result<()> {
+        self.send(self.request(Method::DELETE, &owner_path(owner)))
+            .await?;
+        Ok(())
+    }
+
+    /// Issues a personal access token of the authenticated user
+    pub async fn create_access_token(
+        &self,
+        request: &AccessTokenRequest,
+    ) -> Result<AccessTokenResponse> {
+        self.json(self.request(Method::POST, "/tokens").json(request))
+            .await
+    }
+
+    /// Lists the sessions of the authenticated user
+    pub async fn list_sessions(&self) -> Result<Vec<UserSessionResponse>> {
+        self.json(self.request(Method::GET, "/sessions")).await
+    }
+
+    /// Revokes one of the authenticated user's sessions
+    pub async fn revoke_session(&self, session_id: Uuid) -> Result<()> {
+        self.send(self.request(Method::DELETE, &format!("/sessions/{}", session_id)))
+            .await?;
+        Ok(())
+    }
+
+    /// Signs the authenticated user out of all sessions but the client's one
+    pub async fn revoke_other_sessions(&self) -> Result<()> {
+        self.send(self.request(Method::DELETE, "/sessions")).await?;
+        Ok(())
+    }
+
+    /// Lists the email addresses of the authenticated user
+    pub async fn list_emails(&self) -> Result<Vec<UserEmail>> {
+        self.json(self.request(Method::GET, "/emails")).await
+    }
+
+    /// Adds an email address to the authenticated user, which is mailed a verification link
+    pub async fn add_email(&self, email: &str) -> Result<UserEmail> {
+        let request = AddEmailRequest {
+            email: email.to_string(),
+        };
+        self.json(self.request(Method::POST, "/emails").json(&request))
+            .await
+    }
+
+    /// Verifies an email address of the authenticated user with the token mailed to it
+    pub async fn verify_email(&self, token: &str) -> Result<UserEmail> {
+        let request = EmailVerificationRequest {
+            token: token.to_string(),
+        };
+        self.json(self.request(Method::POST, "/emails/verify").json(&request))
+            .await
+    }
+
+    /// Mails a new verification link to an unverified email address of the authenticated user
+    pub async fn resend_email_verification(&self, email_id: Uuid) -> Result<()> {
+        self.send(self.request(Method::POST, &format!("/emails/{}/verification", email_id)))
+            .await?;
+        Ok(())
+    }
+
+    /// Makes a verified email address the authenticated user's primary address
+    pub async fn set_primary_email(&self, email_id: Uuid) -> Result<()> {
+        self.send(self.request(Method::POST, &format!("/emails/{}/primary", email_id)))
+            .await?;
+        Ok(())
+    }
+
+    /// Removes a non-primary email address from the authenticated user
+    pub async fn remove_email(&self, email_id: Uuid) -> Result<()> {
+        self.send(self.request(Method::DELETE, &format!("/emails/{}", email_id)))
+            .await?;
+        Ok(())
+    }
+
+    /// Lists the tenants an email address and password can log in to
+    pub async fn discover_memberships(
+        &self,
+        email: &str,
+        password: &str,
+    ) -> Result<Vec<MembershipResponse>> {
+        let request = MembershipDiscoveryRequest {
+            email: email.to_string(),
+            password: password.to_string(),
+        };
+        self.json(self.request(Method::POST, "/login/discover").json(&request))
+            .await
+    }
+
+    /// Lists the tenants the authenticated user can switch to
+    pub async fn list_memberships(&self) -> Result<Vec<MembershipResponse>> {
+        self.json(self.request(Method::GET, "/memberships")).await
+    }
+
+    /// Opens a session in another tenant of the authenticated user
+    ///
+    /// The client keeps its token; use the returned one with [`ApiClient::with_token`].
+    pub async fn switch_tenant(
+        &self,
+        request: &TenantSwitchRequest,
+    ) -> Result<TenantSwitchResponse> {
+        self.json(self.request(Method::POST, "/sessions/switch").json(request))
+            .await
+    }
+
+    /// Renews a session with its refresh token, which is used up
+    ///
+    /// Keep the returned refresh token for the next renewal; the client keeps its token.
+    pub async fn refresh_session(&self, refresh_token: &str) -> Result<SessionRefreshResponse> {
+        let request = SessionRefreshRequest {
+            refresh_token: refresh_token.to_string(),
+        };
+        self.json(
+            self.request(Method::POST, "/sessions/refresh")
+                .json(&request),
+        )
+        .await
+    }
+
+    /// Starts a request to a path of the API
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self
+            .client
+            .request(method, format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Sends a request, turning error responses into errors
+    async fn send(&self, request: RequestBuilder) -> Result<reqwest::Response> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::Unavailable(format!("Failed to reach API: {}", e)))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let message = response.text().await.unwrap_or_default();
+        // Throttled logins carry the retry details as JSON
+        if let Ok(info) = serde_json::from_str::<ThrottleInfo>(&message) {
+            match status {
+                StatusCode::TOO_MANY_REQUESTS => return Err(Error::Throttled(info)),
+                StatusCode::LOCKED => return Err(Error::LockedOut(info)),
+                _ => {},
+            }
+        }
+        Err(error_from_status(status, message))
+    }
+
+    /// Sends a request and parses the JSON response
+    async fn json<R: DeserializeOwned>(&self, request: RequestBuilder) -> Result<R> {
+        self.send(request)
+            .await?
+            .json()
+            .await
+            .map_err(|e| Error::Internal(format!("Invalid API response: {}", e)))
+    }
+}
+
+/// Builds the path of a resource owner entry
+fn owner_path(owner: &ResourceOwner) -> String {
+    format!(
+        "/tenants/{}/resources/{}/{}/owners/{}/{}",
+        owner.tenant_id.0, owner.resource_type, owner.resource_id, owner.owner_type, owner.owner_id
+    )
+}
+
+/// Maps an error response back to the error the server responded with
+fn error_from_status(status: StatusCode, message: String) -> Error {
+    match status {
+        StatusCode::UNAUTHORIZED => Error::Authentication(message),
+        StatusCode::FORBIDDEN => Error::Authorization(message),
+        StatusCode::NOT_FOUND => Error::NotFound(message),
+        StatusCode::BAD_REQUEST => Error::InvalidInput(message),
+        StatusCode::CONFLICT => Error::Conflict(message),
+        StatusCode::PAYMENT_REQUIRED => Error::UpgradeRequired(message),
+        StatusCode::TOO_MANY_REQUESTS => Error::RateLimited(message),
+        StatusCode::SERVICE_UNAVAILABLE => Error::Unavailable(message),
+        _ => Error::Internal(format!("API responded with status {}: {}", status, message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::identity::models::OwnerType;
+
+    #[test]
+    fn test_api_client_requests() {
+        let client = ApiClient::new("https://iam.example.com/api/").with_token("token");
+        let request = client.request(Method::GET, "/tenants").build().unwrap();
+        assert_eq!(request.url().as_str(), "https://iam.example.com/api/tenants");
+        assert_eq!(request.headers()["authorization"], "Bearer token");
+
+        let owner = ResourceOwner::new(
+            TenantId::new(),
+            "projects".to_string(),
+            "42".to_string(),
+            OwnerType::Group,
+            Uuid::nil(),
+        );
+        assert_eq!(
+            owner_path(&owner),
+            format!(
+                "/tenants/{}/resources/projects/42/owners/group/{}",
+                owner.tenant_id.0,
+                Uuid::nil()
+            )
+        );
+
+        assert!(matches!(
+            error_from_status(StatusCode::FORBIDDEN, "denied".to_string()),
+            Error::Authorization(_)
+        ));
+        assert!(matches!(
+            error_from_status(StatusCode::BAD_GATEWAY, String::new()),
+            Error::Internal(_)
+        ));
+    }
+}
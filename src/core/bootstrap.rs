@@ -0,0 +1,397 @@
+use argon2::password_hash::PasswordHash;
+use serde::Deserialize;
+use sqlx::PgConnection;
+use std::{collections::HashMap, path::Path};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    core::database::Database,
+    modules::{
+        identity::{
+            auth::AuthenticationService,
+            catalog::PermissionCatalog,
+            models::{PermissionRequest, Role, RoleRequest, User},
+            rbac::{create_admin_role, create_super_admin_role, create_user_role},
+            repository::{RoleRepository, UserRepository},
+        },
+        tenant::{models::Tenant, repository::TenantRepository, service::TenantService},
+    },
+    shared::{
+        error::{Error, Result},
+        types::TenantId,
+    },
+};
+
+/// Initial tenants with their roles, users and SSO providers, e.g. from an
+/// infrastructure-as-code pipeline
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BootstrapSpec {
+    #[serde(default)]
+    pub tenants: Vec<TenantSpec>,
+}
+
+/// Tenant to bootstrap, identified by its domain
+#[derive(Debug, Clone, Deserialize)]
+pub struct TenantSpec {
+    pub name: String,
+    pub domain: String,
+    pub slug: Option<String>,
+    pub region: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<RoleSpec>,
+    #[serde(default)]
+    pub users: Vec<UserSpec>,
+    #[serde(default)]
+    pub sso_providers: Vec<SsoProviderSpec>,
+}
+
+/// Custom role of a tenant, identified by its name
+#[derive(Debug, Clone, Deserialize)]
+pub struct RoleSpec {
+    pub name: String,
+    #[serde(default)]
+    pub permissions: Vec<PermissionRequest>,
+}
+
+/// User of a tenant, identified by its email
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserSpec {
+    pub email: String,
+    /// Argon2 hash of the password; preferred over a plain `password`
+    pub password_hash: Option<String>,
+    pub password: Option<String>,
+    /// Names of built-in roles (`Admin`, `User`, `Super Admin`) or of the tenant's roles
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+/// SSO provider of a tenant, identified by its name
+#[derive(Debug, Clone, Deserialize)]
+pub struct SsoProviderSpec {
+    pub name: String,
+    /// `saml` or `oidc`
+    pub provider_type: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub metadata_url: Option<String>,
+    pub issuer: Option<String>,
+}
+
+/// Counts of what applying a bootstrap spec created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BootstrapReport {
+    pub tenants: usize,
+    pub roles: usize,
+    pub users: usize,
+    pub role_assignments: usize,
+    pub sso_providers: usize,
+}
+
+impl BootstrapSpec {
+    /// Parses a spec in TOML or, for `.json` files, JSON
+    pub fn parse(content: &str, json: bool) -> Result<Self> {
+        if json {
+            serde_json::from_str(content)
+                .map_err(|e| Error::InvalidInput(format!("Invalid bootstrap file: {}", e)))
+        } else {
+            toml::from_str(content)
+                .map_err(|e| Error::InvalidInput(format!("Invalid bootstrap file: {}", e)))
+        }
+    }
+
+    /// Reads a spec from a file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            Error::InvalidInput(format!(
+                "Failed to read bootstrap file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let json = path
+            .extension()
+            .is_some_and(|extension| extension == "json");
+        Self::parse(&content, json)
+    }
+}
+
+/// Applies a bootstrap spec, creating what does not exist yet
+///
+/// Applying a spec is idempotent: existing tenants, roles, users and SSO
+/// providers are left as they are, and users only get missing role assignments.
+/// Tenant regions must be among the configured `regions`.
+pub async fn apply(
+    spec: &BootstrapSpec,
+    db: &Database,
+    regions: Vec<String>,
+) -> Result<BootstrapReport> {
+    let tenants = TenantService::new(TenantRepository::new(db.get_pool())).with_regions(regions);
+    let users = UserRepository::new(db.get_pool());
+    let roles = RoleRepository::new(db.get_pool());
+    let mut report = BootstrapReport::default();
+
+    for tenant_spec in &spec.tenants {
+        let tenant = match tenants.get_tenant_by_domain(&tenant_spec.domain).await? {
+            Some(tenant) => tenant,
+            None => {
+                let mut tenant = Tenant::new(tenant_spec.name.clone(), tenant_spec.domain.clone());
+                tenant.slug = tenant_spec.slug.clone();
+                tenant.region = tenant_spec.region.clone();
+                report.tenants += 1;
+                tenants.create_tenant(tenant).await?
+            },
+        };
+
+        let mut tenant_roles: HashMap<String, Role> = roles
+            .list_roles(tenant.id)
+            .await?
+            .into_iter()
+            .map(|role| (role.name.clone(), role))
+            .collect();
+        for role_spec in &tenant_spec.roles {
+            if tenant_roles.contains_key(&role_spec.name) {
+                continue;
+            }
+            let role = Role::from(RoleRequest {
+                name: role_spec.name.clone(),
+                permissions: role_spec.permissions.clone(),
+                parent_ids: Vec::new(),
+            });
+            PermissionCatalog::builtin().validate(&role.permissions)?;
+            let role = roles.create_role(tenant.id, &role).await?;
+            report.roles += 1;
+            tenant_roles.insert(role.name.clone(), role);
+        }
+
+        for user_spec in &tenant_spec.users {
+            let user_roles = user_spec
+                .roles
+                .iter()
+                .map(|name| resolve_role(&tenant_roles, name))
+                .collect::<Result<Vec<Role>>>()?;
+            match users.get_user_by_email(&user_spec.email, tenant.id).await? {
+                Some(user) => {
+                    for role in user_roles {
+                        if user.roles.iter().any(|held| held.name == role.name) {
+                            continue;
+                        }
+                        let role_id = match tenant_roles.get(&role.name) {
+                            Some(existing) => existing.id,
+                            None => roles.create_role(tenant.id, &role).await?.id,
+                        };
+                        roles
+                            .assign_role(
+                                user.id,
+                                role_id,
+                                tenant.id,
+                                time::OffsetDateTime::now_utc(),
+                                None,
+                            )
+                            .await?;
+                        report.role_assignments += 1;
+                    }
+                },
+                None => {
+                    let mut user = User::new(
+                        tenant.id,
+                        user_spec.email.clone(),
+                        password_hash(user_spec)?,
+                    );
+                    user.roles = user_roles;
+                    users.create_user(user).await?;
+                    report.users += 1;
+                },
+            }
+        }
+
+        let mut conn = db.get_pool().acquire().await?;
+        for provider in &tenant_spec.sso_providers {
+            if create_sso_provider(&mut conn, tenant.id, provider)
+                .await?
+                .is_some()
+            {
+                report.sso_providers += 1;
+            }
+        }
+    }
+
+    info!(
+        "Applied bootstrap: {} tenants, {} roles, {} users, {} role assignments and {} SSO providers created",
+        report.tenants, report.roles, report.users, report.role_assignments, report.sso_providers
+    );
+    Ok(report)
+}
+
+/// Resolves a role name to a role of the tenant or a built-in role
+fn resolve_role(tenant_roles: &HashMap<String, Role>, name: &str) -> Result<Role> {
+    if let Some(role) = tenant_roles.get(name) {
+        return Ok(role.clone());
+    }
+    [
+        create_admin_role(),
+        create_user_role(),
+        create_super_admin_role(),
+    ]
+    .into_iter()
+    .find(|role| role.name == name)
+    .ok_or_else(|| Error::Validation(format!("Unknown role {}", name)))
+}
+
+/// Gets the password hash of a user spec, hashing a plain password
+fn password_hash(user: &UserSpec) -> Result<String> {
+    match (&user.password_hash, &user.password) {
+        (Some(hash), _) => {
+            PasswordHash::new(hash).map_err(|e| {
+                Error::Validation(format!("Invalid password hash of {}: {}", user.email, e))
+            })?;
+            Ok(hash.clone())
+        },
+        (None, Some(password)) => AuthenticationService::hash_password(password),
+        (None, None) => Err(Error::Validation(format!(
+            "User {} needs a password or password hash",
+            user.email
+        ))),
+    }
+}
+
+/// Creates an SSO provider unless the tenant has one with the same name, returning its ID
+///
+/// Runs on a connection so tenants can be provisioned with a provider in one transaction.
+pub(crate) async fn create_sso_provider(
+    conn: &mut PgConnection,
+    tenant_id: TenantId,
+    provider: &SsoProviderSpec,
+) -> Result<Option<Uuid>> {
+    if !matches!(provider.provider_type.as_str(), "saml" | "oidc") {
+        return Err(Error::Validation(format!(
+            "Unknown SSO provider type {}",
+            provider.provider_type
+        )));
+    }
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO sso_providers (
+            id, tenant_id, name, provider_type, client_id, client_secret, metadata_url, issuer
+        )
+        SELECT $1, $2, $3, $4, $5, $6, $7, $8
+        WHERE NOT EXISTS (
+            SELECT 1 FROM sso_providers WHERE tenant_id = $2 AND name = $3
+        )
+        RETURNING id
+        "#,
+        Uuid::new_v4(),
+        tenant_id.0 as Uuid,
+        provider.name,
+        provider.provider_type,
+        provider.client_id,
+        provider.client_secret,
+        provider.metadata_url,
+        provider.issuer,
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::database::tests::create_test_db;
+
+    const SPEC: &str = r#"
+        [[tenants]]
+        name = "Acme"
+        domain = "acme.example.com"
+        slug = "acme"
+
+        [[tenants.roles]]
+        name = "Support"
+        permissions = [
+            { name = "Read Users", action = "Read", resource = "users" },
+        ]
+
+        [[tenants.users]]
+        email = "admin@acme.example.com"
+        password = "correct horse battery staple"
+        roles = ["Admin", "Support"]
+
+        [[tenants.sso_providers]]
+        name = "Okta"
+        provider_type = "oidc"
+        client_id = "acme"
+        client_secret = "secret"
+        issuer = "https://acme.okta.com"
+    "#;
+
+    #[test]
+    fn test_parse_spec() {
+        let spec = BootstrapSpec::parse(SPEC, false).unwrap();
+        assert_eq!(spec.tenants.len(), 1);
+        let tenant = &spec.tenants[0];
+        assert_eq!(tenant.slug.as_deref(), Some("acme"));
+        assert_eq!(tenant.roles[0].permissions.len(), 1);
+        assert_eq!(tenant.users[0].roles, vec!["Admin", "Support"]);
+        assert_eq!(tenant.sso_providers[0].provider_type, "oidc");
+
+        let json = BootstrapSpec::parse(r#"{"tenants": []}"#, true).unwrap();
+        assert!(json.tenants.is_empty());
+        assert!(BootstrapSpec::parse("tenants = 1", false).is_err());
+    }
+
+    #[test]
+    fn test_password_hash() {
+        let mut user = UserSpec {
+            email: "admin@acme.example.com".to_string(),
+            password_hash: None,
+            password: None,
+            roles: Vec::new(),
+        };
+        assert!(matches!(password_hash(&user), Err(Error::Validation(_))));
+
+        user.password = Some("secret".to_string());
+        let hash = password_hash(&user).unwrap();
+        assert!(hash.starts_with("$argon2"));
+
+        user.password_hash = Some("not a hash".to_string());
+        assert!(matches!(password_hash(&user), Err(Error::Validation(_))));
+        user.password_hash = Some(hash.clone());
+        assert_eq!(password_hash(&user).unwrap(), hash);
+    }
+
+    #[tokio::test]
+    async fn test_apply_is_idempotent() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let spec = BootstrapSpec::parse(SPEC, false).unwrap();
+
+        let report = apply(&spec, &db, Vec::new()).await.unwrap();
+        assert_eq!(
+            report,
+            BootstrapReport {
+                tenants: 1,
+                roles: 1,
+                users: 1,
+                role_assignments: 0,
+                sso_providers: 1,
+            }
+        );
+
+        let tenant = TenantRepository::new(db.get_pool())
+            .get_tenant_by_domain("acme.example.com")
+            .await
+            .unwrap();
+        let admin = UserRepository::new(db.get_pool())
+            .get_user_by_email("admin@acme.example.com", tenant.id)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut role_names: Vec<&str> = admin.roles.iter().map(|r| r.name.as_str()).collect();
+        role_names.sort();
+        assert_eq!(role_names, vec!["Admin", "Support"]);
+
+        // Applying the spec again changes nothing
+        let report = apply(&spec, &db, Vec::new()).await.unwrap();
+        assert_eq!(report, BootstrapReport::default());
+    }
+}
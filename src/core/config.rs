@@ -1,4 +1,4 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// Server configuration
 #[derive(Debug, Clone, Deserialize)]
@@ -6,6 +6,12 @@ pub struct ServerConfig {
     pub host: String,
     pub port: u16,
     pub cors_allowed_origins: Vec<String>,
+    /// Path the module APIs are served below; health probes stay at the root
+    #[serde(default = "default_api_prefix")]
+    pub api_prefix: String,
+    /// Terminates TLS with the configured certificate; plain HTTP is served without it
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
 }
 
 impl ServerConfig {
@@ -15,10 +21,30 @@ impl ServerConfig {
             host: "127.0.0.1".to_string(),
             port: 3000,
             cors_allowed_origins: vec!["http://localhost:3000".to_string()],
+            api_prefix: default_api_prefix(),
+            tls: None,
         }
     }
 }
 
+/// Module APIs are served below `/api` unless configured otherwise
+fn default_api_prefix() -> String {
+    "/api".to_string()
+}
+
+/// Certificate the server terminates TLS with
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsConfig {
+    /// PEM file holding the certificate chain, leaf certificate first
+    pub cert_path: String,
+    /// PEM file holding the private key
+    pub key_path: String,
+    /// Seconds between checks of the files for a rotated certificate, which is then
+    /// loaded without a restart; the certificate is never reloaded if unset
+    #[serde(default)]
+    pub reload_interval_secs: Option<u64>,
+}
+
 /// Database configuration
 #[derive(Debug, Clone, Deserialize)]
 pub struct DatabaseConfig {
@@ -50,6 +76,24 @@ impl DatabaseConfig {
 #[derive(Debug, Clone, Deserialize)]
 pub struct RedisConfig {
     pub url: String,
+    #[serde(default)]
+    pub mode: RedisMode,
+    /// Cluster nodes or Sentinels to connect to; `url` is used if empty
+    #[serde(default)]
+    pub urls: Vec<String>,
+    /// Name of the master monitored by the Sentinels
+    pub master_name: Option<String>,
+    /// Username for Redis ACLs, overriding the one in the URLs
+    pub username: Option<String>,
+    pub password: Option<String>,
+    #[serde(default)]
+    pub tls: RedisTlsConfig,
+    /// Base64 encoded 256-bit key encrypting stored sessions with AES-GCM
+    pub session_encryption_key: Option<String>,
+    /// File holding the session encryption key, e.g. written by a KMS agent
+    pub session_encryption_key_file: Option<String>,
+    #[serde(default)]
+    pub fallback: SessionFallbackConfig,
 }
 
 impl RedisConfig {
@@ -57,6 +101,500 @@ impl RedisConfig {
     pub fn default_dev() -> Self {
         Self {
             url: "redis://localhost:6379".to_string(),
+            mode: RedisMode::Standalone,
+            urls: Vec::new(),
+            master_name: None,
+            username: None,
+            password: None,
+            tls: RedisTlsConfig::default(),
+            session_encryption_key: None,
+            session_encryption_key_file: None,
+            fallback: SessionFallbackConfig::default(),
+        }
+    }
+
+    /// Gets the URLs of the cluster nodes or Sentinels
+    pub fn node_urls(&self) -> Vec<String> {
+        if self.urls.is_empty() {
+            vec![self.url.clone()]
+        } else {
+            self.urls.clone()
+        }
+    }
+}
+
+/// Certificates of `rediss://` connections; the system roots verify the server by default
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RedisTlsConfig {
+    /// PEM file of the CA certificate verifying the server, e.g. of a managed Redis
+    pub ca_cert_file: Option<String>,
+    /// PEM files of the client certificate and key for mutual TLS
+    pub client_cert_file: Option<String>,
+    pub client_key_file: Option<String>,
+}
+
+/// Deployment of the Redis server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedisMode {
+    /// A single server at `url`
+    #[default]
+    Standalone,
+    /// A Redis Cluster, discovered from any of its nodes
+    Cluster,
+    /// A master and replicas whose failover is managed by Sentinels
+    Sentinel,
+}
+
+/// Behavior of the session store while Redis is unavailable
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SessionFallbackConfig {
+    /// Consecutive Redis failures after which calls skip Redis
+    pub failure_threshold: u32,
+    /// Seconds until Redis is tried again after the circuit opened
+    pub reset_timeout_secs: u64,
+    /// Accepts valid JWTs without a stored session; revoked sessions are not rejected meanwhile
+    pub stateless_jwt: bool,
+}
+
+impl Default for SessionFallbackConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            reset_timeout_secs: 30,
+            stateless_jwt: false,
+        }
+    }
+}
+
+/// Public self-signup configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SignupConfig {
+    /// Secret for verifying CAPTCHA tokens; CAPTCHAs are not checked without one
+    pub captcha_secret: Option<String>,
+    /// Siteverify endpoint of the CAPTCHA provider
+    pub captcha_verify_url: String,
+    /// Signup attempts allowed per client within a window
+    pub max_attempts: u32,
+    pub window_secs: u64,
+    /// Hours until unverified signups expire
+    pub verification_ttl_hours: i64,
+    /// Link sent to new admins; the verification token is appended
+    pub verification_url: String,
+    /// Keeps verified tenants inactive until an operator approved them
+    pub require_approval: bool,
+    /// Address notified about tenants awaiting approval
+    pub approval_notify_email: Option<String>,
+}
+
+impl Default for SignupConfig {
+    fn default() -> Self {
+        Self {
+            captcha_secret: None,
+            captcha_verify_url: "https://hcaptcha.com/siteverify".to_string(),
+            max_attempts: 5,
+            window_secs: 3600,
+            verification_ttl_hours: 24,
+            verification_url: "http://localhost:3000/signup/verify?token=".to_string(),
+            require_approval: false,
+            approval_notify_email: None,
+        }
+    }
+}
+
+/// Verification of the email addresses users add to their accounts
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EmailConfig {
+    /// Hours until verification links of added addresses expire
+    pub verification_ttl_hours: i64,
+    /// Link mailed to added addresses; the verification token is appended
+    pub verification_url: String,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            verification_ttl_hours: 24,
+            verification_url: "http://localhost:3000/emails/verify?token=".to_string(),
+        }
+    }
+}
+
+/// Policy engine deciding authorization requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyEngineKind {
+    /// Permissions of the user's roles
+    Rbac,
+    /// Open Policy Agent sidecar
+    Opa,
+    /// Cedar agent sidecar
+    Cedar,
+}
+
+impl std::fmt::Display for PolicyEngineKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolicyEngineKind::Rbac => write!(f, "rbac"),
+            PolicyEngineKind::Opa => write!(f, "opa"),
+            PolicyEngineKind::Cedar => write!(f, "cedar"),
+        }
+    }
+}
+
+/// Authorization policy configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PolicyConfig {
+    pub engine: PolicyEngineKind,
+    /// Address of the OPA or Cedar sidecar
+    pub url: Option<String>,
+    /// OPA decision evaluated for every request
+    pub opa_policy_path: String,
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        Self {
+            engine: PolicyEngineKind::Rbac,
+            url: None,
+            opa_policy_path: "acci/authz/allow".to_string(),
+        }
+    }
+}
+
+/// Backend storing the sessions of logged in users
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionStoreKind {
+    /// Redis, guarded by a circuit breaker
+    Redis,
+    /// The `sessions` table, for deployments without Redis
+    Postgres,
+    /// Process memory, for development and tests; sessions are lost on restart
+    Memory,
+}
+
+impl std::fmt::Display for SessionStoreKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionStoreKind::Redis => write!(f, "redis"),
+            SessionStoreKind::Postgres => write!(f, "postgres"),
+            SessionStoreKind::Memory => write!(f, "memory"),
+        }
+    }
+}
+
+/// Kind of token identifying a session
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionTokenMode {
+    /// Signed JWTs carrying the user, tenant and scopes
+    #[default]
+    Jwt,
+    /// Random tokens without claims, only resolvable through the session store
+    Opaque,
+}
+
+/// What happens to a login exceeding the concurrent sessions allowed per user
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionLimitStrategy {
+    /// Rejects the new login
+    #[default]
+    Reject,
+    /// Revokes the user's oldest sessions to make room for the new one
+    EvictOldest,
+}
+
+/// Session storage configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    pub store: SessionStoreKind,
+    pub token_mode: SessionTokenMode,
+    /// Seconds between purges of expired Postgres sessions and archived sessions past retention
+    pub cleanup_interval_secs: u64,
+    /// Keeps summaries of sessions in Postgres after they were revoked or expired
+    pub archive: bool,
+    /// Days archived sessions are kept after they ended
+    pub archive_retention_days: u32,
+    /// Seconds a session lives at most, however actively it is used
+    pub lifetime_secs: u64,
+    /// Seconds without requests after which a session expires; `None` keeps sessions
+    /// valid for their whole lifetime
+    pub idle_timeout_secs: Option<u64>,
+    /// Concurrent login sessions allowed per user unless their tenant's policy overrides
+    /// it; `None` allows any number
+    pub max_sessions: Option<usize>,
+    /// What happens to a login exceeding `max_sessions`
+    pub session_limit_strategy: SessionLimitStrategy,
+    /// Keys signing session JWTs instead of the shared HMAC secret, newest first;
+    /// the first signs and all unexpired ones validate
+    pub signing_keys: Vec<JwtKeyConfig>,
+    /// Seconds between reloads of the signing key files
+    pub signing_key_reload_secs: u64,
+    /// Issuer and audience claims of signed session tokens
+    pub token_issuer: String,
+    pub token_audience: String,
+    /// Seconds sessions are cached in process memory in front of the store; `None`
+    /// looks every token up in the store
+    pub local_cache_ttl_secs: Option<u64>,
+    /// Days the rotating refresh tokens issued with sessions stay valid unless used;
+    /// `None` issues no refresh tokens
+    pub refresh_token_lifetime_days: Option<u32>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            store: SessionStoreKind::Redis,
+            token_mode: SessionTokenMode::Jwt,
+            cleanup_interval_secs: 300,
+            archive: false,
+            archive_retention_days: 365,
+            lifetime_secs: 3600,
+            idle_timeout_secs: None,
+            max_sessions: None,
+            session_limit_strategy: SessionLimitStrategy::Reject,
+            signing_keys: Vec::new(),
+            signing_key_reload_secs: 60,
+            token_issuer: "acci_rust".to_string(),
+            token_audience: "acci_rust".to_string(),
+            local_cache_ttl_secs: None,
+            refresh_token_lifetime_days: Some(30),
+        }
+    }
+}
+
+/// JWT signature algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum JwtAlgorithm {
+    HS256,
+    RS256,
+    ES256,
+}
+
+/// Key signing JWTs; RSA and EC keys are published as JWKs for downstream validation
+#[derive(Debug, Clone, Deserialize)]
+pub struct JwtKeyConfig {
+    pub algorithm: JwtAlgorithm,
+    /// Shared secret of an HS256 key
+    pub secret: Option<String>,
+    /// PKCS#8 PEM of the private key; RSA keys may also be PKCS#1
+    pub private_key_pem: Option<String>,
+    /// File holding the PEM, read if no PEM is given inline
+    pub private_key_file: Option<String>,
+    /// Key ID set in the `kid` header of signed tokens
+    pub key_id: String,
+    /// When the key stops validating tokens, e.g. once the tokens it signed expired
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<time::OffsetDateTime>,
+}
+
+/// Tenant lifecycle configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TenantConfig {
+    /// Days deleted tenants can be restored before they are purged
+    pub deleted_retention_days: u32,
+    /// Seconds between purges of deleted tenants past retention
+    pub purge_interval_secs: u64,
+    /// Seconds between DNS lookups of a pending custom domain's TXT challenge
+    pub domain_verification_interval_secs: u64,
+    /// Days a custom domain claim can stay unverified before it is dropped
+    pub domain_claim_ttl_days: u32,
+    /// Parent domain whose subdomains resolve to the tenant with that slug, e.g.
+    /// `acci.example.com` for `acme.acci.example.com`
+    pub base_domain: Option<String>,
+    /// Link sent to the admins tenants are created with; the invitation token is appended
+    pub invitation_url: String,
+    /// Hours until admin invitations expire
+    pub invitation_ttl_hours: i64,
+    /// Directory archived tenants are stored below, e.g. a mounted bucket; archiving
+    /// is disabled without one
+    pub archive_dir: Option<String>,
+    /// External endpoints notified about tenant lifecycle changes
+    pub webhooks: Vec<TenantWebhookConfig>,
+}
+
+/// External endpoint receiving tenant lifecycle events, e.g. of a billing system
+#[derive(Debug, Clone, Deserialize)]
+pub struct TenantWebhookConfig {
+    pub url: String,
+    /// Secret the payloads are signed with in the `X-Webhook-Signature` header
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+impl Default for TenantConfig {
+    fn default() -> Self {
+        Self {
+            deleted_retention_days: 30,
+            purge_interval_secs: 3600,
+            domain_verification_interval_secs: 300,
+            domain_claim_ttl_days: 7,
+            base_domain: None,
+            invitation_url: "http://localhost:3000/invitations/accept?token=".to_string(),
+            invitation_ttl_hours: 72,
+            archive_dir: None,
+            webhooks: Vec::new(),
+        }
+    }
+}
+
+/// Backends of a data residency region
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegionConfig {
+    /// Region name tenants are assigned to, e.g. `eu`
+    pub name: String,
+    pub database: DatabaseConfig,
+    pub redis_url: String,
+}
+
+/// Kubernetes probe and shutdown configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ProbeConfig {
+    /// Seconds between readiness flipping to not-ready and the server stopping to
+    /// accept connections; should exceed the readiness probe period
+    pub drain_delay_secs: u64,
+    /// Seconds of calls to a dependency its SLIs are computed over
+    pub sli_window_secs: u64,
+    /// Share of failed calls above which a dependency is degraded
+    pub sli_max_error_rate: f64,
+    /// p99 latency in milliseconds above which a dependency is degraded
+    pub sli_max_p99_ms: u64,
+    /// Calls within the window needed before a dependency can be degraded
+    pub sli_min_samples: usize,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        Self {
+            drain_delay_secs: 5,
+            sli_window_secs: 300,
+            sli_max_error_rate: 0.05,
+            sli_max_p99_ms: 1000,
+            sli_min_samples: 20,
+        }
+    }
+}
+
+/// Login throttling per user, keyed by tenant and email
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LoginThrottleConfig {
+    /// Login attempts allowed per user within a window, successful or not
+    pub max_attempts: u32,
+    pub window_secs: u64,
+    /// Consecutive failed logins locking the user out
+    pub max_failures: u32,
+    pub lockout_secs: u64,
+}
+
+impl Default for LoginThrottleConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 20,
+            window_secs: 300,
+            max_failures: 5,
+            lockout_secs: 900,
+        }
+    }
+}
+
+/// SAML service provider and OIDC relying party configuration of the SSO module
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SsoConfig {
+    /// PEM certificate and private key signing SAML requests
+    pub saml_certificate: Option<String>,
+    pub saml_private_key: Option<String>,
+    /// Key to roll over to, published in the SAML metadata ahead of its promotion
+    pub saml_next_certificate: Option<String>,
+    pub saml_next_private_key: Option<String>,
+    /// Organization and technical contact published in the SAML metadata
+    pub saml_org_name: Option<String>,
+    pub saml_org_display_name: Option<String>,
+    pub saml_org_url: Option<String>,
+    pub saml_tech_contact_name: Option<String>,
+    pub saml_tech_contact_email: Option<String>,
+    /// Callback OIDC providers without registered redirect URIs return users to after logging in
+    pub oidc_redirect_url: Option<String>,
+    /// Where OIDC providers return users after logging them out
+    pub oidc_post_logout_redirect_url: Option<String>,
+    /// Base64 encoded 256-bit key encrypting the stored tokens of OIDC providers
+    pub token_encryption_key: Option<String>,
+}
+
+/// External secret stores that secret references are resolved from
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SecretsConfig {
+    /// Address of the Vault server resolving `vault:` references
+    pub vault_address: Option<String>,
+    pub vault_token: Option<String>,
+}
+
+/// Optional application modules that can be switched off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppModule {
+    Sso,
+    Scim,
+    OAuth2Server,
+    HostedUi,
+    Signup,
+}
+
+impl std::fmt::Display for AppModule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppModule::Sso => write!(f, "sso"),
+            AppModule::Scim => write!(f, "scim"),
+            AppModule::OAuth2Server => write!(f, "oauth2_server"),
+            AppModule::HostedUi => write!(f, "hosted_ui"),
+            AppModule::Signup => write!(f, "signup"),
+        }
+    }
+}
+
+/// Module enablement configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ModulesConfig {
+    pub sso: bool,
+    pub scim: bool,
+    pub oauth2_server: bool,
+    pub hosted_ui: bool,
+    /// Public self-signup is opt-in
+    pub signup: bool,
+}
+
+impl Default for ModulesConfig {
+    fn default() -> Self {
+        Self {
+            sso: true,
+            scim: true,
+            oauth2_server: true,
+            hosted_ui: true,
+            signup: false,
+        }
+    }
+}
+
+impl ModulesConfig {
+    /// Checks if a module is enabled
+    pub fn is_enabled(&self, module: AppModule) -> bool {
+        match module {
+            AppModule::Sso => self.sso,
+            AppModule::Scim => self.scim,
+            AppModule::OAuth2Server => self.oauth2_server,
+            AppModule::HostedUi => self.hosted_ui,
+            AppModule::Signup => self.signup,
         }
     }
 }
@@ -67,6 +605,32 @@ pub struct Config {
     pub server: ServerConfig,
     pub database: DatabaseConfig,
     pub redis: RedisConfig,
+    #[serde(default)]
+    pub modules: ModulesConfig,
+    #[serde(default)]
+    pub signup: SignupConfig,
+    #[serde(default)]
+    pub emails: EmailConfig,
+    #[serde(default)]
+    pub sso: SsoConfig,
+    #[serde(default)]
+    pub secrets: SecretsConfig,
+    #[serde(default)]
+    pub policy: PolicyConfig,
+    #[serde(default)]
+    pub probes: ProbeConfig,
+    #[serde(default)]
+    pub login_throttle: LoginThrottleConfig,
+    #[serde(default)]
+    pub sessions: SessionConfig,
+    #[serde(default)]
+    pub tenants: TenantConfig,
+    /// Regional backends; tenants without a region use the home backends above
+    #[serde(default)]
+    pub regions: Vec<RegionConfig>,
+    /// TOML or JSON file declaring tenants, roles, users and SSO providers to create at startup
+    #[serde(default)]
+    pub bootstrap_file: Option<String>,
 }
 
 impl Config {
@@ -76,6 +640,18 @@ impl Config {
             server: ServerConfig::default_dev(),
             database: DatabaseConfig::default_dev(),
             redis: RedisConfig::default_dev(),
+            modules: ModulesConfig::default(),
+            signup: SignupConfig::default(),
+            emails: EmailConfig::default(),
+            sso: SsoConfig::default(),
+            secrets: SecretsConfig::default(),
+            policy: PolicyConfig::default(),
+            probes: ProbeConfig::default(),
+            login_throttle: LoginThrottleConfig::default(),
+            sessions: SessionConfig::default(),
+            tenants: TenantConfig::default(),
+            regions: Vec::new(),
+            bootstrap_file: None,
         }
     }
 
@@ -95,5 +671,38 @@ mod tests {
         assert_eq!(config.server.port, 3000);
         assert_eq!(config.database.port, 5432);
         assert_eq!(config.redis.url, "redis://localhost:6379");
+        assert!(!config.redis.fallback.stateless_jwt);
+        assert_eq!(config.policy.engine, PolicyEngineKind::Rbac);
+        assert_eq!(config.sessions.store, SessionStoreKind::Redis);
+        assert!(config.modules.is_enabled(AppModule::Sso));
+        assert!(!config.modules.is_enabled(AppModule::Signup));
+        assert!(config.signup.captcha_secret.is_none());
+        assert_eq!(config.probes.drain_delay_secs, 5);
+    }
+
+    #[test]
+    fn test_tenant_webhooks_config() {
+        let tenants: TenantConfig = serde_json::from_value(serde_json::json!({
+            "webhooks": [
+                { "url": "https://billing.example.com/hooks", "secret": "s3cret" },
+                { "url": "https://provisioning.example.com/hooks" }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(tenants.webhooks.len(), 2);
+        assert_eq!(tenants.webhooks[0].secret.as_deref(), Some("s3cret"));
+        assert!(tenants.webhooks[1].secret.is_none());
+        assert_eq!(tenants.purge_interval_secs, 3600);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_modules_config() {
+        let modules: ModulesConfig =
+            serde_json::from_value(serde_json::json!({ "scim": false, "hosted_ui": false }))
+                .unwrap();
+        assert!(modules.is_enabled(AppModule::Sso));
+        assert!(!modules.is_enabled(AppModule::Scim));
+        assert!(modules.is_enabled(AppModule::OAuth2Server));
+        assert!(!modules.is_enabled(AppModule::HostedUi));
+    }
+}
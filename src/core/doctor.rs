@@ -0,0 +1,480 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use std::{collections::HashSet, fmt, time::Duration};
+use time::OffsetDateTime;
+
+use crate::{
+    core::{
+        config::{AppModule, Config},
+        database::Database,
+    },
+    modules::identity::sso::parse_wsfed_metadata,
+    shared::error::{Error, Result},
+};
+
+/// Timeout of HTTP requests to identity providers
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    Skip,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "PASS"),
+            CheckStatus::Fail => write!(f, "FAIL"),
+            CheckStatus::Skip => write!(f, "SKIP"),
+        }
+    }
+}
+
+/// Result of a single check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn new(name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        }
+    }
+
+    fn from_result(name: impl Into<String>, result: Result<String>) -> Self {
+        match result {
+            Ok(detail) => Self::new(name, CheckStatus::Pass, detail),
+            Err(e) => Self::new(name, CheckStatus::Fail, e.to_string()),
+        }
+    }
+}
+
+/// Pass/fail report of the startup self-test
+#[derive(Debug, Clone, Default)]
+pub struct DoctorReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl DoctorReport {
+    /// Checks if no check failed
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Fail)
+    }
+}
+
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(f, "[{}] {}: {}", check.status, check.name, check.detail)?;
+        }
+        let failed = self
+            .checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Fail)
+            .count();
+        write!(f, "{} checks, {} failed", self.checks.len(), failed)
+    }
+}
+
+/// Runs the startup self-test against the configured services
+pub async fn run(config: &Config) -> DoctorReport {
+    let mut report = DoctorReport::default();
+
+    let db = match Database::connect(&config.database).await {
+        Ok(db) => {
+            report.checks.push(CheckResult::new(
+                "Database",
+                CheckStatus::Pass,
+                format!(
+                    "connected to {}:{}/{}",
+                    config.database.host, config.database.port, config.database.database
+                ),
+            ));
+            Some(db)
+        },
+        Err(e) => {
+            report.checks.push(CheckResult::new(
+                "Database",
+                CheckStatus::Fail,
+                e.to_string(),
+            ));
+            None
+        },
+    };
+
+    match &db {
+        Some(db) => report.checks.push(CheckResult::from_result(
+            "Migrations",
+            check_migrations(db).await,
+        )),
+        None => report.checks.push(CheckResult::new(
+            "Migrations",
+            CheckStatus::Skip,
+            "database unavailable",
+        )),
+    }
+
+    report.checks.push(CheckResult::from_result(
+        "Redis",
+        check_redis(&config.redis.url).await,
+    ));
+
+    // Emails go through the `Mailer` extension point; the framework ships no transport
+    report.checks.push(CheckResult::new(
+        "SMTP",
+        CheckStatus::Skip,
+        "no mail transport configured, emails are only logged",
+    ));
+
+    if !config.modules.is_enabled(AppModule::Sso) {
+        report.checks.push(CheckResult::new(
+            "SSO providers",
+            CheckStatus::Skip,
+            "SSO module disabled",
+        ));
+    } else if let Some(db) = &db {
+        report.checks.extend(check_sso_providers(db).await);
+    } else {
+        report.checks.push(CheckResult::new(
+            "SSO providers",
+            CheckStatus::Skip,
+            "database unavailable",
+        ));
+    }
+
+    report
+}
+
+/// Checks that all bundled migrations were applied
+async fn check_migrations(db: &Database) -> Result<String> {
+    let migrator = sqlx::migrate!("./migrations");
+    let applied: HashSet<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(&db.get_pool())
+            .await
+            .map_err(|e| Error::Database(format!("Failed to read migration status: {}", e)))?
+            .into_iter()
+            .collect();
+
+    let pending: Vec<String> = migrator
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .map(|migration| migration.version.to_string())
+        .collect();
+    if !pending.is_empty() {
+        return Err(Error::Database(format!(
+            "{} pending migrations: {}",
+            pending.len(),
+            pending.join(", ")
+        )));
+    }
+    Ok(format!("{} migrations applied", migrator.iter().count()))
+}
+
+/// Checks that Redis answers a ping
+async fn check_redis(url: &str) -> Result<String> {
+    let client = redis::Client::open(url)?;
+    let mut conn = client.get_async_connection().await?;
+    let reply: String = redis::cmd("PING").query_async(&mut conn).await?;
+    Ok(format!("{} replied {}", url, reply))
+}
+
+/// Checks the keys of enabled SAML and WS-Federation providers and the discovery of enabled
+/// OIDC providers
+async fn check_sso_providers(db: &Database) -> Vec<CheckResult> {
+    let providers = match sqlx::query!(
+        r#"
+        SELECT name, provider_type, metadata_url, metadata_xml, issuer
+        FROM sso_providers
+        WHERE enabled = true
+        ORDER BY name
+        "#
+    )
+    .fetch_all(&db.get_pool())
+    .await
+    {
+        Ok(providers) => providers,
+        Err(e) => {
+            return vec![CheckResult::new(
+                "SSO providers",
+                CheckStatus::Fail,
+                e.to_string(),
+            )]
+        },
+    };
+    if providers.is_empty() {
+        return vec![CheckResult::new(
+            "SSO providers",
+            CheckStatus::Skip,
+            "no enabled providers",
+        )];
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    let mut checks = Vec::new();
+    for provider in providers {
+        let name = format!(
+            "SSO provider {} ({})",
+            provider.name, provider.provider_type
+        );
+        let result = match provider.provider_type.as_str() {
+            "saml" => check_saml_provider(&client, provider.metadata_url.as_deref()).await,
+            "oidc" => check_oidc_provider(&client, provider.issuer.as_deref()).await,
+            "wsfed" => check_wsfed_provider(provider.metadata_xml.as_deref()),
+            other => Err(Error::InvalidInput(format!(
+                "Unknown provider type {}",
+                other
+            ))),
+        };
+        checks.push(CheckResult::from_result(name, result));
+    }
+    checks
+}
+
+/// Checks that the IdP metadata is reachable and its signing certificates are valid
+async fn check_saml_provider(
+    client: &reqwest::Client,
+    metadata_url: Option<&str>,
+) -> Result<String> {
+    let url = metadata_url
+        .ok_or_else(|| Error::InvalidInput("No metadata URL configured".to_string()))?;
+    let metadata = fetch(client, url)
+        .await?
+        .text()
+        .await
+        .map_err(|e| Error::Internal(format!("Failed to read metadata from {}: {}", url, e)))?;
+
+    let certificates = extract_certificates(&metadata);
+    if certificates.is_empty() {
+        return Err(Error::InvalidInput(
+            "Metadata contains no X.509 certificate".to_string(),
+        ));
+    }
+    check_certificates(&certificates)
+}
+
+/// Checks that the stored federation metadata of the STS is valid, as are its signing certificates
+///
+/// Sign-ins are validated against the stored metadata, not the one currently served.
+fn check_wsfed_provider(metadata_xml: Option<&str>) -> Result<String> {
+    let metadata = metadata_xml
+        .ok_or_else(|| Error::InvalidInput("No federation metadata stored".to_string()))?;
+    let metadata = parse_wsfed_metadata(metadata)?;
+    Ok(format!(
+        "passive endpoint {}, {}",
+        metadata.passive_endpoint,
+        check_certificates(&metadata.certificates)?
+    ))
+}
+
+/// Checks that base64 encoded certificates are valid now, describing when the first expires
+fn check_certificates(certificates: &[String]) -> Result<String> {
+    let mut expires_at = Vec::new();
+    for certificate in certificates {
+        expires_at.push(certificate_expiry(certificate, OffsetDateTime::now_utc())?);
+    }
+    let first_expiry = expires_at
+        .iter()
+        .min()
+        .copied()
+        .unwrap_or_else(OffsetDateTime::now_utc);
+    Ok(format!(
+        "{} valid certificates, first expires {}",
+        expires_at.len(),
+        first_expiry.date()
+    ))
+}
+
+/// Checks that the OIDC discovery document is served for the configured issuer
+async fn check_oidc_provider(client: &reqwest::Client, issuer: Option<&str>) -> Result<String> {
+    let issuer = issuer.ok_or_else(|| Error::InvalidInput("No issuer configured".to_string()))?;
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        issuer.trim_end_matches('/')
+    );
+    let discovery: serde_json::Value = fetch(client, &url)
+        .await?
+        .json()
+        .await
+        .map_err(|e| Error::Internal(format!("Invalid discovery document: {}", e)))?;
+
+    if discovery["issuer"].as_str() != Some(issuer) {
+        return Err(Error::InvalidInput(format!(
+            "Discovery document names issuer {}",
+            discovery["issuer"]
+        )));
+    }
+    if discovery["jwks_uri"].as_str().is_none() {
+        return Err(Error::InvalidInput(
+            "Discovery document has no jwks_uri".to_string(),
+        ));
+    }
+    Ok(format!("discovery document served at {}", url))
+}
+
+/// Fetches a URL, failing on unsuccessful responses
+async fn fetch(client: &reqwest::Client, url: &str) -> Result<reqwest::Response> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| Error::Internal(format!("Failed to fetch {}: {}", url, e)))?;
+    if !response.status().is_success() {
+        return Err(Error::Internal(format!(
+            "{} responded with status {}",
+            url,
+            response.status()
+        )));
+    }
+    Ok(response)
+}
+
+/// Extracts the base64 encoded certificates of SAML metadata
+fn extract_certificates(metadata: &str) -> Vec<String> {
+    const TAG: &str = "X509Certificate>";
+
+    let mut certificates = Vec::new();
+    let mut rest = metadata;
+    while let Some(start) = rest.find(TAG) {
+        let content = &rest[start + TAG.len()..];
+        let Some(end) = content.find("</") else {
+            break;
+        };
+        let certificate: String = content[..end]
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        if !certificate.is_empty() {
+            certificates.push(certificate);
+        }
+        // Skip the closing tag, which ends with the same name
+        rest = &content[end..];
+        match rest.find('>') {
+            Some(i) => rest = &rest[i + 1..],
+            None => break,
+        }
+    }
+    certificates
+}
+
+/// Parses a base64 encoded certificate and checks that it is valid at the given time
+fn certificate_expiry(certificate: &str, now: OffsetDateTime) -> Result<OffsetDateTime> {
+    let der = BASE64
+        .decode(certificate)
+        .map_err(|e| Error::InvalidInput(format!("Invalid certificate encoding: {}", e)))?;
+    let (_, certificate) = x509_parser::parse_x509_certificate(&der)
+        .map_err(|e| Error::InvalidInput(format!("Invalid certificate: {}", e)))?;
+
+    let validity = certificate.validity();
+    let not_before = validity.not_before.to_datetime();
+    let not_after = validity.not_after.to_datetime();
+    if now < not_before {
+        return Err(Error::InvalidInput(format!(
+            "Certificate is not valid before {}",
+            not_before
+        )));
+    }
+    if now > not_after {
+        return Err(Error::InvalidInput(format!(
+            "Certificate expired at {}",
+            not_after
+        )));
+    }
+    Ok(not_after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_certificates() {
+        let metadata = r#"
+            <md:EntityDescriptor>
+                <ds:X509Certificate>
+                    MIIB
+                    AAAA
+                </ds:X509Certificate>
+                <X509Certificate>MIIC</X509Certificate>
+                <ds:X509Certificate></ds:X509Certificate>
+            </md:EntityDescriptor>
+        "#;
+        assert_eq!(
+            extract_certificates(metadata),
+            vec!["MIIBAAAA".to_string(), "MIIC".to_string()]
+        );
+        assert!(extract_certificates("<md:EntityDescriptor/>").is_empty());
+    }
+
+    #[test]
+    fn test_invalid_certificate() {
+        let now = OffsetDateTime::now_utc();
+        assert!(matches!(
+            certificate_expiry("not base64!", now),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            certificate_expiry(&BASE64.encode(b"not a certificate"), now),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_wsfed_provider() {
+        assert!(matches!(
+            check_wsfed_provider(None),
+            Err(Error::InvalidInput(_))
+        ));
+        // Metadata of an STS without signing certificates cannot validate sign-ins
+        let metadata = r#"
+            <EntityDescriptor
+                xmlns:fed="http://docs.oasis-open.org/wsfed/federation/200706"
+                entityID="http://sts.example.com/adfs/services/trust">
+                <RoleDescriptor type="fed:SecurityTokenServiceType">
+                    <fed:PassiveRequestorEndpoint>
+                        <Address>https://sts.example.com/adfs/ls/</Address>
+                    </fed:PassiveRequestorEndpoint>
+                </RoleDescriptor>
+            </EntityDescriptor>
+        "#;
+        let result = check_wsfed_provider(Some(metadata));
+        assert!(
+            matches!(&result, Err(Error::Validation(message)) if message.contains("no signing certificate"))
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let mut report = DoctorReport::default();
+        report
+            .checks
+            .push(CheckResult::new("Redis", CheckStatus::Pass, "PONG"));
+        report
+            .checks
+            .push(CheckResult::new("SMTP", CheckStatus::Skip, "none"));
+        assert!(report.passed());
+        assert_eq!(
+            report.to_string(),
+            "[PASS] Redis: PONG\n[SKIP] SMTP: none\n2 checks, 0 failed"
+        );
+
+        report.checks.push(CheckResult::from_result(
+            "Database",
+            Err(Error::Database("refused".to_string())),
+        ));
+        assert!(!report.passed());
+    }
+}
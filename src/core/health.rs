@@ -0,0 +1,265 @@
+use axum::{
+    extract::State,
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tracing::info;
+
+use crate::shared::sli::{HealthReport, SliTracker};
+
+/// Probe state of an instance, shared by the probe handlers and the shutdown logic
+///
+/// An instance starts once its migrations completed and is ready while started,
+/// not draining and no critical dependency is degraded; clones share their state.
+#[derive(Debug, Clone, Default)]
+pub struct HealthState {
+    started: Arc<AtomicBool>,
+    draining: Arc<AtomicBool>,
+    drain_delay: Duration,
+    sli: SliTracker,
+}
+
+/// Detailed health of an instance, served at `/health/info`
+#[derive(Debug, Serialize)]
+pub struct HealthInfo {
+    pub started: bool,
+    pub draining: bool,
+    pub ready: bool,
+    #[serde(flatten)]
+    pub report: HealthReport,
+}
+
+impl HealthState {
+    /// Creates the state of an instance that has not started yet
+    ///
+    /// Draining keeps serving requests for `drain_delay` after readiness flipped,
+    /// until load balancers stopped routing new requests to the instance.
+    pub fn new(drain_delay: Duration) -> Self {
+        Self {
+            drain_delay,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the tracker of the dependency SLIs that readiness depends on
+    pub fn with_sli(mut self, sli: SliTracker) -> Self {
+        self.sli = sli;
+        self
+    }
+
+    /// Gets the tracker of the dependency SLIs, to record calls to dependencies
+    pub fn sli(&self) -> &SliTracker {
+        &self.sli
+    }
+
+    /// Marks the instance as started, e.g. after the migrations were applied
+    pub fn mark_started(&self) {
+        self.started.store(true, Ordering::SeqCst);
+    }
+
+    /// Checks if the instance has started
+    pub fn is_started(&self) -> bool {
+        self.started.load(Ordering::SeqCst)
+    }
+
+    /// Checks if the instance accepts new traffic
+    pub fn is_ready(&self) -> bool {
+        self.is_started() && !self.is_draining() && !self.sli.is_degraded()
+    }
+
+    /// Reports the probe state and the health of the dependencies
+    pub fn info(&self) -> HealthInfo {
+        let report = self.sli.report();
+        HealthInfo {
+            started: self.is_started(),
+            draining: self.is_draining(),
+            ready: self.is_started() && !self.is_draining() && !report.degraded,
+            report,
+        }
+    }
+
+    /// Checks if the instance is shutting down
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Flips readiness to not-ready and waits for the drain delay
+    ///
+    /// Only the first call waits, so a preStop hook and the following SIGTERM
+    /// do not delay the shutdown twice.
+    pub async fn drain(&self) {
+        if self.draining.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        info!(
+            "Draining, shutting down in {} seconds",
+            self.drain_delay.as_secs()
+        );
+        tokio::time::sleep(self.drain_delay).await;
+    }
+
+    /// Waits for SIGTERM or Ctrl+C and drains the instance
+    ///
+    /// Used as graceful shutdown signal of the server, which then finishes
+    /// in-flight requests before exiting.
+    pub async fn shutdown_signal(self) {
+        let ctrl_c = async {
+            let _ = tokio::signal::ctrl_c().await;
+        };
+        #[cfg(unix)]
+        let terminate = async {
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(mut signal) => {
+                    signal.recv().await;
+                },
+                Err(_) => std::future::pending::<()>().await,
+            }
+        };
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<()>();
+
+        tokio::select! {
+            _ = ctrl_c => {},
+            _ = terminate => {},
+        }
+        self.drain().await;
+    }
+}
+
+/// Liveness probe; succeeds as long as the process serves requests
+async fn live() -> impl IntoResponse {
+    StatusCode::OK
+}
+
+/// Startup probe; succeeds once the migrations were applied
+async fn startup(State(health): State<HealthState>) -> impl IntoResponse {
+    if health.is_started() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+/// Readiness probe; fails before startup, while draining and while a critical
+/// dependency is degraded
+async fn ready(State(health): State<HealthState>) -> impl IntoResponse {
+    if health.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+/// Health score and SLIs of the dependencies
+async fn info(State(health): State<HealthState>) -> impl IntoResponse {
+    Json(health.info())
+}
+
+/// Dependency SLIs in the Prometheus text format
+async fn metrics(State(health): State<HealthState>) -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        health.sli().render_metrics(),
+    )
+}
+
+/// Target of a Kubernetes preStop hook; flips readiness and returns after the drain delay
+async fn pre_stop(State(health): State<HealthState>) -> impl IntoResponse {
+    health.drain().await;
+    StatusCode::OK
+}
+
+/// Creates the router of the Kubernetes probes, health details and metrics
+pub fn router(health: HealthState) -> Router {
+    Router::new()
+        .route("/health/live", get(live))
+        .route("/health/startup", get(startup))
+        .route("/health/ready", get(ready))
+        .route("/health/info", get(info))
+        .route("/health/prestop", get(pre_stop))
+        .route("/metrics", get(metrics))
+        .with_state(health)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::sli::{Dependency, SliThresholds};
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    async fn status(app: &Router, uri: &str) -> StatusCode {
+        app.clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_probes() {
+        let health = HealthState::new(Duration::from_millis(10));
+        let app = router(health.clone());
+
+        assert_eq!(status(&app, "/health/live").await, StatusCode::OK);
+        assert_eq!(
+            status(&app, "/health/startup").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            status(&app, "/health/ready").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        health.mark_started();
+        assert_eq!(status(&app, "/health/startup").await, StatusCode::OK);
+        assert_eq!(status(&app, "/health/ready").await, StatusCode::OK);
+
+        // The preStop hook flips readiness before the pod receives SIGTERM
+        assert_eq!(status(&app, "/health/prestop").await, StatusCode::OK);
+        assert_eq!(
+            status(&app, "/health/ready").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(status(&app, "/health/live").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_degraded_dependency_fails_readiness() {
+        let sli = SliTracker::new(
+            Duration::from_secs(60),
+            SliThresholds {
+                min_samples: 1,
+                ..SliThresholds::default()
+            },
+        );
+        let health = HealthState::new(Duration::from_millis(10)).with_sli(sli.clone());
+        health.mark_started();
+        let app = router(health.clone());
+
+        sli.record(Dependency::Mailer, Duration::from_millis(1), false);
+        assert_eq!(status(&app, "/health/ready").await, StatusCode::OK);
+
+        sli.record(Dependency::Postgres, Duration::from_millis(1), false);
+        assert_eq!(
+            status(&app, "/health/ready").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        let info = health.info();
+        assert!(!info.ready);
+        assert!(info.report.degraded);
+        assert_eq!(status(&app, "/health/info").await, StatusCode::OK);
+        assert_eq!(status(&app, "/metrics").await, StatusCode::OK);
+        assert_eq!(status(&app, "/health/live").await, StatusCode::OK);
+    }
+}
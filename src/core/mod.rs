@@ -1,29 +1,221 @@
+pub mod bootstrap;
 pub mod config;
 pub mod database;
+pub mod doctor;
+pub mod health;
 pub mod server;
 
-use self::{config::Config, database::Database, server::Server};
-use crate::shared::error::Result;
+use std::{sync::Arc, time::Duration};
+
+use self::{
+    bootstrap::BootstrapSpec,
+    config::{AppModule, Config},
+    database::{Database, DatabaseRouter},
+    health::HealthState,
+    server::{ModuleRegistry, Server},
+};
+use crate::{
+    modules::{
+        identity::{
+            self,
+            middleware::ApiKeyQuotaLayer,
+            repository::UserRepository,
+            sso::{self, SsoState},
+        },
+        privacy::{self, PrivacyService},
+        project::{self, ProjectService},
+        scim::{self, ScimService},
+        signup::{self, SignupService},
+        tenant::{
+            domains::DnsTxtResolver, hooks::RegionalTenantReplica, plans::Capability,
+            repository::TenantRepository, service::TenantService, TenantModule,
+        },
+    },
+    shared::{
+        error::Result,
+        mail::{LogMailer, Mailer, TrackedMailer},
+        sli::{SliThresholds, SliTracker},
+    },
+};
+
+/// Seconds between runs of the worker carrying out erasure requests past their grace period
+const ERASURE_INTERVAL_SECS: u64 = 3600;
+
+/// Seconds between runs of the worker expiring and activating time-bound role assignments;
+/// cached permission decisions of the affected users are dropped on each run
+const ROLE_EXPIRY_INTERVAL_SECS: u64 = 60;
+
+/// Seconds between purges of expired signups besides the purge on each signup
+const SIGNUP_CLEANUP_INTERVAL_SECS: u64 = 3600;
 
 #[derive(Debug)]
 pub struct Core {
     pub database: Database,
+    /// Databases of the data residency regions, including the home database above
+    pub databases: DatabaseRouter,
     pub server: Server,
+    /// Declarative seed applied once the migrations completed
+    pub bootstrap: Option<BootstrapSpec>,
+    regions: Vec<String>,
 }
 
 impl Core {
     pub async fn new(config: Config) -> Result<Self> {
-        let database = Database::connect(&config.database).await?;
-        let server = Server::new(&config.server).await?;
-        Ok(Self { database, server })
+        let sli = SliTracker::new(
+            Duration::from_secs(config.probes.sli_window_secs),
+            SliThresholds {
+                max_error_rate: config.probes.sli_max_error_rate,
+                max_p99: Duration::from_millis(config.probes.sli_max_p99_ms),
+                min_samples: config.probes.sli_min_samples,
+            },
+        );
+        let database = Database::connect(&config.database)
+            .await?
+            .with_sli(sli.clone());
+        let databases = DatabaseRouter::connect(database.clone(), &config.regions).await?;
+        // Load the bootstrap file before serving so that a broken file fails fast
+        let bootstrap = config
+            .bootstrap_file
+            .as_ref()
+            .map(BootstrapSpec::from_file)
+            .transpose()?;
+        // No mail transport is configurable yet, so emails are written to the log
+        let mailer = Arc::new(TrackedMailer::new(Arc::new(LogMailer), sli.clone()));
+        let registry = create_module_registry(&config, &databases, mailer).await?;
+        let server = Server::new(&config.server)
+            .await?
+            .with_modules(config.modules.clone())
+            .with_module_registry(registry)
+            .with_health(
+                HealthState::new(Duration::from_secs(config.probes.drain_delay_secs))
+                    .with_sli(sli),
+            );
+        Ok(Self {
+            database,
+            databases,
+            server,
+            bootstrap,
+            regions: config.regions.iter().map(|r| r.name.clone()).collect(),
+        })
     }
 
+    /// Serves requests while applying the migrations; the startup and readiness
+    /// probes succeed once the migrations completed and the bootstrap file was applied
     pub async fn run(&self) -> Result<()> {
         self.database.execute_query(sqlx::query("SELECT 1")).await?;
-        self.server.run().await
+        let startup = async {
+            self.databases.run_migrations().await?;
+            if let Some(spec) = &self.bootstrap {
+                bootstrap::apply(spec, &self.database, self.regions.clone()).await?;
+            }
+            self.server.health().mark_started();
+            Ok(())
+        };
+        futures::future::try_join(self.server.run(), startup).await?;
+        Ok(())
     }
 }
 
+/// Creates the routers of all modules, sharing one authentication service
+///
+/// Spawns the background workers of the modules, so this must be called within
+/// the Tokio runtime.
+async fn create_module_registry(
+    config: &Config,
+    databases: &DatabaseRouter,
+    mailer: Arc<dyn Mailer>,
+) -> Result<ModuleRegistry> {
+    let db = databases.home();
+    let (identity, auth) = identity::create_identity_module(databases, config).await?;
+    let identity = Arc::new(identity.with_mailer(mailer.clone()));
+    identity
+        .clone()
+        .spawn_role_expiry_worker(Duration::from_secs(ROLE_EXPIRY_INTERVAL_SECS));
+    let auth = Arc::new(auth);
+    // Requests made with API keys count against the keys' quotas
+    let quotas = ApiKeyQuotaLayer::new(auth.clone(), &config.server.api_prefix);
+    let users = UserRepository::new(db.get_pool());
+
+    let mut tenants = TenantModule::new(db.clone())
+        .with_regions(databases.regions())
+        .with_invitations(&config.tenants)
+        .with_archives(&config.tenants)
+        .with_webhooks(&config.tenants)
+        .with_mailer(mailer.clone())
+        .with_txt_resolver(Arc::new(DnsTxtResolver::from_system_conf()?));
+    // Sessions of deleted and archived tenants are wiped
+    tenants.register_hook(auth.clone());
+    if !config.regions.is_empty() {
+        tenants.register_hook(Arc::new(RegionalTenantReplica::new(databases.clone())));
+    }
+    tenants.spawn_purge_worker(&config.tenants);
+    tenants.spawn_domain_verification_worker(&config.tenants);
+    // Provisioning clients call the API for their tenant's host or `X-Tenant-ID`
+    let scim = scim::router(
+        auth.clone(),
+        Arc::new(ScimService::new(users.clone(), identity.clone(), auth.clone())),
+    )
+    .layer(quotas.clone())
+    .layer(tenants.plan_gate(Capability::Scim))
+    .layer(tenants.resolution_layer(&config.tenants));
+
+    let privacy = Arc::new(PrivacyService::new(users.clone(), auth.clone()));
+    privacy
+        .clone()
+        .spawn_erasure_worker(Duration::from_secs(ERASURE_INTERVAL_SECS));
+
+    let signup = Arc::new(
+        SignupService::new(
+            TenantService::new(TenantRepository::new(db.get_pool())).with_mailer(mailer.clone()),
+            users.clone(),
+            &config.signup,
+        )
+        .with_mailer(mailer.clone()),
+    );
+    if config.modules.is_enabled(AppModule::Signup) {
+        signup
+            .clone()
+            .spawn_cleanup_worker(Duration::from_secs(SIGNUP_CLEANUP_INTERVAL_SECS));
+    }
+
+    let mut registry = ModuleRegistry::new();
+    // Downstream services validate signed session tokens with the published keys; the
+    // tokens carry the token version of their user in the `ver` claim
+    if let Some(keys) = auth.token_keys() {
+        registry = registry.register_root(identity::jwks_router(keys.clone()));
+    }
+    // The SAML keys are only required while SSO is enabled; identity providers
+    // return users to their tenant's host, which resolves the tenant
+    if config.modules.is_enabled(AppModule::Sso) {
+        let sso = sso::create_sso_service(db.clone(), &config.sso, &config.secrets, &config.redis)
+            .await?
+            .with_mailer(mailer.clone());
+        let sso = sso::router(SsoState {
+            auth: auth.clone(),
+            sso: Arc::new(sso),
+        })
+        .layer(quotas.clone())
+        .layer(tenants.plan_gate(Capability::Sso))
+        .layer(tenants.resolution_layer(&config.tenants));
+        registry = registry.register_optional(AppModule::Sso, sso);
+    }
+
+    Ok(registry
+        .register(identity::router(auth.clone(), identity.clone()).layer(quotas.clone()))
+        .register(
+            tenants
+                .router(auth.clone(), identity.rbac())?
+                .layer(quotas.clone()),
+        )
+        .register(
+            project::router(auth.clone(), Arc::new(ProjectService::new(users.clone())))
+                .layer(quotas.clone()),
+        )
+        .register(privacy::router(auth.clone(), privacy).layer(quotas))
+        .register_optional(AppModule::Scim, scim)
+        .register_optional(AppModule::Signup, signup::router(auth, signup)))
+}
+
 pub async fn init(db: &Database) -> Result<()> {
     db.execute_query(sqlx::query("SELECT 1")).await?;
     Ok(())
@@ -31,7 +223,11 @@ pub async fn init(db: &Database) -> Result<()> {
 
 #[cfg(test)]
 mod tests {
-    use self::config::{DatabaseConfig, RedisConfig, ServerConfig};
+    use self::config::{
+        DatabaseConfig, EmailConfig, LoginThrottleConfig, ModulesConfig, PolicyConfig, ProbeConfig,
+        RedisConfig, SecretsConfig, ServerConfig, SessionConfig, SessionFallbackConfig,
+        SignupConfig, SsoConfig, TenantConfig,
+    };
     use super::*;
 
     #[tokio::test]
@@ -41,6 +237,8 @@ mod tests {
                 host: "127.0.0.1".to_string(),
                 port: 3000,
                 cors_allowed_origins: vec!["http://localhost:3000".to_string()],
+                api_prefix: "/api".to_string(),
+                tls: None,
             },
             database: DatabaseConfig {
                 host: "localhost".to_string(),
@@ -53,7 +251,25 @@ mod tests {
             },
             redis: RedisConfig {
                 url: "redis://localhost:6379".to_string(),
+                fallback: SessionFallbackConfig::default(),
+                ..RedisConfig::default_dev()
             },
+            // SSO requires SAML keys
+            modules: ModulesConfig {
+                sso: false,
+                ..ModulesConfig::default()
+            },
+            signup: SignupConfig::default(),
+            emails: EmailConfig::default(),
+            sso: SsoConfig::default(),
+            secrets: SecretsConfig::default(),
+            policy: PolicyConfig::default(),
+            probes: ProbeConfig::default(),
+            login_throttle: LoginThrottleConfig::default(),
+            sessions: SessionConfig::default(),
+            tenants: TenantConfig::default(),
+            regions: Vec::new(),
+            bootstrap_file: None,
         };
 
         let core = Core::new(config).await.unwrap();
@@ -65,6 +281,70 @@ mod tests {
         assert_eq!(result.rows_affected(), 1);
     }
 
+    #[tokio::test]
+    async fn test_module_routes() {
+        use axum::{
+            body::Body,
+            http::{Request, StatusCode},
+        };
+        use tower::ServiceExt;
+
+        // Composing the routers of all modules fails on conflicting routes
+        let mut config = Config::default_dev();
+        config.sso = SsoConfig {
+            saml_certificate: Some("test_cert".to_string()),
+            saml_private_key: Some("test_key".to_string()),
+            saml_org_name: Some("Test Org".to_string()),
+            saml_org_display_name: Some("Test Organization".to_string()),
+            saml_org_url: Some("https://test.org".to_string()),
+            saml_tech_contact_name: Some("Test Admin".to_string()),
+            saml_tech_contact_email: Some("admin@test.org".to_string()),
+            ..SsoConfig::default()
+        };
+        let registry = create_module_registry(
+            &config,
+            &DatabaseRouter::new(Database::default()),
+            Arc::new(LogMailer),
+        )
+        .await
+        .unwrap();
+        let app = Server::new(&config.server)
+            .await
+            .unwrap()
+            .with_module_registry(registry)
+            .create_router();
+
+        let request = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let users = "/tenants/00000000-0000-0000-0000-000000000000/users";
+        let response = app
+            .clone()
+            .oneshot(request(&format!("/api{}", users)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // SCIM requests are rejected unless they name their tenant
+        let response = app
+            .clone()
+            .oneshot(request("/api/scim/v2/Users"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        // So are SSO requests
+        let response = app
+            .clone()
+            .oneshot(request("/api/sso/presets"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Module routes are only served below the API prefix
+        let response = app.clone().oneshot(request(users)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app.oneshot(request("/health")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
     #[tokio::test]
     async fn test_init() {
         let config = DatabaseConfig {
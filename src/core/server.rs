@@ -1,19 +1,61 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, time::{Duration, SystemTime}};
 use axum::{
     Router,
     routing::get,
     response::IntoResponse,
     http::{StatusCode, Method, HeaderName, HeaderValue},
 };
-use tower_http::cors::CorsLayer;
-use tracing::info;
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+use tokio::task::JoinHandle;
+use tower_http::{cors::CorsLayer, trace::TraceLayer};
+use tracing::{info, warn};
 
-use crate::core::config::ServerConfig;
+use crate::core::{
+    config::{AppModule, ModulesConfig, ServerConfig, TlsConfig},
+    health::{self, HealthState},
+};
+
+/// Routers of the modules served below the API prefix
+#[derive(Debug, Default)]
+pub struct ModuleRegistry {
+    routers: Vec<(Option<AppModule>, Router)>,
+    /// Routers served at the root next to the health routes
+    root: Vec<Router>,
+}
+
+impl ModuleRegistry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the router of a module that is always served
+    pub fn register(mut self, router: Router) -> Self {
+        self.routers.push((None, router));
+        self
+    }
+
+    /// Registers the router of an optional module, served only while it is enabled
+    pub fn register_optional(mut self, module: AppModule, router: Router) -> Self {
+        self.routers.push((Some(module), router));
+        self
+    }
+
+    /// Registers a router served at the root instead of below the API prefix,
+    /// e.g. for documents at well-known locations
+    pub fn register_root(mut self, router: Router) -> Self {
+        self.root.push(router);
+        self
+    }
+}
 
 /// Server instance
 #[derive(Debug)]
 pub struct Server {
     config: ServerConfig,
+    modules: ModulesConfig,
+    registry: ModuleRegistry,
+    health: HealthState,
 }
 
 impl Server {
@@ -21,16 +63,63 @@ impl Server {
     pub async fn new(config: &ServerConfig) -> crate::shared::error::Result<Self> {
         Ok(Self {
             config: config.clone(),
+            modules: ModulesConfig::default(),
+            registry: ModuleRegistry::default(),
+            health: HealthState::default(),
         })
     }
 
-    /// Creates the router with all routes
+    /// Sets which optional modules are enabled
+    pub fn with_modules(mut self, modules: ModulesConfig) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    /// Sets the module routers to serve
+    pub fn with_module_registry(mut self, registry: ModuleRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Sets the probe state reported to Kubernetes and drained on shutdown
+    pub fn with_health(mut self, health: HealthState) -> Self {
+        self.health = health;
+        self
+    }
+
+    /// Gets the probe state
+    pub fn health(&self) -> &HealthState {
+        &self.health
+    }
+
+    /// Gets the module enablement configuration
+    pub fn modules(&self) -> &ModulesConfig {
+        &self.modules
+    }
+
+    /// Merges the router of an optional module if the module is enabled
+    pub fn mount_module(
+        &self,
+        router: Router,
+        module: AppModule,
+        module_router: Router,
+    ) -> Router {
+        if self.modules.is_enabled(module) {
+            router.merge(module_router)
+        } else {
+            info!("Module {} is disabled, skipping its routes", module);
+            router
+        }
+    }
+
+    /// Creates the router with the health routes and all module routes below the API prefix
     pub fn create_router(&self) -> Router {
         // Convert allowed methods to Method enum
         let methods = [
             Method::GET,
             Method::POST,
             Method::PUT,
+            Method::PATCH,
             Method::DELETE,
         ];
 
@@ -46,8 +135,28 @@ impl Server {
             .filter_map(|origin| HeaderValue::from_str(origin).ok())
             .collect();
 
-        Router::new()
+        let api = self.registry.routers
+            .iter()
+            .cloned()
+            .fold(Router::new(), |api, (module, router)| match module {
+                Some(module) => self.mount_module(api, module, router),
+                None => api.merge(router),
+            });
+
+        let router = self.registry.root
+            .iter()
+            .cloned()
+            .fold(Router::new(), Router::merge)
             .route("/health", get(health_check))
+            .merge(health::router(self.health.clone()));
+        // Nesting at the root is not supported, an empty prefix serves the APIs there
+        let router = match self.config.api_prefix.trim_end_matches('/') {
+            "" => router.merge(api),
+            prefix => router.nest(prefix, api),
+        };
+
+        router
+            .layer(TraceLayer::new_for_http())
             .layer(
                 CorsLayer::new()
                     .allow_origin(origins)
@@ -61,12 +170,49 @@ impl Server {
         let app = self.create_router();
 
         let addr = SocketAddr::from(([127, 0, 0, 1], self.config.port));
+        if let Some(tls) = &self.config.tls {
+            return self.run_tls(app, addr, tls).await;
+        }
         info!("Server listening on {}", addr);
 
         let listener = tokio::net::TcpListener::bind(&addr).await
             .map_err(|e| crate::shared::error::Error::Internal(format!("Failed to bind server: {}", e)))?;
 
+        // Stops accepting connections once drained and finishes in-flight requests
         axum::serve(listener, app)
+            .with_graceful_shutdown(self.health.clone().shutdown_signal())
+            .await
+            .map_err(|e| crate::shared::error::Error::Internal(format!("Server error: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Runs the server terminating TLS with the configured certificate
+    async fn run_tls(
+        &self,
+        app: Router,
+        addr: SocketAddr,
+        tls: &TlsConfig,
+    ) -> crate::shared::error::Result<()> {
+        let rustls = RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path).await
+            .map_err(|e| crate::shared::error::Error::Internal(format!("Failed to load TLS certificate: {}", e)))?;
+        if let Some(interval) = tls.reload_interval_secs {
+            spawn_certificate_reloader(rustls.clone(), tls.clone(), Duration::from_secs(interval));
+        }
+        info!("Server listening on {} with TLS", addr);
+
+        // Stops accepting connections once drained and finishes in-flight requests
+        let handle = Handle::new();
+        let shutdown = handle.clone();
+        let signal = self.health.clone().shutdown_signal();
+        tokio::spawn(async move {
+            signal.await;
+            shutdown.graceful_shutdown(None);
+        });
+
+        axum_server::bind_rustls(addr, rustls)
+            .handle(handle)
+            .serve(app.into_make_service())
             .await
             .map_err(|e| crate::shared::error::Error::Internal(format!("Server error: {}", e)))?;
 
@@ -74,6 +220,43 @@ impl Server {
     }
 }
 
+/// Reloads the certificate whenever its files change, e.g. after a renewal
+///
+/// A failed reload keeps the previous certificate in use and is retried on the next check.
+fn spawn_certificate_reloader(
+    rustls: RustlsConfig,
+    tls: TlsConfig,
+    interval: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut loaded = modified_at(&tls).await;
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick completes immediately, right after the certificate was loaded
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let modified = modified_at(&tls).await;
+            if modified.is_none() || modified == loaded {
+                continue;
+            }
+            match rustls.reload_from_pem_file(&tls.cert_path, &tls.key_path).await {
+                Ok(()) => {
+                    info!("Reloaded TLS certificate from {}", tls.cert_path);
+                    loaded = modified;
+                },
+                Err(e) => warn!("Failed to reload TLS certificate: {}", e),
+            }
+        }
+    })
+}
+
+/// Gets when the certificate and key files were last modified
+async fn modified_at(tls: &TlsConfig) -> Option<(SystemTime, SystemTime)> {
+    let cert = tokio::fs::metadata(&tls.cert_path).await.ok()?.modified().ok()?;
+    let key = tokio::fs::metadata(&tls.key_path).await.ok()?.modified().ok()?;
+    Some((cert, key))
+}
+
 /// Health check handler
 async fn health_check() -> impl IntoResponse {
     StatusCode::OK
@@ -92,6 +275,8 @@ mod tests {
             host: "127.0.0.1".to_string(),
             port: 3000,
             cors_allowed_origins: vec!["http://localhost:3000".to_string()],
+            api_prefix: "/api".to_string(),
+            tls: None,
         };
 
         let server = Server::new(&config).await.unwrap();
@@ -110,12 +295,117 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_disabled_module_routes() {
+        let config = ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 3000,
+            cors_allowed_origins: vec!["http://localhost:3000".to_string()],
+            api_prefix: "/api".to_string(),
+            tls: None,
+        };
+        let modules = ModulesConfig {
+            scim: false,
+            ..ModulesConfig::default()
+        };
+
+        let server = Server::new(&config).await.unwrap().with_modules(modules);
+        let app = server.mount_module(
+            server.create_router(),
+            AppModule::Scim,
+            Router::new().route("/scim/v2/Users", get(health_check)),
+        );
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/scim/v2/Users")
+                    .body(Body::empty())
+                    .unwrap()
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_module_registry() {
+        let mut config = ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 3000,
+            cors_allowed_origins: vec!["http://localhost:3000".to_string()],
+            api_prefix: "/api".to_string(),
+            tls: None,
+        };
+        let modules = ModulesConfig {
+            scim: false,
+            ..ModulesConfig::default()
+        };
+        let registry = || ModuleRegistry::new()
+            .register(Router::new().route("/tenants", get(health_check)))
+            .register_root(Router::new().route("/.well-known/jwks.json", get(health_check)))
+            .register_optional(
+                AppModule::Scim,
+                Router::new().route("/scim/v2/Users", get(health_check)),
+            );
+        let status = |app: Router, uri: &'static str| async move {
+            app.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap()
+                .status()
+        };
+
+        let app = Server::new(&config).await.unwrap()
+            .with_modules(modules.clone())
+            .with_module_registry(registry())
+            .create_router();
+        assert_eq!(status(app.clone(), "/api/tenants").await, StatusCode::OK);
+        assert_eq!(status(app.clone(), "/tenants").await, StatusCode::NOT_FOUND);
+        assert_eq!(status(app.clone(), "/api/scim/v2/Users").await, StatusCode::NOT_FOUND);
+        assert_eq!(status(app.clone(), "/.well-known/jwks.json").await, StatusCode::OK);
+        assert_eq!(status(app, "/health").await, StatusCode::OK);
+
+        // Without a prefix the module routes are served at the root
+        config.api_prefix = String::new();
+        let app = Server::new(&config).await.unwrap()
+            .with_modules(modules)
+            .with_module_registry(registry())
+            .create_router();
+        assert_eq!(status(app.clone(), "/tenants").await, StatusCode::OK);
+        assert_eq!(status(app, "/health").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_missing_tls_certificate() {
+        let config = ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            cors_allowed_origins: Vec::new(),
+            api_prefix: "/api".to_string(),
+            tls: Some(TlsConfig {
+                cert_path: "/nonexistent/cert.pem".to_string(),
+                key_path: "/nonexistent/key.pem".to_string(),
+                reload_interval_secs: None,
+            }),
+        };
+
+        // The server refuses to start instead of falling back to plain HTTP
+        let server = Server::new(&config).await.unwrap();
+        assert!(matches!(
+            server.run().await,
+            Err(crate::shared::error::Error::Internal(_))
+        ));
+    }
+
     #[tokio::test]
     async fn test_cors() {
         let config = ServerConfig {
             host: "127.0.0.1".to_string(),
             port: 3000,
             cors_allowed_origins: vec!["http://localhost:3000".to_string()],
+            api_prefix: "/api".to_string(),
+            tls: None,
         };
 
         let server = Server::new(&config).await.unwrap();
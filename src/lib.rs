@@ -1,3 +1,5 @@
+#[cfg(feature = "client")]
+pub mod client;
 pub mod core;
 pub mod modules;
 pub mod shared;
@@ -7,7 +7,7 @@ use tracing_subscriber::{
     Registry,
 };
 
-use crate::core::{config::ServerConfig, server::Server};
+use crate::core::{config::Config, doctor, Core};
 
 mod core;
 mod modules;
@@ -35,12 +35,22 @@ async fn main() -> anyhow::Result<()> {
         );
     }
 
+    // `doctor` checks the configured services instead of starting the server
+    if env::args().nth(1).as_deref() == Some("doctor") {
+        let report = doctor::run(&Config::default_dev()).await;
+        println!("{}", report);
+        if !report.passed() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     // Load configuration
-    let config = ServerConfig::default_dev();
+    let config = Config::default_dev();
 
-    // Create and run server
-    let server = Server::new(&config).await?;
-    server.run().await?;
+    // Create and run the server with all module routes, applying the migrations meanwhile
+    let core = Core::new(config).await?;
+    core.run().await?;
 
     Ok(())
 }
@@ -0,0 +1,286 @@
+use rand::{distributions::Alphanumeric, Rng};
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    modules::{identity::models::TokenScope, signup::models::hash_token},
+    shared::{
+        error::{Error, Result},
+        quota::ApiKeyQuota,
+        types::{TenantId, UserId},
+    },
+};
+
+/// Prefix telling API keys apart from session tokens
+pub const API_KEY_PREFIX: &str = "acci_";
+
+/// Characters of a key shown in listings to recognize it by
+const DISPLAY_PREFIX_LENGTH: usize = 12;
+
+/// How long the recorded last use of a key may lag behind, sparing a write per request
+const LAST_USED_PRECISION_SECS: f64 = 60.0;
+
+/// API key an integration calls the APIs of its tenant with
+///
+/// Requests with a key act as the admin who created it, limited to the key's
+/// scopes; only the hash of a key is stored.
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub tenant_id: TenantId,
+    pub created_by: UserId,
+    pub name: String,
+    /// Start of the key, shown to recognize it by
+    pub key_prefix: String,
+    pub key_hash: String,
+    pub scopes: Vec<TokenScope>,
+    /// Daily request limits of the key
+    pub quota: ApiKeyQuota,
+    pub created_at: OffsetDateTime,
+    /// When the key expires, `None` for keys valid until revoked
+    pub expires_at: Option<OffsetDateTime>,
+    pub last_used_at: Option<OffsetDateTime>,
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+impl ApiKey {
+    /// Creates a key, returning it with its plain value
+    pub fn new(
+        tenant_id: TenantId,
+        created_by: UserId,
+        name: String,
+        scopes: Vec<TokenScope>,
+        quota: ApiKeyQuota,
+        expires_in: Option<Duration>,
+    ) -> (Self, String) {
+        let secret: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(40)
+            .map(char::from)
+            .collect();
+        let key = format!("{}{}", API_KEY_PREFIX, secret);
+        let now = OffsetDateTime::now_utc();
+        let api_key = Self {
+            id: Uuid::new_v4(),
+            tenant_id,
+            created_by,
+            name,
+            key_prefix: key[..DISPLAY_PREFIX_LENGTH].to_string(),
+            key_hash: hash_token(&key),
+            scopes,
+            quota,
+            created_at: now,
+            expires_at: expires_in.map(|expires_in| now + expires_in),
+            last_used_at: None,
+            revoked_at: None,
+        };
+        (api_key, key)
+    }
+
+    /// Checks if the key is neither revoked nor expired
+    pub fn is_valid(&self) -> bool {
+        self.revoked_at.is_none()
+            && self
+                .expires_at
+                .is_none_or(|expires_at| expires_at > OffsetDateTime::now_utc())
+    }
+}
+
+/// Checks if a bearer token is an API key rather than a session token
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(API_KEY_PREFIX)
+}
+
+/// Converts the quota of a key to its stored JSON
+fn to_quota(quota: &ApiKeyQuota) -> Result<serde_json::Value> {
+    serde_json::to_value(quota)
+        .map_err(|e| Error::Internal(format!("Failed to serialize API key quota: {}", e)))
+}
+
+/// Reads the stored quota of a key
+fn from_quota(value: serde_json::Value) -> Result<ApiKeyQuota> {
+    serde_json::from_value(value)
+        .map_err(|e| Error::Internal(format!("Invalid stored API key quota: {}", e)))
+}
+
+/// API key repository
+#[derive(Debug, Clone)]
+pub struct ApiKeyRepository {
+    pool: PgPool,
+}
+
+impl ApiKeyRepository {
+    /// Creates a new ApiKeyRepository instance
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Stores an API key
+    pub async fn create(&self, key: &ApiKey) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO api_keys (
+                id, tenant_id, created_by, name, key_prefix, key_hash, scope, quota,
+                created_at, expires_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+            key.id,
+            key.tenant_id.0 as Uuid,
+            key.created_by.0 as Uuid,
+            key.name,
+            key.key_prefix,
+            key.key_hash,
+            TokenScope::format_list(&key.scopes),
+            to_quota(&key.quota)?,
+            key.created_at,
+            key.expires_at,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Gets an API key by the hash of its value
+    pub async fn get_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>> {
+        let result = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, created_by, name, key_prefix, key_hash, scope, quota,
+                created_at, expires_at, last_used_at, revoked_at
+            FROM api_keys
+            WHERE key_hash = $1
+            "#,
+            key_hash,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        result
+            .map(|r| {
+                Ok(ApiKey {
+                    id: r.id,
+                    tenant_id: TenantId(r.tenant_id),
+                    created_by: UserId(r.created_by),
+                    name: r.name,
+                    key_prefix: r.key_prefix,
+                    key_hash: r.key_hash,
+                    scopes: TokenScope::parse_list(&r.scope)?,
+                    quota: from_quota(r.quota)?,
+                    created_at: r.created_at,
+                    expires_at: r.expires_at,
+                    last_used_at: r.last_used_at,
+                    revoked_at: r.revoked_at,
+                })
+            })
+            .transpose()
+    }
+
+    /// Lists the unrevoked API keys of a tenant, newest first
+    pub async fn list(&self, tenant_id: TenantId) -> Result<Vec<ApiKey>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, created_by, name, key_prefix, key_hash, scope, quota,
+                created_at, expires_at, last_used_at, revoked_at
+            FROM api_keys
+            WHERE tenant_id = $1 AND revoked_at IS NULL
+            ORDER BY created_at DESC
+            "#,
+            tenant_id.0 as Uuid,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|r| {
+                Ok(ApiKey {
+                    id: r.id,
+                    tenant_id: TenantId(r.tenant_id),
+                    created_by: UserId(r.created_by),
+                    name: r.name,
+                    key_prefix: r.key_prefix,
+                    key_hash: r.key_hash,
+                    scopes: TokenScope::parse_list(&r.scope)?,
+                    quota: from_quota(r.quota)?,
+                    created_at: r.created_at,
+                    expires_at: r.expires_at,
+                    last_used_at: r.last_used_at,
+                    revoked_at: r.revoked_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Revokes an API key of a tenant, returning false if there was no such unrevoked key
+    pub async fn revoke(&self, tenant_id: TenantId, id: Uuid) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE api_keys SET revoked_at = NOW()
+            WHERE tenant_id = $1 AND id = $2 AND revoked_at IS NULL
+            "#,
+            tenant_id.0 as Uuid,
+            id,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Records that a key was used, unless its recorded last use is recent enough
+    pub async fn touch(&self, id: Uuid) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE api_keys SET last_used_at = NOW()
+            WHERE id = $1
+                AND (last_used_at IS NULL OR last_used_at < NOW() - make_interval(secs => $2))
+            "#,
+            id,
+            LAST_USED_PRECISION_SECS,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::identity::models::PermissionAction;
+
+    #[test]
+    fn test_api_key() {
+        let scopes = vec![TokenScope::new(PermissionAction::List, "users")];
+        let (api_key, key) = ApiKey::new(
+            TenantId::new(),
+            UserId::new(),
+            "CRM sync".to_string(),
+            scopes.clone(),
+            ApiKeyQuota::default(),
+            None,
+        );
+        assert!(is_api_key(&key));
+        assert!(key.starts_with(&api_key.key_prefix));
+        assert_eq!(api_key.key_hash, hash_token(&key));
+        assert_eq!(api_key.scopes, scopes);
+        assert!(api_key.is_valid());
+
+        // Session tokens are plain alphanumeric strings
+        assert!(!is_api_key("AbCdEf123456"));
+
+        let (expired, other_key) = ApiKey::new(
+            api_key.tenant_id,
+            api_key.created_by,
+            "CRM sync".to_string(),
+            scopes,
+            ApiKeyQuota::default(),
+            Some(Duration::ZERO),
+        );
+        assert_ne!(other_key, key);
+        assert!(!expired.is_valid());
+
+        let mut revoked = api_key;
+        revoked.revoked_at = Some(OffsetDateTime::now_utc());
+        assert!(!revoked.is_valid());
+    }
+}
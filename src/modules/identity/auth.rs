@@ -2,33 +2,77 @@ use argon2::{
     password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
 };
+use once_cell::sync::Lazy;
+use rand::{distributions::Alphanumeric, Rng};
 use rand_core::OsRng;
-use std::collections::HashMap;
-use std::sync::Mutex;
-use std::time::Duration;
+use std::future::Future;
+use std::sync::Arc;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
 use super::{
+    api_key::{is_api_key, ApiKey, ApiKeyRepository},
+    auth_policy::{AuthPolicy, MfaRequirement, PasswordPolicy, SessionLimit},
+    hooks::{AuthHook, LoginContext, RegistrationHook},
+    jwt_keys::JwtKeyRing,
     mfa::MfaService,
-    models::{Credentials, Role, RoleType, User},
+    models::{Credentials, Membership, TokenScope, User, UserEmail},
+    rbac::{ensure_scopes_held, is_tenant_admin},
+    refresh_token::{hash_refresh_token, RefreshToken, RefreshTokenRepository},
     repository::UserRepository,
-    session::{Session, SessionStore},
+    service::record_audit_event,
+    session::{Claims, Session, SessionLifetime, SessionStore, SSO_SESSION_ATTRIBUTE},
+    throttle::{LockoutPolicy, LoginThrottle},
 };
 use crate::{
-    modules::tenant::models::Tenant,
+    core::config::{SessionLimitStrategy, SessionTokenMode},
+    modules::{
+        signup::models::hash_token,
+        tenant::{
+            hooks::{TenantEvent, TenantHook},
+            models::Tenant,
+            service::TenantService,
+        },
+    },
     shared::{
+        audit::{AuditCategory, AuditEvent, AuditStream},
         error::{Error, Result},
+        quota::{ApiKeyQuota, QuotaTracker, QuotaUsage},
         types::{TenantId, UserId},
     },
 };
 
+/// Keys and claims session tokens are signed with
+#[derive(Debug, Clone)]
+struct TokenSigner {
+    keys: JwtKeyRing,
+    issuer: String,
+    audience: String,
+}
+
 /// Authentication service for handling user authentication
 #[derive(Debug)]
 pub struct AuthenticationService {
     repository: UserRepository,
     session_store: Box<dyn SessionStore>,
     mfa_service: MfaService,
+    hooks: Vec<Arc<dyn AuthHook>>,
+    registration_hooks: Vec<Arc<dyn RegistrationHook>>,
+    audit: AuditStream,
+    throttle: Option<LoginThrottle>,
+    /// Global authentication policy, which tenants can override
+    auth_policy: AuthPolicy,
+    /// Tenant service whose settings hold the tenants' policy overrides
+    tenant_policies: Option<TenantService>,
+    refresh_tokens: Option<RefreshTokenRepository>,
+    refresh_token_lifetime: time::Duration,
+    api_keys: Option<ApiKeyRepository>,
+    /// Counts the requests of API keys against their quotas
+    quotas: Option<QuotaTracker>,
+    /// Whether session tokens are JWTs or opaque random strings
+    token_mode: SessionTokenMode,
+    /// Signs session tokens as JWTs; tokens are random strings without it
+    token_signer: Option<TokenSigner>,
 }
 
 impl AuthenticationService {
@@ -38,13 +82,150 @@ impl AuthenticationService {
             repository,
             session_store,
             mfa_service: MfaService::new(Default::default()),
+            hooks: Vec::new(),
+            registration_hooks: Vec::new(),
+            audit: AuditStream::new(),
+            throttle: None,
+            auth_policy: AuthPolicy::default(),
+            tenant_policies: None,
+            refresh_tokens: None,
+            refresh_token_lifetime: time::Duration::days(30),
+            api_keys: None,
+            quotas: None,
+            token_mode: SessionTokenMode::Jwt,
+            token_signer: None,
+        }
+    }
+
+    /// Streams login and account events to the given stream, e.g. one shared with the identity module
+    pub fn with_audit_stream(mut self, audit: AuditStream) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Throttles logins per user, rejecting them with retry information once limits apply
+    ///
+    /// The throttle's lockout thresholds become the global ones tenants can override.
+    pub fn with_login_throttle(mut self, throttle: LoginThrottle) -> Self {
+        self.auth_policy.lockout = throttle.lockout_policy();
+        self.throttle = Some(throttle);
+        self
+    }
+
+    /// Sets how long login sessions live and whether they expire when idle
+    pub fn with_session_lifetime(mut self, lifetime: SessionLifetime) -> Self {
+        self.auth_policy.session_lifetime = lifetime;
+        self
+    }
+
+    /// Limits the concurrent login sessions of users of tenants without their own limit
+    pub fn with_session_limit(mut self, limit: Option<SessionLimit>) -> Self {
+        self.auth_policy.session_limit = limit;
+        self
+    }
+
+    /// Sets the requirements of passwords users register with
+    pub fn with_password_policy(mut self, policy: PasswordPolicy) -> Self {
+        self.auth_policy.password = policy;
+        self
+    }
+
+    /// Sets whether users have to log in with a second factor
+    pub fn with_mfa_requirement(mut self, mfa: MfaRequirement) -> Self {
+        self.auth_policy.mfa = mfa;
+        self
+    }
+
+    /// Lets tenants override the global authentication policy with their `auth_policy` setting
+    pub fn with_tenant_policies(mut self, tenants: TenantService) -> Self {
+        self.tenant_policies = Some(tenants);
+        self
+    }
+
+    /// Gets the authentication policy in effect for a tenant
+    pub async fn auth_policy(&self, tenant_id: TenantId) -> Result<AuthPolicy> {
+        match &self.tenant_policies {
+            Some(tenants) => self.auth_policy.for_tenant(tenants, tenant_id).await,
+            None => Ok(self.auth_policy),
+        }
+    }
+
+    /// Enables rotating refresh tokens that stay valid for `expires_in` unless used
+    pub fn with_refresh_tokens(
+        mut self,
+        repository: RefreshTokenRepository,
+        expires_in: time::Duration,
+    ) -> Self {
+        self.refresh_tokens = Some(repository);
+        self.refresh_token_lifetime = expires_in;
+        self
+    }
+
+    /// Enables API keys tenant admins mint for their integrations
+    pub fn with_api_keys(mut self, repository: ApiKeyRepository) -> Self {
+        self.api_keys = Some(repository);
+        self
+    }
+
+    /// Enforces the request quotas of API keys with the given tracker
+    pub fn with_quota_tracker(mut self, quotas: QuotaTracker) -> Self {
+        self.quotas = Some(quotas);
+        self
+    }
+
+    /// Issues session tokens as JWTs signed with the newest key of the ring
+    ///
+    /// Sessions stay the source of truth, so revoked tokens are rejected even
+    /// though their signature is still valid.
+    pub fn with_token_keys(mut self, keys: JwtKeyRing, issuer: String, audience: String) -> Self {
+        self.token_signer = Some(TokenSigner {
+            keys,
+            issuer,
+            audience,
+        });
+        self
+    }
+
+    /// Issues opaque tokens instead of JWTs, e.g. so that tokens carry no claims
+    ///
+    /// Opaque tokens are only valid while their session is stored; signing keys are ignored.
+    pub fn with_token_mode(mut self, token_mode: SessionTokenMode) -> Self {
+        self.token_mode = token_mode;
+        self
+    }
+
+    /// Gets the keys session tokens are signed with, e.g. to publish their JWKS
+    pub fn token_keys(&self) -> Option<&JwtKeyRing> {
+        self.signer().map(|signer| &signer.keys)
+    }
+
+    /// Gets the signer of session tokens, unless tokens are opaque or no keys are set
+    fn signer(&self) -> Option<&TokenSigner> {
+        match self.token_mode {
+            SessionTokenMode::Jwt => self.token_signer.as_ref(),
+            SessionTokenMode::Opaque => None,
         }
     }
 
-    /// Registers a new user
+    /// Registers a hook that runs on every login
+    pub fn register_hook(&mut self, hook: Arc<dyn AuthHook>) {
+        self.hooks.push(hook);
+    }
+
+    /// Registers a hook that runs on every user registration
+    pub fn register_registration_hook(&mut self, hook: Arc<dyn RegistrationHook>) {
+        self.registration_hooks.push(hook);
+    }
+
+    /// Registers a new user with a valid email address and a password meeting the policy of their tenant
     pub async fn register_user(&self, credentials: Credentials) -> Result<User> {
+        UserEmail::validate(&credentials.email)?;
+        self.auth_policy(credentials.tenant_id)
+            .await?
+            .password
+            .validate(&credentials.password)?;
         let password_hash = Self::hash_password(&credentials.password)?;
-        let user = User {
+        let mut user = User {
             id: UserId::new(),
             tenant_id: credentials.tenant_id,
             email: credentials.email,
@@ -56,16 +237,95 @@ impl AuthenticationService {
             updated_at: OffsetDateTime::now_utc(),
             mfa_enabled: false,
             mfa_secret: None,
+            token_scopes: None,
         };
 
-        self.repository.create_user(user).await
+        for hook in &self.registration_hooks {
+            hook.pre_register(&mut user).await?;
+        }
+
+        let user = self.repository.create_user(user).await?;
+
+        for hook in &self.registration_hooks {
+            hook.post_register(&user).await?;
+        }
+
+        Ok(user)
     }
 
     /// Authenticates a user with credentials
     pub async fn authenticate(&self, credentials: Credentials) -> Result<Session> {
+        self.authenticate_with_context(credentials, LoginContext::default())
+            .await
+    }
+
+    /// Authenticates a user with credentials and request information for hooks
+    pub async fn authenticate_with_context(
+        &self,
+        credentials: Credentials,
+        context: LoginContext,
+    ) -> Result<Session> {
+        let tenant_id = credentials.tenant_id;
+        let email = credentials.email.clone();
+        let result = match self.auth_policy(tenant_id).await {
+            Ok(policy) => {
+                self.throttled(
+                    tenant_id,
+                    &email,
+                    &policy.lockout,
+                    self.login(credentials, context, &policy),
+                )
+                .await
+            },
+            Err(e) => Err(e),
+        };
+        self.audit_login(tenant_id, &email, &result).await;
+        result
+    }
+
+    /// Runs a login within the limits of the login throttle, if one is set
+    ///
+    /// Rejected credentials count as failures against the tenant's lockout policy;
+    /// the failure locking the user out is returned as lockout so clients learn when to retry.
+    async fn throttled(
+        &self,
+        tenant_id: TenantId,
+        email: &str,
+        lockout: &LockoutPolicy,
+        login: impl Future<Output = Result<Session>>,
+    ) -> Result<Session> {
+        let Some(throttle) = &self.throttle else {
+            return login.await;
+        };
+        let key = LoginThrottle::key(tenant_id, email);
+        throttle.check_with(&key, lockout)?;
+
+        match login.await {
+            Ok(session) => {
+                throttle.record_success(&key);
+                Ok(session)
+            },
+            Err(Error::Authentication(reason)) => Err(throttle
+                .record_failure_with(&key, lockout)
+                .unwrap_or(Error::Authentication(reason))),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Verifies credentials and stores a new session
+    ///
+    /// Users without MFA cannot log in with their password if the tenant's policy requires MFA.
+    async fn login(
+        &self,
+        credentials: Credentials,
+        context: LoginContext,
+        policy: &AuthPolicy,
+    ) -> Result<Session> {
+        self.run_pre_login(&credentials.email, credentials.tenant_id, &context)
+            .await?;
+
         let user = self
-            .repository
-            .get_user_by_email(&credentials.email, credentials.tenant_id)
+            .find_login_user(&credentials.email, credentials.tenant_id)
             .await?
             .ok_or_else(|| Error::Authentication("Invalid credentials".to_string()))?;
 
@@ -73,45 +333,411 @@ impl AuthenticationService {
             return Err(Error::Authentication("Invalid credentials".to_string()));
         }
 
+        if !user.active {
+            return Err(Error::Authentication("User account is deactivated".to_string()));
+        }
+        self.repository.ensure_tenant_active(user.tenant_id).await?;
+
+        if !user.mfa_enabled && policy.mfa == MfaRequirement::Required {
+            return Err(Error::Authorization(
+                "The tenant requires MFA, which is not set up for this user".to_string(),
+            ));
+        }
+
         // Verify MFA if enabled
         if user.mfa_enabled {
-            let mfa_code = credentials
-                .mfa_code
-                .ok_or_else(|| Error::Authentication("MFA code required".to_string()))?;
-
-            if !self.mfa_service.verify_code(
-                user.mfa_secret
-                    .as_ref()
-                    .ok_or_else(|| Error::Internal("MFA secret not found".to_string()))?,
-                &mfa_code,
-            )? {
-                return Err(Error::Authentication("Invalid MFA code".to_string()));
-            }
+            self.verify_mfa(&user, credentials.mfa_code.as_deref())?;
         }
 
+        self.make_room_for_session(user.id, user.tenant_id, policy, None)
+            .await?;
         self.repository.update_last_login(user.id).await?;
 
-        let session = Session::new(
+        let mut session = Session::new(
             user.id,
             user.tenant_id,
-            "".to_string(),
-            time::Duration::hours(1),
+            self.issue_token(user.id, user.tenant_id, None, policy.session_lifetime.max)
+                .await?,
+            policy.session_lifetime.initial(),
+        );
+        session.ip_address = context.ip_address.map(|ip| ip.to_string());
+        session.user_agent = context.user_agent.clone();
+        session.device_name = context.device_name.clone();
+
+        self.run_post_login(&user, &context, &mut session).await?;
+        self.session_store.store_session(&session).await?;
+
+        Ok(session)
+    }
+
+    /// Opens a session for the user an SSO login was resolved to
+    ///
+    /// The identity provider already verified the user, so neither password nor
+    /// MFA code are checked; post-login hooks run as for password logins.
+    pub async fn authenticate_sso(
+        &self,
+        tenant_id: TenantId,
+        user_id: UserId,
+        sso_session_id: Option<Uuid>,
+        context: LoginContext,
+    ) -> Result<Session> {
+        let user = self
+            .repository
+            .get_user_by_id(user_id)
+            .await?
+            .filter(|user| user.tenant_id == tenant_id)
+            .ok_or_else(|| Error::Authentication("Invalid credentials".to_string()))?;
+        let result = self.sso_login(user, sso_session_id, context).await;
+        self.audit_login(tenant_id, &user_id.0.to_string(), &result)
+            .await;
+        result
+    }
+
+    /// Stores a new session for an active user verified by an identity provider
+    ///
+    /// The session is linked to the SSO session of the login, if the provider keeps one.
+    async fn sso_login(
+        &self,
+        user: User,
+        sso_session_id: Option<Uuid>,
+        context: LoginContext,
+    ) -> Result<Session> {
+        if !user.active {
+            return Err(Error::Authentication("User account is deactivated".to_string()));
+        }
+        self.repository.ensure_tenant_active(user.tenant_id).await?;
+        let policy = self.auth_policy(user.tenant_id).await?;
+        self.make_room_for_session(user.id, user.tenant_id, &policy, None)
+            .await?;
+        self.repository.update_last_login(user.id).await?;
+
+        let mut session = Session::new(
+            user.id,
+            user.tenant_id,
+            self.issue_token(user.id, user.tenant_id, None, policy.session_lifetime.max)
+                .await?,
+            policy.session_lifetime.initial(),
         );
+        session.ip_address = context.ip_address.map(|ip| ip.to_string());
+        session.user_agent = context.user_agent.clone();
+        session.device_name = context.device_name.clone();
+        if let Some(sso_session_id) = sso_session_id {
+            session.attributes.insert(
+                SSO_SESSION_ATTRIBUTE.to_string(),
+                sso_session_id.to_string(),
+            );
+        }
 
+        self.run_post_login(&user, &context, &mut session).await?;
         self.session_store.store_session(&session).await?;
+        Ok(session)
+    }
+
+    /// Verifies the code of a user's second factor
+    fn verify_mfa(&self, user: &User, mfa_code: Option<&str>) -> Result<()> {
+        let mfa_code =
+            mfa_code.ok_or_else(|| Error::Authentication("MFA code required".to_string()))?;
+        let mfa_secret = user
+            .mfa_secret
+            .as_ref()
+            .ok_or_else(|| Error::Internal("MFA secret not found".to_string()))?;
+        if !self.mfa_service.verify_code(mfa_secret, mfa_code)? {
+            return Err(Error::Authentication("Invalid MFA code".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Makes room for a new login session of a user within the tenant's session limit, or rejects it
+    ///
+    /// Only unexpired login sessions count, except the one the new session replaces;
+    /// scoped sessions of access tokens are neither counted nor evicted. Evicted
+    /// sessions are revoked with their refresh tokens.
+    async fn make_room_for_session(
+        &self,
+        user_id: UserId,
+        tenant_id: TenantId,
+        policy: &AuthPolicy,
+        replaces: Option<Uuid>,
+    ) -> Result<()> {
+        let Some(limit) = policy.session_limit else {
+            return Ok(());
+        };
+        let mut sessions: Vec<Session> = self
+            .session_store
+            .get_user_sessions(tenant_id, user_id)
+            .await?
+            .into_iter()
+            .filter(|session| {
+                session.scopes.is_none()
+                    && !session.is_expired()
+                    && Some(session.id) != replaces
+            })
+            .collect();
+        if sessions.len() < limit.max_sessions {
+            return Ok(());
+        }
+
+        match limit.strategy {
+            SessionLimitStrategy::Reject => Err(Error::Conflict(format!(
+                "Maximum of {} concurrent sessions reached",
+                limit.max_sessions
+            ))),
+            SessionLimitStrategy::EvictOldest => {
+                sessions.sort_by_key(|session| session.created_at);
+                sessions.truncate(sessions.len() + 1 - limit.max_sessions.max(1));
+                let session_ids: Vec<Uuid> = sessions.iter().map(|session| session.id).collect();
+                for session_id in &session_ids {
+                    self.session_store.remove_session(*session_id).await?;
+                }
+                if let Some(refresh_tokens) = &self.refresh_tokens {
+                    refresh_tokens.revoke_session_tokens(&session_ids).await?;
+                }
+                self.run_post_logout(&sessions).await;
+
+                let event = AuditEvent::new(
+                    tenant_id,
+                    AuditCategory::Security,
+                    "sessions_evicted",
+                    "users",
+                    user_id.0,
+                )
+                .with_user(user_id)
+                .with_detail("session_ids", session_ids)
+                .with_detail("max_sessions", limit.max_sessions);
+                record_audit_event(&self.repository, &self.audit, event).await;
+                Ok(())
+            },
+        }
+    }
+
+    /// Lists the tenants the identity of an email address can log in to with a password
+    ///
+    /// Only memberships the password is valid for are listed, so unknown addresses
+    /// and wrong passwords both get an empty list, which counts as a failed login of
+    /// the address and of the client address against the global lockout policy.
+    /// Memberships whose pre-login hooks reject the attempt or whose user is locked
+    /// out of the tenant are not checked.
+    pub async fn discover_memberships(
+        &self,
+        email: &str,
+        password: &str,
+        context: &LoginContext,
+    ) -> Result<Vec<Membership>> {
+        let Some(throttle) = &self.throttle else {
+            return self.verify_memberships(email, password, context).await;
+        };
+        let lockout = self.auth_policy.lockout;
+        let email_key = LoginThrottle::discovery_key(email);
+        let mut keys = vec![email_key.clone()];
+        keys.extend(context.ip_address.map(LoginThrottle::discovery_ip_key));
+        for key in &keys {
+            throttle.check_with(key, &lockout)?;
+        }
+
+        let memberships = self.verify_memberships(email, password, context).await?;
+        if !memberships.is_empty() {
+            throttle.record_success(&email_key);
+            return Ok(memberships);
+        }
+        let mut locked_out = None;
+        for key in &keys {
+            if let Some(e) = throttle.record_failure_with(key, &lockout) {
+                locked_out.get_or_insert(e);
+            }
+        }
+        match locked_out {
+            Some(e) => Err(e),
+            None => Ok(memberships),
+        }
+    }
+
+    /// Lists the memberships of an email address the password is valid for
+    ///
+    /// Verifies a dummy hash if no membership is checked, so unknown addresses take
+    /// as long as known ones.
+    async fn verify_memberships(
+        &self,
+        email: &str,
+        password: &str,
+        context: &LoginContext,
+    ) -> Result<Vec<Membership>> {
+        let mut memberships = Vec::new();
+        let mut checked = false;
+        for membership in self.repository.list_memberships_by_email(email).await? {
+            let locked_out = self.throttle.as_ref().is_some_and(|throttle| {
+                throttle.is_locked_out(&LoginThrottle::key(membership.tenant_id, email))
+            });
+            if locked_out
+                || self
+                    .run_pre_login(email, membership.tenant_id, context)
+                    .await
+                    .is_err()
+            {
+                continue;
+            }
+            let Some(user) = self.repository.get_user_by_id(membership.user_id).await? else {
+                continue;
+            };
+            checked = true;
+            if Self::verify_password(password, &user.password_hash).unwrap_or(false) {
+                memberships.push(membership);
+            }
+        }
+        if !checked {
+            let _ = Self::verify_password(password, &DUMMY_PASSWORD_HASH);
+        }
+        Ok(memberships)
+    }
+
+    /// Lists the tenants a user can switch to, including their own
+    pub async fn list_memberships(&self, user: &User) -> Result<Vec<Membership>> {
+        self.repository.list_memberships(user.id).await
+    }
+
+    /// Opens a session in another tenant the identity of a session's user is a member of
+    ///
+    /// Only unscoped sessions can switch. Pre-login hooks and the target tenant's
+    /// status and policy apply to the new session, which requires an MFA code if the
+    /// target membership has MFA set up. Switches count against the login throttle of
+    /// the target membership like logins, so its codes cannot be guessed endlessly.
+    pub async fn switch_tenant(
+        &self,
+        current: &Session,
+        tenant_id: TenantId,
+        mfa_code: Option<&str>,
+    ) -> Result<Session> {
+        if current.scopes.is_some() {
+            return Err(Error::Authorization(
+                "Scoped tokens cannot switch tenants".to_string(),
+            ));
+        }
+        let not_member = || Error::Authorization("Not a member of the tenant".to_string());
+        let membership = self
+            .repository
+            .list_memberships(current.user_id)
+            .await?
+            .into_iter()
+            .find(|membership| membership.tenant_id == tenant_id)
+            .ok_or_else(not_member)?;
+        let target = self
+            .repository
+            .get_user_by_id(membership.user_id)
+            .await?
+            .filter(|target| target.active && target.tenant_id == tenant_id)
+            .ok_or_else(not_member)?;
+
+        let policy = self.auth_policy(tenant_id).await?;
+        let session = self
+            .throttled(
+                tenant_id,
+                &target.email,
+                &policy.lockout,
+                self.switch_login(current, &target, mfa_code, &policy),
+            )
+            .await?;
+
+        let event = AuditEvent::new(
+            tenant_id,
+            AuditCategory::Security,
+            "tenant_switched",
+            "users",
+            target.id.0,
+        )
+        .with_user(target.id)
+        .with_detail("session_id", session.id)
+        .with_detail("from_tenant_id", current.tenant_id);
+        record_audit_event(&self.repository, &self.audit, event).await;
+        Ok(session)
+    }
+
+    /// Verifies the second factor of the membership switched to and stores its session
+    ///
+    /// Runs within the login throttle of the target membership, so rejected MFA codes
+    /// count as failed logins of it.
+    async fn switch_login(
+        &self,
+        current: &Session,
+        target: &User,
+        mfa_code: Option<&str>,
+        policy: &AuthPolicy,
+    ) -> Result<Session> {
+        let tenant_id = target.tenant_id;
+        let context = LoginContext {
+            ip_address: current.ip_address.as_deref().and_then(|ip| ip.parse().ok()),
+            user_agent: current.user_agent.clone(),
+            device_name: current.device_name.clone(),
+            ..LoginContext::default()
+        };
+        self.run_pre_login(&target.email, tenant_id, &context)
+            .await?;
+        self.repository.ensure_tenant_active(tenant_id).await?;
+
+        if !target.mfa_enabled && policy.mfa == MfaRequirement::Required {
+            return Err(Error::Authorization(
+                "The tenant requires MFA, which is not set up for this user".to_string(),
+            ));
+        }
+        if target.mfa_enabled {
+            self.verify_mfa(target, mfa_code)?;
+        }
+        self.make_room_for_session(target.id, tenant_id, policy, None)
+            .await?;
+        self.repository.update_last_login(target.id).await?;
+
+        let mut session = Session::new(
+            target.id,
+            tenant_id,
+            self.issue_token(target.id, tenant_id, None, policy.session_lifetime.max)
+                .await?,
+            policy.session_lifetime.initial(),
+        );
+        session.ip_address = current.ip_address.clone();
+        session.user_agent = current.user_agent.clone();
+        session.device_name = current.device_name.clone();
 
+        self.run_post_login(target, &context, &mut session).await?;
+        self.session_store.store_session(&session).await?;
         Ok(session)
     }
 
-    /// Authenticates a user with MFA
+    /// Authenticates a user with MFA and request information for hooks
     pub async fn authenticate_with_mfa(
         &self,
         credentials: Credentials,
         mfa_code: String,
+        context: LoginContext,
+    ) -> Result<Session> {
+        let tenant_id = credentials.tenant_id;
+        let email = credentials.email.clone();
+        let result = match self.auth_policy(tenant_id).await {
+            Ok(policy) => {
+                self.throttled(
+                    tenant_id,
+                    &email,
+                    &policy.lockout,
+                    self.login_with_mfa(credentials, mfa_code, context, &policy),
+                )
+                .await
+            },
+            Err(e) => Err(e),
+        };
+        self.audit_login(tenant_id, &email, &result).await;
+        result
+    }
+
+    /// Verifies credentials and an MFA code and stores a new session
+    async fn login_with_mfa(
+        &self,
+        credentials: Credentials,
+        mfa_code: String,
+        context: LoginContext,
+        policy: &AuthPolicy,
     ) -> Result<Session> {
+        self.run_pre_login(&credentials.email, credentials.tenant_id, &context)
+            .await?;
+
         let user = self
-            .repository
-            .get_user_by_email(&credentials.email, credentials.tenant_id)
+            .find_login_user(&credentials.email, credentials.tenant_id)
             .await?
             .ok_or_else(|| Error::Authentication("Invalid credentials".to_string()))?;
 
@@ -119,6 +745,11 @@ impl AuthenticationService {
             return Err(Error::Authentication("Invalid credentials".to_string()));
         }
 
+        if !user.active {
+            return Err(Error::Authentication("User account is deactivated".to_string()));
+        }
+        self.repository.ensure_tenant_active(user.tenant_id).await?;
+
         if !user.mfa_enabled {
             return Err(Error::Authentication(
                 "MFA not enabled for this user".to_string(),
@@ -134,118 +765,899 @@ impl AuthenticationService {
             return Err(Error::Authentication("Invalid MFA code".to_string()));
         }
 
+        self.make_room_for_session(user.id, user.tenant_id, policy, None)
+            .await?;
         self.repository.update_last_login(user.id).await?;
 
-        let session = Session::new(
+        let mut session = Session::new(
             user.id,
             user.tenant_id,
-            "".to_string(),
-            time::Duration::hours(1),
+            self.issue_token(user.id, user.tenant_id, None, policy.session_lifetime.max)
+                .await?,
+            policy.session_lifetime.initial(),
         );
+        session.ip_address = context.ip_address.map(|ip| ip.to_string());
+        session.user_agent = context.user_agent.clone();
+        session.device_name = context.device_name.clone();
 
+        self.run_post_login(&user, &context, &mut session).await?;
         self.session_store.store_session(&session).await?;
 
         Ok(session)
     }
 
-    /// Hashes a password using Argon2
-    pub fn hash_password(password: &str) -> Result<String> {
-        let salt = SaltString::generate(&mut OsRng);
-        let argon2 = Argon2::default();
-        let password_hash = argon2
-            .hash_password(password.as_bytes(), &salt)
-            .map_err(|e| Error::Internal(format!("Failed to hash password: {}", e)))?
-            .to_string();
-        Ok(password_hash)
+    /// Records the outcome of a login attempt as a security event
+    ///
+    /// Failures other than rejected or throttled logins, e.g. an unavailable database, are not recorded.
+    async fn audit_login(&self, tenant_id: TenantId, email: &str, result: &Result<Session>) {
+        let event = match result {
+            Ok(session) => AuditEvent::new(
+                tenant_id,
+                AuditCategory::Security,
+                "login_succeeded",
+                "users",
+                session.user_id.0,
+            )
+            .with_user(session.user_id)
+            .with_detail("session_id", session.id),
+            Err(Error::Authentication(reason)) => AuditEvent::new(
+                tenant_id,
+                AuditCategory::Security,
+                "login_failed",
+                "users",
+                email,
+            )
+            .with_detail("reason", reason),
+            Err(Error::Throttled(info) | Error::LockedOut(info)) => AuditEvent::new(
+                tenant_id,
+                AuditCategory::Security,
+                "login_throttled",
+                "users",
+                email,
+            )
+            .with_detail("reason", &info.message)
+            .with_detail("retry_after", info.retry_after),
+            Err(_) => return,
+        };
+        record_audit_event(&self.repository, &self.audit, event).await;
     }
 
-    /// Verifies a password against a hash
-    fn verify_password(password: &str, hash: &str) -> Result<bool> {
-        let parsed_hash = PasswordHash::new(hash)
-            .map_err(|e| Error::Internal(format!("Failed to parse password hash: {}", e)))?;
-        Ok(Argon2::default()
-            .verify_password(password.as_bytes(), &parsed_hash)
-            .is_ok())
+    /// Runs the pre-login hooks, stopping at the first rejection
+    async fn run_pre_login(
+        &self,
+        email: &str,
+        tenant_id: TenantId,
+        context: &LoginContext,
+    ) -> Result<()> {
+        for hook in &self.hooks {
+            hook.pre_login(email, tenant_id, context).await?;
+        }
+        Ok(())
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::core::database::tests::create_test_db;
-    use crate::modules::identity::mfa::{MfaConfig, MfaService};
-    use std::collections::HashMap;
-    use std::sync::Mutex;
 
-    #[derive(Debug, Default)]
-    struct MockSessionStore {
-        sessions: Mutex<HashMap<String, Session>>,
+    /// Runs the post-login hooks on a new session
+    async fn run_post_login(
+        &self,
+        user: &User,
+        context: &LoginContext,
+        session: &mut Session,
+    ) -> Result<()> {
+        for hook in &self.hooks {
+            hook.post_login(user, context, session).await?;
+        }
+        Ok(())
     }
 
-    #[async_trait::async_trait]
-    impl SessionStore for MockSessionStore {
-        async fn store_session(&self, session: &Session) -> Result<()> {
-            self.sessions
-                .lock()
-                .unwrap()
-                .insert(session.token.clone(), session.clone());
-            Ok(())
+    /// Runs the post-logout hooks on revoked sessions, logging their failures
+    async fn run_post_logout(&self, sessions: &[Session]) {
+        if sessions.is_empty() {
+            return;
         }
-
-        async fn get_session(&self, _id: Uuid) -> Result<Option<Session>> {
-            Ok(None)
+        for hook in &self.hooks {
+            if let Err(e) = hook.post_logout(sessions).await {
+                tracing::warn!("Post-logout hook failed: {}", e);
+            }
         }
+    }
 
-        async fn get_session_by_token(&self, token: &str) -> Result<Option<Session>> {
-            Ok(self.sessions.lock().unwrap().get(token).cloned())
+    /// Looks up a user by their primary email or any verified alias
+    async fn find_login_user(&self, email: &str, tenant_id: TenantId) -> Result<Option<User>> {
+        match self.repository.get_user_by_email(email, tenant_id).await? {
+            Some(user) => Ok(Some(user)),
+            None => {
+                self.repository
+                    .get_user_by_verified_email(email, tenant_id)
+                    .await
+            },
         }
+    }
 
-        async fn remove_session(&self, _id: Uuid) -> Result<()> {
-            Ok(())
+    /// Resolves the active user owning a session token or API key
+    pub async fn current_user(&self, token: &str) -> Result<User> {
+        if is_api_key(token) {
+            return self.resolve_api_key(token).await;
         }
+        Ok(self.resolve_token(token).await?.0)
+    }
 
-        async fn remove_user_sessions(&self, _user_id: UserId) -> Result<()> {
-            Ok(())
-        }
+    /// Resolves the session of a token whose user is active
+    pub async fn current_session(&self, token: &str) -> Result<Session> {
+        Ok(self.resolve_token(token).await?.1)
     }
 
-    #[tokio::test]
-    async fn test_authentication() {
-        let (db, _container) = create_test_db().await.unwrap();
-        let repository = UserRepository::new(db.get_pool());
-        let session_store = Box::new(MockSessionStore::default());
-        let service = AuthenticationService::new(repository, session_store);
+    /// Resolves the unexpired session of a token and its active user
+    async fn resolve_token(&self, token: &str) -> Result<(User, Session)> {
+        if token.is_empty() {
+            return Err(Error::Authentication("Invalid session".to_string()));
+        }
 
-        // Create test tenant
-        let tenant = Tenant::new(
-            "Test Tenant".to_string(),
-            format!("{}.example.com", Uuid::new_v4()),
-        );
+        let mut session = self
+            .session_store
+            .get_session_by_token(token)
+            .await?
+            .filter(|session| !session.is_expired())
+            .ok_or_else(|| Error::Authentication("Invalid session".to_string()))?;
+        self.ensure_token_current(token, &session).await?;
 
-        let mut retries = 3;
-        while retries > 0 {
-            match sqlx::query!(
-                r#"INSERT INTO tenants (id, name, domain, active) VALUES ($1, $2, $3, $4)"#,
-                tenant.id.0 as uuid::Uuid,
-                tenant.name,
-                tenant.domain,
-                tenant.active
-            )
-            .execute(&db.get_pool())
-            .await
-            {
-                Ok(_) => break,
-                Err(e) => {
-                    retries -= 1;
-                    if retries == 0 {
-                        panic!("Failed to create tenant: {}", e);
-                    }
-                    tokio::time::sleep(Duration::from_secs(1)).await;
-                },
+        let mut user = self
+            .repository
+            .get_user_by_id(session.user_id)
+            .await?
+            .filter(|user| user.active && user.tenant_id == session.tenant_id)
+            .ok_or_else(|| Error::Authentication("Invalid session".to_string()))?;
+        self.repository.ensure_tenant_active(user.tenant_id).await?;
+        user.token_scopes = session.scopes.clone();
+
+        let policy = self.auth_policy(user.tenant_id).await?;
+        if policy.session_lifetime.touch(&mut session) {
+            if let Err(e) = self.session_store.store_session(&session).await {
+                tracing::warn!("Failed to extend session {}: {}", session.id, e);
             }
         }
+        Ok((user, session))
+    }
 
-        // Test user registration
+    /// Rejects the JWT of a session issued before its user logged out everywhere
+    ///
+    /// Revoking all sessions removes them, but sessions surviving in a replica or
+    /// cache are still refused by their outdated token version.
+    async fn ensure_token_current(&self, token: &str, session: &Session) -> Result<()> {
+        let Some(signer) = self.signer() else {
+            return Ok(());
+        };
+        // Random tokens of sessions opened before keys were set carry no version
+        if jsonwebtoken::decode_header(token).is_err() {
+            return Ok(());
+        }
+        let claims: Claims = signer.keys.decode(token, |validation| {
+            validation.set_audience(&[&signer.audience]);
+            validation.set_issuer(&[&signer.issuer]);
+        })?;
+        let version = self
+            .session_store
+            .get_token_version(session.tenant_id, session.user_id)
+            .await?;
+        if claims.ver < version {
+            self.session_store.remove_session(session.id).await?;
+            return Err(Error::Authentication("Session revoked".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Issues a personal access token limited to scopes the user holds
+    ///
+    /// Requests with the token only get permissions granted by both the scopes and
+    /// the user's current roles; a token of a scoped session can only narrow its scopes.
+    pub async fn create_access_token(
+        &self,
+        user: &User,
+        scopes: Vec<TokenScope>,
+        expires_in: time::Duration,
+    ) -> Result<Session> {
+        if scopes.is_empty() {
+            return Err(Error::Validation(
+                "Access tokens require at least one scope".to_string(),
+            ));
+        }
+        ensure_scopes_held(user, &scopes)?;
+
+        let token = self
+            .issue_token(user.id, user.tenant_id, Some(&scopes), expires_in)
+            .await?;
+        let mut session = Session::new(user.id, user.tenant_id, token, expires_in);
+        session.scopes = Some(scopes);
+        self.session_store.store_session(&session).await?;
+
+        let event = AuditEvent::new(
+            user.tenant_id,
+            AuditCategory::Security,
+            "access_token_created",
+            "users",
+            user.id.0,
+        )
+        .with_user(user.id)
+        .with_detail("session_id", session.id)
+        .with_detail("scopes", &session.scopes);
+        record_audit_event(&self.repository, &self.audit, event).await;
+        Ok(session)
+    }
+
+    /// Resolves the creator of a valid API key, limited to the key's scopes
+    ///
+    /// Keys stop working once their creator is deactivated or leaves the tenant.
+    async fn resolve_api_key(&self, key: &str) -> Result<User> {
+        let invalid = || Error::Authentication("Invalid API key".to_string());
+        let repository = self.api_keys.as_ref().ok_or_else(invalid)?;
+
+        let api_key = repository
+            .get_by_hash(&hash_token(key))
+            .await?
+            .filter(ApiKey::is_valid)
+            .ok_or_else(invalid)?;
+        let mut user = self
+            .repository
+            .get_user_by_id(api_key.created_by)
+            .await?
+            .filter(|user| user.active && user.tenant_id == api_key.tenant_id)
+            .ok_or_else(invalid)?;
+        self.repository.ensure_tenant_active(user.tenant_id).await?;
+        user.token_scopes = Some(api_key.scopes);
+
+        if let Err(e) = repository.touch(api_key.id).await {
+            tracing::warn!("Failed to record use of API key {}: {}", api_key.id, e);
+        }
+        Ok(user)
+    }
+
+    /// Counts a request made with an API key to an endpoint against the key's quota
+    ///
+    /// Returns `None` for unknown keys, keys without a limit on the endpoint and
+    /// while quotas are not tracked; counting fails open if the tracker is unavailable.
+    pub async fn consume_api_key_quota(
+        &self,
+        key: &str,
+        endpoint: &str,
+    ) -> Result<Option<QuotaUsage>> {
+        let (Some(repository), Some(quotas)) = (&self.api_keys, &self.quotas) else {
+            return Ok(None);
+        };
+        let Some(api_key) = repository
+            .get_by_hash(&hash_token(key))
+            .await?
+            .filter(ApiKey::is_valid)
+        else {
+            return Ok(None);
+        };
+
+        match quotas
+            .consume(&api_key.id.to_string(), endpoint, &api_key.quota)
+            .await
+        {
+            Ok(usage) => Ok(usage),
+            Err(e) => {
+                tracing::warn!("Failed to count request of API key {}: {}", api_key.id, e);
+                Ok(None)
+            },
+        }
+    }
+
+    /// Mints an API key for the tenant of an admin, limited to scopes the admin holds
+    ///
+    /// The plain key is only returned here; keys without a lifetime stay valid until revoked
+    /// and keys without a quota may send unlimited requests.
+    pub async fn create_api_key(
+        &self,
+        actor: &User,
+        name: String,
+        scopes: Vec<TokenScope>,
+        quota: ApiKeyQuota,
+        expires_in: Option<time::Duration>,
+    ) -> Result<(ApiKey, String)> {
+        let repository = self.api_key_repository()?;
+        Self::ensure_api_key_admin(actor)?;
+        if name.trim().is_empty() {
+            return Err(Error::Validation(
+                "API key name must not be empty".to_string(),
+            ));
+        }
+        if scopes.is_empty() {
+            return Err(Error::Validation(
+                "API keys require at least one scope".to_string(),
+            ));
+        }
+        ensure_scopes_held(actor, &scopes)?;
+        if quota.requests_per_day == Some(0) || quota.endpoint_budgets.values().any(|&b| b == 0) {
+            return Err(Error::Validation(
+                "API key quotas must allow at least one request per day".to_string(),
+            ));
+        }
+
+        let (api_key, key) =
+            ApiKey::new(actor.tenant_id, actor.id, name, scopes, quota, expires_in);
+        repository.create(&api_key).await?;
+
+        let event = AuditEvent::new(
+            actor.tenant_id,
+            AuditCategory::Security,
+            "api_key_created",
+            "api_keys",
+            api_key.id,
+        )
+        .with_user(actor.id)
+        .with_detail("name", &api_key.name)
+        .with_detail("scopes", &api_key.scopes)
+        .with_detail("quota", &api_key.quota);
+        record_audit_event(&self.repository, &self.audit, event).await;
+        Ok((api_key, key))
+    }
+
+    /// Lists the unrevoked API keys of an admin's tenant
+    pub async fn list_api_keys(&self, actor: &User) -> Result<Vec<ApiKey>> {
+        let repository = self.api_key_repository()?;
+        Self::ensure_api_key_admin(actor)?;
+        repository.list(actor.tenant_id).await
+    }
+
+    /// Revokes an API key of an admin's tenant
+    pub async fn revoke_api_key(&self, actor: &User, id: Uuid) -> Result<()> {
+        let repository = self.api_key_repository()?;
+        Self::ensure_api_key_admin(actor)?;
+        if !repository.revoke(actor.tenant_id, id).await? {
+            return Err(Error::NotFound("API key not found".to_string()));
+        }
+
+        let event = AuditEvent::new(
+            actor.tenant_id,
+            AuditCategory::Security,
+            "api_key_revoked",
+            "api_keys",
+            id,
+        )
+        .with_user(actor.id);
+        record_audit_event(&self.repository, &self.audit, event).await;
+        Ok(())
+    }
+
+    /// Ensures API keys are managed by tenant admins signed in without a scoped token
+    fn ensure_api_key_admin(actor: &User) -> Result<()> {
+        if !is_tenant_admin(actor) || actor.token_scopes.is_some() {
+            return Err(Error::Authorization(
+                "Only tenant admins can manage API keys".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn api_key_repository(&self) -> Result<&ApiKeyRepository> {
+        self.api_keys
+            .as_ref()
+            .ok_or_else(|| Error::Internal("API keys are not enabled".to_string()))
+    }
+
+    /// Checks whether sessions are issued with rotating refresh tokens
+    pub fn refresh_tokens_enabled(&self) -> bool {
+        self.refresh_tokens.is_some()
+    }
+
+    /// Issues the first refresh token of a session, starting a new token family
+    pub async fn issue_refresh_token(&self, session: &Session) -> Result<String> {
+        let repository = self.refresh_token_repository()?;
+        let (refresh_token, token) = RefreshToken::new(
+            session.tenant_id,
+            session.user_id,
+            session.id,
+            None,
+            self.refresh_token_lifetime,
+        );
+        repository.create(&refresh_token).await?;
+        Ok(token)
+    }
+
+    /// Exchanges a refresh token for a new session and the next token of its family
+    ///
+    /// Every refresh uses up the presented token and ends its session. Presenting
+    /// a token that was already used means it leaked, so the whole family is
+    /// revoked together with its sessions and the reuse is audited.
+    pub async fn refresh_session(&self, token: &str) -> Result<(Session, String)> {
+        let repository = self.refresh_token_repository()?;
+        let invalid = || Error::Authentication("Invalid refresh token".to_string());
+
+        let current = repository
+            .get_by_hash(&hash_refresh_token(token))
+            .await?
+            .filter(|refresh_token| refresh_token.is_valid())
+            .ok_or_else(invalid)?;
+        // Checked before the token is used up, so it still works once the tenant is reactivated
+        self.repository
+            .ensure_tenant_active(current.tenant_id)
+            .await?;
+        if !repository.mark_used(current.id).await? {
+            self.revoke_refresh_token_family(repository, &current)
+                .await?;
+            return Err(invalid());
+        }
+
+        let user = self
+            .repository
+            .get_user_by_id(current.user_id)
+            .await?
+            .filter(|user| user.active && user.tenant_id == current.tenant_id)
+            .ok_or_else(invalid)?;
+        let policy = self.auth_policy(user.tenant_id).await?;
+        // The new session replaces the refreshed one, so it only counts once
+        self.make_room_for_session(user.id, user.tenant_id, &policy, Some(current.session_id))
+            .await?;
+
+        let mut session = Session::new(
+            user.id,
+            user.tenant_id,
+            self.issue_token(user.id, user.tenant_id, None, policy.session_lifetime.max)
+                .await?,
+            policy.session_lifetime.initial(),
+        );
+        if let Some(previous) = self.session_store.get_session(current.session_id).await? {
+            session.ip_address = previous.ip_address;
+            session.user_agent = previous.user_agent;
+            session.device_name = previous.device_name;
+            session.attributes = previous.attributes;
+        }
+        self.session_store.store_session(&session).await?;
+        self.session_store
+            .remove_session(current.session_id)
+            .await?;
+
+        let (next, next_token) = RefreshToken::new(
+            user.tenant_id,
+            user.id,
+            session.id,
+            Some(current.family_id),
+            self.refresh_token_lifetime,
+        );
+        repository.create(&next).await?;
+        Ok((session, next_token))
+    }
+
+    /// Revokes the family of a reused refresh token with all sessions issued with it
+    async fn revoke_refresh_token_family(
+        &self,
+        repository: &RefreshTokenRepository,
+        reused: &RefreshToken,
+    ) -> Result<()> {
+        let session_ids = repository.revoke_family(reused.family_id).await?;
+        let mut revoked = Vec::new();
+        for session_id in &session_ids {
+            if let Some(session) = self.session_store.get_session(*session_id).await? {
+                revoked.push(session);
+            }
+            self.session_store.remove_session(*session_id).await?;
+        }
+        self.run_post_logout(&revoked).await;
+        tracing::warn!(
+            "Refresh token of family {} was reused, revoked {} sessions",
+            reused.family_id,
+            session_ids.len()
+        );
+
+        let event = AuditEvent::new(
+            reused.tenant_id,
+            AuditCategory::Security,
+            "refresh_token_reused",
+            "users",
+            reused.user_id.0,
+        )
+        .with_user(reused.user_id)
+        .with_detail("family_id", reused.family_id)
+        .with_detail("revoked_sessions", session_ids);
+        record_audit_event(&self.repository, &self.audit, event).await;
+        Ok(())
+    }
+
+    /// Gets the refresh token repository, failing if refresh tokens are disabled
+    fn refresh_token_repository(&self) -> Result<&RefreshTokenRepository> {
+        self.refresh_tokens
+            .as_ref()
+            .ok_or_else(|| Error::Internal("Refresh tokens are not enabled".to_string()))
+    }
+
+    /// Lists the sessions of a user
+    pub async fn list_user_sessions(
+        &self,
+        tenant_id: TenantId,
+        user_id: UserId,
+    ) -> Result<Vec<Session>> {
+        self.session_store
+            .get_user_sessions(tenant_id, user_id)
+            .await
+    }
+
+    /// Counts the unexpired sessions of a user
+    pub async fn count_active_sessions(
+        &self,
+        tenant_id: TenantId,
+        user_id: UserId,
+    ) -> Result<usize> {
+        Ok(self
+            .session_store
+            .get_user_sessions(tenant_id, user_id)
+            .await?
+            .iter()
+            .filter(|session| !session.is_expired())
+            .count())
+    }
+
+    /// Revokes one of a user's sessions, e.g. of a lost device
+    pub async fn revoke_user_session(
+        &self,
+        tenant_id: TenantId,
+        user_id: UserId,
+        session_id: Uuid,
+    ) -> Result<()> {
+        let session = self
+            .session_store
+            .get_user_sessions(tenant_id, user_id)
+            .await?
+            .into_iter()
+            .find(|session| session.id == session_id)
+            .ok_or_else(|| Error::NotFound("Session not found".to_string()))?;
+        self.session_store.remove_session(session_id).await?;
+        if let Some(refresh_tokens) = &self.refresh_tokens {
+            refresh_tokens.revoke_session_tokens(&[session_id]).await?;
+        }
+        self.run_post_logout(std::slice::from_ref(&session)).await;
+
+        let event = AuditEvent::new(
+            tenant_id,
+            AuditCategory::Security,
+            "session_revoked",
+            "users",
+            user_id.0,
+        )
+        .with_user(user_id)
+        .with_detail("session_id", session_id);
+        record_audit_event(&self.repository, &self.audit, event).await;
+        Ok(())
+    }
+
+    /// Revokes all sessions of a user except the given one, i.e. signs out other devices
+    ///
+    /// Returns the number of revoked sessions.
+    pub async fn revoke_other_sessions(&self, current: &Session) -> Result<usize> {
+        let others: Vec<Session> = self
+            .session_store
+            .get_user_sessions(current.tenant_id, current.user_id)
+            .await?
+            .into_iter()
+            .filter(|session| session.id != current.id)
+            .collect();
+        for session in &others {
+            self.session_store.remove_session(session.id).await?;
+        }
+        if let Some(refresh_tokens) = &self.refresh_tokens {
+            let session_ids: Vec<Uuid> = others.iter().map(|session| session.id).collect();
+            refresh_tokens.revoke_session_tokens(&session_ids).await?;
+        }
+        self.run_post_logout(&others).await;
+
+        let event = AuditEvent::new(
+            current.tenant_id,
+            AuditCategory::Security,
+            "other_sessions_revoked",
+            "users",
+            current.user_id.0,
+        )
+        .with_user(current.user_id)
+        .with_detail("revoked", others.len());
+        record_audit_event(&self.repository, &self.audit, event).await;
+        Ok(others.len())
+    }
+
+    /// Logs a user out everywhere, revoking all their sessions and outstanding tokens
+    ///
+    /// Bumps the user's token version, so JWTs issued before are rejected even if their
+    /// session survived, and services validating JWTs with the JWKS can tell them apart
+    /// by their `ver` claim. Returns the new version.
+    pub async fn revoke_all_sessions(&self, tenant_id: TenantId, user_id: UserId) -> Result<u64> {
+        let version = self
+            .session_store
+            .increment_token_version(tenant_id, user_id)
+            .await?;
+        let sessions = self
+            .session_store
+            .get_user_sessions(tenant_id, user_id)
+            .await?;
+        self.session_store
+            .remove_user_sessions(tenant_id, user_id)
+            .await?;
+        if let Some(refresh_tokens) = &self.refresh_tokens {
+            refresh_tokens
+                .revoke_user_tokens(tenant_id, user_id)
+                .await?;
+        }
+        self.run_post_logout(&sessions).await;
+
+        let event = AuditEvent::new(
+            tenant_id,
+            AuditCategory::Security,
+            "all_sessions_revoked",
+            "users",
+            user_id.0,
+        )
+        .with_user(user_id)
+        .with_detail("token_version", version);
+        record_audit_event(&self.repository, &self.audit, event).await;
+        Ok(version)
+    }
+
+    /// Lists the sessions of all users of a tenant
+    pub async fn list_tenant_sessions(&self, tenant_id: TenantId) -> Result<Vec<Session>> {
+        self.session_store.get_tenant_sessions(tenant_id).await
+    }
+
+    /// Revokes the sessions of all users of a tenant, e.g. after a credential leak
+    pub async fn revoke_tenant_sessions(&self, tenant_id: TenantId) -> Result<()> {
+        let sessions = self.session_store.get_tenant_sessions(tenant_id).await?;
+        self.session_store.remove_tenant_sessions(tenant_id).await?;
+        if let Some(refresh_tokens) = &self.refresh_tokens {
+            refresh_tokens.revoke_tenant_tokens(tenant_id).await?;
+        }
+        self.run_post_logout(&sessions).await;
+        let event = AuditEvent::new(
+            tenant_id,
+            AuditCategory::Security,
+            "tenant_sessions_revoked",
+            "tenants",
+            tenant_id.0,
+        );
+        record_audit_event(&self.repository, &self.audit, event).await;
+        Ok(())
+    }
+
+    /// Deactivates a user and revokes all of their sessions
+    pub async fn deactivate_user(&self, user_id: UserId, tenant_id: TenantId) -> Result<User> {
+        let user = self
+            .repository
+            .set_user_active(user_id, tenant_id, false)
+            .await?
+            .ok_or_else(|| Error::NotFound("User not found".to_string()))?;
+
+        let sessions = self
+            .session_store
+            .get_user_sessions(user.tenant_id, user.id)
+            .await?;
+        self.session_store
+            .remove_user_sessions(user.tenant_id, user.id)
+            .await?;
+        if let Some(refresh_tokens) = &self.refresh_tokens {
+            refresh_tokens
+                .revoke_user_tokens(user.tenant_id, user.id)
+                .await?;
+        }
+        self.run_post_logout(&sessions).await;
+        self.audit_account_change(&user, "user_deactivated").await;
+
+        Ok(user)
+    }
+
+    /// Reactivates a previously deactivated user
+    pub async fn activate_user(&self, user_id: UserId, tenant_id: TenantId) -> Result<User> {
+        let user = self
+            .repository
+            .set_user_active(user_id, tenant_id, true)
+            .await?
+            .ok_or_else(|| Error::NotFound("User not found".to_string()))?;
+        self.audit_account_change(&user, "user_activated").await;
+        Ok(user)
+    }
+
+    /// Records a change of a user's ability to log in as a security event
+    async fn audit_account_change(&self, user: &User, action: &str) {
+        let event = AuditEvent::new(
+            user.tenant_id,
+            AuditCategory::Security,
+            action,
+            "users",
+            user.id.0,
+        );
+        record_audit_event(&self.repository, &self.audit, event).await;
+    }
+
+    /// Hashes a password using Argon2
+    pub fn hash_password(password: &str) -> Result<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        let argon2 = Argon2::default();
+        let password_hash = argon2
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| Error::Internal(format!("Failed to hash password: {}", e)))?
+            .to_string();
+        Ok(password_hash)
+    }
+
+    /// Creates the token of a new session, a signed JWT in JWT mode with keys set or a random token
+    ///
+    /// JWTs carry the user's current token version, so they can be told apart from
+    /// the tokens revoked by logging out everywhere.
+    async fn issue_token(
+        &self,
+        user_id: UserId,
+        tenant_id: TenantId,
+        scopes: Option<&[TokenScope]>,
+        lifetime: time::Duration,
+    ) -> Result<String> {
+        let Some(signer) = self.signer() else {
+            return Ok(generate_token());
+        };
+        let version = self
+            .session_store
+            .get_token_version(tenant_id, user_id)
+            .await?;
+        let mut claims = Claims::new(
+            user_id,
+            tenant_id,
+            signer.issuer.clone(),
+            signer.audience.clone(),
+            lifetime,
+        )
+        .with_version(version);
+        if let Some(scopes) = scopes {
+            claims = claims.with_scopes(scopes);
+        }
+        signer.keys.encode(&claims)
+    }
+
+    /// Verifies a password against a hash
+    pub(crate) fn verify_password(password: &str, hash: &str) -> Result<bool> {
+        let parsed_hash = PasswordHash::new(hash)
+            .map_err(|e| Error::Internal(format!("Failed to parse password hash: {}", e)))?;
+        Ok(Argon2::default()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_ok())
+    }
+}
+
+/// Hash verified instead of a user's when there is none, so lookups take as long either way
+static DUMMY_PASSWORD_HASH: Lazy<String> = Lazy::new(|| {
+    AuthenticationService::hash_password("dummy password").expect("Failed to hash dummy password")
+});
+
+/// Generates a random session token
+fn generate_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(48)
+        .map(char::from)
+        .collect()
+}
+
+/// Wipes the sessions of deleted and archived tenants
+#[async_trait::async_trait]
+impl TenantHook for AuthenticationService {
+    async fn on_tenant_event(&self, event: TenantEvent, tenant: &Tenant) -> Result<()> {
+        if matches!(event, TenantEvent::Deleted | TenantEvent::Archived) {
+            self.session_store.remove_tenant_sessions(tenant.id).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::database::tests::create_test_db;
+    use crate::modules::identity::auth_policy::AUTH_POLICY_SETTING;
+    use crate::modules::identity::mfa::{MfaConfig, MfaService};
+    use crate::modules::identity::{
+        models::{Role, RoleType, UserOverviewQuery, UserSessionResponse},
+        rbac::create_admin_role,
+        service::IdentityModule,
+        session::InMemorySessionStore,
+    };
+    use crate::modules::tenant::repository::TenantRepository;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_signed_session_tokens() {
+        use crate::modules::identity::jwt_keys::JwtKeys;
+
+        let store = InMemorySessionStore::new();
+        let service =
+            AuthenticationService::new(UserRepository::default(), Box::new(store.clone()));
+        assert!(service.token_keys().is_none());
+        let user_id = UserId(Uuid::new_v4());
+        let tenant_id = TenantId(Uuid::new_v4());
+        let token = service
+            .issue_token(user_id, tenant_id, None, time::Duration::hours(1))
+            .await
+            .unwrap();
+        assert!(jsonwebtoken::decode_header(&token).is_err());
+
+        // With keys, tokens are JWTs downstream services validate with the JWKS
+        let keys: JwtKeyRing = JwtKeys::hmac("secret")
+            .with_key_id("current".to_string())
+            .into();
+        let service = service.with_token_keys(keys.clone(), "acci".to_string(), "api".to_string());
+        let scopes = TokenScope::parse_list("read:users").unwrap();
+        let token = service
+            .issue_token(user_id, tenant_id, Some(&scopes), time::Duration::hours(1))
+            .await
+            .unwrap();
+        let claims: Claims = keys
+            .decode(&token, |validation| {
+                validation.set_audience(&["api"]);
+                validation.set_issuer(&["acci"]);
+            })
+            .unwrap();
+        assert_eq!(claims.sub, user_id.0.to_string());
+        assert_eq!(claims.tenant_id, tenant_id.0.to_string());
+        assert_eq!(claims.scopes().unwrap(), Some(scopes));
+        assert_eq!(claims.ver, 0);
+
+        // They carry the user's token version, so they can be told apart once it was bumped
+        store
+            .increment_token_version(tenant_id, user_id)
+            .await
+            .unwrap();
+        let token = service
+            .issue_token(user_id, tenant_id, None, time::Duration::hours(1))
+            .await
+            .unwrap();
+        let claims: Claims = keys
+            .decode(&token, |validation| {
+                validation.set_audience(&["api"]);
+                validation.set_issuer(&["acci"]);
+            })
+            .unwrap();
+        assert_eq!(claims.ver, 1);
+
+        // Opaque tokens are random whatever keys are set, and no keys are published
+        let service = service.with_token_mode(SessionTokenMode::Opaque);
+        assert!(service.token_keys().is_none());
+        let token = service
+            .issue_token(user_id, tenant_id, None, time::Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!(token.len(), 48);
+        assert!(jsonwebtoken::decode_header(&token).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_authentication() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let session_store = Box::new(InMemorySessionStore::new());
+        let service = AuthenticationService::new(repository, session_store);
+
+        // Create test tenant
+        let tenant = Tenant::new(
+            "Test Tenant".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+
+        let mut retries = 3;
+        while retries > 0 {
+            match sqlx::query!(
+                r#"INSERT INTO tenants (id, name, domain, active) VALUES ($1, $2, $3, $4)"#,
+                tenant.id.0 as uuid::Uuid,
+                tenant.name,
+                tenant.domain,
+                tenant.active
+            )
+            .execute(&db.get_pool())
+            .await
+            {
+                Ok(_) => break,
+                Err(e) => {
+                    retries -= 1;
+                    if retries == 0 {
+                        panic!("Failed to create tenant: {}", e);
+                    }
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                },
+            }
+        }
+
+        // Test user registration
         let credentials = Credentials {
             email: "test@example.com".to_string(),
             password: "password123".to_string(),
@@ -293,7 +1705,7 @@ mod tests {
     async fn test_mfa_authentication() {
         let (db, _container) = create_test_db().await.unwrap();
         let repository = UserRepository::new(db.get_pool());
-        let session_store = Box::new(MockSessionStore::default());
+        let session_store = Box::new(InMemorySessionStore::new());
         let service = AuthenticationService::new(repository, session_store);
 
         // Create test tenant
@@ -394,7 +1806,7 @@ mod tests {
         let mut retries = 3;
         let session = loop {
             match service
-                .authenticate_with_mfa(credentials.clone(), code.clone())
+                .authenticate_with_mfa(credentials.clone(), code.clone(), LoginContext::default())
                 .await
             {
                 Ok(s) => break s,
@@ -411,4 +1823,1146 @@ mod tests {
         assert_eq!(session.user_id, user.id);
         assert_eq!(session.tenant_id, user.tenant_id);
     }
+
+    #[tokio::test]
+    async fn test_deactivated_user_cannot_authenticate() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let session_store = Box::new(InMemorySessionStore::new());
+        let service = AuthenticationService::new(repository, session_store);
+
+        let tenant = Tenant::new(
+            "Test Tenant".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        sqlx::query!(
+            r#"INSERT INTO tenants (id, name, domain, active) VALUES ($1, $2, $3, $4)"#,
+            tenant.id.0 as uuid::Uuid,
+            tenant.name,
+            tenant.domain,
+            tenant.active
+        )
+        .execute(&db.get_pool())
+        .await
+        .unwrap();
+
+        let credentials = Credentials {
+            email: "test@example.com".to_string(),
+            password: "password123".to_string(),
+            tenant_id: tenant.id,
+            mfa_code: None,
+        };
+        let user = service.register_user(credentials.clone()).await.unwrap();
+
+        // Deactivate and verify login is rejected
+        let deactivated = service.deactivate_user(user.id, tenant.id).await.unwrap();
+        assert!(!deactivated.active);
+        let result = service.authenticate(credentials.clone()).await;
+        assert!(matches!(result, Err(Error::Authentication(_))));
+
+        // Reactivate and verify login succeeds again
+        let activated = service.activate_user(user.id, tenant.id).await.unwrap();
+        assert!(activated.active);
+        let session = service.authenticate(credentials).await.unwrap();
+        assert_eq!(session.user_id, user.id);
+    }
+
+    #[tokio::test]
+    async fn test_suspended_tenant_cannot_authenticate() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let session_store = Box::new(InMemorySessionStore::new());
+        let service = AuthenticationService::new(repository, session_store);
+        let tenants = TenantRepository::new(db.get_pool());
+
+        let tenant = tenants
+            .create_tenant(Tenant::new(
+                "Test Tenant".to_string(),
+                format!("{}.example.com", Uuid::new_v4()),
+            ))
+            .await
+            .unwrap();
+        let credentials = Credentials {
+            email: "test@example.com".to_string(),
+            password: "password123".to_string(),
+            tenant_id: tenant.id,
+            mfa_code: None,
+        };
+        service.register_user(credentials.clone()).await.unwrap();
+
+        // Suspended tenants are rejected with their own error, even with valid credentials
+        tenants
+            .suspend_tenant(tenant.id.0, Some("Unpaid invoices"))
+            .await
+            .unwrap();
+        let result = service.authenticate(credentials.clone()).await;
+        assert!(
+            matches!(result, Err(Error::TenantSuspended(ref msg)) if msg.contains("Unpaid invoices"))
+        );
+
+        tenants.reactivate_tenant(tenant.id.0).await.unwrap();
+        assert!(service.authenticate(credentials).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_tenant_auth_policy_overrides() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let session_store = Box::new(InMemorySessionStore::new());
+        let tenants = TenantService::new(TenantRepository::new(db.get_pool()));
+        let service = AuthenticationService::new(repository, session_store)
+            .with_session_lifetime(SessionLifetime {
+                max: time::Duration::hours(8),
+                idle_timeout: None,
+            })
+            .with_tenant_policies(tenants.clone());
+
+        let strict = tenants
+            .create_tenant(Tenant::new(
+                "Strict Tenant".to_string(),
+                format!("{}.example.com", Uuid::new_v4()),
+            ))
+            .await
+            .unwrap();
+        let lenient = tenants
+            .create_tenant(Tenant::new(
+                "Lenient Tenant".to_string(),
+                format!("{}.example.com", Uuid::new_v4()),
+            ))
+            .await
+            .unwrap();
+        tenants
+            .set_setting(
+                strict.id,
+                AUTH_POLICY_SETTING,
+                serde_json::json!({
+                    "password_min_length": 12,
+                    "session_lifetime_secs": 600,
+                }),
+            )
+            .await
+            .unwrap();
+
+        let credentials = |tenant_id, password: &str| Credentials {
+            email: "test@example.com".to_string(),
+            password: password.to_string(),
+            tenant_id,
+            mfa_code: None,
+        };
+
+        // Passwords are checked against the policy of the user's tenant
+        let result = service
+            .register_user(credentials(strict.id, "password123"))
+            .await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+        service
+            .register_user(credentials(strict.id, "password123456"))
+            .await
+            .unwrap();
+        service
+            .register_user(credentials(lenient.id, "password123"))
+            .await
+            .unwrap();
+
+        // Sessions live as long as the tenant's policy allows
+        let session = service
+            .authenticate(credentials(strict.id, "password123456"))
+            .await
+            .unwrap();
+        assert!(session.expires_at <= OffsetDateTime::now_utc() + time::Duration::minutes(10));
+        let session = service
+            .authenticate(credentials(lenient.id, "password123"))
+            .await
+            .unwrap();
+        assert!(session.expires_at > OffsetDateTime::now_utc() + time::Duration::hours(7));
+
+        // Once the tenant requires MFA, users without MFA cannot log in with their password
+        tenants
+            .set_setting(
+                strict.id,
+                AUTH_POLICY_SETTING,
+                serde_json::json!({ "mfa": "required" }),
+            )
+            .await
+            .unwrap();
+        let result = service
+            .authenticate(credentials(strict.id, "password123456"))
+            .await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+        assert!(service
+            .authenticate(credentials(lenient.id, "password123"))
+            .await
+            .is_ok());
+
+        // Invalid overrides are rejected when they are set
+        let result = tenants
+            .set_setting(
+                strict.id,
+                AUTH_POLICY_SETTING,
+                serde_json::json!({ "mfa": "sometimes" }),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_user_overview() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let session_store = Box::new(InMemorySessionStore::new());
+        let service = AuthenticationService::new(repository.clone(), session_store);
+        let identity = IdentityModule::new(repository.clone());
+
+        let tenant = Tenant::new(
+            "Test Tenant".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        sqlx::query!(
+            r#"INSERT INTO tenants (id, name, domain, active) VALUES ($1, $2, $3, $4)"#,
+            tenant.id.0 as uuid::Uuid,
+            tenant.name,
+            tenant.domain,
+            tenant.active
+        )
+        .execute(&db.get_pool())
+        .await
+        .unwrap();
+
+        let mut admin = User::new(
+            tenant.id,
+            "admin@example.com".to_string(),
+            "hash".to_string(),
+        );
+        admin.roles.push(create_admin_role());
+        let admin = repository.create_user(admin).await.unwrap();
+
+        let credentials = Credentials {
+            email: "jane@example.com".to_string(),
+            password: "password123".to_string(),
+            tenant_id: tenant.id,
+            mfa_code: None,
+        };
+        let jane = service.register_user(credentials.clone()).await.unwrap();
+        service.authenticate(credentials.clone()).await.unwrap();
+        service.authenticate(credentials).await.unwrap();
+
+        let john = service
+            .register_user(Credentials {
+                email: "john@example.com".to_string(),
+                password: "password123".to_string(),
+                tenant_id: tenant.id,
+                mfa_code: None,
+            })
+            .await
+            .unwrap();
+        service.deactivate_user(john.id, tenant.id).await.unwrap();
+
+        let page = identity
+            .list_user_overview(&admin, &service, UserOverviewQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.users.len(), 3);
+
+        let entry = page.users.iter().find(|u| u.id == jane.id.0).unwrap();
+        assert_eq!(entry.active_sessions, 2);
+        assert!(entry.last_login.is_some());
+        assert!(!entry.locked);
+        let entry = page.users.iter().find(|u| u.id == john.id.0).unwrap();
+        assert!(entry.locked);
+        let entry = page.users.iter().find(|u| u.id == admin.id.0).unwrap();
+        assert_eq!(entry.roles, vec!["Admin".to_string()]);
+
+        let page = identity
+            .list_user_overview(
+                &admin,
+                &service,
+                UserOverviewQuery {
+                    page: Some(2),
+                    per_page: Some(2),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.users.len(), 1);
+
+        // Regular users cannot list the tenant's users
+        let result = identity
+            .list_user_overview(&jane, &service, UserOverviewQuery::default())
+            .await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+    }
+
+    #[derive(Debug)]
+    struct EmbargoHook;
+
+    #[async_trait::async_trait]
+    impl AuthHook for EmbargoHook {
+        async fn pre_login(
+            &self,
+            _email: &str,
+            _tenant_id: TenantId,
+            context: &LoginContext,
+        ) -> Result<()> {
+            if context.attributes.get("country").map(String::as_str) == Some("XX") {
+                return Err(Error::Authorization("Login not permitted".to_string()));
+            }
+            Ok(())
+        }
+
+        async fn post_login(
+            &self,
+            _user: &User,
+            context: &LoginContext,
+            session: &mut Session,
+        ) -> Result<()> {
+            if let Some(country) = context.attributes.get("country") {
+                session
+                    .attributes
+                    .insert("country".to_string(), country.clone());
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auth_hooks() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let session_store = Box::new(InMemorySessionStore::new());
+        let mut service = AuthenticationService::new(repository, session_store);
+        service.register_hook(Arc::new(EmbargoHook));
+
+        let tenant = Tenant::new(
+            "Test Tenant".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        sqlx::query!(
+            r#"INSERT INTO tenants (id, name, domain, active) VALUES ($1, $2, $3, $4)"#,
+            tenant.id.0 as uuid::Uuid,
+            tenant.name,
+            tenant.domain,
+            tenant.active
+        )
+        .execute(&db.get_pool())
+        .await
+        .unwrap();
+
+        let credentials = Credentials {
+            email: "test@example.com".to_string(),
+            password: "password123".to_string(),
+            tenant_id: tenant.id,
+            mfa_code: None,
+        };
+        service.register_user(credentials.clone()).await.unwrap();
+
+        // Pre-login hook rejects the attempt
+        let mut context = LoginContext::default();
+        context
+            .attributes
+            .insert("country".to_string(), "XX".to_string());
+        let result = service
+            .authenticate_with_context(credentials.clone(), context)
+            .await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+
+        // Post-login hook enriches the session
+        let mut context = LoginContext::default();
+        context
+            .attributes
+            .insert("country".to_string(), "DE".to_string());
+        let session = service
+            .authenticate_with_context(credentials, context)
+            .await
+            .unwrap();
+        assert_eq!(
+            session.attributes.get("country").map(String::as_str),
+            Some("DE")
+        );
+    }
+
+    #[derive(Debug, Default)]
+    struct DefaultRoleHook {
+        provisioned: Mutex<Vec<UserId>>,
+    }
+
+    #[async_trait::async_trait]
+    impl RegistrationHook for DefaultRoleHook {
+        async fn pre_register(&self, user: &mut User) -> Result<()> {
+            if user.email.ends_with("@blocked.example.com") {
+                return Err(Error::Validation(
+                    "Signups from this domain are disabled".to_string(),
+                ));
+            }
+            user.roles
+                .push(Role::new(RoleType::User, "Member".to_string()));
+            Ok(())
+        }
+
+        async fn post_register(&self, user: &User) -> Result<()> {
+            self.provisioned.lock().unwrap().push(user.id);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registration_hooks() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let session_store = Box::new(InMemorySessionStore::new());
+        let mut service = AuthenticationService::new(repository, session_store);
+        let hook = Arc::new(DefaultRoleHook::default());
+        service.register_registration_hook(hook.clone());
+
+        let tenant = Tenant::new(
+            "Test Tenant".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        sqlx::query!(
+            r#"INSERT INTO tenants (id, name, domain, active) VALUES ($1, $2, $3, $4)"#,
+            tenant.id.0 as uuid::Uuid,
+            tenant.name,
+            tenant.domain,
+            tenant.active
+        )
+        .execute(&db.get_pool())
+        .await
+        .unwrap();
+
+        // Vetoed signups are not stored
+        let result = service
+            .register_user(Credentials {
+                email: "eve@blocked.example.com".to_string(),
+                password: "password123".to_string(),
+                tenant_id: tenant.id,
+                mfa_code: None,
+            })
+            .await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+        assert!(hook.provisioned.lock().unwrap().is_empty());
+
+        // Accepted signups get the default role and are provisioned
+        let user = service
+            .register_user(Credentials {
+                email: "alice@example.com".to_string(),
+                password: "password123".to_string(),
+                tenant_id: tenant.id,
+                mfa_code: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(user.roles.len(), 1);
+        assert_eq!(user.roles[0].name, "Member");
+        assert_eq!(*hook.provisioned.lock().unwrap(), vec![user.id]);
+    }
+
+    #[tokio::test]
+    async fn test_own_sessions() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let store = InMemorySessionStore::new();
+        let service = AuthenticationService::new(repository, Box::new(store.clone()));
+
+        let tenant = Tenant::new(
+            "Test Tenant".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        sqlx::query!(
+            r#"INSERT INTO tenants (id, name, domain, active) VALUES ($1, $2, $3, $4)"#,
+            tenant.id.0 as uuid::Uuid,
+            tenant.name,
+            tenant.domain,
+            tenant.active
+        )
+        .execute(&db.get_pool())
+        .await
+        .unwrap();
+
+        let credentials = Credentials {
+            email: "test@example.com".to_string(),
+            password: "password123".to_string(),
+            tenant_id: tenant.id,
+            mfa_code: None,
+        };
+        let user = service.register_user(credentials.clone()).await.unwrap();
+
+        // Logins record the device they came from
+        let context = LoginContext {
+            ip_address: Some("203.0.113.7".parse().unwrap()),
+            user_agent: Some("Firefox".to_string()),
+            device_name: Some("Work laptop".to_string()),
+            ..LoginContext::default()
+        };
+        let current = service
+            .authenticate_with_context(credentials, context)
+            .await
+            .unwrap();
+        let response = UserSessionResponse::new(&current, current.id);
+        assert_eq!(response.ip_address.as_deref(), Some("203.0.113.7"));
+        assert_eq!(response.user_agent.as_deref(), Some("Firefox"));
+        assert_eq!(response.device_name.as_deref(), Some("Work laptop"));
+        assert!(response.current);
+
+        let laptop = Session::new(
+            user.id,
+            tenant.id,
+            "laptop_token".to_string(),
+            time::Duration::hours(1),
+        );
+        let phone = Session::new(
+            user.id,
+            tenant.id,
+            "phone_token".to_string(),
+            time::Duration::hours(1),
+        );
+        store.store_session(&laptop).await.unwrap();
+        store.store_session(&phone).await.unwrap();
+        assert_eq!(
+            service.current_session("laptop_token").await.unwrap().id,
+            laptop.id
+        );
+
+        // Sessions of other users cannot be revoked
+        let result = service
+            .revoke_user_session(tenant.id, UserId::new(), laptop.id)
+            .await;
+        assert!(matches!(result, Err(Error::NotFound(_))));
+
+        service
+            .revoke_user_session(tenant.id, user.id, laptop.id)
+            .await
+            .unwrap();
+        assert!(service.current_session("laptop_token").await.is_err());
+
+        assert_eq!(service.revoke_other_sessions(&current).await.unwrap(), 1);
+        let sessions = service
+            .list_user_sessions(tenant.id, user.id)
+            .await
+            .unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, current.id);
+
+        // Logging out everywhere also ends the current session
+        assert_eq!(
+            service
+                .revoke_all_sessions(tenant.id, user.id)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(service
+            .list_user_sessions(tenant.id, user.id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[derive(Debug, Default)]
+    struct LogoutRecorder {
+        revoked: Mutex<Vec<Uuid>>,
+    }
+
+    #[async_trait::async_trait]
+    impl AuthHook for LogoutRecorder {
+        async fn post_logout(&self, sessions: &[Session]) -> Result<()> {
+            self.revoked
+                .lock()
+                .unwrap()
+                .extend(sessions.iter().map(|session| session.id));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sso_session_link() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let mut service =
+            AuthenticationService::new(repository, Box::new(InMemorySessionStore::new()));
+        let recorder = Arc::new(LogoutRecorder::default());
+        service.register_hook(recorder.clone());
+
+        let tenant = Tenant::new(
+            "Test Tenant".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        sqlx::query!(
+            r#"INSERT INTO tenants (id, name, domain, active) VALUES ($1, $2, $3, $4)"#,
+            tenant.id.0 as uuid::Uuid,
+            tenant.name,
+            tenant.domain,
+            tenant.active
+        )
+        .execute(&db.get_pool())
+        .await
+        .unwrap();
+        let user = service
+            .register_user(Credentials {
+                email: "test@example.com".to_string(),
+                password: "password123".to_string(),
+                tenant_id: tenant.id,
+                mfa_code: None,
+            })
+            .await
+            .unwrap();
+
+        // SSO logins remember the SSO session they were opened with
+        let sso_session_id = Uuid::new_v4();
+        let linked = service
+            .authenticate_sso(
+                tenant.id,
+                user.id,
+                Some(sso_session_id),
+                LoginContext::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(linked.sso_session_id(), Some(sso_session_id));
+        let unlinked = service
+            .authenticate_sso(tenant.id, user.id, None, LoginContext::default())
+            .await
+            .unwrap();
+        assert_eq!(unlinked.sso_session_id(), None);
+
+        // Post-logout hooks learn about every revoked session
+        service
+            .revoke_user_session(tenant.id, user.id, linked.id)
+            .await
+            .unwrap();
+        assert_eq!(*recorder.revoked.lock().unwrap(), vec![linked.id]);
+        service
+            .revoke_all_sessions(tenant.id, user.id)
+            .await
+            .unwrap();
+        assert_eq!(
+            *recorder.revoked.lock().unwrap(),
+            vec![linked.id, unlinked.id]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_rotation() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let store = InMemorySessionStore::new();
+        let service = AuthenticationService::new(repository, Box::new(store.clone()))
+            .with_refresh_tokens(
+                RefreshTokenRepository::new(db.get_pool()),
+                time::Duration::days(30),
+            );
+
+        let tenant = Tenant::new(
+            "Test Tenant".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        sqlx::query!(
+            r#"INSERT INTO tenants (id, name, domain, active) VALUES ($1, $2, $3, $4)"#,
+            tenant.id.0 as uuid::Uuid,
+            tenant.name,
+            tenant.domain,
+            tenant.active
+        )
+        .execute(&db.get_pool())
+        .await
+        .unwrap();
+        let credentials = Credentials {
+            email: "test@example.com".to_string(),
+            password: "password123".to_string(),
+            tenant_id: tenant.id,
+            mfa_code: None,
+        };
+        service.register_user(credentials.clone()).await.unwrap();
+
+        let login = service.authenticate(credentials).await.unwrap();
+        let first = service.issue_refresh_token(&login).await.unwrap();
+
+        // Each refresh replaces the session and the refresh token
+        let (session, second) = service.refresh_session(&first).await.unwrap();
+        assert_ne!(session.id, login.id);
+        assert!(store.get_session(login.id).await.unwrap().is_none());
+        let (session, third) = service.refresh_session(&second).await.unwrap();
+        assert!(service.current_session(&session.token).await.is_ok());
+
+        // Reusing a used token revokes the whole family
+        let result = service.refresh_session(&first).await;
+        assert!(matches!(result, Err(Error::Authentication(_))));
+        assert!(service.current_session(&session.token).await.is_err());
+        assert!(service.refresh_session(&third).await.is_err());
+
+        assert!(service.refresh_session("unknown").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_revoked_token_versions() {
+        use crate::modules::identity::jwt_keys::JwtKeys;
+
+        let (db, _container) = create_test_db().await.unwrap();
+        let store = InMemorySessionStore::new();
+        let keys: JwtKeyRing = JwtKeys::hmac("secret").into();
+        let service =
+            AuthenticationService::new(UserRepository::new(db.get_pool()), Box::new(store.clone()))
+                .with_token_keys(keys.clone(), "acci".to_string(), "api".to_string());
+        let tenant = TenantRepository::new(db.get_pool())
+            .create_tenant(Tenant::new(
+                "Test Tenant".to_string(),
+                format!("{}.example.com", Uuid::new_v4()),
+            ))
+            .await
+            .unwrap();
+        let credentials = Credentials {
+            email: "test@example.com".to_string(),
+            password: "password123".to_string(),
+            tenant_id: tenant.id,
+            mfa_code: None,
+        };
+        let user = service.register_user(credentials.clone()).await.unwrap();
+        let version = |token: &str| {
+            keys.decode::<Claims>(token, |validation| {
+                validation.set_audience(&["api"]);
+                validation.set_issuer(&["acci"]);
+            })
+            .unwrap()
+            .ver
+        };
+
+        // JWTs carry the token version of their user when they were issued
+        let before = service.authenticate(credentials.clone()).await.unwrap();
+        assert_eq!(version(&before.token), 0);
+        assert_eq!(
+            service
+                .revoke_all_sessions(tenant.id, user.id)
+                .await
+                .unwrap(),
+            1
+        );
+        let after = service.authenticate(credentials).await.unwrap();
+        assert_eq!(version(&after.token), 1);
+
+        // A session surviving the logout, e.g. in a replica, is refused by its stale version
+        store.store_session(&before).await.unwrap();
+        assert!(matches!(
+            service.current_session(&before.token).await,
+            Err(Error::Authentication(_))
+        ));
+        assert!(store.get_session(before.id).await.unwrap().is_none());
+        assert!(service.current_session(&after.token).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_session_limits() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let store = InMemorySessionStore::new();
+        let tenants = TenantService::new(TenantRepository::new(db.get_pool()));
+        let service = AuthenticationService::new(repository, Box::new(store.clone()))
+            .with_session_limit(Some(SessionLimit {
+                max_sessions: 2,
+                strategy: SessionLimitStrategy::Reject,
+            }))
+            .with_tenant_policies(tenants.clone())
+            .with_refresh_tokens(
+                RefreshTokenRepository::new(db.get_pool()),
+                time::Duration::days(30),
+            );
+
+        let tenant = tenants
+            .create_tenant(Tenant::new(
+                "Test Tenant".to_string(),
+                format!("{}.example.com", Uuid::new_v4()),
+            ))
+            .await
+            .unwrap();
+        let credentials = Credentials {
+            email: "test@example.com".to_string(),
+            password: "password123".to_string(),
+            tenant_id: tenant.id,
+            mfa_code: None,
+        };
+        let user = service.register_user(credentials.clone()).await.unwrap();
+
+        // Logins beyond the global limit are rejected
+        let oldest = service.authenticate(credentials.clone()).await.unwrap();
+        let second = service.authenticate(credentials.clone()).await.unwrap();
+        let result = service.authenticate(credentials.clone()).await;
+        assert!(matches!(result, Err(Error::Conflict(_))));
+
+        // Refreshing replaces a session, so it does not count against the limit
+        let refresh_token = service.issue_refresh_token(&second).await.unwrap();
+        let (second, _) = service.refresh_session(&refresh_token).await.unwrap();
+
+        // Access tokens are not login sessions and are not limited
+        let scopes = TokenScope::parse_list("read:users").unwrap();
+        let mut scoped_user = user.clone();
+        scoped_user.roles = vec![create_admin_role()];
+        let token = service
+            .create_access_token(&scoped_user, scopes, time::Duration::hours(1))
+            .await
+            .unwrap();
+
+        // The tenant's own policy may evict the oldest sessions instead
+        tenants
+            .set_setting(
+                tenant.id,
+                AUTH_POLICY_SETTING,
+                serde_json::json!({ "session_limit_strategy": "evict_oldest" }),
+            )
+            .await
+            .unwrap();
+        let newest = service.authenticate(credentials).await.unwrap();
+        assert!(store.get_session(oldest.id).await.unwrap().is_none());
+        assert!(store.get_session(second.id).await.unwrap().is_some());
+        assert!(store.get_session(newest.id).await.unwrap().is_some());
+        assert!(store.get_session(token.id).await.unwrap().is_some());
+        assert_eq!(
+            service
+                .count_active_sessions(tenant.id, user.id)
+                .await
+                .unwrap(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tenant_switching() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let service =
+            AuthenticationService::new(repository.clone(), Box::new(InMemorySessionStore::new()));
+
+        let mut tenants = Vec::new();
+        for name in ["Acme", "Globex", "Initech"] {
+            let tenant = Tenant::new(name.to_string(), format!("{}.example.com", Uuid::new_v4()));
+            sqlx::query!(
+                r#"INSERT INTO tenants (id, name, domain, active) VALUES ($1, $2, $3, $4)"#,
+                tenant.id.0 as uuid::Uuid,
+                tenant.name,
+                tenant.domain,
+                tenant.active
+            )
+            .execute(&db.get_pool())
+            .await
+            .unwrap();
+            tenants.push(tenant);
+        }
+        let credentials = Credentials {
+            email: "jane@example.com".to_string(),
+            password: "password123".to_string(),
+            tenant_id: tenants[0].id,
+            mfa_code: None,
+        };
+        let jane = service.register_user(credentials.clone()).await.unwrap();
+        let globex_jane = repository
+            .create_user(User::new(
+                tenants[1].id,
+                "Jane@Example.com".to_string(),
+                AuthenticationService::hash_password("password123").unwrap(),
+            ))
+            .await
+            .unwrap();
+        let session = service.authenticate(credentials).await.unwrap();
+
+        // Users join the identity of their login address once they verified it
+        assert_eq!(service.list_memberships(&jane).await.unwrap().len(), 1);
+        for user in [&jane, &globex_jane] {
+            verify_login_address(&repository, user).await;
+        }
+
+        // Memberships are discovered by email and password at login
+        let memberships = service
+            .discover_memberships("JANE@example.com", "password123", &LoginContext::default())
+            .await
+            .unwrap();
+        let names: Vec<&str> = memberships.iter().map(|m| m.tenant_name.as_str()).collect();
+        assert_eq!(names, ["Acme", "Globex"]);
+        let memberships = service
+            .discover_memberships("JANE@example.com", "wrong", &LoginContext::default())
+            .await
+            .unwrap();
+        assert!(memberships.is_empty());
+
+        assert_eq!(service.list_memberships(&jane).await.unwrap().len(), 2);
+
+        let switched = service
+            .switch_tenant(&session, tenants[1].id, None)
+            .await
+            .unwrap();
+        assert_eq!(switched.tenant_id, tenants[1].id);
+        let user = service.current_user(&switched.token).await.unwrap();
+        assert_eq!(user.id, globex_jane.id);
+
+        // Tenants without a membership cannot be switched to
+        let result = service.switch_tenant(&session, tenants[2].id, None).await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+    }
+
+    #[tokio::test]
+    async fn test_tenant_switch_mfa_throttle() {
+        use crate::core::config::LoginThrottleConfig;
+
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let service =
+            AuthenticationService::new(repository.clone(), Box::new(InMemorySessionStore::new()))
+                .with_login_throttle(LoginThrottle::new(&LoginThrottleConfig {
+                    max_attempts: 20,
+                    window_secs: 60,
+                    max_failures: 3,
+                    lockout_secs: 300,
+                }));
+        let tenants = TenantRepository::new(db.get_pool());
+        let mut created = Vec::new();
+        for name in ["Acme", "Globex"] {
+            let tenant = tenants
+                .create_tenant(Tenant::new(
+                    name.to_string(),
+                    format!("{}.example.com", Uuid::new_v4()),
+                ))
+                .await
+                .unwrap();
+            created.push(tenant);
+        }
+        let credentials = Credentials {
+            email: "jane@example.com".to_string(),
+            password: "password123".to_string(),
+            tenant_id: created[0].id,
+            mfa_code: None,
+        };
+        let jane = service.register_user(credentials.clone()).await.unwrap();
+        let mut globex_jane = User::new(
+            created[1].id,
+            "jane@example.com".to_string(),
+            AuthenticationService::hash_password("password123").unwrap(),
+        );
+        let mfa_service = MfaService::new(MfaConfig::default());
+        let secret = mfa_service.generate_secret().unwrap();
+        globex_jane.mfa_enabled = true;
+        globex_jane.mfa_secret = Some(secret.clone());
+        let globex_jane = repository.create_user(globex_jane).await.unwrap();
+        for user in [&jane, &globex_jane] {
+            verify_login_address(&repository, user).await;
+        }
+        let session = service.authenticate(credentials).await.unwrap();
+
+        // Wrong codes of the target membership lock it out like failed logins
+        let code = mfa_service
+            .create_totp(&secret)
+            .unwrap()
+            .generate_current()
+            .unwrap();
+        let wrong = if code == "000000" { "111111" } else { "000000" };
+        for _ in 0..2 {
+            let result = service
+                .switch_tenant(&session, created[1].id, Some(wrong))
+                .await;
+            assert!(matches!(result, Err(Error::Authentication(_))));
+        }
+        let result = service
+            .switch_tenant(&session, created[1].id, Some(wrong))
+            .await;
+        assert!(matches!(result, Err(Error::LockedOut(_))));
+        let result = service
+            .switch_tenant(&session, created[1].id, Some(&code))
+            .await;
+        assert!(matches!(result, Err(Error::LockedOut(_))));
+    }
+
+    /// Verifies the login address of a user as if they followed the mailed link
+    async fn verify_login_address(repository: &UserRepository, user: &User) {
+        let emails = repository
+            .list_user_emails(user.id, user.tenant_id)
+            .await
+            .unwrap();
+        let primary = emails.iter().find(|e| e.is_primary).unwrap();
+        repository
+            .mark_email_verified(primary.id, user.id, user.tenant_id)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_admin_created_address_cannot_switch_tenants() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let service =
+            AuthenticationService::new(repository.clone(), Box::new(InMemorySessionStore::new()));
+        let identity = IdentityModule::new(repository.clone());
+        let tenants = TenantRepository::new(db.get_pool());
+        let mut created = Vec::new();
+        for name in ["Acme", "Globex"] {
+            let tenant = tenants
+                .create_tenant(Tenant::new(
+                    name.to_string(),
+                    format!("{}.example.com", Uuid::new_v4()),
+                ))
+                .await
+                .unwrap();
+            created.push(tenant);
+        }
+        let (acme, globex) = (&created[0], &created[1]);
+
+        // The victim verified their address in Globex
+        let victim = repository
+            .create_user(User::new(
+                globex.id,
+                "victim@corp.com".to_string(),
+                AuthenticationService::hash_password("victim-password").unwrap(),
+            ))
+            .await
+            .unwrap();
+        verify_login_address(&repository, &victim).await;
+
+        // Acme's admin creates a user with the victim's address and a password they know
+        let mut admin = User::new(
+            acme.id,
+            "admin@acme.example.com".to_string(),
+            "hash".to_string(),
+        );
+        admin.roles.push(create_admin_role());
+        let admin = repository.create_user(admin).await.unwrap();
+        let impostor = identity
+            .create_managed_user(
+                &admin,
+                &User::new(
+                    acme.id,
+                    "Victim@Corp.com".to_string(),
+                    AuthenticationService::hash_password("known-password").unwrap(),
+                ),
+            )
+            .await
+            .unwrap();
+        let session = service
+            .authenticate(Credentials {
+                email: "victim@corp.com".to_string(),
+                password: "known-password".to_string(),
+                tenant_id: acme.id,
+                mfa_code: None,
+            })
+            .await
+            .unwrap();
+
+        // The unverified address links nothing, so Globex stays out of reach
+        let memberships = service.list_memberships(&impostor).await.unwrap();
+        assert_eq!(memberships.len(), 1);
+        assert_eq!(memberships[0].tenant_id, acme.id);
+        let result = service.switch_tenant(&session, globex.id, None).await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+        let memberships = service
+            .discover_memberships(
+                "victim@corp.com",
+                "known-password",
+                &LoginContext::default(),
+            )
+            .await
+            .unwrap();
+        assert!(memberships.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_api_keys() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let service =
+            AuthenticationService::new(repository.clone(), Box::new(InMemorySessionStore::new()))
+                .with_api_keys(ApiKeyRepository::new(db.get_pool()));
+
+        let tenant = Tenant::new(
+            "Test Tenant".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        sqlx::query!(
+            r#"INSERT INTO tenants (id, name, domain, active) VALUES ($1, $2, $3, $4)"#,
+            tenant.id.0 as uuid::Uuid,
+            tenant.name,
+            tenant.domain,
+            tenant.active
+        )
+        .execute(&db.get_pool())
+        .await
+        .unwrap();
+        let mut admin = User::new(
+            tenant.id,
+            "admin@example.com".to_string(),
+            "hash".to_string(),
+        );
+        admin.roles.push(create_admin_role());
+        let admin = repository.create_user(admin).await.unwrap();
+
+        let scopes = TokenScope::parse_list("read:users").unwrap();
+        let quota = ApiKeyQuota {
+            requests_per_day: Some(1000),
+            endpoint_budgets: HashMap::from([("GET /tenants/:tenant_id/users".to_string(), 100)]),
+        };
+        let (api_key, key) = service
+            .create_api_key(
+                &admin,
+                "CRM sync".to_string(),
+                scopes.clone(),
+                quota.clone(),
+                None,
+            )
+            .await
+            .unwrap();
+        let listed = service.list_api_keys(&admin).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, api_key.id);
+        assert_eq!(listed[0].quota, quota);
+
+        // Quotas without any allowed requests are rejected
+        let result = service
+            .create_api_key(
+                &admin,
+                "Disabled".to_string(),
+                scopes.clone(),
+                ApiKeyQuota {
+                    requests_per_day: Some(0),
+                    ..Default::default()
+                },
+                None,
+            )
+            .await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        // Quotas are not counted without a tracker
+        let usage = service
+            .consume_api_key_quota(&key, "GET /tenants/:tenant_id/users")
+            .await
+            .unwrap();
+        assert!(usage.is_none());
+
+        // Requests with the key act as its creator, limited to the key's scopes
+        let user = service.current_user(&key).await.unwrap();
+        assert_eq!(user.id, admin.id);
+        assert_eq!(user.token_scopes, Some(scopes.clone()));
+        let listed = service.list_api_keys(&admin).await.unwrap();
+        assert!(listed[0].last_used_at.is_some());
+
+        // Keys cannot mint further keys, and scopes the admin lacks cannot be delegated
+        let result = service
+            .create_api_key(
+                &user,
+                "Nested".to_string(),
+                scopes,
+                ApiKeyQuota::default(),
+                None,
+            )
+            .await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+        let result = service
+            .create_api_key(
+                &admin,
+                "Projects".to_string(),
+                TokenScope::parse_list("delete:projects").unwrap(),
+                ApiKeyQuota::default(),
+                None,
+            )
+            .await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+
+        service.revoke_api_key(&admin, api_key.id).await.unwrap();
+        assert!(service.current_user(&key).await.is_err());
+        assert!(service.list_api_keys(&admin).await.unwrap().is_empty());
+        let result = service.revoke_api_key(&admin, api_key.id).await;
+        assert!(matches!(result, Err(Error::NotFound(_))));
+    }
 }
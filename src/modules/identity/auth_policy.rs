@@ -0,0 +1,380 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+use super::{session::SessionLifetime, throttle::LockoutPolicy};
+use crate::{
+    core::config::{LoginThrottleConfig, SessionConfig, SessionLimitStrategy},
+    modules::{signup::service::MIN_PASSWORD_LENGTH, tenant::service::TenantService},
+    shared::{
+        error::{Error, Result},
+        types::TenantId,
+    },
+};
+
+/// Key of the tenant setting overriding the global authentication policy
+pub const AUTH_POLICY_SETTING: &str = "auth_policy";
+
+/// Longest minimum password length a tenant can require
+const MAX_PASSWORD_MIN_LENGTH: usize = 128;
+
+/// Requirements passwords have to meet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: MIN_PASSWORD_LENGTH,
+            require_uppercase: false,
+            require_lowercase: false,
+            require_digit: false,
+            require_symbol: false,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Ensures a password meets the policy
+    pub fn validate(&self, password: &str) -> Result<()> {
+        if password.chars().count() < self.min_length {
+            return Err(Error::Validation(format!(
+                "Password must have at least {} characters",
+                self.min_length
+            )));
+        }
+
+        let mut missing = Vec::new();
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            missing.push("an uppercase letter");
+        }
+        if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+            missing.push("a lowercase letter");
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            missing.push("a digit");
+        }
+        if self.require_symbol && password.chars().all(char::is_alphanumeric) {
+            missing.push("a symbol");
+        }
+        if !missing.is_empty() {
+            return Err(Error::Validation(format!(
+                "Password must contain {}",
+                missing.join(", ")
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Whether users have to log in with a second factor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MfaRequirement {
+    /// Users decide whether they set up MFA
+    #[default]
+    Optional,
+    /// Password logins of users without MFA are refused
+    Required,
+}
+
+/// Concurrent login sessions allowed per user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionLimit {
+    pub max_sessions: usize,
+    pub strategy: SessionLimitStrategy,
+}
+
+impl SessionLimit {
+    /// Creates the limit configured for sessions, `None` if sessions are unlimited
+    pub fn from_config(config: &SessionConfig) -> Option<Self> {
+        config.max_sessions.map(|max_sessions| Self {
+            max_sessions,
+            strategy: config.session_limit_strategy,
+        })
+    }
+}
+
+/// Authentication policy in effect for a tenant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuthPolicy {
+    pub password: PasswordPolicy,
+    pub mfa: MfaRequirement,
+    pub session_lifetime: SessionLifetime,
+    pub lockout: LockoutPolicy,
+    /// Concurrent login sessions allowed per user; `None` allows any number
+    pub session_limit: Option<SessionLimit>,
+}
+
+impl Default for AuthPolicy {
+    fn default() -> Self {
+        Self {
+            password: PasswordPolicy::default(),
+            mfa: MfaRequirement::default(),
+            session_lifetime: SessionLifetime::default(),
+            lockout: LockoutPolicy::from(&LoginThrottleConfig::default()),
+            session_limit: None,
+        }
+    }
+}
+
+impl AuthPolicy {
+    /// Applies a tenant's overrides on top of this policy
+    pub fn with_overrides(mut self, overrides: &AuthPolicyOverrides) -> Self {
+        if let Some(min_length) = overrides.password_min_length {
+            self.password.min_length = min_length;
+        }
+        if let Some(required) = overrides.password_require_uppercase {
+            self.password.require_uppercase = required;
+        }
+        if let Some(required) = overrides.password_require_lowercase {
+            self.password.require_lowercase = required;
+        }
+        if let Some(required) = overrides.password_require_digit {
+            self.password.require_digit = required;
+        }
+        if let Some(required) = overrides.password_require_symbol {
+            self.password.require_symbol = required;
+        }
+        if let Some(mfa) = overrides.mfa {
+            self.mfa = mfa;
+        }
+        if let Some(secs) = overrides.session_lifetime_secs {
+            self.session_lifetime.max = time::Duration::seconds(secs as i64);
+        }
+        if let Some(secs) = overrides.session_idle_timeout_secs {
+            self.session_lifetime.idle_timeout = Some(time::Duration::seconds(secs as i64));
+        }
+        if let Some(max_failures) = overrides.max_failed_logins {
+            self.lockout.max_failures = max_failures;
+        }
+        if let Some(secs) = overrides.lockout_secs {
+            self.lockout.lockout = Duration::from_secs(secs);
+        }
+        if let Some(max_sessions) = overrides.max_sessions {
+            let strategy = self
+                .session_limit
+                .map(|limit| limit.strategy)
+                .unwrap_or_default();
+            self.session_limit = Some(SessionLimit {
+                max_sessions,
+                strategy,
+            });
+        }
+        if let (Some(limit), Some(strategy)) =
+            (&mut self.session_limit, overrides.session_limit_strategy)
+        {
+            limit.strategy = strategy;
+        }
+        self
+    }
+
+    /// Gets the policy in effect for a tenant, this one with the tenant's overrides applied
+    ///
+    /// Invalid overrides are ignored with a warning, leaving the tenant on this policy.
+    pub async fn for_tenant(&self, tenants: &TenantService, tenant_id: TenantId) -> Result<Self> {
+        let settings = tenants.settings(tenant_id).await?;
+        let Some(value) = settings.get_value(AUTH_POLICY_SETTING) else {
+            return Ok(*self);
+        };
+        match AuthPolicyOverrides::from_setting(value) {
+            Ok(overrides) => Ok(self.with_overrides(&overrides)),
+            Err(e) => {
+                tracing::warn!(
+                    "Ignoring authentication policy of tenant {}: {}",
+                    tenant_id.0,
+                    e
+                );
+                Ok(*self)
+            },
+        }
+    }
+}
+
+/// Overrides of the global authentication policy, set with the tenant's `auth_policy` setting
+///
+/// Unset fields keep the global policy, e.g.
+/// `{"mfa": "required", "password_min_length": 12, "session_lifetime_secs": 28800}`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthPolicyOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_min_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_require_uppercase: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_require_lowercase: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_require_digit: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_require_symbol: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mfa: Option<MfaRequirement>,
+    /// Seconds a session lives at most
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_lifetime_secs: Option<u64>,
+    /// Seconds without requests after which a session expires
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_idle_timeout_secs: Option<u64>,
+    /// Consecutive failed logins locking a user out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_failed_logins: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lockout_secs: Option<u64>,
+    /// Concurrent login sessions allowed per user
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_sessions: Option<usize>,
+    /// What happens to a login exceeding the session limit, if the tenant has one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_limit_strategy: Option<SessionLimitStrategy>,
+}
+
+impl AuthPolicyOverrides {
+    /// Parses the value of an `auth_policy` setting, ensuring the overrides are within bounds
+    pub fn from_setting(value: &Value) -> Result<Self> {
+        let overrides: Self = serde_json::from_value(value.clone()).map_err(|e| {
+            Error::Validation(format!("Invalid {} setting: {}", AUTH_POLICY_SETTING, e))
+        })?;
+        if overrides
+            .password_min_length
+            .is_some_and(|length| length == 0 || length > MAX_PASSWORD_MIN_LENGTH)
+        {
+            return Err(Error::Validation(format!(
+                "Minimum password length must be between 1 and {}",
+                MAX_PASSWORD_MIN_LENGTH
+            )));
+        }
+        let durations = [
+            overrides.session_lifetime_secs,
+            overrides.session_idle_timeout_secs,
+            overrides.lockout_secs,
+        ];
+        if durations
+            .iter()
+            .any(|secs| secs.is_some_and(|secs| secs == 0 || secs > i64::MAX as u64))
+            || overrides.max_failed_logins == Some(0)
+            || overrides.max_sessions == Some(0)
+        {
+            return Err(Error::Validation(
+                "Session lifetimes, lockouts, failed login and session limits must be positive"
+                    .to_string(),
+            ));
+        }
+        Ok(overrides)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_password_policy() {
+        let policy = PasswordPolicy::default();
+        assert!(policy.validate("password").is_ok());
+        assert!(policy.validate("short").is_err());
+
+        let strict = PasswordPolicy {
+            min_length: 10,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_digit: true,
+            require_symbol: true,
+        };
+        assert!(strict.validate("Correct-Horse-1").is_ok());
+        assert!(strict.validate("Horse-1").is_err());
+        match strict.validate("correcthorsebattery") {
+            Err(Error::Validation(message)) => {
+                assert_eq!(
+                    message,
+                    "Password must contain an uppercase letter, a digit, a symbol"
+                )
+            },
+            other => panic!("Expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_policy_overrides() {
+        let global = AuthPolicy::default();
+        let overrides = AuthPolicyOverrides::from_setting(&json!({
+            "mfa": "required",
+            "password_min_length": 12,
+            "session_idle_timeout_secs": 900,
+            "max_failed_logins": 3,
+        }))
+        .unwrap();
+        let policy = global.with_overrides(&overrides);
+
+        assert_eq!(policy.mfa, MfaRequirement::Required);
+        assert_eq!(policy.password.min_length, 12);
+        assert_eq!(
+            policy.session_lifetime.idle_timeout,
+            Some(time::Duration::minutes(15))
+        );
+        assert_eq!(policy.session_lifetime.max, global.session_lifetime.max);
+        assert_eq!(policy.lockout.max_failures, 3);
+        assert_eq!(policy.lockout.lockout, global.lockout.lockout);
+        assert_eq!(policy.session_limit, None);
+
+        // Tenants can limit sessions themselves or change how the global limit applies
+        let overrides = AuthPolicyOverrides::from_setting(&json!({ "max_sessions": 3 })).unwrap();
+        assert_eq!(
+            global.with_overrides(&overrides).session_limit,
+            Some(SessionLimit {
+                max_sessions: 3,
+                strategy: SessionLimitStrategy::Reject,
+            })
+        );
+        let limited = AuthPolicy {
+            session_limit: Some(SessionLimit {
+                max_sessions: 5,
+                strategy: SessionLimitStrategy::Reject,
+            }),
+            ..global
+        };
+        let overrides = AuthPolicyOverrides::from_setting(&json!({
+            "session_limit_strategy": "evict_oldest",
+        }))
+        .unwrap();
+        assert_eq!(
+            limited.with_overrides(&overrides).session_limit,
+            Some(SessionLimit {
+                max_sessions: 5,
+                strategy: SessionLimitStrategy::EvictOldest,
+            })
+        );
+
+        // Without overrides the global policy applies unchanged
+        assert_eq!(
+            global.with_overrides(&AuthPolicyOverrides::default()),
+            global
+        );
+    }
+
+    #[test]
+    fn test_invalid_policy_overrides() {
+        for value in [
+            json!({ "mfa": "sometimes" }),
+            json!({ "password_min_lenght": 12 }),
+            json!({ "password_min_length": 0 }),
+            json!({ "session_lifetime_secs": 0 }),
+            json!({ "max_failed_logins": 0 }),
+            json!({ "max_sessions": 0 }),
+            json!({ "session_limit_strategy": "evict_newest" }),
+            json!("required"),
+        ] {
+            assert!(matches!(
+                AuthPolicyOverrides::from_setting(&value),
+                Err(Error::Validation(_))
+            ));
+        }
+    }
+}
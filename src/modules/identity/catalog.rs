@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{
+    modules::identity::{
+        models::{Permission, PermissionAction},
+        rbac::resource_matches,
+    },
+    shared::error::{Error, Result},
+};
+
+/// Resource listed in the permission catalog
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CatalogResource {
+    pub resource: String,
+    pub description: String,
+    /// Actions permissions on the resource may grant
+    pub actions: Vec<PermissionAction>,
+}
+
+/// Registry of the valid action and resource pairs of permissions
+///
+/// Modules register their resources at startup; custom roles may only contain
+/// permissions whose resource pattern covers a registered resource supporting the action.
+#[derive(Debug, Clone, Default)]
+pub struct PermissionCatalog {
+    resources: BTreeMap<String, CatalogResource>,
+}
+
+impl PermissionCatalog {
+    /// Creates an empty catalog
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a catalog with the resources of the built-in modules
+    pub fn builtin() -> Self {
+        use PermissionAction::*;
+
+        Self::new()
+            .register(
+                "users",
+                "Users of a tenant",
+                &[Create, Read, Update, Delete, List],
+            )
+            .register(
+                "roles",
+                "Roles of a tenant",
+                &[Create, Read, Update, Delete, List],
+            )
+            .register("audit_log", "Audit log of a tenant", &[Read, List])
+            .register(
+                "projects",
+                "Projects of a tenant",
+                &[Create, Read, Update, Delete, List],
+            )
+            .register("tenants", "Tenants", &[Create, Read, Update, Delete, List])
+    }
+
+    /// Registers a resource; registering it again adds the actions
+    pub fn register(
+        mut self,
+        resource: &str,
+        description: &str,
+        actions: &[PermissionAction],
+    ) -> Self {
+        let entry = self
+            .resources
+            .entry(resource.to_string())
+            .or_insert_with(|| CatalogResource {
+                resource: resource.to_string(),
+                description: description.to_string(),
+                actions: Vec::new(),
+            });
+        for action in actions {
+            if !entry.actions.contains(action) {
+                entry.actions.push(*action);
+            }
+        }
+        self
+    }
+
+    /// Lists the registered resources ordered by name
+    pub fn resources(&self) -> Vec<CatalogResource> {
+        self.resources.values().cloned().collect()
+    }
+
+    /// Checks if a permission on a resource pattern refers to a registered resource
+    pub fn contains(&self, action: PermissionAction, resource: &str) -> bool {
+        self.resources.values().any(|entry| {
+            entry.actions.contains(&action)
+                && (resource_matches(resource, &entry.resource)
+                    || resource_matches(&entry.resource, resource))
+        })
+    }
+
+    /// Ensures all permissions refer to registered resources and actions
+    pub fn validate(&self, permissions: &[Permission]) -> Result<()> {
+        if let Some(permission) = permissions
+            .iter()
+            .find(|p| !self.contains(p.action, &p.resource))
+        {
+            return Err(Error::Validation(format!(
+                "Unknown permission {} on {}",
+                permission.action, permission.resource
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::identity::rbac::{
+        create_admin_role, create_super_admin_role, create_user_role,
+    };
+
+    #[test]
+    fn test_permission_catalog() {
+        let catalog = PermissionCatalog::builtin();
+        for role in [
+            create_user_role(),
+            create_admin_role(),
+            create_super_admin_role(),
+        ] {
+            assert!(catalog.validate(&role.permissions).is_ok());
+        }
+
+        assert!(catalog.contains(PermissionAction::Read, "audit_log"));
+        assert!(!catalog.contains(PermissionAction::Delete, "audit_log"));
+        assert!(!catalog.contains(PermissionAction::Read, "invoices"));
+
+        // Modules register further resources and actions
+        let catalog = catalog.register(
+            "invoices",
+            "Invoices of a tenant",
+            &[PermissionAction::Execute],
+        );
+        let refund = Permission::new(
+            "Execute Refund".to_string(),
+            PermissionAction::Execute,
+            "invoices".to_string(),
+        );
+        assert!(catalog.validate(&[refund]).is_ok());
+        let typo = Permission::new(
+            "Read Invoice".to_string(),
+            PermissionAction::Read,
+            "invoice".to_string(),
+        );
+        assert!(matches!(
+            catalog.validate(&[typo]),
+            Err(Error::Validation(_))
+        ));
+        assert_eq!(catalog.resources().len(), 6);
+    }
+}
@@ -0,0 +1,1300 @@
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRef, FromRequestParts, Path, Query, State},
+    http::{header::AUTHORIZATION, request::Parts, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    routing::{delete, get, post},
+    Json, Router,
+};
+use futures::stream::{self, Stream};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    modules::identity::{
+        auth::AuthenticationService,
+        hooks::LoginContext,
+        jwt_keys::JwtKeyRing,
+        models::{
+            AccessTokenRequest, AccessTokenResponse, ActivityQuery, AddEmailRequest, ApiKeyRequest,
+            ApiKeyResponse, AuthorizationAuditQuery, CreatedApiKeyResponse, Credentials,
+            EmailVerificationRequest, LoginRequest, LoginResponse, MembershipDiscoveryRequest,
+            MembershipResponse, MfaLoginRequest, OwnerType, PolicySimulationRequest, ResourceOwner,
+            RoleAssignmentRequest, RoleRequest, SessionRefreshRequest, SessionRefreshResponse,
+            TenantSwitchRequest, TenantSwitchResponse, TokenScope, User, UserOverviewQuery,
+            UserResponse, UserSessionResponse,
+        },
+        rbac::{
+            action, resource, RbacService, RequirePermission, RequiredAction, RequiredResource,
+        },
+        service::IdentityModule,
+        session::Session,
+    },
+    shared::{
+        audit::AuditCategory,
+        error::{Error, Result},
+        types::{TenantId, UserId},
+    },
+};
+
+/// Header carrying the ID of a request, recorded with its authorization decisions
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Shared state of the identity handlers
+#[derive(Clone, FromRef)]
+pub struct IdentityState {
+    pub auth: Arc<AuthenticationService>,
+    pub identity: Arc<IdentityModule>,
+    pub rbac: Arc<RbacService>,
+}
+
+/// User authenticated by the bearer token of the request
+pub struct AuthenticatedUser(pub User);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthenticatedUser
+where
+    Arc<AuthenticationService>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        let token = bearer_token(parts)?;
+        let service = Arc::<AuthenticationService>::from_ref(state);
+        Ok(Self(service.current_user(token).await?))
+    }
+}
+
+/// Session of the bearer token of the request
+pub struct AuthenticatedSession(pub Session);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthenticatedSession
+where
+    Arc<AuthenticationService>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        let token = bearer_token(parts)?;
+        let service = Arc::<AuthenticationService>::from_ref(state);
+        Ok(Self(service.current_session(token).await?))
+    }
+}
+
+/// Gets the bearer token of a request
+fn bearer_token(parts: &Parts) -> Result<&str> {
+    parts
+        .headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| Error::Authentication("Missing bearer token".to_string()))
+}
+
+#[async_trait]
+impl<S, A, R> FromRequestParts<S> for RequirePermission<A, R>
+where
+    Arc<AuthenticationService>: FromRef<S>,
+    Arc<RbacService>: FromRef<S>,
+    S: Send + Sync,
+    A: RequiredAction,
+    R: RequiredResource,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        let AuthenticatedUser(user) = AuthenticatedUser::from_request_parts(parts, state).await?;
+        let rbac = Arc::<RbacService>::from_ref(state);
+        let request_id = parts
+            .headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok());
+        if !rbac
+            .check_request_permission(&user, A::ACTION, R::RESOURCE, None, request_id)
+            .await?
+        {
+            return Err(Error::Authorization(format!(
+                "Missing permission to {} {}",
+                A::ACTION,
+                R::RESOURCE
+            )));
+        }
+        Ok(Self::new(user))
+    }
+}
+
+/// Default lifetime of personal access tokens in days
+const DEFAULT_ACCESS_TOKEN_DAYS: i64 = 30;
+
+/// Maximum lifetime of personal access tokens in days
+const MAX_ACCESS_TOKEN_DAYS: i64 = 365;
+
+/// Parses the tenant ID from the request path and ensures the actor belongs to it
+fn parse_actor_tenant(actor: &User, tenant_id: &str) -> Result<TenantId> {
+    let tenant_id = Uuid::parse_str(tenant_id)
+        .map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+    if actor.tenant_id.0 != tenant_id {
+        return Err(Error::Authorization("Access to tenant denied".to_string()));
+    }
+    Ok(TenantId(tenant_id))
+}
+
+/// Parses a role ID from the request path
+fn parse_role_id(role_id: &str) -> Result<Uuid> {
+    Uuid::parse_str(role_id).map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))
+}
+
+/// Parses the tenant and user IDs from the request path
+fn parse_user_path(tenant_id: &str, user_id: &str) -> Result<(TenantId, UserId)> {
+    let tenant_id = Uuid::parse_str(tenant_id)
+        .map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+    let user_id = Uuid::parse_str(user_id)
+        .map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+    Ok((TenantId(tenant_id), UserId(user_id)))
+}
+
+/// Builds a resource owner from the request path
+fn parse_resource_owner(
+    tenant_id: TenantId,
+    resource_type: String,
+    resource_id: String,
+    owner_type: &str,
+    owner_id: &str,
+) -> Result<ResourceOwner> {
+    let owner_type: OwnerType = owner_type.parse()?;
+    let owner_id = Uuid::parse_str(owner_id)
+        .map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+    Ok(ResourceOwner::new(
+        tenant_id,
+        resource_type,
+        resource_id,
+        owner_type,
+        owner_id,
+    ))
+}
+
+/// Lists the users of a tenant with login, session and MFA details
+pub async fn list_users(
+    State(auth): State<Arc<AuthenticationService>>,
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(tenant_id): Path<String>,
+    Query(query): Query<UserOverviewQuery>,
+) -> Result<impl IntoResponse> {
+    parse_actor_tenant(&actor, &tenant_id)?;
+    let page = identity.list_user_overview(&actor, &auth, query).await?;
+    Ok((StatusCode::OK, Json(page)))
+}
+
+/// Deactivates a user and revokes their sessions
+pub async fn deactivate_user(
+    State(service): State<Arc<AuthenticationService>>,
+    RequirePermission(actor, _): RequirePermission<action::Update, resource::Users>,
+    Path((tenant_id, user_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    parse_actor_tenant(&actor, &tenant_id)?;
+    let (tenant_id, user_id) = parse_user_path(&tenant_id, &user_id)?;
+    let user = service.deactivate_user(user_id, tenant_id).await?;
+    Ok((StatusCode::OK, Json(UserResponse::from(user))))
+}
+
+/// Lists a page of a user's security relevant activity, newest first
+pub async fn list_user_activity(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, user_id)): Path<(String, String)>,
+    Query(query): Query<ActivityQuery>,
+) -> Result<impl IntoResponse> {
+    parse_actor_tenant(&actor, &tenant_id)?;
+    let (_, user_id) = parse_user_path(&tenant_id, &user_id)?;
+    let page = identity.list_user_activity(&actor, user_id, query).await?;
+    Ok((StatusCode::OK, Json(page)))
+}
+
+/// Revokes the sessions of all users of a tenant
+pub async fn revoke_tenant_sessions(
+    State(service): State<Arc<AuthenticationService>>,
+    RequirePermission(actor, _): RequirePermission<action::Update, resource::Users>,
+    Path(tenant_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let tenant_id = parse_actor_tenant(&actor, &tenant_id)?;
+    service.revoke_tenant_sessions(tenant_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lists the sessions of the current user, e.g. their signed in devices
+pub async fn list_own_sessions(
+    State(service): State<Arc<AuthenticationService>>,
+    AuthenticatedSession(current): AuthenticatedSession,
+) -> Result<impl IntoResponse> {
+    let mut sessions = service
+        .list_user_sessions(current.tenant_id, current.user_id)
+        .await?;
+    sessions.retain(|session| !session.is_expired());
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.created_at));
+    let sessions: Vec<UserSessionResponse> = sessions
+        .iter()
+        .map(|session| UserSessionResponse::new(session, current.id))
+        .collect();
+    Ok((StatusCode::OK, Json(sessions)))
+}
+
+/// Revokes one of the current user's sessions
+pub async fn revoke_own_session(
+    State(service): State<Arc<AuthenticationService>>,
+    AuthenticatedSession(current): AuthenticatedSession,
+    Path(session_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let session_id = Uuid::parse_str(&session_id)
+        .map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+    service
+        .revoke_user_session(current.tenant_id, current.user_id, session_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Ensures the email addresses of a user are managed by the user themselves rather than a scoped token
+fn ensure_unscoped(user: &User) -> Result<()> {
+    if user.token_scopes.is_some() {
+        return Err(Error::Authorization(
+            "Scoped tokens cannot manage email addresses".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Lists the email addresses of the current user
+pub async fn list_own_emails(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(user): AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    let emails = identity.list_emails(user.id, user.tenant_id).await?;
+    Ok((StatusCode::OK, Json(emails)))
+}
+
+/// Adds an email address to the current user, mailing it a verification link
+pub async fn add_own_email(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(user): AuthenticatedUser,
+    Json(request): Json<AddEmailRequest>,
+) -> Result<impl IntoResponse> {
+    ensure_unscoped(&user)?;
+    let email = identity
+        .add_email(user.id, user.tenant_id, request.email)
+        .await?;
+    Ok((StatusCode::CREATED, Json(email)))
+}
+
+/// Verifies an email address of the current user with the token mailed to it
+pub async fn verify_own_email(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(user): AuthenticatedUser,
+    Json(request): Json<EmailVerificationRequest>,
+) -> Result<impl IntoResponse> {
+    ensure_unscoped(&user)?;
+    let email = identity
+        .verify_email(user.id, user.tenant_id, &request.token)
+        .await?;
+    Ok((StatusCode::OK, Json(email)))
+}
+
+/// Mails a new verification link to an unverified email address of the current user
+pub async fn resend_own_email_verification(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(user): AuthenticatedUser,
+    Path(email_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    ensure_unscoped(&user)?;
+    let email_id = Uuid::parse_str(&email_id)
+        .map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+    identity
+        .resend_email_verification(user.id, user.tenant_id, email_id)
+        .await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Makes a verified email address the current user's primary (login) address
+pub async fn set_own_primary_email(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(user): AuthenticatedUser,
+    Path(email_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    ensure_unscoped(&user)?;
+    let email_id = Uuid::parse_str(&email_id)
+        .map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+    identity
+        .set_primary_email(user.id, user.tenant_id, email_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Removes a non-primary email address from the current user
+pub async fn remove_own_email(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(user): AuthenticatedUser,
+    Path(email_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    ensure_unscoped(&user)?;
+    let email_id = Uuid::parse_str(&email_id)
+        .map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+    identity
+        .remove_email(user.id, user.tenant_id, email_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Signs the current user out of all other sessions
+pub async fn revoke_other_sessions(
+    State(service): State<Arc<AuthenticationService>>,
+    AuthenticatedSession(current): AuthenticatedSession,
+) -> Result<impl IntoResponse> {
+    service.revoke_other_sessions(&current).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Logs the current user out everywhere, including the current session
+pub async fn revoke_all_sessions(
+    State(service): State<Arc<AuthenticationService>>,
+    AuthenticatedSession(current): AuthenticatedSession,
+) -> Result<impl IntoResponse> {
+    service
+        .revoke_all_sessions(current.tenant_id, current.user_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Reactivates a user
+pub async fn activate_user(
+    State(service): State<Arc<AuthenticationService>>,
+    RequirePermission(actor, _): RequirePermission<action::Update, resource::Users>,
+    Path((tenant_id, user_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    parse_actor_tenant(&actor, &tenant_id)?;
+    let (tenant_id, user_id) = parse_user_path(&tenant_id, &user_id)?;
+    let user = service.activate_user(user_id, tenant_id).await?;
+    Ok((StatusCode::OK, Json(UserResponse::from(user))))
+}
+
+/// Issues a personal access token limited to the requested scopes
+pub async fn create_access_token(
+    State(auth): State<Arc<AuthenticationService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Json(request): Json<AccessTokenRequest>,
+) -> Result<impl IntoResponse> {
+    let days = request.expires_in_days.unwrap_or(DEFAULT_ACCESS_TOKEN_DAYS);
+    if !(1..=MAX_ACCESS_TOKEN_DAYS).contains(&days) {
+        return Err(Error::Validation(format!(
+            "Token lifetime must be between 1 and {} days",
+            MAX_ACCESS_TOKEN_DAYS
+        )));
+    }
+    let scopes = TokenScope::parse_list(&request.scope)?;
+    let session = auth
+        .create_access_token(&actor, scopes, time::Duration::days(days))
+        .await?;
+    let response = AccessTokenResponse {
+        scope: TokenScope::format_list(session.scopes.as_deref().unwrap_or_default()),
+        token: session.token,
+        expires_at: session.expires_at,
+    };
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Logs in to a tenant with email address, password and, if set up, an MFA code
+pub async fn login(
+    State(auth): State<Arc<AuthenticationService>>,
+    headers: HeaderMap,
+    Json(request): Json<LoginRequest>,
+) -> Result<impl IntoResponse> {
+    let context = LoginContext::from_headers(&headers);
+    let session = auth
+        .authenticate_with_context(request.into(), context)
+        .await?;
+    let response = login_response(&auth, session).await?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Logs in to a tenant with the password and second factor of a user with MFA
+pub async fn login_mfa(
+    State(auth): State<Arc<AuthenticationService>>,
+    headers: HeaderMap,
+    Json(request): Json<MfaLoginRequest>,
+) -> Result<impl IntoResponse> {
+    let context = LoginContext::from_headers(&headers);
+    let credentials = Credentials {
+        email: request.email,
+        password: request.password,
+        tenant_id: request.tenant_id,
+        mfa_code: None,
+    };
+    let session = auth
+        .authenticate_with_mfa(credentials, request.mfa_code, context)
+        .await?;
+    let response = login_response(&auth, session).await?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Describes the session of a login, with a refresh token if they are enabled
+async fn login_response(auth: &AuthenticationService, session: Session) -> Result<LoginResponse> {
+    let refresh_token = if auth.refresh_tokens_enabled() {
+        Some(auth.issue_refresh_token(&session).await?)
+    } else {
+        None
+    };
+    Ok(LoginResponse {
+        token: session.token,
+        refresh_token,
+        tenant_id: session.tenant_id,
+        user_id: session.user_id,
+        expires_at: session.expires_at,
+    })
+}
+
+/// Lists the tenants an email address and password can log in to, for picking one at login
+///
+/// Failed discoveries count against the login throttle like failed logins.
+pub async fn discover_memberships(
+    State(auth): State<Arc<AuthenticationService>>,
+    headers: HeaderMap,
+    Json(request): Json<MembershipDiscoveryRequest>,
+) -> Result<impl IntoResponse> {
+    let context = LoginContext::from_headers(&headers);
+    let memberships: Vec<MembershipResponse> = auth
+        .discover_memberships(&request.email, &request.password, &context)
+        .await?
+        .into_iter()
+        .map(|membership| MembershipResponse::new(membership, None))
+        .collect();
+    Ok((StatusCode::OK, Json(memberships)))
+}
+
+/// Lists the tenants the current user can switch to
+pub async fn list_memberships(
+    State(auth): State<Arc<AuthenticationService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    let memberships: Vec<MembershipResponse> = auth
+        .list_memberships(&actor)
+        .await?
+        .into_iter()
+        .map(|membership| MembershipResponse::new(membership, Some(actor.tenant_id)))
+        .collect();
+    Ok((StatusCode::OK, Json(memberships)))
+}
+
+/// Opens a session in another tenant of the current user's identity
+pub async fn switch_tenant(
+    State(auth): State<Arc<AuthenticationService>>,
+    AuthenticatedSession(current): AuthenticatedSession,
+    Json(request): Json<TenantSwitchRequest>,
+) -> Result<impl IntoResponse> {
+    let session = auth
+        .switch_tenant(&current, request.tenant_id, request.mfa_code.as_deref())
+        .await?;
+    let refresh_token = if auth.refresh_tokens_enabled() {
+        Some(auth.issue_refresh_token(&session).await?)
+    } else {
+        None
+    };
+    let response = TenantSwitchResponse {
+        token: session.token,
+        refresh_token,
+        tenant_id: session.tenant_id,
+        user_id: session.user_id,
+        expires_at: session.expires_at,
+    };
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Exchanges a refresh token for a new session and the next refresh token
+pub async fn refresh_session(
+    State(auth): State<Arc<AuthenticationService>>,
+    Json(request): Json<SessionRefreshRequest>,
+) -> Result<impl IntoResponse> {
+    if !auth.refresh_tokens_enabled() {
+        return Err(Error::NotFound(
+            "Refresh tokens are not enabled".to_string(),
+        ));
+    }
+    let (session, refresh_token) = auth.refresh_session(&request.refresh_token).await?;
+    let response = SessionRefreshResponse {
+        token: session.token,
+        refresh_token,
+        tenant_id: session.tenant_id,
+        user_id: session.user_id,
+        expires_at: session.expires_at,
+    };
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Lists the API keys of a tenant
+pub async fn list_api_keys(
+    State(auth): State<Arc<AuthenticationService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(tenant_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    parse_actor_tenant(&actor, &tenant_id)?;
+    let api_keys: Vec<ApiKeyResponse> = auth
+        .list_api_keys(&actor)
+        .await?
+        .into_iter()
+        .map(ApiKeyResponse::from)
+        .collect();
+    Ok((StatusCode::OK, Json(api_keys)))
+}
+
+/// Mints an API key of a tenant limited to the requested scopes
+pub async fn create_api_key(
+    State(auth): State<Arc<AuthenticationService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(tenant_id): Path<String>,
+    Json(request): Json<ApiKeyRequest>,
+) -> Result<impl IntoResponse> {
+    parse_actor_tenant(&actor, &tenant_id)?;
+    if let Some(days) = request.expires_in_days {
+        if !(1..=MAX_ACCESS_TOKEN_DAYS).contains(&days) {
+            return Err(Error::Validation(format!(
+                "API key lifetime must be between 1 and {} days",
+                MAX_ACCESS_TOKEN_DAYS
+            )));
+        }
+    }
+    let scopes = TokenScope::parse_list(&request.scope)?;
+    let (api_key, key) = auth
+        .create_api_key(
+            &actor,
+            request.name,
+            scopes,
+            request.quota,
+            request.expires_in_days.map(time::Duration::days),
+        )
+        .await?;
+    let response = CreatedApiKeyResponse {
+        key,
+        api_key: api_key.into(),
+    };
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Revokes an API key of a tenant
+pub async fn revoke_api_key(
+    State(auth): State<Arc<AuthenticationService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, key_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    parse_actor_tenant(&actor, &tenant_id)?;
+    let key_id = Uuid::parse_str(&key_id)
+        .map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+    auth.revoke_api_key(&actor, key_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Streams the audit and security events of a tenant as server-sent events
+///
+/// Each event is named after its category and carries the JSON encoded audit event;
+/// only events recorded after the client connected are sent.
+pub async fn stream_audit_events(
+    State(identity): State<Arc<IdentityModule>>,
+    RequirePermission(actor, _): RequirePermission<action::Read, resource::AuditLog>,
+    Path(tenant_id): Path<String>,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, axum::Error>>>> {
+    let tenant_id = parse_actor_tenant(&actor, &tenant_id)?;
+    let subscription = identity.audit_stream().subscribe(tenant_id);
+    let events = stream::unfold(subscription, |mut subscription| async move {
+        let event = subscription.next().await?;
+        let category = match event.category {
+            AuditCategory::Audit => "audit",
+            AuditCategory::Security => "security",
+        };
+        let sse_event = Event::default()
+            .event(category)
+            .id(event.id.to_string())
+            .json_data(&event);
+        Some((sse_event, subscription))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Lists the authorization decisions recorded for a tenant, newest first
+pub async fn list_authorization_decisions(
+    State(identity): State<Arc<IdentityModule>>,
+    RequirePermission(actor, _): RequirePermission<action::Read, resource::AuditLog>,
+    Path(tenant_id): Path<String>,
+    Query(query): Query<AuthorizationAuditQuery>,
+) -> Result<impl IntoResponse> {
+    parse_actor_tenant(&actor, &tenant_id)?;
+    let decisions = identity.list_authorization_decisions(&actor, query).await?;
+    Ok((StatusCode::OK, Json(decisions)))
+}
+
+/// Simulates a permission check for a user of the tenant, explaining its outcome
+pub async fn simulate_policy(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(tenant_id): Path<String>,
+    Json(request): Json<PolicySimulationRequest>,
+) -> Result<impl IntoResponse> {
+    parse_actor_tenant(&actor, &tenant_id)?;
+    let simulation = identity.simulate_permission(&actor, request).await?;
+    Ok((StatusCode::OK, Json(simulation)))
+}
+
+/// Lists the resources and actions permissions of custom roles may refer to
+pub async fn list_permission_catalog(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(_): AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    Ok((
+        StatusCode::OK,
+        Json(identity.permission_catalog().resources()),
+    ))
+}
+
+/// Lists the roles of a tenant
+pub async fn list_roles(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(tenant_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let tenant_id = parse_actor_tenant(&actor, &tenant_id)?;
+    let roles = identity.list_roles(tenant_id).await?;
+    Ok((StatusCode::OK, Json(roles)))
+}
+
+/// Gets a role of a tenant
+pub async fn get_role(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, role_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let tenant_id = parse_actor_tenant(&actor, &tenant_id)?;
+    let role = identity
+        .get_role(tenant_id, parse_role_id(&role_id)?)
+        .await?;
+    Ok((StatusCode::OK, Json(role)))
+}
+
+/// Creates a custom role
+pub async fn create_role(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(tenant_id): Path<String>,
+    Json(request): Json<RoleRequest>,
+) -> Result<impl IntoResponse> {
+    parse_actor_tenant(&actor, &tenant_id)?;
+    let role = identity.create_role(&actor, request).await?;
+    Ok((StatusCode::CREATED, Json(role)))
+}
+
+/// Updates a custom role
+pub async fn update_role(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, role_id)): Path<(String, String)>,
+    Json(request): Json<RoleRequest>,
+) -> Result<impl IntoResponse> {
+    parse_actor_tenant(&actor, &tenant_id)?;
+    let role = identity
+        .update_role(&actor, parse_role_id(&role_id)?, request)
+        .await?;
+    Ok((StatusCode::OK, Json(role)))
+}
+
+/// Deletes a custom role
+pub async fn delete_role(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, role_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    parse_actor_tenant(&actor, &tenant_id)?;
+    identity
+        .delete_role(&actor, parse_role_id(&role_id)?)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Assigns a role to a user, optionally for the validity window in the body
+pub async fn assign_role(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, user_id, role_id)): Path<(String, String, String)>,
+    body: Bytes,
+) -> Result<impl IntoResponse> {
+    let (_, user_id) = parse_user_path(&tenant_id, &user_id)?;
+    parse_actor_tenant(&actor, &tenant_id)?;
+    let request: RoleAssignmentRequest = if body.is_empty() {
+        RoleAssignmentRequest::default()
+    } else {
+        serde_json::from_slice(&body)
+            .map_err(|e| Error::Validation(format!("Invalid role assignment: {}", e)))?
+    };
+    let user = identity
+        .schedule_role(&actor, user_id, parse_role_id(&role_id)?, &request)
+        .await?;
+    Ok((StatusCode::OK, Json(UserResponse::from(user))))
+}
+
+/// Removes a role from a user
+pub async fn revoke_role(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, user_id, role_id)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse> {
+    let (_, user_id) = parse_user_path(&tenant_id, &user_id)?;
+    parse_actor_tenant(&actor, &tenant_id)?;
+    let user = identity
+        .revoke_role(&actor, user_id, parse_role_id(&role_id)?)
+        .await?;
+    Ok((StatusCode::OK, Json(UserResponse::from(user))))
+}
+
+/// Lists the owners of a resource
+pub async fn list_resource_owners(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, resource_type, resource_id)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse> {
+    parse_actor_tenant(&actor, &tenant_id)?;
+    let owners = identity
+        .list_resource_owners(&actor, &resource_type, &resource_id)
+        .await?;
+    Ok((StatusCode::OK, Json(owners)))
+}
+
+/// Registers an owner of a resource
+pub async fn add_resource_owner(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, resource_type, resource_id, owner_type, owner_id)): Path<(
+        String,
+        String,
+        String,
+        String,
+        String,
+    )>,
+) -> Result<impl IntoResponse> {
+    let tenant_id = parse_actor_tenant(&actor, &tenant_id)?;
+    let owner = parse_resource_owner(
+        tenant_id,
+        resource_type,
+        resource_id,
+        &owner_type,
+        &owner_id,
+    )?;
+    let owners = identity.add_resource_owner(&actor, owner).await?;
+    Ok((StatusCode::OK, Json(owners)))
+}
+
+/// Removes an owner of a resource
+pub async fn remove_resource_owner(
+    State(identity): State<Arc<IdentityModule>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, resource_type, resource_id, owner_type, owner_id)): Path<(
+        String,
+        String,
+        String,
+        String,
+        String,
+    )>,
+) -> Result<impl IntoResponse> {
+    let tenant_id = parse_actor_tenant(&actor, &tenant_id)?;
+    let owner = parse_resource_owner(
+        tenant_id,
+        resource_type,
+        resource_id,
+        &owner_type,
+        &owner_id,
+    )?;
+    identity.remove_resource_owner(&actor, owner).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Creates the identity module router
+pub fn router(state: IdentityState) -> Router {
+    Router::new()
+        .route("/tokens", post(create_access_token))
+        .route("/login", post(login))
+        .route("/login/mfa", post(login_mfa))
+        .route("/login/discover", post(discover_memberships))
+        .route("/memberships", get(list_memberships))
+        .route("/sessions/switch", post(switch_tenant))
+        .route("/sessions/refresh", post(refresh_session))
+        .route("/permissions", get(list_permission_catalog))
+        .route(
+            "/sessions",
+            get(list_own_sessions).delete(revoke_other_sessions),
+        )
+        .route("/sessions/all", delete(revoke_all_sessions))
+        .route("/sessions/:id", delete(revoke_own_session))
+        .route("/emails", get(list_own_emails).post(add_own_email))
+        .route("/emails/verify", post(verify_own_email))
+        .route("/emails/:id", delete(remove_own_email))
+        .route("/emails/:id/primary", post(set_own_primary_email))
+        .route(
+            "/emails/:id/verification",
+            post(resend_own_email_verification),
+        )
+        .route("/tenants/:tenant_id/users", get(list_users))
+        .route(
+            "/tenants/:tenant_id/api-keys",
+            get(list_api_keys).post(create_api_key),
+        )
+        .route(
+            "/tenants/:tenant_id/api-keys/:id",
+            delete(revoke_api_key),
+        )
+        .route("/tenants/:tenant_id/audit/stream", get(stream_audit_events))
+        .route(
+            "/tenants/:tenant_id/audit/authorization",
+            get(list_authorization_decisions),
+        )
+        .route(
+            "/tenants/:tenant_id/policy/simulate",
+            post(simulate_policy),
+        )
+        .route(
+            "/tenants/:tenant_id/sessions",
+            delete(revoke_tenant_sessions),
+        )
+        .route(
+            "/tenants/:tenant_id/users/:id/activity",
+            get(list_user_activity),
+        )
+        .route(
+            "/tenants/:tenant_id/users/:id/deactivate",
+            post(deactivate_user),
+        )
+        .route(
+            "/tenants/:tenant_id/users/:id/activate",
+            post(activate_user),
+        )
+        .route(
+            "/tenants/:tenant_id/users/:id/roles/:role_id",
+            post(assign_role).delete(revoke_role),
+        )
+        .route(
+            "/tenants/:tenant_id/roles",
+            get(list_roles).post(create_role),
+        )
+        .route(
+            "/tenants/:tenant_id/roles/:id",
+            get(get_role).put(update_role).delete(delete_role),
+        )
+        .route(
+            "/tenants/:tenant_id/resources/:resource_type/:resource_id/owners",
+            get(list_resource_owners),
+        )
+        .route(
+            "/tenants/:tenant_id/resources/:resource_type/:resource_id/owners/:owner_type/:owner_id",
+            post(add_resource_owner).delete(remove_resource_owner),
+        )
+        .with_state(state)
+}
+
+/// Serves the public keys session tokens are signed with
+pub async fn get_jwks(State(keys): State<JwtKeyRing>) -> impl IntoResponse {
+    Json(keys.jwks())
+}
+
+/// Creates the router publishing the JWKS at its well-known location
+pub fn jwks_router(keys: JwtKeyRing) -> Router {
+    Router::new()
+        .route("/.well-known/jwks.json", get(get_jwks))
+        .with_state(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::identity::repository::UserRepository;
+
+    #[test]
+    fn test_parse_user_path() {
+        let tenant_id = Uuid::new_v4();
+        let user_id = Uuid::new_v4();
+
+        let (parsed_tenant, parsed_user) =
+            parse_user_path(&tenant_id.to_string(), &user_id.to_string()).unwrap();
+        assert_eq!(parsed_tenant.0, tenant_id);
+        assert_eq!(parsed_user.0, user_id);
+
+        let result = parse_user_path("invalid", &user_id.to_string());
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_parse_actor_tenant() {
+        let actor = User::new(
+            TenantId::new(),
+            "admin@example.com".to_string(),
+            "hash".to_string(),
+        );
+
+        let tenant_id = parse_actor_tenant(&actor, &actor.tenant_id.0.to_string()).unwrap();
+        assert_eq!(tenant_id, actor.tenant_id);
+
+        let result = parse_actor_tenant(&actor, &Uuid::new_v4().to_string());
+        assert!(matches!(result, Err(Error::Authorization(_))));
+    }
+
+    #[test]
+    fn test_parse_resource_owner() {
+        let tenant_id = TenantId::new();
+        let owner_id = Uuid::new_v4();
+
+        let owner = parse_resource_owner(
+            tenant_id,
+            "posts".to_string(),
+            "42".to_string(),
+            "group",
+            &owner_id.to_string(),
+        )
+        .unwrap();
+        assert_eq!(owner.owner_type, OwnerType::Group);
+        assert_eq!(owner.owner_id, owner_id);
+
+        let result = parse_resource_owner(
+            tenant_id,
+            "posts".to_string(),
+            "42".to_string(),
+            "team",
+            &owner_id.to_string(),
+        );
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+    }
+
+    #[tokio::test]
+    async fn test_jwks_endpoint() {
+        use crate::modules::identity::jwt_keys::JwtKeys;
+        use axum::{body::Body, http::Request};
+        use tower::ServiceExt;
+
+        let app = jwks_router(JwtKeys::hmac("secret").into());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/.well-known/jwks.json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        // The HMAC secret is never published
+        assert_eq!(&body[..], br#"{"keys":[]}"#);
+    }
+
+    /// Creates the identity router, locking logins out after three failures
+    ///
+    /// Logins of a user beyond `max_attempts` a minute are throttled.
+    fn test_router(repository: UserRepository, max_attempts: u32) -> Router {
+        use crate::{
+            core::config::LoginThrottleConfig,
+            modules::identity::{session::InMemorySessionStore, throttle::LoginThrottle},
+        };
+
+        let auth =
+            AuthenticationService::new(repository.clone(), Box::new(InMemorySessionStore::new()))
+                .with_login_throttle(LoginThrottle::new(&LoginThrottleConfig {
+                    max_attempts,
+                    window_secs: 60,
+                    max_failures: 3,
+                    lockout_secs: 300,
+                }));
+        let identity = Arc::new(IdentityModule::new(repository));
+        router(IdentityState {
+            auth: Arc::new(auth),
+            rbac: identity.rbac(),
+            identity,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_membership_discovery_lockout() {
+        use crate::core::database::tests::create_test_db;
+        use axum::{
+            body::Body,
+            http::{
+                header::{CONTENT_TYPE, RETRY_AFTER},
+                Request,
+            },
+        };
+        use tower::ServiceExt;
+
+        let (db, _container) = create_test_db().await.unwrap();
+        let app = test_router(UserRepository::new(db.get_pool()), 20);
+        let discover = |email: &str, ip: &str| {
+            Request::builder()
+                .method("POST")
+                .uri("/login/discover")
+                .header(CONTENT_TYPE, "application/json")
+                .header("x-forwarded-for", ip)
+                .body(Body::from(
+                    serde_json::json!({ "email": email, "password": "wrong" }).to_string(),
+                ))
+                .unwrap()
+        };
+
+        // Unknown addresses get an empty list but count as failures
+        for _ in 0..2 {
+            let response = app
+                .clone()
+                .oneshot(discover("jane@example.com", "203.0.113.7"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(&body[..], b"[]");
+        }
+        let response = app
+            .clone()
+            .oneshot(discover("jane@example.com", "203.0.113.7"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::LOCKED);
+        assert_eq!(response.headers()[RETRY_AFTER], "300");
+
+        // The address stays locked out from other clients, and so does the client
+        let response = app
+            .clone()
+            .oneshot(discover("jane@example.com", "198.51.100.1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::LOCKED);
+        let response = app
+            .clone()
+            .oneshot(discover("john@example.com", "203.0.113.7"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::LOCKED);
+        let response = app
+            .oneshot(discover("john@example.com", "198.51.100.1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    /// Builds a JSON login request sent from a browser behind a proxy
+    fn login_request(uri: &str, body: serde_json::Value) -> axum::http::Request<axum::body::Body> {
+        use axum::http::header::{CONTENT_TYPE, USER_AGENT};
+
+        axum::http::Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(CONTENT_TYPE, "application/json")
+            .header("x-forwarded-for", "203.0.113.7, 10.0.0.1")
+            .header(USER_AGENT, "Mozilla/5.0")
+            .body(body.to_string().into())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_login_routes() {
+        use crate::{
+            core::database::tests::create_test_db,
+            modules::tenant::{models::Tenant, repository::TenantRepository},
+        };
+        use axum::{body::Body, http::Request};
+        use tower::ServiceExt;
+
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let tenant = TenantRepository::new(db.get_pool())
+            .create_tenant(Tenant::new(
+                "Test Tenant".to_string(),
+                format!("{}.example.com", Uuid::new_v4()),
+            ))
+            .await
+            .unwrap();
+        let user = repository
+            .create_user(User::new(
+                tenant.id,
+                "jane@example.com".to_string(),
+                AuthenticationService::hash_password("password123").unwrap(),
+            ))
+            .await
+            .unwrap();
+        let app = test_router(repository, 20);
+
+        let response = app
+            .clone()
+            .oneshot(login_request(
+                "/login",
+                serde_json::json!({
+                    "tenant_id": tenant.id,
+                    "email": "jane@example.com",
+                    "password": "password123",
+                }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let login: LoginResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(login.user_id, user.id);
+        assert_eq!(login.tenant_id, tenant.id);
+        assert!(login.refresh_token.is_none());
+
+        // The session records the client the login came from
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/sessions")
+                    .header(AUTHORIZATION, format!("Bearer {}", login.token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let sessions: Vec<UserSessionResponse> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].ip_address.as_deref(), Some("203.0.113.7"));
+        assert_eq!(sessions[0].user_agent.as_deref(), Some("Mozilla/5.0"));
+
+        let response = app
+            .clone()
+            .oneshot(login_request(
+                "/login",
+                serde_json::json!({
+                    "tenant_id": tenant.id,
+                    "email": "jane@example.com",
+                    "password": "wrong",
+                }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // MFA logins are rejected for users without MFA
+        let response = app
+            .oneshot(login_request(
+                "/login/mfa",
+                serde_json::json!({
+                    "tenant_id": tenant.id,
+                    "email": "jane@example.com",
+                    "password": "password123",
+                    "mfa_code": "123456",
+                }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_login_throttle_responses() {
+        use crate::{
+            core::database::tests::create_test_db,
+            modules::tenant::{models::Tenant, repository::TenantRepository},
+            shared::error::ThrottleInfo,
+        };
+        use axum::http::header::RETRY_AFTER;
+        use tower::ServiceExt;
+
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let tenant = TenantRepository::new(db.get_pool())
+            .create_tenant(Tenant::new(
+                "Test Tenant".to_string(),
+                format!("{}.example.com", Uuid::new_v4()),
+            ))
+            .await
+            .unwrap();
+        for email in ["jane@example.com", "john@example.com"] {
+            repository
+                .create_user(User::new(
+                    tenant.id,
+                    email.to_string(),
+                    AuthenticationService::hash_password("password123").unwrap(),
+                ))
+                .await
+                .unwrap();
+        }
+        let app = test_router(repository, 3);
+        let login = |email: &str, password: &str| {
+            login_request(
+                "/login",
+                serde_json::json!({
+                    "tenant_id": tenant.id,
+                    "email": email,
+                    "password": password,
+                }),
+            )
+        };
+
+        // The failure locking the user out answers with the lockout details
+        for _ in 0..2 {
+            let response = app
+                .clone()
+                .oneshot(login("jane@example.com", "wrong"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        let response = app
+            .clone()
+            .oneshot(login("jane@example.com", "wrong"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::LOCKED);
+        assert_eq!(response.headers()[RETRY_AFTER], "300");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let info: ThrottleInfo = serde_json::from_slice(&body).unwrap();
+        assert_eq!(info.retry_after, 300);
+        assert_eq!(info.attempts_remaining, 0);
+
+        // Logins beyond the attempts of the window are throttled, even with valid credentials
+        for _ in 0..2 {
+            let response = app
+                .clone()
+                .oneshot(login("john@example.com", "password123"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+        let response = app
+            .clone()
+            .oneshot(login("john@example.com", "wrong"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app
+            .oneshot(login("john@example.com", "password123"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = response.headers()[RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=60).contains(&retry_after));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let info: ThrottleInfo = serde_json::from_slice(&body).unwrap();
+        assert_eq!(info.retry_after, retry_after);
+        assert_eq!(info.attempts_remaining, 2);
+    }
+}
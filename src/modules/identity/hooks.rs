@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+use axum::http::{header::USER_AGENT, HeaderMap};
+use std::{collections::HashMap, net::IpAddr};
+
+use crate::{
+    modules::identity::{models::User, session::Session},
+    shared::{error::Result, types::TenantId},
+};
+
+/// Information about a login attempt available to authentication hooks
+#[derive(Debug, Clone, Default)]
+pub struct LoginContext {
+    pub ip_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    /// Name the user gave the device, stored with the session
+    pub device_name: Option<String>,
+    /// Additional request data, e.g. a country code resolved by a proxy
+    pub attributes: HashMap<String, String>,
+}
+
+impl LoginContext {
+    /// Collects the client address reported by the proxy and the user agent of a request
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        Self {
+            ip_address: headers
+                .get("x-forwarded-for")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .and_then(|value| value.trim().parse().ok()),
+            user_agent: headers
+                .get(USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            ..Self::default()
+        }
+    }
+}
+
+/// Extension point for applications to customize the login flow
+#[async_trait]
+pub trait AuthHook: Send + Sync + std::fmt::Debug {
+    /// Runs before the credentials are verified; returning an error rejects the login
+    async fn pre_login(
+        &self,
+        _email: &str,
+        _tenant_id: TenantId,
+        _context: &LoginContext,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Runs after the user was authenticated, before the session is stored
+    async fn post_login(
+        &self,
+        _user: &User,
+        _context: &LoginContext,
+        _session: &mut Session,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Runs after sessions were revoked, e.g. to end sessions linked to them elsewhere
+    ///
+    /// The sessions are already gone, so errors are only logged.
+    async fn post_logout(&self, _sessions: &[Session]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Extension point for applications to customize user registration
+#[async_trait]
+pub trait RegistrationHook: Send + Sync + std::fmt::Debug {
+    /// Runs before the user is stored; may adjust the new user or veto the signup with an error
+    async fn pre_register(&self, _user: &mut User) -> Result<()> {
+        Ok(())
+    }
+
+    /// Runs after the user was stored, e.g. to provision external accounts
+    async fn post_register(&self, _user: &User) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use axum::{
+    body::Body,
+    extract::MatchedPath,
+    http::{header::AUTHORIZATION, Request},
+    response::{IntoResponse, Response},
+};
+use futures::future::BoxFuture;
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+use crate::modules::identity::{api_key::is_api_key, auth::AuthenticationService};
+
+/// Layer counting requests made with API keys against the keys' daily quotas
+///
+/// Requests exceeding a quota are rejected with 429; all other requests of keys
+/// with a limit on the endpoint get the quota headers. Endpoints are named by
+/// method and route below the API prefix, e.g. `GET /tenants/:tenant_id/users`.
+#[derive(Debug, Clone)]
+pub struct ApiKeyQuotaLayer {
+    auth: Arc<AuthenticationService>,
+    api_prefix: String,
+}
+
+impl ApiKeyQuotaLayer {
+    /// Creates a layer for routes served below the given API prefix
+    pub fn new(auth: Arc<AuthenticationService>, api_prefix: &str) -> Self {
+        Self {
+            auth,
+            api_prefix: api_prefix.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+impl<S> Layer<S> for ApiKeyQuotaLayer {
+    type Service = QuotaEnforcement<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        QuotaEnforcement {
+            inner,
+            auth: self.auth.clone(),
+            api_prefix: self.api_prefix.clone(),
+        }
+    }
+}
+
+/// Service enforcing the quota of the API key a request was made with
+#[derive(Debug, Clone)]
+pub struct QuotaEnforcement<S> {
+    inner: S,
+    auth: Arc<AuthenticationService>,
+    api_prefix: String,
+}
+
+impl<S> Service<Request<Body>> for QuotaEnforcement<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, std::result::Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // Keep the inner service that was polled ready for this request
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let key = api_key(&request);
+        let endpoint = quota_endpoint(&request, &self.api_prefix);
+        let auth = self.auth.clone();
+        Box::pin(async move {
+            let Some(key) = key else {
+                return inner.call(request).await;
+            };
+            let usage = match auth.consume_api_key_quota(&key, &endpoint).await {
+                Ok(usage) => usage,
+                Err(e) => return Ok(e.into_response()),
+            };
+            match usage {
+                Some(usage) if usage.exceeded => Ok(usage.into_response()),
+                Some(usage) => {
+                    let mut response = inner.call(request).await?;
+                    usage.apply_headers(response.headers_mut());
+                    Ok(response)
+                },
+                None => inner.call(request).await,
+            }
+        })
+    }
+}
+
+/// Gets the API key a request authenticates with, if any
+fn api_key(request: &Request<Body>) -> Option<String> {
+    request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .filter(|token| is_api_key(token))
+        .map(str::to_string)
+}
+
+/// Names the endpoint of a request by its method and route below the API prefix
+fn quota_endpoint(request: &Request<Body>, api_prefix: &str) -> String {
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| request.uri().path(), MatchedPath::as_str);
+    let route = match path.strip_prefix(api_prefix) {
+        Some(route) if route.starts_with('/') => route,
+        _ => path,
+    };
+    format!("{} {}", request.method(), route)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_quota_endpoint() {
+        let api = Router::new().route(
+            "/tenants/:tenant_id/users",
+            get(|request: Request<Body>| async move { quota_endpoint(&request, "/api") }),
+        );
+        let router = Router::new().nest("/api", api);
+
+        let response = router
+            .oneshot(
+                Request::get("/api/tenants/acme/users")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"GET /tenants/:tenant_id/users");
+    }
+}
@@ -1,26 +1,237 @@
+pub mod api_key;
 pub mod auth;
+pub mod auth_policy;
+pub mod catalog;
+pub mod handlers;
+pub mod hooks;
+pub mod jwt_keys;
 pub mod models;
 pub mod mfa;
+pub mod middleware;
+pub mod policy;
 pub mod rbac;
+pub mod refresh_token;
 pub mod repository;
 pub mod service;
 pub mod session;
-pub mod session_manager;
+pub mod session_archive;
+pub mod session_cache;
+pub mod sso;
+pub mod throttle;
 
 pub use auth::AuthenticationService;
+pub use hooks::{AuthHook, LoginContext, RegistrationHook};
 pub use service::IdentityModule;
-pub use session::RedisSessionStore;
+pub use session::{
+    InMemorySessionStore, PgSessionStore, RedisSessionStore, RegionalSessionStore,
+    ResilientSessionStore,
+};
+
+use axum::Router;
+use std::sync::Arc;
 
 use crate::{
-    core::database::Database,
-    shared::error::Result,
+    core::{
+        config::{
+            Config, RedisConfig, RegionConfig, SessionConfig, SessionStoreKind, SessionTokenMode,
+        },
+        database::{Database, DatabaseRouter},
+    },
+    modules::tenant::{repository::TenantRepository, service::TenantService},
+    shared::{
+        audit::AuditStream, cache::CacheInvalidationBus, error::Result, quota::QuotaTracker,
+    },
+};
+
+/// Seconds tenant settings, e.g. authentication policy overrides, are cached
+const TENANT_SETTINGS_CACHE_TTL_SECS: u64 = 60;
+
+use self::{
+    session::SessionStore,
+    session_archive::{ArchivingSessionStore, SessionArchive},
+    session_cache::CachedSessionStore,
 };
 
+/// Creates the configured store, archiving its sessions if configured
+fn create_archiving_session_store(
+    sessions: &SessionConfig,
+    redis: &RedisConfig,
+    db: &Database,
+) -> Result<Box<dyn SessionStore>> {
+    let store = create_primary_session_store(sessions, redis, db)?;
+    if !sessions.archive {
+        return Ok(store);
+    }
+    let archive = SessionArchive::new(db.get_pool());
+    archive.clone().spawn_retention_worker(
+        time::Duration::days(sessions.archive_retention_days.into()),
+        std::time::Duration::from_secs(sessions.cleanup_interval_secs),
+    );
+    Ok(Box::new(ArchivingSessionStore::new(store, archive)))
+}
+
+/// Creates the configured session store
+///
+/// The Postgres store and the session archive spawn their cleanup workers, so
+/// this must be called within the Tokio runtime. With a local cache, revocations
+/// are broadcast to the caches of all instances via Redis.
+pub fn create_session_store(
+    sessions: &SessionConfig,
+    redis: &RedisConfig,
+    db: &Database,
+) -> Result<Box<dyn SessionStore>> {
+    let store = create_archiving_session_store(sessions, redis, db)?;
+    let Some(ttl_secs) = sessions.local_cache_ttl_secs else {
+        return Ok(store);
+    };
+    let invalidation = match sessions.store {
+        SessionStoreKind::Redis => {
+            let invalidation = CacheInvalidationBus::with_redis(&redis.url)?;
+            invalidation.spawn_listener()?;
+            invalidation
+        },
+        _ => CacheInvalidationBus::new(),
+    };
+    Ok(Box::new(CachedSessionStore::with_invalidation(
+        store,
+        std::time::Duration::from_secs(ttl_secs),
+        invalidation,
+    )))
+}
+
+/// Creates the configured store keeping the active sessions
+fn create_primary_session_store(
+    sessions: &SessionConfig,
+    redis: &RedisConfig,
+    db: &Database,
+) -> Result<Box<dyn SessionStore>> {
+    match sessions.store {
+        SessionStoreKind::Redis => Ok(Box::new(ResilientSessionStore::new(
+            Box::new(RedisSessionStore::from_config(redis)?),
+            &redis.fallback,
+        ))),
+        SessionStoreKind::Postgres => {
+            let store = PgSessionStore::new(db.get_pool());
+            store
+                .clone()
+                .spawn_cleanup_worker(std::time::Duration::from_secs(
+                    sessions.cleanup_interval_secs,
+                ));
+            Ok(Box::new(store))
+        },
+        SessionStoreKind::Memory => Ok(Box::new(InMemorySessionStore::new())),
+    }
+}
+
+/// Creates the configured session store for each data residency region
+///
+/// Without regions this is the home store; otherwise sessions are kept in the
+/// Redis or database of their tenant's region.
+pub fn create_regional_session_store(
+    sessions: &SessionConfig,
+    redis: &RedisConfig,
+    databases: &DatabaseRouter,
+    regions: &[RegionConfig],
+) -> Result<Box<dyn SessionStore>> {
+    let home = create_session_store(sessions, redis, databases.home())?;
+    if regions.is_empty() {
+        return Ok(home);
+    }
+    let mut store = RegionalSessionStore::new(home, databases.tenant_regions().clone());
+    for region in regions {
+        let redis = RedisConfig {
+            url: region.redis_url.clone(),
+            tls: redis.tls.clone(),
+            session_encryption_key: redis.session_encryption_key.clone(),
+            session_encryption_key_file: redis.session_encryption_key_file.clone(),
+            fallback: redis.fallback.clone(),
+            ..RedisConfig::default_dev()
+        };
+        let db = databases.region(Some(&region.name))?;
+        store = store.with_region(
+            region.name.clone(),
+            create_session_store(sessions, &redis, db)?,
+        );
+    }
+    Ok(Box::new(store))
+}
+
+/// Creates the ring of keys signing session tokens, `None` without signing keys
+///
+/// Whether tokens are signed with them is up to the token mode of the
+/// authentication service. Spawns the worker reloading the key files, so this
+/// must be called within the Tokio runtime.
+pub fn create_token_keys(sessions: &SessionConfig) -> Result<Option<jwt_keys::JwtKeyRing>> {
+    if sessions.token_mode == SessionTokenMode::Opaque || sessions.signing_keys.is_empty() {
+        return Ok(None);
+    }
+    let keys = jwt_keys::JwtKeyRing::from_config(&sessions.signing_keys)?;
+    keys.clone().spawn_reload_worker(
+        sessions.signing_keys.clone(),
+        std::time::Duration::from_secs(sessions.signing_key_reload_secs),
+    );
+    Ok(Some(keys))
+}
+
 /// Creates a new identity module with authentication service
-pub async fn create_identity_module(db: Database) -> Result<(IdentityModule, AuthenticationService)> {
+///
+/// Sessions are kept in the backends of their tenant's data residency region.
+pub async fn create_identity_module(
+    databases: &DatabaseRouter,
+    config: &Config,
+) -> Result<(IdentityModule, AuthenticationService)> {
+    let db = databases.home();
     let repository = repository::UserRepository::new(db.get_pool());
-    let session_store = RedisSessionStore::new("redis://localhost:6379")?;
-    let module = IdentityModule::new(repository.clone());
-    let auth_service = AuthenticationService::new(repository, Box::new(session_store));
+    let session_store =
+        create_regional_session_store(&config.sessions, &config.redis, databases, &config.regions)?;
+    let invalidation = CacheInvalidationBus::with_redis(&config.redis.url)?;
+    invalidation.spawn_listener()?;
+    let audit = AuditStream::new();
+    let tenants = TenantService::new(TenantRepository::new(db.get_pool())).with_settings_cache(
+        std::time::Duration::from_secs(TENANT_SETTINGS_CACHE_TTL_SECS),
+        invalidation.clone(),
+    );
+    let module = IdentityModule::new(repository.clone())
+        .with_cache_invalidation(invalidation)
+        .with_policy_engine(policy::create_policy_engine(&config.policy)?)
+        .with_audit_stream(audit.clone())
+        .with_authorization_audit(false)
+        .with_email_verification(&config.emails);
+    let mut auth_service = AuthenticationService::new(repository, session_store)
+        .with_audit_stream(audit)
+        .with_login_throttle(throttle::LoginThrottle::new(&config.login_throttle))
+        .with_session_lifetime(session::SessionLifetime::from_config(&config.sessions))
+        .with_token_mode(config.sessions.token_mode)
+        .with_session_limit(auth_policy::SessionLimit::from_config(&config.sessions))
+        .with_tenant_policies(tenants)
+        .with_api_keys(api_key::ApiKeyRepository::new(db.get_pool()))
+        .with_quota_tracker(QuotaTracker::new(&config.redis.url)?);
+    if let Some(days) = config.sessions.refresh_token_lifetime_days {
+        auth_service = auth_service.with_refresh_tokens(
+            refresh_token::RefreshTokenRepository::new(db.get_pool()),
+            time::Duration::days(days.into()),
+        );
+    }
+    if let Some(keys) = create_token_keys(&config.sessions)? {
+        auth_service = auth_service.with_token_keys(
+            keys,
+            config.sessions.token_issuer.clone(),
+            config.sessions.token_audience.clone(),
+        );
+    }
     Ok((module, auth_service))
-}
\ No newline at end of file
+}
+
+/// Creates the router publishing the public keys of session tokens
+pub fn jwks_router(keys: jwt_keys::JwtKeyRing) -> Router {
+    handlers::jwks_router(keys)
+}
+
+/// Creates a router for the identity module
+pub fn router(auth_service: Arc<AuthenticationService>, identity: Arc<IdentityModule>) -> Router {
+    handlers::router(handlers::IdentityState {
+        auth: auth_service,
+        rbac: identity.rbac(),
+        identity,
+    })
+}
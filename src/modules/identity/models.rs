@@ -2,7 +2,15 @@ use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
-use crate::shared::types::{TenantId, UserId};
+use crate::{
+    modules::identity::{api_key::ApiKey, rbac::resource_matches, session::Session},
+    shared::{
+        audit::AuditEvent,
+        error::Error,
+        quota::ApiKeyQuota,
+        types::{TenantId, UserId},
+    },
+};
 
 /// User credentials for authentication
 #[derive(Debug, Clone)]
@@ -27,6 +35,9 @@ pub struct User {
     pub updated_at: OffsetDateTime,
     pub mfa_enabled: bool,
     pub mfa_secret: Option<String>,
+    /// Scopes of the token the user authenticated with; `None` for unrestricted sessions
+    #[serde(skip)]
+    pub token_scopes: Option<Vec<TokenScope>>,
 }
 
 /// Role type enum
@@ -35,6 +46,7 @@ pub enum RoleType {
     User,
     Admin,
     SuperAdmin,
+    Custom,
 }
 
 impl std::fmt::Display for RoleType {
@@ -43,6 +55,21 @@ impl std::fmt::Display for RoleType {
             RoleType::User => write!(f, "user"),
             RoleType::Admin => write!(f, "admin"),
             RoleType::SuperAdmin => write!(f, "superadmin"),
+            RoleType::Custom => write!(f, "custom"),
+        }
+    }
+}
+
+impl std::str::FromStr for RoleType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(RoleType::User),
+            "admin" => Ok(RoleType::Admin),
+            "superadmin" => Ok(RoleType::SuperAdmin),
+            "custom" => Ok(RoleType::Custom),
+            _ => Err(Error::InvalidInput(format!("Invalid role type: {}", s))),
         }
     }
 }
@@ -54,6 +81,12 @@ pub struct Role {
     pub role_type: RoleType,
     pub name: String,
     pub permissions: Vec<Permission>,
+    /// Roles whose permissions this role inherits
+    #[serde(default)]
+    pub parent_ids: Vec<Uuid>,
+    /// Permissions inherited from all ancestor roles, resolved when the role is loaded
+    #[serde(default)]
+    pub inherited_permissions: Vec<Permission>,
 }
 
 impl Role {
@@ -64,8 +97,17 @@ impl Role {
             role_type,
             name,
             permissions: Vec::new(),
+            parent_ids: Vec::new(),
+            inherited_permissions: Vec::new(),
         }
     }
+
+    /// Iterates over the role's own and inherited permissions
+    pub fn effective_permissions(&self) -> impl Iterator<Item = &Permission> {
+        self.permissions
+            .iter()
+            .chain(self.inherited_permissions.iter())
+    }
 }
 
 impl std::fmt::Display for Role {
@@ -81,6 +123,80 @@ pub struct Permission {
     pub name: String,
     pub action: PermissionAction,
     pub resource: String,
+    #[serde(default)]
+    pub scope: PermissionScope,
+    #[serde(default)]
+    pub effect: PermissionEffect,
+}
+
+/// Effect of a permission
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionEffect {
+    /// Grants the action
+    #[default]
+    Allow,
+    /// Denies the action regardless of its scope, overriding any grant of the user's roles
+    Deny,
+}
+
+impl std::fmt::Display for PermissionEffect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PermissionEffect::Allow => write!(f, "allow"),
+            PermissionEffect::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+impl std::str::FromStr for PermissionEffect {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(PermissionEffect::Allow),
+            "deny" => Ok(PermissionEffect::Deny),
+            _ => Err(Error::InvalidInput(format!(
+                "Invalid permission effect: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Scope of a permission
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionScope {
+    /// Grants the action on every resource of the type
+    #[default]
+    All,
+    /// Grants the action only on resources the user owns
+    Own,
+}
+
+impl std::fmt::Display for PermissionScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PermissionScope::All => write!(f, "all"),
+            PermissionScope::Own => write!(f, "own"),
+        }
+    }
+}
+
+impl std::str::FromStr for PermissionScope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(PermissionScope::All),
+            "own" => Ok(PermissionScope::Own),
+            _ => Err(Error::InvalidInput(format!(
+                "Invalid permission scope: {}",
+                s
+            ))),
+        }
+    }
 }
 
 /// Permission action enum
@@ -107,6 +223,99 @@ impl std::fmt::Display for PermissionAction {
     }
 }
 
+impl std::str::FromStr for PermissionAction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "create" => Ok(PermissionAction::Create),
+            "read" => Ok(PermissionAction::Read),
+            "update" => Ok(PermissionAction::Update),
+            "delete" => Ok(PermissionAction::Delete),
+            "list" => Ok(PermissionAction::List),
+            "execute" => Ok(PermissionAction::Execute),
+            _ => Err(Error::InvalidInput(format!(
+                "Invalid permission action: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Permission delegated to a token, written as `<action>:<resource>`, e.g. `read:users`
+///
+/// Tokens with scopes only grant what both their scopes and the roles of their
+/// user allow. The resource is a pattern matched with [`resource_matches`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TokenScope {
+    pub action: PermissionAction,
+    pub resource: String,
+}
+
+impl TokenScope {
+    /// Creates a scope
+    pub fn new(action: PermissionAction, resource: &str) -> Self {
+        Self {
+            action,
+            resource: resource.to_string(),
+        }
+    }
+
+    /// Parses a space separated list of scopes as sent in OAuth `scope` parameters
+    pub fn parse_list(scopes: &str) -> Result<Vec<Self>, Error> {
+        scopes.split_whitespace().map(str::parse).collect()
+    }
+
+    /// Formats scopes as a space separated list
+    pub fn format_list(scopes: &[Self]) -> String {
+        scopes
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Checks if the scope grants an action on a resource
+    pub fn covers(&self, action: PermissionAction, resource: &str) -> bool {
+        self.action == action && resource_matches(&self.resource, resource)
+    }
+}
+
+impl std::fmt::Display for TokenScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.action, self.resource)
+    }
+}
+
+impl std::str::FromStr for TokenScope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((action, resource)) if !resource.is_empty() => Ok(Self {
+                action: action.parse()?,
+                resource: resource.to_string(),
+            }),
+            _ => Err(Error::InvalidInput(format!("Invalid scope: {}", s))),
+        }
+    }
+}
+
+impl TryFrom<String> for TokenScope {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<TokenScope> for String {
+    fn from(scope: TokenScope) -> Self {
+        scope.to_string()
+    }
+}
+
 impl User {
     /// Creates a new user
     pub fn new(tenant_id: TenantId, email: String, password_hash: String) -> Self {
@@ -122,9 +331,19 @@ impl User {
             updated_at: OffsetDateTime::now_utc(),
             mfa_enabled: false,
             mfa_secret: None,
+            token_scopes: None,
         }
     }
 
+    /// Checks if the token the user authenticated with was granted an action on a resource
+    ///
+    /// Always true for unrestricted sessions; role permissions are checked separately.
+    pub fn scopes_allow(&self, action: PermissionAction, resource: &str) -> bool {
+        self.token_scopes.as_ref().is_none_or(|scopes| {
+            scopes.iter().any(|scope| scope.covers(action, resource))
+        })
+    }
+
     /// Enables MFA for the user
     pub fn enable_mfa(&mut self, secret: String) {
         self.mfa_enabled = true;
@@ -140,6 +359,487 @@ impl User {
     }
 }
 
+/// Email address belonging to a user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserEmail {
+    pub id: Uuid,
+    pub user_id: UserId,
+    pub tenant_id: TenantId,
+    pub email: String,
+    pub is_primary: bool,
+    pub verified: bool,
+    pub verified_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+impl UserEmail {
+    /// Creates a new, unverified email address for a user
+    pub fn new(user_id: UserId, tenant_id: TenantId, email: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            tenant_id,
+            email,
+            is_primary: false,
+            verified: false,
+            verified_at: None,
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// Marks the email address as verified
+    pub fn verify(&mut self) {
+        self.verified = true;
+        self.verified_at = Some(OffsetDateTime::now_utc());
+    }
+
+    /// Validates an address users are created or reached with
+    ///
+    /// Requires a single `@` between a non-empty local part and a domain with a dot,
+    /// without whitespace; deliverability is proven by verification, not here.
+    pub fn validate(email: &str) -> Result<(), Error> {
+        let invalid = || Error::Validation(format!("Invalid email address: {}", email));
+        let (local, domain) = email.split_once('@').ok_or_else(invalid)?;
+        let valid_domain = domain.contains('.')
+            && !domain.starts_with('.')
+            && !domain.ends_with('.')
+            && !domain.contains("..");
+        if local.is_empty()
+            || domain.contains('@')
+            || !valid_domain
+            || email.contains(char::is_whitespace)
+        {
+            return Err(invalid());
+        }
+        Ok(())
+    }
+}
+
+/// Request adding an email address to the current user
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddEmailRequest {
+    pub email: String,
+}
+
+/// Request verifying an email address with the token mailed to it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailVerificationRequest {
+    pub token: String,
+}
+
+/// User response model
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserResponse {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub email: String,
+    pub roles: Vec<String>,
+    pub active: bool,
+    pub last_login: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+    pub mfa_enabled: bool,
+}
+
+impl From<User> for UserResponse {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id.0,
+            tenant_id: user.tenant_id.0,
+            email: user.email,
+            roles: user.roles.iter().map(|role| role.name.clone()).collect(),
+            active: user.active,
+            last_login: user.last_login,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+            mfa_enabled: user.mfa_enabled,
+        }
+    }
+}
+
+/// Request for a personal access token
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccessTokenRequest {
+    /// Space separated scopes, e.g. `read:users list:projects`
+    pub scope: String,
+    /// Lifetime of the token, 30 days by default
+    pub expires_in_days: Option<i64>,
+}
+
+/// Issued personal access token; the token is only shown once
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccessTokenResponse {
+    pub token: String,
+    pub scope: String,
+    pub expires_at: OffsetDateTime,
+}
+
+/// Request for an API key of the actor's tenant
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeyRequest {
+    /// Name telling the integration using the key apart
+    pub name: String,
+    /// Space separated scopes, e.g. `read:users list:projects`
+    pub scope: String,
+    /// Lifetime of the key; keys without one are valid until revoked
+    pub expires_in_days: Option<i64>,
+    /// Daily request limits; keys without one may send unlimited requests
+    #[serde(default)]
+    pub quota: ApiKeyQuota,
+}
+
+/// API key of a tenant, without its secret
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeyResponse {
+    pub id: Uuid,
+    pub name: String,
+    /// Start of the key, to recognize it by
+    pub prefix: String,
+    pub scope: String,
+    pub quota: ApiKeyQuota,
+    pub created_by: UserId,
+    pub created_at: OffsetDateTime,
+    pub expires_at: Option<OffsetDateTime>,
+    pub last_used_at: Option<OffsetDateTime>,
+}
+
+impl From<ApiKey> for ApiKeyResponse {
+    fn from(api_key: ApiKey) -> Self {
+        Self {
+            id: api_key.id,
+            name: api_key.name,
+            prefix: api_key.key_prefix,
+            scope: TokenScope::format_list(&api_key.scopes),
+            quota: api_key.quota,
+            created_by: api_key.created_by,
+            created_at: api_key.created_at,
+            expires_at: api_key.expires_at,
+            last_used_at: api_key.last_used_at,
+        }
+    }
+}
+
+/// Minted API key; the key is only shown once
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatedApiKeyResponse {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKeyResponse,
+}
+
+/// Session of the current user, e.g. a signed in device or a personal access token
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserSessionResponse {
+    pub id: Uuid,
+    pub created_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub device_name: Option<String>,
+    /// Scopes of a personal access token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Whether this is the session of the request
+    pub current: bool,
+}
+
+impl UserSessionResponse {
+    /// Describes a session, marking it if it is the given current session
+    pub fn new(session: &Session, current_id: Uuid) -> Self {
+        Self {
+            id: session.id,
+            created_at: session.created_at,
+            expires_at: session.expires_at,
+            ip_address: session.ip_address.clone(),
+            user_agent: session.user_agent.clone(),
+            device_name: session.device_name.clone(),
+            scope: session.scopes.as_deref().map(TokenScope::format_list),
+            current: session.id == current_id,
+        }
+    }
+}
+
+/// Membership of a person's identity in a tenant, as one of the tenant's users
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Membership {
+    pub tenant_id: TenantId,
+    pub tenant_name: String,
+    pub tenant_slug: Option<String>,
+    pub user_id: UserId,
+}
+
+/// Tenant a person can log in to or switch to
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MembershipResponse {
+    pub tenant_id: TenantId,
+    pub name: String,
+    pub slug: Option<String>,
+    /// Whether this is the tenant of the request's session
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub current: bool,
+}
+
+impl MembershipResponse {
+    /// Describes a membership, marking it if it is in the given current tenant
+    pub fn new(membership: Membership, current_tenant: Option<TenantId>) -> Self {
+        Self {
+            current: current_tenant == Some(membership.tenant_id),
+            tenant_id: membership.tenant_id,
+            name: membership.tenant_name,
+            slug: membership.tenant_slug,
+        }
+    }
+}
+
+/// Request logging in to a tenant with email address and password
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginRequest {
+    pub tenant_id: TenantId,
+    pub email: String,
+    pub password: String,
+    /// Code of the user's second factor, if they have MFA set up
+    pub mfa_code: Option<String>,
+}
+
+impl From<LoginRequest> for Credentials {
+    fn from(request: LoginRequest) -> Self {
+        Self {
+            email: request.email,
+            password: request.password,
+            tenant_id: request.tenant_id,
+            mfa_code: request.mfa_code,
+        }
+    }
+}
+
+/// Request logging in to a tenant with a user's password and second factor
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MfaLoginRequest {
+    pub tenant_id: TenantId,
+    pub email: String,
+    pub password: String,
+    pub mfa_code: String,
+}
+
+/// Session opened by a login
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginResponse {
+    pub token: String,
+    /// Token for renewing the session, if refresh tokens are enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    pub tenant_id: TenantId,
+    pub user_id: UserId,
+    pub expires_at: OffsetDateTime,
+}
+
+/// Request for the tenants an email address can log in to
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MembershipDiscoveryRequest {
+    pub email: String,
+    pub password: String,
+}
+
+/// Request for a session in another tenant of the current user's identity
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TenantSwitchRequest {
+    pub tenant_id: TenantId,
+    /// Code of the target membership's second factor, if it has MFA set up
+    pub mfa_code: Option<String>,
+}
+
+/// Session opened in the tenant switched to
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TenantSwitchResponse {
+    pub token: String,
+    /// Token for renewing the session, if refresh tokens are enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    pub tenant_id: TenantId,
+    pub user_id: UserId,
+    pub expires_at: OffsetDateTime,
+}
+
+/// Request renewing a session with its refresh token
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionRefreshRequest {
+    pub refresh_token: String,
+}
+
+/// Session replacing the refreshed one, with the refresh token to use next
+///
+/// The presented refresh token is used up; presenting it again revokes all
+/// sessions renewed from it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionRefreshResponse {
+    pub token: String,
+    pub refresh_token: String,
+    pub tenant_id: TenantId,
+    pub user_id: UserId,
+    pub expires_at: OffsetDateTime,
+}
+
+/// Query parameters of the admin user overview
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UserOverviewQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+/// Query parameters of the authorization decisions recorded in the audit log
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AuthorizationAuditQuery {
+    /// Only decisions with this outcome; denied and allowed ones if unset
+    pub allowed: Option<bool>,
+    pub user_id: Option<Uuid>,
+    pub request_id: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// User entry of the admin user overview
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserOverview {
+    pub id: Uuid,
+    pub email: String,
+    pub roles: Vec<String>,
+    pub active: bool,
+    /// Deactivated users are locked out of authentication
+    pub locked: bool,
+    pub mfa_enabled: bool,
+    pub last_login: Option<OffsetDateTime>,
+    pub active_sessions: usize,
+    pub created_at: OffsetDateTime,
+}
+
+/// Page of the admin user overview
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserOverviewPage {
+    pub users: Vec<UserOverview>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+}
+
+/// Query parameters of a user's activity timeline
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ActivityQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+/// Page of a user's security relevant activity, newest first
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActivityPage {
+    pub entries: Vec<AuditEvent>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+}
+
+/// Permission check to simulate for a user of the tenant
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PolicySimulationRequest {
+    pub user_id: Uuid,
+    pub action: PermissionAction,
+    pub resource: String,
+    /// User owning the resource, to simulate owner-scoped permissions
+    pub owner_id: Option<Uuid>,
+    /// Space separated scopes, to simulate a request with a scoped token
+    pub scope: Option<String>,
+}
+
+/// Why a simulated permission check was decided the way it was
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimulationReason {
+    /// A permission on all resources grants the action
+    Granted,
+    /// An owner-scoped permission grants the action on the user's own resource
+    GrantedAsOwner,
+    /// A deny permission overrides all grants
+    Denied,
+    /// The token scopes do not cover the action
+    OutsideTokenScopes,
+    /// No permission of the user's roles grants the action
+    NoMatchingPermission,
+    /// The configured policy engine decided differently than the role permissions
+    PolicyEngine,
+}
+
+/// Role in the inheritance chain of a permission
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoleRef {
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// Permission of a user's roles matching a simulated check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionMatch {
+    /// Roles from the one assigned to the user to the one defining the permission
+    pub chain: Vec<RoleRef>,
+    pub permission: Permission,
+}
+
+/// Outcome of a simulated permission check
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PolicySimulation {
+    pub allowed: bool,
+    pub reason: SimulationReason,
+    /// Allow and deny permissions matching the action and resource
+    pub matches: Vec<PermissionMatch>,
+}
+
+/// Role request model for tenant-defined roles
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoleRequest {
+    pub name: String,
+    pub permissions: Vec<PermissionRequest>,
+    /// Roles of the tenant whose permissions the role inherits
+    #[serde(default)]
+    pub parent_ids: Vec<Uuid>,
+}
+
+/// Validity window of a role assignment, e.g. for temporary elevated access
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RoleAssignmentRequest {
+    /// Start of the assignment, immediately if unset
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub valid_from: Option<OffsetDateTime>,
+    /// End of the assignment, permanent if unset
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub valid_until: Option<OffsetDateTime>,
+}
+
+/// Permission request model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionRequest {
+    pub name: String,
+    pub action: PermissionAction,
+    pub resource: String,
+    #[serde(default)]
+    pub scope: PermissionScope,
+    #[serde(default)]
+    pub effect: PermissionEffect,
+}
+
+impl From<RoleRequest> for Role {
+    fn from(request: RoleRequest) -> Self {
+        let mut role = Role::new(RoleType::Custom, request.name);
+        role.permissions = request
+            .permissions
+            .into_iter()
+            .map(|p| {
+                Permission::new(p.name, p.action, p.resource)
+                    .with_scope(p.scope)
+                    .with_effect(p.effect)
+            })
+            .collect();
+        role.parent_ids = request.parent_ids;
+        role
+    }
+}
+
 impl Permission {
     /// Creates a new permission
     pub fn new(name: String, action: PermissionAction, resource: String) -> Self {
@@ -148,10 +848,97 @@ impl Permission {
             name,
             action,
             resource,
+            scope: PermissionScope::All,
+            effect: PermissionEffect::Allow,
+        }
+    }
+
+    /// Sets the scope of the permission
+    pub fn with_scope(mut self, scope: PermissionScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Sets the effect of the permission
+    pub fn with_effect(mut self, effect: PermissionEffect) -> Self {
+        self.effect = effect;
+        self
+    }
+
+    /// Checks if the permission denies its action
+    pub fn is_deny(&self) -> bool {
+        self.effect == PermissionEffect::Deny
+    }
+}
+
+/// Kind of principal owning a resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OwnerType {
+    User,
+    /// Tenant role, the group concept also used by SCIM
+    Group,
+}
+
+impl std::fmt::Display for OwnerType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OwnerType::User => write!(f, "user"),
+            OwnerType::Group => write!(f, "group"),
         }
     }
 }
 
+impl std::str::FromStr for OwnerType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(OwnerType::User),
+            "group" => Ok(OwnerType::Group),
+            _ => Err(Error::InvalidInput(format!("Invalid owner type: {}", s))),
+        }
+    }
+}
+
+/// Owner of an application resource
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceOwner {
+    pub tenant_id: TenantId,
+    pub resource_type: String,
+    pub resource_id: String,
+    pub owner_type: OwnerType,
+    pub owner_id: Uuid,
+}
+
+impl ResourceOwner {
+    /// Creates a new ownership entry
+    pub fn new(
+        tenant_id: TenantId,
+        resource_type: String,
+        resource_id: String,
+        owner_type: OwnerType,
+        owner_id: Uuid,
+    ) -> Self {
+        Self {
+            tenant_id,
+            resource_type,
+            resource_id,
+            owner_type,
+            owner_id,
+        }
+    }
+
+    /// Checks if a user owns the resource directly or through one of their roles
+    pub fn is_owned_by(&self, user: &User) -> bool {
+        self.tenant_id == user.tenant_id
+            && match self.owner_type {
+                OwnerType::User => self.owner_id == user.id.0,
+                OwnerType::Group => user.roles.iter().any(|role| role.id == self.owner_id),
+            }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -194,6 +981,95 @@ mod tests {
         assert!(user.mfa_secret.is_none());
     }
 
+    #[test]
+    fn test_user_response_conversion() {
+        let mut user = User::new(
+            TenantId::new(),
+            "test@example.com".to_string(),
+            "hash".to_string(),
+        );
+        user.roles
+            .push(Role::new(RoleType::Admin, "Admin".to_string()));
+
+        let response = UserResponse::from(user.clone());
+
+        assert_eq!(response.id, user.id.0);
+        assert_eq!(response.tenant_id, user.tenant_id.0);
+        assert_eq!(response.email, user.email);
+        assert_eq!(response.roles, vec!["Admin".to_string()]);
+        assert!(response.active);
+    }
+
+    #[test]
+    fn test_user_email_verification() {
+        let mut email = UserEmail::new(
+            UserId::new(),
+            TenantId::new(),
+            "alias@example.com".to_string(),
+        );
+        assert!(!email.verified);
+        assert!(!email.is_primary);
+        assert!(email.verified_at.is_none());
+
+        email.verify();
+        assert!(email.verified);
+        assert!(email.verified_at.is_some());
+    }
+
+    #[test]
+    fn test_email_validation() {
+        for email in ["alias@example.com", "first.last+tag@mail.example.co.uk"] {
+            assert!(UserEmail::validate(email).is_ok(), "{}", email);
+        }
+        for email in [
+            "",
+            "alias",
+            "@example.com",
+            "alias@",
+            "alias@localhost",
+            "alias@@example.com",
+            "alias@example..com",
+            "alias@.example.com",
+            "alias @example.com",
+        ] {
+            assert!(
+                matches!(UserEmail::validate(email), Err(Error::Validation(_))),
+                "{}",
+                email
+            );
+        }
+    }
+
+    #[test]
+    fn test_role_request_conversion() {
+        let request: RoleRequest = serde_json::from_value(serde_json::json!({
+            "name": "Auditor",
+            "permissions": [
+                { "name": "Read Logs", "action": "Read", "resource": "audit_log" }
+            ]
+        }))
+        .unwrap();
+
+        let role = Role::from(request);
+        assert_eq!(role.role_type, RoleType::Custom);
+        assert_eq!(role.name, "Auditor");
+        assert_eq!(role.permissions.len(), 1);
+        assert_eq!(role.permissions[0].action, PermissionAction::Read);
+        assert_eq!(role.permissions[0].scope, PermissionScope::All);
+
+        let request: RoleRequest = serde_json::from_value(serde_json::json!({
+            "name": "Author",
+            "permissions": [
+                { "name": "Edit Own Posts", "action": "Update", "resource": "posts", "scope": "own" }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            Role::from(request).permissions[0].scope,
+            PermissionScope::Own
+        );
+    }
+
     #[test]
     fn test_role_creation() {
         let role_type = RoleType::Admin;
@@ -227,4 +1103,65 @@ mod tests {
         assert_eq!(PermissionAction::List.to_string(), "list");
         assert_eq!(PermissionAction::Execute.to_string(), "execute");
     }
+
+    #[test]
+    fn test_role_type_and_action_parsing() {
+        for role_type in [
+            RoleType::User,
+            RoleType::Admin,
+            RoleType::SuperAdmin,
+            RoleType::Custom,
+        ] {
+            assert_eq!(
+                role_type.to_string().parse::<RoleType>().unwrap(),
+                role_type
+            );
+        }
+        assert_eq!(
+            "delete".parse::<PermissionAction>().unwrap(),
+            PermissionAction::Delete
+        );
+        assert!("owner".parse::<RoleType>().is_err());
+        assert!("destroy".parse::<PermissionAction>().is_err());
+    }
+
+    #[test]
+    fn test_resource_ownership() {
+        let mut user = User::new(
+            TenantId::new(),
+            "jane@example.com".to_string(),
+            "hash".to_string(),
+        );
+        let role = Role::new(RoleType::Custom, "Editors".to_string());
+        user.roles.push(role.clone());
+
+        let owned = ResourceOwner::new(
+            user.tenant_id,
+            "posts".to_string(),
+            "1".to_string(),
+            OwnerType::User,
+            user.id.0,
+        );
+        assert!(owned.is_owned_by(&user));
+
+        let group_owned = ResourceOwner::new(
+            user.tenant_id,
+            "posts".to_string(),
+            "2".to_string(),
+            OwnerType::Group,
+            role.id,
+        );
+        assert!(group_owned.is_owned_by(&user));
+
+        let foreign = ResourceOwner::new(
+            TenantId::new(),
+            "posts".to_string(),
+            "1".to_string(),
+            OwnerType::User,
+            user.id.0,
+        );
+        assert!(!foreign.is_owned_by(&user));
+        assert_eq!("group".parse::<OwnerType>().unwrap(), OwnerType::Group);
+        assert!("team".parse::<OwnerType>().is_err());
+    }
 }
@@ -0,0 +1,407 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    core::config::{PolicyConfig, PolicyEngineKind},
+    modules::identity::{
+        models::{PermissionAction, PermissionScope, User},
+        rbac::{has_permission, has_scoped_permission},
+    },
+    shared::error::{Error, Result},
+};
+
+/// Timeout of requests to external policy engines
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Extension point deciding whether a user may perform an action on a resource
+#[async_trait]
+pub trait PolicyEngine: Send + Sync + std::fmt::Debug {
+    /// Decides an authorization request; errors are returned to the caller
+    async fn is_allowed(
+        &self,
+        user: &User,
+        action: PermissionAction,
+        resource: &str,
+    ) -> Result<bool>;
+
+    /// Decides an authorization request on a resource the user owns
+    ///
+    /// By default owning a resource grants nothing beyond [`PolicyEngine::is_allowed`].
+    async fn is_allowed_for_owner(
+        &self,
+        user: &User,
+        action: PermissionAction,
+        resource: &str,
+    ) -> Result<bool> {
+        self.is_allowed(user, action, resource).await
+    }
+}
+
+/// Default engine deciding with the permissions of the user's roles
+#[derive(Debug, Clone, Default)]
+pub struct RbacPolicyEngine;
+
+#[async_trait]
+impl PolicyEngine for RbacPolicyEngine {
+    async fn is_allowed(
+        &self,
+        user: &User,
+        action: PermissionAction,
+        resource: &str,
+    ) -> Result<bool> {
+        Ok(has_permission(user, action, resource))
+    }
+
+    async fn is_allowed_for_owner(
+        &self,
+        user: &User,
+        action: PermissionAction,
+        resource: &str,
+    ) -> Result<bool> {
+        Ok(has_permission(user, action, resource)
+            || (user.scopes_allow(action, resource)
+                && has_scoped_permission(&user.roles, action, resource, PermissionScope::Own)))
+    }
+}
+
+/// Authorization request sent to external policy engines
+#[derive(Debug, Serialize)]
+pub struct PolicyInput {
+    pub user_id: String,
+    pub tenant_id: String,
+    pub roles: Vec<String>,
+    pub action: String,
+    pub resource: String,
+    /// Whether the user owns the resource, e.g. their own profile
+    pub owner: bool,
+}
+
+impl PolicyInput {
+    /// Creates the input of an authorization request
+    pub fn new(user: &User, action: PermissionAction, resource: &str) -> Self {
+        Self {
+            user_id: user.id.0.to_string(),
+            tenant_id: user.tenant_id.0.to_string(),
+            roles: user.roles.iter().map(|role| role.name.clone()).collect(),
+            action: action.to_string(),
+            resource: resource.to_string(),
+            owner: false,
+        }
+    }
+
+    /// Marks the request as one on a resource the user owns
+    pub fn with_owner(mut self) -> Self {
+        self.owner = true;
+        self
+    }
+}
+
+/// Engine querying an Open Policy Agent sidecar through its data API
+///
+/// The policy at `policy_path` must evaluate to a boolean, e.g. `acci/authz/allow`;
+/// undefined decisions deny the request.
+#[derive(Debug, Clone)]
+pub struct OpaPolicyEngine {
+    decision_url: String,
+    client: reqwest::Client,
+}
+
+impl OpaPolicyEngine {
+    /// Creates an engine for the OPA server at `url`
+    pub fn new(url: &str, policy_path: &str) -> Self {
+        Self {
+            decision_url: format!(
+                "{}/v1/data/{}",
+                url.trim_end_matches('/'),
+                policy_path.trim_matches('/')
+            ),
+            client: http_client(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct OpaRequest {
+    input: PolicyInput,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpaResponse {
+    result: Option<bool>,
+}
+
+impl OpaPolicyEngine {
+    /// Queries the decision of the policy
+    async fn decide(&self, input: PolicyInput) -> Result<bool> {
+        let request = OpaRequest { input };
+        let response: OpaResponse = post_json(&self.client, &self.decision_url, &request).await?;
+        Ok(response.result.unwrap_or(false))
+    }
+}
+
+#[async_trait]
+impl PolicyEngine for OpaPolicyEngine {
+    async fn is_allowed(
+        &self,
+        user: &User,
+        action: PermissionAction,
+        resource: &str,
+    ) -> Result<bool> {
+        self.decide(PolicyInput::new(user, action, resource)).await
+    }
+
+    async fn is_allowed_for_owner(
+        &self,
+        user: &User,
+        action: PermissionAction,
+        resource: &str,
+    ) -> Result<bool> {
+        self.decide(PolicyInput::new(user, action, resource).with_owner())
+            .await
+    }
+}
+
+/// Engine querying a Cedar agent sidecar
+///
+/// Users are sent as `User::"<id>"` principals, actions as `Action::"<action>"`
+/// and resources as `Resource::"<resource>"`, with tenant and role names as context.
+#[derive(Debug, Clone)]
+pub struct CedarPolicyEngine {
+    authorize_url: String,
+    client: reqwest::Client,
+}
+
+impl CedarPolicyEngine {
+    /// Creates an engine for the Cedar agent at `url`
+    pub fn new(url: &str) -> Self {
+        Self {
+            authorize_url: format!("{}/v1/is_authorized", url.trim_end_matches('/')),
+            client: http_client(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CedarRequest {
+    principal: String,
+    action: String,
+    resource: String,
+    context: CedarContext,
+}
+
+/// Context of a Cedar authorization request
+#[derive(Debug, Serialize)]
+struct CedarContext {
+    tenant_id: String,
+    roles: Vec<String>,
+    owner: bool,
+}
+
+impl CedarRequest {
+    fn new(input: PolicyInput) -> Self {
+        Self {
+            principal: cedar_uid("User", &input.user_id),
+            action: cedar_uid("Action", &input.action),
+            resource: cedar_uid("Resource", &input.resource),
+            context: CedarContext {
+                tenant_id: input.tenant_id,
+                roles: input.roles,
+                owner: input.owner,
+            },
+        }
+    }
+}
+
+/// Builds the UID of a Cedar entity, escaping its ID as a Cedar string literal
+///
+/// Only quotes, backslashes and control characters are escaped; other characters,
+/// non-ASCII ones included, are valid in Cedar strings as they are.
+fn cedar_uid(entity_type: &str, id: &str) -> String {
+    let mut uid = format!("{}::\"", entity_type);
+    for c in id.chars() {
+        match c {
+            '"' => uid.push_str("\\\""),
+            '\\' => uid.push_str("\\\\"),
+            '\n' => uid.push_str("\\n"),
+            '\r' => uid.push_str("\\r"),
+            '\t' => uid.push_str("\\t"),
+            '\0' => uid.push_str("\\0"),
+            c if c.is_control() => uid.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => uid.push(c),
+        }
+    }
+    uid.push('"');
+    uid
+}
+
+#[derive(Debug, Deserialize)]
+struct CedarResponse {
+    decision: String,
+}
+
+impl CedarPolicyEngine {
+    /// Queries the decision of the agent
+    async fn decide(&self, input: PolicyInput) -> Result<bool> {
+        let request = CedarRequest::new(input);
+        let response: CedarResponse =
+            post_json(&self.client, &self.authorize_url, &request).await?;
+        Ok(response.decision == "Allow")
+    }
+}
+
+#[async_trait]
+impl PolicyEngine for CedarPolicyEngine {
+    async fn is_allowed(
+        &self,
+        user: &User,
+        action: PermissionAction,
+        resource: &str,
+    ) -> Result<bool> {
+        self.decide(PolicyInput::new(user, action, resource)).await
+    }
+
+    async fn is_allowed_for_owner(
+        &self,
+        user: &User,
+        action: PermissionAction,
+        resource: &str,
+    ) -> Result<bool> {
+        self.decide(PolicyInput::new(user, action, resource).with_owner())
+            .await
+    }
+}
+
+/// Creates the policy engine selected in the configuration
+pub fn create_policy_engine(config: &PolicyConfig) -> Result<Arc<dyn PolicyEngine>> {
+    let url = || {
+        config.url.as_deref().ok_or_else(|| {
+            Error::InvalidInput(format!("Policy engine {} requires a URL", config.engine))
+        })
+    };
+    Ok(match config.engine {
+        PolicyEngineKind::Rbac => Arc::new(RbacPolicyEngine),
+        PolicyEngineKind::Opa => Arc::new(OpaPolicyEngine::new(url()?, &config.opa_policy_path)),
+        PolicyEngineKind::Cedar => Arc::new(CedarPolicyEngine::new(url()?)),
+    })
+}
+
+fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+/// Posts a JSON request and parses the JSON response
+async fn post_json<T, R>(client: &reqwest::Client, url: &str, body: &T) -> Result<R>
+where
+    T: Serialize + ?Sized,
+    R: serde::de::DeserializeOwned,
+{
+    let response = client
+        .post(url)
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| Error::Internal(format!("Failed to query policy engine: {}", e)))?;
+    if !response.status().is_success() {
+        return Err(Error::Internal(format!(
+            "Policy engine responded with status {}",
+            response.status()
+        )));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| Error::Internal(format!("Invalid policy engine response: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        modules::identity::rbac::{create_admin_role, create_user_role},
+        shared::types::TenantId,
+    };
+
+    fn admin() -> User {
+        let mut user = User::new(
+            TenantId::new(),
+            "admin@example.com".to_string(),
+            "hash".to_string(),
+        );
+        user.roles = vec![create_admin_role()];
+        user
+    }
+
+    #[tokio::test]
+    async fn test_rbac_policy_engine() {
+        let user = admin();
+        let engine = RbacPolicyEngine;
+        assert!(engine
+            .is_allowed(&user, PermissionAction::Read, "users")
+            .await
+            .unwrap());
+        assert!(!engine
+            .is_allowed(&user, PermissionAction::Execute, "users")
+            .await
+            .unwrap());
+
+        // Owner-scoped grants only apply to resources the user owns
+        let mut user = User::new(
+            user.tenant_id,
+            "user@example.com".to_string(),
+            "hash".to_string(),
+        );
+        user.roles = vec![create_user_role()];
+        assert!(!engine
+            .is_allowed(&user, PermissionAction::Update, "users")
+            .await
+            .unwrap());
+        assert!(engine
+            .is_allowed_for_owner(&user, PermissionAction::Update, "users")
+            .await
+            .unwrap());
+    }
+
+    #[test]
+    fn test_policy_requests() {
+        let user = admin();
+        let opa = OpaPolicyEngine::new("http://localhost:8181/", "/acci/authz/allow");
+        assert_eq!(
+            opa.decision_url,
+            "http://localhost:8181/v1/data/acci/authz/allow"
+        );
+
+        let request = CedarRequest::new(PolicyInput::new(&user, PermissionAction::Read, "users"));
+        assert_eq!(request.principal, format!("User::\"{}\"", user.id.0));
+        assert_eq!(request.action, "Action::\"read\"");
+        assert_eq!(request.resource, "Resource::\"users\"");
+        assert_eq!(request.context.roles, ["Admin"]);
+        assert!(!request.context.owner);
+        let request = CedarRequest::new(
+            PolicyInput::new(&user, PermissionAction::Update, "users").with_owner(),
+        );
+        assert!(request.context.owner);
+
+        // IDs are escaped as Cedar string literals rather than Rust ones
+        assert_eq!(
+            cedar_uid("Resource", r#"docs/"quoted"\path"#),
+            r#"Resource::"docs/\"quoted\"\\path""#
+        );
+        assert_eq!(cedar_uid("Resource", "café\n"), r#"Resource::"café\n""#);
+        assert_eq!(cedar_uid("Resource", "\u{7}"), r#"Resource::"\u{7}""#);
+
+        // External engines need the address of their sidecar
+        let config = PolicyConfig {
+            engine: PolicyEngineKind::Opa,
+            ..PolicyConfig::default()
+        };
+        assert!(matches!(
+            create_policy_engine(&config),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(create_policy_engine(&PolicyConfig::default()).is_ok());
+    }
+}
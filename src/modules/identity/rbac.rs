@@ -1,65 +1,255 @@
 use moka::sync::Cache;
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+    marker::PhantomData,
+    sync::Arc,
+};
+use uuid::Uuid;
 
 use crate::{
-    modules::identity::models::{Permission, PermissionAction, Role, RoleType, User},
+    modules::identity::{
+        models::{
+            Permission, PermissionAction, PermissionMatch, PermissionScope, ResourceOwner, Role,
+            RoleRef, RoleType, SimulationReason, TokenScope, User,
+        },
+        policy::{PolicyEngine, RbacPolicyEngine},
+    },
     shared::{
-        error::Result,
+        cache::{CacheInvalidation, CacheInvalidationHandler},
+        error::{Error, Result},
         types::{TenantId, UserId},
     },
 };
 
+/// Outcome of a permission check, recorded to investigate access issues
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthorizationDecision {
+    pub tenant_id: TenantId,
+    pub user_id: UserId,
+    pub action: PermissionAction,
+    pub resource: String,
+    pub owner_id: Option<UserId>,
+    pub allowed: bool,
+    /// ID of the request the check was part of, e.g. from the `x-request-id` header
+    pub request_id: Option<String>,
+}
+
+/// Extension point receiving the decisions of permission checks, e.g. to audit them
+#[async_trait::async_trait]
+pub trait DecisionRecorder: Send + Sync + std::fmt::Debug {
+    /// Records a decision; failures are handled by the recorder since the check already happened
+    async fn record(&self, decision: AuthorizationDecision);
+}
+
 /// RBAC service for handling permissions
 #[derive(Debug)]
 pub struct RbacService {
     permission_cache: Cache<String, bool>,
+    engine: Arc<dyn PolicyEngine>,
+    recorder: Option<Arc<dyn DecisionRecorder>>,
+    record_allowed: bool,
 }
 
 impl Default for RbacService {
     fn default() -> Self {
+        Self::with_policy_engine(Arc::new(RbacPolicyEngine))
+    }
+}
+
+impl RbacService {
+    /// Creates a new RbacService instance
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a RbacService delegating decisions to a policy engine; decisions are cached as well
+    pub fn with_policy_engine(engine: Arc<dyn PolicyEngine>) -> Self {
         Self {
             permission_cache: Cache::builder()
                 .max_capacity(10_000)
                 .time_to_live(std::time::Duration::from_secs(300))
+                .support_invalidation_closures()
                 .build(),
+            engine,
+            recorder: None,
+            record_allowed: false,
         }
     }
-}
 
-impl RbacService {
-    /// Creates a new RbacService instance
-    pub fn new() -> Self {
-        Self::default()
+    /// Records denied permission checks, and allowed ones as well if `record_allowed` is set
+    pub fn with_decision_recorder(
+        mut self,
+        recorder: Arc<dyn DecisionRecorder>,
+        record_allowed: bool,
+    ) -> Self {
+        self.recorder = Some(recorder);
+        self.record_allowed = record_allowed;
+        self
+    }
+
+    /// Gets the policy engine deciding the permission checks
+    pub fn policy_engine(&self) -> Arc<dyn PolicyEngine> {
+        self.engine.clone()
     }
 
     /// Checks if a user has a specific permission
+    ///
+    /// With the ID of the user owning the resource, e.g. the user whose profile is
+    /// updated, owner-scoped permissions grant access to the user's own resources.
     pub async fn check_permission(
         &self,
         user: &User,
         action: PermissionAction,
         resource: &str,
+        owner_id: Option<UserId>,
     ) -> Result<bool> {
-        let cache_key = format!("{}:{}:{}", user.id.0, action, resource);
+        self.check_request_permission(user, action, resource, owner_id, None)
+            .await
+    }
+
+    /// Checks a permission on behalf of a request, recording the decision with the request's ID
+    pub async fn check_request_permission(
+        &self,
+        user: &User,
+        action: PermissionAction,
+        resource: &str,
+        owner_id: Option<UserId>,
+        request_id: Option<&str>,
+    ) -> Result<bool> {
+        let allowed = self
+            .decide(user, action, resource, owner_id == Some(user.id))
+            .await?;
+        self.record(user, action, resource, owner_id, allowed, request_id)
+            .await;
+        Ok(allowed)
+    }
+
+    /// Passes a decision to the recorder, if any records decisions of its outcome
+    async fn record(
+        &self,
+        user: &User,
+        action: PermissionAction,
+        resource: &str,
+        owner_id: Option<UserId>,
+        allowed: bool,
+        request_id: Option<&str>,
+    ) {
+        let Some(recorder) = &self.recorder else {
+            return;
+        };
+        if allowed && !self.record_allowed {
+            return;
+        }
+        recorder
+            .record(AuthorizationDecision {
+                tenant_id: user.tenant_id,
+                user_id: user.id,
+                action,
+                resource: resource.to_string(),
+                owner_id,
+                allowed,
+                request_id: request_id.map(str::to_string),
+            })
+            .await;
+    }
+
+    /// Decides a permission check without recording it, e.g. to simulate it for an admin
+    pub async fn dry_run(
+        &self,
+        user: &User,
+        action: PermissionAction,
+        resource: &str,
+        owner_id: Option<UserId>,
+    ) -> Result<bool> {
+        self.decide(user, action, resource, owner_id == Some(user.id))
+            .await
+    }
+
+    /// Decides a permission check without recording it
+    ///
+    /// Checks on resources the user owns are decided by the policy engine as well,
+    /// which lets owner-scoped grants apply.
+    async fn decide(
+        &self,
+        user: &User,
+        action: PermissionAction,
+        resource: &str,
+        owner: bool,
+    ) -> Result<bool> {
+        // Token scopes differ between sessions of a user, so they are checked before the cache;
+        // deny permissions win over policy engines as well
+        if !user.scopes_allow(action, resource) || is_denied(&user.roles, action, resource) {
+            return Ok(false);
+        }
+
+        let cache_key = if owner {
+            format!("{}:{}:{}:owner", user.id.0, action, resource)
+        } else {
+            format!("{}:{}:{}", user.id.0, action, resource)
+        };
 
         if let Some(has_permission) = self.permission_cache.get(&cache_key) {
             return Ok(has_permission);
         }
 
-        let has_permission = user.roles.iter().any(|role| {
-            role.permissions
-                .iter()
-                .any(|permission| permission.action == action && permission.resource == resource)
-        });
+        let has_permission = if owner {
+            self.engine
+                .is_allowed_for_owner(user, action, resource)
+                .await?
+        } else {
+            self.engine.is_allowed(user, action, resource).await?
+        };
 
         self.permission_cache.insert(cache_key, has_permission);
         Ok(has_permission)
     }
 
+    /// Checks if a user has a permission on a specific resource, honoring owner-scoped grants
+    pub async fn check_resource_permission(
+        &self,
+        user: &User,
+        action: PermissionAction,
+        resource: &str,
+        owners: &[ResourceOwner],
+    ) -> Result<bool> {
+        let owner = owners
+            .iter()
+            .any(|owner| owner.resource_type == resource && owner.is_owned_by(user));
+        let allowed = self.decide(user, action, resource, owner).await?;
+        self.record(user, action, resource, None, allowed, None)
+            .await;
+        Ok(allowed)
+    }
+
     /// Clears the permission cache for a user
-    pub fn clear_user_cache(&self, _user_id: UserId) {
+    pub fn clear_user_cache(&self, user_id: UserId) {
+        let prefix = format!("{}:", user_id.0);
+        if self
+            .permission_cache
+            .invalidate_entries_if(move |key, _| key.starts_with(&prefix))
+            .is_err()
+        {
+            self.permission_cache.invalidate_all();
+        }
+    }
+
+    /// Clears the permission cache for all users, e.g. after a role changed
+    pub fn clear_cache(&self) {
         self.permission_cache.invalidate_all();
     }
 }
 
+impl CacheInvalidationHandler for RbacService {
+    fn invalidate(&self, invalidation: &CacheInvalidation) {
+        match invalidation {
+            CacheInvalidation::User { user_id } => self.clear_user_cache(*user_id),
+            // Cache keys carry no tenant, so tenant changes drop all entries
+            CacheInvalidation::Tenant { .. } | CacheInvalidation::Permissions => self.clear_cache(),
+            CacheInvalidation::Session { .. } | CacheInvalidation::TenantSettings { .. } => {},
+        }
+    }
+}
+
 /// Permission check trait for request handlers
 #[async_trait::async_trait]
 pub trait PermissionCheck {
@@ -76,21 +266,478 @@ pub trait PermissionCheck {
     fn required_resource(&self) -> &str;
 }
 
-/// Require permission attribute for request handlers
-pub struct RequirePermission {
-    pub action: PermissionAction,
-    pub resource: String,
+/// Action a [`RequirePermission`] extractor requires
+pub trait RequiredAction: Send + Sync + 'static {
+    const ACTION: PermissionAction;
+}
+
+/// Resource a [`RequirePermission`] extractor requires
+///
+/// Implement it on a marker type to guard resources of other modules.
+pub trait RequiredResource: Send + Sync + 'static {
+    const RESOURCE: &'static str;
+}
+
+/// Marker types for the actions of [`RequirePermission`]
+pub mod action {
+    use super::{PermissionAction, RequiredAction};
+
+    macro_rules! required_action {
+        ($($name:ident),*) => {
+            $(
+                #[derive(Debug, Clone, Copy)]
+                pub struct $name;
+
+                impl RequiredAction for $name {
+                    const ACTION: PermissionAction = PermissionAction::$name;
+                }
+            )*
+        };
+    }
+
+    required_action!(Create, Read, Update, Delete, List, Execute);
+}
+
+/// Marker types for the built-in resources of [`RequirePermission`]
+pub mod resource {
+    use super::RequiredResource;
+
+    /// Users of a tenant
+    #[derive(Debug, Clone, Copy)]
+    pub struct Users;
+
+    impl RequiredResource for Users {
+        const RESOURCE: &'static str = "users";
+    }
+
+    /// Projects of a tenant
+    #[derive(Debug, Clone, Copy)]
+    pub struct Projects;
+
+    impl RequiredResource for Projects {
+        const RESOURCE: &'static str = "projects";
+    }
+
+    /// Audit log of a tenant
+    #[derive(Debug, Clone, Copy)]
+    pub struct AuditLog;
+
+    impl RequiredResource for AuditLog {
+        const RESOURCE: &'static str = "audit_log";
+    }
+
+    /// Tenants and their settings, domains and templates
+    #[derive(Debug, Clone, Copy)]
+    pub struct Tenants;
+
+    impl RequiredResource for Tenants {
+        const RESOURCE: &'static str = "tenants";
+    }
+}
+
+/// Handler extractor for the authenticated user holding a permission
+///
+/// Requests without a valid bearer token are rejected with 401, those of users
+/// lacking the permission with 403:
+///
+/// ```ignore
+/// async fn handler(
+///     RequirePermission(actor, _): RequirePermission<action::Update, resource::Users>,
+/// ) {}
+/// ```
+pub struct RequirePermission<A, R>(pub User, pub PhantomData<fn() -> (A, R)>);
+
+impl<A, R> RequirePermission<A, R> {
+    /// Wraps a user already checked for the permission
+    pub fn new(user: User) -> Self {
+        Self(user, PhantomData)
+    }
+}
+
+impl<A: RequiredAction, R: RequiredResource> PermissionCheck for RequirePermission<A, R> {
+    fn user_id(&self) -> Option<UserId> {
+        Some(self.0.id)
+    }
+
+    fn tenant_id(&self) -> Option<TenantId> {
+        Some(self.0.tenant_id)
+    }
+
+    fn required_action(&self) -> PermissionAction {
+        A::ACTION
+    }
+
+    fn required_resource(&self) -> &str {
+        R::RESOURCE
+    }
 }
 
 /// Checks if a user has the required permission
+///
+/// Users authenticated with a scoped token only hold the permissions granted by
+/// both their roles and the token's scopes.
 pub fn has_permission(user: &User, action: PermissionAction, resource: &str) -> bool {
-    user.roles.iter().any(|role| {
-        role.permissions
-            .iter()
-            .any(|permission| permission.action == action && permission.resource == resource)
+    user.scopes_allow(action, resource) && has_role_permission(&user.roles, action, resource)
+}
+
+/// Checks if any of the given roles grants the required permission on all resources
+pub fn has_role_permission(roles: &[Role], action: PermissionAction, resource: &str) -> bool {
+    has_scoped_permission(roles, action, resource, PermissionScope::All)
+}
+
+/// Checks if any of the given roles grants the required permission with the given scope
+///
+/// Both the roles' own and inherited permissions count. The resource of a
+/// permission is a pattern matched with [`resource_matches`]. Deny permissions
+/// of any of the roles win over all grants, see [`is_denied`].
+pub fn has_scoped_permission(
+    roles: &[Role],
+    action: PermissionAction,
+    resource: &str,
+    scope: PermissionScope,
+) -> bool {
+    !is_denied(roles, action, resource)
+        && roles.iter().any(|role| {
+            role.effective_permissions().any(|permission| {
+                !permission.is_deny()
+                    && permission.action == action
+                    && permission.scope == scope
+                    && resource_matches(&permission.resource, resource)
+            })
+        })
+}
+
+/// Checks if any of the given roles denies an action on a resource
+///
+/// Deny permissions apply in every scope, whether they are the roles' own or inherited.
+pub fn is_denied(roles: &[Role], action: PermissionAction, resource: &str) -> bool {
+    roles.iter().any(|role| {
+        role.effective_permissions().any(|permission| {
+            permission.is_deny()
+                && permission.action == action
+                && resource_matches(&permission.resource, resource)
+        })
     })
 }
 
+/// Checks if a permission's resource pattern covers a resource
+///
+/// Resources are `/` separated paths such as `tenants/acme/users`. Patterns
+/// are matched segment by segment:
+///
+/// - a literal segment only matches the same segment,
+/// - `*` matches exactly one segment, e.g. `tenants/*/users`,
+/// - a trailing `**` matches the remaining path including its parent, e.g.
+///   `tenants/acme/**` covers `tenants/acme` and everything below it,
+/// - a pattern consisting of `*` alone matches every resource.
+///
+/// Permissions only grant, so the first matching pattern wins and no pattern is
+/// more specific than another. Wildcards in the checked resource are literal;
+/// they are only covered by wildcards at the same position, so granting
+/// `tenants/*/users` to others requires holding `tenants/*/users`, `tenants/**` or `*`.
+pub fn resource_matches(pattern: &str, resource: &str) -> bool {
+    if pattern == "*" {
+        return true;
+    }
+
+    let mut pattern = pattern.split('/');
+    let mut resource = resource.split('/');
+    loop {
+        match (pattern.next(), resource.next()) {
+            (Some("**"), _) => return pattern.next().is_none(),
+            (Some("*"), Some(_)) => {},
+            (Some(expected), Some(segment)) if expected == segment => {},
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Inheritance graph of the roles of a tenant
+///
+/// Roles inherit the permissions of their parents and, recursively, of the
+/// parents' ancestors, e.g. an Admin role with the parent User holds every
+/// permission of User. Stored hierarchies are kept acyclic by
+/// [`RoleHierarchy::check_parents`]; resolution still visits every role once.
+#[derive(Debug, Clone, Default)]
+pub struct RoleHierarchy {
+    parents: HashMap<Uuid, Vec<Uuid>>,
+    permissions: HashMap<Uuid, Vec<Permission>>,
+}
+
+impl RoleHierarchy {
+    /// Creates an empty hierarchy
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the hierarchy of the given roles from their own permissions and parents
+    pub fn from_roles(roles: &[Role]) -> Self {
+        let mut hierarchy = Self::new();
+        for role in roles {
+            hierarchy.add_role(role.id, role.parent_ids.clone(), role.permissions.clone());
+        }
+        hierarchy
+    }
+
+    /// Adds a role with its direct parents and own permissions
+    pub fn add_role(&mut self, role_id: Uuid, parent_ids: Vec<Uuid>, permissions: Vec<Permission>) {
+        self.parents.insert(role_id, parent_ids);
+        self.permissions.insert(role_id, permissions);
+    }
+
+    /// Gets the direct parents of a role
+    pub fn parents(&self, role_id: Uuid) -> &[Uuid] {
+        self.parents.get(&role_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Gets all ancestors of a role, nearest first
+    pub fn ancestors(&self, role_id: Uuid) -> Vec<Uuid> {
+        let mut visited = HashSet::from([role_id]);
+        let mut ancestors = Vec::new();
+        let mut pending: Vec<Uuid> = self.parents(role_id).iter().rev().copied().collect();
+        while let Some(id) = pending.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            ancestors.push(id);
+            pending.extend(self.parents(id).iter().rev());
+        }
+        ancestors
+    }
+
+    /// Gets the permissions a role inherits from its ancestors
+    pub fn inherited_permissions(&self, role_id: Uuid) -> Vec<Permission> {
+        self.ancestors(role_id)
+            .iter()
+            .filter_map(|id| self.permissions.get(id))
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// Gets the shortest chain of roles from a role up to one of its ancestors, both included
+    pub fn path(&self, role_id: Uuid, ancestor_id: Uuid) -> Option<Vec<Uuid>> {
+        let mut children = HashMap::from([(role_id, role_id)]);
+        let mut pending = VecDeque::from([role_id]);
+        while let Some(id) = pending.pop_front() {
+            if id == ancestor_id {
+                let mut path = vec![id];
+                let mut current = id;
+                while current != role_id {
+                    current = children[&current];
+                    path.push(current);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &parent_id in self.parents(id) {
+                if let Entry::Vacant(entry) = children.entry(parent_id) {
+                    entry.insert(id);
+                    pending.push_back(parent_id);
+                }
+            }
+        }
+        None
+    }
+
+    /// Sets the parents and inherited permissions of a role of the hierarchy
+    pub fn resolve(&self, role: &mut Role) {
+        role.parent_ids = self.parents(role.id).to_vec();
+        role.inherited_permissions = self.inherited_permissions(role.id);
+    }
+
+    /// Ensures a role may declare the given parents
+    ///
+    /// Parents must be known roles other than the role itself, and none of them
+    /// may descend from the role, which would make the hierarchy cyclic.
+    pub fn check_parents(&self, role_id: Uuid, parent_ids: &[Uuid]) -> Result<()> {
+        for &parent_id in parent_ids {
+            if parent_id == role_id {
+                return Err(Error::Validation(
+                    "A role cannot inherit from itself".to_string(),
+                ));
+            }
+            if !self.parents.contains_key(&parent_id) {
+                return Err(Error::NotFound(format!(
+                    "Parent role {} not found",
+                    parent_id
+                )));
+            }
+            if self.ancestors(parent_id).contains(&role_id) {
+                return Err(Error::Validation(format!(
+                    "Inheriting from role {} would create a cycle",
+                    parent_id
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks if a user has a permission on a resource with the given owners
+///
+/// Owner-scoped permissions only apply if the user owns the resource, directly
+/// or through one of their roles.
+pub fn has_owner_permission(
+    user: &User,
+    action: PermissionAction,
+    resource: &str,
+    owners: &[ResourceOwner],
+) -> bool {
+    has_permission(user, action, resource)
+        || (user.scopes_allow(action, resource)
+            && has_scoped_permission(&user.roles, action, resource, PermissionScope::Own)
+            && owners
+                .iter()
+                .any(|owner| owner.resource_type == resource && owner.is_owned_by(user)))
+}
+
+/// Finds the permissions of a user's roles matching an action on a resource
+///
+/// Both allow and deny permissions are returned, each with the chain of roles
+/// from the assigned role to the one defining it, resolved with the tenant's roles.
+pub fn matching_permissions(
+    roles: &[Role],
+    tenant_roles: &[Role],
+    action: PermissionAction,
+    resource: &str,
+) -> Vec<PermissionMatch> {
+    let hierarchy = RoleHierarchy::from_roles(tenant_roles);
+    let names: HashMap<Uuid, &str> = tenant_roles
+        .iter()
+        .chain(roles)
+        .map(|role| (role.id, role.name.as_str()))
+        .collect();
+    let matching = |permissions: &[Permission]| -> Vec<Permission> {
+        permissions
+            .iter()
+            .filter(|p| p.action == action && resource_matches(&p.resource, resource))
+            .cloned()
+            .collect()
+    };
+
+    let mut matches = Vec::new();
+    for role in roles {
+        let mut defining = vec![(vec![role.id], matching(&role.permissions))];
+        for ancestor in hierarchy
+            .ancestors(role.id)
+            .iter()
+            .filter_map(|id| tenant_roles.iter().find(|r| r.id == *id))
+        {
+            if let Some(path) = hierarchy.path(role.id, ancestor.id) {
+                defining.push((path, matching(&ancestor.permissions)));
+            }
+        }
+
+        for (path, permissions) in defining {
+            let chain: Vec<RoleRef> = path
+                .iter()
+                .map(|id| RoleRef {
+                    id: *id,
+                    name: names.get(id).copied().unwrap_or_default().to_string(),
+                })
+                .collect();
+            matches.extend(permissions.into_iter().map(|permission| PermissionMatch {
+                chain: chain.clone(),
+                permission,
+            }));
+        }
+    }
+    matches
+}
+
+/// Explains the decision of the role permissions on a check, mirroring [`RbacService`]
+pub fn simulation_reason(
+    user: &User,
+    matches: &[PermissionMatch],
+    action: PermissionAction,
+    resource: &str,
+    owner_id: Option<UserId>,
+) -> SimulationReason {
+    let granted = |scope: PermissionScope| {
+        matches
+            .iter()
+            .any(|m| !m.permission.is_deny() && m.permission.scope == scope)
+    };
+    if !user.scopes_allow(action, resource) {
+        SimulationReason::OutsideTokenScopes
+    } else if matches.iter().any(|m| m.permission.is_deny()) {
+        SimulationReason::Denied
+    } else if granted(PermissionScope::All) {
+        SimulationReason::Granted
+    } else if owner_id == Some(user.id) && granted(PermissionScope::Own) {
+        SimulationReason::GrantedAsOwner
+    } else {
+        SimulationReason::NoMatchingPermission
+    }
+}
+
+/// Checks if the given roles allow granting a permission to others
+///
+/// Owner-scoped permissions can be granted by anyone holding the permission in any
+/// scope, unrestricted ones only by those holding them unrestricted. Deny
+/// permissions only restrict and can always be granted.
+pub fn can_grant(roles: &[Role], permission: &Permission) -> bool {
+    permission.is_deny()
+        || has_role_permission(roles, permission.action, &permission.resource)
+        || (permission.scope == PermissionScope::Own
+            && has_scoped_permission(
+                roles,
+                permission.action,
+                &permission.resource,
+                PermissionScope::Own,
+            ))
+}
+
+/// Ensures a user may delegate scopes to a token
+///
+/// Every scope must be granted by the user's roles, in any permission scope, and
+/// by the scopes of the token the user authenticated with, so tokens can only be narrowed.
+pub fn ensure_scopes_held(user: &User, scopes: &[TokenScope]) -> Result<()> {
+    for scope in scopes {
+        let held = has_role_permission(&user.roles, scope.action, &scope.resource)
+            || has_scoped_permission(
+                &user.roles,
+                scope.action,
+                &scope.resource,
+                PermissionScope::Own,
+            );
+        if !held || !user.scopes_allow(scope.action, &scope.resource) {
+            return Err(Error::Authorization(format!(
+                "Cannot delegate scope {} without holding it",
+                scope
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Checks whether the user is a super admin, the only role administering across tenants
+pub fn is_super_admin(user: &User) -> bool {
+    user.roles
+        .iter()
+        .any(|role| role.role_type == RoleType::SuperAdmin)
+}
+
+/// Checks whether the user administers their tenant, as an admin or super admin
+pub fn is_tenant_admin(user: &User) -> bool {
+    user.roles
+        .iter()
+        .any(|role| matches!(role.role_type, RoleType::Admin | RoleType::SuperAdmin))
+}
+
+/// Ensures the actor may administer resources of a tenant
+///
+/// Tenant admins are confined to their own tenant whatever permissions their roles
+/// grant, so services enforce this before touching users, roles or providers.
+pub fn ensure_tenant_boundary(actor: &User, tenant_id: TenantId) -> Result<()> {
+    if actor.tenant_id != tenant_id && !is_super_admin(actor) {
+        return Err(Error::Authorization("Access to tenant denied".to_string()));
+    }
+    Ok(())
+}
+
 /// Creates a new user role
 pub fn create_user_role() -> Role {
     let mut role = Role::new(RoleType::User, "User".to_string());
@@ -105,6 +752,12 @@ pub fn create_user_role() -> Role {
             PermissionAction::Read,
             "users".to_string(),
         ),
+        Permission::new(
+            "Update Own Profile".to_string(),
+            PermissionAction::Update,
+            "users".to_string(),
+        )
+        .with_scope(PermissionScope::Own),
     ];
     role
 }
@@ -133,6 +786,26 @@ pub fn create_admin_role() -> Role {
             PermissionAction::Delete,
             "users".to_string(),
         ),
+        Permission::new(
+            "Create Role".to_string(),
+            PermissionAction::Create,
+            "roles".to_string(),
+        ),
+        Permission::new(
+            "Read Role".to_string(),
+            PermissionAction::Read,
+            "roles".to_string(),
+        ),
+        Permission::new(
+            "Update Role".to_string(),
+            PermissionAction::Update,
+            "roles".to_string(),
+        ),
+        Permission::new(
+            "Delete Role".to_string(),
+            PermissionAction::Delete,
+            "roles".to_string(),
+        ),
     ];
     role
 }
@@ -152,7 +825,9 @@ pub fn create_super_admin_role() -> Role {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::modules::identity::models::{Permission, Role, User};
+    use crate::modules::identity::models::{
+        OwnerType, Permission, PermissionEffect, Role, TokenScope, User,
+    };
     use time::OffsetDateTime;
     use uuid::Uuid;
 
@@ -172,6 +847,8 @@ mod tests {
                     name: "Create User".to_string(),
                     action: PermissionAction::Create,
                     resource: "users".to_string(),
+                    scope: PermissionScope::All,
+                    effect: PermissionEffect::Allow,
                 }];
                 role
             }],
@@ -181,25 +858,26 @@ mod tests {
             updated_at: OffsetDateTime::now_utc(),
             mfa_enabled: false,
             mfa_secret: None,
+            token_scopes: None,
         };
 
         // Test permission exists
         let has_permission = rbac
-            .check_permission(&user, PermissionAction::Create, "users")
+            .check_permission(&user, PermissionAction::Create, "users", None)
             .await
             .unwrap();
         assert!(has_permission);
 
         // Test permission does not exist
         let has_permission = rbac
-            .check_permission(&user, PermissionAction::Delete, "users")
+            .check_permission(&user, PermissionAction::Delete, "users", None)
             .await
             .unwrap();
         assert!(!has_permission);
 
         // Test cache hit
         let has_permission = rbac
-            .check_permission(&user, PermissionAction::Create, "users")
+            .check_permission(&user, PermissionAction::Create, "users", None)
             .await
             .unwrap();
         assert!(has_permission);
@@ -207,12 +885,127 @@ mod tests {
         // Test cache clear
         rbac.clear_user_cache(user.id);
         let has_permission = rbac
-            .check_permission(&user, PermissionAction::Create, "users")
+            .check_permission(&user, PermissionAction::Create, "users", None)
             .await
             .unwrap();
         assert!(has_permission);
     }
 
+    #[tokio::test]
+    async fn test_clear_user_cache() {
+        let rbac = RbacService::new();
+        let mut user = User::new(
+            TenantId::new(),
+            "test@example.com".to_string(),
+            "hash".to_string(),
+        );
+        let other = User::new(
+            TenantId::new(),
+            "other@example.com".to_string(),
+            "hash".to_string(),
+        );
+
+        assert!(!rbac
+            .check_permission(&user, PermissionAction::Read, "users", None)
+            .await
+            .unwrap());
+        assert!(!rbac
+            .check_permission(&other, PermissionAction::Read, "users", None)
+            .await
+            .unwrap());
+
+        user.roles.push(create_user_role());
+        rbac.clear_user_cache(user.id);
+        rbac.permission_cache.run_pending_tasks();
+
+        assert!(rbac
+            .check_permission(&user, PermissionAction::Read, "users", None)
+            .await
+            .unwrap());
+        assert!(rbac
+            .permission_cache
+            .contains_key(&format!("{}:read:users", other.id.0)));
+    }
+
+    #[tokio::test]
+    async fn test_owner_id_permission() {
+        let rbac = RbacService::new();
+        let mut user = User::new(
+            TenantId::new(),
+            "test@example.com".to_string(),
+            "hash".to_string(),
+        );
+        user.roles.push(create_user_role());
+
+        // Users may update their own profile but nobody else's
+        assert!(rbac
+            .check_permission(&user, PermissionAction::Update, "users", Some(user.id))
+            .await
+            .unwrap());
+        assert!(!rbac
+            .check_permission(
+                &user,
+                PermissionAction::Update,
+                "users",
+                Some(UserId::new())
+            )
+            .await
+            .unwrap());
+        assert!(!rbac
+            .check_permission(&user, PermissionAction::Update, "users", None)
+            .await
+            .unwrap());
+        assert!(!rbac
+            .check_permission(&user, PermissionAction::Delete, "users", Some(user.id))
+            .await
+            .unwrap());
+    }
+
+    /// Engine denying every request
+    #[derive(Debug)]
+    struct DenyAllEngine;
+
+    #[async_trait::async_trait]
+    impl PolicyEngine for DenyAllEngine {
+        async fn is_allowed(&self, _: &User, _: PermissionAction, _: &str) -> Result<bool> {
+            Ok(false)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_policy_engine_decides_owner_checks() {
+        let mut user = User::new(
+            TenantId::new(),
+            "admin@example.com".to_string(),
+            "hash".to_string(),
+        );
+        user.roles.push(create_admin_role());
+        let owners = vec![ResourceOwner::new(
+            user.tenant_id,
+            "users".to_string(),
+            user.id.0.to_string(),
+            OwnerType::User,
+            user.id.0,
+        )];
+
+        let rbac = RbacService::new();
+        assert!(rbac
+            .check_resource_permission(&user, PermissionAction::Update, "users", &owners)
+            .await
+            .unwrap());
+
+        // Neither role permissions nor ownership override a denying engine
+        let rbac = RbacService::with_policy_engine(Arc::new(DenyAllEngine));
+        assert!(!rbac
+            .check_resource_permission(&user, PermissionAction::Update, "users", &owners)
+            .await
+            .unwrap());
+        assert!(!rbac
+            .check_permission(&user, PermissionAction::Update, "users", Some(user.id))
+            .await
+            .unwrap());
+    }
+
     #[test]
     fn test_has_permission() {
         let user = User {
@@ -235,18 +1028,415 @@ mod tests {
             active: true,
             mfa_enabled: false,
             mfa_secret: None,
+            token_scopes: None,
         };
 
         let has_permission = has_permission(&user, PermissionAction::Create, "users");
         assert!(has_permission);
     }
 
+    #[test]
+    fn test_has_role_permission() {
+        let roles = vec![create_user_role(), create_admin_role()];
+        assert!(has_role_permission(
+            &roles,
+            PermissionAction::Delete,
+            "users"
+        ));
+        assert!(!has_role_permission(
+            &roles,
+            PermissionAction::Delete,
+            "projects"
+        ));
+        assert!(!has_role_permission(&[], PermissionAction::Read, "users"));
+    }
+
+    #[test]
+    fn test_owner_scoped_permission() {
+        let mut user = User::new(
+            TenantId::new(),
+            "author@example.com".to_string(),
+            "hash".to_string(),
+        );
+        let mut author = Role::new(RoleType::Custom, "Author".to_string());
+        author.permissions = vec![Permission::new(
+            "Update Own Posts".to_string(),
+            PermissionAction::Update,
+            "posts".to_string(),
+        )
+        .with_scope(PermissionScope::Own)];
+        user.roles.push(author.clone());
+
+        let owners = vec![ResourceOwner::new(
+            user.tenant_id,
+            "posts".to_string(),
+            "42".to_string(),
+            OwnerType::User,
+            user.id.0,
+        )];
+
+        // Owner-scoped grants never count as unrestricted access
+        assert!(!has_permission(&user, PermissionAction::Update, "posts"));
+        assert!(has_owner_permission(
+            &user,
+            PermissionAction::Update,
+            "posts",
+            &owners
+        ));
+        assert!(!has_owner_permission(
+            &user,
+            PermissionAction::Update,
+            "posts",
+            &[]
+        ));
+        assert!(!has_owner_permission(
+            &user,
+            PermissionAction::Delete,
+            "posts",
+            &owners
+        ));
+
+        // Owner-scoped permissions can be passed on, unrestricted ones cannot
+        assert!(can_grant(&user.roles, &author.permissions[0]));
+        let unrestricted = Permission::new(
+            "Update Posts".to_string(),
+            PermissionAction::Update,
+            "posts".to_string(),
+        );
+        assert!(!can_grant(&user.roles, &unrestricted));
+    }
+
+    #[test]
+    fn test_resource_matches() {
+        assert!(resource_matches("users", "users"));
+        assert!(!resource_matches("users", "projects"));
+        assert!(!resource_matches("users", "users/123"));
+
+        // A lone wildcard matches everything
+        assert!(resource_matches("*", "users"));
+        assert!(resource_matches("*", "tenants/acme/users"));
+
+        // Wildcard segments match exactly one segment
+        assert!(resource_matches("tenants/*/users", "tenants/acme/users"));
+        assert!(!resource_matches(
+            "tenants/*/users",
+            "tenants/acme/projects"
+        ));
+        assert!(!resource_matches("tenants/*/users", "tenants/users"));
+        assert!(!resource_matches("tenants/*", "tenants/acme/users"));
+
+        // Trailing double wildcards cover a subtree including its root
+        assert!(resource_matches("tenants/acme/**", "tenants/acme"));
+        assert!(resource_matches(
+            "tenants/acme/**",
+            "tenants/acme/users/123"
+        ));
+        assert!(!resource_matches("tenants/acme/**", "tenants/other/users"));
+        assert!(!resource_matches("tenants/**/users", "tenants/acme/users"));
+
+        // Wildcards in the checked resource are literal
+        assert!(resource_matches("tenants/*/users", "tenants/*/users"));
+        assert!(!resource_matches("tenants/acme/users", "tenants/*/users"));
+    }
+
+    #[test]
+    fn test_super_admin_wildcard_permission() {
+        let mut user = User::new(
+            TenantId::new(),
+            "root@example.com".to_string(),
+            "hash".to_string(),
+        );
+        user.roles = vec![create_super_admin_role()];
+
+        assert!(has_permission(&user, PermissionAction::Delete, "users"));
+        assert!(has_permission(
+            &user,
+            PermissionAction::Read,
+            "tenants/acme/projects"
+        ));
+        assert!(!has_permission(&user, PermissionAction::Execute, "users"));
+        assert!(can_grant(
+            &user.roles,
+            &Permission::new(
+                "Tenant users".to_string(),
+                PermissionAction::Read,
+                "tenants/*/users".to_string(),
+            )
+        ));
+    }
+
+    #[test]
+    fn test_role_hierarchy() {
+        let user_role = create_user_role();
+        let mut support = Role::new(RoleType::Custom, "Support".to_string());
+        support.parent_ids = vec![user_role.id];
+        let mut lead = Role::new(RoleType::Custom, "Support Lead".to_string());
+        lead.parent_ids = vec![support.id];
+        lead.permissions = vec![Permission::new(
+            "Delete User".to_string(),
+            PermissionAction::Delete,
+            "users".to_string(),
+        )];
+
+        let hierarchy =
+            RoleHierarchy::from_roles(&[user_role.clone(), support.clone(), lead.clone()]);
+        assert_eq!(hierarchy.ancestors(lead.id), vec![support.id, user_role.id]);
+
+        // Permissions are inherited across every level
+        let mut user = User::new(
+            TenantId::new(),
+            "lead@example.com".to_string(),
+            "hash".to_string(),
+        );
+        hierarchy.resolve(&mut lead);
+        user.roles = vec![lead.clone()];
+        assert!(has_permission(&user, PermissionAction::Delete, "users"));
+        assert!(has_permission(&user, PermissionAction::Read, "users"));
+        assert!(!has_permission(&user, PermissionAction::Delete, "projects"));
+
+        // Parents must exist and must not descend from the role
+        assert!(hierarchy.check_parents(lead.id, &[user_role.id]).is_ok());
+        assert!(matches!(
+            hierarchy.check_parents(user_role.id, &[lead.id]),
+            Err(Error::Validation(_))
+        ));
+        assert!(matches!(
+            hierarchy.check_parents(support.id, &[support.id]),
+            Err(Error::Validation(_))
+        ));
+        assert!(matches!(
+            hierarchy.check_parents(support.id, &[Uuid::new_v4()]),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_permission_simulation() {
+        let user_role = create_user_role();
+        let mut support = Role::new(RoleType::Custom, "Support".to_string());
+        support.parent_ids = vec![user_role.id];
+        let mut lead = Role::new(RoleType::Custom, "Support Lead".to_string());
+        lead.parent_ids = vec![support.id];
+        let tenant_roles = [user_role.clone(), support.clone(), lead.clone()];
+        let hierarchy = RoleHierarchy::from_roles(&tenant_roles);
+        assert_eq!(
+            hierarchy.path(lead.id, user_role.id),
+            Some(vec![lead.id, support.id, user_role.id])
+        );
+        assert_eq!(hierarchy.path(user_role.id, lead.id), None);
+
+        let mut user = User::new(
+            TenantId::new(),
+            "lead@example.com".to_string(),
+            "hash".to_string(),
+        );
+        hierarchy.resolve(&mut lead);
+        user.roles = vec![lead.clone()];
+
+        // Inherited permissions name every role of the chain
+        let matches =
+            matching_permissions(&user.roles, &tenant_roles, PermissionAction::Read, "users");
+        assert_eq!(matches.len(), 1);
+        let chain: Vec<&str> = matches[0].chain.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(chain, vec!["Support Lead", "Support", "User"]);
+        assert_eq!(
+            simulation_reason(&user, &matches, PermissionAction::Read, "users", None),
+            SimulationReason::Granted
+        );
+
+        let matches = matching_permissions(
+            &user.roles,
+            &tenant_roles,
+            PermissionAction::Update,
+            "users",
+        );
+        assert_eq!(
+            simulation_reason(&user, &matches, PermissionAction::Update, "users", None),
+            SimulationReason::NoMatchingPermission
+        );
+        assert_eq!(
+            simulation_reason(
+                &user,
+                &matches,
+                PermissionAction::Update,
+                "users",
+                Some(user.id)
+            ),
+            SimulationReason::GrantedAsOwner
+        );
+
+        user.token_scopes = Some(vec![TokenScope::new(PermissionAction::List, "users")]);
+        assert_eq!(
+            simulation_reason(
+                &user,
+                &matches,
+                PermissionAction::Update,
+                "users",
+                Some(user.id)
+            ),
+            SimulationReason::OutsideTokenScopes
+        );
+
+        // Deny permissions of the assigned role override inherited grants
+        user.token_scopes = None;
+        lead.permissions = vec![Permission::new(
+            "Deny Read User".to_string(),
+            PermissionAction::Read,
+            "users".to_string(),
+        )
+        .with_effect(PermissionEffect::Deny)];
+        user.roles = vec![lead];
+        let matches =
+            matching_permissions(&user.roles, &tenant_roles, PermissionAction::Read, "users");
+        assert_eq!(matches.len(), 2);
+        assert_eq!(
+            simulation_reason(&user, &matches, PermissionAction::Read, "users", None),
+            SimulationReason::Denied
+        );
+    }
+
+    #[test]
+    fn test_token_scopes() {
+        let scopes = TokenScope::parse_list("read:users  list:tenants/*/projects").unwrap();
+        assert_eq!(
+            scopes,
+            vec![
+                TokenScope::new(PermissionAction::Read, "users"),
+                TokenScope::new(PermissionAction::List, "tenants/*/projects"),
+            ]
+        );
+        assert_eq!(
+            TokenScope::format_list(&scopes),
+            "read:users list:tenants/*/projects"
+        );
+        assert!(TokenScope::parse_list("users").is_err());
+        assert!(TokenScope::parse_list("fly:users").is_err());
+
+        let mut user = User::new(
+            TenantId::new(),
+            "admin@example.com".to_string(),
+            "hash".to_string(),
+        );
+        user.roles = vec![create_admin_role()];
+        assert!(ensure_scopes_held(&user, &scopes[..1]).is_ok());
+        assert!(matches!(
+            ensure_scopes_held(
+                &user,
+                &[TokenScope::new(PermissionAction::Execute, "users")]
+            ),
+            Err(Error::Authorization(_))
+        ));
+
+        // Scoped tokens get the intersection of their scopes and the user's roles
+        user.token_scopes = Some(scopes);
+        assert!(has_permission(&user, PermissionAction::Read, "users"));
+        assert!(!has_permission(&user, PermissionAction::Delete, "users"));
+        assert!(!has_permission(
+            &user,
+            PermissionAction::List,
+            "tenants/acme/projects"
+        ));
+
+        // Tokens of scoped sessions can only be narrowed
+        assert!(matches!(
+            ensure_scopes_held(&user, &[TokenScope::new(PermissionAction::Delete, "users")]),
+            Err(Error::Authorization(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_deny_permission() {
+        let rbac = RbacService::new();
+        let mut user = User::new(
+            TenantId::new(),
+            "operator@example.com".to_string(),
+            "hash".to_string(),
+        );
+        let mut operator = Role::new(RoleType::Custom, "Operator".to_string());
+        operator.permissions = vec![Permission::new(
+            "No Tenant Deletion".to_string(),
+            PermissionAction::Delete,
+            "tenants".to_string(),
+        )
+        .with_effect(PermissionEffect::Deny)];
+        user.roles = vec![create_super_admin_role(), operator];
+
+        // The deny wins over the wildcard grant of the other role
+        assert!(!has_permission(&user, PermissionAction::Delete, "tenants"));
+        assert!(has_permission(&user, PermissionAction::Delete, "users"));
+        assert!(!rbac
+            .check_permission(&user, PermissionAction::Delete, "tenants", None)
+            .await
+            .unwrap());
+        assert!(!rbac
+            .check_permission(&user, PermissionAction::Delete, "tenants", Some(user.id))
+            .await
+            .unwrap());
+
+        // Denies restrict only, so anyone may grant them
+        assert!(can_grant(&[], &user.roles[1].permissions[0]));
+    }
+
+    #[derive(Debug, Default)]
+    struct CollectingRecorder(std::sync::Mutex<Vec<AuthorizationDecision>>);
+
+    #[async_trait::async_trait]
+    impl DecisionRecorder for CollectingRecorder {
+        async fn record(&self, decision: AuthorizationDecision) {
+            self.0.lock().unwrap().push(decision);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decision_recorder() {
+        let recorder = Arc::new(CollectingRecorder::default());
+        let rbac = RbacService::new().with_decision_recorder(recorder.clone(), false);
+        let mut user = User::new(
+            TenantId::new(),
+            "user@example.com".to_string(),
+            "hash".to_string(),
+        );
+        user.roles = vec![create_user_role()];
+
+        assert!(rbac
+            .check_permission(&user, PermissionAction::Read, "users", None)
+            .await
+            .unwrap());
+        assert!(!rbac
+            .check_request_permission(
+                &user,
+                PermissionAction::Delete,
+                "tenants",
+                None,
+                Some("req-1"),
+            )
+            .await
+            .unwrap());
+
+        // Only the denial is recorded unless allowed decisions are requested
+        let decisions = recorder.0.lock().unwrap().clone();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].user_id, user.id);
+        assert_eq!(decisions[0].action, PermissionAction::Delete);
+        assert_eq!(decisions[0].resource, "tenants");
+        assert!(!decisions[0].allowed);
+        assert_eq!(decisions[0].request_id.as_deref(), Some("req-1"));
+
+        let recorder = Arc::new(CollectingRecorder::default());
+        let rbac = RbacService::new().with_decision_recorder(recorder.clone(), true);
+        assert!(rbac
+            .check_permission(&user, PermissionAction::Read, "users", None)
+            .await
+            .unwrap());
+        assert!(recorder.0.lock().unwrap()[0].allowed);
+    }
+
     #[test]
     fn test_create_user_role() {
         let role = create_user_role();
         assert_eq!(role.role_type, RoleType::User);
         assert_eq!(role.name, "User");
-        assert_eq!(role.permissions.len(), 2);
+        assert_eq!(role.permissions.len(), 3);
     }
 
     #[test]
@@ -254,7 +1444,7 @@ mod tests {
         let role = create_admin_role();
         assert_eq!(role.role_type, RoleType::Admin);
         assert_eq!(role.name, "Admin");
-        assert_eq!(role.permissions.len(), 4);
+        assert_eq!(role.permissions.len(), 8);
     }
 
     #[test]
@@ -264,4 +1454,184 @@ mod tests {
         assert_eq!(role.name, "Super Admin");
         assert_eq!(role.permissions.len(), 4);
     }
+
+    #[test]
+    fn test_tenant_boundary() {
+        let mut admin = User::new(
+            TenantId::new(),
+            "admin@example.com".to_string(),
+            "hash".to_string(),
+        );
+        admin.roles = vec![create_user_role()];
+        assert!(!is_tenant_admin(&admin));
+        admin.roles = vec![create_admin_role()];
+        assert!(is_tenant_admin(&admin));
+        let other_tenant = TenantId::new();
+
+        assert!(ensure_tenant_boundary(&admin, admin.tenant_id).is_ok());
+        assert!(matches!(
+            ensure_tenant_boundary(&admin, other_tenant),
+            Err(Error::Authorization(_))
+        ));
+
+        admin.roles.push(create_super_admin_role());
+        assert!(is_super_admin(&admin));
+        assert!(ensure_tenant_boundary(&admin, other_tenant).is_ok());
+    }
+
+    #[test]
+    fn test_require_permission() {
+        let user = User::new(
+            TenantId::new(),
+            "admin@example.com".to_string(),
+            "hash".to_string(),
+        );
+        let required = RequirePermission::<action::Update, resource::Users>::new(user.clone());
+        assert_eq!(required.user_id(), Some(user.id));
+        assert_eq!(required.tenant_id(), Some(user.tenant_id));
+        assert_eq!(required.required_action(), PermissionAction::Update);
+        assert_eq!(required.required_resource(), "users");
+        assert_eq!(action::Execute::ACTION, PermissionAction::Execute);
+        assert_eq!(resource::Projects::RESOURCE, "projects");
+    }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        /// Resources checked against, without wildcards
+        const RESOURCES: &[&str] = &[
+            "users",
+            "projects",
+            "tenants",
+            "tenants/acme/users",
+            "tenants/globex/users",
+        ];
+
+        fn action() -> impl Strategy<Value = PermissionAction> {
+            prop::sample::select(vec![
+                PermissionAction::Create,
+                PermissionAction::Read,
+                PermissionAction::Update,
+                PermissionAction::Delete,
+            ])
+        }
+
+        fn resource() -> impl Strategy<Value = String> {
+            prop::sample::select(RESOURCES.to_vec()).prop_map(str::to_string)
+        }
+
+        fn pattern() -> impl Strategy<Value = String> {
+            prop::sample::select(vec![
+                "users",
+                "projects",
+                "tenants",
+                "tenants/*/users",
+                "tenants/acme/**",
+                "*",
+            ])
+            .prop_map(str::to_string)
+        }
+
+        fn permission() -> impl Strategy<Value = Permission> {
+            (action(), pattern(), any::<bool>(), any::<bool>()).prop_map(
+                |(action, resource, own, deny)| {
+                    let scope = if own {
+                        PermissionScope::Own
+                    } else {
+                        PermissionScope::All
+                    };
+                    let effect = if deny {
+                        PermissionEffect::Deny
+                    } else {
+                        PermissionEffect::Allow
+                    };
+                    Permission::new("Generated".to_string(), action, resource)
+                        .with_scope(scope)
+                        .with_effect(effect)
+                },
+            )
+        }
+
+        /// Roles with own and inherited permissions
+        fn roles() -> impl Strategy<Value = Vec<Role>> {
+            prop::collection::vec(
+                (
+                    prop::collection::vec(permission(), 0..5),
+                    prop::collection::vec(permission(), 0..3),
+                )
+                    .prop_map(|(permissions, inherited_permissions)| {
+                        let mut role = Role::new(RoleType::Custom, "Generated".to_string());
+                        role.permissions = permissions;
+                        role.inherited_permissions = inherited_permissions;
+                        role
+                    }),
+                0..4,
+            )
+        }
+
+        fn user(roles: Vec<Role>) -> User {
+            let mut user = User::new(
+                TenantId::new(),
+                "user@example.com".to_string(),
+                "hash".to_string(),
+            );
+            user.roles = roles;
+            user
+        }
+
+        proptest! {
+            #[test]
+            fn prop_deny_wins(roles in roles(), action in action(), resource in resource()) {
+                let denied = roles.iter().flat_map(|role| role.effective_permissions()).any(|p| {
+                    p.is_deny() && p.action == action && resource_matches(&p.resource, &resource)
+                });
+                let granted = roles.iter().flat_map(|role| role.effective_permissions()).any(|p| {
+                    !p.is_deny()
+                        && p.scope == PermissionScope::All
+                        && p.action == action
+                        && resource_matches(&p.resource, &resource)
+                });
+                let user = user(roles);
+                let allowed = tokio::runtime::Builder::new_current_thread()
+                    .build()
+                    .unwrap()
+                    .block_on(RbacService::new().check_permission(
+                        &user,
+                        action,
+                        &resource,
+                        Some(user.id),
+                    ))
+                    .unwrap();
+
+                prop_assert_eq!(has_permission(&user, action, &resource), granted && !denied);
+                prop_assert_eq!(is_denied(&user.roles, action, &resource), denied);
+                if denied {
+                    prop_assert!(!allowed);
+                    prop_assert!(!has_scoped_permission(
+                        &user.roles,
+                        action,
+                        &resource,
+                        PermissionScope::Own
+                    ));
+                }
+            }
+
+            #[test]
+            fn prop_deny_never_grants(
+                roles in roles(),
+                deny in permission(),
+                action in action(),
+                resource in resource(),
+            ) {
+                let before = has_permission(&user(roles.clone()), action, &resource);
+                let mut restricted = roles;
+                let mut role = Role::new(RoleType::Custom, "Restricted".to_string());
+                role.permissions = vec![deny.with_effect(PermissionEffect::Deny)];
+                restricted.push(role);
+                let after = has_permission(&user(restricted), action, &resource);
+                prop_assert!(before || !after);
+            }
+        }
+    }
 }
@@ -0,0 +1,272 @@
+use rand::{distributions::Alphanumeric, Rng};
+use ring::digest;
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::shared::{
+    error::Result,
+    types::{TenantId, UserId},
+};
+
+/// Refresh token of a session
+///
+/// Tokens of the same family descend from one login; only the hash of a
+/// token is stored.
+#[derive(Debug, Clone)]
+pub struct RefreshToken {
+    pub id: Uuid,
+    pub family_id: Uuid,
+    pub tenant_id: TenantId,
+    pub user_id: UserId,
+    /// Session the token was issued with
+    pub session_id: Uuid,
+    pub token_hash: String,
+    pub created_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    /// When the token was exchanged for its successor
+    pub used_at: Option<OffsetDateTime>,
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+impl RefreshToken {
+    /// Creates a token for a session, returning it with its plain value
+    ///
+    /// Starts a new family unless the family of the token it replaces is given.
+    pub fn new(
+        tenant_id: TenantId,
+        user_id: UserId,
+        session_id: Uuid,
+        family_id: Option<Uuid>,
+        expires_in: Duration,
+    ) -> (Self, String) {
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(48)
+            .map(char::from)
+            .collect();
+        let now = OffsetDateTime::now_utc();
+        let refresh_token = Self {
+            id: Uuid::new_v4(),
+            family_id: family_id.unwrap_or_else(Uuid::new_v4),
+            tenant_id,
+            user_id,
+            session_id,
+            token_hash: hash_refresh_token(&token),
+            created_at: now,
+            expires_at: now + expires_in,
+            used_at: None,
+            revoked_at: None,
+        };
+        (refresh_token, token)
+    }
+
+    /// Checks if the token can still be exchanged, ignoring whether it was used
+    pub fn is_valid(&self) -> bool {
+        self.revoked_at.is_none() && self.expires_at > OffsetDateTime::now_utc()
+    }
+}
+
+/// Computes the hex encoded SHA-256 hash of a refresh token
+pub fn hash_refresh_token(token: &str) -> String {
+    digest::digest(&digest::SHA256, token.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Refresh token repository
+#[derive(Debug, Clone)]
+pub struct RefreshTokenRepository {
+    pool: PgPool,
+}
+
+impl RefreshTokenRepository {
+    /// Creates a new RefreshTokenRepository instance
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Stores a refresh token
+    pub async fn create(&self, token: &RefreshToken) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO refresh_tokens (
+                id, family_id, tenant_id, user_id, session_id, token_hash, created_at, expires_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            token.id,
+            token.family_id,
+            token.tenant_id.0 as Uuid,
+            token.user_id.0 as Uuid,
+            token.session_id,
+            token.token_hash,
+            token.created_at,
+            token.expires_at,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Gets a refresh token by the hash of its value
+    pub async fn get_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>> {
+        let result = sqlx::query!(
+            r#"
+            SELECT id, family_id, tenant_id, user_id, session_id, token_hash, created_at,
+                expires_at, used_at, revoked_at
+            FROM refresh_tokens
+            WHERE token_hash = $1
+            "#,
+            token_hash,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(|r| RefreshToken {
+            id: r.id,
+            family_id: r.family_id,
+            tenant_id: TenantId(r.tenant_id),
+            user_id: UserId(r.user_id),
+            session_id: r.session_id,
+            token_hash: r.token_hash,
+            created_at: r.created_at,
+            expires_at: r.expires_at,
+            used_at: r.used_at,
+            revoked_at: r.revoked_at,
+        }))
+    }
+
+    /// Marks a token as used, returning false if it was used before
+    ///
+    /// Concurrent refreshes with the same token can only use it once.
+    pub async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens SET used_at = NOW()
+            WHERE id = $1 AND used_at IS NULL
+            "#,
+            id,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Revokes all tokens of a family, returning the sessions they were issued with
+    pub async fn revoke_family(&self, family_id: Uuid) -> Result<Vec<Uuid>> {
+        let session_ids = sqlx::query_scalar!(
+            r#"
+            UPDATE refresh_tokens SET revoked_at = COALESCE(revoked_at, NOW())
+            WHERE family_id = $1
+            RETURNING session_id
+            "#,
+            family_id,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(session_ids)
+    }
+
+    /// Revokes the unused tokens issued with the given sessions
+    pub async fn revoke_session_tokens(&self, session_ids: &[Uuid]) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE refresh_tokens SET revoked_at = NOW()
+            WHERE session_id = ANY($1) AND used_at IS NULL AND revoked_at IS NULL
+            "#,
+            session_ids,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Revokes the unused tokens of a user
+    pub async fn revoke_user_tokens(&self, tenant_id: TenantId, user_id: UserId) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE refresh_tokens SET revoked_at = NOW()
+            WHERE tenant_id = $1 AND user_id = $2 AND used_at IS NULL AND revoked_at IS NULL
+            "#,
+            tenant_id.0 as Uuid,
+            user_id.0 as Uuid,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Revokes the unused tokens of all users of a tenant
+    pub async fn revoke_tenant_tokens(&self, tenant_id: TenantId) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE refresh_tokens SET revoked_at = NOW()
+            WHERE tenant_id = $1 AND used_at IS NULL AND revoked_at IS NULL
+            "#,
+            tenant_id.0 as Uuid,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Removes up to `limit` expired tokens, returning how many were removed
+    pub async fn remove_expired(&self, limit: i64) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM refresh_tokens
+            WHERE id IN (
+                SELECT id FROM refresh_tokens WHERE expires_at <= NOW() LIMIT $1
+            )
+            "#,
+            limit,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refresh_token() {
+        let session_id = Uuid::new_v4();
+        let (first, token) = RefreshToken::new(
+            TenantId::new(),
+            UserId::new(),
+            session_id,
+            None,
+            Duration::days(30),
+        );
+        assert_eq!(first.token_hash, hash_refresh_token(&token));
+        assert_eq!(first.token_hash.len(), 64);
+        assert!(first.is_valid());
+
+        // Rotated tokens stay in the family of the token they replace
+        let (second, second_token) = RefreshToken::new(
+            first.tenant_id,
+            first.user_id,
+            session_id,
+            Some(first.family_id),
+            Duration::days(30),
+        );
+        assert_eq!(second.family_id, first.family_id);
+        assert_ne!(second_token, token);
+
+        let (expired, _) = RefreshToken::new(
+            first.tenant_id,
+            first.user_id,
+            session_id,
+            None,
+            Duration::ZERO,
+        );
+        assert_ne!(expired.family_id, first.family_id);
+        assert!(!expired.is_valid());
+    }
+}
@@ -1,270 +1,1641 @@
-use serde_json;
-use sqlx::{Pool, Postgres};
-use time::{OffsetDateTime, PrimitiveDateTime};
+use std::collections::HashMap;
+
+use sqlx::{PgConnection, Pool, Postgres};
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 use crate::{
     core::database::Database,
-    modules::identity::models::{Role, RoleType, User},
+    modules::identity::{
+        models::{Membership, Permission, ResourceOwner, Role, User, UserEmail, UserOverview},
+        rbac::RoleHierarchy,
+    },
     shared::{
+        audit::{AuditCategory, AuditEvent},
         error::{Error, Result},
         types::{TenantId, UserId},
     },
 };
 
-/// Helper function to convert database roles to domain roles
-fn convert_roles(roles: Option<Vec<String>>) -> Vec<Role> {
-    match roles {
-        Some(roles) => roles
+/// Role row joined with one of its permissions
+struct RoleRow {
+    role_id: Uuid,
+    role_name: String,
+    role_type: String,
+    permission_id: Option<Uuid>,
+    permission_name: Option<String>,
+    action: Option<String>,
+    resource: Option<String>,
+    scope: Option<String>,
+    effect: Option<String>,
+}
+
+/// Folds a role row into a list of roles ordered by role ID
+fn push_role_row(roles: &mut Vec<Role>, row: RoleRow) -> Result<()> {
+    if roles.last().map(|role| role.id) != Some(row.role_id) {
+        roles.push(Role {
+            id: row.role_id,
+            role_type: row.role_type.parse()?,
+            name: row.role_name,
+            permissions: Vec::new(),
+            parent_ids: Vec::new(),
+            inherited_permissions: Vec::new(),
+        });
+    }
+    if let (
+        Some(id),
+        Some(name),
+        Some(action),
+        Some(resource),
+        Some(scope),
+        Some(effect),
+        Some(role),
+    ) = (
+        row.permission_id,
+        row.permission_name,
+        row.action,
+        row.resource,
+        row.scope,
+        row.effect,
+        roles.last_mut(),
+    ) {
+        role.permissions.push(Permission {
+            id,
+            name,
+            action: action.parse()?,
+            resource,
+            scope: scope.parse()?,
+            effect: effect.parse()?,
+        });
+    }
+    Ok(())
+}
+
+/// Replaces the permissions of a role
+async fn save_role_permissions(conn: &mut PgConnection, role_id: Uuid, role: &Role) -> Result<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM permissions
+        WHERE role_id = $1
+        "#,
+        role_id,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    for permission in &role.permissions {
+        sqlx::query!(
+            r#"
+            INSERT INTO permissions (id, role_id, name, action, resource, scope, effect)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (role_id, action, resource) DO NOTHING
+            "#,
+            permission.id,
+            role_id,
+            permission.name,
+            permission.action.to_string(),
+            permission.resource,
+            permission.scope.to_string(),
+            permission.effect.to_string(),
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Replaces the parents of a role
+async fn save_role_parents(
+    conn: &mut PgConnection,
+    tenant_id: TenantId,
+    role: &Role,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM role_parents
+        WHERE role_id = $1
+        "#,
+        role.id,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    for parent_id in &role.parent_ids {
+        sqlx::query!(
+            r#"
+            INSERT INTO role_parents (role_id, parent_id, tenant_id)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            "#,
+            role.id,
+            parent_id,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Inserts a role of a tenant with its permissions and parents
+///
+/// Runs on a connection so callers can create roles within their own transaction.
+pub(crate) async fn insert_role(
+    conn: &mut PgConnection,
+    tenant_id: TenantId,
+    role: &Role,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO roles (id, tenant_id, name, role_type)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        role.id,
+        tenant_id.0 as uuid::Uuid,
+        role.name,
+        role.role_type.to_string(),
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    save_role_permissions(conn, role.id, role).await?;
+    save_role_parents(conn, tenant_id, role).await
+}
+
+/// Loads the inheritance graph above the given roles with the permissions of all ancestors
+async fn load_role_hierarchy(pool: &Pool<Postgres>, role_ids: &[Uuid]) -> Result<RoleHierarchy> {
+    let edges = sqlx::query!(
+        r#"
+        WITH RECURSIVE edges AS (
+            SELECT role_id, parent_id
+            FROM role_parents
+            WHERE role_id = ANY($1)
+            UNION
+            SELECT rp.role_id, rp.parent_id
+            FROM role_parents rp
+            JOIN edges e ON rp.role_id = e.parent_id
+        )
+        SELECT role_id AS "role_id!", parent_id AS "parent_id!"
+        FROM edges
+        ORDER BY role_id, parent_id
+        "#,
+        role_ids,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut hierarchy = RoleHierarchy::new();
+    if edges.is_empty() {
+        return Ok(hierarchy);
+    }
+
+    let mut parents: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for edge in &edges {
+        parents
+            .entry(edge.role_id)
+            .or_default()
+            .push(edge.parent_id);
+    }
+    let ancestor_ids: Vec<Uuid> = edges.iter().map(|edge| edge.parent_id).collect();
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT role_id, id, name, action, resource, scope, effect
+        FROM permissions
+        WHERE role_id = ANY($1)
+        ORDER BY role_id, resource, action
+        "#,
+        &ancestor_ids,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut permissions: HashMap<Uuid, Vec<Permission>> = HashMap::new();
+    for row in rows {
+        permissions
+            .entry(row.role_id)
+            .or_default()
+            .push(Permission {
+                id: row.id,
+                name: row.name,
+                action: row.action.parse()?,
+                resource: row.resource,
+                scope: row.scope.parse()?,
+                effect: row.effect.parse()?,
+            });
+    }
+
+    for id in role_ids.iter().chain(&ancestor_ids) {
+        hierarchy.add_role(
+            *id,
+            parents.get(id).cloned().unwrap_or_default(),
+            permissions.get(id).cloned().unwrap_or_default(),
+        );
+    }
+    Ok(hierarchy)
+}
+
+/// Persists a user's roles and replaces the user's active role assignments
+///
+/// Scheduled assignments that have not taken effect yet are kept, as are the
+/// validity windows of retained assignments.
+async fn save_user_roles(conn: &mut PgConnection, user: &User) -> Result<()> {
+    let mut role_ids = Vec::with_capacity(user.roles.len());
+    for role in &user.roles {
+        // Roles are shared per tenant by name; reuse the existing row if present
+        let role_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO roles (id, tenant_id, name, role_type)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (tenant_id, name) DO UPDATE SET name = EXCLUDED.name
+            RETURNING id
+            "#,
+            role.id,
+            user.tenant_id.0 as uuid::Uuid,
+            role.name,
+            role.role_type.to_string(),
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        for permission in &role.permissions {
+            sqlx::query!(
+                r#"
+                INSERT INTO permissions (id, role_id, name, action, resource, scope, effect)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (role_id, action, resource) DO NOTHING
+                "#,
+                permission.id,
+                role_id,
+                permission.name,
+                permission.action.to_string(),
+                permission.resource,
+                permission.scope.to_string(),
+                permission.effect.to_string(),
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO user_roles (user_id, role_id, tenant_id)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            "#,
+            user.id.0 as uuid::Uuid,
+            role_id,
+            user.tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&mut *conn)
+        .await?;
+        role_ids.push(role_id);
+    }
+
+    sqlx::query!(
+        r#"
+        DELETE FROM user_roles
+        WHERE user_id = $1 AND valid_from <= NOW() AND role_id <> ALL($2)
+        "#,
+        user.id.0 as uuid::Uuid,
+        &role_ids,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Inserts a user with their primary email and roles, returning the user without roles
+///
+/// Runs on a connection so callers can create users within their own transaction.
+pub(crate) async fn insert_user(conn: &mut PgConnection, user: &User) -> Result<User> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO users (id, tenant_id, email, password_hash, active, created_at, updated_at, mfa_enabled, mfa_secret)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING id, tenant_id, email, password_hash, active, last_login, created_at, updated_at, mfa_enabled, mfa_secret
+        "#,
+        user.id.0 as uuid::Uuid,
+        user.tenant_id.0 as uuid::Uuid,
+        user.email,
+        user.password_hash,
+        user.active,
+        user.created_at,
+        user.updated_at,
+        user.mfa_enabled,
+        user.mfa_secret,
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    // Register the login address as the user's primary email; only its owner can verify it
+    sqlx::query!(
+        r#"
+        INSERT INTO user_emails (id, tenant_id, user_id, email, is_primary, verified, verified_at, created_at)
+        VALUES ($1, $2, $3, $4, TRUE, FALSE, NULL, NOW())
+        "#,
+        Uuid::new_v4(),
+        user.tenant_id.0 as uuid::Uuid,
+        user.id.0 as uuid::Uuid,
+        user.email,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    save_user_roles(conn, user).await?;
+
+    Ok(User {
+        id: UserId(result.id),
+        tenant_id: TenantId(result.tenant_id),
+        email: result.email,
+        password_hash: result.password_hash,
+        active: result.active,
+        roles: Vec::new(),
+        last_login: result.last_login,
+        created_at: result.created_at,
+        updated_at: result.updated_at,
+        mfa_enabled: result.mfa_enabled,
+        mfa_secret: result.mfa_secret,
+        token_scopes: None,
+    })
+}
+
+/// User repository for database operations
+#[derive(Debug, Clone)]
+pub struct UserRepository {
+    pool: Pool<Postgres>,
+}
+
+impl UserRepository {
+    /// Creates a new UserRepository instance
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    pub fn get_pool(&self) -> &Pool<Postgres> {
+        &self.pool
+    }
+
+    /// Loads the roles and permissions assigned to the given users
+    async fn load_roles(&self, user_ids: &[Uuid]) -> Result<HashMap<Uuid, Vec<Role>>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT ur.user_id, r.id AS role_id, r.name AS role_name, r.role_type,
+                   p.id AS "permission_id?", p.name AS "permission_name?",
+                   p.action AS "action?", p.resource AS "resource?", p.scope AS "scope?",
+                   p.effect AS "effect?"
+            FROM user_roles ur
+            JOIN roles r ON r.id = ur.role_id
+            LEFT JOIN permissions p ON p.role_id = r.id
+            WHERE ur.user_id = ANY($1)
+              AND ur.valid_from <= NOW()
+              AND (ur.valid_until IS NULL OR ur.valid_until > NOW())
+            ORDER BY ur.user_id, r.name, r.id, p.resource, p.action
+            "#,
+            user_ids,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut roles: HashMap<Uuid, Vec<Role>> = HashMap::new();
+        for row in rows {
+            push_role_row(
+                roles.entry(row.user_id).or_default(),
+                RoleRow {
+                    role_id: row.role_id,
+                    role_name: row.role_name,
+                    role_type: row.role_type,
+                    permission_id: row.permission_id,
+                    permission_name: row.permission_name,
+                    action: row.action,
+                    resource: row.resource,
+                    scope: row.scope,
+                    effect: row.effect,
+                },
+            )?;
+        }
+
+        let role_ids: Vec<Uuid> = roles.values().flatten().map(|role| role.id).collect();
+        let hierarchy = load_role_hierarchy(&self.pool, &role_ids).await?;
+        for role in roles.values_mut().flatten() {
+            hierarchy.resolve(role);
+        }
+        Ok(roles)
+    }
+
+    /// Attaches the stored roles to a user
+    async fn with_roles(&self, mut user: User) -> Result<User> {
+        user.roles = self
+            .load_roles(&[user.id.0])
+            .await?
+            .remove(&user.id.0)
+            .unwrap_or_default();
+        Ok(user)
+    }
+
+    /// Attaches the stored roles to an optional user
+    async fn with_roles_opt(&self, user: Option<User>) -> Result<Option<User>> {
+        match user {
+            Some(user) => Ok(Some(self.with_roles(user).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Gets a user by email and tenant ID
+    ///
+    /// Users of deleted tenants are not found, so they can neither log in nor use
+    /// their sessions.
+    pub async fn get_user_by_email(
+        &self,
+        email: &str,
+        tenant_id: TenantId,
+    ) -> Result<Option<User>> {
+        let result = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, email, password_hash, active, last_login, created_at, updated_at, mfa_enabled, mfa_secret
+            FROM users
+            WHERE email = $1 AND tenant_id = $2
+                AND EXISTS (SELECT 1 FROM tenants t WHERE t.id = users.tenant_id AND t.deleted_at IS NULL)
+            "#,
+            email,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        self.with_roles_opt(result.map(|r| User {
+            id: UserId(r.id),
+            tenant_id: TenantId(r.tenant_id),
+            email: r.email,
+            password_hash: r.password_hash,
+            active: r.active,
+            roles: Vec::new(),
+            last_login: r.last_login,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+            mfa_enabled: r.mfa_enabled,
+            mfa_secret: r.mfa_secret,
+            token_scopes: None,
+        }))
+        .await
+    }
+
+    /// Ensures a tenant is active and not suspended, telling its users why otherwise
+    ///
+    /// Tenants are inactive e.g. while their signup awaits approval.
+    pub async fn ensure_tenant_active(&self, tenant_id: TenantId) -> Result<()> {
+        let tenant = sqlx::query!(
+            r#"
+            SELECT active, suspended_at IS NOT NULL AS "suspended!", suspension_reason
+            FROM tenants
+            WHERE id = $1
+            "#,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match tenant {
+            Some(tenant) if tenant.suspended => {
+                Err(Error::TenantSuspended(match tenant.suspension_reason {
+                    Some(reason) => format!("Tenant is suspended: {}", reason),
+                    None => "Tenant is suspended".to_string(),
+                }))
+            },
+            Some(tenant) if !tenant.active => {
+                Err(Error::Authorization("Tenant is inactive".to_string()))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Updates a user's last login time
+    pub async fn update_last_login(&self, user_id: UserId) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET last_login = NOW()
+            WHERE id = $1
+            "#,
+            user_id.0 as uuid::Uuid,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Records an event in the audit log of its tenant
+    pub async fn insert_audit_event(&self, event: &AuditEvent) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_log (id, tenant_id, user_id, action, table_name, record_id, new_values)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            event.id,
+            event.tenant_id.0 as uuid::Uuid,
+            event.user_id.map(|id| id.0) as Option<uuid::Uuid>,
+            event.action,
+            event.table_name,
+            event.record_id,
+            event.details,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Lists the security events of a tenant with one of the given actions, newest first
+    pub async fn list_security_events(
+        &self,
+        tenant_id: TenantId,
+        actions: &[String],
+        user_id: Option<UserId>,
+        request_id: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<AuditEvent>> {
+        let results = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, user_id, action, table_name, record_id, new_values, created_at
+            FROM audit_log
+            WHERE tenant_id = $1
+              AND action = ANY($2)
+              AND ($3::uuid IS NULL OR user_id = $3)
+              AND ($4::text IS NULL OR new_values->>'request_id' = $4)
+            ORDER BY created_at DESC
+            LIMIT $5
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            actions,
+            user_id.map(|id| id.0) as Option<uuid::Uuid>,
+            request_id,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| AuditEvent {
+                id: r.id,
+                tenant_id: TenantId(r.tenant_id),
+                category: AuditCategory::Security,
+                action: r.action,
+                user_id: r.user_id.map(UserId),
+                table_name: r.table_name,
+                record_id: r.record_id,
+                details: r.new_values,
+                created_at: r.created_at.assume_utc(),
+            })
+            .collect())
+    }
+
+    /// Lists a page of the security events about a user with one of the given actions,
+    /// newest first, with the total count
+    ///
+    /// Failed logins are recorded before the user is known, so they are matched by email.
+    pub async fn list_user_activity(
+        &self,
+        tenant_id: TenantId,
+        user_id: UserId,
+        email: &str,
+        actions: &[String],
+        offset: i64,
+        limit: i64,
+    ) -> Result<(Vec<AuditEvent>, i64)> {
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM audit_log
+            WHERE tenant_id = $1
+              AND action = ANY($2)
+              AND (user_id = $3
+                   OR (table_name = 'users' AND (record_id = $3::text OR LOWER(record_id) = LOWER($4))))
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            actions,
+            user_id.0 as uuid::Uuid,
+            email,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let results = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, user_id, action, table_name, record_id, new_values, created_at
+            FROM audit_log
+            WHERE tenant_id = $1
+              AND action = ANY($2)
+              AND (user_id = $3
+                   OR (table_name = 'users' AND (record_id = $3::text OR LOWER(record_id) = LOWER($4))))
+            ORDER BY created_at DESC, id
+            OFFSET $5
+            LIMIT $6
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            actions,
+            user_id.0 as uuid::Uuid,
+            email,
+            offset,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let events = results
             .into_iter()
-            .filter_map(|r| serde_json::from_str(&r).ok())
-            .collect(),
-        None => Vec::new(),
+            .map(|r| AuditEvent {
+                id: r.id,
+                tenant_id: TenantId(r.tenant_id),
+                category: AuditCategory::Security,
+                action: r.action,
+                user_id: r.user_id.map(UserId),
+                table_name: r.table_name,
+                record_id: r.record_id,
+                details: r.new_values,
+                created_at: r.created_at.assume_utc(),
+            })
+            .collect();
+        Ok((events, total))
+    }
+
+    /// Creates a new user
+    pub async fn create_user(&self, user: User) -> Result<User> {
+        let mut tx = self.pool.begin().await?;
+        let created = insert_user(&mut tx, &user).await?;
+        tx.commit().await?;
+
+        self.with_roles(created).await
+    }
+
+    /// Gets a user by ID, unless their tenant was deleted
+    pub async fn get_user_by_id(&self, id: UserId) -> Result<Option<User>> {
+        let result = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, email, password_hash, active, last_login, created_at, updated_at, mfa_enabled, mfa_secret
+            FROM users
+            WHERE id = $1
+                AND EXISTS (SELECT 1 FROM tenants t WHERE t.id = users.tenant_id AND t.deleted_at IS NULL)
+            "#,
+            id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        self.with_roles_opt(result.map(|r| User {
+            id: UserId(r.id),
+            tenant_id: TenantId(r.tenant_id),
+            email: r.email,
+            password_hash: r.password_hash,
+            active: r.active,
+            roles: Vec::new(),
+            last_login: r.last_login,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+            mfa_enabled: r.mfa_enabled,
+            mfa_secret: r.mfa_secret,
+            token_scopes: None,
+        }))
+        .await
+    }
+
+    /// Updates a user
+    pub async fn update_user(&self, user: User) -> Result<User> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET email = $1, password_hash = $2, active = $3, updated_at = $4, mfa_enabled = $5, mfa_secret = $6
+            WHERE id = $7 AND tenant_id = $8
+            RETURNING id, tenant_id, email, password_hash, active, last_login, created_at, updated_at, mfa_enabled, mfa_secret
+            "#,
+            user.email,
+            user.password_hash,
+            user.active,
+            user.updated_at,
+            user.mfa_enabled,
+            user.mfa_secret,
+            user.id.0 as uuid::Uuid,
+            user.tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        save_user_roles(&mut tx, &user).await?;
+
+        tx.commit().await?;
+
+        self.with_roles(User {
+            id: UserId(result.id),
+            tenant_id: TenantId(result.tenant_id),
+            email: result.email,
+            password_hash: result.password_hash,
+            active: result.active,
+            roles: Vec::new(),
+            last_login: result.last_login,
+            created_at: result.created_at,
+            updated_at: result.updated_at,
+            mfa_enabled: result.mfa_enabled,
+            mfa_secret: result.mfa_secret,
+            token_scopes: None,
+        })
+        .await
+    }
+
+    /// Gets a user by their login address or any verified alias, unless their tenant was deleted
+    ///
+    /// Login addresses count unverified since the tenant set them; unverified aliases do not.
+    pub async fn get_user_by_verified_email(
+        &self,
+        email: &str,
+        tenant_id: TenantId,
+    ) -> Result<Option<User>> {
+        let result = sqlx::query!(
+            r#"
+            SELECT u.id, u.tenant_id, u.email, u.password_hash, u.active, u.last_login, u.created_at, u.updated_at, u.mfa_enabled, u.mfa_secret
+            FROM users u
+            JOIN user_emails e ON e.user_id = u.id
+            WHERE LOWER(e.email) = LOWER($1) AND e.tenant_id = $2 AND (e.verified OR e.is_primary)
+                AND EXISTS (SELECT 1 FROM tenants t WHERE t.id = u.tenant_id AND t.deleted_at IS NULL)
+            "#,
+            email,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        self.with_roles_opt(result.map(|r| User {
+            id: UserId(r.id),
+            tenant_id: TenantId(r.tenant_id),
+            email: r.email,
+            password_hash: r.password_hash,
+            active: r.active,
+            roles: Vec::new(),
+            last_login: r.last_login,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+            mfa_enabled: r.mfa_enabled,
+            mfa_secret: r.mfa_secret,
+            token_scopes: None,
+        }))
+        .await
+    }
+
+    /// Lists all email addresses of a user
+    pub async fn list_user_emails(
+        &self,
+        user_id: UserId,
+        tenant_id: TenantId,
+    ) -> Result<Vec<UserEmail>> {
+        let results = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, user_id, email, is_primary, verified, verified_at, created_at
+            FROM user_emails
+            WHERE user_id = $1 AND tenant_id = $2
+            ORDER BY is_primary DESC, created_at
+            "#,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| UserEmail {
+                id: r.id,
+                user_id: UserId(r.user_id),
+                tenant_id: TenantId(r.tenant_id),
+                email: r.email,
+                is_primary: r.is_primary,
+                verified: r.verified,
+                verified_at: r.verified_at,
+                created_at: r.created_at,
+            })
+            .collect())
+    }
+
+    /// Adds an email address to a user
+    pub async fn add_user_email(&self, email: &UserEmail) -> Result<UserEmail> {
+        let r = sqlx::query!(
+            r#"
+            INSERT INTO user_emails (id, tenant_id, user_id, email, is_primary, verified, verified_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, tenant_id, user_id, email, is_primary, verified, verified_at, created_at
+            "#,
+            email.id,
+            email.tenant_id.0 as uuid::Uuid,
+            email.user_id.0 as uuid::Uuid,
+            email.email,
+            email.is_primary,
+            email.verified,
+            email.verified_at,
+            email.created_at,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(UserEmail {
+            id: r.id,
+            user_id: UserId(r.user_id),
+            tenant_id: TenantId(r.tenant_id),
+            email: r.email,
+            is_primary: r.is_primary,
+            verified: r.verified,
+            verified_at: r.verified_at,
+            created_at: r.created_at,
+        })
+    }
+
+    /// Marks an email address as verified
+    pub async fn mark_email_verified(
+        &self,
+        email_id: Uuid,
+        user_id: UserId,
+        tenant_id: TenantId,
+    ) -> Result<Option<UserEmail>> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_emails
+            SET verified = TRUE, verified_at = NOW(),
+                verification_token_hash = NULL, verification_expires_at = NULL
+            WHERE id = $1 AND user_id = $2 AND tenant_id = $3
+            RETURNING id, tenant_id, user_id, email, is_primary, verified, verified_at, created_at
+            "#,
+            email_id,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(|r| UserEmail {
+            id: r.id,
+            user_id: UserId(r.user_id),
+            tenant_id: TenantId(r.tenant_id),
+            email: r.email,
+            is_primary: r.is_primary,
+            verified: r.verified,
+            verified_at: r.verified_at,
+            created_at: r.created_at,
+        }))
+    }
+
+    /// Stores the hashed token verifying an unverified email address, replacing any earlier one
+    pub async fn set_email_verification(
+        &self,
+        email_id: Uuid,
+        user_id: UserId,
+        tenant_id: TenantId,
+        token_hash: &str,
+        expires_at: OffsetDateTime,
+    ) -> Result<Option<UserEmail>> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_emails
+            SET verification_token_hash = $1, verification_expires_at = $2
+            WHERE id = $3 AND user_id = $4 AND tenant_id = $5 AND NOT verified
+            RETURNING id, tenant_id, user_id, email, is_primary, verified, verified_at, created_at
+            "#,
+            token_hash,
+            expires_at,
+            email_id,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(|r| UserEmail {
+            id: r.id,
+            user_id: UserId(r.user_id),
+            tenant_id: TenantId(r.tenant_id),
+            email: r.email,
+            is_primary: r.is_primary,
+            verified: r.verified,
+            verified_at: r.verified_at,
+            created_at: r.created_at,
+        }))
+    }
+
+    /// Verifies the email address of a user a token was issued for, unless the token expired
+    pub async fn verify_email_token(
+        &self,
+        token_hash: &str,
+        user_id: UserId,
+        tenant_id: TenantId,
+    ) -> Result<Option<UserEmail>> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_emails
+            SET verified = TRUE, verified_at = NOW(),
+                verification_token_hash = NULL, verification_expires_at = NULL
+            WHERE verification_token_hash = $1 AND user_id = $2 AND tenant_id = $3
+                AND verification_expires_at > NOW()
+            RETURNING id, tenant_id, user_id, email, is_primary, verified, verified_at, created_at
+            "#,
+            token_hash,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(|r| UserEmail {
+            id: r.id,
+            user_id: UserId(r.user_id),
+            tenant_id: TenantId(r.tenant_id),
+            email: r.email,
+            is_primary: r.is_primary,
+            verified: r.verified,
+            verified_at: r.verified_at,
+            created_at: r.created_at,
+        }))
+    }
+
+    /// Makes an email address the user's primary (login) address
+    pub async fn set_primary_email(
+        &self,
+        email_id: Uuid,
+        user_id: UserId,
+        tenant_id: TenantId,
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE user_emails
+            SET is_primary = FALSE
+            WHERE user_id = $1 AND tenant_id = $2 AND is_primary
+            "#,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let email = sqlx::query_scalar!(
+            r#"
+            UPDATE user_emails
+            SET is_primary = TRUE
+            WHERE id = $1 AND user_id = $2 AND tenant_id = $3
+            RETURNING email
+            "#,
+            email_id,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| Error::NotFound("Email address not found".to_string()))?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET email = $1, updated_at = NOW()
+            WHERE id = $2 AND tenant_id = $3
+            "#,
+            email,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Removes a non-primary email address from a user
+    pub async fn remove_user_email(
+        &self,
+        email_id: Uuid,
+        user_id: UserId,
+        tenant_id: TenantId,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM user_emails
+            WHERE id = $1 AND user_id = $2 AND tenant_id = $3 AND NOT is_primary
+            "#,
+            email_id,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Sets the active flag of a user
+    pub async fn set_user_active(
+        &self,
+        id: UserId,
+        tenant_id: TenantId,
+        active: bool,
+    ) -> Result<Option<User>> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET active = $1, updated_at = NOW()
+            WHERE id = $2 AND tenant_id = $3
+            RETURNING id, tenant_id, email, password_hash, active, last_login, created_at, updated_at, mfa_enabled, mfa_secret
+            "#,
+            active,
+            id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        self.with_roles_opt(result.map(|r| User {
+            id: UserId(r.id),
+            tenant_id: TenantId(r.tenant_id),
+            email: r.email,
+            password_hash: r.password_hash,
+            active: r.active,
+            roles: Vec::new(),
+            last_login: r.last_login,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+            mfa_enabled: r.mfa_enabled,
+            mfa_secret: r.mfa_secret,
+            token_scopes: None,
+        }))
+        .await
+    }
+
+    /// Deletes a user
+    pub async fn delete_user(&self, id: UserId, tenant_id: TenantId) -> Result<()> {
+        sqlx::query!(
+            r#"
+            DELETE FROM users
+            WHERE id = $1 AND tenant_id = $2
+            "#,
+            id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Lists all users
+    pub async fn list_users(&self) -> Result<Vec<User>> {
+        let results = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, email, password_hash, active, last_login, created_at, updated_at, mfa_enabled, mfa_secret
+            FROM users
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let user_ids: Vec<Uuid> = results.iter().map(|r| r.id).collect();
+        let mut roles = self.load_roles(&user_ids).await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| User {
+                id: UserId(r.id),
+                tenant_id: TenantId(r.tenant_id),
+                email: r.email,
+                password_hash: r.password_hash,
+                active: r.active,
+                roles: roles.remove(&r.id).unwrap_or_default(),
+                last_login: r.last_login,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+                mfa_enabled: r.mfa_enabled,
+                mfa_secret: r.mfa_secret,
+                token_scopes: None,
+            })
+            .collect())
+    }
+
+    /// Lists the memberships of a user's identity in tenants users can log in to
+    ///
+    /// Users are only linked to the identity of their login address once its owner
+    /// verified it, so addresses set by tenant admins or directories never count as
+    /// memberships in other tenants.
+    pub async fn list_memberships(&self, user_id: UserId) -> Result<Vec<Membership>> {
+        let results = sqlx::query!(
+            r#"
+            SELECT u.id AS user_id, u.tenant_id, t.name AS tenant_name, t.slug AS tenant_slug
+            FROM users me
+            JOIN users u ON u.id = me.id OR u.identity_id = me.identity_id
+            JOIN tenants t ON t.id = u.tenant_id
+            WHERE me.id = $1 AND u.active
+                AND t.active AND t.deleted_at IS NULL AND t.suspended_at IS NULL
+            ORDER BY t.name, t.id
+            "#,
+            user_id.0 as uuid::Uuid,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| Membership {
+                tenant_id: TenantId(r.tenant_id),
+                tenant_name: r.tenant_name,
+                tenant_slug: r.tenant_slug,
+                user_id: UserId(r.user_id),
+            })
+            .collect())
+    }
+
+    /// Lists the memberships of the identity of an email address in tenants users can log in to
+    pub async fn list_memberships_by_email(&self, email: &str) -> Result<Vec<Membership>> {
+        let results = sqlx::query!(
+            r#"
+            SELECT u.id AS user_id, u.tenant_id, t.name AS tenant_name, t.slug AS tenant_slug
+            FROM identities i
+            JOIN users u ON u.identity_id = i.id
+            JOIN tenants t ON t.id = u.tenant_id
+            WHERE i.email = LOWER($1) AND u.active
+                AND t.active AND t.deleted_at IS NULL AND t.suspended_at IS NULL
+            ORDER BY t.name, t.id
+            "#,
+            email.trim(),
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| Membership {
+                tenant_id: TenantId(r.tenant_id),
+                tenant_name: r.tenant_name,
+                tenant_slug: r.tenant_slug,
+                user_id: UserId(r.user_id),
+            })
+            .collect())
+    }
+
+    /// Lists a page of a tenant's users, optionally filtered by email, with the total count
+    pub async fn list_tenant_users(
+        &self,
+        tenant_id: TenantId,
+        email: Option<&str>,
+        offset: i64,
+        limit: i64,
+    ) -> Result<(Vec<User>, i64)> {
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM users
+            WHERE tenant_id = $1 AND ($2::text IS NULL OR LOWER(email) = LOWER($2))
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            email,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let results = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, email, password_hash, active, last_login, created_at, updated_at, mfa_enabled, mfa_secret
+            FROM users
+            WHERE tenant_id = $1 AND ($2::text IS NULL OR LOWER(email) = LOWER($2))
+            ORDER BY created_at, id
+            OFFSET $3
+            LIMIT $4
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            email,
+            offset,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let user_ids: Vec<Uuid> = results.iter().map(|r| r.id).collect();
+        let mut roles = self.load_roles(&user_ids).await?;
+
+        let users = results
+            .into_iter()
+            .map(|r| User {
+                id: UserId(r.id),
+                tenant_id: TenantId(r.tenant_id),
+                email: r.email,
+                password_hash: r.password_hash,
+                active: r.active,
+                roles: roles.remove(&r.id).unwrap_or_default(),
+                last_login: r.last_login,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+                mfa_enabled: r.mfa_enabled,
+                mfa_secret: r.mfa_secret,
+                token_scopes: None,
+            })
+            .collect();
+        Ok((users, total))
     }
-}
-
-/// Helper function to convert domain roles to database roles
-fn roles_to_strings(roles: &[Role]) -> Vec<String> {
-    roles
-        .iter()
-        .filter_map(|r| serde_json::to_string(r).ok())
-        .collect()
-}
 
-/// Helper function to convert Option<OffsetDateTime> to Option<PrimitiveDateTime>
-fn to_primitive_datetime(dt: OffsetDateTime) -> PrimitiveDateTime {
-    PrimitiveDateTime::new(dt.date(), dt.time())
-}
+    /// Lists a page of a tenant's users for the admin overview, with the total count
+    ///
+    /// The active session count is left at zero as sessions live in the session store.
+    pub async fn list_user_overview(
+        &self,
+        tenant_id: TenantId,
+        offset: i64,
+        limit: i64,
+    ) -> Result<(Vec<UserOverview>, i64)> {
+        let results = sqlx::query!(
+            r#"
+            SELECT u.id, u.email, u.active, u.mfa_enabled, u.last_login, u.created_at,
+                   ARRAY(
+                       SELECT r.name
+                       FROM user_roles ur
+                       JOIN roles r ON r.id = ur.role_id
+                       WHERE ur.user_id = u.id
+                         AND ur.valid_from <= NOW()
+                         AND (ur.valid_until IS NULL OR ur.valid_until > NOW())
+                       ORDER BY r.name
+                   ) AS "roles!",
+                   COUNT(*) OVER () AS "total!"
+            FROM users u
+            WHERE u.tenant_id = $1
+            ORDER BY u.created_at, u.id
+            OFFSET $2
+            LIMIT $3
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            offset,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
 
-/// Helper function to convert Option<PrimitiveDateTime> to Option<OffsetDateTime>
-fn to_offset_datetime(dt: PrimitiveDateTime) -> OffsetDateTime {
-    dt.assume_utc()
-}
+        // Pages past the end carry no rows to read the total from
+        let total = match results.first() {
+            Some(r) => r.total,
+            None => {
+                sqlx::query_scalar!(
+                    r#"
+                    SELECT COUNT(*) AS "count!"
+                    FROM users
+                    WHERE tenant_id = $1
+                    "#,
+                    tenant_id.0 as uuid::Uuid,
+                )
+                .fetch_one(&self.pool)
+                .await?
+            },
+        };
 
-/// Helper function to convert Option<OffsetDateTime> to Option<PrimitiveDateTime>
-fn convert_to_primitive(dt: Option<OffsetDateTime>) -> Option<PrimitiveDateTime> {
-    dt.map(to_primitive_datetime)
+        let users = results
+            .into_iter()
+            .map(|r| UserOverview {
+                id: r.id,
+                email: r.email,
+                roles: r.roles,
+                active: r.active,
+                locked: !r.active,
+                mfa_enabled: r.mfa_enabled,
+                last_login: r.last_login,
+                active_sessions: 0,
+                created_at: r.created_at,
+            })
+            .collect();
+        Ok((users, total))
+    }
 }
 
-/// Helper function to convert Option<PrimitiveDateTime> to Option<OffsetDateTime>
-fn convert_to_offset(dt: Option<PrimitiveDateTime>) -> Option<OffsetDateTime> {
-    dt.map(to_offset_datetime)
+impl Default for UserRepository {
+    fn default() -> Self {
+        Self::new(Database::default().get_pool())
+    }
 }
 
-/// User repository for database operations
+/// Role repository for tenant-defined roles
 #[derive(Debug, Clone)]
-pub struct UserRepository {
+pub struct RoleRepository {
     pool: Pool<Postgres>,
 }
 
-impl UserRepository {
-    /// Creates a new UserRepository instance
+impl RoleRepository {
+    /// Creates a new RoleRepository instance
     pub fn new(pool: Pool<Postgres>) -> Self {
         Self { pool }
     }
 
-    pub fn get_pool(&self) -> &Pool<Postgres> {
-        &self.pool
+    /// Fetches the roles of a tenant, optionally restricted to a single role
+    async fn fetch_roles(&self, tenant_id: TenantId, role_id: Option<Uuid>) -> Result<Vec<Role>> {
+        let rows = sqlx::query_as!(
+            RoleRow,
+            r#"
+            SELECT r.id AS role_id, r.name AS role_name, r.role_type,
+                   p.id AS "permission_id?", p.name AS "permission_name?",
+                   p.action AS "action?", p.resource AS "resource?", p.scope AS "scope?",
+                   p.effect AS "effect?"
+            FROM roles r
+            LEFT JOIN permissions p ON p.role_id = r.id
+            WHERE r.tenant_id = $1 AND ($2::uuid IS NULL OR r.id = $2)
+            ORDER BY r.name, r.id, p.resource, p.action
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            role_id,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut roles = Vec::new();
+        for row in rows {
+            push_role_row(&mut roles, row)?;
+        }
+
+        let role_ids: Vec<Uuid> = roles.iter().map(|role| role.id).collect();
+        let hierarchy = load_role_hierarchy(&self.pool, &role_ids).await?;
+        for role in &mut roles {
+            hierarchy.resolve(role);
+        }
+        Ok(roles)
     }
 
-    /// Gets a user by email and tenant ID
-    pub async fn get_user_by_email(
-        &self,
-        email: &str,
-        tenant_id: TenantId,
-    ) -> Result<Option<User>> {
-        let result = sqlx::query!(
+    /// Lists all roles of a tenant
+    pub async fn list_roles(&self, tenant_id: TenantId) -> Result<Vec<Role>> {
+        self.fetch_roles(tenant_id, None).await
+    }
+
+    /// Gets a role by ID
+    pub async fn get_role(&self, id: Uuid, tenant_id: TenantId) -> Result<Option<Role>> {
+        Ok(self.fetch_roles(tenant_id, Some(id)).await?.pop())
+    }
+
+    /// Creates a role with its permissions and parents
+    pub async fn create_role(&self, tenant_id: TenantId, role: &Role) -> Result<Role> {
+        let mut tx = self.pool.begin().await?;
+        insert_role(&mut tx, tenant_id, role).await?;
+        tx.commit().await?;
+
+        self.get_role(role.id, tenant_id)
+            .await?
+            .ok_or_else(|| Error::Internal("Created role not found".to_string()))
+    }
+
+    /// Updates the name, permissions and parents of a role
+    pub async fn update_role(&self, tenant_id: TenantId, role: &Role) -> Result<Option<Role>> {
+        let mut tx = self.pool.begin().await?;
+
+        let updated = sqlx::query!(
             r#"
-            SELECT id, tenant_id, email, password_hash, active, roles, last_login, created_at, updated_at, mfa_enabled, mfa_secret
-            FROM users
-            WHERE email = $1 AND tenant_id = $2
+            UPDATE roles
+            SET name = $1
+            WHERE id = $2 AND tenant_id = $3
             "#,
-            email,
+            role.name,
+            role.id,
             tenant_id.0 as uuid::Uuid,
         )
-        .fetch_optional(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(result.map(|r| User {
-            id: UserId(r.id),
-            tenant_id: TenantId(r.tenant_id),
-            email: r.email,
-            password_hash: r.password_hash,
-            active: r.active,
-            roles: convert_roles(Some(r.roles)),
-            last_login: convert_to_offset(r.last_login),
-            created_at: to_offset_datetime(r.created_at),
-            updated_at: to_offset_datetime(r.updated_at),
-            mfa_enabled: r.mfa_enabled,
-            mfa_secret: r.mfa_secret,
-        }))
+        if updated.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        save_role_permissions(&mut tx, role.id, role).await?;
+        save_role_parents(&mut tx, tenant_id, role).await?;
+
+        tx.commit().await?;
+
+        self.get_role(role.id, tenant_id).await
     }
 
-    /// Updates a user's last login time
-    pub async fn update_last_login(&self, user_id: UserId) -> Result<()> {
-        sqlx::query!(
+    /// Assigns a role to a user of the same tenant for the given validity window
+    ///
+    /// An existing assignment takes the new window. Returns false if nothing
+    /// changed, i.e. the role is already active with the same end.
+    pub async fn assign_role(
+        &self,
+        user_id: UserId,
+        role_id: Uuid,
+        tenant_id: TenantId,
+        valid_from: OffsetDateTime,
+        valid_until: Option<OffsetDateTime>,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
             r#"
-            UPDATE users
-            SET last_login = NOW()
-            WHERE id = $1
+            INSERT INTO user_roles (user_id, role_id, tenant_id, valid_from, valid_until)
+            SELECT u.id, r.id, u.tenant_id, $4, $5
+            FROM users u
+            JOIN roles r ON r.tenant_id = u.tenant_id
+            WHERE u.id = $1 AND r.id = $2 AND u.tenant_id = $3
+            ON CONFLICT (user_id, role_id) DO UPDATE
+            SET valid_from = EXCLUDED.valid_from, valid_until = EXCLUDED.valid_until
+            WHERE NOT (
+                user_roles.valid_from <= NOW()
+                AND EXCLUDED.valid_from <= NOW()
+                AND user_roles.valid_until IS NOT DISTINCT FROM EXCLUDED.valid_until
+            )
             "#,
             user_id.0 as uuid::Uuid,
+            role_id,
+            tenant_id.0 as uuid::Uuid,
+            valid_from,
+            valid_until,
         )
         .execute(&self.pool)
         .await?;
-        Ok(())
+        Ok(result.rows_affected() > 0)
     }
 
-    /// Creates a new user
-    pub async fn create_user(&self, user: User) -> Result<User> {
-        let result = sqlx::query!(
+    /// Removes up to `limit` lapsed role assignments, returning the tenant, user and role of each
+    pub async fn delete_expired_assignments(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<(TenantId, UserId, Uuid)>> {
+        let results = sqlx::query!(
             r#"
-            INSERT INTO users (id, tenant_id, email, password_hash, active, roles, created_at, updated_at, mfa_enabled, mfa_secret)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-            RETURNING id, tenant_id, email, password_hash, active, roles, last_login, created_at, updated_at, mfa_enabled, mfa_secret
+            DELETE FROM user_roles
+            WHERE (user_id, role_id) IN (
+                SELECT user_id, role_id
+                FROM user_roles
+                WHERE valid_until <= NOW()
+                LIMIT $1
+            )
+            RETURNING tenant_id, user_id, role_id
             "#,
-            user.id.0 as uuid::Uuid,
-            user.tenant_id.0 as uuid::Uuid,
-            user.email,
-            user.password_hash,
-            user.active,
-            &roles_to_strings(&user.roles),
-            to_primitive_datetime(user.created_at),
-            to_primitive_datetime(user.updated_at),
-            user.mfa_enabled,
-            user.mfa_secret,
+            limit,
         )
-        .fetch_one(&self.pool)
+        .fetch_all(&self.pool)
         .await?;
 
-        Ok(User {
-            id: UserId(result.id),
-            tenant_id: TenantId(result.tenant_id),
-            email: result.email,
-            password_hash: result.password_hash,
-            active: result.active,
-            roles: convert_roles(Some(result.roles)),
-            last_login: convert_to_offset(result.last_login),
-            created_at: to_offset_datetime(result.created_at),
-            updated_at: to_offset_datetime(result.updated_at),
-            mfa_enabled: result.mfa_enabled,
-            mfa_secret: result.mfa_secret,
-        })
+        Ok(results
+            .into_iter()
+            .map(|r| (TenantId(r.tenant_id), UserId(r.user_id), r.role_id))
+            .collect())
     }
 
-    /// Gets a user by ID
-    pub async fn get_user_by_id(&self, id: UserId) -> Result<Option<User>> {
-        let result = sqlx::query!(
+    /// Lists the users with scheduled role assignments that took effect after `since`
+    pub async fn list_activated_assignments(&self, since: OffsetDateTime) -> Result<Vec<UserId>> {
+        let results = sqlx::query_scalar!(
             r#"
-            SELECT id, tenant_id, email, password_hash, active, roles, last_login, created_at, updated_at, mfa_enabled, mfa_secret
-            FROM users
-            WHERE id = $1
+            SELECT DISTINCT user_id
+            FROM user_roles
+            WHERE valid_from > $1 AND valid_from <= NOW()
             "#,
-            id.0 as uuid::Uuid,
+            since,
         )
-        .fetch_optional(&self.pool)
+        .fetch_all(&self.pool)
         .await?;
 
-        Ok(result.map(|r| User {
-            id: UserId(r.id),
-            tenant_id: TenantId(r.tenant_id),
-            email: r.email,
-            password_hash: r.password_hash,
-            active: r.active,
-            roles: convert_roles(Some(r.roles)),
-            last_login: convert_to_offset(r.last_login),
-            created_at: to_offset_datetime(r.created_at),
-            updated_at: to_offset_datetime(r.updated_at),
-            mfa_enabled: r.mfa_enabled,
-            mfa_secret: r.mfa_secret,
-        }))
+        Ok(results.into_iter().map(UserId).collect())
     }
 
-    /// Updates a user
-    pub async fn update_user(&self, user: User) -> Result<User> {
+    /// Removes a role assignment from a user
+    pub async fn revoke_role(
+        &self,
+        user_id: UserId,
+        role_id: Uuid,
+        tenant_id: TenantId,
+    ) -> Result<bool> {
         let result = sqlx::query!(
             r#"
-            UPDATE users
-            SET email = $1, password_hash = $2, active = $3, roles = $4, updated_at = $5, mfa_enabled = $6, mfa_secret = $7
-            WHERE id = $8 AND tenant_id = $9
-            RETURNING id, tenant_id, email, password_hash, active, roles, last_login, created_at, updated_at, mfa_enabled, mfa_secret
+            DELETE FROM user_roles
+            WHERE user_id = $1 AND role_id = $2 AND tenant_id = $3
             "#,
-            user.email,
-            user.password_hash,
-            user.active,
-            &roles_to_strings(&user.roles),
-            to_primitive_datetime(user.updated_at),
-            user.mfa_enabled,
-            user.mfa_secret,
-            user.id.0 as uuid::Uuid,
-            user.tenant_id.0 as uuid::Uuid,
+            user_id.0 as uuid::Uuid,
+            role_id,
+            tenant_id.0 as uuid::Uuid,
         )
-        .fetch_one(&self.pool)
+        .execute(&self.pool)
         .await?;
+        Ok(result.rows_affected() > 0)
+    }
 
-        Ok(User {
-            id: UserId(result.id),
-            tenant_id: TenantId(result.tenant_id),
-            email: result.email,
-            password_hash: result.password_hash,
-            active: result.active,
-            roles: convert_roles(Some(result.roles)),
-            last_login: convert_to_offset(result.last_login),
-            created_at: to_offset_datetime(result.created_at),
-            updated_at: to_offset_datetime(result.updated_at),
-            mfa_enabled: result.mfa_enabled,
-            mfa_secret: result.mfa_secret,
-        })
+    /// Lists the IDs and emails of the users holding a role
+    pub async fn list_role_members(
+        &self,
+        role_id: Uuid,
+        tenant_id: TenantId,
+    ) -> Result<Vec<(UserId, String)>> {
+        let results = sqlx::query!(
+            r#"
+            SELECT u.id, u.email
+            FROM user_roles ur
+            JOIN users u ON u.id = ur.user_id
+            WHERE ur.role_id = $1 AND ur.tenant_id = $2
+              AND ur.valid_from <= NOW()
+              AND (ur.valid_until IS NULL OR ur.valid_until > NOW())
+            ORDER BY u.email
+            "#,
+            role_id,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| (UserId(r.id), r.email))
+            .collect())
     }
 
-    /// Deletes a user
-    pub async fn delete_user(&self, id: UserId, tenant_id: TenantId) -> Result<()> {
-        sqlx::query!(
+    /// Deletes a role and its assignments
+    pub async fn delete_role(&self, id: Uuid, tenant_id: TenantId) -> Result<bool> {
+        let result = sqlx::query!(
             r#"
-            DELETE FROM users
+            DELETE FROM roles
             WHERE id = $1 AND tenant_id = $2
             "#,
-            id.0 as uuid::Uuid,
+            id,
             tenant_id.0 as uuid::Uuid,
         )
         .execute(&self.pool)
         .await?;
-        Ok(())
+        Ok(result.rows_affected() > 0)
     }
+}
 
-    /// Lists all users
-    pub async fn list_users(&self) -> Result<Vec<User>> {
+/// Resource owner repository for object-level authorization
+#[derive(Debug, Clone)]
+pub struct ResourceOwnerRepository {
+    pool: Pool<Postgres>,
+}
+
+impl ResourceOwnerRepository {
+    /// Creates a new ResourceOwnerRepository instance
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    /// Lists the owners of a resource
+    pub async fn list_owners(
+        &self,
+        tenant_id: TenantId,
+        resource_type: &str,
+        resource_id: &str,
+    ) -> Result<Vec<ResourceOwner>> {
         let results = sqlx::query!(
             r#"
-            SELECT id, tenant_id, email, password_hash, active, roles, last_login, created_at, updated_at, mfa_enabled, mfa_secret
-            FROM users
-            "#
+            SELECT tenant_id, resource_type, resource_id, owner_type, owner_id
+            FROM resource_owners
+            WHERE tenant_id = $1 AND resource_type = $2 AND resource_id = $3
+            ORDER BY created_at, owner_id
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            resource_type,
+            resource_id,
         )
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(results
+        results
             .into_iter()
-            .map(|r| User {
-                id: UserId(r.id),
-                tenant_id: TenantId(r.tenant_id),
-                email: r.email,
-                password_hash: r.password_hash,
-                active: r.active,
-                roles: convert_roles(Some(r.roles)),
-                last_login: convert_to_offset(r.last_login),
-                created_at: to_offset_datetime(r.created_at),
-                updated_at: to_offset_datetime(r.updated_at),
-                mfa_enabled: r.mfa_enabled,
-                mfa_secret: r.mfa_secret,
+            .map(|r| {
+                Ok(ResourceOwner {
+                    tenant_id: TenantId(r.tenant_id),
+                    resource_type: r.resource_type,
+                    resource_id: r.resource_id,
+                    owner_type: r.owner_type.parse()?,
+                    owner_id: r.owner_id,
+                })
             })
-            .collect())
+            .collect()
     }
-}
 
-impl Default for UserRepository {
-    fn default() -> Self {
-        Self::new(Database::default().get_pool())
+    /// Registers an owner of a resource
+    pub async fn add_owner(&self, owner: &ResourceOwner) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO resource_owners (tenant_id, resource_type, resource_id, owner_type, owner_id)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT DO NOTHING
+            "#,
+            owner.tenant_id.0 as uuid::Uuid,
+            owner.resource_type,
+            owner.resource_id,
+            owner.owner_type.to_string(),
+            owner.owner_id,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Removes an owner of a resource
+    pub async fn remove_owner(&self, owner: &ResourceOwner) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM resource_owners
+            WHERE tenant_id = $1 AND resource_type = $2 AND resource_id = $3
+              AND owner_type = $4 AND owner_id = $5
+            "#,
+            owner.tenant_id.0 as uuid::Uuid,
+            owner.resource_type,
+            owner.resource_id,
+            owner.owner_type.to_string(),
+            owner.owner_id,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
     }
 }
 
@@ -272,8 +1643,10 @@ impl Default for UserRepository {
 mod tests {
     use super::*;
     use crate::core::database::tests::create_test_db;
+    use crate::modules::identity::models::{PermissionAction, RoleType};
     use crate::modules::tenant::models::Tenant;
     use std::time::Duration;
+    use time::OffsetDateTime;
 
     async fn setup_test_tenant(db: &Database) -> Result<Tenant> {
         let tenant = Tenant::new(
@@ -326,6 +1699,7 @@ mod tests {
             updated_at: OffsetDateTime::now_utc(),
             mfa_enabled: false,
             mfa_secret: None,
+            token_scopes: None,
         };
 
         let mut retries = 3;
@@ -386,4 +1760,152 @@ mod tests {
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_user_emails() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let tenant = setup_test_tenant(&db).await.unwrap();
+
+        let user = User::new(
+            tenant.id,
+            "primary@example.com".to_string(),
+            "hash".to_string(),
+        );
+        let user = repository.create_user(user).await.unwrap();
+
+        // Primary address is registered unverified on creation, but finds the user in the tenant
+        let emails = repository
+            .list_user_emails(user.id, tenant.id)
+            .await
+            .unwrap();
+        assert_eq!(emails.len(), 1);
+        assert!(emails[0].is_primary);
+        assert!(!emails[0].verified);
+        let found = repository
+            .get_user_by_verified_email("PRIMARY@example.com", tenant.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, user.id);
+
+        // Unverified aliases cannot be used for lookup
+        let alias = UserEmail::new(user.id, tenant.id, "alias@corp.example.com".to_string());
+        let alias = repository.add_user_email(&alias).await.unwrap();
+        assert!(repository
+            .get_user_by_verified_email("alias@corp.example.com", tenant.id)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Aliases are verified with the token mailed to them until it expires
+        let now = OffsetDateTime::now_utc();
+        repository
+            .set_email_verification(
+                alias.id,
+                user.id,
+                tenant.id,
+                "expired-token-hash",
+                now - time::Duration::minutes(1),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(repository
+            .verify_email_token("expired-token-hash", user.id, tenant.id)
+            .await
+            .unwrap()
+            .is_none());
+        repository
+            .set_email_verification(
+                alias.id,
+                user.id,
+                tenant.id,
+                "token-hash",
+                now + time::Duration::hours(1),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let verified = repository
+            .verify_email_token("token-hash", user.id, tenant.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(verified.verified);
+
+        // Verified aliases resolve to the same account (case-insensitive)
+        let found = repository
+            .get_user_by_verified_email("ALIAS@corp.example.com", tenant.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, user.id);
+
+        // Promote the alias to primary
+        repository
+            .set_primary_email(alias.id, user.id, tenant.id)
+            .await
+            .unwrap();
+        let updated = repository.get_user_by_id(user.id).await.unwrap().unwrap();
+        assert_eq!(updated.email, "alias@corp.example.com");
+
+        // The primary address cannot be removed
+        assert!(!repository
+            .remove_user_email(alias.id, user.id, tenant.id)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_user_roles() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let tenant = setup_test_tenant(&db).await.unwrap();
+
+        let mut editor = Role::new(RoleType::User, "Editor".to_string());
+        editor.permissions.push(Permission::new(
+            "Update Documents".to_string(),
+            PermissionAction::Update,
+            "documents".to_string(),
+        ));
+
+        let mut user = User::new(
+            tenant.id,
+            "editor@example.com".to_string(),
+            "hash".to_string(),
+        );
+        user.roles.push(editor.clone());
+        let user = repository.create_user(user).await.unwrap();
+        assert_eq!(user.roles.len(), 1);
+        assert_eq!(user.roles[0].permissions.len(), 1);
+        assert_eq!(
+            user.roles[0].permissions[0].action,
+            PermissionAction::Update
+        );
+
+        // A second copy of the role is resolved to the existing row
+        let mut other = User::new(
+            tenant.id,
+            "other@example.com".to_string(),
+            "hash".to_string(),
+        );
+        other
+            .roles
+            .push(Role::new(RoleType::User, "Editor".to_string()));
+        let other = repository.create_user(other).await.unwrap();
+        assert_eq!(other.roles[0].id, user.roles[0].id);
+        assert_eq!(other.roles[0].permissions.len(), 1);
+
+        // Removing the role from a user only drops the assignment
+        let mut user = user;
+        user.roles.clear();
+        let user = repository.update_user(user).await.unwrap();
+        assert!(user.roles.is_empty());
+
+        let users = repository.list_users().await.unwrap();
+        let other = users.iter().find(|u| u.id == other.id).unwrap();
+        assert_eq!(other.roles.len(), 1);
+        assert_eq!(other.roles[0].name, "Editor");
+    }
 }
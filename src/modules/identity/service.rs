@@ -1,39 +1,246 @@
 use crate::{
-    core::database::Database,
+    core::config::EmailConfig,
     modules::{
         identity::{
-            models::{Permission, PermissionAction, Role, RoleType, User},
-            rbac::{create_user_role, RbacService},
-            repository::UserRepository,
+            auth::AuthenticationService,
+            catalog::PermissionCatalog,
+            models::{
+                ActivityPage, ActivityQuery, AuthorizationAuditQuery, OwnerType, Permission,
+                PermissionAction, PolicySimulation, PolicySimulationRequest, ResourceOwner, Role,
+                RoleAssignmentRequest, RoleRequest, RoleType, SimulationReason, TokenScope, User,
+                UserEmail, UserOverviewPage, UserOverviewQuery,
+            },
+            policy::PolicyEngine,
+            rbac::{
+                can_grant, ensure_tenant_boundary, has_permission, matching_permissions,
+                simulation_reason, AuthorizationDecision, DecisionRecorder, RbacService,
+                RoleHierarchy,
+            },
+            repository::{ResourceOwnerRepository, RoleRepository, UserRepository},
         },
-        tenant::models::Tenant,
+        signup::models::hash_token,
     },
     shared::{
+        audit::{AuditCategory, AuditEvent, AuditStream},
+        cache::{CacheInvalidation, CacheInvalidationBus},
         error::{Error, Result},
+        mail::{EmailMessage, LogMailer, Mailer},
         types::{TenantId, UserId},
     },
 };
+use async_trait::async_trait;
+use rand::{distributions::Alphanumeric, Rng};
+use std::sync::Arc;
 use time::OffsetDateTime;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
+/// Default number of users per page of the admin user overview
+const DEFAULT_OVERVIEW_PAGE_SIZE: i64 = 50;
+
+/// Maximum number of users per page of the admin user overview
+const MAX_OVERVIEW_PAGE_SIZE: i64 = 100;
+
 /// Identity module for managing users and permissions
 #[derive(Debug)]
 pub struct IdentityModule {
     repository: UserRepository,
-    rbac: RbacService,
+    role_repository: RoleRepository,
+    owner_repository: ResourceOwnerRepository,
+    rbac: Arc<RbacService>,
+    invalidation: CacheInvalidationBus,
+    audit: AuditStream,
+    catalog: Arc<PermissionCatalog>,
+    /// Whether allowed decisions are audited too, `None` if decisions are not audited
+    authorization_audit: Option<bool>,
+    mailer: Arc<dyn Mailer>,
+    email_verification_ttl: time::Duration,
+    email_verification_url: String,
+}
+
+/// Action of audit events about denied permission checks
+pub const AUTHORIZATION_DENIED: &str = "authorization_denied";
+
+/// Action of audit events about allowed permission checks
+pub const AUTHORIZATION_ALLOWED: &str = "authorization_allowed";
+
+/// Security relevant actions shown in the activity timeline of a user
+const ACTIVITY_ACTIONS: &[&str] = &[
+    "login_succeeded",
+    "login_failed",
+    "login_throttled",
+    "access_token_created",
+    "user_deactivated",
+    "user_activated",
+];
+
+/// Default number of entries per page of the activity timeline
+const DEFAULT_ACTIVITY_PAGE_SIZE: i64 = 50;
+
+/// Maximum number of entries per page of the activity timeline
+const MAX_ACTIVITY_PAGE_SIZE: i64 = 200;
+
+/// Default number of authorization decisions returned by a query
+const DEFAULT_DECISION_LIMIT: i64 = 100;
+
+/// Maximum number of authorization decisions returned by a query
+const MAX_DECISION_LIMIT: i64 = 1000;
+
+/// Lapsed role assignments removed per batch of the expiry worker
+const EXPIRED_ASSIGNMENT_BATCH_SIZE: i64 = 100;
+
+/// Records an event in the audit log and streams it to the subscribers of its tenant
+///
+/// Failures to store the event are only logged since the audited change already happened.
+pub(crate) async fn record_audit_event(
+    repository: &UserRepository,
+    audit: &AuditStream,
+    event: AuditEvent,
+) {
+    if let Err(e) = repository.insert_audit_event(&event).await {
+        warn!("Failed to record audit event {}: {}", event.action, e);
+    }
+    audit.publish(event);
+}
+
+/// Recorder storing authorization decisions as security events in the audit log
+#[derive(Debug)]
+pub struct AuditDecisionRecorder {
+    repository: UserRepository,
+    audit: AuditStream,
+}
+
+impl AuditDecisionRecorder {
+    /// Creates a recorder storing decisions with the given repository and stream
+    pub fn new(repository: UserRepository, audit: AuditStream) -> Self {
+        Self { repository, audit }
+    }
+}
+
+#[async_trait]
+impl DecisionRecorder for AuditDecisionRecorder {
+    async fn record(&self, decision: AuthorizationDecision) {
+        let action = if decision.allowed {
+            AUTHORIZATION_ALLOWED
+        } else {
+            AUTHORIZATION_DENIED
+        };
+        let event = AuditEvent::new(
+            decision.tenant_id,
+            AuditCategory::Security,
+            action,
+            "permissions",
+            format!("{}:{}", decision.action, decision.resource),
+        )
+        .with_user(decision.user_id)
+        .with_detail("action", decision.action.to_string())
+        .with_detail("resource", decision.resource)
+        .with_detail("owner_id", decision.owner_id.map(|id| id.0))
+        .with_detail("request_id", decision.request_id);
+        record_audit_event(&self.repository, &self.audit, event).await;
+    }
 }
 
 impl IdentityModule {
     /// Creates a new IdentityModule instance
     pub fn new(repository: UserRepository) -> Self {
+        let rbac = Arc::new(RbacService::new());
+        let invalidation = CacheInvalidationBus::new();
+        invalidation.register(rbac.clone());
         Self {
+            role_repository: RoleRepository::new(repository.get_pool().clone()),
+            owner_repository: ResourceOwnerRepository::new(repository.get_pool().clone()),
             repository,
-            rbac: RbacService::new(),
+            rbac,
+            invalidation,
+            audit: AuditStream::new(),
+            catalog: Arc::new(PermissionCatalog::builtin()),
+            authorization_audit: None,
+            mailer: Arc::new(LogMailer),
+            email_verification_ttl: time::Duration::hours(24),
+            email_verification_url: EmailConfig::default().verification_url,
+        }
+    }
+
+    /// Uses the given mailer for the verification emails of added email addresses
+    pub fn with_mailer(mut self, mailer: Arc<dyn Mailer>) -> Self {
+        self.mailer = mailer;
+        self
+    }
+
+    /// Sets how long verification links of added email addresses are valid and where they point to
+    pub fn with_email_verification(mut self, config: &EmailConfig) -> Self {
+        self.email_verification_ttl = time::Duration::hours(config.verification_ttl_hours);
+        self.email_verification_url = config.verification_url.clone();
+        self
+    }
+
+    /// Uses the given bus to invalidate the permission cache, e.g. one shared with other instances
+    pub fn with_cache_invalidation(mut self, invalidation: CacheInvalidationBus) -> Self {
+        invalidation.register(self.rbac.clone());
+        self.invalidation = invalidation;
+        self
+    }
+
+    /// Decides permission checks with the given policy engine instead of the role permissions
+    pub fn with_policy_engine(mut self, engine: Arc<dyn PolicyEngine>) -> Self {
+        self.rebuild_rbac(engine);
+        self
+    }
+
+    /// Streams the audit events of this module to the given stream, e.g. one shared with the authentication service
+    pub fn with_audit_stream(mut self, audit: AuditStream) -> Self {
+        self.audit = audit;
+        if self.authorization_audit.is_some() {
+            self.rebuild_rbac(self.rbac.policy_engine());
         }
+        self
     }
 
-    /// Creates a new user
+    /// Records denied permission checks in the audit log, and allowed ones as well if `record_allowed` is set
+    pub fn with_authorization_audit(mut self, record_allowed: bool) -> Self {
+        self.authorization_audit = Some(record_allowed);
+        self.rebuild_rbac(self.rbac.policy_engine());
+        self
+    }
+
+    /// Replaces the RBAC service, keeping the audit of its decisions
+    fn rebuild_rbac(&mut self, engine: Arc<dyn PolicyEngine>) {
+        let mut rbac = RbacService::with_policy_engine(engine);
+        if let Some(record_allowed) = self.authorization_audit {
+            let recorder = AuditDecisionRecorder::new(self.repository.clone(), self.audit.clone());
+            rbac = rbac.with_decision_recorder(Arc::new(recorder), record_allowed);
+        }
+        let rbac = Arc::new(rbac);
+        self.invalidation.register(rbac.clone());
+        self.rbac = rbac;
+    }
+
+    /// Gets the stream of the audit events recorded by this module
+    pub fn audit_stream(&self) -> &AuditStream {
+        &self.audit
+    }
+
+    /// Validates the permissions of custom roles against the given catalog
+    pub fn with_permission_catalog(mut self, catalog: PermissionCatalog) -> Self {
+        self.catalog = Arc::new(catalog);
+        self
+    }
+
+    /// Gets the catalog of the permissions custom roles may contain
+    pub fn permission_catalog(&self) -> &PermissionCatalog {
+        &self.catalog
+    }
+
+    /// Gets the RBAC service whose permission cache this module invalidates
+    pub fn rbac(&self) -> Arc<RbacService> {
+        self.rbac.clone()
+    }
+
+    /// Creates a new user with a valid login address
     pub async fn create_user(&self, user: &User) -> Result<User> {
+        UserEmail::validate(&user.email)?;
         self.repository.create_user(user.clone()).await
     }
 
@@ -47,7 +254,19 @@ impl IdentityModule {
 
     /// Updates a user
     pub async fn update_user(&self, user: &User) -> Result<User> {
-        self.repository.update_user(user.clone()).await
+        let user = self.repository.update_user(user.clone()).await?;
+        self.invalidation
+            .publish(CacheInvalidation::User { user_id: user.id })
+            .await;
+        self.audit(AuditEvent::new(
+            user.tenant_id,
+            AuditCategory::Audit,
+            "user_updated",
+            "users",
+            user.id.0,
+        ))
+        .await;
+        Ok(user)
     }
 
     /// Deletes a user
@@ -58,7 +277,73 @@ impl IdentityModule {
         let tenant_id = TenantId(uuid::Uuid::parse_str(tenant_id).map_err(|e| {
             crate::shared::error::Error::InvalidInput(format!("Invalid UUID: {}", e))
         })?);
-        self.repository.delete_user(user_id, tenant_id).await
+        self.repository.delete_user(user_id, tenant_id).await?;
+        self.invalidation
+            .publish(CacheInvalidation::User { user_id })
+            .await;
+        self.audit(AuditEvent::new(
+            tenant_id,
+            AuditCategory::Audit,
+            "user_deleted",
+            "users",
+            user_id.0,
+        ))
+        .await;
+        Ok(())
+    }
+
+    /// Gets a user the actor administers, hiding users of other tenants
+    pub async fn get_managed_user(&self, actor: &User, user_id: UserId) -> Result<User> {
+        self.repository
+            .get_user_by_id(user_id)
+            .await?
+            .filter(|user| ensure_tenant_boundary(actor, user.tenant_id).is_ok())
+            .ok_or_else(|| Error::NotFound("User not found".to_string()))
+    }
+
+    /// Creates a user on behalf of an admin, confined to the admin's tenant
+    pub async fn create_managed_user(&self, actor: &User, user: &User) -> Result<User> {
+        ensure_tenant_boundary(actor, user.tenant_id)?;
+        if !has_permission(actor, PermissionAction::Create, "users") {
+            return Err(Error::Authorization(
+                "Missing permission to create users".to_string(),
+            ));
+        }
+        self.create_user(user).await
+    }
+
+    /// Updates a user on behalf of an admin; users cannot be moved to another tenant
+    pub async fn update_managed_user(&self, actor: &User, user: &User) -> Result<User> {
+        let existing = self.get_managed_user(actor, user.id).await?;
+        if existing.tenant_id != user.tenant_id {
+            return Err(Error::Validation(
+                "Users cannot be moved to another tenant".to_string(),
+            ));
+        }
+        if !self
+            .check_permission(actor, PermissionAction::Update, "users", Some(user.id))
+            .await?
+        {
+            return Err(Error::Authorization(
+                "Missing permission to update users".to_string(),
+            ));
+        }
+        self.update_user(user).await
+    }
+
+    /// Deletes a user on behalf of an admin
+    pub async fn delete_managed_user(&self, actor: &User, user_id: UserId) -> Result<()> {
+        let user = self.get_managed_user(actor, user_id).await?;
+        if !self
+            .check_permission(actor, PermissionAction::Delete, "users", Some(user_id))
+            .await?
+        {
+            return Err(Error::Authorization(
+                "Missing permission to delete users".to_string(),
+            ));
+        }
+        self.delete_user(&user.id.0.to_string(), &user.tenant_id.0.to_string())
+            .await
     }
 
     /// Lists all users
@@ -66,23 +351,853 @@ impl IdentityModule {
         self.repository.list_users().await
     }
 
-    /// Checks if a user has a specific permission
+    /// Lists a page of the tenant's users with login, session and MFA details for admins
+    pub async fn list_user_overview(
+        &self,
+        actor: &User,
+        auth: &AuthenticationService,
+        query: UserOverviewQuery,
+    ) -> Result<UserOverviewPage> {
+        if !has_permission(actor, PermissionAction::Read, "users") {
+            return Err(Error::Authorization(
+                "Missing permission to list users".to_string(),
+            ));
+        }
+        let page = query.page.unwrap_or(1).max(1);
+        let per_page = query
+            .per_page
+            .unwrap_or(DEFAULT_OVERVIEW_PAGE_SIZE)
+            .clamp(1, MAX_OVERVIEW_PAGE_SIZE);
+
+        let (mut users, total) = self
+            .repository
+            .list_user_overview(actor.tenant_id, (page - 1) * per_page, per_page)
+            .await?;
+        for user in &mut users {
+            user.active_sessions = auth
+                .count_active_sessions(actor.tenant_id, UserId(user.id))
+                .await?;
+        }
+
+        Ok(UserOverviewPage {
+            users,
+            page,
+            per_page,
+            total,
+        })
+    }
+
+    /// Lists a page of the security relevant activity of a user, newest first
+    ///
+    /// Users may view their own timeline with owner-scoped permissions to read users.
+    pub async fn list_user_activity(
+        &self,
+        actor: &User,
+        user_id: UserId,
+        query: ActivityQuery,
+    ) -> Result<ActivityPage> {
+        if !self
+            .check_permission(actor, PermissionAction::Read, "users", Some(user_id))
+            .await?
+        {
+            return Err(Error::Authorization(
+                "Missing permission to read the user's activity".to_string(),
+            ));
+        }
+        let user = self
+            .repository
+            .get_user_by_id(user_id)
+            .await?
+            .filter(|user| user.tenant_id == actor.tenant_id)
+            .ok_or_else(|| Error::NotFound("User not found".to_string()))?;
+
+        let page = query.page.unwrap_or(1).max(1);
+        let per_page = query
+            .per_page
+            .unwrap_or(DEFAULT_ACTIVITY_PAGE_SIZE)
+            .clamp(1, MAX_ACTIVITY_PAGE_SIZE);
+        let actions: Vec<String> = ACTIVITY_ACTIONS.iter().map(|a| a.to_string()).collect();
+
+        let (entries, total) = self
+            .repository
+            .list_user_activity(
+                user.tenant_id,
+                user.id,
+                &user.email,
+                &actions,
+                (page - 1) * per_page,
+                per_page,
+            )
+            .await?;
+        Ok(ActivityPage {
+            entries,
+            page,
+            per_page,
+            total,
+        })
+    }
+
+    /// Lists the authorization decisions recorded for the actor's tenant, newest first
+    pub async fn list_authorization_decisions(
+        &self,
+        actor: &User,
+        query: AuthorizationAuditQuery,
+    ) -> Result<Vec<AuditEvent>> {
+        if !has_permission(actor, PermissionAction::Read, "audit_log") {
+            return Err(Error::Authorization(
+                "Missing permission to read the audit log".to_string(),
+            ));
+        }
+        let actions: Vec<String> = match query.allowed {
+            Some(true) => vec![AUTHORIZATION_ALLOWED.to_string()],
+            Some(false) => vec![AUTHORIZATION_DENIED.to_string()],
+            None => vec![
+                AUTHORIZATION_DENIED.to_string(),
+                AUTHORIZATION_ALLOWED.to_string(),
+            ],
+        };
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_DECISION_LIMIT)
+            .clamp(1, MAX_DECISION_LIMIT);
+        self.repository
+            .list_security_events(
+                actor.tenant_id,
+                &actions,
+                query.user_id.map(UserId),
+                query.request_id.as_deref(),
+                limit,
+            )
+            .await
+    }
+
+    /// Lists all email addresses of a user
+    pub async fn list_emails(&self, user_id: UserId, tenant_id: TenantId) -> Result<Vec<UserEmail>> {
+        self.repository.list_user_emails(user_id, tenant_id).await
+    }
+
+    /// Adds an unverified email address to a user and mails it a verification link
+    pub async fn add_email(
+        &self,
+        user_id: UserId,
+        tenant_id: TenantId,
+        email: String,
+    ) -> Result<UserEmail> {
+        let email = email.trim().to_string();
+        UserEmail::validate(&email)?;
+        let email = self
+            .repository
+            .add_user_email(&UserEmail::new(user_id, tenant_id, email))
+            .await?;
+        self.send_email_verification(&email).await?;
+        Ok(email)
+    }
+
+    /// Mails a new verification link to an unverified email address of a user
+    ///
+    /// Links sent earlier stop working.
+    pub async fn resend_email_verification(
+        &self,
+        user_id: UserId,
+        tenant_id: TenantId,
+        email_id: Uuid,
+    ) -> Result<()> {
+        let email = self
+            .repository
+            .list_user_emails(user_id, tenant_id)
+            .await?
+            .into_iter()
+            .find(|e| e.id == email_id)
+            .ok_or_else(|| Error::NotFound("Email address not found".to_string()))?;
+        if email.verified {
+            return Err(Error::Validation(
+                "Email address is already verified".to_string(),
+            ));
+        }
+        self.send_email_verification(&email).await
+    }
+
+    /// Stores a new verification token of an email address and mails it the link
+    async fn send_email_verification(&self, email: &UserEmail) -> Result<()> {
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(48)
+            .map(char::from)
+            .collect();
+        let expires_at = OffsetDateTime::now_utc() + self.email_verification_ttl;
+        self.repository
+            .set_email_verification(
+                email.id,
+                email.user_id,
+                email.tenant_id,
+                &hash_token(&token),
+                expires_at,
+            )
+            .await?
+            .ok_or_else(|| Error::NotFound("Email address not found".to_string()))?;
+
+        self.mailer
+            .send(&EmailMessage {
+                to: email.email.clone(),
+                subject: "Verify your email address".to_string(),
+                body: format!(
+                    "Please confirm that {} belongs to your account:\n\n{}{}\n\nThe link expires at {}.",
+                    email.email, self.email_verification_url, token, expires_at
+                ),
+            })
+            .await
+    }
+
+    /// Verifies an email address of a user with the token mailed to it
+    pub async fn verify_email(
+        &self,
+        user_id: UserId,
+        tenant_id: TenantId,
+        token: &str,
+    ) -> Result<UserEmail> {
+        let email = self
+            .repository
+            .verify_email_token(&hash_token(token.trim()), user_id, tenant_id)
+            .await?
+            .ok_or_else(|| {
+                Error::Validation("Invalid or expired verification token".to_string())
+            })?;
+        self.audit_email(&email, "email_verified").await;
+        Ok(email)
+    }
+
+    /// Makes a verified email address the user's primary address
+    pub async fn set_primary_email(
+        &self,
+        user_id: UserId,
+        tenant_id: TenantId,
+        email_id: Uuid,
+    ) -> Result<()> {
+        let emails = self.repository.list_user_emails(user_id, tenant_id).await?;
+        let email = emails
+            .iter()
+            .find(|e| e.id == email_id)
+            .ok_or_else(|| Error::NotFound("Email address not found".to_string()))?;
+
+        if !email.verified {
+            return Err(Error::Validation(
+                "Only verified email addresses can become primary".to_string(),
+            ));
+        }
+
+        self.repository
+            .set_primary_email(email_id, user_id, tenant_id)
+            .await?;
+        self.audit_email(email, "primary_email_changed").await;
+        Ok(())
+    }
+
+    /// Records a change of the addresses a user can log in with; such changes are security relevant
+    async fn audit_email(&self, email: &UserEmail, action: &str) {
+        self.audit(
+            AuditEvent::new(
+                email.tenant_id,
+                AuditCategory::Security,
+                action,
+                "user_emails",
+                email.id,
+            )
+            .with_user(email.user_id)
+            .with_detail("email", &email.email),
+        )
+        .await;
+    }
+
+    /// Removes a non-primary email address from a user
+    pub async fn remove_email(
+        &self,
+        user_id: UserId,
+        tenant_id: TenantId,
+        email_id: Uuid,
+    ) -> Result<()> {
+        if !self
+            .repository
+            .remove_user_email(email_id, user_id, tenant_id)
+            .await?
+        {
+            return Err(Error::NotFound(
+                "Email address not found or is the primary address".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Finds a user by their login address or any verified alias, e.g. to map SSO aliases
+    pub async fn find_user_by_email(&self, email: &str, tenant_id: TenantId) -> Result<Option<User>> {
+        self.repository
+            .get_user_by_verified_email(email, tenant_id)
+            .await
+    }
+
+    /// Checks if a user has a specific permission, optionally on a resource owned by a user
     pub async fn check_permission(
         &self,
         user: &User,
         action: PermissionAction,
         resource: &str,
+        owner_id: Option<UserId>,
     ) -> Result<bool> {
-        self.rbac.check_permission(user, action, resource).await
+        self.rbac
+            .check_permission(user, action, resource, owner_id)
+            .await
+    }
+
+    /// Simulates a permission check for a user of the actor's tenant without recording it
+    ///
+    /// The outcome is explained with the matching permissions of the user's roles and
+    /// their inheritance chains, so admins can debug role configurations.
+    pub async fn simulate_permission(
+        &self,
+        actor: &User,
+        request: PolicySimulationRequest,
+    ) -> Result<PolicySimulation> {
+        if !has_permission(actor, PermissionAction::Update, "users") {
+            return Err(Error::Authorization(
+                "Missing permission to simulate permission checks".to_string(),
+            ));
+        }
+        let mut user = self
+            .get_tenant_user(actor.tenant_id, UserId(request.user_id))
+            .await?;
+        user.token_scopes = request
+            .scope
+            .as_deref()
+            .map(TokenScope::parse_list)
+            .transpose()?;
+        let owner_id = request.owner_id.map(UserId);
+
+        let tenant_roles = self.role_repository.list_roles(actor.tenant_id).await?;
+        let matches = matching_permissions(
+            &user.roles,
+            &tenant_roles,
+            request.action,
+            &request.resource,
+        );
+        let allowed = self
+            .rbac
+            .dry_run(&user, request.action, &request.resource, owner_id)
+            .await?;
+        let mut reason =
+            simulation_reason(&user, &matches, request.action, &request.resource, owner_id);
+        let granted = matches!(
+            reason,
+            SimulationReason::Granted | SimulationReason::GrantedAsOwner
+        );
+        if allowed != granted {
+            reason = SimulationReason::PolicyEngine;
+        }
+
+        Ok(PolicySimulation {
+            allowed,
+            reason,
+            matches,
+        })
+    }
+
+    /// Checks if a user has a permission on a specific resource, honoring owner-scoped grants
+    pub async fn check_resource_permission(
+        &self,
+        user: &User,
+        action: PermissionAction,
+        resource_type: &str,
+        resource_id: &str,
+    ) -> Result<bool> {
+        let owners = self
+            .owner_repository
+            .list_owners(user.tenant_id, resource_type, resource_id)
+            .await?;
+        self.rbac
+            .check_resource_permission(user, action, resource_type, &owners)
+            .await
+    }
+
+    /// Lists the owners of a resource
+    pub async fn list_resource_owners(
+        &self,
+        actor: &User,
+        resource_type: &str,
+        resource_id: &str,
+    ) -> Result<Vec<ResourceOwner>> {
+        let owners = self
+            .owner_repository
+            .list_owners(actor.tenant_id, resource_type, resource_id)
+            .await?;
+        if !self
+            .rbac
+            .check_resource_permission(actor, PermissionAction::Read, resource_type, &owners)
+            .await?
+        {
+            return Err(Error::Authorization(format!(
+                "Missing permission to read {}",
+                resource_type
+            )));
+        }
+        Ok(owners)
+    }
+
+    /// Registers an owner of a resource
+    ///
+    /// Resources without owners can be claimed by anyone allowed to create them;
+    /// afterwards changing ownership requires the update permission on the resource.
+    pub async fn add_resource_owner(
+        &self,
+        actor: &User,
+        owner: ResourceOwner,
+    ) -> Result<Vec<ResourceOwner>> {
+        self.ensure_owner_manageable(actor, &owner).await?;
+        match owner.owner_type {
+            OwnerType::User => {
+                self.get_tenant_user(actor.tenant_id, UserId(owner.owner_id))
+                    .await?;
+            },
+            OwnerType::Group => {
+                self.get_role(actor.tenant_id, owner.owner_id).await?;
+            },
+        }
+        self.owner_repository.add_owner(&owner).await?;
+        self.owner_repository
+            .list_owners(actor.tenant_id, &owner.resource_type, &owner.resource_id)
+            .await
+    }
+
+    /// Removes an owner of a resource
+    pub async fn remove_resource_owner(&self, actor: &User, owner: ResourceOwner) -> Result<()> {
+        self.ensure_owner_manageable(actor, &owner).await?;
+        if !self.owner_repository.remove_owner(&owner).await? {
+            return Err(Error::NotFound("Resource owner not found".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Lists the roles of a tenant
+    pub async fn list_roles(&self, tenant_id: TenantId) -> Result<Vec<Role>> {
+        self.role_repository.list_roles(tenant_id).await
+    }
+
+    /// Gets a role of a tenant
+    pub async fn get_role(&self, tenant_id: TenantId, role_id: Uuid) -> Result<Role> {
+        self.role_repository
+            .get_role(role_id, tenant_id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Role not found".to_string()))
+    }
+
+    /// Creates a custom role in the actor's tenant
+    pub async fn create_role(&self, actor: &User, request: RoleRequest) -> Result<Role> {
+        Self::ensure_role_manageable(actor, PermissionAction::Create)?;
+        let role = Role::from(request);
+        Self::validate_role(&role)?;
+        Self::ensure_grantable(actor, &role.permissions)?;
+        self.catalog.validate(&role.permissions)?;
+        self.ensure_unique_role_name(actor.tenant_id, &role).await?;
+        self.ensure_valid_parents(actor, &role).await?;
+        let role = self
+            .role_repository
+            .create_role(actor.tenant_id, &role)
+            .await?;
+        self.audit_role(actor, "role_created", &role).await;
+        Ok(role)
+    }
+
+    /// Replaces the name, permissions and parents of a custom role
+    pub async fn update_role(
+        &self,
+        actor: &User,
+        role_id: Uuid,
+        request: RoleRequest,
+    ) -> Result<Role> {
+        Self::ensure_role_manageable(actor, PermissionAction::Update)?;
+        let existing = self.get_custom_role(actor, role_id).await?;
+
+        let mut role = Role::from(request);
+        role.id = existing.id;
+        Self::validate_role(&role)?;
+        Self::ensure_grantable(actor, &role.permissions)?;
+        self.catalog.validate(&role.permissions)?;
+        self.ensure_unique_role_name(actor.tenant_id, &role).await?;
+        self.ensure_valid_parents(actor, &role).await?;
+
+        let role = self
+            .role_repository
+            .update_role(actor.tenant_id, &role)
+            .await?
+            .ok_or_else(|| Error::NotFound("Role not found".to_string()))?;
+        self.invalidation
+            .publish(CacheInvalidation::Permissions)
+            .await;
+        self.audit_role(actor, "role_updated", &role).await;
+        Ok(role)
+    }
+
+    /// Deletes a custom role
+    pub async fn delete_role(&self, actor: &User, role_id: Uuid) -> Result<()> {
+        Self::ensure_role_manageable(actor, PermissionAction::Delete)?;
+        let role = self.get_custom_role(actor, role_id).await?;
+        if !self
+            .role_repository
+            .delete_role(role_id, actor.tenant_id)
+            .await?
+        {
+            return Err(Error::NotFound("Role not found".to_string()));
+        }
+        self.invalidation
+            .publish(CacheInvalidation::Permissions)
+            .await;
+        self.audit_role(actor, "role_deleted", &role).await;
+        Ok(())
+    }
+
+    /// Assigns a role of the actor's tenant to a user
+    ///
+    /// A time-bound assignment of the role becomes permanent.
+    pub async fn assign_role(&self, actor: &User, user_id: UserId, role_id: Uuid) -> Result<User> {
+        self.schedule_role(actor, user_id, role_id, &RoleAssignmentRequest::default())
+            .await
+    }
+
+    /// Assigns a role of the actor's tenant to a user for a validity window
+    ///
+    /// The role takes effect at `valid_from` and lapses at `valid_until`, replacing
+    /// the window of an existing assignment.
+    pub async fn schedule_role(
+        &self,
+        actor: &User,
+        user_id: UserId,
+        role_id: Uuid,
+        request: &RoleAssignmentRequest,
+    ) -> Result<User> {
+        let now = OffsetDateTime::now_utc();
+        let valid_from = request.valid_from.unwrap_or(now);
+        if request
+            .valid_until
+            .is_some_and(|valid_until| valid_until <= valid_from.max(now))
+        {
+            return Err(Error::Validation(
+                "Role assignment must end in the future and after it starts".to_string(),
+            ));
+        }
+
+        let role = self.get_assignable_role(actor, role_id).await?;
+        let user = self.get_tenant_user(actor.tenant_id, user_id).await?;
+
+        if !self
+            .role_repository
+            .assign_role(
+                user.id,
+                role.id,
+                actor.tenant_id,
+                valid_from,
+                request.valid_until,
+            )
+            .await?
+        {
+            return Ok(user);
+        }
+        self.invalidation
+            .publish(CacheInvalidation::User { user_id: user.id })
+            .await;
+        self.audit(
+            AuditEvent::new(
+                actor.tenant_id,
+                AuditCategory::Security,
+                "role_assigned",
+                "users",
+                user.id.0,
+            )
+            .with_user(actor.id)
+            .with_detail("role_id", role.id)
+            .with_detail("role", role.name)
+            .with_detail("valid_from", request.valid_from.map(|t| t.unix_timestamp()))
+            .with_detail(
+                "valid_until",
+                request.valid_until.map(|t| t.unix_timestamp()),
+            ),
+        )
+        .await;
+        self.get_tenant_user(actor.tenant_id, user_id).await
+    }
+
+    /// Removes lapsed role assignments and refreshes users whose scheduled roles took effect
+    ///
+    /// Assignments outside their window are already ignored by permission checks;
+    /// this drops them from storage and invalidates cached users so the change is
+    /// visible immediately. Returns the number of removed assignments.
+    pub async fn expire_role_assignments(&self, activated_since: OffsetDateTime) -> Result<usize> {
+        let mut removed = 0;
+        loop {
+            let expired = self
+                .role_repository
+                .delete_expired_assignments(EXPIRED_ASSIGNMENT_BATCH_SIZE)
+                .await?;
+            for (tenant_id, user_id, role_id) in &expired {
+                self.invalidation
+                    .publish(CacheInvalidation::User { user_id: *user_id })
+                    .await;
+                self.audit(
+                    AuditEvent::new(
+                        *tenant_id,
+                        AuditCategory::Security,
+                        "role_expired",
+                        "users",
+                        user_id.0,
+                    )
+                    .with_detail("role_id", role_id),
+                )
+                .await;
+            }
+            removed += expired.len();
+            if expired.len() < EXPIRED_ASSIGNMENT_BATCH_SIZE as usize {
+                break;
+            }
+        }
+
+        for user_id in self
+            .role_repository
+            .list_activated_assignments(activated_since)
+            .await?
+        {
+            self.invalidation
+                .publish(CacheInvalidation::User { user_id })
+                .await;
+        }
+        Ok(removed)
+    }
+
+    /// Periodically expires and activates time-bound role assignments in the background
+    pub fn spawn_role_expiry_worker(
+        self: Arc<Self>,
+        interval: std::time::Duration,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            let mut last_run = OffsetDateTime::now_utc();
+            loop {
+                ticker.tick().await;
+                let now = OffsetDateTime::now_utc();
+                match self.expire_role_assignments(last_run).await {
+                    Ok(removed) => {
+                        last_run = now;
+                        if removed > 0 {
+                            info!("Removed {} expired role assignments", removed);
+                        }
+                    },
+                    Err(e) => error!("Failed to expire role assignments: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Removes a role from a user
+    pub async fn revoke_role(&self, actor: &User, user_id: UserId, role_id: Uuid) -> Result<User> {
+        let role = self.get_assignable_role(actor, role_id).await?;
+        let user = self.get_tenant_user(actor.tenant_id, user_id).await?;
+
+        if !self
+            .role_repository
+            .revoke_role(user.id, role.id, actor.tenant_id)
+            .await?
+        {
+            return Err(Error::NotFound("Role is not assigned to user".to_string()));
+        }
+        self.invalidation
+            .publish(CacheInvalidation::User { user_id: user.id })
+            .await;
+        self.audit_role_assignment(actor, "role_revoked", &user, &role)
+            .await;
+        self.get_tenant_user(actor.tenant_id, user_id).await
+    }
+
+    /// Records an audit event of this module
+    async fn audit(&self, event: AuditEvent) {
+        record_audit_event(&self.repository, &self.audit, event).await;
+    }
+
+    /// Records a change of a role definition
+    async fn audit_role(&self, actor: &User, action: &str, role: &Role) {
+        self.audit(
+            AuditEvent::new(
+                actor.tenant_id,
+                AuditCategory::Audit,
+                action,
+                "roles",
+                role.id,
+            )
+            .with_user(actor.id)
+            .with_detail("name", &role.name),
+        )
+        .await;
+    }
+
+    /// Records a role being assigned to or revoked from a user; role changes are security relevant
+    async fn audit_role_assignment(&self, actor: &User, action: &str, user: &User, role: &Role) {
+        self.audit(
+            AuditEvent::new(
+                actor.tenant_id,
+                AuditCategory::Security,
+                action,
+                "users",
+                user.id.0,
+            )
+            .with_user(actor.id)
+            .with_detail("role_id", role.id)
+            .with_detail("role", &role.name),
+        )
+        .await;
+    }
+
+    /// Loads a role the actor is allowed to assign or revoke
+    async fn get_assignable_role(&self, actor: &User, role_id: Uuid) -> Result<Role> {
+        if !has_permission(actor, PermissionAction::Update, "users") {
+            return Err(Error::Authorization(
+                "Missing permission to manage user roles".to_string(),
+            ));
+        }
+        let role = self.get_role(actor.tenant_id, role_id).await?;
+        // Holding a role grants the permissions it inherits as well
+        let permissions: Vec<Permission> = role.effective_permissions().cloned().collect();
+        Self::ensure_grantable(actor, &permissions)?;
+        Ok(role)
+    }
+
+    /// Ensures the actor may change the owners of a resource
+    async fn ensure_owner_manageable(&self, actor: &User, owner: &ResourceOwner) -> Result<()> {
+        if owner.tenant_id != actor.tenant_id {
+            return Err(Error::Authorization("Access to tenant denied".to_string()));
+        }
+        if owner.resource_type.trim().is_empty() || owner.resource_id.trim().is_empty() {
+            return Err(Error::Validation(
+                "Resource type and ID must not be empty".to_string(),
+            ));
+        }
+
+        let owners = self
+            .owner_repository
+            .list_owners(actor.tenant_id, &owner.resource_type, &owner.resource_id)
+            .await?;
+        let allowed = if owners.is_empty() {
+            self.rbac
+                .check_permission(actor, PermissionAction::Create, &owner.resource_type, None)
+                .await?
+        } else {
+            self.rbac
+                .check_resource_permission(
+                    actor,
+                    PermissionAction::Update,
+                    &owner.resource_type,
+                    &owners,
+                )
+                .await?
+        };
+        if !allowed {
+            return Err(Error::Authorization(format!(
+                "Missing permission to manage owners of {}",
+                owner.resource_type
+            )));
+        }
+        Ok(())
+    }
+
+    /// Loads a user of a tenant
+    async fn get_tenant_user(&self, tenant_id: TenantId, user_id: UserId) -> Result<User> {
+        self.repository
+            .get_user_by_id(user_id)
+            .await?
+            .filter(|user| user.tenant_id == tenant_id)
+            .ok_or_else(|| Error::NotFound("User not found".to_string()))
+    }
+
+    /// Loads a custom role the actor is allowed to manage
+    async fn get_custom_role(&self, actor: &User, role_id: Uuid) -> Result<Role> {
+        let role = self.get_role(actor.tenant_id, role_id).await?;
+        if role.role_type != RoleType::Custom {
+            return Err(Error::Validation(
+                "Built-in roles cannot be modified".to_string(),
+            ));
+        }
+        // Changing a role affects everyone holding it, so the actor must hold all of it
+        let permissions: Vec<Permission> = role.effective_permissions().cloned().collect();
+        Self::ensure_grantable(actor, &permissions)?;
+        Ok(role)
+    }
+
+    /// Ensures the actor holds the permission to create, update or delete roles
+    fn ensure_role_manageable(actor: &User, action: PermissionAction) -> Result<()> {
+        if !has_permission(actor, action, "roles") {
+            return Err(Error::Authorization(
+                "Missing permission to manage roles".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Validates a role definition
+    fn validate_role(role: &Role) -> Result<()> {
+        if role.name.trim().is_empty() {
+            return Err(Error::Validation("Role name must not be empty".to_string()));
+        }
+        if role
+            .permissions
+            .iter()
+            .any(|p| p.resource.trim().is_empty())
+        {
+            return Err(Error::Validation(
+                "Permission resource must not be empty".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Ensures the role name is not used by another role of the tenant
+    async fn ensure_unique_role_name(&self, tenant_id: TenantId, role: &Role) -> Result<()> {
+        let roles = self.role_repository.list_roles(tenant_id).await?;
+        if roles.iter().any(|r| r.name == role.name && r.id != role.id) {
+            return Err(Error::Validation(format!(
+                "Role {} already exists",
+                role.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Ensures the parents of a role exist, keep the hierarchy acyclic and are grantable by the actor
+    async fn ensure_valid_parents(&self, actor: &User, role: &Role) -> Result<()> {
+        if role.parent_ids.is_empty() {
+            return Ok(());
+        }
+        let roles = self.role_repository.list_roles(actor.tenant_id).await?;
+        RoleHierarchy::from_roles(&roles).check_parents(role.id, &role.parent_ids)?;
+
+        // Inheriting from a role grants all of its permissions
+        for parent in roles.iter().filter(|r| role.parent_ids.contains(&r.id)) {
+            let permissions: Vec<Permission> = parent.effective_permissions().cloned().collect();
+            Self::ensure_grantable(actor, &permissions)?;
+        }
+        Ok(())
+    }
+
+    /// Ensures the actor holds every permission they are about to grant
+    fn ensure_grantable(actor: &User, permissions: &[Permission]) -> Result<()> {
+        if let Some(permission) = permissions
+            .iter()
+            .find(|p| !can_grant(&actor.roles, p) || !actor.scopes_allow(p.action, &p.resource))
+        {
+            return Err(Error::Authorization(format!(
+                "Cannot grant permission {} on {} without holding it",
+                permission.action, permission.resource
+            )));
+        }
+        Ok(())
     }
 }
 
 impl Default for IdentityModule {
     fn default() -> Self {
-        Self {
-            repository: UserRepository::default(),
-            rbac: RbacService::new(),
-        }
+        Self::new(UserRepository::default())
     }
 }
 
@@ -90,10 +1205,13 @@ impl Default for IdentityModule {
 mod tests {
     use super::*;
     use crate::{
-        core::database::tests::create_test_db,
-        modules::identity::models::{Permission, Role, RoleType},
+        core::database::{tests::create_test_db, Database},
+        modules::identity::models::{
+            Permission, PermissionEffect, PermissionRequest, PermissionScope, Role, RoleType,
+        },
+        modules::identity::rbac::{create_admin_role, create_user_role},
         modules::tenant::models::Tenant,
-        shared::types::{TenantId, UserId},
+        shared::types::UserId,
     };
     use std::time::Duration;
     use time::OffsetDateTime;
@@ -150,6 +1268,7 @@ mod tests {
             updated_at: OffsetDateTime::now_utc(),
             mfa_enabled: false,
             mfa_secret: None,
+            token_scopes: None,
         };
 
         let mut retries = 3;
@@ -171,7 +1290,7 @@ mod tests {
         let mut retries = 3;
         let has_permission = loop {
             match module
-                .check_permission(&created, PermissionAction::Create, "users")
+                .check_permission(&created, PermissionAction::Create, "users", None)
                 .await
             {
                 Ok(p) => break p,
@@ -189,7 +1308,7 @@ mod tests {
         let mut retries = 3;
         let has_permission = loop {
             match module
-                .check_permission(&created, PermissionAction::Delete, "users")
+                .check_permission(&created, PermissionAction::Delete, "users", None)
                 .await
             {
                 Ok(p) => break p,
@@ -204,4 +1323,691 @@ mod tests {
         };
         assert!(!has_permission);
     }
+
+    /// Mailer keeping the emails it was asked to send
+    #[derive(Debug, Default)]
+    struct RecordingMailer {
+        messages: std::sync::Mutex<Vec<EmailMessage>>,
+    }
+
+    #[async_trait]
+    impl Mailer for RecordingMailer {
+        async fn send(&self, message: &EmailMessage) -> Result<()> {
+            self.messages.lock().unwrap().push(message.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_email_verification() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let mailer = Arc::new(RecordingMailer::default());
+        let module =
+            IdentityModule::new(UserRepository::new(db.get_pool())).with_mailer(mailer.clone());
+        let tenant = setup_test_tenant(&db).await.unwrap();
+        let user = module
+            .create_user(&User::new(
+                tenant.id,
+                "jane@example.com".to_string(),
+                "hash".to_string(),
+            ))
+            .await
+            .unwrap();
+
+        // The login address is the primary address, unverified until its owner follows a link
+        let emails = module.list_emails(user.id, tenant.id).await.unwrap();
+        assert_eq!(emails.len(), 1);
+        assert!(emails[0].is_primary && !emails[0].verified);
+
+        // Added addresses are validated like login addresses
+        let result = module
+            .add_email(user.id, tenant.id, "jane@localhost".to_string())
+            .await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        // Added addresses are mailed a verification link
+        let alias = module
+            .add_email(user.id, tenant.id, " jane@corp.example.com ".to_string())
+            .await
+            .unwrap();
+        assert_eq!(alias.email, "jane@corp.example.com");
+        assert!(!alias.verified);
+        let result = module.set_primary_email(user.id, tenant.id, alias.id).await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        // Resending replaces the earlier link
+        module
+            .resend_email_verification(user.id, tenant.id, alias.id)
+            .await
+            .unwrap();
+        let tokens: Vec<String> = mailer
+            .messages
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|message| {
+                assert_eq!(message.to, "jane@corp.example.com");
+                let link = message.body.lines().nth(2).unwrap();
+                link.rsplit("token=").next().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(tokens.len(), 2);
+        let result = module.verify_email(user.id, tenant.id, &tokens[0]).await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        // Tokens only verify addresses of the user they were issued to
+        let other = module
+            .create_user(&User::new(
+                tenant.id,
+                "john@example.com".to_string(),
+                "hash".to_string(),
+            ))
+            .await
+            .unwrap();
+        let result = module.verify_email(other.id, tenant.id, &tokens[1]).await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        let verified = module
+            .verify_email(user.id, tenant.id, &tokens[1])
+            .await
+            .unwrap();
+        assert!(verified.verified);
+        let result = module
+            .resend_email_verification(user.id, tenant.id, alias.id)
+            .await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        module
+            .set_primary_email(user.id, tenant.id, alias.id)
+            .await
+            .unwrap();
+        let user = module
+            .get_user(&user.id.0.to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.email, "jane@corp.example.com");
+    }
+
+    #[tokio::test]
+    async fn test_custom_roles() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let module = IdentityModule::new(UserRepository::new(db.get_pool()));
+        let tenant = setup_test_tenant(&db).await.unwrap();
+
+        let mut admin = User::new(
+            tenant.id,
+            "admin@example.com".to_string(),
+            "hash".to_string(),
+        );
+        admin.roles.push(create_admin_role());
+        let admin = module.create_user(&admin).await.unwrap();
+
+        // Roles may only contain permissions the actor holds
+        let request = RoleRequest {
+            name: "Support".to_string(),
+            permissions: vec![PermissionRequest {
+                name: "Read User".to_string(),
+                action: PermissionAction::Read,
+                resource: "users".to_string(),
+                scope: PermissionScope::All,
+                effect: PermissionEffect::Allow,
+            }],
+            parent_ids: Vec::new(),
+        };
+        let role = module.create_role(&admin, request).await.unwrap();
+        assert_eq!(role.role_type, RoleType::Custom);
+        assert_eq!(role.permissions.len(), 1);
+
+        // Managing roles requires permissions on roles, even for grantable permissions
+        let mut member = User::new(
+            tenant.id,
+            "member@example.com".to_string(),
+            "hash".to_string(),
+        );
+        member.roles.push(create_user_role());
+        let member = module.create_user(&member).await.unwrap();
+        let request = RoleRequest {
+            name: "Viewer".to_string(),
+            permissions: vec![PermissionRequest {
+                name: "Read User".to_string(),
+                action: PermissionAction::Read,
+                resource: "users".to_string(),
+                scope: PermissionScope::All,
+                effect: PermissionEffect::Allow,
+            }],
+            parent_ids: Vec::new(),
+        };
+        let result = module.create_role(&member, request).await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+        let result = module.delete_role(&member, role.id).await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+
+        let request = RoleRequest {
+            name: "Billing".to_string(),
+            permissions: vec![PermissionRequest {
+                name: "Execute Refund".to_string(),
+                action: PermissionAction::Execute,
+                resource: "invoices".to_string(),
+                scope: PermissionScope::All,
+                effect: PermissionEffect::Allow,
+            }],
+            parent_ids: Vec::new(),
+        };
+        let result = module.create_role(&admin, request).await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+
+        // Update replaces the permission set
+        let request = RoleRequest {
+            name: "Support".to_string(),
+            permissions: vec![PermissionRequest {
+                name: "Update User".to_string(),
+                action: PermissionAction::Update,
+                resource: "users".to_string(),
+                scope: PermissionScope::All,
+                effect: PermissionEffect::Allow,
+            }],
+            parent_ids: Vec::new(),
+        };
+        let updated = module.update_role(&admin, role.id, request).await.unwrap();
+        assert_eq!(updated.permissions.len(), 1);
+        assert_eq!(updated.permissions[0].action, PermissionAction::Update);
+
+        // Built-in roles are not managed through the custom role API
+        let builtin = admin.roles[0].id;
+        let result = module.delete_role(&admin, builtin).await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        module.delete_role(&admin, role.id).await.unwrap();
+        let roles = module.list_roles(tenant.id).await.unwrap();
+        assert!(roles.iter().all(|r| r.id != role.id));
+    }
+
+    #[tokio::test]
+    async fn test_role_inheritance() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let module = IdentityModule::new(UserRepository::new(db.get_pool()));
+        let tenant = setup_test_tenant(&db).await.unwrap();
+
+        let mut admin = User::new(
+            tenant.id,
+            "admin@example.com".to_string(),
+            "hash".to_string(),
+        );
+        admin.roles.push(create_admin_role());
+        let admin = module.create_user(&admin).await.unwrap();
+
+        let request = |name: &str, action: PermissionAction, parent_ids: Vec<Uuid>| RoleRequest {
+            name: name.to_string(),
+            permissions: vec![PermissionRequest {
+                name: format!("{} User", action),
+                action,
+                resource: "users".to_string(),
+                scope: PermissionScope::All,
+                effect: PermissionEffect::Allow,
+            }],
+            parent_ids,
+        };
+        let support = module
+            .create_role(&admin, request("Support", PermissionAction::Read, vec![]))
+            .await
+            .unwrap();
+        let lead = module
+            .create_role(
+                &admin,
+                request("Support Lead", PermissionAction::Update, vec![support.id]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(lead.parent_ids, vec![support.id]);
+        assert_eq!(lead.inherited_permissions.len(), 1);
+
+        // Members of the child role hold the parent's permissions
+        let member = module
+            .create_user(&User::new(
+                tenant.id,
+                "lead@example.com".to_string(),
+                "hash".to_string(),
+            ))
+            .await
+            .unwrap();
+        let member = module
+            .assign_role(&admin, member.id, lead.id)
+            .await
+            .unwrap();
+        assert!(has_permission(&member, PermissionAction::Read, "users"));
+
+        // Assigning a role requires holding the permissions it inherits
+        let manager_role = module
+            .create_role(&admin, request("Manager", PermissionAction::Update, vec![]))
+            .await
+            .unwrap();
+        let manager = module
+            .create_user(&User::new(
+                tenant.id,
+                "manager@example.com".to_string(),
+                "hash".to_string(),
+            ))
+            .await
+            .unwrap();
+        let manager = module
+            .assign_role(&admin, manager.id, manager_role.id)
+            .await
+            .unwrap();
+        let result = module.assign_role(&manager, member.id, lead.id).await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+
+        // Cycles are rejected
+        let result = module
+            .update_role(
+                &admin,
+                support.id,
+                request("Support", PermissionAction::Read, vec![lead.id]),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_role_assignment() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let module = IdentityModule::new(UserRepository::new(db.get_pool()));
+        let tenant = setup_test_tenant(&db).await.unwrap();
+
+        let mut admin = User::new(
+            tenant.id,
+            "admin@example.com".to_string(),
+            "hash".to_string(),
+        );
+        admin.roles.push(create_admin_role());
+        let admin = module.create_user(&admin).await.unwrap();
+        let member = module
+            .create_user(&User::new(
+                tenant.id,
+                "member@example.com".to_string(),
+                "hash".to_string(),
+            ))
+            .await
+            .unwrap();
+
+        let role = module
+            .create_role(
+                &admin,
+                RoleRequest {
+                    name: "Reviewer".to_string(),
+                    permissions: vec![PermissionRequest {
+                        name: "Read User".to_string(),
+                        action: PermissionAction::Read,
+                        resource: "users".to_string(),
+                        scope: PermissionScope::All,
+                        effect: PermissionEffect::Allow,
+                    }],
+                    parent_ids: Vec::new(),
+                },
+            )
+            .await
+            .unwrap();
+
+        // Cache the missing permission before the assignment
+        assert!(!module
+            .check_permission(&member, PermissionAction::Read, "users", None)
+            .await
+            .unwrap());
+
+        let member = module.assign_role(&admin, member.id, role.id).await.unwrap();
+        assert_eq!(member.roles.len(), 1);
+        assert!(module
+            .check_permission(&member, PermissionAction::Read, "users", None)
+            .await
+            .unwrap());
+
+        // Members without user management permissions cannot assign roles
+        let result = module.assign_role(&member, admin.id, role.id).await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+
+        let member = module.revoke_role(&admin, member.id, role.id).await.unwrap();
+        assert!(member.roles.is_empty());
+        let result = module.revoke_role(&admin, member.id, role.id).await;
+        assert!(matches!(result, Err(Error::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_time_bound_role_assignment() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let module = IdentityModule::new(UserRepository::new(db.get_pool()));
+        let tenant = setup_test_tenant(&db).await.unwrap();
+
+        let mut admin = User::new(
+            tenant.id,
+            "admin@example.com".to_string(),
+            "hash".to_string(),
+        );
+        admin.roles.push(create_admin_role());
+        let admin = module.create_user(&admin).await.unwrap();
+        let member = module
+            .create_user(&User::new(
+                tenant.id,
+                "oncall@example.com".to_string(),
+                "hash".to_string(),
+            ))
+            .await
+            .unwrap();
+        let role = module
+            .create_role(
+                &admin,
+                RoleRequest {
+                    name: "On-call Admin".to_string(),
+                    permissions: vec![PermissionRequest {
+                        name: "Update User".to_string(),
+                        action: PermissionAction::Update,
+                        resource: "users".to_string(),
+                        scope: PermissionScope::All,
+                        effect: PermissionEffect::Allow,
+                    }],
+                    parent_ids: Vec::new(),
+                },
+            )
+            .await
+            .unwrap();
+
+        let now = OffsetDateTime::now_utc();
+        let window =
+            |from: Option<OffsetDateTime>, until: Option<OffsetDateTime>| RoleAssignmentRequest {
+                valid_from: from,
+                valid_until: until,
+            };
+
+        // Windows ending in the past or before they start are rejected
+        let result = module
+            .schedule_role(
+                &admin,
+                member.id,
+                role.id,
+                &window(None, Some(now - time::Duration::hours(1))),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        // Scheduled roles only take effect at their start
+        let member = module
+            .schedule_role(
+                &admin,
+                member.id,
+                role.id,
+                &window(Some(now + time::Duration::days(1)), None),
+            )
+            .await
+            .unwrap();
+        assert!(member.roles.is_empty());
+
+        let member = module
+            .schedule_role(
+                &admin,
+                member.id,
+                role.id,
+                &window(None, Some(now + time::Duration::days(2))),
+            )
+            .await
+            .unwrap();
+        assert_eq!(member.roles.len(), 1);
+
+        // Lapsed assignments are ignored and removed by the expiry worker
+        sqlx::query(
+            "UPDATE user_roles SET valid_from = NOW() - INTERVAL '2 days', \
+             valid_until = NOW() - INTERVAL '1 day' WHERE user_id = $1",
+        )
+        .bind(member.id.0)
+        .execute(&db.get_pool())
+        .await
+        .unwrap();
+        let member = module
+            .get_user(&member.id.0.to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(member.roles.is_empty());
+        assert_eq!(module.expire_role_assignments(now).await.unwrap(), 1);
+        assert_eq!(module.expire_role_assignments(now).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_tenant_boundaries() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let module = IdentityModule::new(UserRepository::new(db.get_pool()));
+        let tenant = setup_test_tenant(&db).await.unwrap();
+        let other_tenant = setup_test_tenant(&db).await.unwrap();
+
+        let mut admin = User::new(
+            tenant.id,
+            "admin@example.com".to_string(),
+            "hash".to_string(),
+        );
+        admin.roles.push(create_admin_role());
+        let admin = module.create_user(&admin).await.unwrap();
+
+        // Admins cannot create users in other tenants
+        let outsider = User::new(
+            other_tenant.id,
+            "outsider@example.com".to_string(),
+            "hash".to_string(),
+        );
+        let result = module.create_managed_user(&admin, &outsider).await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+
+        // Users of other tenants are invisible to them
+        let outsider = module.create_user(&outsider).await.unwrap();
+        let result = module.get_managed_user(&admin, outsider.id).await;
+        assert!(matches!(result, Err(Error::NotFound(_))));
+        let result = module.delete_managed_user(&admin, outsider.id).await;
+        assert!(matches!(result, Err(Error::NotFound(_))));
+
+        // Users of their own tenant cannot be moved to another one
+        let member = module
+            .create_managed_user(
+                &admin,
+                &User::new(
+                    tenant.id,
+                    "member@example.com".to_string(),
+                    "hash".to_string(),
+                ),
+            )
+            .await
+            .unwrap();
+        let mut moved = member.clone();
+        moved.tenant_id = other_tenant.id;
+        let result = module.update_managed_user(&admin, &moved).await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        module.delete_managed_user(&admin, member.id).await.unwrap();
+        assert!(module
+            .get_user(&member.id.0.to_string())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resource_owners() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let module = IdentityModule::new(UserRepository::new(db.get_pool()));
+        let tenant = setup_test_tenant(&db).await.unwrap();
+
+        let mut author_role = Role::new(RoleType::Custom, "Author".to_string());
+        author_role.permissions = vec![
+            Permission::new(
+                "Create Posts".to_string(),
+                PermissionAction::Create,
+                "posts".to_string(),
+            ),
+            Permission::new(
+                "Update Own Posts".to_string(),
+                PermissionAction::Update,
+                "posts".to_string(),
+            )
+            .with_scope(PermissionScope::Own),
+        ];
+        let mut author = User::new(
+            tenant.id,
+            "author@example.com".to_string(),
+            "hash".to_string(),
+        );
+        author.roles.push(author_role.clone());
+        let author = module.create_user(&author).await.unwrap();
+        let mut other = User::new(
+            tenant.id,
+            "other@example.com".to_string(),
+            "hash".to_string(),
+        );
+        other.roles.push(author_role);
+        let other = module.create_user(&other).await.unwrap();
+        assert_eq!(author.roles[0].permissions[1].scope, PermissionScope::Own);
+
+        // Unowned resources can be claimed by users allowed to create them
+        let owner = ResourceOwner::new(
+            tenant.id,
+            "posts".to_string(),
+            "42".to_string(),
+            OwnerType::User,
+            author.id.0,
+        );
+        let owners = module
+            .add_resource_owner(&author, owner.clone())
+            .await
+            .unwrap();
+        assert_eq!(owners, vec![owner.clone()]);
+
+        assert!(module
+            .check_resource_permission(&author, PermissionAction::Update, "posts", "42")
+            .await
+            .unwrap());
+        assert!(!module
+            .check_resource_permission(&other, PermissionAction::Update, "posts", "42")
+            .await
+            .unwrap());
+        assert!(!module
+            .check_resource_permission(&author, PermissionAction::Update, "posts", "43")
+            .await
+            .unwrap());
+
+        // Only owners may change the ownership afterwards
+        let shared = ResourceOwner::new(
+            tenant.id,
+            "posts".to_string(),
+            "42".to_string(),
+            OwnerType::User,
+            other.id.0,
+        );
+        let result = module.add_resource_owner(&other, shared.clone()).await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+
+        // Deny permissions keep users from claiming resources they could otherwise create
+        let mut blocked = Role::new(RoleType::Custom, "Blocked".to_string());
+        blocked.permissions = vec![Permission::new(
+            "Deny Create Posts".to_string(),
+            PermissionAction::Create,
+            "posts".to_string(),
+        )
+        .with_effect(PermissionEffect::Deny)];
+        let mut denied = other.clone();
+        denied.roles.push(blocked);
+        let unclaimed = ResourceOwner::new(
+            tenant.id,
+            "posts".to_string(),
+            "44".to_string(),
+            OwnerType::User,
+            other.id.0,
+        );
+        let result = module.add_resource_owner(&denied, unclaimed).await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+
+        module
+            .add_resource_owner(&author, shared.clone())
+            .await
+            .unwrap();
+        assert!(module
+            .check_resource_permission(&other, PermissionAction::Update, "posts", "42")
+            .await
+            .unwrap());
+
+        module.remove_resource_owner(&other, owner).await.unwrap();
+        assert!(!module
+            .check_resource_permission(&author, PermissionAction::Update, "posts", "42")
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_user_activity() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let repository = UserRepository::new(db.get_pool());
+        let module = IdentityModule::new(repository.clone());
+        let tenant = setup_test_tenant(&db).await.unwrap();
+
+        let mut user = User::new(
+            tenant.id,
+            "alice@example.com".to_string(),
+            "hash".to_string(),
+        );
+        user.roles = vec![create_user_role()];
+        let user = module.create_user(&user).await.unwrap();
+
+        let events = [
+            AuditEvent::new(
+                tenant.id,
+                AuditCategory::Security,
+                "login_failed",
+                "users",
+                "Alice@example.com",
+            ),
+            AuditEvent::new(
+                tenant.id,
+                AuditCategory::Security,
+                "login_succeeded",
+                "users",
+                user.id.0,
+            )
+            .with_user(user.id),
+            // Data changes are not part of the timeline
+            AuditEvent::new(
+                tenant.id,
+                AuditCategory::Audit,
+                "user_updated",
+                "users",
+                user.id.0,
+            )
+            .with_user(user.id),
+            AuditEvent::new(
+                tenant.id,
+                AuditCategory::Security,
+                "login_failed",
+                "users",
+                "bob@example.com",
+            ),
+        ];
+        for event in &events {
+            repository.insert_audit_event(event).await.unwrap();
+        }
+
+        let page = module
+            .list_user_activity(
+                &user,
+                user.id,
+                ActivityQuery {
+                    page: Some(1),
+                    per_page: Some(1),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.entries.len(), 1);
+
+        let page = module
+            .list_user_activity(&user, user.id, ActivityQuery::default())
+            .await
+            .unwrap();
+        let mut actions: Vec<_> = page.entries.iter().map(|e| e.action.as_str()).collect();
+        actions.sort();
+        assert_eq!(actions, ["login_failed", "login_succeeded"]);
+    }
 }
@@ -0,0 +1,384 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::{
+    modules::identity::session::{Session, SessionStore},
+    shared::{
+        error::Result,
+        types::{TenantId, UserId},
+    },
+};
+
+/// Archived sessions removed per batch of the retention worker
+const ARCHIVE_RETENTION_BATCH_SIZE: i64 = 1000;
+
+/// Why an archived session ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionEndReason {
+    Revoked,
+    Expired,
+}
+
+/// Summary of a session kept for investigations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedSession {
+    pub id: Uuid,
+    pub tenant_id: TenantId,
+    pub user_id: UserId,
+    pub created_at: OffsetDateTime,
+    /// When the session was revoked or expired; `None` while it is active
+    pub ended_at: Option<OffsetDateTime>,
+    pub end_reason: Option<SessionEndReason>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub device_name: Option<String>,
+}
+
+/// Long-term archive of session summaries in Postgres
+///
+/// Sessions are archived when they are stored and marked when they are revoked,
+/// so sessions whose store entry expired, e.g. through a Redis TTL, stay
+/// traceable until they leave the retention period.
+#[derive(Debug, Clone)]
+pub struct SessionArchive {
+    pool: PgPool,
+}
+
+impl SessionArchive {
+    /// Creates a new SessionArchive
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Archives a session, updating the expiry of an already archived one
+    pub async fn record(&self, session: &Session) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO session_archive (
+                id, tenant_id, user_id, created_at, expires_at, ip_address, user_agent,
+                device_name
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (id) DO UPDATE SET expires_at = EXCLUDED.expires_at
+            "#,
+            session.id,
+            session.tenant_id.0 as Uuid,
+            session.user_id.0 as Uuid,
+            session.created_at,
+            session.expires_at,
+            session.ip_address,
+            session.user_agent,
+            session.device_name,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Marks a session as revoked unless it already ended
+    pub async fn mark_revoked(&self, session_id: Uuid) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE session_archive SET revoked_at = NOW()
+            WHERE id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+            "#,
+            session_id,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Marks the active sessions of a user as revoked
+    pub async fn mark_user_revoked(&self, tenant_id: TenantId, user_id: UserId) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE session_archive SET revoked_at = NOW()
+            WHERE tenant_id = $1 AND user_id = $2
+                AND revoked_at IS NULL AND expires_at > NOW()
+            "#,
+            tenant_id.0 as Uuid,
+            user_id.0 as Uuid,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Marks the active sessions of all users of a tenant as revoked
+    pub async fn mark_tenant_revoked(&self, tenant_id: TenantId) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE session_archive SET revoked_at = NOW()
+            WHERE tenant_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+            "#,
+            tenant_id.0 as Uuid,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Lists the archived sessions of a user, newest first
+    pub async fn list_user_sessions(
+        &self,
+        tenant_id: TenantId,
+        user_id: UserId,
+        limit: i64,
+    ) -> Result<Vec<ArchivedSession>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, user_id, created_at, expires_at, revoked_at,
+                ip_address, user_agent, device_name
+            FROM session_archive
+            WHERE tenant_id = $1 AND user_id = $2
+            ORDER BY created_at DESC
+            LIMIT $3
+            "#,
+            tenant_id.0 as Uuid,
+            user_id.0 as Uuid,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let now = OffsetDateTime::now_utc();
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let (ended_at, end_reason) = match row.revoked_at {
+                    Some(revoked_at) => (Some(revoked_at), Some(SessionEndReason::Revoked)),
+                    None if row.expires_at <= now => {
+                        (Some(row.expires_at), Some(SessionEndReason::Expired))
+                    },
+                    None => (None, None),
+                };
+                ArchivedSession {
+                    id: row.id,
+                    tenant_id: TenantId(row.tenant_id),
+                    user_id: UserId(row.user_id),
+                    created_at: row.created_at,
+                    ended_at,
+                    end_reason,
+                    ip_address: row.ip_address,
+                    user_agent: row.user_agent,
+                    device_name: row.device_name,
+                }
+            })
+            .collect())
+    }
+
+    /// Removes up to `limit` sessions that ended before `cutoff`, returning how many were removed
+    pub async fn remove_ended_before(&self, cutoff: OffsetDateTime, limit: i64) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM session_archive
+            WHERE id IN (
+                SELECT id FROM session_archive
+                WHERE COALESCE(revoked_at, expires_at) < $1
+                LIMIT $2
+            )
+            "#,
+            cutoff,
+            limit,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Periodically removes sessions that ended longer than `retention` ago
+    pub fn spawn_retention_worker(
+        self,
+        retention: time::Duration,
+        interval: std::time::Duration,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let cutoff = OffsetDateTime::now_utc() - retention;
+                let mut removed = 0;
+                loop {
+                    match self
+                        .remove_ended_before(cutoff, ARCHIVE_RETENTION_BATCH_SIZE)
+                        .await
+                    {
+                        Ok(count) => {
+                            removed += count;
+                            if count < ARCHIVE_RETENTION_BATCH_SIZE as u64 {
+                                break;
+                            }
+                        },
+                        Err(e) => {
+                            tracing::error!("Failed to remove archived sessions: {}", e);
+                            break;
+                        },
+                    }
+                }
+                if removed > 0 {
+                    tracing::info!("Removed {} archived sessions past retention", removed);
+                }
+            }
+        })
+    }
+}
+
+/// Session store archiving the sessions of another store
+///
+/// Archiving failures are logged rather than failing logins or revocations.
+#[derive(Debug)]
+pub struct ArchivingSessionStore {
+    inner: Box<dyn SessionStore>,
+    archive: SessionArchive,
+}
+
+impl ArchivingSessionStore {
+    /// Creates a store archiving the sessions of `inner`
+    pub fn new(inner: Box<dyn SessionStore>, archive: SessionArchive) -> Self {
+        Self { inner, archive }
+    }
+
+    /// Logs a failed archive update
+    fn log_failure(result: Result<()>) {
+        if let Err(e) = result {
+            tracing::warn!("Failed to archive session: {}", e);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for ArchivingSessionStore {
+    async fn store_session(&self, session: &Session) -> Result<()> {
+        self.inner.store_session(session).await?;
+        Self::log_failure(self.archive.record(session).await);
+        Ok(())
+    }
+
+    async fn get_session(&self, session_id: Uuid) -> Result<Option<Session>> {
+        self.inner.get_session(session_id).await
+    }
+
+    async fn get_session_by_token(&self, token: &str) -> Result<Option<Session>> {
+        self.inner.get_session_by_token(token).await
+    }
+
+    async fn get_user_sessions(
+        &self,
+        tenant_id: TenantId,
+        user_id: UserId,
+    ) -> Result<Vec<Session>> {
+        self.inner.get_user_sessions(tenant_id, user_id).await
+    }
+
+    async fn get_tenant_sessions(&self, tenant_id: TenantId) -> Result<Vec<Session>> {
+        self.inner.get_tenant_sessions(tenant_id).await
+    }
+
+    async fn remove_session(&self, session_id: Uuid) -> Result<()> {
+        self.inner.remove_session(session_id).await?;
+        Self::log_failure(self.archive.mark_revoked(session_id).await);
+        Ok(())
+    }
+
+    async fn remove_user_sessions(&self, tenant_id: TenantId, user_id: UserId) -> Result<()> {
+        self.inner.remove_user_sessions(tenant_id, user_id).await?;
+        Self::log_failure(self.archive.mark_user_revoked(tenant_id, user_id).await);
+        Ok(())
+    }
+
+    async fn remove_tenant_sessions(&self, tenant_id: TenantId) -> Result<()> {
+        self.inner.remove_tenant_sessions(tenant_id).await?;
+        Self::log_failure(self.archive.mark_tenant_revoked(tenant_id).await);
+        Ok(())
+    }
+
+    async fn get_token_version(&self, tenant_id: TenantId, user_id: UserId) -> Result<u64> {
+        self.inner.get_token_version(tenant_id, user_id).await
+    }
+
+    async fn increment_token_version(&self, tenant_id: TenantId, user_id: UserId) -> Result<u64> {
+        self.inner.increment_token_version(tenant_id, user_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::database::tests::create_test_db, modules::identity::session::InMemorySessionStore,
+    };
+    use time::Duration;
+
+    #[tokio::test]
+    async fn test_session_archive() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let tenant_id = TenantId::new();
+        sqlx::query("INSERT INTO tenants (id, name, domain) VALUES ($1, $2, $3)")
+            .bind(tenant_id.0)
+            .bind("Archive Tenant")
+            .bind(format!("{}.example.com", tenant_id.0))
+            .execute(&db.get_pool())
+            .await
+            .unwrap();
+
+        let archive = SessionArchive::new(db.get_pool());
+        let store =
+            ArchivingSessionStore::new(Box::new(InMemorySessionStore::new()), archive.clone());
+        let user_id = UserId::new();
+
+        let mut revoked = Session::new(
+            user_id,
+            tenant_id,
+            "revoked_token".to_string(),
+            Duration::hours(1),
+        );
+        revoked.ip_address = Some("203.0.113.7".to_string());
+        revoked.device_name = Some("Phone".to_string());
+        let expired = Session::new(
+            user_id,
+            tenant_id,
+            "expired_token".to_string(),
+            Duration::ZERO,
+        );
+        let active = Session::new(
+            user_id,
+            tenant_id,
+            "active_token".to_string(),
+            Duration::hours(1),
+        );
+        for session in [&revoked, &expired, &active] {
+            store.store_session(session).await.unwrap();
+        }
+        store.remove_session(revoked.id).await.unwrap();
+
+        let sessions = archive
+            .list_user_sessions(tenant_id, user_id, 10)
+            .await
+            .unwrap();
+        assert_eq!(sessions.len(), 3);
+        let find = |id: Uuid| sessions.iter().find(|s| s.id == id).unwrap();
+        assert_eq!(find(revoked.id).end_reason, Some(SessionEndReason::Revoked));
+        assert_eq!(find(revoked.id).ip_address.as_deref(), Some("203.0.113.7"));
+        assert_eq!(find(revoked.id).device_name.as_deref(), Some("Phone"));
+        assert_eq!(find(expired.id).end_reason, Some(SessionEndReason::Expired));
+        assert!(find(active.id).ended_at.is_none());
+
+        // Only sessions that ended before the cutoff leave the archive
+        let removed = archive
+            .remove_ended_before(OffsetDateTime::now_utc() + Duration::minutes(1), 10)
+            .await
+            .unwrap();
+        assert_eq!(removed, 2);
+        let sessions = archive
+            .list_user_sessions(tenant_id, user_id, 10)
+            .await
+            .unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, active.id);
+    }
+}
@@ -0,0 +1,256 @@
+use moka::sync::Cache;
+use std::{sync::Arc, time::Duration};
+use uuid::Uuid;
+
+use crate::{
+    modules::identity::session::{Session, SessionStore},
+    shared::{
+        cache::{CacheInvalidation, CacheInvalidationBus, CacheInvalidationHandler},
+        error::Result,
+        types::{TenantId, UserId},
+    },
+};
+
+/// Sessions kept per local cache
+const MAX_CACHED_SESSIONS: u64 = 100_000;
+
+/// Local cache of sessions by token
+///
+/// Clones share their entries, so the cache registered for invalidations is the
+/// one the store reads.
+#[derive(Debug, Clone)]
+pub struct SessionCache {
+    sessions: Cache<String, Session>,
+}
+
+impl SessionCache {
+    /// Creates a cache keeping sessions for at most `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            sessions: Cache::builder()
+                .max_capacity(MAX_CACHED_SESSIONS)
+                .time_to_live(ttl)
+                .support_invalidation_closures()
+                .build(),
+        }
+    }
+
+    /// Gets the cached session of a token unless it expired
+    pub fn get(&self, token: &str) -> Option<Session> {
+        self.sessions
+            .get(token)
+            .filter(|session| !session.is_expired())
+    }
+
+    /// Caches a session
+    pub fn insert(&self, session: &Session) {
+        self.sessions.insert(session.token.clone(), session.clone());
+    }
+
+    /// Drops the cached sessions matching a predicate
+    fn remove(&self, predicate: impl Fn(&Session) -> bool + Send + Sync + 'static) {
+        if let Err(e) = self
+            .sessions
+            .invalidate_entries_if(move |_, session| predicate(session))
+        {
+            tracing::warn!("Failed to invalidate cached sessions: {}", e);
+            self.sessions.invalidate_all();
+        }
+    }
+}
+
+impl CacheInvalidationHandler for SessionCache {
+    fn invalidate(&self, invalidation: &CacheInvalidation) {
+        match *invalidation {
+            CacheInvalidation::Session { session_id } => {
+                self.remove(move |session| session.id == session_id)
+            },
+            CacheInvalidation::User { user_id } => {
+                self.remove(move |session| session.user_id == user_id)
+            },
+            CacheInvalidation::Tenant { tenant_id } => {
+                self.remove(move |session| session.tenant_id == tenant_id)
+            },
+            CacheInvalidation::Permissions | CacheInvalidation::TenantSettings { .. } => {},
+        }
+    }
+}
+
+/// Session store caching token lookups of another store, e.g. Redis, in process memory
+///
+/// Saves a round trip to the store on every authenticated request. Revocations
+/// drop the cached sessions of this instance right away and, if the invalidation
+/// bus broadcasts via Redis, those of other instances too; otherwise other
+/// instances accept revoked sessions until their entries expire, so the TTL
+/// should be short.
+#[derive(Debug)]
+pub struct CachedSessionStore {
+    inner: Box<dyn SessionStore>,
+    cache: SessionCache,
+    invalidation: CacheInvalidationBus,
+}
+
+impl CachedSessionStore {
+    /// Creates a store caching the sessions of `inner` for `ttl`
+    pub fn new(inner: Box<dyn SessionStore>, ttl: Duration) -> Self {
+        Self::with_invalidation(inner, ttl, CacheInvalidationBus::new())
+    }
+
+    /// Creates a store whose revocations are distributed by the given bus
+    pub fn with_invalidation(
+        inner: Box<dyn SessionStore>,
+        ttl: Duration,
+        invalidation: CacheInvalidationBus,
+    ) -> Self {
+        let cache = SessionCache::new(ttl);
+        invalidation.register(Arc::new(cache.clone()));
+        Self {
+            inner,
+            cache,
+            invalidation,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for CachedSessionStore {
+    async fn store_session(&self, session: &Session) -> Result<()> {
+        self.inner.store_session(session).await?;
+        self.cache.insert(session);
+        Ok(())
+    }
+
+    async fn get_session(&self, session_id: Uuid) -> Result<Option<Session>> {
+        self.inner.get_session(session_id).await
+    }
+
+    async fn get_session_by_token(&self, token: &str) -> Result<Option<Session>> {
+        if let Some(session) = self.cache.get(token) {
+            return Ok(Some(session));
+        }
+        let session = self.inner.get_session_by_token(token).await?;
+        if let Some(session) = &session {
+            self.cache.insert(session);
+        }
+        Ok(session)
+    }
+
+    async fn get_user_sessions(
+        &self,
+        tenant_id: TenantId,
+        user_id: UserId,
+    ) -> Result<Vec<Session>> {
+        self.inner.get_user_sessions(tenant_id, user_id).await
+    }
+
+    async fn get_tenant_sessions(&self, tenant_id: TenantId) -> Result<Vec<Session>> {
+        self.inner.get_tenant_sessions(tenant_id).await
+    }
+
+    async fn remove_session(&self, session_id: Uuid) -> Result<()> {
+        self.inner.remove_session(session_id).await?;
+        self.invalidation
+            .publish(CacheInvalidation::Session { session_id })
+            .await;
+        Ok(())
+    }
+
+    async fn remove_user_sessions(&self, tenant_id: TenantId, user_id: UserId) -> Result<()> {
+        self.inner.remove_user_sessions(tenant_id, user_id).await?;
+        self.invalidation
+            .publish(CacheInvalidation::User { user_id })
+            .await;
+        Ok(())
+    }
+
+    async fn remove_tenant_sessions(&self, tenant_id: TenantId) -> Result<()> {
+        self.inner.remove_tenant_sessions(tenant_id).await?;
+        self.invalidation
+            .publish(CacheInvalidation::Tenant { tenant_id })
+            .await;
+        Ok(())
+    }
+
+    async fn get_token_version(&self, tenant_id: TenantId, user_id: UserId) -> Result<u64> {
+        self.inner.get_token_version(tenant_id, user_id).await
+    }
+
+    async fn increment_token_version(&self, tenant_id: TenantId, user_id: UserId) -> Result<u64> {
+        let version = self
+            .inner
+            .increment_token_version(tenant_id, user_id)
+            .await?;
+        self.invalidation
+            .publish(CacheInvalidation::User { user_id })
+            .await;
+        Ok(version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::identity::session::InMemorySessionStore;
+
+    #[tokio::test]
+    async fn test_cached_session_store() {
+        let inner = InMemorySessionStore::new();
+        let bus = CacheInvalidationBus::new();
+        let store = CachedSessionStore::with_invalidation(
+            Box::new(inner.clone()),
+            Duration::from_secs(60),
+            bus.clone(),
+        );
+        let tenant_id = TenantId::new();
+        let user_id = UserId::new();
+
+        let session = Session::new(
+            user_id,
+            tenant_id,
+            "cached_token".to_string(),
+            time::Duration::hours(1),
+        );
+        inner.store_session(&session).await.unwrap();
+        let cached = store
+            .get_session_by_token("cached_token")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(cached.id, session.id);
+
+        // Lookups are served locally until the session is invalidated
+        inner.remove_session(session.id).await.unwrap();
+        assert!(store
+            .get_session_by_token("cached_token")
+            .await
+            .unwrap()
+            .is_some());
+        bus.publish(CacheInvalidation::User { user_id }).await;
+        assert!(store
+            .get_session_by_token("cached_token")
+            .await
+            .unwrap()
+            .is_none());
+
+        // Revoking through the store drops the cached session right away
+        store.store_session(&session).await.unwrap();
+        assert!(store.cache.get("cached_token").is_some());
+        store.remove_session(session.id).await.unwrap();
+        assert!(store.cache.get("cached_token").is_none());
+        assert!(store
+            .get_session_by_token("cached_token")
+            .await
+            .unwrap()
+            .is_none());
+
+        // Expired sessions are never served from the cache
+        let expired = Session::new(
+            user_id,
+            tenant_id,
+            "expired_token".to_string(),
+            time::Duration::ZERO,
+        );
+        store.cache.insert(&expired);
+        assert!(store.cache.get("expired_token").is_none());
+    }
+}
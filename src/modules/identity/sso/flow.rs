@@ -0,0 +1,192 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Mutex};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::shared::error::{Error, Result};
+
+/// Seconds a user has to complete an OIDC login at the identity provider
+pub const OIDC_FLOW_TTL_SECS: i64 = 600;
+
+/// Prefix of the Redis keys holding pending OIDC logins
+const FLOW_KEY_PREFIX: &str = "sso:oidc_flow";
+
+/// Pending OIDC login, stored under its state until the provider returns the user
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OidcFlow {
+    pub provider_id: Uuid,
+    pub nonce: String,
+    pub pkce_verifier: String,
+    /// Redirect URI the login was started with, if not the provider's default
+    pub redirect_uri: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+}
+
+impl OidcFlow {
+    /// Creates a flow expiring after the OIDC flow lifetime
+    pub fn new(
+        provider_id: Uuid,
+        nonce: String,
+        pkce_verifier: String,
+        redirect_uri: Option<String>,
+    ) -> Self {
+        Self {
+            provider_id,
+            nonce,
+            pkce_verifier,
+            redirect_uri,
+            expires_at: OffsetDateTime::now_utc() + Duration::seconds(OIDC_FLOW_TTL_SECS),
+        }
+    }
+
+    /// Checks if the flow is expired
+    pub fn is_expired(&self) -> bool {
+        OffsetDateTime::now_utc() >= self.expires_at
+    }
+}
+
+/// Storage of pending OIDC logins
+///
+/// Taking a flow removes it, so each state can complete one login only.
+#[async_trait]
+pub trait OidcFlowStore: Send + Sync + std::fmt::Debug {
+    /// Stores a flow under its state until it expires
+    async fn store_flow(&self, state: &str, flow: &OidcFlow) -> Result<()>;
+
+    /// Removes and returns the flow stored under a state
+    async fn take_flow(&self, state: &str) -> Result<Option<OidcFlow>>;
+}
+
+/// Flow store keeping pending logins in process memory
+///
+/// Only suitable for a single instance, as the provider may return the user to another one.
+#[derive(Debug, Default)]
+pub struct InMemoryOidcFlowStore {
+    flows: Mutex<HashMap<String, OidcFlow>>,
+}
+
+impl InMemoryOidcFlowStore {
+    /// Creates an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl OidcFlowStore for InMemoryOidcFlowStore {
+    async fn store_flow(&self, state: &str, flow: &OidcFlow) -> Result<()> {
+        let mut flows = self
+            .flows
+            .lock()
+            .map_err(|_| Error::Internal("OIDC flow store lock poisoned".to_string()))?;
+        flows.retain(|_, flow| !flow.is_expired());
+        flows.insert(state.to_string(), flow.clone());
+        Ok(())
+    }
+
+    async fn take_flow(&self, state: &str) -> Result<Option<OidcFlow>> {
+        let mut flows = self
+            .flows
+            .lock()
+            .map_err(|_| Error::Internal("OIDC flow store lock poisoned".to_string()))?;
+        Ok(flows.remove(state))
+    }
+}
+
+/// Flow store keeping pending logins in Redis, shared by all instances
+#[derive(Debug, Clone)]
+pub struct RedisOidcFlowStore {
+    client: redis::Client,
+}
+
+impl RedisOidcFlowStore {
+    /// Creates a store keeping its flows in the given Redis
+    pub fn new(redis_url: &str) -> Result<Self> {
+        Ok(Self {
+            client: redis::Client::open(redis_url)?,
+        })
+    }
+}
+
+#[async_trait]
+impl OidcFlowStore for RedisOidcFlowStore {
+    async fn store_flow(&self, state: &str, flow: &OidcFlow) -> Result<()> {
+        let value = serde_json::to_string(flow)
+            .map_err(|e| Error::Internal(format!("Failed to serialize OIDC flow: {}", e)))?;
+        let ttl = (flow.expires_at - OffsetDateTime::now_utc())
+            .whole_seconds()
+            .max(1);
+        let mut conn = self.client.get_async_connection().await?;
+        redis::cmd("SET")
+            .arg(format!("{}:{}", FLOW_KEY_PREFIX, state))
+            .arg(value)
+            .arg("EX")
+            .arg(ttl)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn take_flow(&self, state: &str) -> Result<Option<OidcFlow>> {
+        let key = format!("{}:{}", FLOW_KEY_PREFIX, state);
+        let mut conn = self.client.get_async_connection().await?;
+        // Reading and deleting in one transaction lets only one callback use the flow
+        let (value,): (Option<String>,) = redis::pipe()
+            .atomic()
+            .get(&key)
+            .del(&key)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+        value
+            .map(|value| {
+                serde_json::from_str(&value)
+                    .map_err(|e| Error::Internal(format!("Invalid OIDC flow: {}", e)))
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_in_memory_flow_store() {
+        let store = InMemoryOidcFlowStore::new();
+        let flow = OidcFlow::new(
+            Uuid::new_v4(),
+            "nonce".to_string(),
+            "verifier".to_string(),
+            None,
+        );
+        store.store_flow("state", &flow).await.unwrap();
+
+        // A flow can be taken once
+        assert_eq!(store.take_flow("state").await.unwrap(), Some(flow));
+        assert_eq!(store.take_flow("state").await.unwrap(), None);
+        assert_eq!(store.take_flow("unknown").await.unwrap(), None);
+
+        // Expired flows are dropped when new ones are stored
+        let mut expired = OidcFlow::new(
+            Uuid::new_v4(),
+            "nonce".to_string(),
+            "verifier".to_string(),
+            None,
+        );
+        expired.expires_at = OffsetDateTime::now_utc() - Duration::seconds(1);
+        assert!(expired.is_expired());
+        store.store_flow("expired", &expired).await.unwrap();
+        let fresh = OidcFlow::new(
+            Uuid::new_v4(),
+            "nonce".to_string(),
+            "verifier".to_string(),
+            Some("https://app.example.com/sso/callback".to_string()),
+        );
+        store.store_flow("fresh", &fresh).await.unwrap();
+        assert_eq!(store.take_flow("expired").await.unwrap(), None);
+        assert_eq!(store.take_flow("fresh").await.unwrap(), Some(fresh));
+    }
+}
@@ -0,0 +1,600 @@
+use axum::{
+    extract::{FromRef, Path, Query, RawQuery, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, COOKIE, SET_COOKIE},
+        HeaderMap, StatusCode,
+    },
+    response::{AppendHeaders, IntoResponse, Redirect, Response},
+    routing::{get, patch, post},
+    Form, Json, Router,
+};
+use serde::Deserialize;
+use std::{collections::HashSet, sync::Arc};
+use uuid::Uuid;
+
+use crate::{
+    modules::identity::{
+        auth::AuthenticationService,
+        handlers::{AuthenticatedSession, AuthenticatedUser, REQUEST_ID_HEADER},
+        hooks::LoginContext,
+    },
+    shared::error::{Error, Result},
+};
+
+use super::{
+    models::{
+        SamlMetadataImportRequest, SsoAuthEventQuery, SsoIdentity, SsoLoginResolution,
+        SsoLoginResponse, SsoLogoutResponse, SsoProvider, SsoProviderUpdate, SsoSession,
+    },
+    presets::{SsoPreset, SsoPresetRequest},
+    service::SsoService,
+};
+
+/// Cookie carrying the relay state or OIDC state of a pending login
+const STATE_COOKIE: &str = "sso_state";
+
+/// Seconds a user has to complete a login at the identity provider
+const FLOW_COOKIE_MAX_AGE_SECS: u64 = 600;
+
+/// Shared state of the SSO handlers
+#[derive(Clone, FromRef)]
+pub struct SsoState {
+    pub auth: Arc<AuthenticationService>,
+    pub sso: Arc<SsoService>,
+}
+
+/// SAML response posted by the identity provider
+#[derive(Debug, Deserialize)]
+pub struct AcsForm {
+    #[serde(rename = "SAMLResponse")]
+    pub saml_response: String,
+    #[serde(rename = "RelayState")]
+    pub relay_state: Option<String>,
+}
+
+/// Sign-in response the WS-Federation STS posts to the reply URL
+#[derive(Debug, Deserialize)]
+pub struct WsFedForm {
+    pub wa: String,
+    pub wresult: String,
+    pub wctx: Option<String>,
+}
+
+/// Options of a login started at the identity provider
+#[derive(Debug, Deserialize)]
+pub struct LoginQuery {
+    /// Registered redirect URI the OIDC provider should return to
+    pub redirect_uri: Option<String>,
+}
+
+/// Authorization response the OIDC provider redirects to
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    pub code: String,
+    pub state: String,
+}
+
+/// Return of a user the OIDC provider logged out
+#[derive(Debug, Deserialize)]
+pub struct PostLogoutQuery {
+    pub state: Option<String>,
+}
+
+/// Front-channel logout request of the OIDC provider
+#[derive(Debug, Deserialize)]
+pub struct FrontChannelLogoutQuery {
+    pub iss: Option<String>,
+    pub sid: Option<String>,
+}
+
+/// Creates a SAML provider in the caller's tenant from IdP metadata
+async fn import_saml_metadata(
+    State(sso): State<Arc<SsoService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Json(request): Json<SamlMetadataImportRequest>,
+) -> Result<impl IntoResponse> {
+    let import = sso.import_saml_metadata(&actor, &request).await?;
+    Ok((StatusCode::CREATED, Json(import)))
+}
+
+/// Lists the identity providers with presets
+async fn list_presets() -> impl IntoResponse {
+    Json(SsoPreset::catalog())
+}
+
+/// Creates a provider in the caller's tenant from the preset of a known IdP
+async fn create_provider_from_preset(
+    State(sso): State<Arc<SsoService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Json(request): Json<SsoPresetRequest>,
+) -> Result<impl IntoResponse> {
+    let provider = sso.create_provider_from_preset(&actor, &request).await?;
+    Ok((StatusCode::CREATED, Json(provider)))
+}
+
+/// Changes the configuration of a provider in the caller's tenant
+async fn update_provider(
+    State(sso): State<Arc<SsoService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(provider_id): Path<String>,
+    Json(update): Json<SsoProviderUpdate>,
+) -> Result<impl IntoResponse> {
+    let provider = sso
+        .update_managed_provider(&actor, parse_provider_id(&provider_id)?, update)
+        .await?;
+    Ok((StatusCode::OK, Json(provider)))
+}
+
+/// Deletes a provider of the caller's tenant
+async fn delete_provider(
+    State(sso): State<Arc<SsoService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(provider_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    sso.delete_managed_provider(&actor, parse_provider_id(&provider_id)?)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Enables a provider of the caller's tenant
+async fn enable_provider(
+    State(sso): State<Arc<SsoService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(provider_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let provider = sso
+        .set_managed_provider_enabled(&actor, parse_provider_id(&provider_id)?, true)
+        .await?;
+    Ok((StatusCode::OK, Json(provider)))
+}
+
+/// Disables a provider of the caller's tenant, rejecting further logins through it
+async fn disable_provider(
+    State(sso): State<Arc<SsoService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(provider_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let provider = sso
+        .set_managed_provider_enabled(&actor, parse_provider_id(&provider_id)?, false)
+        .await?;
+    Ok((StatusCode::OK, Json(provider)))
+}
+
+/// Makes the next SAML signing key the active one
+async fn promote_signing_key(
+    State(sso): State<Arc<SsoService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    sso.promote_saml_signing_key(&actor)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lists the login events of the providers of the caller's tenant, newest first
+async fn list_auth_events(
+    State(sso): State<Arc<SsoService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Query(query): Query<SsoAuthEventQuery>,
+) -> Result<impl IntoResponse> {
+    let events = sso.list_auth_events(&actor, query).await?;
+    Ok(Json(events))
+}
+
+/// Publishes the service provider metadata of a SAML provider
+async fn get_metadata(
+    State(sso): State<Arc<SsoService>>,
+    Path(provider_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let provider = get_provider(&sso, &provider_id).await?;
+    let metadata = sso.generate_metadata(&provider)?;
+    Ok(([(CONTENT_TYPE, "application/samlmetadata+xml")], metadata))
+}
+
+/// Redirects the browser to the identity provider to sign in
+///
+/// The relay state or OIDC state is kept in a short-lived cookie and checked
+/// when the identity provider returns the user, binding the login to the
+/// browser that started it.
+async fn login(
+    State(sso): State<Arc<SsoService>>,
+    Path(provider_id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<LoginQuery>,
+) -> Result<impl IntoResponse> {
+    let provider = get_provider(&sso, &provider_id).await?;
+    let result = sso
+        .initiate_auth(&provider, query.redirect_uri.as_deref())
+        .await;
+    sso.record_login_initiated(&provider, result.as_ref().err(), request_id(&headers))
+        .await;
+    let (url, state) = result?;
+    Ok((
+        AppendHeaders([(SET_COOKIE, flow_cookie(STATE_COOKIE, &state))]),
+        Redirect::to(&url),
+    ))
+}
+
+/// Completes a SAML login with the response posted to the assertion consumer service
+///
+/// Responses whose relay state does not match the one of a login started here
+/// are treated as unsolicited, which the provider has to allow.
+async fn saml_acs(
+    State(state): State<SsoState>,
+    Path(provider_id): Path<String>,
+    headers: HeaderMap,
+    Form(form): Form<AcsForm>,
+) -> Result<impl IntoResponse> {
+    let provider = get_provider(&state.sso, &provider_id).await?;
+    let relay_state = form
+        .relay_state
+        .filter(|relay_state| read_cookie(&headers, STATE_COOKIE).as_ref() == Some(relay_state));
+    let identity = state
+        .sso
+        .validate_response(&provider, &form.saml_response, relay_state.as_deref())
+        .await;
+    complete_login(&state, &provider, identity, &headers).await
+}
+
+/// Completes a WS-Federation login with the token the STS posted to the reply URL
+///
+/// As for SAML, a context that does not match the login started here makes the
+/// response unsolicited.
+async fn wsfed_reply(
+    State(state): State<SsoState>,
+    Path(provider_id): Path<String>,
+    headers: HeaderMap,
+    Form(form): Form<WsFedForm>,
+) -> Result<impl IntoResponse> {
+    let provider = get_provider(&state.sso, &provider_id).await?;
+    let identity = if form.wa == "wsignin1.0" {
+        let context = form
+            .wctx
+            .filter(|context| read_cookie(&headers, STATE_COOKIE).as_ref() == Some(context));
+        state
+            .sso
+            .validate_response(&provider, &form.wresult, context.as_deref())
+            .await
+    } else {
+        Err(Error::InvalidInput(format!(
+            "Unsupported WS-Federation action {}",
+            form.wa
+        )))
+    };
+    complete_login(&state, &provider, identity, &headers).await
+}
+
+/// Completes an OIDC login with the authorization code the provider redirected with
+async fn oidc_callback(
+    State(state): State<SsoState>,
+    Path(provider_id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<CallbackQuery>,
+) -> Result<impl IntoResponse> {
+    let provider = get_provider(&state.sso, &provider_id).await?;
+    let identity = oidc_identity(&state.sso, &provider, &headers, &query).await;
+    complete_login(&state, &provider, identity, &headers).await
+}
+
+/// Validates the authorization response against the state of the login started in this browser
+async fn oidc_identity(
+    sso: &SsoService,
+    provider: &SsoProvider,
+    headers: &HeaderMap,
+    query: &CallbackQuery,
+) -> Result<SsoIdentity> {
+    if read_cookie(headers, STATE_COOKIE).as_deref() != Some(query.state.as_str()) {
+        return Err(Error::Authentication("Invalid OIDC state".to_string()));
+    }
+    sso.validate_response(provider, &query.code, Some(&query.state))
+        .await
+}
+
+/// Opens a session for a validated SSO identity or starts linking it to an existing user
+///
+/// The outcome is recorded in the event trail of the provider.
+async fn complete_login(
+    state: &SsoState,
+    provider: &SsoProvider,
+    identity: Result<SsoIdentity>,
+    headers: &HeaderMap,
+) -> Result<impl IntoResponse> {
+    let request_id = request_id(headers);
+    let identity = match identity {
+        Ok(identity) => identity,
+        Err(e) => {
+            state
+                .sso
+                .record_login_completed(provider, None, Some(&e), request_id)
+                .await;
+            return Err(e);
+        },
+    };
+    let result = resolve_login(state, provider, &identity, headers).await;
+    state
+        .sso
+        .record_login_completed(
+            provider,
+            Some(&identity.external_id),
+            result.as_ref().err(),
+            request_id,
+        )
+        .await;
+    let response = result?;
+    // The flow is over, so its cookie is dropped
+    Ok((
+        AppendHeaders([(SET_COOKIE, clear_cookie(STATE_COOKIE))]),
+        Json(response),
+    ))
+}
+
+/// Signs in the user mapped to an SSO identity or starts linking it to an existing user
+async fn resolve_login(
+    state: &SsoState,
+    provider: &SsoProvider,
+    identity: &SsoIdentity,
+    headers: &HeaderMap,
+) -> Result<SsoLoginResponse> {
+    Ok(match state.sso.resolve_user(provider, identity).await? {
+        SsoLoginResolution::Mapped(mapping) => {
+            let session = state
+                .auth
+                .authenticate_sso(
+                    mapping.tenant_id,
+                    mapping.user_id,
+                    identity.session_id,
+                    LoginContext::from_headers(headers),
+                )
+                .await?;
+            let refresh_token = if state.auth.refresh_tokens_enabled() {
+                Some(state.auth.issue_refresh_token(&session).await?)
+            } else {
+                None
+            };
+            SsoLoginResponse::Authenticated {
+                token: session.token,
+                refresh_token,
+                expires_at: session.expires_at,
+            }
+        },
+        SsoLoginResolution::LinkRequired(request) => SsoLoginResponse::LinkRequired {
+            link_id: request.id,
+            expires_at: request.expires_at,
+        },
+        SsoLoginResolution::Unknown { .. } => {
+            return Err(Error::Authentication(
+                "No account matches the SSO identity".to_string(),
+            ))
+        },
+    })
+}
+
+/// Parses a provider ID from the request path
+fn parse_provider_id(provider_id: &str) -> Result<Uuid> {
+    Uuid::parse_str(provider_id).map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))
+}
+
+/// Hands the caller's IdP access token to the application for calling the customer's APIs
+async fn access_token(
+    State(sso): State<Arc<SsoService>>,
+    AuthenticatedUser(user): AuthenticatedUser,
+    Path(provider_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let token = sso
+        .access_token(&user, parse_provider_id(&provider_id)?)
+        .await?;
+    Ok(([(CACHE_CONTROL, "no-store")], Json(token)))
+}
+
+/// Signs the caller out and returns where to also end their session at the IdP
+async fn logout(
+    State(state): State<SsoState>,
+    AuthenticatedSession(current): AuthenticatedSession,
+    Path(provider_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let provider = get_provider(&state.sso, &provider_id).await?;
+    if provider.tenant_id != current.tenant_id {
+        return Err(Error::NotFound("SSO provider not found".to_string()));
+    }
+    // The IdP logout needs the linked SSO session, which revoking the session ends
+    let redirect_url = state.sso.initiate_logout(&provider, &current).await?;
+    state
+        .auth
+        .revoke_user_session(current.tenant_id, current.user_id, current.id)
+        .await?;
+    Ok((StatusCode::OK, Json(SsoLogoutResponse { redirect_url })))
+}
+
+/// Single logout service receiving the logout requests and responses of the IdP
+///
+/// Users logged out by the IdP are signed out of the application sessions
+/// opened with the ended SSO sessions.
+async fn saml_slo(
+    State(state): State<SsoState>,
+    Path(provider_id): Path<String>,
+    RawQuery(query): RawQuery,
+) -> Result<Response> {
+    let provider = get_provider(&state.sso, &provider_id).await?;
+    let query = query.unwrap_or_default();
+    if query
+        .split('&')
+        .any(|pair| pair.starts_with("SAMLResponse="))
+    {
+        state.sso.handle_logout_response(&provider, &query).await?;
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    let (sessions, redirect_url) = state.sso.handle_logout_request(&provider, &query).await?;
+    sign_out_users(&state, &sessions).await?;
+    Ok(Redirect::to(&redirect_url).into_response())
+}
+
+/// Ends the SSO session of a user the OIDC provider returned after logging them out
+async fn oidc_post_logout(
+    State(sso): State<Arc<SsoService>>,
+    Query(query): Query<PostLogoutQuery>,
+) -> Result<impl IntoResponse> {
+    if let Some(logout_state) = query.state {
+        sso.complete_oidc_logout(&logout_state).await?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Front-channel logout URI the OIDC provider loads when a user logs out there
+///
+/// Like SAML single logout, signs the users out of the application sessions
+/// opened with the ended SSO sessions.
+async fn oidc_frontchannel_logout(
+    State(state): State<SsoState>,
+    Path(provider_id): Path<String>,
+    Query(query): Query<FrontChannelLogoutQuery>,
+) -> Result<impl IntoResponse> {
+    let provider = get_provider(&state.sso, &provider_id).await?;
+    let sid = query
+        .sid
+        .ok_or_else(|| Error::InvalidInput("Missing sid".to_string()))?;
+    let sessions = state
+        .sso
+        .handle_frontchannel_logout(&provider, query.iss.as_deref(), &sid)
+        .await?;
+    sign_out_users(&state, &sessions).await?;
+    Ok((StatusCode::OK, [(CACHE_CONTROL, "no-store")]))
+}
+
+/// Revokes the application sessions opened with ended SSO sessions
+async fn sign_out_users(state: &SsoState, sessions: &[SsoSession]) -> Result<()> {
+    let ended: HashSet<_> = sessions.iter().map(|session| session.id).collect();
+    let users: HashSet<_> = sessions
+        .iter()
+        .map(|session| (session.tenant_id, session.user_id))
+        .collect();
+    for (tenant_id, user_id) in users {
+        let linked = state
+            .auth
+            .list_user_sessions(tenant_id, user_id)
+            .await?
+            .into_iter()
+            .filter(|session| {
+                session
+                    .sso_session_id()
+                    .is_some_and(|id| ended.contains(&id))
+            });
+        for session in linked {
+            state
+                .auth
+                .revoke_user_session(tenant_id, user_id, session.id)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Gets a provider by the ID in the request path
+async fn get_provider(sso: &SsoService, provider_id: &str) -> Result<SsoProvider> {
+    sso.get_provider(parse_provider_id(provider_id)?)
+        .await?
+        .ok_or_else(|| Error::NotFound("SSO provider not found".to_string()))
+}
+
+/// Builds the cookie of a pending login
+///
+/// The identity provider returns the user with a cross-site request, a POST for
+/// SAML, so the cookie must not be restricted to same-site requests.
+fn flow_cookie(name: &str, value: &str) -> String {
+    format!(
+        "{}={}; Max-Age={}; Path=/; HttpOnly; Secure; SameSite=None",
+        name, value, FLOW_COOKIE_MAX_AGE_SECS
+    )
+}
+
+/// Builds a cookie removing the cookie of a pending login
+fn clear_cookie(name: &str) -> String {
+    format!(
+        "{}=; Max-Age=0; Path=/; HttpOnly; Secure; SameSite=None",
+        name
+    )
+}
+
+/// Reads a cookie sent with the request
+fn read_cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(cookie_name, _)| *cookie_name == name)
+        .map(|(_, value)| value.to_string())
+}
+
+/// Reads the ID of the request, recorded with its SSO events
+fn request_id(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Builds the router of the SSO endpoints
+pub fn router(state: SsoState) -> Router {
+    Router::new()
+        .route("/sso/providers/import-metadata", post(import_saml_metadata))
+        .route(
+            "/sso/providers/from-preset",
+            post(create_provider_from_preset),
+        )
+        .route("/sso/presets", get(list_presets))
+        .route("/sso/events", get(list_auth_events))
+        .route(
+            "/sso/providers/:id",
+            patch(update_provider).delete(delete_provider),
+        )
+        .route("/sso/providers/:id/enable", post(enable_provider))
+        .route("/sso/providers/:id/disable", post(disable_provider))
+        .route("/sso/signing-key/promote", post(promote_signing_key))
+        .route("/sso/:provider/metadata", get(get_metadata))
+        .route("/sso/:provider/login", get(login))
+        .route("/sso/:provider/acs", post(saml_acs))
+        .route("/sso/:provider/callback", get(oidc_callback))
+        .route("/sso/:provider/wsfed", post(wsfed_reply))
+        .route("/sso/:provider/token", get(access_token))
+        .route("/sso/:provider/logout", post(logout))
+        .route("/sso/:provider/slo", get(saml_slo))
+        .route("/sso/logout/callback", get(oidc_post_logout))
+        .route(
+            "/sso/:provider/frontchannel-logout",
+            get(oidc_frontchannel_logout),
+        )
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{header::USER_AGENT, HeaderValue};
+
+    #[test]
+    fn test_flow_cookies() {
+        let cookie = flow_cookie(STATE_COOKIE, "abc123");
+        assert!(cookie.starts_with("sso_state=abc123;"));
+        assert!(cookie.contains("HttpOnly"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            COOKIE,
+            HeaderValue::from_static("theme=dark; sso_state=abc123; sso_nonce=n0nce"),
+        );
+        assert_eq!(
+            read_cookie(&headers, STATE_COOKIE).as_deref(),
+            Some("abc123")
+        );
+        assert_eq!(read_cookie(&headers, "sso_nonce").as_deref(), Some("n0nce"));
+        assert_eq!(read_cookie(&headers, "missing"), None);
+
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("203.0.113.7, 10.0.0.1"),
+        );
+        headers.insert(USER_AGENT, HeaderValue::from_static("Browser/1.0"));
+        let context = LoginContext::from_headers(&headers);
+        assert_eq!(context.ip_address, Some("203.0.113.7".parse().unwrap()));
+        assert_eq!(context.user_agent.as_deref(), Some("Browser/1.0"));
+    }
+}
@@ -1,20 +1,45 @@
-//! SSO module for handling SAML and OIDC authentication
+//! SSO module for handling SAML, OIDC and WS-Federation authentication
+mod flow;
+mod handlers;
 mod models;
 mod saml;
 mod oidc;
+mod presets;
 mod repository;
 mod service;
+mod wsfed;
+mod xmldsig;
 
-pub use models::{SsoProvider, SsoProviderType, SsoUserMapping, SsoSession};
+pub use flow::{InMemoryOidcFlowStore, OidcFlow, OidcFlowStore, RedisOidcFlowStore};
+pub use handlers::{router, SsoState};
+pub use presets::{SsoPreset, SsoPresetInfo, SsoPresetRequest};
+pub use models::{
+    AttributeMapping, GroupRole, OidcValidation, RoleMapping, SamlIdpMetadata, SamlMetadataImport,
+    SamlMetadataImportRequest, SsoLinkProof, SsoLinkRequest, SsoLoginResolution, SsoLoginResponse, SsoProvider, SsoProviderType, SsoUserMapping,
+    SsoProfile, SsoSession, SsoAuthEventQuery, SsoAuthStage, SsoFailureCategory,
+};
 pub use service::SsoService;
+pub use wsfed::parse_wsfed_metadata;
+
+use std::sync::Arc;
 
 use crate::{
-    core::database::Database,
-    shared::error::Result,
+    core::{config::{RedisConfig, SecretsConfig, SsoConfig}, database::Database},
+    shared::{error::Result, secrets::SchemeSecretResolver},
 };
 
 /// Creates a new SSO service
-pub async fn create_sso_service(db: Database) -> Result<SsoService> {
+///
+/// Pending OIDC logins are kept in Redis, so any instance can complete them.
+pub async fn create_sso_service(
+    db: Database,
+    config: &SsoConfig,
+    secrets: &SecretsConfig,
+    redis: &RedisConfig,
+) -> Result<SsoService> {
+    let users = crate::modules::identity::repository::UserRepository::new(db.get_pool());
     let repository = repository::SsoRepository::new(db);
-    Ok(SsoService::new(repository))
-}
\ No newline at end of file
+    Ok(SsoService::new(repository, users, config)?
+        .with_secret_resolver(SchemeSecretResolver::from_config(secrets))
+        .with_flow_store(Arc::new(flow::RedisOidcFlowStore::new(&redis.url)?)))
+}
@@ -1,8 +1,12 @@
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
-use crate::shared::types::{TenantId, UserId};
+use crate::shared::{
+    error::Error,
+    types::{TenantId, UserId},
+};
 
 /// SSO provider type enum
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -12,6 +16,8 @@ pub enum SsoProviderType {
     Saml,
     /// OpenID Connect provider
     Oidc,
+    /// WS-Federation security token service, e.g. ADFS
+    WsFed,
 }
 
 impl std::fmt::Display for SsoProviderType {
@@ -19,6 +25,7 @@ impl std::fmt::Display for SsoProviderType {
         match self {
             SsoProviderType::Saml => write!(f, "saml"),
             SsoProviderType::Oidc => write!(f, "oidc"),
+            SsoProviderType::WsFed => write!(f, "wsfed"),
         }
     }
 }
@@ -39,12 +46,398 @@ pub struct SsoProvider {
     pub single_logout_url: Option<String>,
     pub client_id: Option<String>,
     pub client_secret: Option<String>,
+    /// Reference to the client secret in an external secret store, used instead
+    /// of storing the secret, e.g. `vault:secret/data/sso/okta#client_secret`
+    #[serde(default)]
+    pub client_secret_ref: Option<String>,
     pub issuer: Option<String>,
     pub discovery_url: Option<String>,
+    #[serde(default)]
+    pub oidc_validation: OidcValidation,
+    /// Accepts SAML responses the IdP sends without a prior authentication request
+    #[serde(default)]
+    pub allow_idp_initiated: bool,
+    /// Algorithms the SAML requests of the service provider are signed with
+    #[serde(default)]
+    pub saml_signing: SamlSigning,
+    /// Keeps the OIDC access and refresh tokens of logins, encrypted, for calling upstream APIs
+    #[serde(default)]
+    pub store_tokens: bool,
+    /// Scopes requested on top of the login scopes, e.g. ones of Microsoft Graph
+    #[serde(default)]
+    pub token_scopes: Vec<String>,
+    #[serde(default)]
+    pub attribute_mapping: AttributeMapping,
+    #[serde(default)]
+    pub role_mapping: RoleMapping,
+    /// Redirect URIs an OIDC login may return to, the first being the default
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
 }
 
+/// Validation controls applied to the ID tokens of an OIDC provider
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OidcValidation {
+    /// JWS algorithms accepted for ID token signatures, e.g. `RS256`; empty
+    /// accepts the algorithms advertised by the provider
+    pub allowed_algorithms: Vec<String>,
+    /// Requires the `iss` claim to equal the configured issuer byte for byte;
+    /// otherwise a trailing slash difference is tolerated
+    pub issuer_exact_match: bool,
+    /// Rejects tokens whose email is not marked as verified
+    pub require_email_verified: bool,
+    /// Hosted domains accepted in the `hd` claim; empty accepts any
+    pub allowed_hosted_domains: Vec<String>,
+    /// Directory tenants accepted in the `tid` claim; empty accepts any
+    pub allowed_tenant_ids: Vec<String>,
+}
+
+impl Default for OidcValidation {
+    fn default() -> Self {
+        Self {
+            allowed_algorithms: Vec::new(),
+            issuer_exact_match: true,
+            require_email_verified: true,
+            allowed_hosted_domains: Vec::new(),
+            allowed_tenant_ids: Vec::new(),
+        }
+    }
+}
+
+/// Algorithm signing the SAML requests of the service provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SamlSignatureAlgorithm {
+    #[default]
+    RsaSha256,
+    RsaSha384,
+    RsaSha512,
+    /// ECDSA on the P-256 curve
+    EcdsaSha256,
+    /// ECDSA on the P-384 curve
+    EcdsaSha384,
+}
+
+impl SamlSignatureAlgorithm {
+    /// All supported algorithms
+    pub const ALL: [SamlSignatureAlgorithm; 5] = [
+        SamlSignatureAlgorithm::RsaSha256,
+        SamlSignatureAlgorithm::RsaSha384,
+        SamlSignatureAlgorithm::RsaSha512,
+        SamlSignatureAlgorithm::EcdsaSha256,
+        SamlSignatureAlgorithm::EcdsaSha384,
+    ];
+
+    /// Gets the name the algorithm is stored with
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SamlSignatureAlgorithm::RsaSha256 => "rsa-sha256",
+            SamlSignatureAlgorithm::RsaSha384 => "rsa-sha384",
+            SamlSignatureAlgorithm::RsaSha512 => "rsa-sha512",
+            SamlSignatureAlgorithm::EcdsaSha256 => "ecdsa-sha256",
+            SamlSignatureAlgorithm::EcdsaSha384 => "ecdsa-sha384",
+        }
+    }
+
+    /// Gets the XML signature URI identifying the algorithm (RFC 6931)
+    pub fn uri(&self) -> &'static str {
+        match self {
+            SamlSignatureAlgorithm::RsaSha256 => {
+                "http://www.w3.org/2001/04/xmldsig-more#rsa-sha256"
+            },
+            SamlSignatureAlgorithm::RsaSha384 => {
+                "http://www.w3.org/2001/04/xmldsig-more#rsa-sha384"
+            },
+            SamlSignatureAlgorithm::RsaSha512 => {
+                "http://www.w3.org/2001/04/xmldsig-more#rsa-sha512"
+            },
+            SamlSignatureAlgorithm::EcdsaSha256 => {
+                "http://www.w3.org/2001/04/xmldsig-more#ecdsa-sha256"
+            },
+            SamlSignatureAlgorithm::EcdsaSha384 => {
+                "http://www.w3.org/2001/04/xmldsig-more#ecdsa-sha384"
+            },
+        }
+    }
+
+    /// Finds the algorithm identified by an XML signature URI
+    pub fn from_uri(uri: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.uri() == uri)
+    }
+
+    /// Checks if the algorithm signs with an elliptic curve key
+    pub fn is_ecdsa(&self) -> bool {
+        matches!(
+            self,
+            SamlSignatureAlgorithm::EcdsaSha256 | SamlSignatureAlgorithm::EcdsaSha384
+        )
+    }
+}
+
+impl std::str::FromStr for SamlSignatureAlgorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.as_str() == s)
+            .ok_or_else(|| Error::Internal(format!("Invalid SAML signature algorithm {}", s)))
+    }
+}
+
+/// Digest algorithm of the references in XML signatures of the service provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SamlDigestAlgorithm {
+    #[default]
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl SamlDigestAlgorithm {
+    /// All supported algorithms
+    pub const ALL: [SamlDigestAlgorithm; 3] = [
+        SamlDigestAlgorithm::Sha256,
+        SamlDigestAlgorithm::Sha384,
+        SamlDigestAlgorithm::Sha512,
+    ];
+
+    /// Gets the name the algorithm is stored with
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SamlDigestAlgorithm::Sha256 => "sha256",
+            SamlDigestAlgorithm::Sha384 => "sha384",
+            SamlDigestAlgorithm::Sha512 => "sha512",
+        }
+    }
+
+    /// Gets the XML signature URI identifying the algorithm
+    pub fn uri(&self) -> &'static str {
+        match self {
+            SamlDigestAlgorithm::Sha256 => "http://www.w3.org/2001/04/xmlenc#sha256",
+            SamlDigestAlgorithm::Sha384 => "http://www.w3.org/2001/04/xmldsig-more#sha384",
+            SamlDigestAlgorithm::Sha512 => "http://www.w3.org/2001/04/xmlenc#sha512",
+        }
+    }
+
+    /// Finds the algorithm identified by an XML signature URI
+    pub fn from_uri(uri: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.uri() == uri)
+    }
+}
+
+impl std::str::FromStr for SamlDigestAlgorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.as_str() == s)
+            .ok_or_else(|| Error::Internal(format!("Invalid SAML digest algorithm {}", s)))
+    }
+}
+
+/// Canonicalization applied to XML before it is digested and signed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SamlCanonicalization {
+    /// Exclusive canonicalization, which most IdPs expect
+    #[default]
+    ExclusiveC14n,
+    ExclusiveC14nWithComments,
+    /// Inclusive canonicalization 1.0, still required by some older IdPs
+    InclusiveC14n,
+}
+
+impl SamlCanonicalization {
+    /// All supported canonicalization methods
+    pub const ALL: [SamlCanonicalization; 3] = [
+        SamlCanonicalization::ExclusiveC14n,
+        SamlCanonicalization::ExclusiveC14nWithComments,
+        SamlCanonicalization::InclusiveC14n,
+    ];
+
+    /// Gets the name the method is stored with
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SamlCanonicalization::ExclusiveC14n => "exclusive-c14n",
+            SamlCanonicalization::ExclusiveC14nWithComments => "exclusive-c14n-with-comments",
+            SamlCanonicalization::InclusiveC14n => "inclusive-c14n",
+        }
+    }
+
+    /// Gets the XML signature URI identifying the method
+    pub fn uri(&self) -> &'static str {
+        match self {
+            SamlCanonicalization::ExclusiveC14n => "http://www.w3.org/2001/10/xml-exc-c14n#",
+            SamlCanonicalization::ExclusiveC14nWithComments => {
+                "http://www.w3.org/2001/10/xml-exc-c14n#WithComments"
+            },
+            SamlCanonicalization::InclusiveC14n => {
+                "http://www.w3.org/TR/2001/REC-xml-c14n-20010315"
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for SamlCanonicalization {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|method| method.as_str() == s)
+            .ok_or_else(|| Error::Internal(format!("Invalid SAML canonicalization {}", s)))
+    }
+}
+
+/// Algorithms the SAML requests of the service provider are signed with
+///
+/// The defaults suit most IdPs; some only accept other algorithms.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamlSigning {
+    /// Signs redirect binding messages and embedded XML signatures; must fit the SP key
+    pub signature_algorithm: SamlSignatureAlgorithm,
+    pub digest_algorithm: SamlDigestAlgorithm,
+    pub canonicalization: SamlCanonicalization,
+}
+
+/// Attributes a provider's SAML assertions or OIDC ID tokens carry profile fields in
+///
+/// Unset fields are read from the attributes identity providers commonly use.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AttributeMapping {
+    pub email: Option<String>,
+    pub name: Option<String>,
+    pub locale: Option<String>,
+    /// Source attribute of each custom profile attribute, by profile attribute name
+    pub custom: BTreeMap<String, String>,
+}
+
+impl AttributeMapping {
+    /// Reads the email of a login, falling back to the one the protocol provides
+    pub fn email(&self, attributes: &HashMap<String, Vec<String>>, default: &str) -> String {
+        self.email
+            .as_deref()
+            .and_then(|name| first_value(attributes, name))
+            .unwrap_or_else(|| default.to_string())
+    }
+
+    /// Reads the profile of a login
+    pub fn profile(&self, attributes: &HashMap<String, Vec<String>>) -> SsoProfile {
+        let mapped = |configured: &Option<String>, defaults: &[&str]| match configured {
+            Some(name) => first_value(attributes, name),
+            None => defaults
+                .iter()
+                .find_map(|name| first_value(attributes, name)),
+        };
+        SsoProfile {
+            name: mapped(&self.name, &["name", "displayName"]),
+            locale: mapped(&self.locale, &["locale"]),
+            attributes: self
+                .custom
+                .iter()
+                .filter_map(|(field, source)| {
+                    first_value(attributes, source).map(|value| (field.clone(), value))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Gets the first value of an attribute
+fn first_value(attributes: &HashMap<String, Vec<String>>, name: &str) -> Option<String> {
+    attributes
+        .get(name)
+        .and_then(|values| values.first())
+        .cloned()
+}
+
+/// Local roles granted to the members of IdP groups
+///
+/// The roles of the mapping are owned by the IdP: every login grants those of
+/// the user's groups and revokes the others, however they were assigned.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoleMapping {
+    /// Attribute listing the groups of a user; `groups` and `memberOf` if unset
+    pub group_attribute: Option<String>,
+    pub groups: Vec<GroupRole>,
+}
+
+/// Role granted to the members of a group, compared case-insensitively
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupRole {
+    pub group: String,
+    pub role_id: Uuid,
+}
+
+impl RoleMapping {
+    /// Reads the groups of a login
+    pub fn groups(&self, attributes: &HashMap<String, Vec<String>>) -> Vec<String> {
+        match &self.group_attribute {
+            Some(name) => attributes.get(name).cloned().unwrap_or_default(),
+            None => ["groups", "memberOf"]
+                .iter()
+                .filter_map(|name| attributes.get(*name))
+                .flatten()
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Gets the roles the mapping grants or revokes
+    pub fn managed_roles(&self) -> BTreeSet<Uuid> {
+        self.groups.iter().map(|group| group.role_id).collect()
+    }
+
+    /// Gets the roles granted to a member of the given groups
+    pub fn granted_roles(&self, groups: &[String]) -> BTreeSet<Uuid> {
+        self.groups
+            .iter()
+            .filter(|mapped| {
+                groups
+                    .iter()
+                    .any(|group| group.eq_ignore_ascii_case(&mapped.group))
+            })
+            .map(|mapped| mapped.role_id)
+            .collect()
+    }
+}
+
+/// Profile of a user as last asserted by an identity provider
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SsoProfile {
+    pub name: Option<String>,
+    pub locale: Option<String>,
+    pub attributes: BTreeMap<String, String>,
+}
+
+/// Identity an SSO login was validated for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SsoIdentity {
+    pub external_id: String,
+    pub email: String,
+    pub profile: SsoProfile,
+    /// Groups of the user, as the provider's role mapping reads them
+    pub groups: Vec<String>,
+    /// SSO session kept for the login, which only known users get
+    pub session_id: Option<Uuid>,
+    /// Tokens of an OIDC login, if the provider stores them
+    pub tokens: Option<OidcTokens>,
+}
+
 impl SsoProvider {
     /// Creates a new SAML provider
     pub fn new_saml(
@@ -71,8 +464,17 @@ impl SsoProvider {
             single_logout_url,
             client_id: None,
             client_secret: None,
+            client_secret_ref: None,
             issuer: None,
             discovery_url: None,
+            oidc_validation: OidcValidation::default(),
+            allow_idp_initiated: false,
+            saml_signing: SamlSigning::default(),
+            store_tokens: false,
+            token_scopes: Vec::new(),
+            attribute_mapping: AttributeMapping::default(),
+            role_mapping: RoleMapping::default(),
+            redirect_uris: Vec::new(),
             created_at: OffsetDateTime::now_utc(),
             updated_at: OffsetDateTime::now_utc(),
         }
@@ -102,8 +504,17 @@ impl SsoProvider {
             single_logout_url: None,
             client_id: Some(client_id),
             client_secret: Some(client_secret),
+            client_secret_ref: None,
             issuer: Some(issuer),
             discovery_url,
+            oidc_validation: OidcValidation::default(),
+            allow_idp_initiated: false,
+            saml_signing: SamlSigning::default(),
+            store_tokens: false,
+            token_scopes: Vec::new(),
+            attribute_mapping: AttributeMapping::default(),
+            role_mapping: RoleMapping::default(),
+            redirect_uris: Vec::new(),
             created_at: OffsetDateTime::now_utc(),
             updated_at: OffsetDateTime::now_utc(),
         }
@@ -119,6 +530,9 @@ pub struct SsoUserMapping {
     pub provider_id: Uuid,
     pub external_id: String,
     pub email: String,
+    /// Profile synced from the provider on every login
+    #[serde(default)]
+    pub profile: SsoProfile,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
 }
@@ -139,12 +553,318 @@ impl SsoUserMapping {
             provider_id,
             external_id,
             email,
+            profile: SsoProfile::default(),
             created_at: OffsetDateTime::now_utc(),
             updated_at: OffsetDateTime::now_utc(),
         }
     }
 }
 
+/// Pending link between an SSO identity and an existing user with the same email
+///
+/// The mapping is only created once the user proved ownership of the
+/// existing account, either with its password or with an emailed code.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SsoLinkRequest {
+    pub id: Uuid,
+    pub tenant_id: TenantId,
+    pub user_id: UserId,
+    pub provider_id: Uuid,
+    pub external_id: String,
+    pub email: String,
+    #[serde(skip)]
+    pub code_hash: Option<String>,
+    pub attempts: i32,
+    pub expires_at: OffsetDateTime,
+    pub created_at: OffsetDateTime,
+}
+
+impl SsoLinkRequest {
+    /// Creates a new link request
+    pub fn new(
+        user_id: UserId,
+        tenant_id: TenantId,
+        provider_id: Uuid,
+        external_id: String,
+        email: String,
+        expires_at: OffsetDateTime,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            tenant_id,
+            user_id,
+            provider_id,
+            external_id,
+            email,
+            code_hash: None,
+            attempts: 0,
+            expires_at,
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// Checks if the link request is expired
+    pub fn is_expired(&self) -> bool {
+        OffsetDateTime::now_utc() >= self.expires_at
+    }
+}
+
+/// Proof of ownership of the existing account when linking an SSO identity
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "method", rename_all = "lowercase")]
+pub enum SsoLinkProof {
+    /// Password of the existing account
+    Password { password: String },
+    /// Code emailed to the existing account
+    Code { code: String },
+}
+
+/// Result of resolving the local user of an SSO login
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SsoLoginResolution {
+    /// The SSO identity is mapped to a user
+    Mapped(SsoUserMapping),
+    /// A user with the same email exists and must confirm the link
+    LinkRequired(SsoLinkRequest),
+    /// No local user matches the SSO identity
+    Unknown { external_id: String, email: String },
+}
+
+/// Changes to the configuration of an SSO provider; omitted fields stay as they are
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SsoProviderUpdate {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub metadata_url: Option<String>,
+    pub metadata_xml: Option<String>,
+    pub entity_id: Option<String>,
+    pub assertion_consumer_service_url: Option<String>,
+    pub single_logout_url: Option<String>,
+    pub client_id: Option<String>,
+    /// New client secret, e.g. when the IdP rotates it; replaces a secret reference
+    pub client_secret: Option<String>,
+    /// New secret reference; replaces a stored client secret
+    pub client_secret_ref: Option<String>,
+    pub issuer: Option<String>,
+    pub discovery_url: Option<String>,
+    pub oidc_validation: Option<OidcValidation>,
+    pub allow_idp_initiated: Option<bool>,
+    pub saml_signing: Option<SamlSigning>,
+    pub store_tokens: Option<bool>,
+    pub token_scopes: Option<Vec<String>>,
+    pub attribute_mapping: Option<AttributeMapping>,
+    pub role_mapping: Option<RoleMapping>,
+    pub redirect_uris: Option<Vec<String>>,
+}
+
+impl SsoProviderUpdate {
+    /// Applies the changes to a provider
+    pub fn apply(self, provider: &mut SsoProvider) {
+        if let Some(name) = self.name {
+            provider.name = name;
+        }
+        if let Some(oidc_validation) = self.oidc_validation {
+            provider.oidc_validation = oidc_validation;
+        }
+        if let Some(allow_idp_initiated) = self.allow_idp_initiated {
+            provider.allow_idp_initiated = allow_idp_initiated;
+        }
+        if let Some(saml_signing) = self.saml_signing {
+            provider.saml_signing = saml_signing;
+        }
+        if let Some(store_tokens) = self.store_tokens {
+            provider.store_tokens = store_tokens;
+        }
+        if let Some(token_scopes) = self.token_scopes {
+            provider.token_scopes = token_scopes;
+        }
+        if let Some(attribute_mapping) = self.attribute_mapping {
+            provider.attribute_mapping = attribute_mapping;
+        }
+        if let Some(role_mapping) = self.role_mapping {
+            provider.role_mapping = role_mapping;
+        }
+        if let Some(redirect_uris) = self.redirect_uris {
+            provider.redirect_uris = redirect_uris;
+        }
+        // A provider keeps either a secret or a reference to one
+        if self.client_secret.is_some() {
+            provider.client_secret_ref = None;
+        }
+        if self.client_secret_ref.is_some() {
+            provider.client_secret = None;
+            provider.client_secret_ref = self.client_secret_ref;
+        }
+        let fields = [
+            (&mut provider.description, self.description),
+            (&mut provider.metadata_url, self.metadata_url),
+            (&mut provider.metadata_xml, self.metadata_xml),
+            (&mut provider.entity_id, self.entity_id),
+            (
+                &mut provider.assertion_consumer_service_url,
+                self.assertion_consumer_service_url,
+            ),
+            (&mut provider.single_logout_url, self.single_logout_url),
+            (&mut provider.client_id, self.client_id),
+            (&mut provider.client_secret, self.client_secret),
+            (&mut provider.issuer, self.issuer),
+            (&mut provider.discovery_url, self.discovery_url),
+        ];
+        for (field, value) in fields {
+            if value.is_some() {
+                *field = value;
+            }
+        }
+        provider.updated_at = OffsetDateTime::now_utc();
+    }
+}
+
+/// Outcome of a completed SSO login returned to the browser
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SsoLoginResponse {
+    /// A session was opened for the mapped user
+    Authenticated {
+        token: String,
+        /// Token for renewing the session, if refresh tokens are enabled
+        #[serde(skip_serializing_if = "Option::is_none")]
+        refresh_token: Option<String>,
+        expires_at: OffsetDateTime,
+    },
+    /// The user must confirm linking the SSO identity to their account
+    LinkRequired {
+        link_id: Uuid,
+        expires_at: OffsetDateTime,
+    },
+}
+
+/// Request to create a SAML provider from the metadata of its identity provider
+///
+/// Exactly one of `metadata_xml` and `metadata_url` must be given.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SamlMetadataImportRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub metadata_xml: Option<String>,
+    pub metadata_url: Option<String>,
+    pub assertion_consumer_service_url: String,
+}
+
+/// Identity provider settings extracted from SAML metadata
+#[derive(Debug, Clone, Serialize)]
+pub struct SamlIdpMetadata {
+    pub entity_id: String,
+    pub sso_url: String,
+    pub slo_url: Option<String>,
+    /// Base64 encoded DER signing certificates
+    pub certificates: Vec<String>,
+    /// When the first of the signing certificates expires
+    pub certificates_expire_at: OffsetDateTime,
+}
+
+/// Subject of a validated SAML assertion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SamlAssertion {
+    /// Assertion ID, used to reject replays
+    pub id: String,
+    pub name_id: String,
+    pub session_index: Option<String>,
+    pub email: Option<String>,
+    /// Values of the attributes of the assertion, by attribute name
+    pub attributes: HashMap<String, Vec<String>>,
+    /// When the assertion stops being valid
+    pub expires_at: OffsetDateTime,
+}
+
+/// Subject of a validated OIDC login
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OidcLogin {
+    pub subject: String,
+    pub email: String,
+    /// Session of the provider the user logged in with, from the `sid` claim
+    pub session_id: Option<String>,
+    pub id_token: String,
+    /// Values of the claims of the ID token, by claim name
+    pub claims: HashMap<String, Vec<String>>,
+    pub tokens: OidcTokens,
+}
+
+/// Access and refresh token an OIDC provider issued
+#[derive(Clone, PartialEq, Eq)]
+pub struct OidcTokens {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<OffsetDateTime>,
+    /// Scopes the provider granted
+    pub scopes: Vec<String>,
+}
+
+impl std::fmt::Debug for OidcTokens {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OidcTokens")
+            .field("expires_at", &self.expires_at)
+            .field("scopes", &self.scopes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OidcTokens {
+    /// Checks if the access token expires within the given time
+    pub fn expires_within(&self, duration: time::Duration) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= OffsetDateTime::now_utc() + duration)
+    }
+}
+
+/// Stored tokens of the last OIDC login of a user at a provider
+#[derive(Debug, Clone)]
+pub struct SsoToken {
+    pub provider_id: Uuid,
+    pub user_id: UserId,
+    pub tenant_id: TenantId,
+    pub tokens: OidcTokens,
+    pub updated_at: OffsetDateTime,
+}
+
+/// IdP access token handed to the application for calling the customer's APIs
+#[derive(Debug, Clone, Serialize)]
+pub struct SsoAccessToken {
+    pub access_token: String,
+    pub expires_at: Option<OffsetDateTime>,
+    pub scopes: Vec<String>,
+}
+
+/// Logout request an identity provider sent for one of its subjects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SamlLogoutRequest {
+    pub id: String,
+    pub name_id: String,
+    /// Sessions to end; all sessions of the subject if empty
+    pub session_indexes: Vec<String>,
+}
+
+/// Answer of an identity provider to a logout request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SamlLogoutResponse {
+    pub in_response_to: Option<String>,
+    pub status: String,
+}
+
+/// Where to send the browser to also end the session at the identity provider
+#[derive(Debug, Clone, Serialize)]
+pub struct SsoLogoutResponse {
+    /// `None` if the provider has no session to end or no logout endpoint
+    pub redirect_url: Option<String>,
+}
+
+/// SAML provider created from imported metadata
+#[derive(Debug, Clone, Serialize)]
+pub struct SamlMetadataImport {
+    pub provider: SsoProvider,
+    pub metadata: SamlIdpMetadata,
+}
+
 /// SSO session
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SsoSession {
@@ -154,6 +874,9 @@ pub struct SsoSession {
     pub provider_id: Uuid,
     pub session_index: Option<String>,
     pub name_id: Option<String>,
+    /// ID token of an OIDC login, kept as hint for logging out at the provider
+    #[serde(default, skip_serializing)]
+    pub id_token: Option<String>,
     pub created_at: OffsetDateTime,
     pub expires_at: OffsetDateTime,
 }
@@ -175,17 +898,102 @@ impl SsoSession {
             provider_id,
             session_index,
             name_id,
+            id_token: None,
             created_at: OffsetDateTime::now_utc(),
             expires_at,
         }
     }
 
+    /// Keeps the ID token the session was created with
+    pub fn with_id_token(mut self, id_token: String) -> Self {
+        self.id_token = Some(id_token);
+        self
+    }
+
     /// Checks if the session is expired
     pub fn is_expired(&self) -> bool {
         OffsetDateTime::now_utc() >= self.expires_at
     }
 }
 
+/// Stage of an SSO login recorded in the event trail of its provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SsoAuthStage {
+    /// The user was sent to the identity provider
+    Initiated,
+    /// The user returned and was signed in or asked to link their account
+    Succeeded,
+    /// The login was rejected or could not be completed
+    Failed,
+}
+
+impl SsoAuthStage {
+    /// All stages, in the order of a login
+    pub const ALL: [SsoAuthStage; 3] = [
+        SsoAuthStage::Initiated,
+        SsoAuthStage::Succeeded,
+        SsoAuthStage::Failed,
+    ];
+
+    /// Gets the audit log action events of the stage are recorded with
+    pub fn action(&self) -> &'static str {
+        match self {
+            SsoAuthStage::Initiated => "sso_login_initiated",
+            SsoAuthStage::Succeeded => "sso_login_succeeded",
+            SsoAuthStage::Failed => "sso_login_failed",
+        }
+    }
+}
+
+/// Why an SSO login failed, coarse enough to tell admins where to look
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SsoFailureCategory {
+    /// The response of the IdP or the identity in it was rejected, e.g. for a
+    /// bad signature, a stale state or an unknown user
+    Rejected,
+    /// The provider is misconfigured or the request was malformed
+    InvalidRequest,
+    /// The IdP or a secret store could not be reached
+    ProviderUnavailable,
+    /// The provider or a record it needs does not exist
+    NotFound,
+    /// Any other failure, e.g. of the database
+    Internal,
+}
+
+impl SsoFailureCategory {
+    /// Categorizes the error a login failed with
+    pub fn from_error(error: &Error) -> Self {
+        match error {
+            Error::Authentication(_) | Error::Authorization(_) => SsoFailureCategory::Rejected,
+            Error::Validation(_) | Error::InvalidInput(_) => SsoFailureCategory::InvalidRequest,
+            Error::Unavailable(_) => SsoFailureCategory::ProviderUnavailable,
+            Error::NotFound(_) => SsoFailureCategory::NotFound,
+            _ => SsoFailureCategory::Internal,
+        }
+    }
+}
+
+/// Query parameters of the SSO event trail of a tenant
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SsoAuthEventQuery {
+    /// Only events of this provider; those of all providers of the tenant if unset
+    pub provider_id: Option<Uuid>,
+    /// Only login stages of this kind; all stages if unset
+    pub stage: Option<SsoAuthStage>,
+    /// Start of the time range, inclusive
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub from: Option<OffsetDateTime>,
+    /// End of the time range, exclusive
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub to: Option<OffsetDateTime>,
+    /// Only events of this request, e.g. from the `x-request-id` header
+    pub request_id: Option<String>,
+    pub limit: Option<i64>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -275,5 +1083,241 @@ mod tests {
         assert_eq!(mapping.provider_id, provider_id);
         assert_eq!(mapping.external_id, "external_id");
         assert_eq!(mapping.email, "user@example.com");
+        assert_eq!(mapping.profile, SsoProfile::default());
+    }
+
+    #[test]
+    fn test_attribute_mapping() {
+        let attributes: HashMap<String, Vec<String>> = [
+            ("mail", vec!["jane@example.com"]),
+            ("displayName", vec!["Jane Doe"]),
+            ("preferredLanguage", vec!["de-CH"]),
+            ("department", vec!["Finance", "Audit"]),
+        ]
+        .into_iter()
+        .map(|(name, values)| {
+            (
+                name.to_string(),
+                values.into_iter().map(String::from).collect(),
+            )
+        })
+        .collect();
+
+        // Unset fields fall back to the common attributes
+        let mapping = AttributeMapping::default();
+        assert_eq!(
+            mapping.email(&attributes, "subject@example.com"),
+            "subject@example.com"
+        );
+        let profile = mapping.profile(&attributes);
+        assert_eq!(profile.name.as_deref(), Some("Jane Doe"));
+        assert_eq!(profile.locale, None);
+        assert!(profile.attributes.is_empty());
+
+        let mapping: AttributeMapping = serde_json::from_str(
+            r#"{
+                "email": "mail",
+                "locale": "preferredLanguage",
+                "custom": {"department": "department", "cost_center": "costCenter"}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            mapping.email(&attributes, "subject@example.com"),
+            "jane@example.com"
+        );
+        let profile = mapping.profile(&attributes);
+        assert_eq!(profile.locale.as_deref(), Some("de-CH"));
+        assert_eq!(
+            profile.attributes.get("department").map(String::as_str),
+            Some("Finance")
+        );
+        assert!(!profile.attributes.contains_key("cost_center"));
+    }
+
+    #[test]
+    fn test_role_mapping() {
+        let finance = Uuid::new_v4();
+        let admins = Uuid::new_v4();
+        let mapping = RoleMapping {
+            group_attribute: None,
+            groups: vec![
+                GroupRole {
+                    group: "CN=Finance,OU=Groups,DC=example,DC=com".to_string(),
+                    role_id: finance,
+                },
+                GroupRole {
+                    group: "admins".to_string(),
+                    role_id: admins,
+                },
+            ],
+        };
+        let attributes: HashMap<String, Vec<String>> = [
+            (
+                "memberOf".to_string(),
+                vec!["cn=finance,ou=groups,dc=example,dc=com".to_string()],
+            ),
+            ("groups".to_string(), vec!["staff".to_string()]),
+        ]
+        .into_iter()
+        .collect();
+
+        let groups = mapping.groups(&attributes);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(mapping.granted_roles(&groups), BTreeSet::from([finance]));
+        assert_eq!(mapping.managed_roles(), BTreeSet::from([finance, admins]));
+
+        // A configured attribute replaces the default ones
+        let mapping = RoleMapping {
+            group_attribute: Some("groups".to_string()),
+            ..mapping
+        };
+        assert_eq!(mapping.groups(&attributes), vec!["staff".to_string()]);
+        assert!(mapping
+            .granted_roles(&mapping.groups(&attributes))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_sso_provider_update() {
+        let mut provider = SsoProvider::new_oidc(
+            TenantId::new(),
+            "Okta".to_string(),
+            Some("Workforce".to_string()),
+            "client".to_string(),
+            "old-secret".to_string(),
+            "https://acme.okta.com".to_string(),
+            None,
+        );
+        let update: SsoProviderUpdate =
+            serde_json::from_str(r#"{"name": "Okta Prod", "client_secret": "new-secret"}"#)
+                .unwrap();
+        update.apply(&mut provider);
+
+        assert_eq!(provider.name, "Okta Prod");
+        assert_eq!(provider.client_secret.as_deref(), Some("new-secret"));
+        // Omitted fields stay as they are
+        assert_eq!(provider.description.as_deref(), Some("Workforce"));
+        assert_eq!(provider.client_id.as_deref(), Some("client"));
+        assert_eq!(provider.oidc_validation, OidcValidation::default());
+        assert!(!provider.allow_idp_initiated);
+
+        let update: SsoProviderUpdate =
+            serde_json::from_str(r#"{"allow_idp_initiated": true}"#).unwrap();
+        update.apply(&mut provider);
+        assert!(provider.allow_idp_initiated);
+        assert_eq!(provider.name, "Okta Prod");
+
+        // Secrets and secret references replace each other
+        let update: SsoProviderUpdate = serde_json::from_str(
+            r#"{"client_secret_ref": "vault:secret/data/sso/okta#client_secret"}"#,
+        )
+        .unwrap();
+        update.apply(&mut provider);
+        assert!(provider.client_secret.is_none());
+        assert!(provider.client_secret_ref.is_some());
+        let update: SsoProviderUpdate =
+            serde_json::from_str(r#"{"client_secret": "inline-secret"}"#).unwrap();
+        update.apply(&mut provider);
+        assert_eq!(provider.client_secret.as_deref(), Some("inline-secret"));
+        assert!(provider.client_secret_ref.is_none());
+    }
+
+    #[test]
+    fn test_sso_link_request() {
+        let request = SsoLinkRequest::new(
+            UserId::new(),
+            TenantId::new(),
+            Uuid::new_v4(),
+            "external_id".to_string(),
+            "user@example.com".to_string(),
+            OffsetDateTime::now_utc() + Duration::minutes(15),
+        );
+        assert!(!request.is_expired());
+        assert_eq!(request.attempts, 0);
+        assert!(request.code_hash.is_none());
+
+        let proof: SsoLinkProof =
+            serde_json::from_str(r#"{"method": "code", "code": "123456"}"#).unwrap();
+        assert!(matches!(proof, SsoLinkProof::Code { code } if code == "123456"));
+        let proof: SsoLinkProof =
+            serde_json::from_str(r#"{"method": "password", "password": "secret"}"#).unwrap();
+        assert!(matches!(proof, SsoLinkProof::Password { .. }));
+    }
+
+    #[test]
+    fn test_sso_auth_events() {
+        assert_eq!(
+            SsoFailureCategory::from_error(&Error::Authentication("Invalid nonce".to_string())),
+            SsoFailureCategory::Rejected
+        );
+        assert_eq!(
+            SsoFailureCategory::from_error(&Error::Unavailable("timeout".to_string())),
+            SsoFailureCategory::ProviderUnavailable
+        );
+        assert_eq!(
+            SsoFailureCategory::from_error(&Error::Internal("pool closed".to_string())),
+            SsoFailureCategory::Internal
+        );
+
+        let query: SsoAuthEventQuery = serde_json::from_str(
+            r#"{"stage": "failed", "from": "2025-01-28T00:00:00Z", "to": "2025-01-29T00:00:00Z"}"#,
+        )
+        .unwrap();
+        assert_eq!(query.stage, Some(SsoAuthStage::Failed));
+        assert_eq!(query.from.unwrap().day(), 28);
+        assert!(query.provider_id.is_none());
+        assert_eq!(SsoAuthStage::Failed.action(), "sso_login_failed");
+    }
+
+    #[test]
+    fn test_saml_signing() {
+        let signing: SamlSigning = serde_json::from_str(
+            r#"{"signature_algorithm": "ecdsa-sha384", "canonicalization": "inclusive-c14n"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            signing.signature_algorithm,
+            SamlSignatureAlgorithm::EcdsaSha384
+        );
+        assert_eq!(signing.digest_algorithm, SamlDigestAlgorithm::Sha256);
+        assert_eq!(
+            signing.canonicalization,
+            SamlCanonicalization::InclusiveC14n
+        );
+        assert!(signing.signature_algorithm.is_ecdsa());
+        assert!(!SamlSignatureAlgorithm::RsaSha512.is_ecdsa());
+
+        // Stored names and signature URIs map back to the algorithms
+        for algorithm in SamlSignatureAlgorithm::ALL {
+            assert_eq!(
+                algorithm
+                    .as_str()
+                    .parse::<SamlSignatureAlgorithm>()
+                    .unwrap(),
+                algorithm
+            );
+            assert_eq!(
+                SamlSignatureAlgorithm::from_uri(algorithm.uri()),
+                Some(algorithm)
+            );
+        }
+        for algorithm in SamlDigestAlgorithm::ALL {
+            assert_eq!(
+                algorithm.as_str().parse::<SamlDigestAlgorithm>().unwrap(),
+                algorithm
+            );
+        }
+        for method in SamlCanonicalization::ALL {
+            assert_eq!(
+                method.as_str().parse::<SamlCanonicalization>().unwrap(),
+                method
+            );
+        }
+        assert!("rsa-sha1".parse::<SamlSignatureAlgorithm>().is_err());
+        assert_eq!(
+            SamlSignatureAlgorithm::from_uri("http://www.w3.org/2000/09/xmldsig#rsa-sha1"),
+            None
+        );
     }
 }
\ No newline at end of file
@@ -1,23 +1,259 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use openidconnect::{
     core::{
-        CoreAuthenticationFlow, CoreClient, CoreIdToken, CoreIdTokenClaims, CoreProviderMetadata,
-        CoreResponseType, CoreTokenResponse,
+        CoreAuthenticationFlow, CoreClient, CoreJwsSigningAlgorithm, CoreProviderMetadata,
+        CoreTokenResponse,
     },
     reqwest::async_http_client,
-    AccessToken, AuthorizationCode, ClientId, ClientSecret, CsrfToken, IssuerUrl, Nonce, OAuth2TokenResponse,
-    RedirectUrl, Scope, TokenResponse,
+    AuthorizationCode, ClientId, ClientSecret, CsrfToken, IssuerUrl, Nonce, OAuth2TokenResponse,
+    PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, RefreshToken, RequestTokenError, Scope,
+    TokenResponse,
 };
-use time::OffsetDateTime;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use time::{Duration, OffsetDateTime};
 use url::Url;
 
 use crate::shared::error::{Error, Result};
 
-use super::models::SsoProvider;
+use super::models::{OidcLogin, OidcTokens, OidcValidation, SsoProvider};
+
+/// Seconds to wait for a provider to serve its discovery document
+const DISCOVERY_TIMEOUT_SECS: u64 = 10;
+
+/// Scopes of every login
+const LOGIN_SCOPES: &[&str] = &["openid", "email", "profile"];
+
+/// Scope asking the provider for a refresh token
+const OFFLINE_ACCESS_SCOPE: &str = "offline_access";
+
+/// Header fields of an ID token checked against the validation controls
+#[derive(Debug, Deserialize)]
+struct TokenHeader {
+    alg: String,
+}
+
+/// Claims of an ID token checked against the validation controls
+#[derive(Debug, Deserialize)]
+struct ControlledClaims {
+    iss: String,
+    email_verified: Option<bool>,
+    /// Hosted domain of Google Workspace accounts
+    hd: Option<String>,
+    /// Directory tenant of Microsoft Entra ID accounts
+    tid: Option<String>,
+}
+
+/// Logout settings of a provider's discovery document
+#[derive(Debug, Deserialize)]
+struct LogoutDiscovery {
+    end_session_endpoint: Option<String>,
+}
+
+/// Ensures the validation controls of a provider can be applied
+pub fn validate_oidc_controls(validation: &OidcValidation) -> Result<()> {
+    for alg in &validation.allowed_algorithms {
+        let parsed = serde_json::from_value::<CoreJwsSigningAlgorithm>(serde_json::Value::String(
+            alg.clone(),
+        ));
+        if !matches!(parsed, Ok(parsed) if parsed != CoreJwsSigningAlgorithm::None) {
+            return Err(Error::InvalidInput(format!(
+                "Unsupported signing algorithm {}",
+                alg
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Ensures the redirect URIs of a provider are safe to return logins to
+///
+/// Besides https URIs, http is allowed for loopback hosts and custom schemes
+/// for native apps, e.g. `com.example.app:/callback`.
+pub fn validate_redirect_uris(redirect_uris: &[String]) -> Result<()> {
+    for redirect_uri in redirect_uris {
+        let url = Url::parse(redirect_uri).map_err(|e| {
+            Error::InvalidInput(format!("Invalid redirect URI {}: {}", redirect_uri, e))
+        })?;
+        let allowed = match url.scheme() {
+            "https" => true,
+            "http" => matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]")),
+            "javascript" | "data" | "file" | "vbscript" | "blob" => false,
+            _ => true,
+        };
+        if !allowed || url.fragment().is_some() {
+            return Err(Error::InvalidInput(format!(
+                "Redirect URI {} is not allowed",
+                redirect_uri
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Applies the validation controls of a provider to an ID token
+///
+/// Only checks the controls; the signature and standard claims are verified by
+/// the OIDC client beforehand.
+pub fn enforce_oidc_controls(provider: &SsoProvider, id_token: &str) -> Result<()> {
+    let validation = &provider.oidc_validation;
+    let mut segments = id_token.split('.');
+    let (Some(header), Some(payload)) = (segments.next(), segments.next()) else {
+        return Err(Error::Authentication("Malformed ID token".to_string()));
+    };
+    let header: TokenHeader = decode_segment(header)?;
+    let claims: ControlledClaims = decode_segment(payload)?;
+
+    if !validation.allowed_algorithms.is_empty()
+        && !validation.allowed_algorithms.contains(&header.alg)
+    {
+        return Err(Error::Authentication(format!(
+            "ID token signed with disallowed algorithm {}",
+            header.alg
+        )));
+    }
+
+    let issuer = provider
+        .issuer
+        .as_deref()
+        .ok_or_else(|| Error::Internal("Missing issuer URL".to_string()))?;
+    let issuer_matches = if validation.issuer_exact_match {
+        claims.iss == issuer
+    } else {
+        claims.iss.trim_end_matches('/') == issuer.trim_end_matches('/')
+    };
+    if !issuer_matches {
+        return Err(Error::Authentication(format!(
+            "ID token issuer {} does not match {}",
+            claims.iss, issuer
+        )));
+    }
+
+    if validation.require_email_verified && claims.email_verified != Some(true) {
+        return Err(Error::Authentication(
+            "Email of ID token is not verified".to_string(),
+        ));
+    }
+    if !is_allowed(&validation.allowed_hosted_domains, claims.hd.as_deref()) {
+        return Err(Error::Authentication(
+            "Hosted domain of ID token is not allowed".to_string(),
+        ));
+    }
+    if !is_allowed(&validation.allowed_tenant_ids, claims.tid.as_deref()) {
+        return Err(Error::Authentication(
+            "Tenant of ID token is not allowed".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Checks a claim against an allow list, where an empty list allows anything
+fn is_allowed(allowed: &[String], value: Option<&str>) -> bool {
+    allowed.is_empty()
+        || value.is_some_and(|value| {
+            allowed
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(value))
+        })
+}
+
+/// Reads the claims of an already verified ID token as text values
+///
+/// Arrays become several values; objects and nulls are skipped.
+fn claim_values(id_token: &str) -> Result<HashMap<String, Vec<String>>> {
+    let payload = id_token
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| Error::Authentication("Malformed ID token".to_string()))?;
+    let claims: serde_json::Map<String, Value> = decode_segment(payload)?;
+
+    let text = |value: Value| match value {
+        Value::String(value) => Some(value),
+        Value::Bool(_) | Value::Number(_) => Some(value.to_string()),
+        Value::Null | Value::Array(_) | Value::Object(_) => None,
+    };
+    Ok(claims
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let values: Vec<String> = match value {
+                Value::Array(items) => items.into_iter().filter_map(text).collect(),
+                value => text(value).into_iter().collect(),
+            };
+            (!values.is_empty()).then_some((name, values))
+        })
+        .collect())
+}
+
+/// Gets the scopes a login at a provider requests
+///
+/// Providers storing tokens also ask for a refresh token and the scopes of the
+/// upstream APIs the tokens are used for.
+fn requested_scopes(provider: &SsoProvider) -> Vec<String> {
+    let mut scopes: Vec<String> = LOGIN_SCOPES.iter().map(|scope| scope.to_string()).collect();
+    if provider.store_tokens {
+        let extra = std::iter::once(OFFLINE_ACCESS_SCOPE)
+            .chain(provider.token_scopes.iter().map(String::as_str));
+        for scope in extra {
+            if !scopes.iter().any(|requested| requested == scope) {
+                scopes.push(scope.to_string());
+            }
+        }
+    }
+    scopes
+}
+
+/// Reads the access and refresh token of a token response
+///
+/// Providers omitting the granted scopes granted the requested ones.
+fn oidc_tokens(response: &CoreTokenResponse, requested: &[String]) -> OidcTokens {
+    OidcTokens {
+        access_token: response.access_token().secret().clone(),
+        refresh_token: response
+            .refresh_token()
+            .map(|refresh_token| refresh_token.secret().clone()),
+        expires_at: response.expires_in().map(|expires_in| {
+            OffsetDateTime::now_utc() + Duration::seconds(expires_in.as_secs() as i64)
+        }),
+        scopes: response
+            .scopes()
+            .map(|scopes| scopes.iter().map(|scope| scope.to_string()).collect())
+            .unwrap_or_else(|| requested.to_vec()),
+    }
+}
+
+/// Gets the URL of a provider's discovery document
+fn discovery_document_url(provider: &SsoProvider) -> Result<String> {
+    if let Some(discovery_url) = &provider.discovery_url {
+        return Ok(discovery_url.clone());
+    }
+    let issuer = provider
+        .issuer
+        .as_ref()
+        .ok_or_else(|| Error::Internal("Missing issuer URL".to_string()))?;
+    Ok(format!(
+        "{}/.well-known/openid-configuration",
+        issuer.trim_end_matches('/')
+    ))
+}
+
+/// Decodes a base64url encoded JSON segment of a token
+fn decode_segment<T: DeserializeOwned>(segment: &str) -> Result<T> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(segment)
+        .map_err(|e| Error::Authentication(format!("Malformed ID token: {}", e)))?;
+    serde_json::from_slice(&bytes)
+        .map_err(|e| Error::Authentication(format!("Malformed ID token: {}", e)))
+}
 
 /// OIDC configuration
 #[derive(Debug, Clone)]
 pub struct OidcConfig {
-    pub redirect_url: String,
+    /// Redirect URI of providers without registered redirect URIs
+    pub redirect_url: Option<String>,
+    /// Where providers return users after logging them out; providers usually
+    /// require it to be registered
+    pub post_logout_redirect_url: Option<String>,
 }
 
 /// OIDC service for handling OpenID Connect authentication
@@ -32,8 +268,35 @@ impl OidcService {
         Self { config }
     }
 
-    /// Creates an OIDC client for a provider
-    async fn create_client(&self, provider: &SsoProvider) -> Result<CoreClient> {
+    /// Picks the redirect URI of a login
+    ///
+    /// A requested URI must be registered with the provider; otherwise its first
+    /// URI, or the configured default, is used.
+    pub fn redirect_uri(&self, provider: &SsoProvider, requested: Option<&str>) -> Result<String> {
+        match requested {
+            Some(requested) if provider.redirect_uris.iter().any(|uri| uri == requested) => {
+                Ok(requested.to_string())
+            },
+            Some(_) => Err(Error::Validation(
+                "Redirect URI is not registered for this provider".to_string(),
+            )),
+            None => provider
+                .redirect_uris
+                .first()
+                .or(self.config.redirect_url.as_ref())
+                .cloned()
+                .ok_or_else(|| {
+                    Error::Internal("No redirect URI configured for this provider".to_string())
+                }),
+        }
+    }
+
+    /// Creates an OIDC client for a provider returning logins to the given redirect URI
+    async fn create_client(
+        &self,
+        provider: &SsoProvider,
+        redirect_uri: &str,
+    ) -> Result<CoreClient> {
         let issuer_url = provider
             .issuer
             .as_ref()
@@ -67,47 +330,60 @@ impl OidcService {
             .map_err(|e| Error::Internal(format!("Failed to discover provider metadata: {}", e)))?
         };
 
-        CoreClient::from_provider_metadata(
+        Ok(CoreClient::from_provider_metadata(
             provider_metadata,
             ClientId::new(client_id.clone()),
             Some(ClientSecret::new(client_secret.clone())),
         )
         .set_redirect_uri(
-            RedirectUrl::new(self.config.redirect_url.clone())
+            RedirectUrl::new(redirect_uri.to_string())
                 .map_err(|e| Error::Internal(format!("Invalid redirect URL: {}", e)))?,
-        )
-        .map_err(|e| Error::Internal(format!("Failed to create OIDC client: {}", e)))
+        ))
     }
 
-    /// Creates an authorization URL
-    pub async fn create_auth_url(&self, provider: &SsoProvider) -> Result<(Url, CsrfToken, Nonce)> {
-        let client = self.create_client(provider).await?;
+    /// Creates an authorization URL returning to the given or default redirect URI
+    ///
+    /// The code is bound to the returned PKCE verifier, which the login has to keep.
+    pub async fn create_auth_url(
+        &self,
+        provider: &SsoProvider,
+        redirect_uri: Option<&str>,
+    ) -> Result<(Url, CsrfToken, Nonce, PkceCodeVerifier)> {
+        let redirect_uri = self.redirect_uri(provider, redirect_uri)?;
+        let client = self.create_client(provider, &redirect_uri).await?;
 
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
         let (auth_url, csrf_token, nonce) = client
             .authorize_url(
                 CoreAuthenticationFlow::AuthorizationCode,
                 CsrfToken::new_random,
                 Nonce::new_random,
             )
-            .add_scope(Scope::new("openid".to_string()))
-            .add_scope(Scope::new("email".to_string()))
-            .add_scope(Scope::new("profile".to_string()))
+            .add_scopes(requested_scopes(provider).into_iter().map(Scope::new))
+            .set_pkce_challenge(pkce_challenge)
             .url();
 
-        Ok((auth_url, csrf_token, nonce))
+        Ok((auth_url, csrf_token, nonce, pkce_verifier))
     }
 
     /// Validates an authorization code and exchanges it for tokens
+    ///
+    /// The redirect URI is the one the login was started with; it is checked
+    /// against the provider again, as it may have been removed meanwhile.
     pub async fn validate_auth_code(
         &self,
         provider: &SsoProvider,
         code: &str,
         nonce: Nonce,
-    ) -> Result<(String, String)> {
-        let client = self.create_client(provider).await?;
+        pkce_verifier: PkceCodeVerifier,
+        redirect_uri: Option<&str>,
+    ) -> Result<OidcLogin> {
+        let redirect_uri = self.redirect_uri(provider, redirect_uri)?;
+        let client = self.create_client(provider, &redirect_uri).await?;
 
         let token_response = client
             .exchange_code(AuthorizationCode::new(code.to_string()))
+            .set_pkce_verifier(pkce_verifier)
             .request_async(async_http_client)
             .await
             .map_err(|e| Error::Authentication(format!("Failed to exchange auth code: {}", e)))?;
@@ -119,6 +395,8 @@ impl OidcService {
         let claims = id_token
             .claims(&client.id_token_verifier(), &nonce)
             .map_err(|e| Error::Authentication(format!("Failed to verify ID token: {}", e)))?;
+        let id_token = id_token.to_string();
+        enforce_oidc_controls(provider, &id_token)?;
 
         let subject = claims.subject().to_string();
         let email = claims
@@ -126,37 +404,95 @@ impl OidcService {
             .map(|e| e.to_string())
             .unwrap_or_else(|| subject.clone());
 
-        Ok((subject, email))
+        let claims = claim_values(&id_token)?;
+        Ok(OidcLogin {
+            subject,
+            email,
+            session_id: claims.get("sid").and_then(|sid| sid.first()).cloned(),
+            id_token,
+            claims,
+            tokens: oidc_tokens(&token_response, &requested_scopes(provider)),
+        })
     }
 
-    /// Validates an ID token
-    pub fn validate_id_token(
+    /// Exchanges a refresh token for a new access token
+    ///
+    /// Providers rotating refresh tokens return a new one, which replaces the
+    /// given one; the others leave it unset. A refresh token the provider
+    /// rejects fails with an authentication error.
+    pub async fn refresh_tokens(
         &self,
         provider: &SsoProvider,
-        id_token: &str,
-    ) -> Result<CoreIdTokenClaims> {
-        let client_id = provider
-            .client_id
-            .as_ref()
-            .ok_or_else(|| Error::Internal("Missing client ID".to_string()))?;
+        refresh_token: &str,
+    ) -> Result<OidcTokens> {
+        let redirect_uri = self.redirect_uri(provider, None)?;
+        let client = self.create_client(provider, &redirect_uri).await?;
 
-        let issuer = provider
-            .issuer
-            .as_ref()
-            .ok_or_else(|| Error::Internal("Missing issuer URL".to_string()))?;
+        let token_response = client
+            .exchange_refresh_token(&RefreshToken::new(refresh_token.to_string()))
+            .request_async(async_http_client)
+            .await
+            .map_err(|e| match e {
+                RequestTokenError::ServerResponse(response) => Error::Authentication(format!(
+                    "Provider rejected the refresh token: {}",
+                    response.error()
+                )),
+                e => Error::Unavailable(format!("Failed to refresh tokens: {}", e)),
+            })?;
+
+        Ok(oidc_tokens(&token_response, &requested_scopes(provider)))
+    }
 
-        let token = CoreIdToken::from_str(id_token)
-            .map_err(|e| Error::Authentication(format!("Invalid ID token: {}", e)))?;
+    /// Builds the URL logging a user out at the provider
+    ///
+    /// Returns `None` if the provider does not advertise an end session endpoint.
+    pub async fn end_session_url(
+        &self,
+        provider: &SsoProvider,
+        id_token_hint: Option<&str>,
+        state: &str,
+    ) -> Result<Option<Url>> {
+        let discovery: LogoutDiscovery = reqwest::Client::new()
+            .get(discovery_document_url(provider)?)
+            .timeout(std::time::Duration::from_secs(DISCOVERY_TIMEOUT_SECS))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Error::Internal(format!("Failed to discover provider metadata: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to discover provider metadata: {}", e)))?;
 
-        let claims = token
-            .claims(&CoreIdTokenClaims::options_for_basic_validation(
-                ClientId::new(client_id.clone()),
-                IssuerUrl::new(issuer.clone())
-                    .map_err(|e| Error::Internal(format!("Invalid issuer URL: {}", e)))?,
-            ))
-            .map_err(|e| Error::Authentication(format!("Failed to validate ID token: {}", e)))?;
+        discovery
+            .end_session_endpoint
+            .map(|endpoint| self.build_end_session_url(&endpoint, provider, id_token_hint, state))
+            .transpose()
+    }
 
-        Ok(claims)
+    /// Adds the logout parameters to the end session endpoint of a provider
+    fn build_end_session_url(
+        &self,
+        endpoint: &str,
+        provider: &SsoProvider,
+        id_token_hint: Option<&str>,
+        state: &str,
+    ) -> Result<Url> {
+        let mut url = Url::parse(endpoint)
+            .map_err(|e| Error::Internal(format!("Invalid end session endpoint: {}", e)))?;
+        {
+            let mut query = url.query_pairs_mut();
+            if let Some(id_token_hint) = id_token_hint {
+                query.append_pair("id_token_hint", id_token_hint);
+            }
+            if let Some(client_id) = &provider.client_id {
+                query.append_pair("client_id", client_id);
+            }
+            if let Some(redirect_url) = &self.config.post_logout_redirect_url {
+                query.append_pair("post_logout_redirect_uri", redirect_url);
+            }
+            query.append_pair("state", state);
+        }
+        Ok(url)
     }
 }
 
@@ -168,7 +504,8 @@ mod tests {
     #[tokio::test]
     async fn test_oidc_auth_url() {
         let config = OidcConfig {
-            redirect_url: "http://localhost:3000/auth/callback".to_string(),
+            redirect_url: Some("http://localhost:3000/auth/callback".to_string()),
+            post_logout_redirect_url: None,
         };
 
         let service = OidcService::new(config);
@@ -183,19 +520,167 @@ mod tests {
             Some("https://accounts.google.com/.well-known/openid-configuration".to_string()),
         );
 
-        let result = service.create_auth_url(&provider).await;
+        let result = service.create_auth_url(&provider, None).await;
         assert!(result.is_err()); // Will fail without a real provider
     }
 
+    fn token(header: serde_json::Value, claims: serde_json::Value) -> String {
+        format!(
+            "{}.{}.signature",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        )
+    }
+
     #[test]
-    fn test_id_token_validation() {
-        let config = OidcConfig {
-            redirect_url: "http://localhost:3000/auth/callback".to_string(),
+    fn test_oidc_controls() {
+        let mut provider = SsoProvider::new_oidc(
+            TenantId::new(),
+            "Test Provider".to_string(),
+            None,
+            "client_id".to_string(),
+            "client_secret".to_string(),
+            "https://accounts.google.com".to_string(),
+            None,
+        );
+        let rs256 = serde_json::json!({ "alg": "RS256" });
+        let claims = serde_json::json!({
+            "iss": "https://accounts.google.com",
+            "email_verified": true,
+            "hd": "test.org",
+        });
+
+        // Defaults require an exactly matching issuer and a verified email
+        assert!(enforce_oidc_controls(&provider, &token(rs256.clone(), claims.clone())).is_ok());
+        let unverified = serde_json::json!({ "iss": "https://accounts.google.com" });
+        assert!(enforce_oidc_controls(&provider, &token(rs256.clone(), unverified)).is_err());
+        let slashed = serde_json::json!({
+            "iss": "https://accounts.google.com/",
+            "email_verified": true,
+        });
+        assert!(enforce_oidc_controls(&provider, &token(rs256.clone(), slashed.clone())).is_err());
+        provider.oidc_validation.issuer_exact_match = false;
+        assert!(enforce_oidc_controls(&provider, &token(rs256.clone(), slashed)).is_ok());
+
+        // Pinned algorithms
+        provider.oidc_validation.allowed_algorithms = vec!["ES256".to_string()];
+        assert!(enforce_oidc_controls(&provider, &token(rs256.clone(), claims.clone())).is_err());
+        let es256 = serde_json::json!({ "alg": "ES256" });
+        assert!(enforce_oidc_controls(&provider, &token(es256, claims.clone())).is_ok());
+        provider.oidc_validation.allowed_algorithms.clear();
+
+        // Restricted hosted domains and directory tenants
+        provider.oidc_validation.allowed_hosted_domains = vec!["Test.org".to_string()];
+        assert!(enforce_oidc_controls(&provider, &token(rs256.clone(), claims.clone())).is_ok());
+        provider.oidc_validation.allowed_hosted_domains = vec!["other.org".to_string()];
+        assert!(enforce_oidc_controls(&provider, &token(rs256.clone(), claims.clone())).is_err());
+        provider.oidc_validation.allowed_hosted_domains.clear();
+        provider.oidc_validation.allowed_tenant_ids = vec!["tenant".to_string()];
+        assert!(enforce_oidc_controls(&provider, &token(rs256, claims)).is_err());
+
+        assert!(enforce_oidc_controls(&provider, "invalid").is_err());
+    }
+
+    #[test]
+    fn test_validate_oidc_controls() {
+        let mut validation = OidcValidation::default();
+        assert!(validate_oidc_controls(&validation).is_ok());
+        validation.allowed_algorithms = vec!["RS256".to_string(), "ES256".to_string()];
+        assert!(validate_oidc_controls(&validation).is_ok());
+        validation.allowed_algorithms = vec!["none".to_string()];
+        assert!(validate_oidc_controls(&validation).is_err());
+        validation.allowed_algorithms = vec!["XS999".to_string()];
+        assert!(validate_oidc_controls(&validation).is_err());
+    }
+    #[test]
+    fn test_end_session_url() {
+        let service = OidcService::new(OidcConfig {
+            redirect_url: Some("http://localhost:3000/auth/callback".to_string()),
+            post_logout_redirect_url: Some("https://app.example.com/logged-out".to_string()),
+        });
+        let provider = SsoProvider::new_oidc(
+            TenantId::new(),
+            "Test Provider".to_string(),
+            None,
+            "client_id".to_string(),
+            "client_secret".to_string(),
+            "https://idp.example.com/".to_string(),
+            None,
+        );
+        assert_eq!(
+            discovery_document_url(&provider).unwrap(),
+            "https://idp.example.com/.well-known/openid-configuration"
+        );
+
+        let url = service
+            .build_end_session_url(
+                "https://idp.example.com/logout?ui=compact",
+                &provider,
+                Some("id.token.hint"),
+                "state123",
+            )
+            .unwrap();
+        let query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        let param = |name: &str| {
+            query
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
         };
+        assert_eq!(param("ui"), Some("compact"));
+        assert_eq!(param("id_token_hint"), Some("id.token.hint"));
+        assert_eq!(param("client_id"), Some("client_id"));
+        assert_eq!(
+            param("post_logout_redirect_uri"),
+            Some("https://app.example.com/logged-out")
+        );
+        assert_eq!(param("state"), Some("state123"));
+    }
 
-        let service = OidcService::new(config);
+    #[test]
+    fn test_claim_values() {
+        let id_token = token(
+            serde_json::json!({ "alg": "RS256" }),
+            serde_json::json!({
+                "sid": "idp-session",
+                "groups": ["admins", "finance"],
+                "email_verified": true,
+                "address": { "country": "CH" },
+                "nickname": null
+            }),
+        );
+        let claims = claim_values(&id_token).unwrap();
+        assert_eq!(claims["sid"], vec!["idp-session"]);
+        assert_eq!(claims["groups"], vec!["admins", "finance"]);
+        assert_eq!(claims["email_verified"], vec!["true"]);
+        assert!(!claims.contains_key("address"));
+        assert!(!claims.contains_key("nickname"));
+    }
 
-        let provider = SsoProvider::new_oidc(
+    #[test]
+    fn test_redirect_uris() {
+        let uris = |uris: &[&str]| uris.iter().map(|uri| uri.to_string()).collect::<Vec<_>>();
+        assert!(validate_redirect_uris(&uris(&[
+            "https://app.example.com/sso/callback",
+            "https://staging.example.com/sso/callback",
+            "http://localhost:8080/callback",
+            "com.example.app:/callback",
+        ]))
+        .is_ok());
+        for uri in [
+            "http://app.example.com/callback",
+            "javascript:alert(1)",
+            "https://app.example.com/callback#fragment",
+            "not a uri",
+        ] {
+            assert!(validate_redirect_uris(&uris(&[uri])).is_err(), "{}", uri);
+        }
+
+        let service = OidcService::new(OidcConfig {
+            redirect_url: Some("https://default.example.com/callback".to_string()),
+            post_logout_redirect_url: None,
+        });
+        let mut provider = SsoProvider::new_oidc(
             TenantId::new(),
             "Test Provider".to_string(),
             None,
@@ -204,9 +689,84 @@ mod tests {
             "https://accounts.google.com".to_string(),
             None,
         );
+        assert_eq!(
+            service.redirect_uri(&provider, None).unwrap(),
+            "https://default.example.com/callback"
+        );
+
+        provider.redirect_uris = uris(&[
+            "https://app.example.com/sso/callback",
+            "com.example.app:/callback",
+        ]);
+        assert_eq!(
+            service.redirect_uri(&provider, None).unwrap(),
+            "https://app.example.com/sso/callback"
+        );
+        assert_eq!(
+            service
+                .redirect_uri(&provider, Some("com.example.app:/callback"))
+                .unwrap(),
+            "com.example.app:/callback"
+        );
+        assert!(service
+            .redirect_uri(&provider, Some("https://evil.example.com/callback"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_token_scopes() {
+        let mut provider = SsoProvider::new_oidc(
+            TenantId::new(),
+            "Test Provider".to_string(),
+            None,
+            "client_id".to_string(),
+            "client_secret".to_string(),
+            "https://login.microsoftonline.com/tenant/v2.0".to_string(),
+            None,
+        );
+        provider.token_scopes = vec![
+            "https://graph.microsoft.com/User.Read".to_string(),
+            "email".to_string(),
+        ];
+        assert_eq!(requested_scopes(&provider), ["openid", "email", "profile"]);
+
+        provider.store_tokens = true;
+        let requested = requested_scopes(&provider);
+        assert_eq!(
+            requested,
+            [
+                "openid",
+                "email",
+                "profile",
+                "offline_access",
+                "https://graph.microsoft.com/User.Read"
+            ]
+        );
 
-        let invalid_token = "invalid.token.here";
-        let result = service.validate_id_token(&provider, invalid_token);
-        assert!(result.is_err());
+        let response: CoreTokenResponse = serde_json::from_str(
+            r#"{
+                "access_token": "access",
+                "token_type": "Bearer",
+                "expires_in": 3600,
+                "refresh_token": "refresh"
+            }"#,
+        )
+        .unwrap();
+        let tokens = oidc_tokens(&response, &requested);
+        assert_eq!(tokens.access_token, "access");
+        assert_eq!(tokens.refresh_token.as_deref(), Some("refresh"));
+        assert_eq!(tokens.scopes, requested);
+        assert!(!tokens.expires_within(Duration::minutes(59)));
+        assert!(tokens.expires_within(Duration::minutes(61)));
+        assert!(!format!("{:?}", tokens).contains("refresh"));
+
+        let response: CoreTokenResponse = serde_json::from_str(
+            r#"{"access_token": "access", "token_type": "Bearer", "scope": "openid User.Read"}"#,
+        )
+        .unwrap();
+        let tokens = oidc_tokens(&response, &requested);
+        assert_eq!(tokens.refresh_token, None);
+        assert_eq!(tokens.expires_at, None);
+        assert_eq!(tokens.scopes, ["openid", "User.Read"]);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,350 @@
+use serde::{Deserialize, Serialize};
+
+use crate::shared::{
+    error::{Error, Result},
+    types::TenantId,
+};
+
+use super::models::{AttributeMapping, OidcValidation, RoleMapping, SamlIdpMetadata, SsoProvider};
+
+/// SAML attribute of the email address in WS-Federation claim names
+const CLAIM_EMAIL: &str = "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/emailaddress";
+
+/// SAML attribute of the display name in WS-Federation claim names
+const CLAIM_NAME: &str = "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/name";
+
+/// SAML attribute Azure AD sends the display name in
+const AZURE_CLAIM_DISPLAY_NAME: &str = "http://schemas.microsoft.com/identity/claims/displayname";
+
+/// SAML attribute Azure AD sends the group object IDs in
+const AZURE_CLAIM_GROUPS: &str = "http://schemas.microsoft.com/ws/2008/06/identity/claims/groups";
+
+/// Identity provider whose settings are known, so admins only supply their credentials
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SsoPreset {
+    AzureAd,
+    Okta,
+    GoogleWorkspace,
+    Auth0,
+}
+
+/// Entry of the preset catalog shown to admins
+#[derive(Debug, Clone, Serialize)]
+pub struct SsoPresetInfo {
+    pub preset: SsoPreset,
+    pub name: &'static str,
+    /// What the admin enters as the directory of their tenant at the IdP
+    pub directory_hint: &'static str,
+}
+
+/// Request to create a provider from a preset
+///
+/// OIDC providers need the directory and client credentials; SAML providers
+/// are created instead if a metadata URL is given.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SsoPresetRequest {
+    pub preset: SsoPreset,
+    pub name: String,
+    pub description: Option<String>,
+    /// Tenant of the organization at the IdP, see [`SsoPresetInfo::directory_hint`]
+    pub directory: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    /// Reference to the client secret in a secret store, used instead of `client_secret`
+    pub client_secret_ref: Option<String>,
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    pub metadata_url: Option<String>,
+    pub assertion_consumer_service_url: Option<String>,
+}
+
+impl SsoPreset {
+    /// All presets, in the order they are listed
+    pub const ALL: [SsoPreset; 4] = [
+        SsoPreset::AzureAd,
+        SsoPreset::Okta,
+        SsoPreset::GoogleWorkspace,
+        SsoPreset::Auth0,
+    ];
+
+    /// Lists the presets with what admins have to enter for them
+    pub fn catalog() -> Vec<SsoPresetInfo> {
+        Self::ALL
+            .iter()
+            .map(|&preset| SsoPresetInfo {
+                preset,
+                name: preset.display_name(),
+                directory_hint: preset.directory_hint(),
+            })
+            .collect()
+    }
+
+    /// Gets the name of the identity provider
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            SsoPreset::AzureAd => "Microsoft Entra ID (Azure AD)",
+            SsoPreset::Okta => "Okta",
+            SsoPreset::GoogleWorkspace => "Google Workspace",
+            SsoPreset::Auth0 => "Auth0",
+        }
+    }
+
+    /// Describes the directory admins enter
+    fn directory_hint(&self) -> &'static str {
+        match self {
+            SsoPreset::AzureAd => "Directory (tenant) ID",
+            SsoPreset::Okta => "Okta domain, e.g. acme.okta.com",
+            SsoPreset::GoogleWorkspace => "Workspace domain, e.g. acme.com",
+            SsoPreset::Auth0 => "Auth0 domain, e.g. acme.eu.auth0.com",
+        }
+    }
+
+    /// Gets the OIDC issuer of a directory
+    ///
+    /// Auth0 issues tokens with a trailing slash, which the exact issuer match requires.
+    pub fn issuer(&self, directory: &str) -> String {
+        match self {
+            SsoPreset::AzureAd => format!("https://login.microsoftonline.com/{}/v2.0", directory),
+            SsoPreset::Okta => format!("https://{}", directory),
+            SsoPreset::GoogleWorkspace => "https://accounts.google.com".to_string(),
+            SsoPreset::Auth0 => format!("https://{}/", directory),
+        }
+    }
+
+    /// Gets the ID token controls confining logins to a directory
+    ///
+    /// Azure AD tokens carry no `email_verified` claim, so it is not required.
+    pub fn oidc_validation(&self, directory: &str) -> OidcValidation {
+        let mut validation = OidcValidation::default();
+        match self {
+            SsoPreset::AzureAd => {
+                validation.allowed_tenant_ids = vec![directory.to_string()];
+                validation.require_email_verified = false;
+            },
+            SsoPreset::GoogleWorkspace => {
+                validation.allowed_hosted_domains = vec![directory.to_string()];
+            },
+            SsoPreset::Auth0 => validation.allowed_algorithms = vec!["RS256".to_string()],
+            SsoPreset::Okta => {},
+        }
+        validation
+    }
+
+    /// Gets the claims the ID tokens of the IdP carry profile fields in
+    ///
+    /// Azure AD only sends the optional `email` claim if configured, while the
+    /// user principal name in `preferred_username` is always present.
+    pub fn oidc_attribute_mapping(&self) -> AttributeMapping {
+        match self {
+            SsoPreset::AzureAd => AttributeMapping {
+                email: Some("preferred_username".to_string()),
+                ..AttributeMapping::default()
+            },
+            SsoPreset::Okta | SsoPreset::GoogleWorkspace | SsoPreset::Auth0 => {
+                AttributeMapping::default()
+            },
+        }
+    }
+
+    /// Gets the SAML attributes the assertions of the IdP carry profile fields in
+    pub fn saml_attribute_mapping(&self) -> AttributeMapping {
+        match self {
+            SsoPreset::AzureAd => AttributeMapping {
+                email: Some(CLAIM_EMAIL.to_string()),
+                name: Some(AZURE_CLAIM_DISPLAY_NAME.to_string()),
+                ..AttributeMapping::default()
+            },
+            SsoPreset::Auth0 => AttributeMapping {
+                email: Some(CLAIM_EMAIL.to_string()),
+                name: Some(CLAIM_NAME.to_string()),
+                ..AttributeMapping::default()
+            },
+            SsoPreset::Okta | SsoPreset::GoogleWorkspace => AttributeMapping::default(),
+        }
+    }
+
+    /// Gets the SAML attribute listing the groups of a user, if the IdP uses an unusual one
+    fn saml_group_attribute(&self) -> Option<String> {
+        match self {
+            SsoPreset::AzureAd => Some(AZURE_CLAIM_GROUPS.to_string()),
+            SsoPreset::Okta | SsoPreset::GoogleWorkspace | SsoPreset::Auth0 => None,
+        }
+    }
+
+    /// Builds an OIDC provider of a tenant from the request
+    pub fn oidc_provider(
+        &self,
+        tenant_id: TenantId,
+        request: &SsoPresetRequest,
+    ) -> Result<SsoProvider> {
+        let directory = request
+            .directory
+            .as_deref()
+            .ok_or_else(|| Error::Validation(format!("{} is required", self.directory_hint())))?;
+        validate_directory(directory)?;
+        let client_id = request
+            .client_id
+            .clone()
+            .ok_or_else(|| Error::Validation("client_id is required".to_string()))?;
+        if request.client_secret.is_none() == request.client_secret_ref.is_none() {
+            return Err(Error::Validation(
+                "Either client_secret or client_secret_ref is required".to_string(),
+            ));
+        }
+
+        let mut provider = SsoProvider::new_oidc(
+            tenant_id,
+            request.name.clone(),
+            request.description.clone(),
+            client_id,
+            request.client_secret.clone().unwrap_or_default(),
+            self.issuer(directory),
+            None,
+        );
+        if request.client_secret_ref.is_some() {
+            provider.client_secret = None;
+            provider.client_secret_ref = request.client_secret_ref.clone();
+        }
+        provider.oidc_validation = self.oidc_validation(directory);
+        provider.attribute_mapping = self.oidc_attribute_mapping();
+        provider.redirect_uris = request.redirect_uris.clone();
+        Ok(provider)
+    }
+
+    /// Builds a SAML provider of a tenant from the request and the fetched IdP metadata
+    pub fn saml_provider(
+        &self,
+        tenant_id: TenantId,
+        request: &SsoPresetRequest,
+        metadata_xml: String,
+        metadata: &SamlIdpMetadata,
+    ) -> Result<SsoProvider> {
+        let assertion_consumer_service_url = request
+            .assertion_consumer_service_url
+            .clone()
+            .ok_or_else(|| {
+                Error::Validation("assertion_consumer_service_url is required".to_string())
+            })?;
+
+        let mut provider = SsoProvider::new_saml(
+            tenant_id,
+            request.name.clone(),
+            request.description.clone(),
+            request.metadata_url.clone(),
+            Some(metadata_xml),
+            metadata.entity_id.clone(),
+            assertion_consumer_service_url,
+            metadata.slo_url.clone(),
+        );
+        provider.attribute_mapping = self.saml_attribute_mapping();
+        provider.role_mapping = RoleMapping {
+            group_attribute: self.saml_group_attribute(),
+            ..RoleMapping::default()
+        };
+        Ok(provider)
+    }
+}
+
+/// Ensures a directory only names a tenant, so it cannot redirect the issuer elsewhere
+fn validate_directory(directory: &str) -> Result<()> {
+    if directory.is_empty()
+        || !directory
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+    {
+        return Err(Error::Validation(format!(
+            "Invalid IdP directory {}",
+            directory
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(preset: SsoPreset, directory: &str) -> SsoPresetRequest {
+        SsoPresetRequest {
+            preset,
+            name: "Corporate SSO".to_string(),
+            description: None,
+            directory: Some(directory.to_string()),
+            client_id: Some("client_id".to_string()),
+            client_secret: Some("client_secret".to_string()),
+            client_secret_ref: None,
+            redirect_uris: vec!["https://app.example.com/sso/callback".to_string()],
+            metadata_url: None,
+            assertion_consumer_service_url: None,
+        }
+    }
+
+    #[test]
+    fn test_oidc_presets() {
+        let tenant_id = TenantId::new();
+        let directory = "0b5f7c2e-4a1d-4f7e-9c3b-2d8e6a1f0c9d";
+        let provider = SsoPreset::AzureAd
+            .oidc_provider(tenant_id, &request(SsoPreset::AzureAd, directory))
+            .unwrap();
+        assert_eq!(
+            provider.issuer.as_deref(),
+            Some("https://login.microsoftonline.com/0b5f7c2e-4a1d-4f7e-9c3b-2d8e6a1f0c9d/v2.0")
+        );
+        assert_eq!(provider.oidc_validation.allowed_tenant_ids, vec![directory]);
+        assert!(!provider.oidc_validation.require_email_verified);
+        assert_eq!(
+            provider.attribute_mapping.email.as_deref(),
+            Some("preferred_username")
+        );
+        assert_eq!(provider.redirect_uris.len(), 1);
+
+        let provider = SsoPreset::GoogleWorkspace
+            .oidc_provider(tenant_id, &request(SsoPreset::GoogleWorkspace, "acme.com"))
+            .unwrap();
+        assert_eq!(
+            provider.issuer.as_deref(),
+            Some("https://accounts.google.com")
+        );
+        assert_eq!(
+            provider.oidc_validation.allowed_hosted_domains,
+            vec!["acme.com"]
+        );
+
+        let provider = SsoPreset::Auth0
+            .oidc_provider(tenant_id, &request(SsoPreset::Auth0, "acme.eu.auth0.com"))
+            .unwrap();
+        assert_eq!(
+            provider.issuer.as_deref(),
+            Some("https://acme.eu.auth0.com/")
+        );
+
+        // Directories cannot point the issuer at another host
+        for directory in ["", "evil.com/acme", "acme.okta.com:8443"] {
+            assert!(SsoPreset::Okta
+                .oidc_provider(tenant_id, &request(SsoPreset::Okta, directory))
+                .is_err());
+        }
+        let mut missing_secret = request(SsoPreset::Okta, "acme.okta.com");
+        missing_secret.client_secret = None;
+        assert!(SsoPreset::Okta
+            .oidc_provider(tenant_id, &missing_secret)
+            .is_err());
+        let mut secret_ref = missing_secret;
+        secret_ref.client_secret_ref = Some("vault:secret/data/sso/okta#client_secret".to_string());
+        let provider = SsoPreset::Okta
+            .oidc_provider(tenant_id, &secret_ref)
+            .unwrap();
+        assert!(provider.client_secret.is_none());
+        assert!(provider.client_secret_ref.is_some());
+    }
+
+    #[test]
+    fn test_preset_catalog() {
+        let catalog = SsoPreset::catalog();
+        assert_eq!(catalog.len(), SsoPreset::ALL.len());
+        assert_eq!(catalog[0].preset, SsoPreset::AzureAd);
+        let preset: SsoPreset = serde_json::from_str(r#""google_workspace""#).unwrap();
+        assert_eq!(preset, SsoPreset::GoogleWorkspace);
+    }
+}
@@ -5,28 +5,40 @@ use uuid::Uuid;
 use crate::{
     core::database::Database,
     shared::{
+        audit::{AuditCategory, AuditEvent},
         error::{Error, Result},
         types::{TenantId, UserId},
     },
 };
 
-use super::models::{SsoProvider, SsoProviderType, SsoUserMapping, SsoSession};
+use super::models::{
+    AttributeMapping, OidcTokens, OidcValidation, RoleMapping, SamlSigning, SsoAuthEventQuery,
+    SsoLinkRequest, SsoProfile, SsoProvider, SsoProviderType, SsoSession, SsoToken, SsoUserMapping,
+};
 
 /// Repository for SSO operations
 #[derive(Debug, Clone)]
 pub struct SsoRepository {
-    db: Database,
+    pool: PgPool,
 }
 
 impl SsoRepository {
     /// Creates a new SsoRepository instance
     pub fn new(db: Database) -> Self {
-        Self { db }
+        Self {
+            pool: db.get_pool(),
+        }
+    }
+
+    /// Gets the connection pool, for tests setting up rows of other modules
+    #[cfg(test)]
+    pub(super) fn pool(&self) -> &PgPool {
+        &self.pool
     }
 
     /// Creates a new SSO provider
     pub async fn create_provider(&self, provider: &SsoProvider) -> Result<SsoProvider> {
-        let pool = self.db.pool();
+        let pool = &self.pool;
         let mut tx = pool.begin().await?;
 
         let result = sqlx::query!(
@@ -35,9 +47,16 @@ impl SsoRepository {
                 id, tenant_id, name, description, provider_type, enabled,
                 metadata_url, metadata_xml, entity_id, assertion_consumer_service_url,
                 single_logout_url, client_id, client_secret, issuer, discovery_url,
-                created_at, updated_at
+                allowed_algorithms, issuer_exact_match, require_email_verified,
+                allowed_hosted_domains, allowed_tenant_ids, allow_idp_initiated,
+                attribute_mapping, role_mapping, redirect_uris, client_secret_ref,
+                saml_signature_algorithm, saml_digest_algorithm, saml_canonicalization,
+                store_tokens, token_scopes, created_at, updated_at
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18,
+                $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
             RETURNING *
             "#,
             provider.id,
@@ -55,6 +74,21 @@ impl SsoRepository {
             provider.client_secret,
             provider.issuer,
             provider.discovery_url,
+            &provider.oidc_validation.allowed_algorithms,
+            provider.oidc_validation.issuer_exact_match,
+            provider.oidc_validation.require_email_verified,
+            &provider.oidc_validation.allowed_hosted_domains,
+            &provider.oidc_validation.allowed_tenant_ids,
+            provider.allow_idp_initiated,
+            attribute_mapping_value(&provider.attribute_mapping)?,
+            role_mapping_value(&provider.role_mapping)?,
+            &provider.redirect_uris,
+            provider.client_secret_ref,
+            provider.saml_signing.signature_algorithm.as_str(),
+            provider.saml_signing.digest_algorithm.as_str(),
+            provider.saml_signing.canonicalization.as_str(),
+            provider.store_tokens,
+            &provider.token_scopes,
             provider.created_at,
             provider.updated_at,
         )
@@ -71,6 +105,7 @@ impl SsoRepository {
             provider_type: match result.provider_type.as_str() {
                 "saml" => SsoProviderType::Saml,
                 "oidc" => SsoProviderType::Oidc,
+                "wsfed" => SsoProviderType::WsFed,
                 _ => return Err(Error::Internal("Invalid provider type".to_string())),
             },
             enabled: result.enabled,
@@ -81,8 +116,27 @@ impl SsoRepository {
             single_logout_url: result.single_logout_url,
             client_id: result.client_id,
             client_secret: result.client_secret,
+            client_secret_ref: result.client_secret_ref,
             issuer: result.issuer,
             discovery_url: result.discovery_url,
+            oidc_validation: OidcValidation {
+                allowed_algorithms: result.allowed_algorithms,
+                issuer_exact_match: result.issuer_exact_match,
+                require_email_verified: result.require_email_verified,
+                allowed_hosted_domains: result.allowed_hosted_domains,
+                allowed_tenant_ids: result.allowed_tenant_ids,
+            },
+            allow_idp_initiated: result.allow_idp_initiated,
+            saml_signing: saml_signing(
+                &result.saml_signature_algorithm,
+                &result.saml_digest_algorithm,
+                &result.saml_canonicalization,
+            ),
+            store_tokens: result.store_tokens,
+            token_scopes: result.token_scopes,
+            attribute_mapping: serde_json::from_value(result.attribute_mapping).unwrap_or_default(),
+            role_mapping: serde_json::from_value(result.role_mapping).unwrap_or_default(),
+            redirect_uris: result.redirect_uris,
             created_at: result.created_at,
             updated_at: result.updated_at,
         })
@@ -90,7 +144,7 @@ impl SsoRepository {
 
     /// Gets a provider by ID
     pub async fn get_provider(&self, id: Uuid) -> Result<Option<SsoProvider>> {
-        let pool = self.db.pool();
+        let pool = &self.pool;
         let result = sqlx::query!(
             r#"
             SELECT * FROM sso_providers WHERE id = $1
@@ -108,6 +162,7 @@ impl SsoRepository {
             provider_type: match r.provider_type.as_str() {
                 "saml" => SsoProviderType::Saml,
                 "oidc" => SsoProviderType::Oidc,
+                "wsfed" => SsoProviderType::WsFed,
                 _ => SsoProviderType::Saml, // Default to SAML to avoid runtime errors
             },
             enabled: r.enabled,
@@ -118,8 +173,27 @@ impl SsoRepository {
             single_logout_url: r.single_logout_url,
             client_id: r.client_id,
             client_secret: r.client_secret,
+            client_secret_ref: r.client_secret_ref,
             issuer: r.issuer,
             discovery_url: r.discovery_url,
+            oidc_validation: OidcValidation {
+                allowed_algorithms: r.allowed_algorithms,
+                issuer_exact_match: r.issuer_exact_match,
+                require_email_verified: r.require_email_verified,
+                allowed_hosted_domains: r.allowed_hosted_domains,
+                allowed_tenant_ids: r.allowed_tenant_ids,
+            },
+            allow_idp_initiated: r.allow_idp_initiated,
+            saml_signing: saml_signing(
+                &r.saml_signature_algorithm,
+                &r.saml_digest_algorithm,
+                &r.saml_canonicalization,
+            ),
+            store_tokens: r.store_tokens,
+            token_scopes: r.token_scopes,
+            attribute_mapping: serde_json::from_value(r.attribute_mapping).unwrap_or_default(),
+            role_mapping: serde_json::from_value(r.role_mapping).unwrap_or_default(),
+            redirect_uris: r.redirect_uris,
             created_at: r.created_at,
             updated_at: r.updated_at,
         }))
@@ -127,7 +201,7 @@ impl SsoRepository {
 
     /// Lists all providers for a tenant
     pub async fn list_providers(&self, tenant_id: TenantId) -> Result<Vec<SsoProvider>> {
-        let pool = self.db.pool();
+        let pool = &self.pool;
         let results = sqlx::query!(
             r#"
             SELECT * FROM sso_providers WHERE tenant_id = $1
@@ -147,6 +221,66 @@ impl SsoRepository {
                 provider_type: match r.provider_type.as_str() {
                     "saml" => SsoProviderType::Saml,
                     "oidc" => SsoProviderType::Oidc,
+                    "wsfed" => SsoProviderType::WsFed,
+                    _ => SsoProviderType::Saml,
+                },
+                enabled: r.enabled,
+                metadata_url: r.metadata_url,
+                metadata_xml: r.metadata_xml,
+                entity_id: r.entity_id,
+                assertion_consumer_service_url: r.assertion_consumer_service_url,
+                single_logout_url: r.single_logout_url,
+                client_id: r.client_id,
+                client_secret: r.client_secret,
+                client_secret_ref: r.client_secret_ref,
+                issuer: r.issuer,
+                discovery_url: r.discovery_url,
+                oidc_validation: OidcValidation {
+                    allowed_algorithms: r.allowed_algorithms,
+                    issuer_exact_match: r.issuer_exact_match,
+                    require_email_verified: r.require_email_verified,
+                    allowed_hosted_domains: r.allowed_hosted_domains,
+                    allowed_tenant_ids: r.allowed_tenant_ids,
+                },
+                allow_idp_initiated: r.allow_idp_initiated,
+                saml_signing: saml_signing(
+                    &r.saml_signature_algorithm,
+                    &r.saml_digest_algorithm,
+                    &r.saml_canonicalization,
+                ),
+                store_tokens: r.store_tokens,
+                token_scopes: r.token_scopes,
+                attribute_mapping: serde_json::from_value(r.attribute_mapping).unwrap_or_default(),
+                role_mapping: serde_json::from_value(r.role_mapping).unwrap_or_default(),
+                redirect_uris: r.redirect_uris,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+            })
+            .collect())
+    }
+
+    /// Lists the providers of all tenants whose metadata is fetched from a URL
+    pub async fn list_metadata_providers(&self) -> Result<Vec<SsoProvider>> {
+        let pool = &self.pool;
+        let results = sqlx::query!(
+            r#"
+            SELECT * FROM sso_providers WHERE metadata_url IS NOT NULL
+            "#,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| SsoProvider {
+                id: r.id,
+                tenant_id: TenantId(r.tenant_id),
+                name: r.name,
+                description: r.description,
+                provider_type: match r.provider_type.as_str() {
+                    "saml" => SsoProviderType::Saml,
+                    "oidc" => SsoProviderType::Oidc,
+                    "wsfed" => SsoProviderType::WsFed,
                     _ => SsoProviderType::Saml,
                 },
                 enabled: r.enabled,
@@ -157,26 +291,144 @@ impl SsoRepository {
                 single_logout_url: r.single_logout_url,
                 client_id: r.client_id,
                 client_secret: r.client_secret,
+                client_secret_ref: r.client_secret_ref,
                 issuer: r.issuer,
                 discovery_url: r.discovery_url,
+                oidc_validation: OidcValidation {
+                    allowed_algorithms: r.allowed_algorithms,
+                    issuer_exact_match: r.issuer_exact_match,
+                    require_email_verified: r.require_email_verified,
+                    allowed_hosted_domains: r.allowed_hosted_domains,
+                    allowed_tenant_ids: r.allowed_tenant_ids,
+                },
+                allow_idp_initiated: r.allow_idp_initiated,
+                saml_signing: saml_signing(
+                    &r.saml_signature_algorithm,
+                    &r.saml_digest_algorithm,
+                    &r.saml_canonicalization,
+                ),
+                store_tokens: r.store_tokens,
+                token_scopes: r.token_scopes,
+                attribute_mapping: serde_json::from_value(r.attribute_mapping).unwrap_or_default(),
+                role_mapping: serde_json::from_value(r.role_mapping).unwrap_or_default(),
+                redirect_uris: r.redirect_uris,
                 created_at: r.created_at,
                 updated_at: r.updated_at,
             })
             .collect())
     }
 
+    /// Updates the configuration of a provider, returning it as stored
+    pub async fn update_provider(&self, provider: &SsoProvider) -> Result<SsoProvider> {
+        let pool = &self.pool;
+        let result = sqlx::query!(
+            r#"
+            UPDATE sso_providers
+            SET name = $2, description = $3, enabled = $4, metadata_url = $5,
+                metadata_xml = $6, entity_id = $7, assertion_consumer_service_url = $8,
+                single_logout_url = $9, client_id = $10, client_secret = $11, issuer = $12,
+                discovery_url = $13, allowed_algorithms = $14, issuer_exact_match = $15,
+                require_email_verified = $16, allowed_hosted_domains = $17,
+                allowed_tenant_ids = $18, allow_idp_initiated = $19, attribute_mapping = $20,
+                role_mapping = $21, redirect_uris = $22, client_secret_ref = $23,
+                saml_signature_algorithm = $24, saml_digest_algorithm = $25,
+                saml_canonicalization = $26, store_tokens = $27, token_scopes = $28,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+            provider.id,
+            provider.name,
+            provider.description,
+            provider.enabled,
+            provider.metadata_url,
+            provider.metadata_xml,
+            provider.entity_id,
+            provider.assertion_consumer_service_url,
+            provider.single_logout_url,
+            provider.client_id,
+            provider.client_secret,
+            provider.issuer,
+            provider.discovery_url,
+            &provider.oidc_validation.allowed_algorithms,
+            provider.oidc_validation.issuer_exact_match,
+            provider.oidc_validation.require_email_verified,
+            &provider.oidc_validation.allowed_hosted_domains,
+            &provider.oidc_validation.allowed_tenant_ids,
+            provider.allow_idp_initiated,
+            attribute_mapping_value(&provider.attribute_mapping)?,
+            role_mapping_value(&provider.role_mapping)?,
+            &provider.redirect_uris,
+            provider.client_secret_ref,
+            provider.saml_signing.signature_algorithm.as_str(),
+            provider.saml_signing.digest_algorithm.as_str(),
+            provider.saml_signing.canonicalization.as_str(),
+            provider.store_tokens,
+            &provider.token_scopes,
+        )
+        .execute(pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound("SSO provider not found".to_string()));
+        }
+
+        self.get_provider(provider.id)
+            .await?
+            .ok_or_else(|| Error::NotFound("SSO provider not found".to_string()))
+    }
+
+    /// Enables or disables a provider, returning it as stored
+    pub async fn set_provider_enabled(&self, id: Uuid, enabled: bool) -> Result<SsoProvider> {
+        let pool = &self.pool;
+        let result = sqlx::query!(
+            r#"
+            UPDATE sso_providers
+            SET enabled = $2, updated_at = NOW()
+            WHERE id = $1
+            "#,
+            id,
+            enabled,
+        )
+        .execute(pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound("SSO provider not found".to_string()));
+        }
+
+        self.get_provider(id)
+            .await?
+            .ok_or_else(|| Error::NotFound("SSO provider not found".to_string()))
+    }
+
+    /// Deletes a provider together with its user mappings, sessions and link requests
+    pub async fn delete_provider(&self, id: Uuid) -> Result<()> {
+        let pool = &self.pool;
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM sso_providers WHERE id = $1
+            "#,
+            id,
+        )
+        .execute(pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound("SSO provider not found".to_string()));
+        }
+
+        Ok(())
+    }
+
     /// Creates a new SSO user mapping
     pub async fn create_user_mapping(&self, mapping: &SsoUserMapping) -> Result<SsoUserMapping> {
-        let pool = self.db.pool();
+        let pool = &self.pool;
         let mut tx = pool.begin().await?;
 
         let result = sqlx::query!(
             r#"
             INSERT INTO sso_user_mappings (
                 id, user_id, tenant_id, provider_id, external_id,
-                email, created_at, updated_at
+                email, profile, created_at, updated_at
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
             RETURNING *
             "#,
             mapping.id,
@@ -185,6 +437,7 @@ impl SsoRepository {
             mapping.provider_id,
             mapping.external_id,
             mapping.email,
+            profile_value(&mapping.profile)?,
             mapping.created_at,
             mapping.updated_at,
         )
@@ -200,6 +453,7 @@ impl SsoRepository {
             provider_id: result.provider_id,
             external_id: result.external_id,
             email: result.email,
+            profile: serde_json::from_value(result.profile).unwrap_or_default(),
             created_at: result.created_at,
             updated_at: result.updated_at,
         })
@@ -211,7 +465,7 @@ impl SsoRepository {
         provider_id: Uuid,
         external_id: &str,
     ) -> Result<Option<SsoUserMapping>> {
-        let pool = self.db.pool();
+        let pool = &self.pool;
         let result = sqlx::query!(
             r#"
             SELECT * FROM sso_user_mappings
@@ -230,23 +484,43 @@ impl SsoRepository {
             provider_id: r.provider_id,
             external_id: r.external_id,
             email: r.email,
+            profile: serde_json::from_value(r.profile).unwrap_or_default(),
             created_at: r.created_at,
             updated_at: r.updated_at,
         }))
     }
 
+    /// Updates the email and profile of a user mapping
+    pub async fn update_user_mapping(&self, mapping: &SsoUserMapping) -> Result<()> {
+        let pool = &self.pool;
+        sqlx::query!(
+            r#"
+            UPDATE sso_user_mappings
+            SET email = $2, profile = $3, updated_at = NOW()
+            WHERE id = $1
+            "#,
+            mapping.id,
+            mapping.email,
+            profile_value(&mapping.profile)?,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
     /// Creates a new SSO session
     pub async fn create_session(&self, session: &SsoSession) -> Result<SsoSession> {
-        let pool = self.db.pool();
+        let pool = &self.pool;
         let mut tx = pool.begin().await?;
 
         let result = sqlx::query!(
             r#"
             INSERT INTO sso_sessions (
                 id, user_id, tenant_id, provider_id, session_index,
-                name_id, created_at, expires_at
+                name_id, id_token, created_at, expires_at
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
             RETURNING *
             "#,
             session.id,
@@ -255,6 +529,7 @@ impl SsoRepository {
             session.provider_id,
             session.session_index,
             session.name_id,
+            session.id_token,
             session.created_at,
             session.expires_at,
         )
@@ -270,6 +545,7 @@ impl SsoRepository {
             provider_id: result.provider_id,
             session_index: result.session_index,
             name_id: result.name_id,
+            id_token: result.id_token,
             created_at: result.created_at,
             expires_at: result.expires_at,
         })
@@ -277,7 +553,7 @@ impl SsoRepository {
 
     /// Gets a session by ID
     pub async fn get_session(&self, id: Uuid) -> Result<Option<SsoSession>> {
-        let pool = self.db.pool();
+        let pool = &self.pool;
         let result = sqlx::query!(
             r#"
             SELECT * FROM sso_sessions WHERE id = $1
@@ -294,14 +570,131 @@ impl SsoRepository {
             provider_id: r.provider_id,
             session_index: r.session_index,
             name_id: r.name_id,
+            id_token: r.id_token,
+            created_at: r.created_at,
+            expires_at: r.expires_at,
+        }))
+    }
+
+    /// Gets the newest active session of a user at a provider
+    pub async fn get_latest_user_session(
+        &self,
+        provider_id: Uuid,
+        user_id: UserId,
+    ) -> Result<Option<SsoSession>> {
+        let pool = &self.pool;
+        let result = sqlx::query!(
+            r#"
+            SELECT * FROM sso_sessions
+            WHERE provider_id = $1 AND user_id = $2 AND expires_at > NOW()
+            ORDER BY created_at DESC
+            LIMIT 1
+            "#,
+            provider_id,
+            user_id.0,
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(result.map(|r| SsoSession {
+            id: r.id,
+            user_id: UserId(r.user_id),
+            tenant_id: TenantId(r.tenant_id),
+            provider_id: r.provider_id,
+            session_index: r.session_index,
+            name_id: r.name_id,
+            id_token: r.id_token,
             created_at: r.created_at,
             expires_at: r.expires_at,
         }))
     }
 
+    /// Lists the sessions of a provider for the subject the IdP identifies by `name_id`
+    pub async fn list_sessions_by_name_id(
+        &self,
+        provider_id: Uuid,
+        name_id: &str,
+    ) -> Result<Vec<SsoSession>> {
+        let pool = &self.pool;
+        let results = sqlx::query!(
+            r#"
+            SELECT * FROM sso_sessions
+            WHERE provider_id = $1 AND name_id = $2
+            "#,
+            provider_id,
+            name_id,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| SsoSession {
+                id: r.id,
+                user_id: UserId(r.user_id),
+                tenant_id: TenantId(r.tenant_id),
+                provider_id: r.provider_id,
+                session_index: r.session_index,
+                name_id: r.name_id,
+                id_token: r.id_token,
+                created_at: r.created_at,
+                expires_at: r.expires_at,
+            })
+            .collect())
+    }
+
+    /// Lists the sessions of a provider created in the IdP session `session_index`
+    pub async fn list_sessions_by_session_index(
+        &self,
+        provider_id: Uuid,
+        session_index: &str,
+    ) -> Result<Vec<SsoSession>> {
+        let pool = &self.pool;
+        let results = sqlx::query!(
+            r#"
+            SELECT * FROM sso_sessions
+            WHERE provider_id = $1 AND session_index = $2
+            "#,
+            provider_id,
+            session_index,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| SsoSession {
+                id: r.id,
+                user_id: UserId(r.user_id),
+                tenant_id: TenantId(r.tenant_id),
+                provider_id: r.provider_id,
+                session_index: r.session_index,
+                name_id: r.name_id,
+                id_token: r.id_token,
+                created_at: r.created_at,
+                expires_at: r.expires_at,
+            })
+            .collect())
+    }
+
+    /// Deletes a session
+    pub async fn delete_session(&self, id: Uuid) -> Result<()> {
+        let pool = &self.pool;
+        sqlx::query!(
+            r#"
+            DELETE FROM sso_sessions WHERE id = $1
+            "#,
+            id,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
     /// Deletes expired sessions
     pub async fn cleanup_expired_sessions(&self) -> Result<u64> {
-        let pool = self.db.pool();
+        let pool = &self.pool;
         let result = sqlx::query!(
             r#"
             DELETE FROM sso_sessions
@@ -313,6 +706,397 @@ impl SsoRepository {
 
         Ok(result.rows_affected())
     }
+
+    /// Stores the tokens of a user's login at a provider, replacing earlier ones
+    ///
+    /// The tokens are stored as given, so the caller encrypts them.
+    pub async fn upsert_token(&self, token: &SsoToken) -> Result<()> {
+        let pool = &self.pool;
+        sqlx::query!(
+            r#"
+            INSERT INTO sso_tokens (
+                provider_id, user_id, tenant_id, access_token, refresh_token,
+                expires_at, scopes, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (provider_id, user_id) DO UPDATE
+            SET access_token = EXCLUDED.access_token,
+                refresh_token = COALESCE(EXCLUDED.refresh_token, sso_tokens.refresh_token),
+                expires_at = EXCLUDED.expires_at, scopes = EXCLUDED.scopes,
+                updated_at = EXCLUDED.updated_at
+            "#,
+            token.provider_id,
+            token.user_id.0,
+            token.tenant_id.0,
+            token.tokens.access_token,
+            token.tokens.refresh_token,
+            token.tokens.expires_at,
+            &token.tokens.scopes,
+            token.updated_at,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets the stored tokens of a user at a provider
+    pub async fn get_token(&self, provider_id: Uuid, user_id: UserId) -> Result<Option<SsoToken>> {
+        let pool = &self.pool;
+        let result = sqlx::query!(
+            r#"
+            SELECT * FROM sso_tokens
+            WHERE provider_id = $1 AND user_id = $2
+            "#,
+            provider_id,
+            user_id.0,
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(result.map(|r| SsoToken {
+            provider_id: r.provider_id,
+            user_id: UserId(r.user_id),
+            tenant_id: TenantId(r.tenant_id),
+            tokens: OidcTokens {
+                access_token: r.access_token,
+                refresh_token: r.refresh_token,
+                expires_at: r.expires_at,
+                scopes: r.scopes,
+            },
+            updated_at: r.updated_at,
+        }))
+    }
+
+    /// Lists the refreshable tokens whose access token expires before the given time
+    pub async fn list_expiring_tokens(&self, before: OffsetDateTime) -> Result<Vec<SsoToken>> {
+        let pool = &self.pool;
+        let results = sqlx::query!(
+            r#"
+            SELECT * FROM sso_tokens
+            WHERE refresh_token IS NOT NULL AND expires_at <= $1
+            ORDER BY expires_at
+            "#,
+            before,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| SsoToken {
+                provider_id: r.provider_id,
+                user_id: UserId(r.user_id),
+                tenant_id: TenantId(r.tenant_id),
+                tokens: OidcTokens {
+                    access_token: r.access_token,
+                    refresh_token: r.refresh_token,
+                    expires_at: r.expires_at,
+                    scopes: r.scopes,
+                },
+                updated_at: r.updated_at,
+            })
+            .collect())
+    }
+
+    /// Deletes the stored tokens of a user at a provider
+    pub async fn delete_token(&self, provider_id: Uuid, user_id: UserId) -> Result<()> {
+        let pool = &self.pool;
+        sqlx::query!(
+            r#"
+            DELETE FROM sso_tokens
+            WHERE provider_id = $1 AND user_id = $2
+            "#,
+            provider_id,
+            user_id.0,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records the ID of an accepted assertion, returning false if it was accepted before
+    ///
+    /// IDs are kept until the assertion expires; expired ones of the provider are
+    /// removed on the way.
+    pub async fn record_assertion(
+        &self,
+        provider_id: Uuid,
+        assertion_id: &str,
+        expires_at: OffsetDateTime,
+    ) -> Result<bool> {
+        let pool = &self.pool;
+        sqlx::query!(
+            r#"
+            DELETE FROM sso_used_assertions
+            WHERE provider_id = $1 AND expires_at <= NOW()
+            "#,
+            provider_id,
+        )
+        .execute(pool)
+        .await?;
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO sso_used_assertions (provider_id, assertion_id, expires_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (provider_id, assertion_id) DO NOTHING
+            "#,
+            provider_id,
+            assertion_id,
+            expires_at,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Creates a new link request
+    pub async fn create_link_request(&self, request: &SsoLinkRequest) -> Result<()> {
+        let pool = &self.pool;
+        sqlx::query!(
+            r#"
+            INSERT INTO sso_link_requests (
+                id, tenant_id, user_id, provider_id, external_id,
+                email, code_hash, attempts, expires_at, created_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+            request.id,
+            request.tenant_id.0,
+            request.user_id.0,
+            request.provider_id,
+            request.external_id,
+            request.email,
+            request.code_hash,
+            request.attempts,
+            request.expires_at,
+            request.created_at,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets a link request by ID
+    pub async fn get_link_request(&self, id: Uuid) -> Result<Option<SsoLinkRequest>> {
+        let pool = &self.pool;
+        let result = sqlx::query!(
+            r#"
+            SELECT * FROM sso_link_requests WHERE id = $1
+            "#,
+            id,
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(result.map(|r| SsoLinkRequest {
+            id: r.id,
+            tenant_id: TenantId(r.tenant_id),
+            user_id: UserId(r.user_id),
+            provider_id: r.provider_id,
+            external_id: r.external_id,
+            email: r.email,
+            code_hash: r.code_hash,
+            attempts: r.attempts,
+            expires_at: r.expires_at,
+            created_at: r.created_at,
+        }))
+    }
+
+    /// Stores the hash of the code emailed for a link request
+    pub async fn set_link_code(&self, id: Uuid, code_hash: &str) -> Result<()> {
+        let pool = &self.pool;
+        sqlx::query!(
+            r#"
+            UPDATE sso_link_requests
+            SET code_hash = $1
+            WHERE id = $2
+            "#,
+            code_hash,
+            id,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Counts a failed attempt to confirm a link request
+    pub async fn increment_link_attempts(&self, id: Uuid) -> Result<()> {
+        let pool = &self.pool;
+        sqlx::query!(
+            r#"
+            UPDATE sso_link_requests
+            SET attempts = attempts + 1
+            WHERE id = $1
+            "#,
+            id,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Replaces a link request by the user mapping it confirmed
+    pub async fn complete_link_request(
+        &self,
+        request: &SsoLinkRequest,
+        mapping: &SsoUserMapping,
+    ) -> Result<SsoUserMapping> {
+        let pool = &self.pool;
+        let mut tx = pool.begin().await?;
+
+        let deleted = sqlx::query!(
+            r#"
+            DELETE FROM sso_link_requests WHERE id = $1
+            "#,
+            request.id,
+        )
+        .execute(&mut *tx)
+        .await?;
+        if deleted.rows_affected() == 0 {
+            return Err(Error::NotFound("Link request not found".to_string()));
+        }
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO sso_user_mappings (
+                id, user_id, tenant_id, provider_id, external_id,
+                email, profile, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING *
+            "#,
+            mapping.id,
+            mapping.user_id.0,
+            mapping.tenant_id.0,
+            mapping.provider_id,
+            mapping.external_id,
+            mapping.email,
+            profile_value(&mapping.profile)?,
+            mapping.created_at,
+            mapping.updated_at,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(SsoUserMapping {
+            id: result.id,
+            user_id: UserId(result.user_id),
+            tenant_id: TenantId(result.tenant_id),
+            provider_id: result.provider_id,
+            external_id: result.external_id,
+            email: result.email,
+            profile: serde_json::from_value(result.profile).unwrap_or_default(),
+            created_at: result.created_at,
+            updated_at: result.updated_at,
+        })
+    }
+
+    /// Deletes link requests that expired
+    pub async fn cleanup_expired_link_requests(&self) -> Result<u64> {
+        let pool = &self.pool;
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM sso_link_requests
+            WHERE expires_at <= NOW()
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Lists the login events of a tenant's providers with one of the given actions, newest first
+    ///
+    /// Events are kept in the audit log, with the provider as record.
+    pub async fn list_auth_events(
+        &self,
+        tenant_id: TenantId,
+        actions: &[String],
+        query: &SsoAuthEventQuery,
+        limit: i64,
+    ) -> Result<Vec<AuditEvent>> {
+        let pool = &self.pool;
+        let results = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, user_id, action, table_name, record_id, new_values, created_at
+            FROM audit_log
+            WHERE tenant_id = $1
+              AND action = ANY($2)
+              AND table_name = 'sso_providers'
+              AND ($3::text IS NULL OR record_id = $3)
+              AND ($4::timestamptz IS NULL OR created_at >= $4 AT TIME ZONE 'UTC')
+              AND ($5::timestamptz IS NULL OR created_at < $5 AT TIME ZONE 'UTC')
+              AND ($6::text IS NULL OR new_values->>'request_id' = $6)
+            ORDER BY created_at DESC
+            LIMIT $7
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            actions,
+            query.provider_id.map(|id| id.to_string()),
+            query.from,
+            query.to,
+            query.request_id.as_deref(),
+            limit,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| AuditEvent {
+                id: r.id,
+                tenant_id: TenantId(r.tenant_id),
+                category: AuditCategory::Security,
+                action: r.action,
+                user_id: r.user_id.map(UserId),
+                table_name: r.table_name,
+                record_id: r.record_id,
+                details: r.new_values,
+                created_at: r.created_at.assume_utc(),
+            })
+            .collect())
+    }
+}
+
+/// Serializes the attribute mapping of a provider for storage
+fn attribute_mapping_value(mapping: &AttributeMapping) -> Result<serde_json::Value> {
+    serde_json::to_value(mapping)
+        .map_err(|e| Error::Internal(format!("Failed to serialize attribute mapping: {}", e)))
+}
+
+/// Reads the stored SAML signing settings of a provider, defaulting unknown values
+fn saml_signing(
+    signature_algorithm: &str,
+    digest_algorithm: &str,
+    canonicalization: &str,
+) -> SamlSigning {
+    SamlSigning {
+        signature_algorithm: signature_algorithm.parse().unwrap_or_default(),
+        digest_algorithm: digest_algorithm.parse().unwrap_or_default(),
+        canonicalization: canonicalization.parse().unwrap_or_default(),
+    }
+}
+
+/// Serializes the role mapping of a provider for storage
+fn role_mapping_value(mapping: &RoleMapping) -> Result<serde_json::Value> {
+    serde_json::to_value(mapping)
+        .map_err(|e| Error::Internal(format!("Failed to serialize role mapping: {}", e)))
+}
+
+/// Serializes the profile of a user mapping for storage
+fn profile_value(profile: &SsoProfile) -> Result<serde_json::Value> {
+    serde_json::to_value(profile)
+        .map_err(|e| Error::Internal(format!("Failed to serialize SSO profile: {}", e)))
 }
 
 #[cfg(test)]
@@ -339,13 +1123,14 @@ mod tests {
         let tenant_id = TenantId::new();
         sqlx::query!(
             r#"
-            INSERT INTO tenants (id, name)
-            VALUES ($1, $2)
+            INSERT INTO tenants (id, name, domain)
+            VALUES ($1, $2, $3)
             "#,
             tenant_id.0,
             "Test Tenant",
+            format!("{}.example.com", tenant_id.0),
         )
-        .execute(repository.db.pool())
+        .execute(repository.pool())
         .await
         .unwrap();
 
@@ -370,6 +1155,26 @@ mod tests {
         let providers = repository.list_providers(tenant_id).await.unwrap();
         assert!(!providers.is_empty());
         assert!(providers.iter().any(|p| p.id == created.id));
+
+        let mut changed = retrieved.clone();
+        changed.name = "Renamed SAML".to_string();
+        changed.single_logout_url = None;
+        let updated = repository.update_provider(&changed).await.unwrap();
+        assert_eq!(updated.name, "Renamed SAML");
+        assert!(updated.single_logout_url.is_none());
+
+        let disabled = repository
+            .set_provider_enabled(created.id, false)
+            .await
+            .unwrap();
+        assert!(!disabled.enabled);
+
+        repository.delete_provider(created.id).await.unwrap();
+        assert!(repository.get_provider(created.id).await.unwrap().is_none());
+        assert!(matches!(
+            repository.delete_provider(created.id).await,
+            Err(Error::NotFound(_))
+        ));
     }
 
     #[tokio::test]
@@ -393,13 +1198,14 @@ mod tests {
 
         sqlx::query!(
             r#"
-            INSERT INTO tenants (id, name)
-            VALUES ($1, $2)
+            INSERT INTO tenants (id, name, domain)
+            VALUES ($1, $2, $3)
             "#,
             tenant_id.0,
             "Test Tenant",
+            format!("{}.example.com", tenant_id.0),
         )
-        .execute(repository.db.pool())
+        .execute(repository.pool())
         .await
         .unwrap();
 
@@ -413,7 +1219,7 @@ mod tests {
             "test@example.com",
             "hash",
         )
-        .execute(repository.db.pool())
+        .execute(repository.pool())
         .await
         .unwrap();
 
@@ -472,13 +1278,14 @@ mod tests {
 
         sqlx::query!(
             r#"
-            INSERT INTO tenants (id, name)
-            VALUES ($1, $2)
+            INSERT INTO tenants (id, name, domain)
+            VALUES ($1, $2, $3)
             "#,
             tenant_id.0,
             "Test Tenant",
+            format!("{}.example.com", tenant_id.0),
         )
-        .execute(repository.db.pool())
+        .execute(repository.pool())
         .await
         .unwrap();
 
@@ -492,7 +1299,7 @@ mod tests {
             "test@example.com",
             "hash",
         )
-        .execute(repository.db.pool())
+        .execute(repository.pool())
         .await
         .unwrap();
 
@@ -540,5 +1347,16 @@ mod tests {
 
         let cleaned = repository.cleanup_expired_sessions().await.unwrap();
         assert_eq!(cleaned, 1);
+
+        // Assertions are accepted once
+        let expires_at = OffsetDateTime::now_utc() + Duration::minutes(5);
+        assert!(repository
+            .record_assertion(provider.id, "assertion-1", expires_at)
+            .await
+            .unwrap());
+        assert!(!repository
+            .record_assertion(provider.id, "assertion-1", expires_at)
+            .await
+            .unwrap());
     }
-}
\ No newline at end of file
+}
@@ -1,68 +1,163 @@
+use async_trait::async_trait;
+use rand::Rng;
+use ring::digest;
+use std::sync::Arc;
 use time::{Duration, OffsetDateTime};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
-use crate::shared::{
-    error::{Error, Result},
-    types::{TenantId, UserId},
+use crate::{
+    core::config::SsoConfig,
+    modules::identity::{
+        auth::AuthenticationService,
+        hooks::AuthHook,
+        models::{PermissionAction, User},
+        rbac::{ensure_tenant_boundary, has_permission, is_super_admin},
+        repository::{RoleRepository, UserRepository},
+        service::record_audit_event,
+        session::{Session, SessionCipher},
+    },
+    shared::{
+        audit::{AuditCategory, AuditEvent, AuditStream},
+        cache::{CacheInvalidation, CacheInvalidationBus},
+        error::{Error, Result},
+        mail::{EmailMessage, LogMailer, Mailer},
+        secrets::{SchemeSecretResolver, SecretResolver},
+        types::{TenantId, UserId},
+    },
 };
 
 use super::{
-    models::{SsoProvider, SsoProviderType, SsoSession, SsoUserMapping},
-    oidc::{OidcConfig, OidcService},
+    flow::{InMemoryOidcFlowStore, OidcFlow, OidcFlowStore},
+    models::{
+        OidcTokens, SamlAssertion, SamlMetadataImport, SamlMetadataImportRequest, SsoAccessToken,
+        SsoAuthEventQuery, SsoAuthStage, SsoFailureCategory, SsoIdentity, SsoLinkProof,
+        SsoLinkRequest, SsoLoginResolution, SsoProvider, SsoProviderType, SsoProviderUpdate,
+        SsoSession, SsoToken, SsoUserMapping,
+    },
+    oidc::{validate_oidc_controls, validate_redirect_uris, OidcConfig, OidcService},
+    presets::SsoPresetRequest,
     repository::SsoRepository,
-    saml::{SamlConfig, SamlService},
+    saml::{parse_idp_metadata, SamlConfig, SamlService, SamlSigningKey, SUCCESS_STATUS},
+    wsfed::{parse_wsfed_metadata, WsFedService},
 };
 
-/// SSO service configuration
-#[derive(Debug, Clone)]
-pub struct SsoConfig {
-    pub saml: SamlConfig,
-    pub oidc: OidcConfig,
-}
+/// Minutes a user has to confirm linking an SSO identity to their account
+pub const LINK_REQUEST_TTL_MINUTES: i64 = 15;
+
+/// Failed confirmations after which a link request can no longer be used
+pub const MAX_LINK_ATTEMPTS: i32 = 5;
+
+/// Hours an SSO session is kept for logging the user out at the IdP
+const SSO_SESSION_TTL_HOURS: i64 = 8;
+
+/// Seconds to wait for an identity provider to serve its metadata
+const METADATA_FETCH_TIMEOUT_SECS: u64 = 10;
+
+/// Days before the expiry of an IdP signing certificate from which refreshes warn about it
+const CERTIFICATE_EXPIRY_WARNING_DAYS: i64 = 30;
+
+/// Default number of SSO login events returned by a query
+const DEFAULT_AUTH_EVENT_LIMIT: i64 = 100;
+
+/// Maximum number of SSO login events returned by a query
+const MAX_AUTH_EVENT_LIMIT: i64 = 1000;
+
+/// Minutes before its expiry from which a stored IdP access token is refreshed
+const TOKEN_REFRESH_MARGIN_MINUTES: i64 = 5;
 
 /// SSO service for handling authentication
 #[derive(Debug)]
 pub struct SsoService {
     repository: SsoRepository,
+    users: UserRepository,
+    roles: RoleRepository,
+    invalidation: CacheInvalidationBus,
+    mailer: Arc<dyn Mailer>,
+    secrets: SchemeSecretResolver,
+    audit: AuditStream,
+    flows: Arc<dyn OidcFlowStore>,
     saml_service: SamlService,
     oidc_service: OidcService,
+    wsfed_service: WsFedService,
+    /// Key of the stored tokens of OIDC providers, which cannot store them without one
+    token_cipher: Option<SessionCipher>,
 }
 
 impl SsoService {
     /// Creates a new SsoService instance
-    pub fn new(repository: SsoRepository) -> Self {
-        let saml_config = SamlConfig {
-            certificate: std::env::var("SAML_CERTIFICATE")
-                .expect("SAML_CERTIFICATE must be set"),
-            private_key: std::env::var("SAML_PRIVATE_KEY")
-                .expect("SAML_PRIVATE_KEY must be set"),
-            organization_name: std::env::var("SAML_ORG_NAME")
-                .expect("SAML_ORG_NAME must be set"),
-            organization_display_name: std::env::var("SAML_ORG_DISPLAY_NAME")
-                .expect("SAML_ORG_DISPLAY_NAME must be set"),
-            organization_url: std::env::var("SAML_ORG_URL")
-                .expect("SAML_ORG_URL must be set"),
-            technical_contact_name: std::env::var("SAML_TECH_CONTACT_NAME")
-                .expect("SAML_TECH_CONTACT_NAME must be set"),
-            technical_contact_email: std::env::var("SAML_TECH_CONTACT_EMAIL")
-                .expect("SAML_TECH_CONTACT_EMAIL must be set"),
-        };
+    ///
+    /// Fails if the configuration lacks a required SAML or OIDC setting or has
+    /// an invalid token encryption key.
+    pub fn new(
+        repository: SsoRepository,
+        users: UserRepository,
+        config: &SsoConfig,
+    ) -> Result<Self> {
+        Ok(Self {
+            repository,
+            roles: RoleRepository::new(users.get_pool().clone()),
+            users,
+            invalidation: CacheInvalidationBus::new(),
+            mailer: Arc::new(LogMailer),
+            secrets: SchemeSecretResolver::new(),
+            audit: AuditStream::new(),
+            flows: Arc::new(InMemoryOidcFlowStore::new()),
+            saml_service: SamlService::new(saml_config(config)?),
+            oidc_service: OidcService::new(oidc_config(config)),
+            wsfed_service: WsFedService::new(),
+            token_cipher: config
+                .token_encryption_key
+                .as_deref()
+                .map(SessionCipher::from_base64)
+                .transpose()?,
+        })
+    }
 
-        let oidc_config = OidcConfig {
-            redirect_url: std::env::var("OIDC_REDIRECT_URL")
-                .expect("OIDC_REDIRECT_URL must be set"),
-        };
+    /// Uses the given mailer for account link codes
+    pub fn with_mailer(mut self, mailer: Arc<dyn Mailer>) -> Self {
+        self.mailer = mailer;
+        self
+    }
 
-        Self {
-            repository,
-            saml_service: SamlService::new(saml_config),
-            oidc_service: OidcService::new(oidc_config),
-        }
+    /// Streams SSO login events to the given stream, e.g. one shared with the identity module
+    pub fn with_audit_stream(mut self, audit: AuditStream) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Keeps pending OIDC logins in the given store, e.g. Redis when running several instances
+    pub fn with_flow_store(mut self, flows: Arc<dyn OidcFlowStore>) -> Self {
+        self.flows = flows;
+        self
+    }
+
+    /// Uses the given resolver for client secrets stored as references
+    ///
+    /// Without one, providers can only store their client secrets inline. Tenant
+    /// admins choose the references, so the Vault token should only grant access
+    /// to the secrets of IdPs.
+    pub fn with_secret_resolver(mut self, secrets: SchemeSecretResolver) -> Self {
+        self.secrets = secrets;
+        self
+    }
+
+    /// Uses the given bus to invalidate the permissions of users whose roles SSO logins changed
+    pub fn with_cache_invalidation(mut self, invalidation: CacheInvalidationBus) -> Self {
+        self.invalidation = invalidation;
+        self
     }
 
     /// Creates a new SSO provider
     pub async fn create_provider(&self, provider: &SsoProvider) -> Result<SsoProvider> {
-        // Validate provider configuration
+        self.validate_provider(provider)?;
+        self.validate_role_mapping(provider).await?;
+        self.repository.create_provider(provider).await
+    }
+
+    /// Checks that a provider has the configuration its type requires
+    fn validate_provider(&self, provider: &SsoProvider) -> Result<()> {
         match provider.provider_type {
             SsoProviderType::Saml => {
                 if provider.entity_id.is_none() || provider.assertion_consumer_service_url.is_none() {
@@ -71,20 +166,58 @@ impl SsoService {
                             .to_string(),
                     ));
                 }
+                self.saml_service.validate_signing(&provider.saml_signing)?;
             }
             SsoProviderType::Oidc => {
                 if provider.client_id.is_none()
-                    || provider.client_secret.is_none()
+                    || (provider.client_secret.is_none() && provider.client_secret_ref.is_none())
                     || provider.issuer.is_none()
                 {
                     return Err(Error::InvalidInput(
-                        "OIDC provider requires client_id, client_secret, and issuer".to_string(),
+                        "OIDC provider requires client_id, client_secret or client_secret_ref, and issuer"
+                            .to_string(),
                     ));
                 }
+                if let Some(reference) = &provider.client_secret_ref {
+                    if !self.secrets.supports(reference) {
+                        return Err(Error::InvalidInput(format!(
+                            "Unsupported client secret reference {}",
+                            reference
+                        )));
+                    }
+                }
+                validate_oidc_controls(&provider.oidc_validation)?;
+                validate_redirect_uris(&provider.redirect_uris)?;
             }
+            SsoProviderType::WsFed => {
+                if provider.entity_id.is_none() || provider.assertion_consumer_service_url.is_none() {
+                    return Err(Error::InvalidInput(
+                        "WS-Federation provider requires entity_id (the realm) and assertion_consumer_service_url"
+                            .to_string(),
+                    ));
+                }
+                let metadata = provider.metadata_xml.as_deref().ok_or_else(|| {
+                    Error::InvalidInput(
+                        "WS-Federation provider requires the federation metadata of its STS"
+                            .to_string(),
+                    )
+                })?;
+                parse_wsfed_metadata(metadata)?;
+            },
         }
-
-        self.repository.create_provider(provider).await
+        if provider.store_tokens {
+            if provider.provider_type != SsoProviderType::Oidc {
+                return Err(Error::InvalidInput(
+                    "Only OIDC providers can store tokens".to_string(),
+                ));
+            }
+            if self.token_cipher.is_none() {
+                return Err(Error::InvalidInput(
+                    "Storing tokens requires a token encryption key".to_string(),
+                ));
+            }
+        }
+        Ok(())
     }
 
     /// Gets a provider by ID
@@ -97,11 +230,400 @@ impl SsoService {
         self.repository.list_providers(tenant_id).await
     }
 
-    /// Initiates SSO authentication
+    /// Creates a provider on behalf of an admin, confined to the admin's tenant
+    pub async fn create_managed_provider(
+        &self,
+        actor: &User,
+        provider: &SsoProvider,
+    ) -> Result<SsoProvider> {
+        ensure_tenant_boundary(actor, provider.tenant_id)?;
+        if !has_permission(actor, PermissionAction::Create, "sso_providers") {
+            return Err(Error::Authorization(
+                "Missing permission to create SSO providers".to_string(),
+            ));
+        }
+        self.create_provider(provider).await
+    }
+
+    /// Changes the configuration of a provider the actor administers, e.g. to rotate its secret
+    pub async fn update_managed_provider(
+        &self,
+        actor: &User,
+        id: Uuid,
+        update: SsoProviderUpdate,
+    ) -> Result<SsoProvider> {
+        let mut provider = self
+            .get_administered_provider(actor, id, PermissionAction::Update)
+            .await?;
+        update.apply(&mut provider);
+        self.validate_provider(&provider)?;
+        self.validate_role_mapping(&provider).await?;
+        self.repository.update_provider(&provider).await
+    }
+
+    /// Enables or disables a provider the actor administers
+    ///
+    /// Logins through a disabled provider are rejected; its user mappings stay
+    /// so enabling it again restores access.
+    pub async fn set_managed_provider_enabled(
+        &self,
+        actor: &User,
+        id: Uuid,
+        enabled: bool,
+    ) -> Result<SsoProvider> {
+        let provider = self
+            .get_administered_provider(actor, id, PermissionAction::Update)
+            .await?;
+        self.repository
+            .set_provider_enabled(provider.id, enabled)
+            .await
+    }
+
+    /// Deletes a provider the actor administers, retiring its IdP
+    ///
+    /// The user mappings of the provider are deleted with it, so its identities
+    /// have to be linked again if the IdP is added back.
+    pub async fn delete_managed_provider(&self, actor: &User, id: Uuid) -> Result<()> {
+        let provider = self
+            .get_administered_provider(actor, id, PermissionAction::Delete)
+            .await?;
+        self.repository.delete_provider(provider.id).await
+    }
+
+    /// Gets a provider the actor may change with the given action
+    async fn get_administered_provider(
+        &self,
+        actor: &User,
+        id: Uuid,
+        action: PermissionAction,
+    ) -> Result<SsoProvider> {
+        let provider = self
+            .get_managed_provider(actor, id)
+            .await?
+            .ok_or_else(|| Error::NotFound("SSO provider not found".to_string()))?;
+        if !has_permission(actor, action, "sso_providers") {
+            return Err(Error::Authorization(format!(
+                "Missing permission to {} SSO providers",
+                action
+            )));
+        }
+        Ok(provider)
+    }
+
+    /// Creates a SAML provider in the actor's tenant from the metadata of its IdP
+    ///
+    /// The metadata is either given inline or fetched from its URL, and is kept
+    /// with the provider so the signing certificates remain available.
+    pub async fn import_saml_metadata(
+        &self,
+        actor: &User,
+        request: &SamlMetadataImportRequest,
+    ) -> Result<SamlMetadataImport> {
+        let xml = match (&request.metadata_xml, &request.metadata_url) {
+            (Some(xml), None) => xml.clone(),
+            (None, Some(url)) => self.fetch_metadata(url).await?,
+            _ => {
+                return Err(Error::Validation(
+                    "Either metadata_xml or metadata_url must be given".to_string(),
+                ))
+            },
+        };
+        let metadata = parse_idp_metadata(&xml)?;
+
+        let provider = SsoProvider::new_saml(
+            actor.tenant_id,
+            request.name.clone(),
+            request.description.clone(),
+            request.metadata_url.clone(),
+            Some(xml),
+            metadata.entity_id.clone(),
+            request.assertion_consumer_service_url.clone(),
+            metadata.slo_url.clone(),
+        );
+        let provider = self.create_managed_provider(actor, &provider).await?;
+
+        Ok(SamlMetadataImport { provider, metadata })
+    }
+
+    /// Creates a provider in the actor's tenant from the preset of a known IdP
+    ///
+    /// A SAML provider is created from the IdP's metadata if the request names
+    /// its URL, an OIDC provider from the directory and client credentials otherwise.
+    pub async fn create_provider_from_preset(
+        &self,
+        actor: &User,
+        request: &SsoPresetRequest,
+    ) -> Result<SsoProvider> {
+        let provider = match &request.metadata_url {
+            Some(metadata_url) => {
+                let xml = self.fetch_metadata(metadata_url).await?;
+                let metadata = parse_idp_metadata(&xml)?;
+                request
+                    .preset
+                    .saml_provider(actor.tenant_id, request, xml, &metadata)?
+            },
+            None => request.preset.oidc_provider(actor.tenant_id, request)?,
+        };
+        self.create_managed_provider(actor, &provider).await
+    }
+
+    /// Re-fetches the metadata of a provider, returning whether its certificates or endpoints changed
+    ///
+    /// Metadata announcing a different entity ID is rejected rather than
+    /// silently switching the provider to another IdP.
+    pub async fn refresh_provider_metadata(&self, provider: &SsoProvider) -> Result<bool> {
+        let url = provider
+            .metadata_url
+            .as_deref()
+            .ok_or_else(|| Error::Validation("SSO provider has no metadata URL".to_string()))?;
+        let xml = self.fetch_metadata(url).await?;
+        let metadata = parse_idp_metadata(&xml)?;
+        if provider.entity_id.as_deref() != Some(metadata.entity_id.as_str()) {
+            return Err(Error::Validation(format!(
+                "SAML metadata of provider {} announces entity ID {}",
+                provider.id, metadata.entity_id
+            )));
+        }
+
+        if metadata.certificates_expire_at
+            <= OffsetDateTime::now_utc() + Duration::days(CERTIFICATE_EXPIRY_WARNING_DAYS)
+        {
+            warn!(
+                "Signing certificate of SSO provider {} ({}) expires at {}",
+                provider.id, provider.name, metadata.certificates_expire_at
+            );
+        }
+
+        if provider.metadata_xml.as_deref() == Some(xml.as_str())
+            && provider.single_logout_url == metadata.slo_url
+        {
+            return Ok(false);
+        }
+        let mut updated = provider.clone();
+        updated.metadata_xml = Some(xml);
+        updated.single_logout_url = metadata.slo_url;
+        self.repository.update_provider(&updated).await?;
+        Ok(true)
+    }
+
+    /// Refreshes the metadata of all providers with a metadata URL, returning how many changed
+    ///
+    /// A provider whose metadata cannot be fetched or parsed keeps its stored
+    /// metadata and does not stop the others from being refreshed.
+    pub async fn refresh_all_metadata(&self) -> Result<usize> {
+        let providers = self.repository.list_metadata_providers().await?;
+        let mut refreshed = 0;
+        for provider in providers
+            .iter()
+            .filter(|provider| provider.provider_type == SsoProviderType::Saml)
+        {
+            match self.refresh_provider_metadata(provider).await {
+                Ok(true) => refreshed += 1,
+                Ok(false) => {},
+                Err(e) => error!(
+                    "Failed to refresh metadata of SSO provider {}: {}",
+                    provider.id, e
+                ),
+            }
+        }
+        Ok(refreshed)
+    }
+
+    /// Periodically refreshes the metadata of providers with a metadata URL
+    pub fn spawn_metadata_refresh_worker(
+        self: Arc<Self>,
+        interval: std::time::Duration,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match self.refresh_all_metadata().await {
+                    Ok(0) => {},
+                    Ok(refreshed) => info!("Updated the metadata of {} SSO providers", refreshed),
+                    Err(e) => error!("Failed to refresh SSO provider metadata: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Downloads the metadata document of an identity provider
+    async fn fetch_metadata(&self, url: &str) -> Result<String> {
+        let url = url::Url::parse(url)
+            .map_err(|e| Error::Validation(format!("Invalid metadata URL: {}", e)))?;
+        if url.scheme() != "https" {
+            return Err(Error::Validation("Metadata URL must use https".to_string()));
+        }
+
+        let response = reqwest::Client::new()
+            .get(url)
+            .timeout(std::time::Duration::from_secs(METADATA_FETCH_TIMEOUT_SECS))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Error::Validation(format!("Failed to fetch SAML metadata: {}", e)))?;
+        response
+            .text()
+            .await
+            .map_err(|e| Error::Validation(format!("Failed to read SAML metadata: {}", e)))
+    }
+
+    /// Gets a provider the actor administers, hiding providers of other tenants
+    pub async fn get_managed_provider(
+        &self,
+        actor: &User,
+        id: Uuid,
+    ) -> Result<Option<SsoProvider>> {
+        Ok(self
+            .get_provider(id)
+            .await?
+            .filter(|provider| ensure_tenant_boundary(actor, provider.tenant_id).is_ok()))
+    }
+
+    /// Lists the providers of a tenant the actor administers
+    pub async fn list_managed_providers(
+        &self,
+        actor: &User,
+        tenant_id: TenantId,
+    ) -> Result<Vec<SsoProvider>> {
+        ensure_tenant_boundary(actor, tenant_id)?;
+        self.list_providers(tenant_id).await
+    }
+
+    /// Generates the service provider metadata of a SAML provider
+    pub fn generate_metadata(&self, provider: &SsoProvider) -> Result<String> {
+        if provider.provider_type != SsoProviderType::Saml {
+            return Err(Error::InvalidInput(
+                "Only SAML providers publish metadata".to_string(),
+            ));
+        }
+        self.saml_service.generate_metadata(provider)
+    }
+
+    /// Promotes the next SAML signing key of the service provider, completing a rollover
+    ///
+    /// The key is shared by all tenants, so only super admins may promote it.
+    pub fn promote_saml_signing_key(&self, actor: &User) -> Result<()> {
+        if !is_super_admin(actor) {
+            return Err(Error::Authorization(
+                "Only super admins may rotate the SAML signing key".to_string(),
+            ));
+        }
+        self.saml_service.promote_next_key()?;
+        info!("Promoted the next SAML signing key");
+        Ok(())
+    }
+
+    /// Records that a login at a provider was started, or the error it could not be started with
+    pub async fn record_login_initiated(
+        &self,
+        provider: &SsoProvider,
+        error: Option<&Error>,
+        request_id: Option<&str>,
+    ) {
+        let stage = match error {
+            None => SsoAuthStage::Initiated,
+            Some(_) => SsoAuthStage::Failed,
+        };
+        self.record_auth_event(provider, stage, None, error, request_id)
+            .await;
+    }
+
+    /// Records the outcome of a login the IdP returned the user from
+    ///
+    /// The subject is the user's ID at the IdP, if the response was valid enough to tell.
+    pub async fn record_login_completed(
+        &self,
+        provider: &SsoProvider,
+        subject: Option<&str>,
+        error: Option<&Error>,
+        request_id: Option<&str>,
+    ) {
+        let stage = match error {
+            None => SsoAuthStage::Succeeded,
+            Some(_) => SsoAuthStage::Failed,
+        };
+        self.record_auth_event(provider, stage, subject, error, request_id)
+            .await;
+    }
+
+    /// Records a stage of a login in the event trail of its provider
+    ///
+    /// Messages of internal errors are left out since tenant admins read the trail.
+    async fn record_auth_event(
+        &self,
+        provider: &SsoProvider,
+        stage: SsoAuthStage,
+        subject: Option<&str>,
+        error: Option<&Error>,
+        request_id: Option<&str>,
+    ) {
+        let category = error.map(SsoFailureCategory::from_error);
+        let message = error
+            .filter(|_| category != Some(SsoFailureCategory::Internal))
+            .map(Error::message);
+        let event = AuditEvent::new(
+            provider.tenant_id,
+            AuditCategory::Security,
+            stage.action(),
+            "sso_providers",
+            provider.id,
+        )
+        .with_detail("provider_type", provider.provider_type.to_string())
+        .with_detail("subject", subject)
+        .with_detail("error_category", category)
+        .with_detail("error", message)
+        .with_detail("request_id", request_id);
+        record_audit_event(&self.users, &self.audit, event).await;
+    }
+
+    /// Lists the login events of the providers of the actor's tenant, newest first
+    pub async fn list_auth_events(
+        &self,
+        actor: &User,
+        query: SsoAuthEventQuery,
+    ) -> Result<Vec<AuditEvent>> {
+        if !has_permission(actor, PermissionAction::Read, "audit_log") {
+            return Err(Error::Authorization(
+                "Missing permission to read the audit log".to_string(),
+            ));
+        }
+        let actions: Vec<String> = match query.stage {
+            Some(stage) => vec![stage.action().to_string()],
+            None => SsoAuthStage::ALL
+                .iter()
+                .map(|stage| stage.action().to_string())
+                .collect(),
+        };
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_AUTH_EVENT_LIMIT)
+            .clamp(1, MAX_AUTH_EVENT_LIMIT);
+        self.repository
+            .list_auth_events(actor.tenant_id, &actions, &query, limit)
+            .await
+    }
+
+    /// Copies a provider with its client secret resolved from the secret store it references
+    async fn with_resolved_secret(&self, provider: &SsoProvider) -> Result<SsoProvider> {
+        let mut provider = provider.clone();
+        if let Some(reference) = &provider.client_secret_ref {
+            provider.client_secret = Some(self.secrets.resolve(reference).await?);
+        }
+        Ok(provider)
+    }
+
+    /// Initiates SSO authentication, returning where to send the user and the
+    /// SAML relay state, OIDC state or WS-Federation context of the login
+    ///
+    /// OIDC logins return to the given redirect URI, which must be registered
+    /// with the provider, or to its default one. Their nonce and PKCE verifier
+    /// are kept in the flow store under the state.
     pub async fn initiate_auth(
         &self,
         provider: &SsoProvider,
-    ) -> Result<(String, Option<String>, Option<String>)> {
+        redirect_uri: Option<&str>,
+    ) -> Result<(String, String)> {
         if !provider.enabled {
             return Err(Error::Authentication(
                 "SSO provider is disabled".to_string(),
@@ -111,27 +633,43 @@ impl SsoService {
         match provider.provider_type {
             SsoProviderType::Saml => {
                 let (request, relay_state) = self.saml_service.create_auth_request(provider)?;
-                Ok((request, Some(relay_state), None))
+                Ok((request, relay_state))
             }
             SsoProviderType::Oidc => {
-                let (url, csrf_token, nonce) = self.oidc_service.create_auth_url(provider).await?;
-                Ok((
-                    url.to_string(),
-                    Some(csrf_token.secret().to_string()),
-                    Some(nonce.secret().to_string()),
-                ))
+                let provider = self.with_resolved_secret(provider).await?;
+                let (url, csrf_token, nonce, pkce_verifier) = self
+                    .oidc_service
+                    .create_auth_url(&provider, redirect_uri)
+                    .await?;
+                let flow = OidcFlow::new(
+                    provider.id,
+                    nonce.secret().to_string(),
+                    pkce_verifier.secret().to_string(),
+                    redirect_uri.map(str::to_string),
+                );
+                self.flows.store_flow(csrf_token.secret(), &flow).await?;
+                Ok((url.to_string(), csrf_token.secret().to_string()))
             }
+            SsoProviderType::WsFed => {
+                let context = Uuid::new_v4().to_string();
+                let url = self.wsfed_service.create_signin_url(provider, &context)?;
+                Ok((url, context))
+            },
         }
     }
 
     /// Validates SSO response
+    ///
+    /// The identity's email and profile are read as the provider's attribute
+    /// mapping configures. The state is the SAML relay state or WS-Federation
+    /// context, missing for IdP-initiated logins, or the OIDC state; OIDC logins
+    /// complete the flow stored under it, which can only be used once.
     pub async fn validate_response(
         &self,
         provider: &SsoProvider,
         response: &str,
         relay_state: Option<&str>,
-        nonce: Option<&str>,
-    ) -> Result<(String, String)> {
+    ) -> Result<SsoIdentity> {
         if !provider.enabled {
             return Err(Error::Authentication(
                 "SSO provider is disabled".to_string(),
@@ -140,44 +678,297 @@ impl SsoService {
 
         match provider.provider_type {
             SsoProviderType::Saml => {
-                let relay_state = relay_state.ok_or_else(|| {
-                    Error::Authentication("Missing SAML relay state".to_string())
-                })?;
+                if relay_state.is_none() && !provider.allow_idp_initiated {
+                    return Err(Error::Authentication(
+                        "IdP-initiated SSO is not allowed for this provider".to_string(),
+                    ));
+                }
 
-                let (name_id, session_index, email) =
+                let assertion =
                     self.saml_service
                         .validate_response(provider, response, relay_state)?;
+                self.assertion_identity(provider, assertion).await
+            }
+            SsoProviderType::Oidc => {
+                let state = relay_state
+                    .ok_or_else(|| Error::Authentication("Missing OIDC state".to_string()))?;
+                let flow = self
+                    .flows
+                    .take_flow(state)
+                    .await?
+                    .filter(|flow| flow.provider_id == provider.id && !flow.is_expired())
+                    .ok_or_else(|| {
+                        Error::Authentication("Unknown or expired OIDC state".to_string())
+                    })?;
 
-                // Create SSO session if session index is provided
-                if let Some(session_index) = session_index {
-                    self.create_session(
-                        provider.id,
-                        &name_id,
-                        Some(session_index),
-                        Some(name_id.clone()),
+                let resolved = self.with_resolved_secret(provider).await?;
+                let login = self
+                    .oidc_service
+                    .validate_auth_code(
+                        &resolved,
+                        response,
+                        openidconnect::Nonce::new(flow.nonce),
+                        openidconnect::PkceCodeVerifier::new(flow.pkce_verifier),
+                        flow.redirect_uri.as_deref(),
                     )
                     .await?;
+
+                // Keep the session of known users so they can be logged out at the provider
+                let mut session_id = None;
+                if let Some(mapping) = self.get_user_mapping(provider.id, &login.subject).await? {
+                    let session = SsoSession::new(
+                        mapping.user_id,
+                        mapping.tenant_id,
+                        provider.id,
+                        login.session_id,
+                        Some(login.subject.clone()),
+                        OffsetDateTime::now_utc() + Duration::hours(SSO_SESSION_TTL_HOURS),
+                    )
+                    .with_id_token(login.id_token);
+                    session_id = Some(self.repository.create_session(&session).await?.id);
                 }
 
-                Ok((name_id, email.unwrap_or_else(|| name_id.clone())))
+                let mapping = &provider.attribute_mapping;
+                Ok(SsoIdentity {
+                    email: mapping.email(&login.claims, &login.email),
+                    profile: mapping.profile(&login.claims),
+                    groups: provider.role_mapping.groups(&login.claims),
+                    external_id: login.subject,
+                    session_id,
+                    tokens: provider.store_tokens.then_some(login.tokens),
+                })
             }
-            SsoProviderType::Oidc => {
-                let nonce = nonce.ok_or_else(|| {
-                    Error::Authentication("Missing OIDC nonce".to_string())
-                })?;
+            SsoProviderType::WsFed => {
+                if relay_state.is_none() && !provider.allow_idp_initiated {
+                    return Err(Error::Authentication(
+                        "IdP-initiated SSO is not allowed for this provider".to_string(),
+                    ));
+                }
+                let assertion = self.wsfed_service.validate_response(provider, response)?;
+                self.assertion_identity(provider, assertion).await
+            },
+        }
+    }
 
-                let (subject, email) = self
+    /// Builds the identity of a validated SAML or WS-Federation assertion
+    ///
+    /// Assertions can only be used once. The SSO session of a known user is kept
+    /// so a SAML IdP can log them out, or so logging out of the application also
+    /// signs them out of the WS-Federation STS.
+    async fn assertion_identity(
+        &self,
+        provider: &SsoProvider,
+        assertion: SamlAssertion,
+    ) -> Result<SsoIdentity> {
+        if !self
+            .repository
+            .record_assertion(provider.id, &assertion.id, assertion.expires_at)
+            .await?
+        {
+            return Err(Error::Authentication(
+                "SAML assertion was already used".to_string(),
+            ));
+        }
+
+        let mut session_id = None;
+        if assertion.session_index.is_some() || provider.provider_type == SsoProviderType::WsFed {
+            if let Some(mapping) = self
+                .get_user_mapping(provider.id, &assertion.name_id)
+                .await?
+            {
+                let session = SsoSession::new(
+                    mapping.user_id,
+                    mapping.tenant_id,
+                    provider.id,
+                    assertion.session_index.clone(),
+                    Some(assertion.name_id.clone()),
+                    OffsetDateTime::now_utc() + Duration::hours(SSO_SESSION_TTL_HOURS),
+                );
+                session_id = Some(self.repository.create_session(&session).await?.id);
+            }
+        }
+
+        let mapping = &provider.attribute_mapping;
+        let default_email = assertion.email.as_deref().unwrap_or(&assertion.name_id);
+        Ok(SsoIdentity {
+            email: mapping.email(&assertion.attributes, default_email),
+            profile: mapping.profile(&assertion.attributes),
+            groups: provider.role_mapping.groups(&assertion.attributes),
+            external_id: assertion.name_id,
+            session_id,
+            tokens: None,
+        })
+    }
+
+    /// Builds the IdP logout redirect for the SSO session an application session was opened with
+    ///
+    /// Sessions opened before they were linked fall back to the newest SSO session
+    /// of the user. Returns `None` if there is no such session or the provider has
+    /// no logout endpoint. The SSO session ends once the IdP confirms the logout,
+    /// the application session is revoked or, for OIDC, the IdP returns the user.
+    pub async fn initiate_logout(
+        &self,
+        provider: &SsoProvider,
+        app_session: &Session,
+    ) -> Result<Option<String>> {
+        let session = match app_session.sso_session_id() {
+            Some(id) => self.repository.get_session(id).await?.filter(|session| {
+                session.provider_id == provider.id && session.user_id == app_session.user_id
+            }),
+            None => {
+                self.repository
+                    .get_latest_user_session(provider.id, app_session.user_id)
+                    .await?
+            },
+        };
+        let Some(session) = session else {
+            return Ok(None);
+        };
+
+        match provider.provider_type {
+            SsoProviderType::Saml => {
+                if provider.single_logout_url.is_none() || session.name_id.is_none() {
+                    return Ok(None);
+                }
+                self.saml_service
+                    .create_logout_request(provider, &session, &session.id.to_string())
+                    .map(Some)
+            },
+            SsoProviderType::Oidc => {
+                let resolved = self.with_resolved_secret(provider).await?;
+                let url = self
                     .oidc_service
-                    .validate_auth_code(
-                        provider,
-                        response,
-                        openidconnect::Nonce::new(nonce.to_string()),
+                    .end_session_url(
+                        &resolved,
+                        session.id_token.as_deref(),
+                        &session.id.to_string(),
                     )
                     .await?;
+                if url.is_none() {
+                    // No user will return from the provider to end the session
+                    self.repository.delete_session(session.id).await?;
+                }
+                Ok(url.map(String::from))
+            },
+            SsoProviderType::WsFed => {
+                // The STS does not confirm sign-outs, so the session ends right away
+                self.repository.delete_session(session.id).await?;
+                self.wsfed_service.create_signout_url(provider).map(Some)
+            },
+        }
+    }
+
+    /// Ends the SSO session of a user the OIDC provider returned after logging them out
+    ///
+    /// The `state` carries the session the logout was initiated for.
+    pub async fn complete_oidc_logout(&self, state: &str) -> Result<()> {
+        let session_id = Uuid::parse_str(state)
+            .map_err(|_| Error::InvalidInput("Invalid logout state".to_string()))?;
+        if let Some(session) = self.repository.get_session(session_id).await? {
+            self.repository.delete_session(session.id).await?;
+        }
+        Ok(())
+    }
+
+    /// Handles a front-channel logout of the OIDC provider, ending the SSO sessions of its session `sid`
+    ///
+    /// Returns the ended sessions, whose users must also be signed out of the
+    /// application.
+    pub async fn handle_frontchannel_logout(
+        &self,
+        provider: &SsoProvider,
+        issuer: Option<&str>,
+        sid: &str,
+    ) -> Result<Vec<SsoSession>> {
+        if provider.provider_type != SsoProviderType::Oidc {
+            return Err(Error::InvalidInput(
+                "Only OIDC providers send front-channel logouts".to_string(),
+            ));
+        }
+        if let Some(issuer) = issuer {
+            let expected = provider.issuer.as_deref().unwrap_or_default();
+            if issuer.trim_end_matches('/') != expected.trim_end_matches('/') {
+                return Err(Error::Authentication(
+                    "Logout issuer does not match the provider".to_string(),
+                ));
+            }
+        }
+
+        let sessions = self
+            .repository
+            .list_sessions_by_session_index(provider.id, sid)
+            .await?;
+        for session in &sessions {
+            self.repository.delete_session(session.id).await?;
+        }
+        Ok(sessions)
+    }
+
+    /// Handles a logout request of the IdP, ending the SSO sessions it names
+    ///
+    /// Returns the ended sessions, whose users must also be signed out of the
+    /// application, and the redirect answering the IdP.
+    pub async fn handle_logout_request(
+        &self,
+        provider: &SsoProvider,
+        query: &str,
+    ) -> Result<(Vec<SsoSession>, String)> {
+        let (request, relay_state) = self.saml_service.parse_logout_request(provider, query)?;
+        let sessions: Vec<SsoSession> = self
+            .repository
+            .list_sessions_by_name_id(provider.id, &request.name_id)
+            .await?
+            .into_iter()
+            .filter(|session| {
+                request.session_indexes.is_empty()
+                    || session
+                        .session_index
+                        .as_ref()
+                        .is_some_and(|index| request.session_indexes.contains(index))
+            })
+            .collect();
+        for session in &sessions {
+            self.repository.delete_session(session.id).await?;
+        }
 
-                Ok((subject, email))
+        let redirect = self.saml_service.create_logout_response(
+            provider,
+            &request.id,
+            relay_state.as_deref(),
+        )?;
+        Ok((sessions, redirect))
+    }
+
+    /// Handles the answer of the IdP to a logout request, ending the SSO session it was sent for
+    pub async fn handle_logout_response(&self, provider: &SsoProvider, query: &str) -> Result<()> {
+        let (response, relay_state) = self.saml_service.parse_logout_response(provider, query)?;
+        if response.status != SUCCESS_STATUS {
+            return Err(Error::Authentication(format!(
+                "Identity provider logout failed with status {}",
+                response.status
+            )));
+        }
+
+        // The relay state carries the SSO session, covered by the IdP's signature
+        let session_id = relay_state
+            .and_then(|relay_state| Uuid::parse_str(&relay_state).ok())
+            .ok_or_else(|| Error::Authentication("Invalid SAML relay state".to_string()))?;
+        if let Some(session) = self.repository.get_session(session_id).await? {
+            if session.provider_id == provider.id {
+                self.repository.delete_session(session.id).await?;
             }
         }
+        Ok(())
+    }
+
+    /// Creates the hook ending the SSO sessions linked to revoked application sessions
+    ///
+    /// Register it with the authentication service, so logging out of the
+    /// application also forgets the SSO session.
+    pub fn logout_hook(&self) -> Arc<dyn AuthHook> {
+        Arc::new(SsoLogoutHook {
+            repository: self.repository.clone(),
+        })
     }
 
     /// Creates a user mapping
@@ -204,6 +995,370 @@ impl SsoService {
             .await
     }
 
+    /// Resolves the local user of a validated SSO login
+    ///
+    /// Identities without a mapping are never attached to an existing user with
+    /// the same email right away; a link request is created instead which the
+    /// user has to confirm with `complete_link`. Mapped users get the email,
+    /// profile and group roles of the identity.
+    pub async fn resolve_user(
+        &self,
+        provider: &SsoProvider,
+        identity: &SsoIdentity,
+    ) -> Result<SsoLoginResolution> {
+        let external_id = identity.external_id.as_str();
+        let email = identity.email.as_str();
+        if let Some(mut mapping) = self.get_user_mapping(provider.id, external_id).await? {
+            if mapping.email != identity.email || mapping.profile != identity.profile {
+                mapping.email = identity.email.clone();
+                mapping.profile = identity.profile.clone();
+                self.repository.update_user_mapping(&mapping).await?;
+            }
+            self.sync_roles(provider, &mapping, &identity.groups)
+                .await?;
+            if let Some(tokens) = &identity.tokens {
+                self.store_tokens(&mapping, tokens).await?;
+            }
+            return Ok(SsoLoginResolution::Mapped(mapping));
+        }
+
+        let mut user = self
+            .users
+            .get_user_by_email(email, provider.tenant_id)
+            .await?;
+        if user.is_none() {
+            user = self
+                .users
+                .get_user_by_verified_email(email, provider.tenant_id)
+                .await?;
+        }
+        let Some(user) = user else {
+            return Ok(SsoLoginResolution::Unknown {
+                external_id: external_id.to_string(),
+                email: email.to_string(),
+            });
+        };
+
+        let request = SsoLinkRequest::new(
+            user.id,
+            provider.tenant_id,
+            provider.id,
+            external_id.to_string(),
+            email.to_string(),
+            OffsetDateTime::now_utc() + Duration::minutes(LINK_REQUEST_TTL_MINUTES),
+        );
+        self.repository.create_link_request(&request).await?;
+        Ok(SsoLoginResolution::LinkRequired(request))
+    }
+
+    /// Stores the tokens of a mapped user's login, encrypted
+    async fn store_tokens(&self, mapping: &SsoUserMapping, tokens: &OidcTokens) -> Result<()> {
+        let token = SsoToken {
+            provider_id: mapping.provider_id,
+            user_id: mapping.user_id,
+            tenant_id: mapping.tenant_id,
+            tokens: tokens.clone(),
+            updated_at: OffsetDateTime::now_utc(),
+        };
+        self.repository.upsert_token(&self.seal_token(token)?).await
+    }
+
+    /// Gets the cipher of stored tokens
+    fn token_cipher(&self) -> Result<&SessionCipher> {
+        self.token_cipher
+            .as_ref()
+            .ok_or_else(|| Error::Internal("No token encryption key configured".to_string()))
+    }
+
+    /// Encrypts the tokens of a stored token, binding them to its provider and user
+    fn seal_token(&self, mut token: SsoToken) -> Result<SsoToken> {
+        let cipher = self.token_cipher()?;
+        let aad = token_aad(token.provider_id, token.user_id);
+        token.tokens.access_token = cipher.encrypt(&token.tokens.access_token, &aad)?;
+        token.tokens.refresh_token = token
+            .tokens
+            .refresh_token
+            .map(|refresh_token| cipher.encrypt(&refresh_token, &aad))
+            .transpose()?;
+        Ok(token)
+    }
+
+    /// Decrypts the tokens of a stored token
+    fn open_token(&self, mut token: SsoToken) -> Result<SsoToken> {
+        let cipher = self.token_cipher()?;
+        let aad = token_aad(token.provider_id, token.user_id);
+        token.tokens.access_token = cipher.decrypt(&token.tokens.access_token, &aad)?;
+        token.tokens.refresh_token = token
+            .tokens
+            .refresh_token
+            .map(|refresh_token| cipher.decrypt(&refresh_token, &aad))
+            .transpose()?;
+        Ok(token)
+    }
+
+    /// Gets a valid IdP access token of the user for calling the customer's APIs
+    ///
+    /// The token is the one of the user's last login at the provider, refreshed
+    /// if it is about to expire. Users have to log in again once the provider
+    /// rejects the refresh token.
+    pub async fn access_token(&self, user: &User, provider_id: Uuid) -> Result<SsoAccessToken> {
+        let provider = self
+            .get_provider(provider_id)
+            .await?
+            .filter(|provider| provider.tenant_id == user.tenant_id)
+            .ok_or_else(|| Error::NotFound("SSO provider not found".to_string()))?;
+        if !provider.enabled || !provider.store_tokens {
+            return Err(Error::NotFound(
+                "SSO provider does not store tokens".to_string(),
+            ));
+        }
+        let token = self
+            .repository
+            .get_token(provider.id, user.id)
+            .await?
+            .ok_or_else(|| Error::NotFound("No IdP token stored for the user".to_string()))?;
+
+        let mut token = self.open_token(token)?;
+        if token
+            .tokens
+            .expires_within(Duration::minutes(TOKEN_REFRESH_MARGIN_MINUTES))
+        {
+            token = self.refresh_token(&provider, token).await?;
+        }
+        Ok(SsoAccessToken {
+            access_token: token.tokens.access_token,
+            expires_at: token.tokens.expires_at,
+            scopes: token.tokens.scopes,
+        })
+    }
+
+    /// Exchanges the refresh token of a decrypted stored token, storing the new tokens
+    ///
+    /// Tokens whose refresh token the provider rejects are deleted.
+    async fn refresh_token(&self, provider: &SsoProvider, token: SsoToken) -> Result<SsoToken> {
+        let refresh_token = token.tokens.refresh_token.as_deref().ok_or_else(|| {
+            Error::Authentication("IdP access token expired and cannot be refreshed".to_string())
+        })?;
+        let resolved = self.with_resolved_secret(provider).await?;
+        let mut tokens = match self
+            .oidc_service
+            .refresh_tokens(&resolved, refresh_token)
+            .await
+        {
+            Ok(tokens) => tokens,
+            Err(e @ Error::Authentication(_)) => {
+                self.repository
+                    .delete_token(token.provider_id, token.user_id)
+                    .await?;
+                return Err(e);
+            },
+            Err(e) => return Err(e),
+        };
+        if tokens.refresh_token.is_none() {
+            tokens.refresh_token = token.tokens.refresh_token;
+        }
+
+        let token = SsoToken {
+            tokens,
+            updated_at: OffsetDateTime::now_utc(),
+            ..token
+        };
+        self.repository
+            .upsert_token(&self.seal_token(token.clone())?)
+            .await?;
+        Ok(token)
+    }
+
+    /// Refreshes the stored tokens about to expire, returning how many were refreshed
+    ///
+    /// Tokens of providers that stopped storing tokens are deleted. A token that
+    /// cannot be refreshed does not stop the others from being refreshed.
+    pub async fn refresh_expiring_tokens(&self) -> Result<usize> {
+        let tokens = self
+            .repository
+            .list_expiring_tokens(
+                OffsetDateTime::now_utc() + Duration::minutes(TOKEN_REFRESH_MARGIN_MINUTES),
+            )
+            .await?;
+        let mut refreshed = 0;
+        for token in tokens {
+            let (provider_id, user_id) = (token.provider_id, token.user_id);
+            let provider = match self.get_provider(provider_id).await? {
+                Some(provider) if provider.store_tokens => provider,
+                _ => {
+                    self.repository.delete_token(provider_id, user_id).await?;
+                    continue;
+                },
+            };
+            if !provider.enabled {
+                continue;
+            }
+            let result = match self.open_token(token) {
+                Ok(token) => self.refresh_token(&provider, token).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(_) => refreshed += 1,
+                Err(e) => warn!(
+                    "Failed to refresh the IdP token of user {} at SSO provider {}: {}",
+                    user_id.0, provider_id, e
+                ),
+            }
+        }
+        Ok(refreshed)
+    }
+
+    /// Periodically refreshes the stored tokens about to expire
+    pub fn spawn_token_refresh_worker(
+        self: Arc<Self>,
+        interval: std::time::Duration,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match self.refresh_expiring_tokens().await {
+                    Ok(0) => {},
+                    Ok(refreshed) => info!("Refreshed {} stored IdP tokens", refreshed),
+                    Err(e) => error!("Failed to refresh stored IdP tokens: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Grants the roles of a user's groups and revokes the other roles the provider manages
+    async fn sync_roles(
+        &self,
+        provider: &SsoProvider,
+        mapping: &SsoUserMapping,
+        groups: &[String],
+    ) -> Result<()> {
+        let granted = provider.role_mapping.granted_roles(groups);
+        let mut changed = false;
+        for role_id in provider.role_mapping.managed_roles() {
+            changed |= if granted.contains(&role_id) {
+                self.roles
+                    .assign_role(
+                        mapping.user_id,
+                        role_id,
+                        mapping.tenant_id,
+                        OffsetDateTime::now_utc(),
+                        None,
+                    )
+                    .await?
+            } else {
+                self.roles
+                    .revoke_role(mapping.user_id, role_id, mapping.tenant_id)
+                    .await?
+            };
+        }
+        if changed {
+            self.invalidation
+                .publish(CacheInvalidation::User {
+                    user_id: mapping.user_id,
+                })
+                .await;
+        }
+        Ok(())
+    }
+
+    /// Ensures the roles a provider maps groups to belong to its tenant
+    async fn validate_role_mapping(&self, provider: &SsoProvider) -> Result<()> {
+        for role_id in provider.role_mapping.managed_roles() {
+            if self
+                .roles
+                .get_role(role_id, provider.tenant_id)
+                .await?
+                .is_none()
+            {
+                return Err(Error::Validation(format!(
+                    "Role {} of the role mapping does not exist",
+                    role_id
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Emails a one-time code confirming a link request to the existing account
+    pub async fn send_link_code(&self, link_id: Uuid) -> Result<()> {
+        let request = self.get_link_request(link_id).await?;
+        let user = self.get_link_user(&request).await?;
+
+        let code = format!("{:06}", rand::thread_rng().gen_range(0..1_000_000));
+        self.repository
+            .set_link_code(request.id, &hash_link_code(&code))
+            .await?;
+
+        // The code goes to the existing account, never to the address asserted by the IdP
+        self.mailer
+            .send(&EmailMessage {
+                to: user.email,
+                subject: "Confirm linking your account".to_string(),
+                body: format!(
+                    "Enter the code {} to link your account to the single sign-on identity {}. \
+                     The code expires at {}.",
+                    code, request.email, request.expires_at
+                ),
+            })
+            .await
+    }
+
+    /// Attaches the SSO identity of a link request to the existing user once ownership was proven
+    pub async fn complete_link(
+        &self,
+        link_id: Uuid,
+        proof: SsoLinkProof,
+    ) -> Result<SsoUserMapping> {
+        let request = self.get_link_request(link_id).await?;
+        let user = self.get_link_user(&request).await?;
+
+        let verified = match proof {
+            SsoLinkProof::Password { password } => {
+                AuthenticationService::verify_password(&password, &user.password_hash)?
+            }
+            SsoLinkProof::Code { code } => request
+                .code_hash
+                .as_deref()
+                .is_some_and(|hash| hash == hash_link_code(&code)),
+        };
+        if !verified {
+            self.repository.increment_link_attempts(request.id).await?;
+            return Err(Error::Authentication(
+                "Account link could not be verified".to_string(),
+            ));
+        }
+
+        let mapping = SsoUserMapping::new(
+            request.user_id,
+            request.tenant_id,
+            request.provider_id,
+            request.external_id.clone(),
+            request.email.clone(),
+        );
+        self.repository
+            .complete_link_request(&request, &mapping)
+            .await
+    }
+
+    /// Gets a link request that can still be confirmed
+    async fn get_link_request(&self, id: Uuid) -> Result<SsoLinkRequest> {
+        self.repository
+            .get_link_request(id)
+            .await?
+            .filter(|request| !request.is_expired() && request.attempts < MAX_LINK_ATTEMPTS)
+            .ok_or_else(|| Error::NotFound("Link request not found or expired".to_string()))
+    }
+
+    /// Gets the active user a link request would attach the SSO identity to
+    async fn get_link_user(&self, request: &SsoLinkRequest) -> Result<User> {
+        self.users
+            .get_user_by_id(request.user_id)
+            .await?
+            .filter(|user| user.active && user.tenant_id == request.tenant_id)
+            .ok_or_else(|| Error::NotFound("User not found".to_string()))
+    }
+
     /// Creates an SSO session
     pub async fn create_session(
         &self,
@@ -224,7 +1379,7 @@ impl SsoService {
             provider_id,
             session_index,
             name_id,
-            OffsetDateTime::now_utc() + Duration::hours(8),
+            OffsetDateTime::now_utc() + Duration::hours(SSO_SESSION_TTL_HOURS),
         );
 
         self.repository.create_session(&session).await
@@ -239,12 +1394,104 @@ impl SsoService {
     pub async fn cleanup_expired_sessions(&self) -> Result<u64> {
         self.repository.cleanup_expired_sessions().await
     }
+
+    /// Cleans up expired link requests
+    pub async fn cleanup_expired_link_requests(&self) -> Result<u64> {
+        self.repository.cleanup_expired_link_requests().await
+    }
+}
+
+/// Hook ending the SSO sessions linked to revoked application sessions
+#[derive(Debug)]
+struct SsoLogoutHook {
+    repository: SsoRepository,
+}
+
+#[async_trait]
+impl AuthHook for SsoLogoutHook {
+    async fn post_logout(&self, sessions: &[Session]) -> Result<()> {
+        for session_id in sessions.iter().filter_map(Session::sso_session_id) {
+            self.repository.delete_session(session_id).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds the SAML service provider configuration
+fn saml_config(config: &SsoConfig) -> Result<SamlConfig> {
+    let next_signing_key = match (&config.saml_next_certificate, &config.saml_next_private_key) {
+        (Some(certificate), Some(private_key)) => Some(SamlSigningKey {
+            certificate: certificate.clone(),
+            private_key: private_key.clone(),
+        }),
+        (None, None) => None,
+        _ => {
+            return Err(Error::InvalidInput(
+                "saml_next_certificate and saml_next_private_key must be set together".to_string(),
+            ))
+        },
+    };
+    Ok(SamlConfig {
+        certificate: required_setting(&config.saml_certificate, "saml_certificate")?,
+        private_key: required_setting(&config.saml_private_key, "saml_private_key")?,
+        next_signing_key,
+        organization_name: required_setting(&config.saml_org_name, "saml_org_name")?,
+        organization_display_name: required_setting(
+            &config.saml_org_display_name,
+            "saml_org_display_name",
+        )?,
+        organization_url: required_setting(&config.saml_org_url, "saml_org_url")?,
+        technical_contact_name: required_setting(
+            &config.saml_tech_contact_name,
+            "saml_tech_contact_name",
+        )?,
+        technical_contact_email: required_setting(
+            &config.saml_tech_contact_email,
+            "saml_tech_contact_email",
+        )?,
+    })
+}
+
+/// Builds the OIDC relying party configuration
+fn oidc_config(config: &SsoConfig) -> OidcConfig {
+    OidcConfig {
+        redirect_url: config.oidc_redirect_url.clone(),
+        post_logout_redirect_url: config.oidc_post_logout_redirect_url.clone(),
+    }
+}
+
+/// Gets a required SSO setting
+fn required_setting(value: &Option<String>, name: &str) -> Result<String> {
+    value
+        .clone()
+        .ok_or_else(|| Error::InvalidInput(format!("SSO configuration requires {}", name)))
+}
+
+/// Computes the hex encoded SHA-256 hash of a link code
+fn hash_link_code(code: &str) -> String {
+    digest::digest(&digest::SHA256, code.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Gets the data the stored tokens of a user at a provider are bound to
+fn token_aad(provider_id: Uuid, user_id: UserId) -> String {
+    format!("sso_token:{}:{}", provider_id, user_id.0)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::{config::DatabaseConfig, database::Database};
+    use crate::{
+        core::{config::DatabaseConfig, database::Database},
+        modules::identity::sso::{
+            models::SsoProfile,
+            saml::tests::{TEST_CERT, TEST_KEY},
+        },
+    };
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 
     async fn create_test_service() -> SsoService {
         let config = DatabaseConfig {
@@ -257,22 +1504,49 @@ mod tests {
             ssl_mode: false,
         };
 
-        // Set required environment variables for testing
-        std::env::set_var("SAML_CERTIFICATE", "test_cert");
-        std::env::set_var("SAML_PRIVATE_KEY", "test_key");
-        std::env::set_var("SAML_ORG_NAME", "Test Org");
-        std::env::set_var("SAML_ORG_DISPLAY_NAME", "Test Organization");
-        std::env::set_var("SAML_ORG_URL", "https://test.org");
-        std::env::set_var("SAML_TECH_CONTACT_NAME", "Test Admin");
-        std::env::set_var("SAML_TECH_CONTACT_EMAIL", "admin@test.org");
-        std::env::set_var(
-            "OIDC_REDIRECT_URL",
-            "http://localhost:3000/auth/callback",
-        );
-
         let db = Database::connect(&config).await.unwrap();
+        let users = UserRepository::new(db.get_pool());
         let repository = SsoRepository::new(db);
-        SsoService::new(repository)
+        SsoService::new(repository, users, &test_sso_config()).unwrap()
+    }
+
+    fn test_sso_config() -> SsoConfig {
+        SsoConfig {
+            saml_certificate: Some(TEST_CERT.to_string()),
+            saml_private_key: Some(TEST_KEY.to_string()),
+            saml_org_name: Some("Test Org".to_string()),
+            saml_org_display_name: Some("Test Organization".to_string()),
+            saml_org_url: Some("https://test.org".to_string()),
+            saml_tech_contact_name: Some("Test Admin".to_string()),
+            saml_tech_contact_email: Some("admin@test.org".to_string()),
+            oidc_redirect_url: Some("http://localhost:3000/auth/callback".to_string()),
+            token_encryption_key: Some(BASE64.encode([7u8; 32])),
+            ..SsoConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_sso_service_config() {
+        let saml = saml_config(&test_sso_config()).unwrap();
+        assert_eq!(saml.organization_name, "Test Org");
+        assert!(saml.next_signing_key.is_none());
+        let oidc = oidc_config(&test_sso_config());
+        assert_eq!(
+            oidc.redirect_url.as_deref(),
+            Some("http://localhost:3000/auth/callback")
+        );
+
+        // Missing settings are reported instead of panicking
+        let config = SsoConfig {
+            saml_certificate: None,
+            ..test_sso_config()
+        };
+        assert!(matches!(saml_config(&config), Err(Error::InvalidInput(_))));
+        let config = SsoConfig {
+            saml_next_certificate: Some("next_cert".to_string()),
+            ..test_sso_config()
+        };
+        assert!(matches!(saml_config(&config), Err(Error::InvalidInput(_))));
     }
 
     #[tokio::test]
@@ -283,13 +1557,14 @@ mod tests {
         let tenant_id = TenantId::new();
         sqlx::query!(
             r#"
-            INSERT INTO tenants (id, name)
-            VALUES ($1, $2)
+            INSERT INTO tenants (id, name, domain)
+            VALUES ($1, $2, $3)
             "#,
             tenant_id.0,
             "Test Tenant",
+            format!("{}.example.com", tenant_id.0),
         )
-        .execute(service.repository.db.pool())
+        .execute(service.repository.pool())
         .await
         .unwrap();
 
@@ -323,13 +1598,14 @@ mod tests {
 
         sqlx::query!(
             r#"
-            INSERT INTO tenants (id, name)
-            VALUES ($1, $2)
+            INSERT INTO tenants (id, name, domain)
+            VALUES ($1, $2, $3)
             "#,
             tenant_id.0,
             "Test Tenant",
+            format!("{}.example.com", tenant_id.0),
         )
-        .execute(service.repository.db.pool())
+        .execute(service.repository.pool())
         .await
         .unwrap();
 
@@ -343,7 +1619,7 @@ mod tests {
             "test@example.com",
             "hash",
         )
-        .execute(service.repository.db.pool())
+        .execute(service.repository.pool())
         .await
         .unwrap();
 
@@ -380,4 +1656,265 @@ mod tests {
             .unwrap();
         assert_eq!(retrieved.id, mapping.id);
     }
+
+    #[tokio::test]
+    async fn test_sso_token_storage() {
+        let service = create_test_service().await;
+        let tenant_id = TenantId::new();
+        let user_id = UserId::new();
+        sqlx::query!(
+            r#"
+            INSERT INTO tenants (id, name, domain)
+            VALUES ($1, $2, $3)
+            "#,
+            tenant_id.0,
+            "Test Tenant",
+            format!("{}.example.com", tenant_id.0),
+        )
+        .execute(service.repository.pool())
+        .await
+        .unwrap();
+        sqlx::query!(
+            r#"
+            INSERT INTO users (id, tenant_id, email, password_hash)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            user_id.0,
+            tenant_id.0,
+            "tokens@example.com",
+            "hash",
+        )
+        .execute(service.repository.pool())
+        .await
+        .unwrap();
+
+        let mut provider = SsoProvider::new_oidc(
+            tenant_id,
+            "Entra ID".to_string(),
+            None,
+            "client_id".to_string(),
+            "client_secret".to_string(),
+            "https://login.microsoftonline.com/tenant/v2.0".to_string(),
+            None,
+        );
+        provider.store_tokens = true;
+        provider.token_scopes = vec!["https://graph.microsoft.com/User.Read".to_string()];
+        let provider = service.create_provider(&provider).await.unwrap();
+        assert!(provider.store_tokens);
+
+        // Only OIDC providers of a service with a key can store tokens
+        let mut saml = SsoProvider::new_saml(
+            tenant_id,
+            "Test SAML".to_string(),
+            None,
+            None,
+            None,
+            "https://test.org/sp".to_string(),
+            "https://test.org/acs".to_string(),
+            None,
+        );
+        saml.store_tokens = true;
+        assert!(matches!(
+            service.validate_provider(&saml),
+            Err(Error::InvalidInput(_))
+        ));
+        let unkeyed = SsoService {
+            token_cipher: None,
+            ..create_test_service().await
+        };
+        assert!(matches!(
+            unkeyed.validate_provider(&provider),
+            Err(Error::InvalidInput(_))
+        ));
+
+        service
+            .create_user_mapping(
+                user_id,
+                tenant_id,
+                provider.id,
+                "subject".to_string(),
+                "tokens@example.com".to_string(),
+            )
+            .await
+            .unwrap();
+        let user = service
+            .users
+            .get_user_by_email("tokens@example.com", tenant_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            service.access_token(&user, provider.id).await,
+            Err(Error::NotFound(_))
+        ));
+
+        let mut login = identity("subject", "tokens@example.com");
+        login.tokens = Some(OidcTokens {
+            access_token: "graph-access-token".to_string(),
+            refresh_token: Some("graph-refresh-token".to_string()),
+            expires_at: Some(OffsetDateTime::now_utc() + Duration::hours(1)),
+            scopes: provider.token_scopes.clone(),
+        });
+        service.resolve_user(&provider, &login).await.unwrap();
+
+        // Tokens are encrypted at rest and bound to their provider and user
+        let stored = service
+            .repository
+            .get_token(provider.id, user_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_ne!(stored.tokens.access_token, "graph-access-token");
+        assert!(service
+            .open_token(SsoToken {
+                user_id: UserId::new(),
+                ..stored
+            })
+            .is_err());
+
+        let token = service.access_token(&user, provider.id).await.unwrap();
+        assert_eq!(token.access_token, "graph-access-token");
+        assert_eq!(token.scopes, provider.token_scopes);
+
+        // A token that cannot be refreshed is not handed out once it expires
+        login.tokens = Some(OidcTokens {
+            access_token: "expired".to_string(),
+            refresh_token: None,
+            expires_at: Some(OffsetDateTime::now_utc()),
+            scopes: Vec::new(),
+        });
+        service
+            .repository
+            .delete_token(provider.id, user_id)
+            .await
+            .unwrap();
+        service.resolve_user(&provider, &login).await.unwrap();
+        assert!(matches!(
+            service.access_token(&user, provider.id).await,
+            Err(Error::Authentication(_))
+        ));
+    }
+
+    #[derive(Debug, Default)]
+    struct RecordingMailer {
+        messages: std::sync::Mutex<Vec<EmailMessage>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Mailer for RecordingMailer {
+        async fn send(&self, message: &EmailMessage) -> Result<()> {
+            self.messages.lock().unwrap().push(message.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sso_account_linking() {
+        let mailer = Arc::new(RecordingMailer::default());
+        let service = create_test_service().await.with_mailer(mailer.clone());
+
+        let tenant_id = TenantId::new();
+        sqlx::query!(
+            r#"
+            INSERT INTO tenants (id, name, domain)
+            VALUES ($1, $2, $3)
+            "#,
+            tenant_id.0,
+            "Test Tenant",
+            format!("{}.example.com", tenant_id.0),
+        )
+        .execute(service.repository.pool())
+        .await
+        .unwrap();
+
+        let user = service
+            .users
+            .create_user(User::new(
+                tenant_id,
+                "linked@example.com".to_string(),
+                AuthenticationService::hash_password("password").unwrap(),
+            ))
+            .await
+            .unwrap();
+        let provider = service
+            .create_provider(&SsoProvider::new_oidc(
+                tenant_id,
+                "Test OIDC".to_string(),
+                None,
+                "client_id".to_string(),
+                "client_secret".to_string(),
+                "https://issuer.example.com".to_string(),
+                None,
+            ))
+            .await
+            .unwrap();
+
+        // Unknown emails are left to the caller, e.g. for provisioning
+        let resolution = service
+            .resolve_user(&provider, &identity("other", "other@example.com"))
+            .await
+            .unwrap();
+        assert!(matches!(resolution, SsoLoginResolution::Unknown { .. }));
+
+        // A matching email requires an explicit link instead of a duplicate user
+        let SsoLoginResolution::LinkRequired(request) = service
+            .resolve_user(&provider, &identity("subject", "linked@example.com"))
+            .await
+            .unwrap()
+        else {
+            panic!("Expected a link request");
+        };
+        assert_eq!(request.user_id, user.id);
+
+        let result = service
+            .complete_link(
+                request.id,
+                SsoLinkProof::Password {
+                    password: "wrong".to_string(),
+                },
+            )
+            .await;
+        assert!(matches!(result, Err(Error::Authentication(_))));
+
+        // Confirm with the emailed code
+        service.send_link_code(request.id).await.unwrap();
+        let message = mailer.messages.lock().unwrap()[0].clone();
+        assert_eq!(message.to, "linked@example.com");
+        let code: String = message.body.split_whitespace().nth(3).unwrap().to_string();
+        let mapping = service
+            .complete_link(request.id, SsoLinkProof::Code { code })
+            .await
+            .unwrap();
+        assert_eq!(mapping.user_id, user.id);
+
+        // Subsequent logins resolve to the linked user
+        let resolution = service
+            .resolve_user(&provider, &identity("subject", "linked@example.com"))
+            .await
+            .unwrap();
+        assert!(matches!(resolution, SsoLoginResolution::Mapped(m) if m.user_id == user.id));
+
+        // Logins keep the profile of the mapping in sync
+        let mut updated = identity("subject", "renamed@example.com");
+        updated.profile.name = Some("Jane Doe".to_string());
+        service.resolve_user(&provider, &updated).await.unwrap();
+        let mapping = service
+            .get_user_mapping(provider.id, "subject")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(mapping.email, "renamed@example.com");
+        assert_eq!(mapping.profile.name.as_deref(), Some("Jane Doe"));
+    }
+
+    fn identity(external_id: &str, email: &str) -> SsoIdentity {
+        SsoIdentity {
+            external_id: external_id.to_string(),
+            email: email.to_string(),
+            profile: SsoProfile::default(),
+            groups: Vec::new(),
+            session_id: None,
+            tokens: None,
+        }
+    }
 }
\ No newline at end of file
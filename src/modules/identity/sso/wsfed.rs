@@ -0,0 +1,528 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use std::collections::HashMap;
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+use url::Url;
+use x509_parser::parse_x509_certificate;
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::shared::error::{Error, Result};
+
+use super::{
+    models::{SamlAssertion, SsoProvider},
+    xmldsig::{self, DSIG_NAMESPACE},
+};
+
+/// Namespace of SAML 1.1 assertions, the tokens ADFS issues to WS-Federation relying parties
+const SAML11_ASSERTION_NAMESPACE: &str = "urn:oasis:names:tc:SAML:1.0:assertion";
+
+/// Claim types tried for the email of a user, in order
+const EMAIL_CLAIMS: &[&str] = &[
+    "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/emailaddress",
+    "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/upn",
+];
+
+/// Clock difference tolerated between the STS and this server
+const CLOCK_SKEW: Duration = Duration::minutes(3);
+
+/// Settings of a WS-Federation security token service, read from its federation metadata
+#[derive(Debug, Clone, PartialEq)]
+pub struct WsFedMetadata {
+    /// Entity ID the STS issues its tokens as
+    pub issuer: String,
+    /// Endpoint of the passive requestor profile sign-ins and sign-outs go to
+    pub passive_endpoint: String,
+    /// Base64 encoded DER certificates the STS signs tokens with
+    pub certificates: Vec<String>,
+}
+
+/// Extracts the settings of a security token service from its federation metadata
+///
+/// The metadata must carry a `SecurityTokenServiceType` role with a passive
+/// requestor endpoint and at least one signing certificate, as ADFS publishes
+/// at `/FederationMetadata/2007-06/FederationMetadata.xml`.
+pub fn parse_wsfed_metadata(xml: &str) -> Result<WsFedMetadata> {
+    let invalid =
+        |e: xml::reader::Error| Error::Validation(format!("Invalid WS-Federation metadata: {}", e));
+    let mut issuer = None;
+    let mut passive_endpoint = None;
+    let mut certificates = Vec::new();
+    let mut in_sts_role = false;
+    let mut in_signing_key = false;
+    let mut in_passive_endpoint = false;
+    let mut text_of: Option<String> = None;
+    for event in EventReader::from_str(xml) {
+        match event.map_err(invalid)? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let attribute = |local: &str| {
+                    attributes
+                        .iter()
+                        .find(|attr| attr.name.local_name == local)
+                        .map(|attr| attr.value.clone())
+                };
+                match name.local_name.as_str() {
+                    "EntityDescriptor" if issuer.is_none() => issuer = attribute("entityID"),
+                    "RoleDescriptor" => {
+                        in_sts_role = attribute("type")
+                            .is_some_and(|kind| kind.ends_with("SecurityTokenServiceType"));
+                    },
+                    "KeyDescriptor" if in_sts_role => {
+                        in_signing_key = attribute("use").is_none_or(|usage| usage == "signing");
+                    },
+                    "PassiveRequestorEndpoint" if in_sts_role => in_passive_endpoint = true,
+                    "X509Certificate" if in_signing_key => text_of = Some(name.local_name.clone()),
+                    "Address" if in_passive_endpoint => text_of = Some(name.local_name.clone()),
+                    _ => {},
+                }
+            },
+            XmlEvent::Characters(text) => match text_of.take().as_deref() {
+                Some("X509Certificate") => certificates.push(normalize_certificate(&text)?),
+                Some("Address") if passive_endpoint.is_none() => {
+                    passive_endpoint = Some(text.trim().to_string())
+                },
+                _ => {},
+            },
+            XmlEvent::EndElement { name } => {
+                text_of = None;
+                match name.local_name.as_str() {
+                    "RoleDescriptor" => in_sts_role = false,
+                    "KeyDescriptor" => in_signing_key = false,
+                    "PassiveRequestorEndpoint" => in_passive_endpoint = false,
+                    _ => {},
+                }
+            },
+            _ => {},
+        }
+    }
+
+    let issuer = issuer
+        .filter(|issuer| !issuer.trim().is_empty())
+        .ok_or_else(|| Error::Validation("WS-Federation metadata has no entity ID".to_string()))?;
+    let passive_endpoint = passive_endpoint.ok_or_else(|| {
+        Error::Validation("WS-Federation metadata has no passive requestor endpoint".to_string())
+    })?;
+    Url::parse(&passive_endpoint)
+        .map_err(|e| Error::Validation(format!("Invalid WS-Federation passive endpoint: {}", e)))?;
+    if certificates.is_empty() {
+        return Err(Error::Validation(
+            "WS-Federation metadata has no signing certificate".to_string(),
+        ));
+    }
+    Ok(WsFedMetadata {
+        issuer,
+        passive_endpoint,
+        certificates,
+    })
+}
+
+/// Strips whitespace from a base64 encoded certificate and checks that it parses
+fn normalize_certificate(cert: &str) -> Result<String> {
+    let cert: String = cert.chars().filter(|c| !c.is_whitespace()).collect();
+    let der = BASE64.decode(&cert).map_err(|e| {
+        Error::Validation(format!(
+            "Invalid certificate in WS-Federation metadata: {}",
+            e
+        ))
+    })?;
+    parse_x509_certificate(&der).map_err(|e| {
+        Error::Validation(format!(
+            "Invalid certificate in WS-Federation metadata: {}",
+            e
+        ))
+    })?;
+    Ok(cert)
+}
+
+/// Fields of the SAML 1.1 assertion in a sign-in response
+#[derive(Debug, Default)]
+struct WsFedToken {
+    assertion_count: usize,
+    id: Option<String>,
+    issuer: Option<String>,
+    version: (Option<String>, Option<String>),
+    not_before: Option<String>,
+    not_on_or_after: Option<String>,
+    audiences: Vec<String>,
+    name_identifier: Option<String>,
+    attributes: HashMap<String, Vec<String>>,
+    /// Reference URIs of the signatures inside the assertion
+    signature_references: Vec<String>,
+    /// Whether a signature was found outside the assertion
+    foreign_signature: bool,
+}
+
+impl WsFedToken {
+    /// Extracts the assertion of a `RequestSecurityTokenResponse`
+    fn parse(xml: &str) -> Result<Self> {
+        let mut token = Self::default();
+        let mut depth = 0usize;
+        // Depth of the assertion and of the signature currently open, if any
+        let mut assertion_depth = None;
+        let mut signature_depth = None;
+        let mut attribute: Option<String> = None;
+        let mut text_of: Option<String> = None;
+        for event in EventReader::from_str(xml) {
+            let event = event.map_err(|e| {
+                Error::Authentication(format!("Invalid WS-Federation token: {}", e))
+            })?;
+            match event {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    depth += 1;
+                    let value = |local: &str| {
+                        attributes
+                            .iter()
+                            .find(|attr| attr.name.local_name == local)
+                            .map(|attr| attr.value.clone())
+                    };
+                    let namespace = name.namespace.as_deref();
+                    match name.local_name.as_str() {
+                        "Assertion" if namespace == Some(SAML11_ASSERTION_NAMESPACE) => {
+                            token.assertion_count += 1;
+                            assertion_depth = Some(depth);
+                            token.id = value("AssertionID");
+                            token.issuer = value("Issuer");
+                            token.version = (value("MajorVersion"), value("MinorVersion"));
+                        },
+                        "Signature" if namespace == Some(DSIG_NAMESPACE) => {
+                            signature_depth = Some(depth);
+                            if assertion_depth.is_none() {
+                                token.foreign_signature = true;
+                            }
+                        },
+                        "Reference" if signature_depth.is_some() && assertion_depth.is_some() => {
+                            token
+                                .signature_references
+                                .push(value("URI").unwrap_or_default());
+                        },
+                        _ if assertion_depth.is_none() || signature_depth.is_some() => {},
+                        "Conditions" => {
+                            token.not_before = value("NotBefore");
+                            token.not_on_or_after = value("NotOnOrAfter");
+                        },
+                        "Attribute" => {
+                            attribute = value("AttributeName").map(|name| {
+                                match value("AttributeNamespace") {
+                                    Some(namespace) => {
+                                        format!("{}/{}", namespace.trim_end_matches('/'), name)
+                                    },
+                                    None => name,
+                                }
+                            });
+                        },
+                        "Audience" | "NameIdentifier" | "AttributeValue" => {
+                            text_of = Some(name.local_name.clone())
+                        },
+                        _ => {},
+                    }
+                },
+                XmlEvent::Characters(text) => {
+                    let text = text.trim().to_string();
+                    match text_of.take().as_deref() {
+                        Some("Audience") => token.audiences.push(text),
+                        // The subjects of all statements name the same user
+                        Some("NameIdentifier") if token.name_identifier.is_none() => {
+                            token.name_identifier = Some(text)
+                        },
+                        Some("AttributeValue") => {
+                            if let Some(attribute) = &attribute {
+                                token
+                                    .attributes
+                                    .entry(attribute.clone())
+                                    .or_default()
+                                    .push(text);
+                            }
+                        },
+                        _ => {},
+                    }
+                },
+                XmlEvent::EndElement { name } => {
+                    text_of = None;
+                    if signature_depth == Some(depth) {
+                        signature_depth = None;
+                    }
+                    if assertion_depth == Some(depth) {
+                        assertion_depth = None;
+                    }
+                    if name.local_name == "Attribute" {
+                        attribute = None;
+                    }
+                    depth = depth.saturating_sub(1);
+                },
+                _ => {},
+            }
+        }
+        Ok(token)
+    }
+
+    /// Checks the token was issued by the STS for this realm and is currently valid
+    ///
+    /// Its signature has to cover the assertion read here, so signatures
+    /// elsewhere in the response and additional assertions are rejected.
+    fn check(self, issuer: &str, realm: &str, now: OffsetDateTime) -> Result<SamlAssertion> {
+        let rejected = |reason: &str| Err(Error::Authentication(reason.to_string()));
+        if self.assertion_count != 1 {
+            return rejected("WS-Federation response must carry exactly one SAML 1.1 assertion");
+        }
+        if self.version != (Some("1".to_string()), Some("1".to_string())) {
+            return rejected("Unsupported SAML assertion version");
+        }
+        let id = match self.id {
+            Some(id) if !id.is_empty() => id,
+            _ => return rejected("SAML assertion has no ID"),
+        };
+        if self.foreign_signature || self.signature_references != [format!("#{}", id)] {
+            return rejected("SAML assertion is not signed");
+        }
+        if self.issuer.as_deref() != Some(issuer) {
+            return rejected("SAML assertion was issued by another STS");
+        }
+        if !self.audiences.iter().any(|audience| audience == realm) {
+            return rejected("SAML assertion is not meant for this relying party");
+        }
+
+        let timestamp = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|value| {
+                    OffsetDateTime::parse(value, &Rfc3339).map_err(|_| {
+                        Error::Authentication("Invalid SAML assertion conditions".to_string())
+                    })
+                })
+                .transpose()
+        };
+        if timestamp(&self.not_before)?.is_some_and(|not_before| now + CLOCK_SKEW < not_before) {
+            return rejected("SAML assertion is not valid yet");
+        }
+        let expires_at = timestamp(&self.not_on_or_after)?
+            .ok_or_else(|| Error::Authentication("SAML assertion does not expire".to_string()))?;
+        if now - CLOCK_SKEW >= expires_at {
+            return rejected("SAML assertion is expired");
+        }
+
+        let name_id = self
+            .name_identifier
+            .ok_or_else(|| Error::Authentication("SAML assertion has no subject".to_string()))?;
+        let email = EMAIL_CLAIMS.iter().find_map(|claim| {
+            self.attributes
+                .get(*claim)
+                .and_then(|values| values.first())
+                .cloned()
+        });
+        Ok(SamlAssertion {
+            id,
+            name_id,
+            session_index: None,
+            email,
+            attributes: self.attributes,
+            expires_at,
+        })
+    }
+}
+
+/// WS-Federation service for ADFS configurations that cannot use SAML 2.0 or OIDC
+///
+/// Implements the passive requestor profile: users are redirected to the STS
+/// with a `wsignin1.0` request and come back with a SAML 1.1 token posted to
+/// the reply URL.
+#[derive(Debug, Default)]
+pub struct WsFedService;
+
+impl WsFedService {
+    /// Creates a new WsFedService instance
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Builds the URL sending a user to the STS to sign in
+    ///
+    /// `context` comes back as `wctx` with the token.
+    pub fn create_signin_url(&self, provider: &SsoProvider, context: &str) -> Result<String> {
+        let metadata = provider_metadata(provider)?;
+        let mut url = Url::parse(&metadata.passive_endpoint)
+            .map_err(|e| Error::Validation(format!("Invalid passive endpoint: {}", e)))?;
+        url.query_pairs_mut()
+            .append_pair("wa", "wsignin1.0")
+            .append_pair("wtrealm", &realm(provider)?)
+            .append_pair("wctx", context);
+        if let Some(reply) = &provider.assertion_consumer_service_url {
+            url.query_pairs_mut().append_pair("wreply", reply);
+        }
+        Ok(url.to_string())
+    }
+
+    /// Builds the URL ending the user's session at the STS
+    pub fn create_signout_url(&self, provider: &SsoProvider) -> Result<String> {
+        let metadata = provider_metadata(provider)?;
+        let mut url = Url::parse(&metadata.passive_endpoint)
+            .map_err(|e| Error::Validation(format!("Invalid passive endpoint: {}", e)))?;
+        url.query_pairs_mut().append_pair("wa", "wsignout1.0");
+        if let Some(reply) = &provider.single_logout_url {
+            url.query_pairs_mut().append_pair("wreply", reply);
+        }
+        Ok(url.to_string())
+    }
+
+    /// Validates the `wresult` the STS posted to the reply URL
+    ///
+    /// The token must be signed by one of the certificates of the STS metadata.
+    /// The issuer is the provider's configured issuer, or the entity ID of the
+    /// metadata if none is set.
+    pub fn validate_response(
+        &self,
+        provider: &SsoProvider,
+        wresult: &str,
+    ) -> Result<SamlAssertion> {
+        let metadata = provider_metadata(provider)?;
+        let signed = xmldsig::verify(wresult, &metadata.certificates)?;
+        let token = WsFedToken::parse(wresult)?;
+        if !token.id.as_ref().is_some_and(|id| signed.contains(id)) {
+            return Err(Error::Authentication(
+                "Invalid WS-Federation token signature".to_string(),
+            ));
+        }
+
+        let issuer = provider.issuer.as_deref().unwrap_or(&metadata.issuer);
+        token.check(issuer, &realm(provider)?, OffsetDateTime::now_utc())
+    }
+}
+
+/// Gets the STS settings of a provider
+fn provider_metadata(provider: &SsoProvider) -> Result<WsFedMetadata> {
+    let metadata = provider.metadata_xml.as_deref().ok_or_else(|| {
+        Error::Validation("SSO provider has no WS-Federation metadata".to_string())
+    })?;
+    parse_wsfed_metadata(metadata)
+}
+
+/// Gets the realm the application is registered with at the STS
+fn realm(provider: &SsoProvider) -> Result<String> {
+    provider
+        .entity_id
+        .clone()
+        .ok_or_else(|| Error::Validation("SSO provider has no realm".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_CERT: &str = "MIIBsTCCAVegAwIBAgIUFlD8KkvkpfLCi2Rq17XfUpE+mwYwCgYIKoZIzj0EAwIwLjEsMCoGA1UEAwwjQURGUyBTaWduaW5nIC0gYWRmcy5jb250b3NvLmV4YW1wbGUwHhcNMjYxMDE3MDc0NTUzWhcNMzYxMDE0MDc0NTUzWjAuMSwwKgYDVQQDDCNBREZTIFNpZ25pbmcgLSBhZGZzLmNvbnRvc28uZXhhbXBsZTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABOTegU4fUqHqglkvnkNrt+plvX8C/Ck0OJmAG9kPnVWXuklMbMr9vCU1GWhWhXG3lg9/vGsXyXJQJ0ZCK46BHJSjUzBRMB0GA1UdDgQWBBTVBzuZD46o2b8o4LIMdj7LdiEqkDAfBgNVHSMEGDAWgBTVBzuZD46o2b8o4LIMdj7LdiEqkDAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0gAMEUCIQDJLFBYyRsb4rpg6K/C315ZfWUSD9gsi2ooOthWvmwrNgIga5k6dmqi5odS+S5IawHa9j5vb2OJGs7WTa7SO/GaGJM=";
+
+    fn federation_metadata() -> String {
+        format!(
+            r#"<EntityDescriptor xmlns="urn:oasis:names:tc:SAML:2.0:metadata" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xmlns:fed="http://docs.oasis-open.org/wsfed/federation/200706" xmlns:wsa="http://www.w3.org/2005/08/addressing" entityID="http://adfs.contoso.example/adfs/services/trust"><RoleDescriptor xsi:type="fed:ApplicationServiceType" protocolSupportEnumeration="http://docs.oasis-open.org/wsfed/federation/200706"><fed:ApplicationServiceEndpoint><wsa:EndpointReference><wsa:Address>https://adfs.contoso.example/adfs/services/trust/2005/issuedtokenmixedasymmetricbasic256</wsa:Address></wsa:EndpointReference></fed:ApplicationServiceEndpoint></RoleDescriptor><RoleDescriptor xsi:type="fed:SecurityTokenServiceType" protocolSupportEnumeration="http://docs.oasis-open.org/wsfed/federation/200706"><KeyDescriptor use="signing"><KeyInfo xmlns="http://www.w3.org/2000/09/xmldsig#"><X509Data><X509Certificate>{}</X509Certificate></X509Data></KeyInfo></KeyDescriptor><fed:PassiveRequestorEndpoint><wsa:EndpointReference><wsa:Address>https://adfs.contoso.example/adfs/ls/</wsa:Address></wsa:EndpointReference></fed:PassiveRequestorEndpoint></RoleDescriptor></EntityDescriptor>"#,
+            TEST_CERT
+        )
+    }
+
+    fn provider() -> SsoProvider {
+        let mut provider = SsoProvider::new_saml(
+            crate::shared::types::TenantId::new(),
+            "ADFS".to_string(),
+            None,
+            None,
+            Some(federation_metadata()),
+            "urn:app:contoso".to_string(),
+            "https://app.example.com/sso/wsfed".to_string(),
+            None,
+        );
+        provider.provider_type = super::super::models::SsoProviderType::WsFed;
+        provider
+    }
+
+    fn token(assertion_id: &str, reference: &str) -> String {
+        format!(
+            r##"<t:RequestSecurityTokenResponse xmlns:t="http://schemas.xmlsoap.org/ws/2005/02/trust"><t:RequestedSecurityToken><saml:Assertion xmlns:saml="urn:oasis:names:tc:SAML:1.0:assertion" MajorVersion="1" MinorVersion="1" AssertionID="{}" Issuer="http://adfs.contoso.example/adfs/services/trust" IssueInstant="2025-01-01T10:00:00.000Z"><saml:Conditions NotBefore="2025-01-01T10:00:00.000Z" NotOnOrAfter="2025-01-01T11:00:00.000Z"><saml:AudienceRestrictionCondition><saml:Audience>urn:app:contoso</saml:Audience></saml:AudienceRestrictionCondition></saml:Conditions><saml:AttributeStatement><saml:Subject><saml:NameIdentifier>CONTOSO\jdoe</saml:NameIdentifier></saml:Subject><saml:Attribute AttributeName="emailaddress" AttributeNamespace="http://schemas.xmlsoap.org/ws/2005/05/identity/claims"><saml:AttributeValue>jdoe@contoso.example</saml:AttributeValue></saml:Attribute><saml:Attribute AttributeName="Group" AttributeNamespace="http://schemas.xmlsoap.org/claims"><saml:AttributeValue>Admins</saml:AttributeValue><saml:AttributeValue>Users</saml:AttributeValue></saml:Attribute></saml:AttributeStatement><ds:Signature xmlns:ds="http://www.w3.org/2000/09/xmldsig#"><ds:SignedInfo><ds:Reference URI="{}"/></ds:SignedInfo></ds:Signature></saml:Assertion></t:RequestedSecurityToken></t:RequestSecurityTokenResponse>"##,
+            assertion_id, reference
+        )
+    }
+
+    fn at(value: &str) -> OffsetDateTime {
+        OffsetDateTime::parse(value, &Rfc3339).unwrap()
+    }
+
+    const ISSUER: &str = "http://adfs.contoso.example/adfs/services/trust";
+
+    #[test]
+    fn test_parse_wsfed_metadata() {
+        let metadata = parse_wsfed_metadata(&federation_metadata()).unwrap();
+        assert_eq!(metadata.issuer, ISSUER);
+        assert_eq!(
+            metadata.passive_endpoint,
+            "https://adfs.contoso.example/adfs/ls/"
+        );
+        assert_eq!(metadata.certificates, vec![TEST_CERT.to_string()]);
+
+        // SAML 2.0 IdP metadata carries no security token service
+        assert!(matches!(
+            parse_wsfed_metadata(
+                r#"<EntityDescriptor xmlns="urn:oasis:names:tc:SAML:2.0:metadata" entityID="https://idp.example.com"/>"#
+            ),
+            Err(Error::Validation(_))
+        ));
+        assert!(parse_wsfed_metadata("not xml").is_err());
+    }
+
+    #[test]
+    fn test_wsfed_urls() {
+        let service = WsFedService::new();
+        let mut provider = provider();
+
+        let url = Url::parse(&service.create_signin_url(&provider, "state").unwrap()).unwrap();
+        assert_eq!(url.path(), "/adfs/ls/");
+        let query: HashMap<_, _> = url.query_pairs().into_owned().collect();
+        assert_eq!(query["wa"], "wsignin1.0");
+        assert_eq!(query["wtrealm"], "urn:app:contoso");
+        assert_eq!(query["wctx"], "state");
+        assert_eq!(query["wreply"], "https://app.example.com/sso/wsfed");
+
+        provider.single_logout_url = Some("https://app.example.com/signed-out".to_string());
+        let url = Url::parse(&service.create_signout_url(&provider).unwrap()).unwrap();
+        let query: HashMap<_, _> = url.query_pairs().into_owned().collect();
+        assert_eq!(query["wa"], "wsignout1.0");
+        assert_eq!(query["wreply"], "https://app.example.com/signed-out");
+    }
+
+    #[test]
+    fn test_wsfed_token() {
+        let now = at("2025-01-01T10:30:00Z");
+        let assertion = WsFedToken::parse(&token("_a1", "#_a1"))
+            .unwrap()
+            .check(ISSUER, "urn:app:contoso", now)
+            .unwrap();
+        assert_eq!(assertion.id, "_a1");
+        assert_eq!(assertion.name_id, "CONTOSO\\jdoe");
+        assert_eq!(assertion.email.as_deref(), Some("jdoe@contoso.example"));
+        assert_eq!(
+            assertion.attributes["http://schemas.xmlsoap.org/claims/Group"],
+            vec!["Admins", "Users"]
+        );
+        assert_eq!(assertion.expires_at, at("2025-01-01T11:00:00Z"));
+
+        let check = |xml: &str, realm: &str, now: OffsetDateTime| {
+            WsFedToken::parse(xml).unwrap().check(ISSUER, realm, now)
+        };
+        // Other relying parties, expired tokens and tokens from other issuers are rejected
+        assert!(check(&token("_a1", "#_a1"), "urn:app:other", now).is_err());
+        assert!(check(
+            &token("_a1", "#_a1"),
+            "urn:app:contoso",
+            at("2025-01-01T12:00:00Z")
+        )
+        .is_err());
+        assert!(WsFedToken::parse(&token("_a1", "#_a1"))
+            .unwrap()
+            .check("https://sts.other.example", "urn:app:contoso", now)
+            .is_err());
+        // The signature must cover the assertion that is read
+        assert!(check(&token("_a1", "#_other"), "urn:app:contoso", now).is_err());
+        let wrapped = token("_a1", "#_a1").replace(
+            "<t:RequestedSecurityToken>",
+            r#"<t:RequestedSecurityToken><saml:Assertion xmlns:saml="urn:oasis:names:tc:SAML:1.0:assertion" MajorVersion="1" MinorVersion="1" AssertionID="_evil"/>"#,
+        );
+        assert!(check(&wrapped, "urn:app:contoso", now).is_err());
+    }
+}
@@ -0,0 +1,688 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ring::{
+    digest,
+    signature::{
+        UnparsedPublicKey, VerificationAlgorithm, ECDSA_P256_SHA256_FIXED, ECDSA_P384_SHA384_FIXED,
+        RSA_PKCS1_2048_8192_SHA256, RSA_PKCS1_2048_8192_SHA384, RSA_PKCS1_2048_8192_SHA512,
+    },
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use x509_parser::parse_x509_certificate;
+use xml::{
+    attribute::OwnedAttribute,
+    escape::escape_str_attribute,
+    name::OwnedName,
+    reader::{EventReader, ParserConfig, XmlEvent},
+};
+
+use crate::shared::error::{Error, Result};
+
+use super::models::{SamlDigestAlgorithm, SamlSignatureAlgorithm, SamlSigning};
+
+/// Namespace of XML signatures
+pub const DSIG_NAMESPACE: &str = "http://www.w3.org/2000/09/xmldsig#";
+
+/// Transform removing the signature from the element it signs
+const ENVELOPED_SIGNATURE: &str = "http://www.w3.org/2000/09/xmldsig#enveloped-signature";
+
+/// Exclusive canonicalization, whose transforms may list prefixes to treat inclusively
+const EXCLUSIVE_C14N: &str = "http://www.w3.org/2001/10/xml-exc-c14n#";
+const EXCLUSIVE_C14N_WITH_COMMENTS: &str = "http://www.w3.org/2001/10/xml-exc-c14n#WithComments";
+
+/// Inclusive canonicalization 1.0
+const INCLUSIVE_C14N: &str = "http://www.w3.org/TR/2001/REC-xml-c14n-20010315";
+const INCLUSIVE_C14N_WITH_COMMENTS: &str =
+    "http://www.w3.org/TR/2001/REC-xml-c14n-20010315#WithComments";
+
+/// Attributes identifying the elements signature references point to
+const ID_ATTRIBUTES: &[&str] = &["ID", "AssertionID", "Id"];
+
+/// Signature value of a signature template, filled in once the signed info is signed
+const EMPTY_SIGNATURE_VALUE: &str = "<ds:SignatureValue></ds:SignatureValue>";
+
+/// Verifies the enveloped XML signatures of a document
+///
+/// Every signature must reference the element it is a child of and be made by
+/// one of the certificates, given base64 encoded; documents with duplicate IDs
+/// are rejected so a reference cannot be redirected to another element. Returns
+/// the IDs of the signed elements, which is empty for unsigned documents.
+pub fn verify(xml: &str, certificates: &[String]) -> Result<Vec<String>> {
+    let root = parse(xml).map_err(|e| invalid(&e))?;
+    let elements = root.descendants();
+    let mut ids: HashMap<&str, &Element> = HashMap::new();
+    for element in &elements {
+        if let Some(id) = element.id() {
+            if ids.insert(id, element).is_some() {
+                return Err(invalid("duplicate ID"));
+            }
+        }
+    }
+
+    let mut signed = Vec::new();
+    for parent in &elements {
+        for signature in parent
+            .child_elements()
+            .filter(|child| child.is(DSIG_NAMESPACE, "Signature"))
+        {
+            signed.push(verify_signature(
+                &root,
+                &ids,
+                parent,
+                signature,
+                certificates,
+            )?);
+        }
+    }
+    Ok(signed)
+}
+
+/// Verifies one enveloped signature over its parent, returning the parent's ID
+fn verify_signature(
+    root: &Element,
+    ids: &HashMap<&str, &Element>,
+    parent: &Element,
+    signature: &Element,
+    certificates: &[String],
+) -> Result<String> {
+    let signed_info = signature.dsig_child("SignedInfo")?;
+    let canonicalization =
+        Canonicalization::from_element(signed_info.dsig_child("CanonicalizationMethod")?)?;
+    let algorithm = signed_info
+        .dsig_child("SignatureMethod")?
+        .attribute("Algorithm")
+        .and_then(SamlSignatureAlgorithm::from_uri)
+        .ok_or_else(|| invalid("unsupported signature algorithm"))?;
+
+    // The only reference must point to the element the signature is part of
+    let reference = signed_info.dsig_child("Reference")?;
+    let target = match reference.attribute("URI") {
+        Some("") => Some(root),
+        Some(uri) => uri.strip_prefix('#').and_then(|id| ids.get(id).copied()),
+        None => None,
+    };
+    if !target.is_some_and(|target| std::ptr::eq(target, parent)) {
+        return Err(invalid("signature does not cover its parent element"));
+    }
+
+    let mut enveloped = false;
+    // Without a canonicalization transform the referenced element is canonicalized inclusively
+    let mut reference_canonicalization = Canonicalization::new(false, false);
+    if let Some(transforms) = reference
+        .child_elements()
+        .find(|child| child.is(DSIG_NAMESPACE, "Transforms"))
+    {
+        for transform in transforms.child_elements() {
+            if transform.attribute("Algorithm") == Some(ENVELOPED_SIGNATURE) {
+                enveloped = true;
+            } else {
+                reference_canonicalization = Canonicalization::from_element(transform)?;
+            }
+        }
+    }
+    if !enveloped {
+        return Err(invalid("signature is not enveloped"));
+    }
+
+    let digest_algorithm = reference
+        .dsig_child("DigestMethod")?
+        .attribute("Algorithm")
+        .and_then(SamlDigestAlgorithm::from_uri)
+        .ok_or_else(|| invalid("unsupported digest algorithm"))?;
+    let expected = decode(&reference.dsig_child("DigestValue")?.text())?;
+    let canonical = reference_canonicalization.canonicalize(parent, Some(signature), "");
+    if digest_value(digest_algorithm, canonical.as_bytes()) != expected {
+        return Err(invalid("digest does not match the signed element"));
+    }
+
+    let signature_value = decode(&signature.dsig_child("SignatureValue")?.text())?;
+    let signed = canonicalization.canonicalize(signed_info, None, "");
+    if !verify_with_certificates(certificates, algorithm, signed.as_bytes(), &signature_value) {
+        return Err(invalid("signature was not made by the identity provider"));
+    }
+    Ok(parent.id().unwrap_or_default().to_string())
+}
+
+/// Signs a document with an enveloped signature over its root element
+///
+/// The root gets the given ID for the signature to reference. `sign` signs the
+/// canonical signed info with the signature algorithm of `signing`; the
+/// certificate is published in the signature's key info, base64 encoded.
+pub fn sign_enveloped(
+    xml: &str,
+    id: &str,
+    signing: &SamlSigning,
+    certificate: &str,
+    sign: impl FnOnce(&[u8]) -> Result<Vec<u8>>,
+) -> Result<String> {
+    let signing_failed =
+        |e: &dyn std::fmt::Display| Error::Internal(format!("Failed to sign XML document: {}", e));
+    let mut root = parse(xml).map_err(|e| signing_failed(&e))?;
+    root.attributes
+        .retain(|attr| attr.name.namespace.is_some() || attr.name.local_name != "ID");
+    root.attributes
+        .push(OwnedAttribute::new(OwnedName::local("ID"), id));
+
+    let method = signing.canonicalization.uri();
+    let canonicalization = Canonicalization::from_uri(method, Vec::new())
+        .ok_or_else(|| signing_failed(&"unsupported canonicalization"))?;
+    let digest = digest_value(
+        signing.digest_algorithm,
+        canonicalization.canonicalize(&root, None, "").as_bytes(),
+    );
+    let template = format!(
+        r##"<ds:Signature xmlns:ds="{}"><ds:SignedInfo><ds:CanonicalizationMethod Algorithm="{}"/><ds:SignatureMethod Algorithm="{}"/><ds:Reference URI="#{}"><ds:Transforms><ds:Transform Algorithm="{}"/><ds:Transform Algorithm="{}"/></ds:Transforms><ds:DigestMethod Algorithm="{}"/><ds:DigestValue>{}</ds:DigestValue></ds:Reference></ds:SignedInfo>{}<ds:KeyInfo><ds:X509Data><ds:X509Certificate>{}</ds:X509Certificate></ds:X509Data></ds:KeyInfo></ds:Signature>"##,
+        DSIG_NAMESPACE,
+        method,
+        signing.signature_algorithm.uri(),
+        escape_str_attribute(id),
+        ENVELOPED_SIGNATURE,
+        method,
+        signing.digest_algorithm.uri(),
+        BASE64.encode(digest),
+        EMPTY_SIGNATURE_VALUE,
+        certificate,
+    );
+
+    // The signed info is canonicalized with the namespaces in scope where it ends up
+    let document = canonicalization.canonicalize(&root, None, &template);
+    let signed_root = parse(&document).map_err(|e| signing_failed(&e))?;
+    let signed_info = signed_root
+        .dsig_child("Signature")
+        .and_then(|signature| signature.dsig_child("SignedInfo"))
+        .map_err(|e| signing_failed(&e))?;
+    let signature = sign(
+        canonicalization
+            .canonicalize(signed_info, None, "")
+            .as_bytes(),
+    )?;
+    Ok(document.replacen(
+        EMPTY_SIGNATURE_VALUE,
+        &format!(
+            "<ds:SignatureValue>{}</ds:SignatureValue>",
+            BASE64.encode(signature)
+        ),
+        1,
+    ))
+}
+
+/// Checks a signature against base64 encoded DER certificates, accepting any of them
+pub fn verify_with_certificates(
+    certificates: &[String],
+    algorithm: SamlSignatureAlgorithm,
+    message: &[u8],
+    signature: &[u8],
+) -> bool {
+    let verification: &'static dyn VerificationAlgorithm = match algorithm {
+        SamlSignatureAlgorithm::RsaSha256 => &RSA_PKCS1_2048_8192_SHA256,
+        SamlSignatureAlgorithm::RsaSha384 => &RSA_PKCS1_2048_8192_SHA384,
+        SamlSignatureAlgorithm::RsaSha512 => &RSA_PKCS1_2048_8192_SHA512,
+        SamlSignatureAlgorithm::EcdsaSha256 => &ECDSA_P256_SHA256_FIXED,
+        SamlSignatureAlgorithm::EcdsaSha384 => &ECDSA_P384_SHA384_FIXED,
+    };
+    certificates.iter().any(|cert| {
+        let Ok(der) = BASE64.decode(cert) else {
+            return false;
+        };
+        let Ok((_, cert)) = parse_x509_certificate(&der) else {
+            return false;
+        };
+        UnparsedPublicKey::new(
+            verification,
+            cert.public_key().subject_public_key.data.as_ref(),
+        )
+        .verify(message, signature)
+        .is_ok()
+    })
+}
+
+/// Error of a signature that fails verification
+fn invalid(reason: &str) -> Error {
+    Error::Authentication(format!("Invalid XML signature: {}", reason))
+}
+
+/// Decodes a base64 value, which XML signatures may wrap over several lines
+fn decode(value: &str) -> Result<Vec<u8>> {
+    let value: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    BASE64
+        .decode(value)
+        .map_err(|_| invalid("malformed base64 value"))
+}
+
+/// Digests canonical XML
+fn digest_value(algorithm: SamlDigestAlgorithm, data: &[u8]) -> Vec<u8> {
+    let algorithm = match algorithm {
+        SamlDigestAlgorithm::Sha256 => &digest::SHA256,
+        SamlDigestAlgorithm::Sha384 => &digest::SHA384,
+        SamlDigestAlgorithm::Sha512 => &digest::SHA512,
+    };
+    digest::digest(algorithm, data).as_ref().to_vec()
+}
+
+/// Element of a parsed document
+#[derive(Debug)]
+struct Element {
+    name: OwnedName,
+    attributes: Vec<OwnedAttribute>,
+    /// Namespaces in scope of the element by prefix, the default one under ""
+    namespaces: BTreeMap<String, String>,
+    children: Vec<Node>,
+}
+
+/// Content of an element
+#[derive(Debug)]
+enum Node {
+    Element(Element),
+    Text(String),
+    Comment(String),
+    ProcessingInstruction(String, Option<String>),
+}
+
+/// Parses a document into its root element
+///
+/// Whitespace and comments are kept, as signatures may cover them.
+fn parse(xml: &str) -> std::result::Result<Element, String> {
+    let config = ParserConfig::new()
+        .trim_whitespace(false)
+        .whitespace_to_characters(true)
+        .cdata_to_characters(true)
+        .coalesce_characters(true)
+        .ignore_comments(false);
+    let mut open: Vec<Element> = Vec::new();
+    let mut root = None;
+    // Line ends are normalized before parsing, which xml-rs leaves to its callers
+    let xml = xml.replace("\r\n", "\n").replace('\r', "\n");
+    for event in EventReader::new_with_config(xml.as_bytes(), config) {
+        let node = match event.map_err(|e| e.to_string())? {
+            XmlEvent::StartElement {
+                name,
+                attributes,
+                namespace,
+            } => {
+                open.push(Element {
+                    name,
+                    attributes: attributes.into_iter().map(normalize_attribute).collect(),
+                    namespaces: namespace
+                        .0
+                        .into_iter()
+                        .filter(|(prefix, _)| prefix != "xml" && prefix != "xmlns")
+                        .collect(),
+                    children: Vec::new(),
+                });
+                continue;
+            },
+            XmlEvent::EndElement { .. } => match open.pop() {
+                Some(element) if open.is_empty() => {
+                    root = Some(element);
+                    continue;
+                },
+                Some(element) => Node::Element(element),
+                None => continue,
+            },
+            XmlEvent::Characters(text) => Node::Text(text),
+            XmlEvent::Comment(comment) => Node::Comment(comment),
+            XmlEvent::ProcessingInstruction { name, data } => {
+                Node::ProcessingInstruction(name, data)
+            },
+            _ => continue,
+        };
+        // Content outside the root element is not part of any signed element
+        if let Some(parent) = open.last_mut() {
+            parent.children.push(node);
+        }
+    }
+    root.ok_or_else(|| "no root element".to_string())
+}
+
+/// Replaces whitespace characters in an attribute value by spaces, as XML parsers
+/// must before canonicalization; xml-rs leaves them as they are
+///
+/// Whitespace written as character references is replaced as well, which the
+/// standard preserves, but identity providers don't write those in attributes.
+fn normalize_attribute(mut attribute: OwnedAttribute) -> OwnedAttribute {
+    attribute.value = attribute.value.replace(['\t', '\n'], " ");
+    attribute
+}
+
+impl Element {
+    /// Checks the namespace and local name of the element
+    fn is(&self, namespace: &str, local_name: &str) -> bool {
+        self.name.namespace.as_deref() == Some(namespace) && self.name.local_name == local_name
+    }
+
+    /// Gets the child elements
+    fn child_elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|child| match child {
+            Node::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    /// Gets the one child element of the signature namespace with the given name
+    fn dsig_child(&self, local_name: &str) -> Result<&Element> {
+        let mut children = self
+            .child_elements()
+            .filter(|child| child.is(DSIG_NAMESPACE, local_name));
+        match (children.next(), children.next()) {
+            (Some(child), None) => Ok(child),
+            (None, _) => Err(invalid(&format!("missing {}", local_name))),
+            (Some(_), Some(_)) => Err(invalid(&format!("more than one {}", local_name))),
+        }
+    }
+
+    /// Gets the value of an unqualified attribute
+    fn attribute(&self, local_name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|attr| attr.name.namespace.is_none() && attr.name.local_name == local_name)
+            .map(|attr| attr.value.as_str())
+    }
+
+    /// Gets the ID references can point to the element with
+    fn id(&self) -> Option<&str> {
+        ID_ATTRIBUTES.iter().find_map(|name| self.attribute(name))
+    }
+
+    /// Gets the text content of the element
+    fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|child| match child {
+                Node::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Gets the element and all elements below it, in document order
+    fn descendants(&self) -> Vec<&Element> {
+        let mut elements = vec![self];
+        for child in self.child_elements() {
+            elements.extend(child.descendants());
+        }
+        elements
+    }
+}
+
+/// Canonicalization method of a signature or reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Canonicalization {
+    exclusive: bool,
+    comments: bool,
+    /// Prefixes exclusive canonicalization renders like inclusive canonicalization
+    inclusive_prefixes: Vec<String>,
+}
+
+impl Canonicalization {
+    fn new(exclusive: bool, comments: bool) -> Self {
+        Self {
+            exclusive,
+            comments,
+            inclusive_prefixes: Vec::new(),
+        }
+    }
+
+    /// Finds the canonicalization method identified by a URI
+    fn from_uri(uri: &str, inclusive_prefixes: Vec<String>) -> Option<Self> {
+        let (exclusive, comments) = match uri {
+            EXCLUSIVE_C14N => (true, false),
+            EXCLUSIVE_C14N_WITH_COMMENTS => (true, true),
+            INCLUSIVE_C14N => (false, false),
+            INCLUSIVE_C14N_WITH_COMMENTS => (false, true),
+            _ => return None,
+        };
+        Some(Self {
+            inclusive_prefixes,
+            ..Self::new(exclusive, comments)
+        })
+    }
+
+    /// Reads the method of a `CanonicalizationMethod` or `Transform` element
+    fn from_element(element: &Element) -> Result<Self> {
+        let inclusive_prefixes = element
+            .child_elements()
+            .find(|child| child.is(EXCLUSIVE_C14N, "InclusiveNamespaces"))
+            .and_then(|namespaces| namespaces.attribute("PrefixList"))
+            .map(|prefixes| {
+                prefixes
+                    .split_whitespace()
+                    .map(|prefix| if prefix == "#default" { "" } else { prefix }.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        element
+            .attribute("Algorithm")
+            .and_then(|uri| Self::from_uri(uri, inclusive_prefixes))
+            .ok_or_else(|| invalid("unsupported canonicalization"))
+    }
+
+    /// Canonicalizes an element, leaving out the excluded one below it
+    ///
+    /// `content` is written unchanged as the first content of the element.
+    fn canonicalize(&self, element: &Element, excluded: Option<&Element>, content: &str) -> String {
+        let mut out = String::new();
+        self.write(element, &BTreeMap::new(), excluded, content, &mut out);
+        out
+    }
+
+    /// Writes an element below elements that rendered the given namespaces
+    fn write(
+        &self,
+        element: &Element,
+        rendered: &BTreeMap<String, String>,
+        excluded: Option<&Element>,
+        content: &str,
+        out: &mut String,
+    ) {
+        // Exclusive canonicalization only declares the namespaces an element uses
+        let prefixes: BTreeSet<&str> = if self.exclusive {
+            let name_prefix = element.name.prefix.as_deref().unwrap_or("");
+            std::iter::once(name_prefix)
+                .chain(
+                    element
+                        .attributes
+                        .iter()
+                        .filter_map(|attr| attr.name.prefix.as_deref()),
+                )
+                .chain(
+                    self.inclusive_prefixes
+                        .iter()
+                        .map(String::as_str)
+                        .filter(|prefix| element.namespaces.contains_key(*prefix)),
+                )
+                .filter(|prefix| *prefix != "xml")
+                .collect()
+        } else {
+            element.namespaces.keys().map(String::as_str).collect()
+        };
+        let mut rendered = rendered.clone();
+        let mut declarations = String::new();
+        for prefix in prefixes {
+            let uri = element.namespaces.get(prefix).map_or("", String::as_str);
+            if rendered.get(prefix).map_or("", String::as_str) == uri {
+                continue;
+            }
+            let attribute = match prefix {
+                "" => "xmlns".to_string(),
+                prefix => format!("xmlns:{}", prefix),
+            };
+            declarations.push_str(&format!(" {}=\"{}\"", attribute, escape_attribute(uri)));
+            rendered.insert(prefix.to_string(), uri.to_string());
+        }
+
+        let mut attributes: Vec<&OwnedAttribute> = element.attributes.iter().collect();
+        attributes.sort_by_key(|attr| {
+            (
+                attr.name.namespace.as_deref().unwrap_or(""),
+                attr.name.local_name.as_str(),
+            )
+        });
+        let name = qualified_name(&element.name);
+        out.push_str(&format!("<{}{}", name, declarations));
+        for attr in attributes {
+            out.push_str(&format!(
+                " {}=\"{}\"",
+                qualified_name(&attr.name),
+                escape_attribute(&attr.value)
+            ));
+        }
+        out.push('>');
+        out.push_str(content);
+
+        for child in &element.children {
+            match child {
+                Node::Element(child)
+                    if excluded.is_some_and(|excluded| std::ptr::eq(child, excluded)) => {},
+                Node::Element(child) => self.write(child, &rendered, excluded, "", out),
+                Node::Text(text) => out.push_str(&escape_text(text)),
+                Node::Comment(comment) if self.comments => {
+                    out.push_str(&format!("<!--{}-->", comment))
+                },
+                Node::Comment(_) => {},
+                Node::ProcessingInstruction(target, data) => match data {
+                    Some(data) => out.push_str(&format!("<?{} {}?>", target, data)),
+                    None => out.push_str(&format!("<?{}?>", target)),
+                },
+            }
+        }
+        out.push_str(&format!("</{}>", name));
+    }
+}
+
+/// Formats the name of an element or attribute as written in the document
+fn qualified_name(name: &OwnedName) -> String {
+    match &name.prefix {
+        Some(prefix) => format!("{}:{}", prefix, name.local_name),
+        None => name.local_name.clone(),
+    }
+}
+
+/// Escapes text content as canonical XML does
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\r' => escaped.push_str("&#xD;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escapes an attribute value as canonical XML does
+fn escape_attribute(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t' => escaped.push_str("&#x9;"),
+            '\n' => escaped.push_str("&#xA;"),
+            '\r' => escaped.push_str("&#xD;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::identity::sso::models::SamlCanonicalization;
+    use ring::{
+        rand::SystemRandom,
+        signature::{EcdsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+    };
+
+    const TEST_KEY: &str = "MIGHAgEAMBMGByqGSM49AgEGCCqGSM49AwEHBG0wawIBAQQgYMrUBtbYMZF3KFeTPNEi0hC7MXAO5I5oJXo3OSyJpk2hRANCAATki7DbAePH2cBI2zlZpuL4t+2ygp/HNvwvmH0gEjlZbnu3v0iFXxg9lWR2LeF+vBGfFigg1XvB6jHR65LkGasr";
+
+    const TEST_CERT: &str = "MIIBhDCCASmgAwIBAgIUVg3ifZlWusw/9NCfgllbslhFQaYwCgYIKoZIzj0EAwIwFzEVMBMGA1UEAwwMaWRwLnRlc3Qub3JnMB4XDTI2MTAxNzExMzIzN1oXDTM2MTAxNDExMzIzN1owFzEVMBMGA1UEAwwMaWRwLnRlc3Qub3JnMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE5Iuw2wHjx9nASNs5Wabi+LftsoKfxzb8L5h9IBI5WW57t79IhV8YPZVkdi3hfrwRnxYoINV7weox0euS5BmrK6NTMFEwHQYDVR0OBBYEFLbUBl8MI7FTIxVZwd3Nk87VFkotMB8GA1UdIwQYMBaAFLbUBl8MI7FTIxVZwd3Nk87VFkotMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDSQAwRgIhAOlxvCG7ewlqZUQgSvdBZMtulrUDg4VsjU2s0hedg8u4AiEA6QSk88BP+RAVvCC8mlKl+9kmIW2OYLqRNxKjgxA9hiU=";
+
+    const DOCUMENT: &str = "<?xml version=\"1.0\"?>\n<a:root xmlns:a=\"urn:a\" xmlns:b=\"urn:b\" xmlns=\"urn:default\" xmlns:unused=\"urn:unused\"><!-- note --><a:child z=\"2\" b:attr=\"1\" a=\"x&amp;&lt;&quot;\ty\"\n  >text &amp; &gt; more<empty xmlns=\"\"/><b:leaf xmlns:b=\"urn:b\"><![CDATA[<cdata>]]></b:leaf></a:child><?pi data?></a:root>";
+
+    fn sign(xml: &str, canonicalization: SamlCanonicalization) -> String {
+        let signing = SamlSigning {
+            signature_algorithm: SamlSignatureAlgorithm::EcdsaSha256,
+            canonicalization,
+            ..SamlSigning::default()
+        };
+        sign_enveloped(xml, "_doc", &signing, TEST_CERT, |data| {
+            let rng = SystemRandom::new();
+            let key = EcdsaKeyPair::from_pkcs8(
+                &ECDSA_P256_SHA256_FIXED_SIGNING,
+                &BASE64.decode(TEST_KEY).unwrap(),
+                &rng,
+            )
+            .unwrap();
+            Ok(key.sign(&rng, data).unwrap().as_ref().to_vec())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_canonicalization() {
+        let root = parse(DOCUMENT).unwrap();
+        let canonicalize = |uri: &str| {
+            Canonicalization::from_uri(uri, Vec::new())
+                .unwrap()
+                .canonicalize(&root, None, "")
+        };
+
+        // Matches the output of libxml2
+        assert_eq!(
+            canonicalize(EXCLUSIVE_C14N_WITH_COMMENTS),
+            r#"<a:root xmlns:a="urn:a"><!-- note --><a:child xmlns:b="urn:b" a="x&amp;&lt;&quot; y" z="2" b:attr="1">text &amp; &gt; more<empty></empty><b:leaf>&lt;cdata&gt;</b:leaf></a:child><?pi data?></a:root>"#
+        );
+        assert_eq!(
+            canonicalize(INCLUSIVE_C14N_WITH_COMMENTS),
+            r#"<a:root xmlns="urn:default" xmlns:a="urn:a" xmlns:b="urn:b" xmlns:unused="urn:unused"><!-- note --><a:child a="x&amp;&lt;&quot; y" z="2" b:attr="1">text &amp; &gt; more<empty xmlns=""></empty><b:leaf>&lt;cdata&gt;</b:leaf></a:child><?pi data?></a:root>"#
+        );
+        assert!(!canonicalize(EXCLUSIVE_C14N).contains("note"));
+
+        // Line ends are normalized and whitespace in attributes is replaced by spaces
+        let normalized = parse("<a b=\"x\r\ny\tz\">c\r\nd\re</a>").unwrap();
+        assert_eq!(
+            Canonicalization::from_uri(EXCLUSIVE_C14N, Vec::new())
+                .unwrap()
+                .canonicalize(&normalized, None, ""),
+            "<a b=\"x y z\">c\nd\ne</a>"
+        );
+
+        // Prefixes listed for inclusive treatment are declared where they are in scope
+        let child = root.child_elements().next().unwrap();
+        let canonical = Canonicalization::from_uri(EXCLUSIVE_C14N, vec!["unused".to_string()])
+            .unwrap()
+            .canonicalize(child, None, "");
+        assert!(canonical.starts_with(
+            r#"<a:child xmlns:a="urn:a" xmlns:b="urn:b" xmlns:unused="urn:unused" a="#
+        ));
+    }
+
+    #[test]
+    fn test_signature() {
+        let certificates = vec![TEST_CERT.to_string()];
+        for canonicalization in SamlCanonicalization::ALL {
+            let signed = sign(DOCUMENT, canonicalization);
+            assert_eq!(verify(&signed, &certificates).unwrap(), vec!["_doc"]);
+        }
+        let signed = sign(DOCUMENT, SamlCanonicalization::ExclusiveC14n);
+        assert!(verify(DOCUMENT, &certificates).unwrap().is_empty());
+
+        // Changed content and other keys fail verification
+        assert!(verify(&signed.replace("more", "less"), &certificates).is_err());
+        assert!(verify(&signed, &[]).is_err());
+
+        // A second element with the signed ID could be read in place of the signed one
+        let wrapped = format!(
+            r#"<outer><a:root xmlns:a="urn:a" ID="_doc"/>{}</outer>"#,
+            signed
+        );
+        assert!(verify(&wrapped, &certificates).is_err());
+        // A signature must cover the element it is part of
+        let moved = signed.replacen(r#"ID="_doc""#, r#"ID="_other""#, 1);
+        assert!(verify(&moved, &certificates).is_err());
+    }
+}
@@ -0,0 +1,281 @@
+use moka::{sync::Cache, Expiry};
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    core::config::LoginThrottleConfig,
+    shared::{
+        error::{Error, Result, ThrottleInfo},
+        types::TenantId,
+    },
+};
+
+/// Attempts counted within a window starting with the first attempt
+#[derive(Debug)]
+struct Window {
+    started: Instant,
+    count: AtomicU32,
+}
+
+impl Window {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            started: Instant::now(),
+            count: AtomicU32::new(0),
+        })
+    }
+}
+
+/// Consecutive failed logins locking a user out and how long the lockout lasts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockoutPolicy {
+    pub max_failures: u32,
+    pub lockout: Duration,
+}
+
+impl From<&LoginThrottleConfig> for LockoutPolicy {
+    fn from(config: &LoginThrottleConfig) -> Self {
+        Self {
+            max_failures: config.max_failures,
+            lockout: Duration::from_secs(config.lockout_secs),
+        }
+    }
+}
+
+/// Expires each lockout after its own duration, which may differ per tenant
+struct LockoutExpiry;
+
+impl Expiry<String, (Instant, Duration)> for LockoutExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &String,
+        value: &(Instant, Duration),
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Some(value.1)
+    }
+}
+
+/// Gets the whole seconds left of a period that started at `since`, at least one
+fn seconds_left(since: Instant, period: Duration) -> u64 {
+    period.saturating_sub(since.elapsed()).as_secs().max(1)
+}
+
+/// Throttle of the login attempts of users, keyed by tenant and email
+///
+/// Limits the attempts per window and locks users out after consecutive failed
+/// logins; rejections tell clients when to retry. Counters are kept in memory
+/// and thus per process.
+///
+/// The lockout policy can be overridden per login, e.g. by tenants with stricter thresholds.
+#[derive(Debug, Clone)]
+pub struct LoginThrottle {
+    max_attempts: u32,
+    window: Duration,
+    lockout_policy: LockoutPolicy,
+    attempts: Cache<String, Arc<Window>>,
+    failures: Cache<String, Arc<AtomicU32>>,
+    /// When each lockout started and how long it lasts
+    lockouts: Cache<String, (Instant, Duration)>,
+}
+
+impl LoginThrottle {
+    /// Creates a throttle with the given limits
+    pub fn new(config: &LoginThrottleConfig) -> Self {
+        let window = Duration::from_secs(config.window_secs);
+        let lockout_policy = LockoutPolicy::from(config);
+        Self {
+            max_attempts: config.max_attempts,
+            window,
+            lockout_policy,
+            attempts: Cache::builder()
+                .max_capacity(100_000)
+                .time_to_live(window)
+                .build(),
+            // Failures count as consecutive until the user stayed idle for a lockout period
+            failures: Cache::builder()
+                .max_capacity(100_000)
+                .time_to_idle(lockout_policy.lockout)
+                .build(),
+            lockouts: Cache::builder()
+                .max_capacity(100_000)
+                .expire_after(LockoutExpiry)
+                .build(),
+        }
+    }
+
+    /// Gets the lockout policy applied unless a login overrides it
+    pub fn lockout_policy(&self) -> LockoutPolicy {
+        self.lockout_policy
+    }
+
+    /// Builds the key of a user's login attempts
+    pub fn key(tenant_id: TenantId, email: &str) -> String {
+        format!("{}:{}", tenant_id.0, email.trim().to_lowercase())
+    }
+
+    /// Builds the key of the membership discoveries of an email address across tenants
+    pub fn discovery_key(email: &str) -> String {
+        format!("discover:{}", email.trim().to_lowercase())
+    }
+
+    /// Builds the key of the membership discoveries of a client address
+    pub fn discovery_ip_key(ip: IpAddr) -> String {
+        format!("discover-ip:{}", ip)
+    }
+
+    /// Checks whether the user of a key is locked out, without recording an attempt
+    pub fn is_locked_out(&self, key: &str) -> bool {
+        self.lockouts.contains_key(key)
+    }
+
+    /// Records a login attempt and fails while the user is locked out or exceeded
+    /// the attempts of the current window
+    pub fn check(&self, key: &str) -> Result<()> {
+        self.check_with(key, &self.lockout_policy)
+    }
+
+    /// Records a login attempt like [`Self::check`], counting failures against the given policy
+    pub fn check_with(&self, key: &str, policy: &LockoutPolicy) -> Result<()> {
+        if let Some((since, lockout)) = self.lockouts.get(key) {
+            return Err(Error::LockedOut(ThrottleInfo {
+                message: "Too many failed logins, the account is temporarily locked".to_string(),
+                retry_after: seconds_left(since, lockout),
+                attempts_remaining: 0,
+            }));
+        }
+
+        let window = self.attempts.get_with(key.to_string(), Window::new);
+        if window.count.fetch_add(1, Ordering::SeqCst) >= self.max_attempts {
+            return Err(Error::Throttled(ThrottleInfo {
+                message: "Too many login attempts, please try again later".to_string(),
+                retry_after: seconds_left(window.started, self.window),
+                attempts_remaining: self.failures_remaining_with(key, policy),
+            }));
+        }
+        Ok(())
+    }
+
+    /// Records a failed login, returning the lockout error once the user is locked out
+    pub fn record_failure(&self, key: &str) -> Option<Error> {
+        self.record_failure_with(key, &self.lockout_policy)
+    }
+
+    /// Records a failed login like [`Self::record_failure`], locking out as the given policy says
+    pub fn record_failure_with(&self, key: &str, policy: &LockoutPolicy) -> Option<Error> {
+        let failures = self
+            .failures
+            .get_with(key.to_string(), || Arc::new(AtomicU32::new(0)));
+        if failures.fetch_add(1, Ordering::SeqCst) + 1 < policy.max_failures {
+            return None;
+        }
+
+        self.failures.invalidate(key);
+        self.lockouts
+            .insert(key.to_string(), (Instant::now(), policy.lockout));
+        Some(Error::LockedOut(ThrottleInfo {
+            message: "Too many failed logins, the account is temporarily locked".to_string(),
+            retry_after: policy.lockout.as_secs().max(1),
+            attempts_remaining: 0,
+        }))
+    }
+
+    /// Resets the failed logins of a user after a successful login
+    pub fn record_success(&self, key: &str) {
+        self.failures.invalidate(key);
+    }
+
+    /// Gets the failed logins left before the user is locked out
+    pub fn failures_remaining(&self, key: &str) -> u32 {
+        self.failures_remaining_with(key, &self.lockout_policy)
+    }
+
+    /// Gets the failed logins left before the user is locked out by the given policy
+    fn failures_remaining_with(&self, key: &str, policy: &LockoutPolicy) -> u32 {
+        let failures = self
+            .failures
+            .get(key)
+            .map(|failures| failures.load(Ordering::SeqCst))
+            .unwrap_or(0);
+        policy.max_failures.saturating_sub(failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_login_throttle() {
+        let throttle = LoginThrottle::new(&LoginThrottleConfig {
+            max_attempts: 4,
+            window_secs: 60,
+            max_failures: 2,
+            lockout_secs: 300,
+        });
+        let tenant_id = TenantId::new();
+        let key = LoginThrottle::key(tenant_id, " Alice@Example.com");
+        assert_eq!(key, LoginThrottle::key(tenant_id, "alice@example.com"));
+
+        assert!(throttle.check(&key).is_ok());
+        assert!(throttle.record_failure(&key).is_none());
+        assert_eq!(throttle.failures_remaining(&key), 1);
+
+        // A success resets the consecutive failures
+        throttle.record_success(&key);
+        assert_eq!(throttle.failures_remaining(&key), 2);
+
+        assert!(throttle.check(&key).is_ok());
+        assert!(throttle.record_failure(&key).is_none());
+        assert!(throttle.check(&key).is_ok());
+        match throttle.record_failure(&key) {
+            Some(Error::LockedOut(info)) => {
+                assert_eq!(info.retry_after, 300);
+                assert_eq!(info.attempts_remaining, 0);
+            },
+            other => panic!("Expected a lockout, got {:?}", other),
+        }
+        assert!(matches!(throttle.check(&key), Err(Error::LockedOut(_))));
+        assert!(throttle.is_locked_out(&key));
+
+        // Other users are throttled independently, also once they used up their attempts
+        let other = LoginThrottle::key(tenant_id, "bob@example.com");
+        for _ in 0..4 {
+            assert!(throttle.check(&other).is_ok());
+        }
+        match throttle.check(&other) {
+            Err(Error::Throttled(info)) => {
+                assert!(info.retry_after > 0 && info.retry_after <= 60);
+                assert_eq!(info.attempts_remaining, 2);
+            },
+            other => panic!("Expected throttling, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_overridden_lockout_policy() {
+        let throttle = LoginThrottle::new(&LoginThrottleConfig::default());
+        let strict = LockoutPolicy {
+            max_failures: 1,
+            lockout: Duration::from_secs(3600),
+        };
+        let key = LoginThrottle::key(TenantId::new(), "alice@example.com");
+
+        assert!(throttle.check_with(&key, &strict).is_ok());
+        match throttle.record_failure_with(&key, &strict) {
+            Some(Error::LockedOut(info)) => assert_eq!(info.retry_after, 3600),
+            other => panic!("Expected a lockout, got {:?}", other),
+        }
+        // The lockout keeps its duration however later logins are checked
+        match throttle.check(&key) {
+            Err(Error::LockedOut(info)) => assert!(info.retry_after > 900),
+            other => panic!("Expected a lockout, got {:?}", other),
+        }
+    }
+}
@@ -1,4 +1,6 @@
 pub mod identity;
-pub mod tenant;
-
-
+pub mod privacy;
+pub mod project;
+pub mod scim;
+pub mod signup;
+pub mod tenant;
\ No newline at end of file
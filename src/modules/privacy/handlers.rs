@@ -0,0 +1,170 @@
+use axum::{
+    extract::{FromRef, Path, State},
+    http::{header::CONTENT_DISPOSITION, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    modules::{
+        identity::{auth::AuthenticationService, handlers::AuthenticatedUser, models::User},
+        privacy::service::PrivacyService,
+    },
+    shared::{
+        error::{Error, Result},
+        types::UserId,
+    },
+};
+
+/// Shared state of the privacy handlers
+#[derive(Clone, FromRef)]
+pub struct PrivacyState {
+    pub auth: Arc<AuthenticationService>,
+    pub privacy: Arc<PrivacyService>,
+}
+
+/// Parses the user ID from the request path and ensures the actor belongs to the tenant
+fn parse_subject(actor: &User, tenant_id: &str, user_id: &str) -> Result<UserId> {
+    let tenant_id = Uuid::parse_str(tenant_id)
+        .map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+    if actor.tenant_id.0 != tenant_id {
+        return Err(Error::Authorization("Access to tenant denied".to_string()));
+    }
+    let user_id = Uuid::parse_str(user_id)
+        .map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+    Ok(UserId(user_id))
+}
+
+/// Parses an export ID from the request path
+fn parse_export_id(export_id: &str) -> Result<Uuid> {
+    Uuid::parse_str(export_id).map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))
+}
+
+/// Starts exporting the data of a user
+pub async fn request_export(
+    State(privacy): State<Arc<PrivacyService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, user_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let user_id = parse_subject(&actor, &tenant_id, &user_id)?;
+    let export = privacy.request_export(&actor, user_id).await?;
+    Ok((StatusCode::ACCEPTED, Json(export)))
+}
+
+/// Gets the status of a data export
+pub async fn get_export(
+    State(privacy): State<Arc<PrivacyService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, user_id, export_id)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse> {
+    let user_id = parse_subject(&actor, &tenant_id, &user_id)?;
+    let export = privacy
+        .get_export(&actor, user_id, parse_export_id(&export_id)?)
+        .await?;
+    Ok((StatusCode::OK, Json(export)))
+}
+
+/// Downloads the archive of a completed data export
+pub async fn download_export(
+    State(privacy): State<Arc<PrivacyService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, user_id, export_id)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse> {
+    let user_id = parse_subject(&actor, &tenant_id, &user_id)?;
+    let export_id = parse_export_id(&export_id)?;
+    let archive = privacy
+        .get_export_archive(&actor, user_id, export_id)
+        .await?;
+    let disposition = format!("attachment; filename=\"export-{}.json\"", export_id);
+    Ok((
+        StatusCode::OK,
+        [(CONTENT_DISPOSITION, disposition)],
+        Json(archive),
+    ))
+}
+
+/// Schedules the erasure of a user's personal data
+pub async fn request_erasure(
+    State(privacy): State<Arc<PrivacyService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, user_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let user_id = parse_subject(&actor, &tenant_id, &user_id)?;
+    let erasure = privacy.request_erasure(&actor, user_id).await?;
+    Ok((StatusCode::ACCEPTED, Json(erasure)))
+}
+
+/// Gets the most recent erasure request of a user
+pub async fn get_erasure(
+    State(privacy): State<Arc<PrivacyService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, user_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let user_id = parse_subject(&actor, &tenant_id, &user_id)?;
+    let erasure = privacy.get_erasure(&actor, user_id).await?;
+    Ok((StatusCode::OK, Json(erasure)))
+}
+
+/// Cancels a pending erasure request
+pub async fn cancel_erasure(
+    State(privacy): State<Arc<PrivacyService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, user_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let user_id = parse_subject(&actor, &tenant_id, &user_id)?;
+    let erasure = privacy.cancel_erasure(&actor, user_id).await?;
+    Ok((StatusCode::OK, Json(erasure)))
+}
+
+/// Creates the privacy module router
+pub fn router(state: PrivacyState) -> Router {
+    Router::new()
+        .route(
+            "/tenants/:tenant_id/users/:id/exports",
+            post(request_export),
+        )
+        .route(
+            "/tenants/:tenant_id/users/:id/exports/:export_id",
+            get(get_export),
+        )
+        .route(
+            "/tenants/:tenant_id/users/:id/exports/:export_id/download",
+            get(download_export),
+        )
+        .route(
+            "/tenants/:tenant_id/users/:id/erasure",
+            get(get_erasure)
+                .post(request_erasure)
+                .delete(cancel_erasure),
+        )
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::types::TenantId;
+
+    #[test]
+    fn test_parse_subject() {
+        let actor = User::new(
+            TenantId::new(),
+            "jane@example.com".to_string(),
+            "hash".to_string(),
+        );
+        let user_id = Uuid::new_v4();
+
+        let parsed =
+            parse_subject(&actor, &actor.tenant_id.0.to_string(), &user_id.to_string()).unwrap();
+        assert_eq!(parsed.0, user_id);
+
+        let result = parse_subject(&actor, &Uuid::new_v4().to_string(), &user_id.to_string());
+        assert!(matches!(result, Err(Error::Authorization(_))));
+
+        let result = parse_subject(&actor, &actor.tenant_id.0.to_string(), "invalid");
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+    }
+}
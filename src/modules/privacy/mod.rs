@@ -0,0 +1,20 @@
+mod handlers;
+pub mod models;
+pub mod repository;
+pub mod service;
+
+pub use handlers::PrivacyState;
+pub use service::PrivacyService;
+
+use axum::Router;
+use std::sync::Arc;
+
+use crate::modules::identity::AuthenticationService;
+
+/// Creates a router for data subject requests such as data exports and erasures
+pub fn router(auth_service: Arc<AuthenticationService>, privacy: Arc<PrivacyService>) -> Router {
+    handlers::router(PrivacyState {
+        auth: auth_service,
+        privacy,
+    })
+}
@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    modules::identity::{
+        models::{Role, UserEmail, UserResponse},
+        session::Session,
+    },
+    shared::{
+        error::Error,
+        types::{TenantId, UserId},
+    },
+};
+
+/// Processing status of a data export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportStatus {
+    Pending,
+    Processing,
+    Completed,
+    Failed,
+}
+
+impl std::fmt::Display for ExportStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportStatus::Pending => write!(f, "pending"),
+            ExportStatus::Processing => write!(f, "processing"),
+            ExportStatus::Completed => write!(f, "completed"),
+            ExportStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl std::str::FromStr for ExportStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(ExportStatus::Pending),
+            "processing" => Ok(ExportStatus::Processing),
+            "completed" => Ok(ExportStatus::Completed),
+            "failed" => Ok(ExportStatus::Failed),
+            _ => Err(Error::InvalidInput(format!("Invalid export status: {}", s))),
+        }
+    }
+}
+
+/// Export of the data stored about a user
+#[derive(Debug, Clone, Serialize)]
+pub struct DataExport {
+    pub id: Uuid,
+    pub tenant_id: TenantId,
+    pub user_id: UserId,
+    pub requested_by: UserId,
+    pub status: ExportStatus,
+    pub error: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub completed_at: Option<OffsetDateTime>,
+}
+
+impl DataExport {
+    /// Creates a new pending export
+    pub fn new(tenant_id: TenantId, user_id: UserId, requested_by: UserId) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            tenant_id,
+            user_id,
+            requested_by,
+            status: ExportStatus::Pending,
+            error: None,
+            created_at: OffsetDateTime::now_utc(),
+            completed_at: None,
+        }
+    }
+}
+
+/// Processing status of an erasure request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErasureStatus {
+    Pending,
+    Cancelled,
+    Completed,
+    Failed,
+}
+
+impl std::fmt::Display for ErasureStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErasureStatus::Pending => write!(f, "pending"),
+            ErasureStatus::Cancelled => write!(f, "cancelled"),
+            ErasureStatus::Completed => write!(f, "completed"),
+            ErasureStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl std::str::FromStr for ErasureStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(ErasureStatus::Pending),
+            "cancelled" => Ok(ErasureStatus::Cancelled),
+            "completed" => Ok(ErasureStatus::Completed),
+            "failed" => Ok(ErasureStatus::Failed),
+            _ => Err(Error::InvalidInput(format!(
+                "Invalid erasure status: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Request to erase the personal data of a user
+#[derive(Debug, Clone, Serialize)]
+pub struct DataErasure {
+    pub id: Uuid,
+    pub tenant_id: TenantId,
+    pub user_id: UserId,
+    pub requested_by: UserId,
+    pub status: ErasureStatus,
+    pub error: Option<String>,
+    pub scheduled_at: OffsetDateTime,
+    pub created_at: OffsetDateTime,
+    pub cancelled_at: Option<OffsetDateTime>,
+    pub completed_at: Option<OffsetDateTime>,
+}
+
+impl DataErasure {
+    /// Creates a new pending erasure carried out after the grace period
+    pub fn new(
+        tenant_id: TenantId,
+        user_id: UserId,
+        requested_by: UserId,
+        grace_period: Duration,
+    ) -> Self {
+        let now = OffsetDateTime::now_utc();
+        Self {
+            id: Uuid::new_v4(),
+            tenant_id,
+            user_id,
+            requested_by,
+            status: ErasureStatus::Pending,
+            error: None,
+            scheduled_at: now + grace_period,
+            created_at: now,
+            cancelled_at: None,
+            completed_at: None,
+        }
+    }
+}
+
+/// Session entry of an export; the session token is left out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub id: Uuid,
+    pub created_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    pub attributes: HashMap<String, String>,
+}
+
+impl From<Session> for SessionRecord {
+    fn from(session: Session) -> Self {
+        Self {
+            id: session.id,
+            created_at: session.created_at,
+            expires_at: session.expires_at,
+            attributes: session.attributes,
+        }
+    }
+}
+
+/// Link between a user and an account at an SSO provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SsoMappingRecord {
+    pub provider: String,
+    pub provider_type: String,
+    pub external_id: String,
+    pub created_at: OffsetDateTime,
+}
+
+/// Audit log entry concerning a user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEventRecord {
+    pub id: Uuid,
+    pub action: String,
+    pub table_name: String,
+    pub record_id: String,
+    pub old_values: Option<serde_json::Value>,
+    pub new_values: Option<serde_json::Value>,
+    pub created_at: OffsetDateTime,
+}
+
+/// MFA state of a user; secrets and codes are left out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaRecord {
+    pub enabled: bool,
+    pub unused_backup_codes: i64,
+}
+
+/// Everything stored about a user
+#[derive(Debug, Serialize)]
+pub struct UserDataArchive {
+    pub generated_at: OffsetDateTime,
+    pub profile: UserResponse,
+    pub emails: Vec<UserEmail>,
+    pub roles: Vec<Role>,
+    pub mfa: MfaRecord,
+    pub sessions: Vec<SessionRecord>,
+    pub sso_mappings: Vec<SsoMappingRecord>,
+    pub audit_events: Vec<AuditEventRecord>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_status_parsing() {
+        for status in [
+            ExportStatus::Pending,
+            ExportStatus::Processing,
+            ExportStatus::Completed,
+            ExportStatus::Failed,
+        ] {
+            assert_eq!(status.to_string().parse::<ExportStatus>().unwrap(), status);
+        }
+        assert!("done".parse::<ExportStatus>().is_err());
+        assert_eq!(
+            serde_json::to_string(&ExportStatus::Processing).unwrap(),
+            "\"processing\""
+        );
+    }
+
+    #[test]
+    fn test_erasure_scheduling() {
+        let erasure = DataErasure::new(
+            TenantId::new(),
+            UserId::new(),
+            UserId::new(),
+            Duration::days(30),
+        );
+        assert_eq!(erasure.status, ErasureStatus::Pending);
+        assert_eq!(
+            erasure.scheduled_at - erasure.created_at,
+            Duration::days(30)
+        );
+        assert_eq!(
+            "cancelled".parse::<ErasureStatus>().unwrap(),
+            ErasureStatus::Cancelled
+        );
+        assert!("erased".parse::<ErasureStatus>().is_err());
+    }
+
+    #[test]
+    fn test_session_record_omits_token() {
+        let session = Session::new(
+            UserId::new(),
+            TenantId::new(),
+            "secret-token".to_string(),
+            time::Duration::hours(1),
+        );
+        let json = serde_json::to_value(SessionRecord::from(session.clone())).unwrap();
+        assert_eq!(json["id"], session.id.to_string());
+        assert!(!json.to_string().contains("secret-token"));
+    }
+}
@@ -0,0 +1,458 @@
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    modules::privacy::models::{
+        AuditEventRecord, DataErasure, DataExport, ErasureStatus, ExportStatus, SsoMappingRecord,
+    },
+    shared::{
+        error::Result,
+        types::{TenantId, UserId},
+    },
+};
+
+/// Privacy repository for data subject requests
+#[derive(Debug, Clone)]
+pub struct PrivacyRepository {
+    pool: Pool<Postgres>,
+}
+
+impl PrivacyRepository {
+    /// Creates a new PrivacyRepository instance
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    /// Creates a data export
+    pub async fn create_export(&self, export: &DataExport) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO data_exports (id, tenant_id, user_id, requested_by, status, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            export.id,
+            export.tenant_id.0 as uuid::Uuid,
+            export.user_id.0 as uuid::Uuid,
+            export.requested_by.0 as uuid::Uuid,
+            export.status.to_string(),
+            export.created_at,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Updates the status of a data export, storing the archive or error when it finished
+    pub async fn update_export(
+        &self,
+        id: Uuid,
+        status: ExportStatus,
+        archive: Option<serde_json::Value>,
+        error: Option<String>,
+    ) -> Result<()> {
+        let finished = matches!(status, ExportStatus::Completed | ExportStatus::Failed);
+        sqlx::query!(
+            r#"
+            UPDATE data_exports
+            SET status = $1, archive = $2, error = $3,
+                completed_at = CASE WHEN $4 THEN NOW() ELSE NULL END
+            WHERE id = $5
+            "#,
+            status.to_string(),
+            archive,
+            error,
+            finished,
+            id,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Gets a data export of a user
+    pub async fn get_export(
+        &self,
+        id: Uuid,
+        user_id: UserId,
+        tenant_id: TenantId,
+    ) -> Result<Option<DataExport>> {
+        let result = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, user_id, requested_by, status, error, created_at, completed_at
+            FROM data_exports
+            WHERE id = $1 AND user_id = $2 AND tenant_id = $3
+            "#,
+            id,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        result
+            .map(|r| {
+                Ok(DataExport {
+                    id: r.id,
+                    tenant_id: TenantId(r.tenant_id),
+                    user_id: UserId(r.user_id),
+                    requested_by: UserId(r.requested_by),
+                    status: r.status.parse()?,
+                    error: r.error,
+                    created_at: r.created_at,
+                    completed_at: r.completed_at,
+                })
+            })
+            .transpose()
+    }
+
+    /// Gets the archive of a completed data export
+    pub async fn get_export_archive(
+        &self,
+        id: Uuid,
+        user_id: UserId,
+        tenant_id: TenantId,
+    ) -> Result<Option<serde_json::Value>> {
+        let archive = sqlx::query_scalar!(
+            r#"
+            SELECT archive
+            FROM data_exports
+            WHERE id = $1 AND user_id = $2 AND tenant_id = $3 AND status = 'completed'
+            "#,
+            id,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(archive.flatten())
+    }
+
+    /// Creates an erasure request
+    pub async fn create_erasure(&self, erasure: &DataErasure) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO data_erasures (id, tenant_id, user_id, requested_by, status, scheduled_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            erasure.id,
+            erasure.tenant_id.0 as uuid::Uuid,
+            erasure.user_id.0 as uuid::Uuid,
+            erasure.requested_by.0 as uuid::Uuid,
+            erasure.status.to_string(),
+            erasure.scheduled_at,
+            erasure.created_at,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Gets the most recent erasure request of a user
+    pub async fn get_latest_erasure(
+        &self,
+        user_id: UserId,
+        tenant_id: TenantId,
+    ) -> Result<Option<DataErasure>> {
+        let result = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, user_id, requested_by, status, error, scheduled_at, created_at,
+                   cancelled_at, completed_at
+            FROM data_erasures
+            WHERE user_id = $1 AND tenant_id = $2
+            ORDER BY created_at DESC
+            LIMIT 1
+            "#,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        result
+            .map(|r| {
+                Ok(DataErasure {
+                    id: r.id,
+                    tenant_id: TenantId(r.tenant_id),
+                    user_id: UserId(r.user_id),
+                    requested_by: UserId(r.requested_by),
+                    status: r.status.parse()?,
+                    error: r.error,
+                    scheduled_at: r.scheduled_at,
+                    created_at: r.created_at,
+                    cancelled_at: r.cancelled_at,
+                    completed_at: r.completed_at,
+                })
+            })
+            .transpose()
+    }
+
+    /// Cancels the pending erasure request of a user
+    pub async fn cancel_erasure(&self, user_id: UserId, tenant_id: TenantId) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE data_erasures
+            SET status = 'cancelled', cancelled_at = NOW()
+            WHERE user_id = $1 AND tenant_id = $2 AND status = 'pending'
+            "#,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Lists pending erasure requests whose grace period has ended
+    pub async fn list_due_erasures(&self, limit: i64) -> Result<Vec<DataErasure>> {
+        let results = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, user_id, requested_by, status, error, scheduled_at, created_at,
+                   cancelled_at, completed_at
+            FROM data_erasures
+            WHERE status = 'pending' AND scheduled_at <= NOW()
+            ORDER BY scheduled_at
+            LIMIT $1
+            "#,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        results
+            .into_iter()
+            .map(|r| {
+                Ok(DataErasure {
+                    id: r.id,
+                    tenant_id: TenantId(r.tenant_id),
+                    user_id: UserId(r.user_id),
+                    requested_by: UserId(r.requested_by),
+                    status: r.status.parse()?,
+                    error: r.error,
+                    scheduled_at: r.scheduled_at,
+                    created_at: r.created_at,
+                    cancelled_at: r.cancelled_at,
+                    completed_at: r.completed_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Marks an erasure request as failed
+    pub async fn fail_erasure(&self, id: Uuid, error: String) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE data_erasures
+            SET status = $1, error = $2, completed_at = NOW()
+            WHERE id = $3 AND status = 'pending'
+            "#,
+            ErasureStatus::Failed.to_string(),
+            error,
+            id,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Anonymizes a user, deletes the personal data linked to them and writes a tombstone
+    ///
+    /// Returns false if the erasure was cancelled in the meantime.
+    pub async fn erase_user_data(
+        &self,
+        erasure: &DataErasure,
+        anonymized_email: &str,
+        password_hash: &str,
+    ) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let claimed = sqlx::query!(
+            r#"
+            UPDATE data_erasures
+            SET status = $1, completed_at = NOW()
+            WHERE id = $2 AND status = 'pending'
+            "#,
+            ErasureStatus::Completed.to_string(),
+            erasure.id,
+        )
+        .execute(&mut *tx)
+        .await?;
+        if claimed.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET email = $1, password_hash = $2, active = FALSE, mfa_enabled = FALSE,
+                mfa_secret = NULL, updated_at = NOW()
+            WHERE id = $3 AND tenant_id = $4
+            "#,
+            anonymized_email,
+            password_hash,
+            erasure.user_id.0 as uuid::Uuid,
+            erasure.tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM user_emails WHERE user_id = $1 AND tenant_id = $2",
+            erasure.user_id.0 as uuid::Uuid,
+            erasure.tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM sso_user_mappings WHERE user_id = $1 AND tenant_id = $2",
+            erasure.user_id.0 as uuid::Uuid,
+            erasure.tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM mfa_backup_codes WHERE user_id = $1 AND tenant_id = $2",
+            erasure.user_id.0 as uuid::Uuid,
+            erasure.tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM user_roles WHERE user_id = $1 AND tenant_id = $2",
+            erasure.user_id.0 as uuid::Uuid,
+            erasure.tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM project_members WHERE user_id = $1 AND tenant_id = $2",
+            erasure.user_id.0 as uuid::Uuid,
+            erasure.tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM data_exports WHERE user_id = $1 AND tenant_id = $2",
+            erasure.user_id.0 as uuid::Uuid,
+            erasure.tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO user_tombstones (user_id, tenant_id, erasure_id, requested_by)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id) DO UPDATE SET erasure_id = $3, requested_by = $4, erased_at = NOW()
+            "#,
+            erasure.user_id.0 as uuid::Uuid,
+            erasure.tenant_id.0 as uuid::Uuid,
+            erasure.id,
+            erasure.requested_by.0 as uuid::Uuid,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Checks if the data of a user was erased
+    pub async fn has_tombstone(&self, user_id: UserId, tenant_id: TenantId) -> Result<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM user_tombstones WHERE user_id = $1 AND tenant_id = $2
+            ) AS "exists!"
+            "#,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(exists)
+    }
+
+    /// Lists the SSO provider accounts linked to a user
+    pub async fn list_sso_mappings(
+        &self,
+        user_id: UserId,
+        tenant_id: TenantId,
+    ) -> Result<Vec<SsoMappingRecord>> {
+        let results = sqlx::query!(
+            r#"
+            SELECT p.name, p.provider_type, m.external_id, m.created_at
+            FROM sso_user_mappings m
+            JOIN sso_providers p ON p.id = m.provider_id
+            WHERE m.user_id = $1 AND m.tenant_id = $2
+            ORDER BY m.created_at
+            "#,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| SsoMappingRecord {
+                provider: r.name,
+                provider_type: r.provider_type,
+                external_id: r.external_id,
+                created_at: r.created_at,
+            })
+            .collect())
+    }
+
+    /// Lists the audit events performed by or recorded about a user
+    pub async fn list_audit_events(
+        &self,
+        user_id: UserId,
+        tenant_id: TenantId,
+    ) -> Result<Vec<AuditEventRecord>> {
+        let results = sqlx::query!(
+            r#"
+            SELECT id, action, table_name, record_id, old_values, new_values, created_at
+            FROM audit_log
+            WHERE tenant_id = $2 AND (user_id = $1 OR (table_name = 'users' AND record_id = $1::text))
+            ORDER BY created_at
+            "#,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| AuditEventRecord {
+                id: r.id,
+                action: r.action,
+                table_name: r.table_name,
+                record_id: r.record_id,
+                old_values: r.old_values,
+                new_values: r.new_values,
+                created_at: r.created_at.assume_utc(),
+            })
+            .collect())
+    }
+
+    /// Counts the unused MFA backup codes of a user
+    pub async fn count_unused_backup_codes(
+        &self,
+        user_id: UserId,
+        tenant_id: TenantId,
+    ) -> Result<i64> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM mfa_backup_codes
+            WHERE user_id = $1 AND tenant_id = $2 AND NOT used
+            "#,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count)
+    }
+}
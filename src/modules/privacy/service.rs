@@ -0,0 +1,550 @@
+use rand::{distributions::Alphanumeric, Rng};
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    modules::{
+        identity::{
+            auth::AuthenticationService,
+            models::{PermissionAction, User, UserResponse},
+            rbac::has_permission,
+            repository::UserRepository,
+        },
+        privacy::{
+            models::{
+                DataErasure, DataExport, ErasureStatus, ExportStatus, MfaRecord, SessionRecord,
+                UserDataArchive,
+            },
+            repository::PrivacyRepository,
+        },
+    },
+    shared::{
+        error::{Error, Result},
+        types::UserId,
+    },
+};
+
+/// Grace period during which an erasure request can be cancelled
+const DEFAULT_ERASURE_GRACE_PERIOD: Duration = Duration::days(30);
+
+/// Maximum number of erasures carried out per run
+const ERASURE_BATCH_SIZE: i64 = 100;
+
+/// Privacy service handling data subject requests
+#[derive(Debug, Clone)]
+pub struct PrivacyService {
+    repository: PrivacyRepository,
+    users: UserRepository,
+    auth: Arc<AuthenticationService>,
+    erasure_grace_period: Duration,
+}
+
+impl PrivacyService {
+    /// Creates a new PrivacyService instance
+    pub fn new(users: UserRepository, auth: Arc<AuthenticationService>) -> Self {
+        Self {
+            repository: PrivacyRepository::new(users.get_pool().clone()),
+            users,
+            auth,
+            erasure_grace_period: DEFAULT_ERASURE_GRACE_PERIOD,
+        }
+    }
+
+    /// Sets the grace period before personal data is erased
+    pub fn with_erasure_grace_period(mut self, grace_period: Duration) -> Self {
+        self.erasure_grace_period = grace_period;
+        self
+    }
+
+    /// Starts exporting the data of a user; the archive is generated in the background
+    pub async fn request_export(&self, actor: &User, user_id: UserId) -> Result<DataExport> {
+        let user = self
+            .get_subject(actor, user_id, PermissionAction::Read)
+            .await?;
+        let export = DataExport::new(user.tenant_id, user.id, actor.id);
+        self.repository.create_export(&export).await?;
+
+        let service = self.clone();
+        let export_id = export.id;
+        tokio::spawn(async move { service.run_export(export_id, user).await });
+
+        Ok(export)
+    }
+
+    /// Gets the status of a data export
+    pub async fn get_export(
+        &self,
+        actor: &User,
+        user_id: UserId,
+        export_id: Uuid,
+    ) -> Result<DataExport> {
+        let user = self
+            .get_subject(actor, user_id, PermissionAction::Read)
+            .await?;
+        self.repository
+            .get_export(export_id, user.id, user.tenant_id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Export not found".to_string()))
+    }
+
+    /// Gets the archive of a completed data export
+    pub async fn get_export_archive(
+        &self,
+        actor: &User,
+        user_id: UserId,
+        export_id: Uuid,
+    ) -> Result<serde_json::Value> {
+        let export = self.get_export(actor, user_id, export_id).await?;
+        if export.status != ExportStatus::Completed {
+            return Err(Error::Conflict(format!("Export is {}", export.status)));
+        }
+        self.repository
+            .get_export_archive(export.id, export.user_id, export.tenant_id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Export not found".to_string()))
+    }
+
+    /// Assembles everything stored about a user
+    pub async fn build_archive(&self, user: &User) -> Result<UserDataArchive> {
+        let emails = self.users.list_user_emails(user.id, user.tenant_id).await?;
+        let sessions = self
+            .auth
+            .list_user_sessions(user.tenant_id, user.id)
+            .await?;
+        let sso_mappings = self
+            .repository
+            .list_sso_mappings(user.id, user.tenant_id)
+            .await?;
+        let audit_events = self
+            .repository
+            .list_audit_events(user.id, user.tenant_id)
+            .await?;
+        let unused_backup_codes = self
+            .repository
+            .count_unused_backup_codes(user.id, user.tenant_id)
+            .await?;
+
+        Ok(UserDataArchive {
+            generated_at: OffsetDateTime::now_utc(),
+            profile: UserResponse::from(user.clone()),
+            emails,
+            roles: user.roles.clone(),
+            mfa: MfaRecord {
+                enabled: user.mfa_enabled,
+                unused_backup_codes,
+            },
+            sessions: sessions.into_iter().map(SessionRecord::from).collect(),
+            sso_mappings,
+            audit_events,
+        })
+    }
+
+    /// Schedules the erasure of a user's personal data after the grace period
+    pub async fn request_erasure(&self, actor: &User, user_id: UserId) -> Result<DataErasure> {
+        let user = self
+            .get_subject(actor, user_id, PermissionAction::Delete)
+            .await?;
+        if self
+            .repository
+            .has_tombstone(user.id, user.tenant_id)
+            .await?
+        {
+            return Err(Error::Conflict("User data was already erased".to_string()));
+        }
+        if let Some(erasure) = self
+            .repository
+            .get_latest_erasure(user.id, user.tenant_id)
+            .await?
+        {
+            if erasure.status == ErasureStatus::Pending {
+                return Err(Error::Conflict(format!(
+                    "Erasure already scheduled for {}",
+                    erasure.scheduled_at
+                )));
+            }
+        }
+
+        let erasure =
+            DataErasure::new(user.tenant_id, user.id, actor.id, self.erasure_grace_period);
+        self.repository.create_erasure(&erasure).await?;
+        Ok(erasure)
+    }
+
+    /// Gets the most recent erasure request of a user
+    pub async fn get_erasure(&self, actor: &User, user_id: UserId) -> Result<DataErasure> {
+        let user = self
+            .get_subject(actor, user_id, PermissionAction::Read)
+            .await?;
+        self.repository
+            .get_latest_erasure(user.id, user.tenant_id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Erasure not found".to_string()))
+    }
+
+    /// Cancels a pending erasure request during its grace period
+    pub async fn cancel_erasure(&self, actor: &User, user_id: UserId) -> Result<DataErasure> {
+        let user = self
+            .get_subject(actor, user_id, PermissionAction::Delete)
+            .await?;
+        if !self
+            .repository
+            .cancel_erasure(user.id, user.tenant_id)
+            .await?
+        {
+            return Err(Error::NotFound("No pending erasure found".to_string()));
+        }
+        self.repository
+            .get_latest_erasure(user.id, user.tenant_id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Erasure not found".to_string()))
+    }
+
+    /// Carries out the erasure requests whose grace period has ended
+    pub async fn process_due_erasures(&self) -> Result<usize> {
+        let erasures = self
+            .repository
+            .list_due_erasures(ERASURE_BATCH_SIZE)
+            .await?;
+        let mut erased = 0;
+        for erasure in erasures {
+            match self.run_erasure(&erasure).await {
+                Ok(true) => erased += 1,
+                Ok(false) => {},
+                Err(e) => {
+                    error!("Data erasure {} failed: {}", erasure.id, e);
+                    if let Err(e) = self
+                        .repository
+                        .fail_erasure(erasure.id, e.to_string())
+                        .await
+                    {
+                        error!(
+                            "Failed to record status of data erasure {}: {}",
+                            erasure.id, e
+                        );
+                    }
+                },
+            }
+        }
+        Ok(erased)
+    }
+
+    /// Periodically carries out due erasure requests in the background
+    pub fn spawn_erasure_worker(self: Arc<Self>, interval: std::time::Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match self.process_due_erasures().await {
+                    Ok(0) => {},
+                    Ok(erased) => info!("Erased the personal data of {} users", erased),
+                    Err(e) => error!("Failed to process data erasures: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Revokes the sessions of a user and anonymizes their data
+    async fn run_erasure(&self, erasure: &DataErasure) -> Result<bool> {
+        self.auth
+            .deactivate_user(erasure.user_id, erasure.tenant_id)
+            .await?;
+
+        let password: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        let password_hash = AuthenticationService::hash_password(&password)?;
+        let email = format!("erased-{}@erased.invalid", erasure.user_id.0);
+
+        self.repository
+            .erase_user_data(erasure, &email, &password_hash)
+            .await
+    }
+
+    /// Generates the archive of an export and records the outcome
+    async fn run_export(&self, export_id: Uuid, user: User) {
+        let result = async {
+            self.repository
+                .update_export(export_id, ExportStatus::Processing, None, None)
+                .await?;
+            let archive = serde_json::to_value(self.build_archive(&user).await?)
+                .map_err(|e| Error::Internal(format!("Failed to serialize archive: {}", e)))?;
+            self.repository
+                .update_export(export_id, ExportStatus::Completed, Some(archive), None)
+                .await
+        }
+        .await;
+
+        if let Err(e) = result {
+            error!("Data export {} failed: {}", export_id, e);
+            if let Err(e) = self
+                .repository
+                .update_export(export_id, ExportStatus::Failed, None, Some(e.to_string()))
+                .await
+            {
+                error!(
+                    "Failed to record status of data export {}: {}",
+                    export_id, e
+                );
+            }
+        }
+    }
+
+    /// Loads the user a request is about; admins need the given permission on users
+    async fn get_subject(
+        &self,
+        actor: &User,
+        user_id: UserId,
+        action: PermissionAction,
+    ) -> Result<User> {
+        if actor.id != user_id && !has_permission(actor, action, "users") {
+            return Err(Error::Authorization(
+                "Missing permission to access user data".to_string(),
+            ));
+        }
+        self.users
+            .get_user_by_id(user_id)
+            .await?
+            .filter(|user| user.tenant_id == actor.tenant_id)
+            .ok_or_else(|| Error::NotFound("User not found".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::database::{tests::create_test_db, Database},
+        modules::{
+            identity::session::{Session, SessionStore},
+            tenant::models::Tenant,
+        },
+        shared::types::TenantId,
+    };
+
+    #[derive(Debug, Default)]
+    struct StaticSessionStore {
+        sessions: Vec<Session>,
+    }
+
+    #[async_trait::async_trait]
+    impl SessionStore for StaticSessionStore {
+        async fn store_session(&self, _session: &Session) -> Result<()> {
+            Ok(())
+        }
+
+        async fn get_session(&self, _id: Uuid) -> Result<Option<Session>> {
+            Ok(None)
+        }
+
+        async fn get_session_by_token(&self, _token: &str) -> Result<Option<Session>> {
+            Ok(None)
+        }
+
+        async fn get_user_sessions(
+            &self,
+            _tenant_id: TenantId,
+            user_id: UserId,
+        ) -> Result<Vec<Session>> {
+            Ok(self
+                .sessions
+                .iter()
+                .filter(|session| session.user_id == user_id)
+                .cloned()
+                .collect())
+        }
+
+        async fn get_tenant_sessions(&self, tenant_id: TenantId) -> Result<Vec<Session>> {
+            Ok(self
+                .sessions
+                .iter()
+                .filter(|session| session.tenant_id == tenant_id)
+                .cloned()
+                .collect())
+        }
+
+        async fn remove_session(&self, _id: Uuid) -> Result<()> {
+            Ok(())
+        }
+
+        async fn remove_user_sessions(&self, _tenant_id: TenantId, _user_id: UserId) -> Result<()> {
+            Ok(())
+        }
+
+        async fn remove_tenant_sessions(&self, _tenant_id: TenantId) -> Result<()> {
+            Ok(())
+        }
+
+        async fn get_token_version(&self, _tenant_id: TenantId, _user_id: UserId) -> Result<u64> {
+            Ok(0)
+        }
+
+        async fn increment_token_version(
+            &self,
+            _tenant_id: TenantId,
+            _user_id: UserId,
+        ) -> Result<u64> {
+            Ok(1)
+        }
+    }
+
+    async fn setup_test_tenant(db: &Database) -> Tenant {
+        let tenant = Tenant::new(
+            "Test Tenant".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        sqlx::query!(
+            r#"INSERT INTO tenants (id, name, domain, active) VALUES ($1, $2, $3, $4)"#,
+            tenant.id.0 as uuid::Uuid,
+            tenant.name,
+            tenant.domain,
+            tenant.active
+        )
+        .execute(&db.get_pool())
+        .await
+        .unwrap();
+        tenant
+    }
+
+    #[tokio::test]
+    async fn test_data_export() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let tenant = setup_test_tenant(&db).await;
+        let users = UserRepository::new(db.get_pool());
+
+        let user = users
+            .create_user(User::new(
+                tenant.id,
+                "jane@example.com".to_string(),
+                "hash".to_string(),
+            ))
+            .await
+            .unwrap();
+        let other = users
+            .create_user(User::new(
+                tenant.id,
+                "john@example.com".to_string(),
+                "hash".to_string(),
+            ))
+            .await
+            .unwrap();
+
+        let session = Session::new(
+            user.id,
+            tenant.id,
+            "secret-token".to_string(),
+            time::Duration::hours(1),
+        );
+        let auth = Arc::new(AuthenticationService::new(
+            users.clone(),
+            Box::new(StaticSessionStore {
+                sessions: vec![session.clone()],
+            }),
+        ));
+        let service = PrivacyService::new(users, auth);
+
+        // Users without admin permissions can only export their own data
+        let result = service.request_export(&other, user.id).await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+
+        let export = service.request_export(&user, user.id).await.unwrap();
+        assert_eq!(export.status, ExportStatus::Pending);
+
+        let mut retries = 50;
+        let export = loop {
+            let export = service.get_export(&user, user.id, export.id).await.unwrap();
+            if export.status == ExportStatus::Completed || retries == 0 {
+                break export;
+            }
+            retries -= 1;
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        };
+        assert_eq!(export.status, ExportStatus::Completed);
+        assert!(export.completed_at.is_some());
+
+        let archive = service
+            .get_export_archive(&user, user.id, export.id)
+            .await
+            .unwrap();
+        assert_eq!(archive["profile"]["email"], "jane@example.com");
+        assert_eq!(archive["emails"].as_array().unwrap().len(), 1);
+        assert_eq!(archive["sessions"][0]["id"], session.id.to_string());
+        assert!(!archive.to_string().contains("secret-token"));
+        assert!(!archive.to_string().contains("password_hash"));
+    }
+
+    #[tokio::test]
+    async fn test_data_erasure() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let tenant = setup_test_tenant(&db).await;
+        let users = UserRepository::new(db.get_pool());
+
+        let user = users
+            .create_user(User::new(
+                tenant.id,
+                "jane@example.com".to_string(),
+                "hash".to_string(),
+            ))
+            .await
+            .unwrap();
+        let other = users
+            .create_user(User::new(
+                tenant.id,
+                "john@example.com".to_string(),
+                "hash".to_string(),
+            ))
+            .await
+            .unwrap();
+
+        let auth = Arc::new(AuthenticationService::new(
+            users.clone(),
+            Box::new(StaticSessionStore::default()),
+        ));
+        let service = PrivacyService::new(users.clone(), auth);
+
+        // Users without admin permissions can only erase their own data
+        let result = service.request_erasure(&other, user.id).await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+
+        // Erasures can be cancelled during the grace period
+        let erasure = service.request_erasure(&user, user.id).await.unwrap();
+        assert_eq!(erasure.status, ErasureStatus::Pending);
+        let result = service.request_erasure(&user, user.id).await;
+        assert!(matches!(result, Err(Error::Conflict(_))));
+        assert_eq!(service.process_due_erasures().await.unwrap(), 0);
+
+        let erasure = service.cancel_erasure(&user, user.id).await.unwrap();
+        assert_eq!(erasure.status, ErasureStatus::Cancelled);
+        assert!(erasure.cancelled_at.is_some());
+
+        let service = service.with_erasure_grace_period(Duration::ZERO);
+        service.request_erasure(&user, user.id).await.unwrap();
+        assert_eq!(service.process_due_erasures().await.unwrap(), 1);
+
+        let erasure = service.get_erasure(&user, user.id).await.unwrap();
+        assert_eq!(erasure.status, ErasureStatus::Completed);
+
+        let erased = users.get_user_by_id(user.id).await.unwrap().unwrap();
+        assert!(!erased.active);
+        assert!(erased.email.ends_with("@erased.invalid"));
+        assert!(erased.mfa_secret.is_none());
+        assert!(users
+            .list_user_emails(user.id, tenant.id)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(users
+            .get_user_by_email("jane@example.com", tenant.id)
+            .await
+            .unwrap()
+            .is_none());
+
+        // The tombstone prevents erasing the user again
+        let result = service.request_erasure(&user, user.id).await;
+        assert!(matches!(result, Err(Error::Conflict(_))));
+    }
+}
@@ -0,0 +1,172 @@
+use axum::{
+    extract::{FromRef, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, put},
+    Json, Router,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    modules::{
+        identity::{auth::AuthenticationService, handlers::AuthenticatedUser, models::User},
+        project::{models::ProjectRequest, service::ProjectService},
+    },
+    shared::{
+        error::{Error, Result},
+        types::UserId,
+    },
+};
+
+/// Shared state of the project handlers
+#[derive(Clone, FromRef)]
+pub struct ProjectState {
+    pub auth: Arc<AuthenticationService>,
+    pub projects: Arc<ProjectService>,
+}
+
+/// Parses a UUID from the request path
+fn parse_id(id: &str) -> Result<Uuid> {
+    Uuid::parse_str(id).map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))
+}
+
+/// Ensures the actor belongs to the tenant of the request path
+fn ensure_actor_tenant(actor: &User, tenant_id: &str) -> Result<()> {
+    if actor.tenant_id.0 != parse_id(tenant_id)? {
+        return Err(Error::Authorization("Access to tenant denied".to_string()));
+    }
+    Ok(())
+}
+
+/// Lists the projects visible to the actor
+pub async fn list_projects(
+    State(projects): State<Arc<ProjectService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(tenant_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    ensure_actor_tenant(&actor, &tenant_id)?;
+    let projects = projects.list_projects(&actor).await?;
+    Ok((StatusCode::OK, Json(projects)))
+}
+
+/// Gets a project
+pub async fn get_project(
+    State(projects): State<Arc<ProjectService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, project_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    ensure_actor_tenant(&actor, &tenant_id)?;
+    let project = projects.get_project(&actor, parse_id(&project_id)?).await?;
+    Ok((StatusCode::OK, Json(project)))
+}
+
+/// Creates a project
+pub async fn create_project(
+    State(projects): State<Arc<ProjectService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(tenant_id): Path<String>,
+    Json(request): Json<ProjectRequest>,
+) -> Result<impl IntoResponse> {
+    ensure_actor_tenant(&actor, &tenant_id)?;
+    let project = projects.create_project(&actor, request).await?;
+    Ok((StatusCode::CREATED, Json(project)))
+}
+
+/// Updates a project
+pub async fn update_project(
+    State(projects): State<Arc<ProjectService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, project_id)): Path<(String, String)>,
+    Json(request): Json<ProjectRequest>,
+) -> Result<impl IntoResponse> {
+    ensure_actor_tenant(&actor, &tenant_id)?;
+    let project = projects
+        .update_project(&actor, parse_id(&project_id)?, request)
+        .await?;
+    Ok((StatusCode::OK, Json(project)))
+}
+
+/// Deletes a project
+pub async fn delete_project(
+    State(projects): State<Arc<ProjectService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, project_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    ensure_actor_tenant(&actor, &tenant_id)?;
+    projects
+        .delete_project(&actor, parse_id(&project_id)?)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lists the members of a project
+pub async fn list_members(
+    State(projects): State<Arc<ProjectService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, project_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    ensure_actor_tenant(&actor, &tenant_id)?;
+    let members = projects
+        .list_members(&actor, parse_id(&project_id)?)
+        .await?;
+    Ok((StatusCode::OK, Json(members)))
+}
+
+/// Grants a role to a user within a project
+pub async fn add_member(
+    State(projects): State<Arc<ProjectService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, project_id, user_id, role_id)): Path<(String, String, String, String)>,
+) -> Result<impl IntoResponse> {
+    ensure_actor_tenant(&actor, &tenant_id)?;
+    projects
+        .add_member(
+            &actor,
+            parse_id(&project_id)?,
+            UserId(parse_id(&user_id)?),
+            parse_id(&role_id)?,
+        )
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Removes a role granted to a user within a project
+pub async fn remove_member(
+    State(projects): State<Arc<ProjectService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path((tenant_id, project_id, user_id, role_id)): Path<(String, String, String, String)>,
+) -> Result<impl IntoResponse> {
+    ensure_actor_tenant(&actor, &tenant_id)?;
+    projects
+        .remove_member(
+            &actor,
+            parse_id(&project_id)?,
+            UserId(parse_id(&user_id)?),
+            parse_id(&role_id)?,
+        )
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Creates the project module router
+pub fn router(state: ProjectState) -> Router {
+    Router::new()
+        .route(
+            "/tenants/:tenant_id/projects",
+            get(list_projects).post(create_project),
+        )
+        .route(
+            "/tenants/:tenant_id/projects/:id",
+            get(get_project).put(update_project).delete(delete_project),
+        )
+        .route(
+            "/tenants/:tenant_id/projects/:id/members",
+            get(list_members),
+        )
+        .route(
+            "/tenants/:tenant_id/projects/:id/members/:user_id/roles/:role_id",
+            put(add_member).delete(remove_member),
+        )
+        .with_state(state)
+}
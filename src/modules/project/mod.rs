@@ -0,0 +1,20 @@
+mod handlers;
+pub mod models;
+pub mod repository;
+pub mod service;
+
+pub use handlers::ProjectState;
+pub use service::ProjectService;
+
+use axum::Router;
+use std::sync::Arc;
+
+use crate::modules::identity::AuthenticationService;
+
+/// Creates a router for projects and project memberships
+pub fn router(auth_service: Arc<AuthenticationService>, projects: Arc<ProjectService>) -> Router {
+    handlers::router(ProjectState {
+        auth: auth_service,
+        projects,
+    })
+}
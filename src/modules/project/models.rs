@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    modules::identity::models::Role,
+    shared::types::{TenantId, UserId},
+};
+
+/// Project model, an optional scope within a tenant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub id: Uuid,
+    pub tenant_id: TenantId,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+impl Project {
+    /// Creates a new project
+    pub fn new(tenant_id: TenantId, name: String, description: Option<String>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            tenant_id,
+            name,
+            description,
+            created_at: OffsetDateTime::now_utc(),
+            updated_at: OffsetDateTime::now_utc(),
+        }
+    }
+}
+
+/// Project request model
+#[derive(Debug, Deserialize)]
+pub struct ProjectRequest {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// Member of a project with the roles granted within it
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectMember {
+    pub user_id: UserId,
+    pub email: String,
+    pub roles: Vec<Role>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_creation() {
+        let tenant_id = TenantId::new();
+        let project = Project::new(tenant_id, "Website".to_string(), None);
+
+        assert_eq!(project.tenant_id, tenant_id);
+        assert_eq!(project.name, "Website");
+        assert!(project.description.is_none());
+    }
+}
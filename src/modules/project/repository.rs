@@ -0,0 +1,251 @@
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    modules::project::models::Project,
+    shared::{
+        error::Result,
+        types::{TenantId, UserId},
+    },
+};
+
+/// Project repository for database operations
+#[derive(Debug, Clone)]
+pub struct ProjectRepository {
+    pool: Pool<Postgres>,
+}
+
+impl ProjectRepository {
+    /// Creates a new ProjectRepository instance
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    /// Creates a project
+    pub async fn create_project(&self, project: &Project) -> Result<Project> {
+        let r = sqlx::query!(
+            r#"
+            INSERT INTO projects (id, tenant_id, name, description, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, tenant_id, name, description, created_at, updated_at
+            "#,
+            project.id,
+            project.tenant_id.0 as uuid::Uuid,
+            project.name,
+            project.description,
+            project.created_at,
+            project.updated_at,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Project {
+            id: r.id,
+            tenant_id: TenantId(r.tenant_id),
+            name: r.name,
+            description: r.description,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+        })
+    }
+
+    /// Gets a project by ID
+    pub async fn get_project(&self, id: Uuid, tenant_id: TenantId) -> Result<Option<Project>> {
+        let result = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, name, description, created_at, updated_at
+            FROM projects
+            WHERE id = $1 AND tenant_id = $2
+            "#,
+            id,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(|r| Project {
+            id: r.id,
+            tenant_id: TenantId(r.tenant_id),
+            name: r.name,
+            description: r.description,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+        }))
+    }
+
+    /// Lists the projects of a tenant, optionally only those a user is a member of
+    pub async fn list_projects(
+        &self,
+        tenant_id: TenantId,
+        member: Option<UserId>,
+    ) -> Result<Vec<Project>> {
+        let results = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, name, description, created_at, updated_at
+            FROM projects p
+            WHERE tenant_id = $1
+              AND ($2::uuid IS NULL OR EXISTS (
+                  SELECT 1 FROM project_members m WHERE m.project_id = p.id AND m.user_id = $2
+              ))
+            ORDER BY name
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            member.map(|user_id| user_id.0),
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| Project {
+                id: r.id,
+                tenant_id: TenantId(r.tenant_id),
+                name: r.name,
+                description: r.description,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+            })
+            .collect())
+    }
+
+    /// Updates the name and description of a project
+    pub async fn update_project(&self, project: &Project) -> Result<Option<Project>> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE projects
+            SET name = $1, description = $2
+            WHERE id = $3 AND tenant_id = $4
+            RETURNING id, tenant_id, name, description, created_at, updated_at
+            "#,
+            project.name,
+            project.description,
+            project.id,
+            project.tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(|r| Project {
+            id: r.id,
+            tenant_id: TenantId(r.tenant_id),
+            name: r.name,
+            description: r.description,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+        }))
+    }
+
+    /// Deletes a project and its memberships
+    pub async fn delete_project(&self, id: Uuid, tenant_id: TenantId) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM projects
+            WHERE id = $1 AND tenant_id = $2
+            "#,
+            id,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Lists the user ID, email and role ID of every role granted within a project
+    pub async fn list_member_roles(
+        &self,
+        project_id: Uuid,
+        tenant_id: TenantId,
+    ) -> Result<Vec<(UserId, String, Uuid)>> {
+        let results = sqlx::query!(
+            r#"
+            SELECT m.user_id, u.email, m.role_id
+            FROM project_members m
+            JOIN users u ON u.id = m.user_id
+            WHERE m.project_id = $1 AND m.tenant_id = $2
+            ORDER BY u.email, m.role_id
+            "#,
+            project_id,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| (UserId(r.user_id), r.email, r.role_id))
+            .collect())
+    }
+
+    /// Lists the IDs of the roles granted to a user within a project
+    pub async fn list_user_role_ids(
+        &self,
+        project_id: Uuid,
+        user_id: UserId,
+        tenant_id: TenantId,
+    ) -> Result<Vec<Uuid>> {
+        let role_ids = sqlx::query_scalar!(
+            r#"
+            SELECT role_id
+            FROM project_members
+            WHERE project_id = $1 AND user_id = $2 AND tenant_id = $3
+            "#,
+            project_id,
+            user_id.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(role_ids)
+    }
+
+    /// Grants a role to a user within a project, all of the same tenant
+    pub async fn add_member_role(
+        &self,
+        project_id: Uuid,
+        user_id: UserId,
+        role_id: Uuid,
+        tenant_id: TenantId,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO project_members (project_id, user_id, role_id, tenant_id)
+            SELECT p.id, u.id, r.id, p.tenant_id
+            FROM projects p
+            JOIN users u ON u.tenant_id = p.tenant_id
+            JOIN roles r ON r.tenant_id = p.tenant_id
+            WHERE p.id = $1 AND u.id = $2 AND r.id = $3 AND p.tenant_id = $4
+            ON CONFLICT DO NOTHING
+            "#,
+            project_id,
+            user_id.0 as uuid::Uuid,
+            role_id,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Removes a role granted to a user within a project
+    pub async fn remove_member_role(
+        &self,
+        project_id: Uuid,
+        user_id: UserId,
+        role_id: Uuid,
+        tenant_id: TenantId,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM project_members
+            WHERE project_id = $1 AND user_id = $2 AND role_id = $3 AND tenant_id = $4
+            "#,
+            project_id,
+            user_id.0 as uuid::Uuid,
+            role_id,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
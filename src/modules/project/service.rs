@@ -0,0 +1,439 @@
+use uuid::Uuid;
+
+use crate::{
+    modules::{
+        identity::{
+            models::{PermissionAction, Role, User},
+            rbac::{can_grant, has_permission, has_role_permission},
+            repository::{RoleRepository, UserRepository},
+        },
+        project::{
+            models::{Project, ProjectMember, ProjectRequest},
+            repository::ProjectRepository,
+        },
+    },
+    shared::{
+        error::{Error, Result},
+        types::UserId,
+    },
+};
+
+/// Project service scoping memberships and permissions below tenants
+///
+/// A user's effective permissions within a project are their tenant-wide
+/// roles plus the roles granted to them in that project.
+#[derive(Debug, Clone)]
+pub struct ProjectService {
+    repository: ProjectRepository,
+    role_repository: RoleRepository,
+    users: UserRepository,
+}
+
+impl ProjectService {
+    /// Creates a new ProjectService instance
+    pub fn new(users: UserRepository) -> Self {
+        Self {
+            repository: ProjectRepository::new(users.get_pool().clone()),
+            role_repository: RoleRepository::new(users.get_pool().clone()),
+            users,
+        }
+    }
+
+    /// Gets the roles granted to a user within a project
+    pub async fn project_roles(&self, user: &User, project_id: Uuid) -> Result<Vec<Role>> {
+        let role_ids = self
+            .repository
+            .list_user_role_ids(project_id, user.id, user.tenant_id)
+            .await?;
+        if role_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let roles = self.role_repository.list_roles(user.tenant_id).await?;
+        Ok(roles
+            .into_iter()
+            .filter(|role| role_ids.contains(&role.id))
+            .collect())
+    }
+
+    /// Checks if a user has a permission tenant-wide or within a project
+    pub async fn check_permission(
+        &self,
+        user: &User,
+        project_id: Uuid,
+        action: PermissionAction,
+        resource: &str,
+    ) -> Result<bool> {
+        if has_permission(user, action, resource) {
+            return Ok(true);
+        }
+        let roles = self.project_roles(user, project_id).await?;
+        Ok(has_role_permission(&roles, action, resource))
+    }
+
+    /// Lists all projects of the tenant, or only the actor's projects without tenant-wide access
+    pub async fn list_projects(&self, actor: &User) -> Result<Vec<Project>> {
+        let member = if has_permission(actor, PermissionAction::Read, "projects") {
+            None
+        } else {
+            Some(actor.id)
+        };
+        self.repository.list_projects(actor.tenant_id, member).await
+    }
+
+    /// Gets a project the actor may read
+    pub async fn get_project(&self, actor: &User, project_id: Uuid) -> Result<Project> {
+        let project = self.find_project(actor, project_id).await?;
+        self.ensure_permission(actor, project_id, PermissionAction::Read)
+            .await?;
+        Ok(project)
+    }
+
+    /// Creates a project in the actor's tenant
+    pub async fn create_project(&self, actor: &User, request: ProjectRequest) -> Result<Project> {
+        if !has_permission(actor, PermissionAction::Create, "projects") {
+            return Err(Error::Authorization(
+                "Missing permission to create projects".to_string(),
+            ));
+        }
+        let project = Project::new(actor.tenant_id, request.name, request.description);
+        self.validate_project(&project).await?;
+        self.repository.create_project(&project).await
+    }
+
+    /// Updates the name and description of a project
+    pub async fn update_project(
+        &self,
+        actor: &User,
+        project_id: Uuid,
+        request: ProjectRequest,
+    ) -> Result<Project> {
+        let mut project = self.find_project(actor, project_id).await?;
+        self.ensure_permission(actor, project_id, PermissionAction::Update)
+            .await?;
+
+        project.name = request.name;
+        project.description = request.description;
+        self.validate_project(&project).await?;
+        self.repository
+            .update_project(&project)
+            .await?
+            .ok_or_else(|| Error::NotFound("Project not found".to_string()))
+    }
+
+    /// Deletes a project
+    pub async fn delete_project(&self, actor: &User, project_id: Uuid) -> Result<()> {
+        if !has_permission(actor, PermissionAction::Delete, "projects") {
+            return Err(Error::Authorization(
+                "Missing permission to delete projects".to_string(),
+            ));
+        }
+        if !self
+            .repository
+            .delete_project(project_id, actor.tenant_id)
+            .await?
+        {
+            return Err(Error::NotFound("Project not found".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Lists the members of a project with their project roles
+    pub async fn list_members(&self, actor: &User, project_id: Uuid) -> Result<Vec<ProjectMember>> {
+        self.find_project(actor, project_id).await?;
+        self.ensure_permission(actor, project_id, PermissionAction::Read)
+            .await?;
+
+        let roles = self.role_repository.list_roles(actor.tenant_id).await?;
+        let mut members: Vec<ProjectMember> = Vec::new();
+        for (user_id, email, role_id) in self
+            .repository
+            .list_member_roles(project_id, actor.tenant_id)
+            .await?
+        {
+            if members.last().map(|m| m.user_id) != Some(user_id) {
+                members.push(ProjectMember {
+                    user_id,
+                    email,
+                    roles: Vec::new(),
+                });
+            }
+            if let (Some(member), Some(role)) =
+                (members.last_mut(), roles.iter().find(|r| r.id == role_id))
+            {
+                member.roles.push(role.clone());
+            }
+        }
+        Ok(members)
+    }
+
+    /// Grants a role to a user within a project
+    pub async fn add_member(
+        &self,
+        actor: &User,
+        project_id: Uuid,
+        user_id: UserId,
+        role_id: Uuid,
+    ) -> Result<()> {
+        let role = self.get_grantable_role(actor, project_id, role_id).await?;
+        self.get_tenant_user(actor, user_id).await?;
+        self.repository
+            .add_member_role(project_id, user_id, role.id, actor.tenant_id)
+            .await?;
+        Ok(())
+    }
+
+    /// Removes a role granted to a user within a project
+    pub async fn remove_member(
+        &self,
+        actor: &User,
+        project_id: Uuid,
+        user_id: UserId,
+        role_id: Uuid,
+    ) -> Result<()> {
+        let role = self.get_grantable_role(actor, project_id, role_id).await?;
+        if !self
+            .repository
+            .remove_member_role(project_id, user_id, role.id, actor.tenant_id)
+            .await?
+        {
+            return Err(Error::NotFound(
+                "Role is not granted to user in project".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Loads a project of the actor's tenant
+    async fn find_project(&self, actor: &User, project_id: Uuid) -> Result<Project> {
+        self.repository
+            .get_project(project_id, actor.tenant_id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Project not found".to_string()))
+    }
+
+    /// Loads a user of the actor's tenant
+    async fn get_tenant_user(&self, actor: &User, user_id: UserId) -> Result<User> {
+        self.users
+            .get_user_by_id(user_id)
+            .await?
+            .filter(|user| user.tenant_id == actor.tenant_id)
+            .ok_or_else(|| Error::NotFound("User not found".to_string()))
+    }
+
+    /// Loads a role the actor may grant within a project
+    async fn get_grantable_role(
+        &self,
+        actor: &User,
+        project_id: Uuid,
+        role_id: Uuid,
+    ) -> Result<Role> {
+        self.find_project(actor, project_id).await?;
+        self.ensure_permission(actor, project_id, PermissionAction::Update)
+            .await?;
+
+        let role = self
+            .role_repository
+            .get_role(role_id, actor.tenant_id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Role not found".to_string()))?;
+
+        // The actor must hold every permission of the role within the project
+        let mut actor_roles = actor.roles.clone();
+        actor_roles.extend(self.project_roles(actor, project_id).await?);
+        if let Some(permission) = role
+            .permissions
+            .iter()
+            .find(|p| !can_grant(&actor_roles, p))
+        {
+            return Err(Error::Authorization(format!(
+                "Cannot grant permission {} on {} without holding it",
+                permission.action, permission.resource
+            )));
+        }
+        Ok(role)
+    }
+
+    /// Ensures the actor holds a permission on projects tenant-wide or within the project
+    async fn ensure_permission(
+        &self,
+        actor: &User,
+        project_id: Uuid,
+        action: PermissionAction,
+    ) -> Result<()> {
+        if !self
+            .check_permission(actor, project_id, action, "projects")
+            .await?
+        {
+            return Err(Error::Authorization(format!(
+                "Missing permission {} on project",
+                action
+            )));
+        }
+        Ok(())
+    }
+
+    /// Validates a project and ensures its name is unique within the tenant
+    async fn validate_project(&self, project: &Project) -> Result<()> {
+        if project.name.trim().is_empty() {
+            return Err(Error::Validation(
+                "Project name must not be empty".to_string(),
+            ));
+        }
+        let projects = self
+            .repository
+            .list_projects(project.tenant_id, None)
+            .await?;
+        if projects
+            .iter()
+            .any(|p| p.name == project.name && p.id != project.id)
+        {
+            return Err(Error::Conflict(format!(
+                "Project {} already exists",
+                project.name
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::database::tests::create_test_db,
+        modules::{
+            identity::models::{Permission, RoleType},
+            tenant::models::Tenant,
+        },
+    };
+
+    fn project_role(name: &str, actions: &[PermissionAction]) -> Role {
+        let mut role = Role::new(RoleType::Custom, name.to_string());
+        role.permissions = actions
+            .iter()
+            .map(|action| Permission::new(name.to_string(), *action, "projects".to_string()))
+            .collect();
+        role
+    }
+
+    #[tokio::test]
+    async fn test_project_scoped_permissions() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let tenant = Tenant::new(
+            "Test Tenant".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        sqlx::query!(
+            r#"INSERT INTO tenants (id, name, domain, active) VALUES ($1, $2, $3, $4)"#,
+            tenant.id.0 as uuid::Uuid,
+            tenant.name,
+            tenant.domain,
+            tenant.active
+        )
+        .execute(&db.get_pool())
+        .await
+        .unwrap();
+
+        let users = UserRepository::new(db.get_pool());
+        let roles = RoleRepository::new(db.get_pool());
+        let service = ProjectService::new(users.clone());
+
+        let owner_role = project_role(
+            "Project Owner",
+            &[
+                PermissionAction::Create,
+                PermissionAction::Read,
+                PermissionAction::Update,
+                PermissionAction::Delete,
+            ],
+        );
+        let mut owner = User::new(
+            tenant.id,
+            "owner@example.com".to_string(),
+            "hash".to_string(),
+        );
+        owner.roles.push(owner_role);
+        let owner = users.create_user(owner).await.unwrap();
+
+        let member = users
+            .create_user(User::new(
+                tenant.id,
+                "member@example.com".to_string(),
+                "hash".to_string(),
+            ))
+            .await
+            .unwrap();
+        let viewer = roles
+            .create_role(
+                tenant.id,
+                &project_role("Project Viewer", &[PermissionAction::Read]),
+            )
+            .await
+            .unwrap();
+
+        let website = service
+            .create_project(
+                &owner,
+                ProjectRequest {
+                    name: "Website".to_string(),
+                    description: None,
+                },
+            )
+            .await
+            .unwrap();
+        let backend = service
+            .create_project(
+                &owner,
+                ProjectRequest {
+                    name: "Backend".to_string(),
+                    description: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        // Members without tenant-wide access neither see nor read projects
+        assert!(service.list_projects(&member).await.unwrap().is_empty());
+        let result = service.get_project(&member, website.id).await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+
+        service
+            .add_member(&owner, website.id, member.id, viewer.id)
+            .await
+            .unwrap();
+
+        // The viewer role only applies within the project it was granted in
+        let projects = service.list_projects(&member).await.unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].id, website.id);
+        assert!(service
+            .check_permission(&member, website.id, PermissionAction::Read, "projects")
+            .await
+            .unwrap());
+        assert!(!service
+            .check_permission(&member, backend.id, PermissionAction::Read, "projects")
+            .await
+            .unwrap());
+        assert!(!service
+            .check_permission(&member, website.id, PermissionAction::Update, "projects")
+            .await
+            .unwrap());
+
+        let members = service.list_members(&member, website.id).await.unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].roles[0].id, viewer.id);
+
+        // Project viewers cannot grant roles
+        let result = service
+            .add_member(&member, website.id, member.id, viewer.id)
+            .await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+
+        service
+            .remove_member(&owner, website.id, member.id, viewer.id)
+            .await
+            .unwrap();
+        assert!(service.list_projects(&member).await.unwrap().is_empty());
+    }
+}
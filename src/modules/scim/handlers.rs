@@ -0,0 +1,249 @@
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, Path, Query, State},
+    http::{header::CONTENT_TYPE, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    modules::{
+        identity::{auth::AuthenticationService, handlers::AuthenticatedUser, models::User},
+        scim::{
+            models::{
+                ScimErrorResponse, ScimGroup, ScimListQuery, ScimPatchRequest, ScimUser,
+                ERROR_SCHEMA,
+            },
+            service::ScimService,
+        },
+        tenant::middleware::CurrentTenant,
+    },
+    shared::error::Error,
+};
+
+/// Content type of SCIM responses
+const SCIM_CONTENT_TYPE: &str = "application/scim+json";
+
+/// Shared state of the SCIM handlers
+#[derive(Clone, FromRef)]
+pub struct ScimState {
+    pub auth: Arc<AuthenticationService>,
+    pub scim: Arc<ScimService>,
+}
+
+/// Error rendered as a SCIM error response
+#[derive(Debug)]
+pub struct ScimError(pub Error);
+
+impl From<Error> for ScimError {
+    fn from(error: Error) -> Self {
+        Self(error)
+    }
+}
+
+impl IntoResponse for ScimError {
+    fn into_response(self) -> Response {
+        let status = self.0.status_code();
+        let scim_type = match &self.0 {
+            Error::Conflict(_) => Some("uniqueness".to_string()),
+            Error::InvalidInput(_) | Error::Validation(_) => Some("invalidValue".to_string()),
+            _ => None,
+        };
+        let body = ScimErrorResponse {
+            schemas: vec![ERROR_SCHEMA.to_string()],
+            status: status.as_u16().to_string(),
+            detail: self.0.message().to_string(),
+            scim_type,
+        };
+        ScimJson(status, body).into_response()
+    }
+}
+
+type ScimResult<T> = std::result::Result<T, ScimError>;
+
+/// JSON body sent with the SCIM content type
+struct ScimJson<T>(StatusCode, T);
+
+impl<T: Serialize> IntoResponse for ScimJson<T> {
+    fn into_response(self) -> Response {
+        (self.0, [(CONTENT_TYPE, SCIM_CONTENT_TYPE)], Json(self.1)).into_response()
+    }
+}
+
+/// Provisioning client authenticated by the bearer token of the request
+///
+/// If the request's tenant was resolved, the token must belong to that tenant.
+pub struct ScimActor(pub User);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ScimActor
+where
+    Arc<AuthenticationService>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ScimError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> ScimResult<Self> {
+        let AuthenticatedUser(user) = AuthenticatedUser::from_request_parts(parts, state).await?;
+        if let Some(CurrentTenant(tenant)) = parts.extensions.get::<CurrentTenant>() {
+            if tenant.id != user.tenant_id {
+                return Err(Error::Authorization(
+                    "Token does not belong to the tenant of the request".to_string(),
+                )
+                .into());
+            }
+        }
+        Ok(Self(user))
+    }
+}
+
+/// Parses a resource ID from the request path
+fn parse_id(id: &str) -> ScimResult<Uuid> {
+    Ok(Uuid::parse_str(id).map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))?)
+}
+
+/// Lists users
+pub async fn list_users(
+    State(scim): State<Arc<ScimService>>,
+    ScimActor(actor): ScimActor,
+    Query(query): Query<ScimListQuery>,
+) -> ScimResult<impl IntoResponse> {
+    let users = scim.list_users(&actor, &query).await?;
+    Ok(ScimJson(StatusCode::OK, users))
+}
+
+/// Gets a user
+pub async fn get_user(
+    State(scim): State<Arc<ScimService>>,
+    ScimActor(actor): ScimActor,
+    Path(id): Path<String>,
+) -> ScimResult<impl IntoResponse> {
+    let user = scim.get_user(&actor, parse_id(&id)?).await?;
+    Ok(ScimJson(StatusCode::OK, user))
+}
+
+/// Provisions a user
+pub async fn create_user(
+    State(scim): State<Arc<ScimService>>,
+    ScimActor(actor): ScimActor,
+    Json(request): Json<ScimUser>,
+) -> ScimResult<impl IntoResponse> {
+    let user = scim.create_user(&actor, request).await?;
+    Ok(ScimJson(StatusCode::CREATED, user))
+}
+
+/// Patches a user
+pub async fn patch_user(
+    State(scim): State<Arc<ScimService>>,
+    ScimActor(actor): ScimActor,
+    Path(id): Path<String>,
+    Json(request): Json<ScimPatchRequest>,
+) -> ScimResult<impl IntoResponse> {
+    let user = scim.patch_user(&actor, parse_id(&id)?, request).await?;
+    Ok(ScimJson(StatusCode::OK, user))
+}
+
+/// Deprovisions a user
+pub async fn delete_user(
+    State(scim): State<Arc<ScimService>>,
+    ScimActor(actor): ScimActor,
+    Path(id): Path<String>,
+) -> ScimResult<impl IntoResponse> {
+    scim.delete_user(&actor, parse_id(&id)?).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lists groups
+pub async fn list_groups(
+    State(scim): State<Arc<ScimService>>,
+    ScimActor(actor): ScimActor,
+    Query(query): Query<ScimListQuery>,
+) -> ScimResult<impl IntoResponse> {
+    let groups = scim.list_groups(&actor, &query).await?;
+    Ok(ScimJson(StatusCode::OK, groups))
+}
+
+/// Gets a group
+pub async fn get_group(
+    State(scim): State<Arc<ScimService>>,
+    ScimActor(actor): ScimActor,
+    Path(id): Path<String>,
+) -> ScimResult<impl IntoResponse> {
+    let group = scim.get_group(&actor, parse_id(&id)?).await?;
+    Ok(ScimJson(StatusCode::OK, group))
+}
+
+/// Creates a group
+pub async fn create_group(
+    State(scim): State<Arc<ScimService>>,
+    ScimActor(actor): ScimActor,
+    Json(request): Json<ScimGroup>,
+) -> ScimResult<impl IntoResponse> {
+    let group = scim.create_group(&actor, request).await?;
+    Ok(ScimJson(StatusCode::CREATED, group))
+}
+
+/// Patches a group
+pub async fn patch_group(
+    State(scim): State<Arc<ScimService>>,
+    ScimActor(actor): ScimActor,
+    Path(id): Path<String>,
+    Json(request): Json<ScimPatchRequest>,
+) -> ScimResult<impl IntoResponse> {
+    let group = scim.patch_group(&actor, parse_id(&id)?, request).await?;
+    Ok(ScimJson(StatusCode::OK, group))
+}
+
+/// Deletes a group
+pub async fn delete_group(
+    State(scim): State<Arc<ScimService>>,
+    ScimActor(actor): ScimActor,
+    Path(id): Path<String>,
+) -> ScimResult<impl IntoResponse> {
+    scim.delete_group(&actor, parse_id(&id)?).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Creates the SCIM router
+pub fn router(state: ScimState) -> Router {
+    Router::new()
+        .route("/scim/v2/Users", get(list_users).post(create_user))
+        .route(
+            "/scim/v2/Users/:id",
+            get(get_user).patch(patch_user).delete(delete_user),
+        )
+        .route("/scim/v2/Groups", get(list_groups).post(create_group))
+        .route(
+            "/scim/v2/Groups/:id",
+            get(get_group).patch(patch_group).delete(delete_group),
+        )
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scim_error_response() {
+        let response = ScimError(Error::Conflict("User exists".to_string())).into_response();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            SCIM_CONTENT_TYPE
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["schemas"][0], ERROR_SCHEMA);
+        assert_eq!(json["status"], "409");
+        assert_eq!(json["scimType"], "uniqueness");
+        assert_eq!(json["detail"], "User exists");
+    }
+}
@@ -0,0 +1,19 @@
+mod handlers;
+pub mod models;
+pub mod service;
+
+pub use handlers::{ScimError, ScimState};
+pub use service::ScimService;
+
+use axum::Router;
+use std::sync::Arc;
+
+use crate::modules::identity::AuthenticationService;
+
+/// Creates a router serving the SCIM 2.0 `/scim/v2` endpoints
+pub fn router(auth_service: Arc<AuthenticationService>, scim: Arc<ScimService>) -> Router {
+    handlers::router(ScimState {
+        auth: auth_service,
+        scim,
+    })
+}
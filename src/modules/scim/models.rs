@@ -0,0 +1,316 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{
+    modules::identity::models::{Role, User},
+    shared::{
+        error::{Error, Result},
+        types::UserId,
+    },
+};
+
+/// Schema URN of SCIM user resources
+pub const USER_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+/// Schema URN of SCIM group resources
+pub const GROUP_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+/// Schema URN of SCIM list responses
+pub const LIST_RESPONSE_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+/// Schema URN of SCIM patch requests
+pub const PATCH_OP_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:PatchOp";
+/// Schema URN of SCIM error responses
+pub const ERROR_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+
+fn default_active() -> bool {
+    true
+}
+
+/// Resource metadata
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimMeta {
+    pub resource_type: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_modified: OffsetDateTime,
+    pub location: String,
+}
+
+/// Email address of a SCIM user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScimEmail {
+    pub value: String,
+    #[serde(default)]
+    pub primary: bool,
+}
+
+/// Reference to a user or group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScimMember {
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+}
+
+/// SCIM user resource
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimUser {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub user_name: String,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    #[serde(default)]
+    pub emails: Vec<ScimEmail>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<ScimMember>,
+    /// Initial password; a random one is generated when omitted
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ScimMeta>,
+}
+
+impl From<User> for ScimUser {
+    fn from(user: User) -> Self {
+        let id = user.id.0.to_string();
+        Self {
+            schemas: vec![USER_SCHEMA.to_string()],
+            meta: Some(ScimMeta {
+                resource_type: "User".to_string(),
+                created: user.created_at,
+                last_modified: user.updated_at,
+                location: format!("/scim/v2/Users/{}", id),
+            }),
+            id: Some(id),
+            emails: vec![ScimEmail {
+                value: user.email.clone(),
+                primary: true,
+            }],
+            user_name: user.email,
+            active: user.active,
+            groups: user
+                .roles
+                .into_iter()
+                .map(|role| ScimMember {
+                    value: role.id.to_string(),
+                    display: Some(role.name),
+                })
+                .collect(),
+            password: None,
+        }
+    }
+}
+
+/// SCIM group resource, backed by a tenant role
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimGroup {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub display_name: String,
+    #[serde(default)]
+    pub members: Vec<ScimMember>,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ScimMeta>,
+}
+
+impl ScimGroup {
+    /// Creates a group resource from a role and the users holding it
+    pub fn new(role: Role, members: Vec<(UserId, String)>) -> Self {
+        let id = role.id.to_string();
+        Self {
+            schemas: vec![GROUP_SCHEMA.to_string()],
+            meta: Some(ScimMeta {
+                resource_type: "Group".to_string(),
+                // Roles do not expose timestamps; report the time of the request
+                created: OffsetDateTime::now_utc(),
+                last_modified: OffsetDateTime::now_utc(),
+                location: format!("/scim/v2/Groups/{}", id),
+            }),
+            id: Some(id),
+            display_name: role.name,
+            members: members
+                .into_iter()
+                .map(|(user_id, email)| ScimMember {
+                    value: user_id.0.to_string(),
+                    display: Some(email),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// SCIM list response
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimListResponse<T> {
+    pub schemas: Vec<String>,
+    pub total_results: i64,
+    pub start_index: i64,
+    pub items_per_page: i64,
+    #[serde(rename = "Resources")]
+    pub resources: Vec<T>,
+}
+
+impl<T> ScimListResponse<T> {
+    /// Creates a list response for a page of resources
+    pub fn new(resources: Vec<T>, total_results: i64, start_index: i64) -> Self {
+        Self {
+            schemas: vec![LIST_RESPONSE_SCHEMA.to_string()],
+            total_results,
+            start_index,
+            items_per_page: resources.len() as i64,
+            resources,
+        }
+    }
+}
+
+/// Query parameters of SCIM list requests
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimListQuery {
+    pub filter: Option<String>,
+    pub start_index: Option<i64>,
+    pub count: Option<i64>,
+}
+
+/// SCIM patch request
+#[derive(Debug, Deserialize)]
+pub struct ScimPatchRequest {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(rename = "Operations")]
+    pub operations: Vec<ScimPatchOperation>,
+}
+
+/// Single operation of a SCIM patch request
+#[derive(Debug, Deserialize)]
+pub struct ScimPatchOperation {
+    pub op: String,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub value: Option<serde_json::Value>,
+}
+
+/// Equality filter, the only filter form used by common provisioning clients
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScimFilter {
+    pub attribute: String,
+    pub value: String,
+}
+
+impl std::str::FromStr for ScimFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.trim().splitn(3, char::is_whitespace);
+        let (Some(attribute), Some(operator), Some(value)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(Error::InvalidInput(format!("Invalid filter: {}", s)));
+        };
+
+        if !operator.eq_ignore_ascii_case("eq") {
+            return Err(Error::InvalidInput(format!(
+                "Unsupported filter operator: {}",
+                operator
+            )));
+        }
+
+        let value = value
+            .trim()
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .ok_or_else(|| Error::InvalidInput(format!("Invalid filter value: {}", value)))?;
+
+        Ok(Self {
+            attribute: attribute.to_string(),
+            value: value.replace("\\\"", "\""),
+        })
+    }
+}
+
+/// SCIM error response
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimErrorResponse {
+    pub schemas: Vec<String>,
+    pub status: String,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scim_type: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{modules::identity::models::RoleType, shared::types::TenantId};
+
+    #[test]
+    fn test_filter_parsing() {
+        let filter: ScimFilter = r#"userName eq "jane@example.com""#.parse().unwrap();
+        assert_eq!(filter.attribute, "userName");
+        assert_eq!(filter.value, "jane@example.com");
+
+        let filter: ScimFilter = r#"displayName EQ "Sales Team""#.parse().unwrap();
+        assert_eq!(filter.attribute, "displayName");
+        assert_eq!(filter.value, "Sales Team");
+
+        assert!(r#"userName co "jane""#.parse::<ScimFilter>().is_err());
+        assert!("userName eq jane".parse::<ScimFilter>().is_err());
+        assert!("userName".parse::<ScimFilter>().is_err());
+    }
+
+    #[test]
+    fn test_user_conversion() {
+        let mut user = User::new(
+            TenantId::new(),
+            "jane@example.com".to_string(),
+            "hash".to_string(),
+        );
+        user.roles
+            .push(Role::new(RoleType::Custom, "Sales".to_string()));
+
+        let json = serde_json::to_value(ScimUser::from(user.clone())).unwrap();
+        assert_eq!(json["schemas"][0], USER_SCHEMA);
+        assert_eq!(json["id"], user.id.0.to_string());
+        assert_eq!(json["userName"], "jane@example.com");
+        assert_eq!(json["active"], true);
+        assert_eq!(json["groups"][0]["display"], "Sales");
+        assert_eq!(json["meta"]["resourceType"], "User");
+        assert!(json.get("password").is_none());
+    }
+
+    #[test]
+    fn test_patch_request_parsing() {
+        let request: ScimPatchRequest = serde_json::from_value(serde_json::json!({
+            "schemas": [PATCH_OP_SCHEMA],
+            "Operations": [
+                { "op": "replace", "path": "active", "value": false },
+                { "op": "remove", "path": "members[value eq \"42\"]" }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(request.operations.len(), 2);
+        assert_eq!(request.operations[0].path.as_deref(), Some("active"));
+        assert!(request.operations[1].value.is_none());
+    }
+
+    #[test]
+    fn test_list_response_serialization() {
+        let response = ScimListResponse::new(vec![1, 2], 5, 3);
+        let json = serde_json::to_value(response).unwrap();
+        assert_eq!(json["totalResults"], 5);
+        assert_eq!(json["startIndex"], 3);
+        assert_eq!(json["itemsPerPage"], 2);
+        assert_eq!(json["Resources"], serde_json::json!([1, 2]));
+    }
+}
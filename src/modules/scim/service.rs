@@ -0,0 +1,744 @@
+use rand::{distributions::Alphanumeric, Rng};
+use serde_json::Value;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    modules::{
+        identity::{
+            auth::AuthenticationService,
+            models::{Credentials, PermissionAction, PermissionRequest, Role, RoleRequest, User},
+            rbac::has_permission,
+            repository::{RoleRepository, UserRepository},
+            service::IdentityModule,
+        },
+        scim::models::{
+            ScimFilter, ScimGroup, ScimListQuery, ScimListResponse, ScimMember, ScimPatchOperation,
+            ScimPatchRequest, ScimUser,
+        },
+    },
+    shared::{
+        error::{Error, Result},
+        types::UserId,
+    },
+};
+
+/// Page size used when a client does not request one
+const DEFAULT_PAGE_SIZE: i64 = 100;
+/// Largest page a client may request
+const MAX_PAGE_SIZE: i64 = 1000;
+
+/// SCIM provisioning service mapping users and groups onto the identity module
+///
+/// Groups are backed by the roles of the actor's tenant, so group membership
+/// changes are subject to the same escalation checks as role assignments.
+#[derive(Debug)]
+pub struct ScimService {
+    repository: UserRepository,
+    role_repository: RoleRepository,
+    identity: Arc<IdentityModule>,
+    auth: Arc<AuthenticationService>,
+}
+
+impl ScimService {
+    /// Creates a new ScimService instance
+    pub fn new(
+        repository: UserRepository,
+        identity: Arc<IdentityModule>,
+        auth: Arc<AuthenticationService>,
+    ) -> Self {
+        Self {
+            role_repository: RoleRepository::new(repository.get_pool().clone()),
+            repository,
+            identity,
+            auth,
+        }
+    }
+
+    /// Lists a page of the tenant's users, optionally filtered by `userName`
+    pub async fn list_users(
+        &self,
+        actor: &User,
+        query: &ScimListQuery,
+    ) -> Result<ScimListResponse<ScimUser>> {
+        Self::ensure_permission(actor, PermissionAction::Read, "users")?;
+        let (start_index, count) = Self::page(query);
+
+        let email = match Self::parse_filter(query)? {
+            Some(filter) => match filter.attribute.to_ascii_lowercase().as_str() {
+                "username" | "emails" | "emails.value" => Some(filter.value),
+                _ => {
+                    return Err(Error::InvalidInput(format!(
+                        "Unsupported filter attribute: {}",
+                        filter.attribute
+                    )))
+                },
+            },
+            None => None,
+        };
+
+        let (users, total) = self
+            .repository
+            .list_tenant_users(actor.tenant_id, email.as_deref(), start_index - 1, count)
+            .await?;
+        Ok(ScimListResponse::new(
+            users.into_iter().map(ScimUser::from).collect(),
+            total,
+            start_index,
+        ))
+    }
+
+    /// Gets a user of the tenant
+    pub async fn get_user(&self, actor: &User, id: Uuid) -> Result<ScimUser> {
+        Self::ensure_permission(actor, PermissionAction::Read, "users")?;
+        Ok(ScimUser::from(self.get_tenant_user(actor, id).await?))
+    }
+
+    /// Provisions a new user in the tenant
+    pub async fn create_user(&self, actor: &User, request: ScimUser) -> Result<ScimUser> {
+        Self::ensure_permission(actor, PermissionAction::Create, "users")?;
+
+        let email = request.user_name.trim().to_string();
+        if !email.contains('@') {
+            return Err(Error::Validation(format!(
+                "userName must be an email address: {}",
+                email
+            )));
+        }
+        if self
+            .repository
+            .get_user_by_email(&email, actor.tenant_id)
+            .await?
+            .is_some()
+        {
+            return Err(Error::Conflict(format!("User {} already exists", email)));
+        }
+
+        // Provisioned users usually sign in through SSO and never see this password
+        let password = request.password.unwrap_or_else(|| {
+            rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(32)
+                .map(char::from)
+                .collect()
+        });
+
+        let mut user = self
+            .auth
+            .register_user(Credentials {
+                email,
+                password,
+                tenant_id: actor.tenant_id,
+                mfa_code: None,
+            })
+            .await?;
+        if !request.active {
+            user = self.auth.deactivate_user(user.id, user.tenant_id).await?;
+        }
+        Ok(ScimUser::from(user))
+    }
+
+    /// Applies a patch to a user; only `active` is stored, other attributes are ignored
+    pub async fn patch_user(
+        &self,
+        actor: &User,
+        id: Uuid,
+        request: ScimPatchRequest,
+    ) -> Result<ScimUser> {
+        Self::ensure_permission(actor, PermissionAction::Update, "users")?;
+        let user = self.get_tenant_user(actor, id).await?;
+
+        let mut active = None;
+        for operation in &request.operations {
+            match operation.op.to_ascii_lowercase().as_str() {
+                "add" | "replace" => match operation.path.as_deref() {
+                    Some(path) if path.eq_ignore_ascii_case("active") => {
+                        active = Some(Self::parse_bool(operation.value.as_ref())?);
+                    },
+                    Some(_) => {},
+                    None => {
+                        if let Some(value) = Self::object_value(operation, "active") {
+                            active = Some(Self::parse_bool(Some(value))?);
+                        }
+                    },
+                },
+                "remove" => {},
+                other => {
+                    return Err(Error::InvalidInput(format!(
+                        "Unsupported patch operation: {}",
+                        other
+                    )))
+                },
+            }
+        }
+
+        let user = match active {
+            Some(false) if user.active => {
+                self.auth.deactivate_user(user.id, actor.tenant_id).await?
+            },
+            Some(true) if !user.active => self.auth.activate_user(user.id, actor.tenant_id).await?,
+            _ => user,
+        };
+        Ok(ScimUser::from(user))
+    }
+
+    /// Deprovisions a user, revoking their sessions before removing the account
+    pub async fn delete_user(&self, actor: &User, id: Uuid) -> Result<()> {
+        Self::ensure_permission(actor, PermissionAction::Delete, "users")?;
+        let user = self.get_tenant_user(actor, id).await?;
+        self.auth.deactivate_user(user.id, actor.tenant_id).await?;
+        self.repository.delete_user(user.id, actor.tenant_id).await
+    }
+
+    /// Lists a page of the tenant's groups, optionally filtered by `displayName`
+    pub async fn list_groups(
+        &self,
+        actor: &User,
+        query: &ScimListQuery,
+    ) -> Result<ScimListResponse<ScimGroup>> {
+        Self::ensure_permission(actor, PermissionAction::Read, "users")?;
+        let (start_index, count) = Self::page(query);
+
+        let mut roles = self.role_repository.list_roles(actor.tenant_id).await?;
+        if let Some(filter) = Self::parse_filter(query)? {
+            if !filter.attribute.eq_ignore_ascii_case("displayName") {
+                return Err(Error::InvalidInput(format!(
+                    "Unsupported filter attribute: {}",
+                    filter.attribute
+                )));
+            }
+            roles.retain(|role| role.name == filter.value);
+        }
+
+        let total = roles.len() as i64;
+        let mut groups = Vec::new();
+        for role in roles
+            .into_iter()
+            .skip((start_index - 1) as usize)
+            .take(count as usize)
+        {
+            groups.push(self.to_group(actor, role).await?);
+        }
+        Ok(ScimListResponse::new(groups, total, start_index))
+    }
+
+    /// Gets a group of the tenant
+    pub async fn get_group(&self, actor: &User, id: Uuid) -> Result<ScimGroup> {
+        Self::ensure_permission(actor, PermissionAction::Read, "users")?;
+        let role = self.identity.get_role(actor.tenant_id, id).await?;
+        self.to_group(actor, role).await
+    }
+
+    /// Creates a group as a custom role without permissions and adds its members
+    pub async fn create_group(&self, actor: &User, request: ScimGroup) -> Result<ScimGroup> {
+        Self::ensure_permission(actor, PermissionAction::Update, "users")?;
+        let role = self
+            .identity
+            .create_role(
+                actor,
+                RoleRequest {
+                    name: request.display_name,
+                    permissions: Vec::new(),
+                    parent_ids: Vec::new(),
+                },
+            )
+            .await?;
+
+        for member in &request.members {
+            self.identity
+                .assign_role(actor, Self::parse_member(member)?, role.id)
+                .await?;
+        }
+        self.to_group(actor, role).await
+    }
+
+    /// Applies a patch to the name or members of a group
+    pub async fn patch_group(
+        &self,
+        actor: &User,
+        id: Uuid,
+        request: ScimPatchRequest,
+    ) -> Result<ScimGroup> {
+        Self::ensure_permission(actor, PermissionAction::Update, "users")?;
+        let mut role = self.identity.get_role(actor.tenant_id, id).await?;
+
+        for operation in &request.operations {
+            let op = operation.op.to_ascii_lowercase();
+            let path = operation.path.as_deref().map(str::trim);
+
+            match (op.as_str(), path) {
+                ("add", Some(path)) if path.eq_ignore_ascii_case("members") => {
+                    for member in Self::parse_members(operation.value.as_ref())? {
+                        self.identity.assign_role(actor, member, role.id).await?;
+                    }
+                },
+                ("remove", Some(path)) if path.eq_ignore_ascii_case("members") => {
+                    let members = match operation.value {
+                        Some(_) => Self::parse_members(operation.value.as_ref())?,
+                        None => self.member_ids(actor, role.id).await?,
+                    };
+                    for member in members {
+                        self.remove_member(actor, member, role.id).await?;
+                    }
+                },
+                ("remove", Some(path)) if path.to_ascii_lowercase().starts_with("members[") => {
+                    let filter: ScimFilter =
+                        path["members[".len()..].trim_end_matches(']').parse()?;
+                    let member = Self::parse_id(&filter.value)?;
+                    self.remove_member(actor, UserId(member), role.id).await?;
+                },
+                ("replace", Some(path)) if path.eq_ignore_ascii_case("members") => {
+                    let members = Self::parse_members(operation.value.as_ref())?;
+                    self.replace_members(actor, role.id, members).await?;
+                },
+                ("replace", Some(path)) if path.eq_ignore_ascii_case("displayName") => {
+                    let name = operation
+                        .value
+                        .as_ref()
+                        .and_then(Value::as_str)
+                        .ok_or_else(|| {
+                            Error::InvalidInput("displayName must be a string".to_string())
+                        })?;
+                    role = self.rename_group(actor, role, name).await?;
+                },
+                ("add" | "replace", None) => {
+                    if let Some(name) = Self::object_value(operation, "displayName") {
+                        let name = name.as_str().ok_or_else(|| {
+                            Error::InvalidInput("displayName must be a string".to_string())
+                        })?;
+                        role = self.rename_group(actor, role, name).await?;
+                    }
+                    if let Some(members) = Self::object_value(operation, "members") {
+                        let members = Self::parse_members(Some(members))?;
+                        if op == "add" {
+                            for member in members {
+                                self.identity.assign_role(actor, member, role.id).await?;
+                            }
+                        } else {
+                            self.replace_members(actor, role.id, members).await?;
+                        }
+                    }
+                },
+                _ => {
+                    return Err(Error::InvalidInput(format!(
+                        "Unsupported patch operation: {} {}",
+                        operation.op,
+                        path.unwrap_or_default()
+                    )))
+                },
+            }
+        }
+
+        self.to_group(actor, role).await
+    }
+
+    /// Deletes a group and its role
+    pub async fn delete_group(&self, actor: &User, id: Uuid) -> Result<()> {
+        Self::ensure_permission(actor, PermissionAction::Update, "users")?;
+        self.identity.delete_role(actor, id).await
+    }
+
+    /// Loads a user of the actor's tenant
+    async fn get_tenant_user(&self, actor: &User, id: Uuid) -> Result<User> {
+        self.repository
+            .get_user_by_id(UserId(id))
+            .await?
+            .filter(|user| user.tenant_id == actor.tenant_id)
+            .ok_or_else(|| Error::NotFound("User not found".to_string()))
+    }
+
+    /// Builds the group resource of a role
+    async fn to_group(&self, actor: &User, role: Role) -> Result<ScimGroup> {
+        let members = self
+            .role_repository
+            .list_role_members(role.id, actor.tenant_id)
+            .await?;
+        Ok(ScimGroup::new(role, members))
+    }
+
+    /// Lists the IDs of the users holding a role
+    async fn member_ids(&self, actor: &User, role_id: Uuid) -> Result<Vec<UserId>> {
+        Ok(self
+            .role_repository
+            .list_role_members(role_id, actor.tenant_id)
+            .await?
+            .into_iter()
+            .map(|(user_id, _)| user_id)
+            .collect())
+    }
+
+    /// Removes a member from a group, ignoring users that are not members
+    async fn remove_member(&self, actor: &User, user_id: UserId, role_id: Uuid) -> Result<()> {
+        match self.identity.revoke_role(actor, user_id, role_id).await {
+            Ok(_) | Err(Error::NotFound(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Replaces the members of a group
+    async fn replace_members(
+        &self,
+        actor: &User,
+        role_id: Uuid,
+        members: Vec<UserId>,
+    ) -> Result<()> {
+        for current in self.member_ids(actor, role_id).await? {
+            if !members.contains(&current) {
+                self.remove_member(actor, current, role_id).await?;
+            }
+        }
+        for member in members {
+            self.identity.assign_role(actor, member, role_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Renames the role backing a group, keeping its permissions
+    async fn rename_group(&self, actor: &User, role: Role, name: &str) -> Result<Role> {
+        if role.name == name {
+            return Ok(role);
+        }
+        self.identity
+            .update_role(
+                actor,
+                role.id,
+                RoleRequest {
+                    name: name.to_string(),
+                    permissions: role
+                        .permissions
+                        .into_iter()
+                        .map(|p| PermissionRequest {
+                            name: p.name,
+                            action: p.action,
+                            resource: p.resource,
+                            scope: p.scope,
+                            effect: p.effect,
+                        })
+                        .collect(),
+                    parent_ids: role.parent_ids,
+                },
+            )
+            .await
+    }
+
+    /// Ensures the actor holds a permission
+    fn ensure_permission(actor: &User, action: PermissionAction, resource: &str) -> Result<()> {
+        if !has_permission(actor, action, resource) {
+            return Err(Error::Authorization(format!(
+                "Missing permission {} on {}",
+                action, resource
+            )));
+        }
+        Ok(())
+    }
+
+    /// Resolves the 1-based start index and page size of a list request
+    fn page(query: &ScimListQuery) -> (i64, i64) {
+        (
+            query.start_index.unwrap_or(1).max(1),
+            query
+                .count
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(0, MAX_PAGE_SIZE),
+        )
+    }
+
+    /// Parses the filter of a list request
+    fn parse_filter(query: &ScimListQuery) -> Result<Option<ScimFilter>> {
+        query.filter.as_deref().map(str::parse).transpose()
+    }
+
+    /// Gets an attribute of an operation whose value is an object of attributes
+    fn object_value<'a>(operation: &'a ScimPatchOperation, attribute: &str) -> Option<&'a Value> {
+        operation
+            .value
+            .as_ref()
+            .and_then(Value::as_object)?
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(attribute))
+            .map(|(_, value)| value)
+    }
+
+    /// Parses a boolean, accepting the string form some clients send
+    fn parse_bool(value: Option<&Value>) -> Result<bool> {
+        match value {
+            Some(Value::Bool(value)) => Ok(*value),
+            Some(Value::String(value)) => value
+                .to_ascii_lowercase()
+                .parse()
+                .map_err(|_| Error::InvalidInput(format!("Invalid boolean: {}", value))),
+            _ => Err(Error::InvalidInput("Expected a boolean value".to_string())),
+        }
+    }
+
+    /// Parses a list of member references
+    fn parse_members(value: Option<&Value>) -> Result<Vec<UserId>> {
+        let members: Vec<ScimMember> = match value {
+            Some(Value::Array(_)) => serde_json::from_value(value.cloned().unwrap_or_default())
+                .map_err(|e| Error::InvalidInput(format!("Invalid members: {}", e)))?,
+            _ => {
+                return Err(Error::InvalidInput(
+                    "Expected a list of members".to_string(),
+                ))
+            },
+        };
+        members.iter().map(Self::parse_member).collect()
+    }
+
+    /// Parses a member reference
+    fn parse_member(member: &ScimMember) -> Result<UserId> {
+        Ok(UserId(Self::parse_id(&member.value)?))
+    }
+
+    /// Parses a resource ID
+    fn parse_id(id: &str) -> Result<Uuid> {
+        Uuid::parse_str(id).map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::database::{tests::create_test_db, Database},
+        modules::{
+            identity::{
+                rbac::create_admin_role,
+                session::{Session, SessionStore},
+            },
+            scim::models::PATCH_OP_SCHEMA,
+            tenant::models::Tenant,
+        },
+        shared::types::TenantId,
+    };
+
+    #[derive(Debug, Default)]
+    struct NoopSessionStore;
+
+    #[async_trait::async_trait]
+    impl SessionStore for NoopSessionStore {
+        async fn store_session(&self, _session: &Session) -> Result<()> {
+            Ok(())
+        }
+
+        async fn get_session(&self, _id: Uuid) -> Result<Option<Session>> {
+            Ok(None)
+        }
+
+        async fn get_session_by_token(&self, _token: &str) -> Result<Option<Session>> {
+            Ok(None)
+        }
+
+        async fn get_user_sessions(
+            &self,
+            _tenant_id: TenantId,
+            _user_id: UserId,
+        ) -> Result<Vec<Session>> {
+            Ok(Vec::new())
+        }
+
+        async fn get_tenant_sessions(&self, _tenant_id: TenantId) -> Result<Vec<Session>> {
+            Ok(Vec::new())
+        }
+
+        async fn remove_session(&self, _id: Uuid) -> Result<()> {
+            Ok(())
+        }
+
+        async fn remove_user_sessions(&self, _tenant_id: TenantId, _user_id: UserId) -> Result<()> {
+            Ok(())
+        }
+
+        async fn remove_tenant_sessions(&self, _tenant_id: TenantId) -> Result<()> {
+            Ok(())
+        }
+
+        async fn get_token_version(&self, _tenant_id: TenantId, _user_id: UserId) -> Result<u64> {
+            Ok(0)
+        }
+
+        async fn increment_token_version(
+            &self,
+            _tenant_id: TenantId,
+            _user_id: UserId,
+        ) -> Result<u64> {
+            Ok(1)
+        }
+    }
+
+    async fn setup(db: &Database) -> (ScimService, User) {
+        let tenant = Tenant::new(
+            "Test Tenant".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        sqlx::query!(
+            r#"INSERT INTO tenants (id, name, domain, active) VALUES ($1, $2, $3, $4)"#,
+            tenant.id.0 as uuid::Uuid,
+            tenant.name,
+            tenant.domain,
+            tenant.active
+        )
+        .execute(&db.get_pool())
+        .await
+        .unwrap();
+
+        let repository = UserRepository::new(db.get_pool());
+        let mut admin = User::new(
+            tenant.id,
+            "admin@example.com".to_string(),
+            "hash".to_string(),
+        );
+        admin.roles.push(create_admin_role());
+        let admin = repository.create_user(admin).await.unwrap();
+
+        let identity = Arc::new(IdentityModule::new(repository.clone()));
+        let auth = Arc::new(AuthenticationService::new(
+            repository.clone(),
+            Box::new(NoopSessionStore),
+        ));
+        (ScimService::new(repository, identity, auth), admin)
+    }
+
+    fn patch(operations: Value) -> ScimPatchRequest {
+        serde_json::from_value(serde_json::json!({
+            "schemas": [PATCH_OP_SCHEMA],
+            "Operations": operations,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_scim_users() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let (service, admin) = setup(&db).await;
+
+        let request: ScimUser = serde_json::from_value(serde_json::json!({
+            "schemas": [crate::modules::scim::models::USER_SCHEMA],
+            "userName": "jane@example.com",
+            "active": true
+        }))
+        .unwrap();
+        let created = service.create_user(&admin, request.clone()).await.unwrap();
+        let id = Uuid::parse_str(created.id.as_deref().unwrap()).unwrap();
+
+        // Duplicates are reported as conflicts
+        let result = service.create_user(&admin, request).await;
+        assert!(matches!(result, Err(Error::Conflict(_))));
+
+        // Filter by userName
+        let query = ScimListQuery {
+            filter: Some(r#"userName eq "JANE@example.com""#.to_string()),
+            ..Default::default()
+        };
+        let list = service.list_users(&admin, &query).await.unwrap();
+        assert_eq!(list.total_results, 1);
+        assert_eq!(list.resources[0].user_name, "jane@example.com");
+
+        // Pagination
+        let query = ScimListQuery {
+            start_index: Some(2),
+            count: Some(1),
+            ..Default::default()
+        };
+        let list = service.list_users(&admin, &query).await.unwrap();
+        assert_eq!(list.total_results, 2);
+        assert_eq!(list.start_index, 2);
+        assert_eq!(list.resources.len(), 1);
+
+        // Deactivation through PATCH, including the string form sent by Azure AD
+        let user = service
+            .patch_user(
+                &admin,
+                id,
+                patch(serde_json::json!([{ "op": "Replace", "path": "active", "value": "False" }])),
+            )
+            .await
+            .unwrap();
+        assert!(!user.active);
+
+        let user = service
+            .patch_user(
+                &admin,
+                id,
+                patch(serde_json::json!([{ "op": "replace", "value": { "active": true } }])),
+            )
+            .await
+            .unwrap();
+        assert!(user.active);
+
+        // Deprovisioning
+        service.delete_user(&admin, id).await.unwrap();
+        let result = service.get_user(&admin, id).await;
+        assert!(matches!(result, Err(Error::NotFound(_))));
+
+        // Users without user management permissions are rejected
+        let other = User::new(
+            TenantId::new(),
+            "other@example.com".to_string(),
+            "hash".to_string(),
+        );
+        let result = service.list_users(&other, &ScimListQuery::default()).await;
+        assert!(matches!(result, Err(Error::Authorization(_))));
+    }
+
+    #[tokio::test]
+    async fn test_scim_groups() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let (service, admin) = setup(&db).await;
+
+        let request: ScimUser = serde_json::from_value(serde_json::json!({
+            "userName": "jane@example.com"
+        }))
+        .unwrap();
+        let jane = service.create_user(&admin, request).await.unwrap();
+        let jane_id = jane.id.clone().unwrap();
+
+        let request: ScimGroup = serde_json::from_value(serde_json::json!({
+            "displayName": "Sales",
+            "members": [{ "value": jane_id }]
+        }))
+        .unwrap();
+        let group = service.create_group(&admin, request).await.unwrap();
+        let group_id = Uuid::parse_str(group.id.as_deref().unwrap()).unwrap();
+        assert_eq!(group.members.len(), 1);
+
+        let query = ScimListQuery {
+            filter: Some(r#"displayName eq "Sales""#.to_string()),
+            ..Default::default()
+        };
+        let list = service.list_groups(&admin, &query).await.unwrap();
+        assert_eq!(list.total_results, 1);
+
+        // Rename and remove the member
+        let group = service
+            .patch_group(
+                &admin,
+                group_id,
+                patch(serde_json::json!([
+                    { "op": "replace", "path": "displayName", "value": "Sales EMEA" },
+                    { "op": "remove", "path": format!("members[value eq \"{}\"]", jane_id) }
+                ])),
+            )
+            .await
+            .unwrap();
+        assert_eq!(group.display_name, "Sales EMEA");
+        assert!(group.members.is_empty());
+
+        // Add the member back
+        let group = service
+            .patch_group(
+                &admin,
+                group_id,
+                patch(serde_json::json!([
+                    { "op": "add", "path": "members", "value": [{ "value": jane_id }] }
+                ])),
+            )
+            .await
+            .unwrap();
+        assert_eq!(group.members.len(), 1);
+
+        service.delete_group(&admin, group_id).await.unwrap();
+        let result = service.get_group(&admin, group_id).await;
+        assert!(matches!(result, Err(Error::NotFound(_))));
+    }
+}
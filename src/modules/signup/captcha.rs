@@ -0,0 +1,69 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::shared::error::{Error, Result};
+
+/// Extension point for verifying CAPTCHA tokens submitted with public forms
+#[async_trait]
+pub trait CaptchaVerifier: Send + Sync + std::fmt::Debug {
+    /// Checks if a token was issued for a solved challenge
+    async fn verify(&self, token: &str, remote_ip: Option<&str>) -> Result<bool>;
+}
+
+/// Answer of a siteverify endpoint
+#[derive(Debug, Deserialize)]
+struct SiteverifyResponse {
+    success: bool,
+}
+
+/// Verifier for providers implementing the siteverify protocol (hCaptcha, reCAPTCHA, Turnstile)
+#[derive(Debug, Clone)]
+pub struct HttpCaptchaVerifier {
+    url: String,
+    secret: String,
+    client: reqwest::Client,
+}
+
+impl HttpCaptchaVerifier {
+    /// Creates a new verifier for the given siteverify endpoint
+    pub fn new(url: String, secret: String) -> Self {
+        Self {
+            url,
+            secret,
+            client: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl CaptchaVerifier for HttpCaptchaVerifier {
+    async fn verify(&self, token: &str, remote_ip: Option<&str>) -> Result<bool> {
+        let mut form = vec![("secret", self.secret.as_str()), ("response", token)];
+        if let Some(remote_ip) = remote_ip {
+            form.push(("remoteip", remote_ip));
+        }
+
+        let response = self
+            .client
+            .post(&self.url)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to verify CAPTCHA: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(Error::Internal(format!(
+                "CAPTCHA provider responded with status {}",
+                response.status()
+            )));
+        }
+
+        let result: SiteverifyResponse = response
+            .json()
+            .await
+            .map_err(|e| Error::Internal(format!("Invalid CAPTCHA response: {}", e)))?;
+        Ok(result.success)
+    }
+}
@@ -0,0 +1,148 @@
+use axum::{
+    extract::{FromRef, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    modules::{
+        identity::{auth::AuthenticationService, handlers::AuthenticatedUser},
+        signup::{
+            models::{ApprovalQuery, ReviewRequest, SignupRequest, VerifySignupRequest},
+            service::SignupService,
+        },
+        tenant::models::TenantResponse,
+    },
+    shared::{
+        error::{Error, Result},
+        types::TenantId,
+    },
+};
+
+/// Shared state of the signup handlers
+#[derive(Clone, FromRef)]
+pub struct SignupState {
+    pub auth: Arc<AuthenticationService>,
+    pub signup: Arc<SignupService>,
+}
+
+/// Parses a tenant ID from the request path
+fn parse_tenant_id(tenant_id: &str) -> Result<TenantId> {
+    Uuid::parse_str(tenant_id)
+        .map(TenantId)
+        .map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))
+}
+
+/// Gets the key rate limits are applied to, the client IP reported by the proxy
+fn client_key(headers: &HeaderMap) -> String {
+    headers
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .or_else(|| {
+            headers
+                .get("x-real-ip")
+                .and_then(|value| value.to_str().ok())
+        })
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Signs up a new tenant with its first admin
+pub async fn signup(
+    State(signup): State<Arc<SignupService>>,
+    headers: HeaderMap,
+    Json(request): Json<SignupRequest>,
+) -> Result<impl IntoResponse> {
+    let response = signup.signup(request, &client_key(&headers)).await?;
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
+
+/// Verifies the email address of a signup and activates its tenant
+pub async fn verify_signup(
+    State(signup): State<Arc<SignupService>>,
+    Json(request): Json<VerifySignupRequest>,
+) -> Result<impl IntoResponse> {
+    let tenant = signup.verify(&request.token).await?;
+    Ok((StatusCode::OK, Json(TenantResponse::from(tenant))))
+}
+
+/// Lists the approval queue of self-signed-up tenants
+pub async fn list_approvals(
+    State(signup): State<Arc<SignupService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Query(query): Query<ApprovalQuery>,
+) -> Result<impl IntoResponse> {
+    let approvals = signup.list_approvals(&actor, query.status).await?;
+    Ok((StatusCode::OK, Json(approvals)))
+}
+
+/// Lists the issuance and completion counters of signup verifications per tenant
+pub async fn list_funnel(
+    State(signup): State<Arc<SignupService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    let funnel = signup.list_funnel(&actor).await?;
+    Ok((StatusCode::OK, Json(funnel)))
+}
+
+/// Approves a pending tenant
+pub async fn approve_tenant(
+    State(signup): State<Arc<SignupService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(tenant_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let approval = signup.approve(&actor, parse_tenant_id(&tenant_id)?).await?;
+    Ok((StatusCode::OK, Json(approval)))
+}
+
+/// Rejects a pending tenant
+pub async fn reject_tenant(
+    State(signup): State<Arc<SignupService>>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(tenant_id): Path<String>,
+    Json(request): Json<ReviewRequest>,
+) -> Result<impl IntoResponse> {
+    let approval = signup
+        .reject(&actor, parse_tenant_id(&tenant_id)?, request.reason)
+        .await?;
+    Ok((StatusCode::OK, Json(approval)))
+}
+
+/// Creates the signup module router
+pub fn router(state: SignupState) -> Router {
+    Router::new()
+        .route("/signup", post(signup))
+        .route("/signup/verify", post(verify_signup))
+        .route("/signup/approvals", get(list_approvals))
+        .route("/signup/funnel", get(list_funnel))
+        .route("/signup/approvals/:tenant_id/approve", post(approve_tenant))
+        .route("/signup/approvals/:tenant_id/reject", post(reject_tenant))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_client_key() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(client_key(&headers), "unknown");
+
+        headers.insert("x-real-ip", HeaderValue::from_static("10.0.0.2"));
+        assert_eq!(client_key(&headers), "10.0.0.2");
+
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("10.0.0.1, 192.168.0.1"),
+        );
+        assert_eq!(client_key(&headers), "10.0.0.1");
+    }
+}
@@ -0,0 +1,21 @@
+pub mod captcha;
+mod handlers;
+pub mod models;
+pub mod repository;
+pub mod service;
+
+pub use handlers::SignupState;
+pub use service::SignupService;
+
+use axum::Router;
+use std::sync::Arc;
+
+use crate::modules::identity::AuthenticationService;
+
+/// Creates a router for public self-signup and the approval queue; mount it only when the signup module is enabled
+pub fn router(auth_service: Arc<AuthenticationService>, signup: Arc<SignupService>) -> Router {
+    handlers::router(SignupState {
+        auth: auth_service,
+        signup,
+    })
+}
@@ -0,0 +1,196 @@
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    modules::tenant::models::Tenant,
+    shared::{
+        error::Error,
+        types::{TenantId, UserId},
+    },
+};
+
+/// Public signup creating a tenant together with its first admin
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignupRequest {
+    pub tenant_name: String,
+    pub domain: String,
+    pub email: String,
+    pub password: String,
+    pub captcha_token: Option<String>,
+}
+
+/// Signup awaiting email verification
+#[derive(Debug, Clone, Serialize)]
+pub struct SignupResponse {
+    pub tenant_id: TenantId,
+    pub email: String,
+    pub expires_at: OffsetDateTime,
+}
+
+/// Verification of the email address of a signup
+#[derive(Debug, Clone, Deserialize)]
+pub struct VerifySignupRequest {
+    pub token: String,
+}
+
+/// Pending verification of a signup; only the hash of the token is stored
+#[derive(Debug, Clone)]
+pub struct SignupVerification {
+    pub id: Uuid,
+    pub tenant_id: TenantId,
+    pub user_id: UserId,
+    pub token_hash: String,
+    pub expires_at: OffsetDateTime,
+    pub created_at: OffsetDateTime,
+}
+
+impl SignupVerification {
+    /// Creates a new verification expiring after the given time
+    pub fn new(tenant_id: TenantId, user_id: UserId, token: &str, ttl: Duration) -> Self {
+        let now = OffsetDateTime::now_utc();
+        Self {
+            id: Uuid::new_v4(),
+            tenant_id,
+            user_id,
+            token_hash: hash_token(token),
+            expires_at: now + ttl,
+            created_at: now,
+        }
+    }
+
+    /// Checks if the verification expired
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= OffsetDateTime::now_utc()
+    }
+}
+
+/// Review status of a self-signed-up tenant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalStatus {
+    PendingApproval,
+    Approved,
+    Rejected,
+}
+
+impl std::fmt::Display for ApprovalStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApprovalStatus::PendingApproval => write!(f, "pending_approval"),
+            ApprovalStatus::Approved => write!(f, "approved"),
+            ApprovalStatus::Rejected => write!(f, "rejected"),
+        }
+    }
+}
+
+impl std::str::FromStr for ApprovalStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending_approval" => Ok(ApprovalStatus::PendingApproval),
+            "approved" => Ok(ApprovalStatus::Approved),
+            "rejected" => Ok(ApprovalStatus::Rejected),
+            _ => Err(Error::InvalidInput(format!(
+                "Invalid approval status: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Entry of the approval queue of self-signed-up tenants
+#[derive(Debug, Clone, Serialize)]
+pub struct TenantApproval {
+    pub id: Uuid,
+    pub tenant_id: TenantId,
+    pub tenant_name: String,
+    pub domain: String,
+    pub user_id: UserId,
+    pub email: String,
+    pub status: ApprovalStatus,
+    pub reason: Option<String>,
+    pub reviewed_by: Option<UserId>,
+    pub reviewed_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+impl TenantApproval {
+    /// Creates a new approval awaiting review
+    pub fn new(tenant: &Tenant, user_id: UserId, email: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            tenant_id: tenant.id,
+            tenant_name: tenant.name.clone(),
+            domain: tenant.domain.clone(),
+            user_id,
+            email,
+            status: ApprovalStatus::PendingApproval,
+            reason: None,
+            reviewed_by: None,
+            reviewed_at: None,
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+}
+
+/// Query parameters of the approval queue
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ApprovalQuery {
+    pub status: Option<ApprovalStatus>,
+}
+
+/// Decision on a pending approval
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ReviewRequest {
+    pub reason: Option<String>,
+}
+
+/// Computes the hex encoded SHA-256 hash of a verification token
+pub fn hash_token(token: &str) -> String {
+    digest::digest(&digest::SHA256, token.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signup_verification() {
+        let verification =
+            SignupVerification::new(TenantId::new(), UserId::new(), "token", Duration::hours(24));
+        assert!(!verification.is_expired());
+        assert_eq!(verification.token_hash, hash_token("token"));
+        assert_eq!(verification.token_hash.len(), 64);
+        assert_ne!(verification.token_hash, hash_token("other"));
+
+        let expired =
+            SignupVerification::new(TenantId::new(), UserId::new(), "token", Duration::hours(-1));
+        assert!(expired.is_expired());
+    }
+
+    #[test]
+    fn test_approval_status_parsing() {
+        for status in [
+            ApprovalStatus::PendingApproval,
+            ApprovalStatus::Approved,
+            ApprovalStatus::Rejected,
+        ] {
+            assert_eq!(
+                status.to_string().parse::<ApprovalStatus>().unwrap(),
+                status
+            );
+        }
+        assert!("pending".parse::<ApprovalStatus>().is_err());
+
+        let query: ApprovalQuery =
+            serde_json::from_value(serde_json::json!({ "status": "pending_approval" })).unwrap();
+        assert_eq!(query.status, Some(ApprovalStatus::PendingApproval));
+    }
+}
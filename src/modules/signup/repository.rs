@@ -0,0 +1,289 @@
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    modules::signup::models::{ApprovalStatus, SignupVerification, TenantApproval},
+    shared::{
+        error::Result,
+        types::{TenantId, UserId},
+    },
+};
+
+/// Signup repository for pending email verifications
+#[derive(Debug, Clone)]
+pub struct SignupRepository {
+    pool: Pool<Postgres>,
+}
+
+impl SignupRepository {
+    /// Creates a new SignupRepository instance
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    /// Creates a pending verification
+    pub async fn create_verification(&self, verification: &SignupVerification) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO signup_verifications (id, tenant_id, user_id, token_hash, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            verification.id,
+            verification.tenant_id.0 as uuid::Uuid,
+            verification.user_id.0 as uuid::Uuid,
+            verification.token_hash,
+            verification.expires_at,
+            verification.created_at,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Gets a pending verification by the hash of its token
+    pub async fn get_verification(&self, token_hash: &str) -> Result<Option<SignupVerification>> {
+        let result = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, user_id, token_hash, expires_at, created_at
+            FROM signup_verifications
+            WHERE token_hash = $1
+            "#,
+            token_hash,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(|r| SignupVerification {
+            id: r.id,
+            tenant_id: TenantId(r.tenant_id),
+            user_id: UserId(r.user_id),
+            token_hash: r.token_hash,
+            expires_at: r.expires_at,
+            created_at: r.created_at,
+        }))
+    }
+
+    /// Lists the tenant and user IDs of expired verifications
+    pub async fn list_expired(&self, limit: i64) -> Result<Vec<(TenantId, UserId)>> {
+        let results = sqlx::query!(
+            r#"
+            SELECT tenant_id, user_id
+            FROM signup_verifications
+            WHERE expires_at <= NOW()
+            ORDER BY expires_at
+            LIMIT $1
+            "#,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| (TenantId(r.tenant_id), UserId(r.user_id)))
+            .collect())
+    }
+
+    /// Activates the admin of a signup and marks the admin's email as verified
+    ///
+    /// The tenant is activated as well unless an approval is given, which is
+    /// queued instead. Returns false if the verification was already used.
+    pub async fn complete_verification(
+        &self,
+        verification: &SignupVerification,
+        approval: Option<&TenantApproval>,
+    ) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let claimed = sqlx::query!(
+            r#"
+            DELETE FROM signup_verifications
+            WHERE id = $1
+            "#,
+            verification.id,
+        )
+        .execute(&mut *tx)
+        .await?;
+        if claimed.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        match approval {
+            Some(approval) => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO tenant_approvals (id, tenant_id, user_id, status, created_at)
+                    VALUES ($1, $2, $3, $4, $5)
+                    "#,
+                    approval.id,
+                    approval.tenant_id.0 as uuid::Uuid,
+                    approval.user_id.0 as uuid::Uuid,
+                    approval.status.to_string(),
+                    approval.created_at,
+                )
+                .execute(&mut *tx)
+                .await?;
+            },
+            None => {
+                sqlx::query!(
+                    r#"
+                    UPDATE tenants
+                    SET active = TRUE, updated_at = NOW()
+                    WHERE id = $1
+                    "#,
+                    verification.tenant_id.0 as uuid::Uuid,
+                )
+                .execute(&mut *tx)
+                .await?;
+            },
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET active = TRUE, updated_at = NOW()
+            WHERE id = $1 AND tenant_id = $2
+            "#,
+            verification.user_id.0 as uuid::Uuid,
+            verification.tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE user_emails
+            SET verified = TRUE, verified_at = NOW()
+            WHERE user_id = $1 AND tenant_id = $2 AND is_primary
+            "#,
+            verification.user_id.0 as uuid::Uuid,
+            verification.tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Lists the approvals of self-signed-up tenants, optionally only those with a status
+    pub async fn list_approvals(
+        &self,
+        status: Option<ApprovalStatus>,
+    ) -> Result<Vec<TenantApproval>> {
+        let results = sqlx::query!(
+            r#"
+            SELECT a.id, a.tenant_id, t.name AS tenant_name, t.domain, a.user_id, u.email,
+                   a.status, a.reason, a.reviewed_by, a.reviewed_at, a.created_at
+            FROM tenant_approvals a
+            JOIN tenants t ON t.id = a.tenant_id
+            JOIN users u ON u.id = a.user_id
+            WHERE ($1::text IS NULL OR a.status = $1)
+            ORDER BY a.created_at
+            "#,
+            status.map(|status| status.to_string()),
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        results
+            .into_iter()
+            .map(|r| {
+                Ok(TenantApproval {
+                    id: r.id,
+                    tenant_id: TenantId(r.tenant_id),
+                    tenant_name: r.tenant_name,
+                    domain: r.domain,
+                    user_id: UserId(r.user_id),
+                    email: r.email,
+                    status: r.status.parse()?,
+                    reason: r.reason,
+                    reviewed_by: r.reviewed_by.map(UserId),
+                    reviewed_at: r.reviewed_at,
+                    created_at: r.created_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Gets the approval of a tenant
+    pub async fn get_approval(&self, tenant_id: TenantId) -> Result<Option<TenantApproval>> {
+        let result = sqlx::query!(
+            r#"
+            SELECT a.id, a.tenant_id, t.name AS tenant_name, t.domain, a.user_id, u.email,
+                   a.status, a.reason, a.reviewed_by, a.reviewed_at, a.created_at
+            FROM tenant_approvals a
+            JOIN tenants t ON t.id = a.tenant_id
+            JOIN users u ON u.id = a.user_id
+            WHERE a.tenant_id = $1
+            "#,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        result
+            .map(|r| {
+                Ok(TenantApproval {
+                    id: r.id,
+                    tenant_id: TenantId(r.tenant_id),
+                    tenant_name: r.tenant_name,
+                    domain: r.domain,
+                    user_id: UserId(r.user_id),
+                    email: r.email,
+                    status: r.status.parse()?,
+                    reason: r.reason,
+                    reviewed_by: r.reviewed_by.map(UserId),
+                    reviewed_at: r.reviewed_at,
+                    created_at: r.created_at,
+                })
+            })
+            .transpose()
+    }
+
+    /// Decides on a pending approval, activating the tenant when it was approved
+    ///
+    /// Returns false if the approval was not pending.
+    pub async fn review_approval(
+        &self,
+        tenant_id: TenantId,
+        status: ApprovalStatus,
+        reviewed_by: UserId,
+        reason: Option<&str>,
+    ) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let reviewed = sqlx::query!(
+            r#"
+            UPDATE tenant_approvals
+            SET status = $1, reason = $2, reviewed_by = $3, reviewed_at = NOW()
+            WHERE tenant_id = $4 AND status = $5
+            "#,
+            status.to_string(),
+            reason,
+            reviewed_by.0 as uuid::Uuid,
+            tenant_id.0 as uuid::Uuid,
+            ApprovalStatus::PendingApproval.to_string(),
+        )
+        .execute(&mut *tx)
+        .await?;
+        if reviewed.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        if status == ApprovalStatus::Approved {
+            sqlx::query!(
+                r#"
+                UPDATE tenants
+                SET active = TRUE, updated_at = NOW()
+                WHERE id = $1
+                "#,
+                tenant_id.0 as uuid::Uuid,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+}
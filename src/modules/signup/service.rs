@@ -0,0 +1,746 @@
+use rand::{distributions::Alphanumeric, Rng};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::{
+    core::config::SignupConfig,
+    modules::{
+        identity::{
+            auth::AuthenticationService,
+            models::{RoleType, User, UserEmail},
+            rbac::create_admin_role,
+            repository::UserRepository,
+        },
+        signup::{
+            captcha::{CaptchaVerifier, HttpCaptchaVerifier},
+            models::{
+                hash_token, ApprovalStatus, SignupRequest, SignupResponse, SignupVerification,
+                TenantApproval,
+            },
+            repository::SignupRepository,
+        },
+        tenant::{models::Tenant, service::TenantService},
+    },
+    shared::{
+        error::{Error, Result},
+        mail::{EmailMessage, LogMailer, Mailer},
+        rate_limit::RateLimiter,
+        token_funnel::{TokenFlow, TokenFunnel, TokenFunnelRepository, TokenOutcome},
+        types::{TenantId, UserId},
+    },
+};
+
+/// Number of expired signups removed per signup attempt
+pub const EXPIRED_SIGNUP_BATCH_SIZE: i64 = 10;
+
+/// Minimum length of the admin password chosen at signup
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Public self-signup creating a tenant together with its first admin
+///
+/// Tenant and admin stay inactive until the admin verified their email
+/// address; unverified signups are removed once their verification expired.
+/// When approval is required, verified tenants are queued for review by a
+/// super admin instead of being activated.
+#[derive(Debug, Clone)]
+pub struct SignupService {
+    repository: SignupRepository,
+    funnel: TokenFunnelRepository,
+    tenants: TenantService,
+    users: UserRepository,
+    captcha: Option<Arc<dyn CaptchaVerifier>>,
+    mailer: Arc<dyn Mailer>,
+    limiter: RateLimiter,
+    verification_ttl: time::Duration,
+    verification_url: String,
+    require_approval: bool,
+    approval_notify_email: Option<String>,
+}
+
+impl SignupService {
+    /// Creates a new SignupService instance
+    pub fn new(tenants: TenantService, users: UserRepository, config: &SignupConfig) -> Self {
+        let captcha = config.captcha_secret.as_ref().map(|secret| {
+            Arc::new(HttpCaptchaVerifier::new(
+                config.captcha_verify_url.clone(),
+                secret.clone(),
+            )) as Arc<dyn CaptchaVerifier>
+        });
+        Self {
+            repository: SignupRepository::new(users.get_pool().clone()),
+            funnel: TokenFunnelRepository::new(users.get_pool().clone()),
+            tenants,
+            users,
+            captcha,
+            mailer: Arc::new(LogMailer),
+            limiter: RateLimiter::new(
+                config.max_attempts,
+                std::time::Duration::from_secs(config.window_secs),
+            ),
+            verification_ttl: time::Duration::hours(config.verification_ttl_hours),
+            verification_url: config.verification_url.clone(),
+            require_approval: config.require_approval,
+            approval_notify_email: config.approval_notify_email.clone(),
+        }
+    }
+
+    /// Uses the given verifier for CAPTCHA tokens
+    pub fn with_captcha_verifier(mut self, captcha: Arc<dyn CaptchaVerifier>) -> Self {
+        self.captcha = Some(captcha);
+        self
+    }
+
+    /// Uses the given mailer for verification emails
+    pub fn with_mailer(mut self, mailer: Arc<dyn Mailer>) -> Self {
+        self.mailer = mailer;
+        self
+    }
+
+    /// Creates an inactive tenant and admin and sends the verification email
+    pub async fn signup(&self, request: SignupRequest, client: &str) -> Result<SignupResponse> {
+        self.limiter.check(client)?;
+        validate_request(&request)?;
+
+        if let Some(captcha) = &self.captcha {
+            let token = request
+                .captcha_token
+                .as_deref()
+                .ok_or_else(|| Error::Validation("CAPTCHA token is required".to_string()))?;
+            if !captcha.verify(token, Some(client)).await? {
+                return Err(Error::Validation("CAPTCHA verification failed".to_string()));
+            }
+        }
+
+        // Free domains held by signups that were never verified
+        self.remove_expired_signups().await?;
+        let domain = request.domain.trim().to_lowercase();
+        if self.tenants.get_tenant_by_domain(&domain).await?.is_some() {
+            return Err(Error::Conflict(format!(
+                "Domain {} is already taken",
+                domain
+            )));
+        }
+
+        let mut tenant = Tenant::new(request.tenant_name.trim().to_string(), domain);
+        tenant.active = false;
+        let tenant = self.tenants.create_tenant(tenant).await?;
+
+        match self.create_admin(&tenant, &request).await {
+            Ok(response) => Ok(response),
+            Err(e) => {
+                // Remove the half-created signup, including the admin if it was stored
+                let admin = self
+                    .users
+                    .get_user_by_email(request.email.trim(), tenant.id)
+                    .await?;
+                if let Some(admin) = admin {
+                    self.users.delete_user(admin.id, tenant.id).await?;
+                }
+                self.tenants.discard_tenant(tenant.id.0).await?;
+                Err(e)
+            },
+        }
+    }
+
+    /// Activates the admin of a signup and its tenant, or queues the tenant for approval
+    pub async fn verify(&self, token: &str) -> Result<Tenant> {
+        let verification = self
+            .repository
+            .get_verification(&hash_token(token))
+            .await?
+            .filter(|v| !v.is_expired())
+            .ok_or_else(|| Error::NotFound("Invalid or expired verification token".to_string()))?;
+
+        let approval = if self.require_approval {
+            let tenant = self.get_tenant(verification.tenant_id).await?;
+            let admin = self
+                .users
+                .get_user_by_id(verification.user_id)
+                .await?
+                .ok_or_else(|| Error::NotFound("User not found".to_string()))?;
+            Some(TenantApproval::new(&tenant, admin.id, admin.email))
+        } else {
+            None
+        };
+
+        if !self
+            .repository
+            .complete_verification(&verification, approval.as_ref())
+            .await?
+        {
+            return Err(Error::NotFound(
+                "Invalid or expired verification token".to_string(),
+            ));
+        }
+        self.record_funnel(verification.tenant_id, TokenOutcome::Completed, 1)
+            .await;
+
+        if let Some(approval) = approval {
+            self.notify(EmailMessage {
+                to: approval.email.clone(),
+                subject: format!("{} is awaiting approval", approval.tenant_name),
+                body: format!(
+                    "Thank you for verifying your email address. {} will be activated once it has been approved; we will let you know.",
+                    approval.tenant_name
+                ),
+            })
+            .await;
+            if let Some(to) = &self.approval_notify_email {
+                self.notify(EmailMessage {
+                    to: to.clone(),
+                    subject: format!("Tenant {} awaits approval", approval.tenant_name),
+                    body: format!(
+                        "{} ({}) was signed up by {} and awaits approval.",
+                        approval.tenant_name, approval.domain, approval.email
+                    ),
+                })
+                .await;
+            }
+        }
+        self.get_tenant(verification.tenant_id).await
+    }
+
+    /// Lists the issuance and completion counters of signup verifications per tenant
+    pub async fn list_funnel(&self, actor: &User) -> Result<Vec<TokenFunnel>> {
+        ensure_reviewer(actor)?;
+        self.funnel.list(None).await
+    }
+
+    /// Lists the approval queue, optionally only approvals with a status
+    pub async fn list_approvals(
+        &self,
+        actor: &User,
+        status: Option<ApprovalStatus>,
+    ) -> Result<Vec<TenantApproval>> {
+        ensure_reviewer(actor)?;
+        self.repository.list_approvals(status).await
+    }
+
+    /// Approves a pending tenant, activating it
+    pub async fn approve(&self, actor: &User, tenant_id: TenantId) -> Result<TenantApproval> {
+        let approval = self
+            .review(actor, tenant_id, ApprovalStatus::Approved, None)
+            .await?;
+        self.notify(EmailMessage {
+            to: approval.email.clone(),
+            subject: format!("{} has been approved", approval.tenant_name),
+            body: format!(
+                "{} has been approved and is ready to use.",
+                approval.tenant_name
+            ),
+        })
+        .await;
+        Ok(approval)
+    }
+
+    /// Rejects a pending tenant, which stays inactive
+    pub async fn reject(
+        &self,
+        actor: &User,
+        tenant_id: TenantId,
+        reason: Option<String>,
+    ) -> Result<TenantApproval> {
+        let approval = self
+            .review(
+                actor,
+                tenant_id,
+                ApprovalStatus::Rejected,
+                reason.as_deref(),
+            )
+            .await?;
+        let mut body = format!("{} has not been approved.", approval.tenant_name);
+        if let Some(reason) = &approval.reason {
+            body.push_str(&format!("\n\nReason: {}", reason));
+        }
+        self.notify(EmailMessage {
+            to: approval.email.clone(),
+            subject: format!("{} has not been approved", approval.tenant_name),
+            body,
+        })
+        .await;
+        Ok(approval)
+    }
+
+    /// Records the decision on a pending approval
+    async fn review(
+        &self,
+        actor: &User,
+        tenant_id: TenantId,
+        status: ApprovalStatus,
+        reason: Option<&str>,
+    ) -> Result<TenantApproval> {
+        ensure_reviewer(actor)?;
+        let reviewed = self
+            .repository
+            .review_approval(tenant_id, status, actor.id, reason)
+            .await?;
+        let approval = self
+            .repository
+            .get_approval(tenant_id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Approval not found".to_string()))?;
+        if !reviewed {
+            return Err(Error::Conflict(format!(
+                "Tenant was already {}",
+                approval.status
+            )));
+        }
+        Ok(approval)
+    }
+
+    /// Sends a notification; failures are only logged as the change was already stored
+    async fn notify(&self, message: EmailMessage) {
+        if let Err(e) = self.mailer.send(&message).await {
+            warn!("Failed to send notification to {}: {}", message.to, e);
+        }
+    }
+
+    /// Gets a tenant by ID
+    async fn get_tenant(&self, tenant_id: TenantId) -> Result<Tenant> {
+        self.tenants
+            .get_tenant(tenant_id.0)
+            .await?
+            .ok_or_else(|| Error::NotFound("Tenant not found".to_string()))
+    }
+
+    /// Removes tenants and admins of signups whose verification expired
+    pub async fn remove_expired_signups(&self) -> Result<usize> {
+        let expired = self
+            .repository
+            .list_expired(EXPIRED_SIGNUP_BATCH_SIZE)
+            .await?;
+        for (tenant_id, user_id) in &expired {
+            self.discard(*tenant_id, *user_id).await?;
+            self.record_funnel(*tenant_id, TokenOutcome::Expired, 1)
+                .await;
+        }
+        Ok(expired.len())
+    }
+
+    /// Periodically purges expired signups in the background, besides the purge on each signup
+    pub fn spawn_cleanup_worker(self: Arc<Self>, interval: std::time::Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let mut removed = 0;
+                loop {
+                    match self.remove_expired_signups().await {
+                        Ok(count) => {
+                            removed += count;
+                            if count < EXPIRED_SIGNUP_BATCH_SIZE as usize {
+                                break;
+                            }
+                        },
+                        Err(e) => {
+                            error!("Failed to remove expired signups: {}", e);
+                            break;
+                        },
+                    }
+                }
+                if removed > 0 {
+                    info!("Removed {} expired signups", removed);
+                }
+            }
+        })
+    }
+
+    /// Counts signup verifications in the funnel; failures are only logged
+    async fn record_funnel(&self, tenant_id: TenantId, outcome: TokenOutcome, count: i64) {
+        if let Err(e) = self
+            .funnel
+            .record(tenant_id, TokenFlow::SignupVerification, outcome, count)
+            .await
+        {
+            warn!(
+                "Failed to record signup verification funnel of tenant {}: {}",
+                tenant_id.0, e
+            );
+        }
+    }
+
+    /// Creates the inactive admin of a new tenant and sends the verification email
+    async fn create_admin(
+        &self,
+        tenant: &Tenant,
+        request: &SignupRequest,
+    ) -> Result<SignupResponse> {
+        let mut user = User::new(
+            tenant.id,
+            request.email.trim().to_string(),
+            AuthenticationService::hash_password(&request.password)?,
+        );
+        user.active = false;
+        user.roles.push(create_admin_role());
+        let user = self.users.create_user(user).await?;
+
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(48)
+            .map(char::from)
+            .collect();
+        let verification =
+            SignupVerification::new(tenant.id, user.id, &token, self.verification_ttl);
+        self.repository.create_verification(&verification).await?;
+
+        self.mailer
+            .send(&EmailMessage {
+                to: user.email.clone(),
+                subject: format!("Verify your email address for {}", tenant.name),
+                body: format!(
+                    "Please confirm your email address to activate {}:\n\n{}{}\n\nThe link expires at {}.",
+                    tenant.name, self.verification_url, token, verification.expires_at
+                ),
+            })
+            .await?;
+        self.record_funnel(tenant.id, TokenOutcome::Issued, 1).await;
+
+        Ok(SignupResponse {
+            tenant_id: tenant.id,
+            email: user.email,
+            expires_at: verification.expires_at,
+        })
+    }
+
+    /// Permanently deletes the admin and tenant of an unverified signup, freeing its domain
+    async fn discard(&self, tenant_id: TenantId, user_id: UserId) -> Result<()> {
+        self.users.delete_user(user_id, tenant_id).await?;
+        self.tenants.discard_tenant(tenant_id.0).await
+    }
+}
+
+/// Ensures the actor may review signups, which is reserved to super admins
+fn ensure_reviewer(actor: &User) -> Result<()> {
+    if !actor
+        .roles
+        .iter()
+        .any(|role| role.role_type == RoleType::SuperAdmin)
+    {
+        return Err(Error::Authorization(
+            "Only super admins may review signups".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Validates the fields of a signup request
+fn validate_request(request: &SignupRequest) -> Result<()> {
+    if request.tenant_name.trim().is_empty() {
+        return Err(Error::Validation(
+            "Tenant name must not be empty".to_string(),
+        ));
+    }
+    let domain = request.domain.trim();
+    if domain.is_empty() || !domain.contains('.') || domain.contains(char::is_whitespace) {
+        return Err(Error::Validation(format!("Invalid domain: {}", domain)));
+    }
+    UserEmail::validate(&request.email)?;
+    if request.password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(Error::Validation(format!(
+            "Password must have at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::database::tests::create_test_db,
+        modules::{
+            identity::{
+                models::{Credentials, Role},
+                session::InMemorySessionStore,
+            },
+            tenant::repository::TenantRepository,
+        },
+    };
+    use std::sync::Mutex;
+
+    #[derive(Debug, Default)]
+    struct RecordingMailer {
+        messages: Mutex<Vec<EmailMessage>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Mailer for RecordingMailer {
+        async fn send(&self, message: &EmailMessage) -> Result<()> {
+            self.messages.lock().unwrap().push(message.clone());
+            Ok(())
+        }
+    }
+
+    #[derive(Debug)]
+    struct StaticCaptcha;
+
+    #[async_trait::async_trait]
+    impl CaptchaVerifier for StaticCaptcha {
+        async fn verify(&self, token: &str, _remote_ip: Option<&str>) -> Result<bool> {
+            Ok(token == "solved")
+        }
+    }
+
+    fn signup_request(domain: &str, captcha_token: &str) -> SignupRequest {
+        SignupRequest {
+            tenant_name: "Acme".to_string(),
+            domain: domain.to_string(),
+            email: "admin@acme.example.com".to_string(),
+            password: "correct horse battery".to_string(),
+            captcha_token: Some(captcha_token.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_validate_request() {
+        assert!(validate_request(&signup_request("acme.example.com", "solved")).is_ok());
+
+        let mut request = signup_request("localhost", "solved");
+        assert!(matches!(
+            validate_request(&request),
+            Err(Error::Validation(_))
+        ));
+
+        request.domain = "acme.example.com".to_string();
+        request.password = "short".to_string();
+        assert!(matches!(
+            validate_request(&request),
+            Err(Error::Validation(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_public_signup() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let tenants = TenantService::new(TenantRepository::new(db.get_pool()));
+        let users = UserRepository::new(db.get_pool());
+        let mailer = Arc::new(RecordingMailer::default());
+        let config = SignupConfig {
+            max_attempts: 2,
+            ..SignupConfig::default()
+        };
+        let service = SignupService::new(tenants.clone(), users.clone(), &config)
+            .with_captcha_verifier(Arc::new(StaticCaptcha))
+            .with_mailer(mailer.clone());
+
+        let domain = format!("{}.example.com", uuid::Uuid::new_v4());
+
+        // Unsolved CAPTCHAs are rejected before anything is created
+        let result = service
+            .signup(signup_request(&domain, "bot"), "10.0.0.1")
+            .await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+        assert!(tenants
+            .get_tenant_by_domain(&domain)
+            .await
+            .unwrap()
+            .is_none());
+
+        let response = service
+            .signup(signup_request(&domain, "solved"), "10.0.0.1")
+            .await
+            .unwrap();
+        let tenant = tenants
+            .get_tenant(response.tenant_id.0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!tenant.active);
+        let admin = users
+            .get_user_by_email("admin@acme.example.com", tenant.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!admin.active);
+        assert!(admin.roles.iter().any(|role| role.name == "Admin"));
+
+        // The domain cannot be claimed twice
+        let result = service
+            .signup(signup_request(&domain, "solved"), "10.0.0.2")
+            .await;
+        assert!(matches!(result, Err(Error::Conflict(_))));
+
+        // Clients are throttled once they used up their attempts
+        let result = service
+            .signup(signup_request(&domain, "solved"), "10.0.0.1")
+            .await;
+        assert!(matches!(result, Err(Error::RateLimited(_))));
+
+        // The verification link activates tenant and admin exactly once
+        let message = mailer.messages.lock().unwrap()[0].clone();
+        assert_eq!(message.to, "admin@acme.example.com");
+        let token = message
+            .body
+            .split(&config.verification_url)
+            .nth(1)
+            .and_then(|rest| rest.split_whitespace().next())
+            .unwrap()
+            .to_string();
+        assert!(matches!(
+            service.verify("invalid").await,
+            Err(Error::NotFound(_))
+        ));
+        let tenant = service.verify(&token).await.unwrap();
+        assert!(tenant.active);
+        assert!(
+            users
+                .get_user_by_id(admin.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .active
+        );
+        let emails = users.list_user_emails(admin.id, tenant.id).await.unwrap();
+        assert!(emails[0].verified);
+        assert!(matches!(
+            service.verify(&token).await,
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_expired_signup_frees_domain() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let tenants = TenantService::new(TenantRepository::new(db.get_pool()));
+        let users = UserRepository::new(db.get_pool());
+        let config = SignupConfig {
+            verification_ttl_hours: 0,
+            ..SignupConfig::default()
+        };
+        let service = SignupService::new(tenants.clone(), users, &config)
+            .with_captcha_verifier(Arc::new(StaticCaptcha))
+            .with_mailer(Arc::new(RecordingMailer::default()));
+
+        let domain = format!("{}.example.com", uuid::Uuid::new_v4());
+        let expired = service
+            .signup(signup_request(&domain, "solved"), "10.0.0.1")
+            .await
+            .unwrap();
+
+        // Signing up again removes the expired signup for good, so its domain can be taken
+        let response = service
+            .signup(signup_request(&domain, "solved"), "10.0.0.2")
+            .await
+            .unwrap();
+        assert_ne!(response.tenant_id, expired.tenant_id);
+        let tenant = tenants
+            .get_tenant_by_domain(&domain)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tenant.id, response.tenant_id);
+    }
+
+    #[tokio::test]
+    async fn test_signup_approval() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let tenants = TenantService::new(TenantRepository::new(db.get_pool()));
+        let users = UserRepository::new(db.get_pool());
+        let mailer = Arc::new(RecordingMailer::default());
+        let config = SignupConfig {
+            require_approval: true,
+            approval_notify_email: Some("operator@example.com".to_string()),
+            ..SignupConfig::default()
+        };
+        let service = SignupService::new(tenants.clone(), users.clone(), &config)
+            .with_captcha_verifier(Arc::new(StaticCaptcha))
+            .with_mailer(mailer.clone());
+
+        let domain = format!("{}.example.com", uuid::Uuid::new_v4());
+        let response = service
+            .signup(signup_request(&domain, "solved"), "10.0.0.1")
+            .await
+            .unwrap();
+        let token = mailer.messages.lock().unwrap()[0]
+            .body
+            .split(&config.verification_url)
+            .nth(1)
+            .and_then(|rest| rest.split_whitespace().next())
+            .unwrap()
+            .to_string();
+
+        // Verified tenants stay inactive until they were approved, locking out their admin
+        let tenant = service.verify(&token).await.unwrap();
+        assert!(!tenant.active);
+        let auth = AuthenticationService::new(users.clone(), Box::new(InMemorySessionStore::new()));
+        let credentials = Credentials {
+            email: "admin@acme.example.com".to_string(),
+            password: "correct horse battery".to_string(),
+            tenant_id: response.tenant_id,
+            mfa_code: None,
+        };
+        assert!(matches!(
+            auth.authenticate(credentials.clone()).await,
+            Err(Error::Authorization(_))
+        ));
+        let recipients: Vec<String> = mailer
+            .messages
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|message| message.to.clone())
+            .collect();
+        assert_eq!(
+            recipients,
+            vec![
+                "admin@acme.example.com",
+                "admin@acme.example.com",
+                "operator@example.com"
+            ]
+        );
+
+        // Only super admins may review signups
+        let mut reviewer = User::new(
+            TenantId::new(),
+            "reviewer@example.com".to_string(),
+            String::new(),
+        );
+        assert!(matches!(
+            service.approve(&reviewer, response.tenant_id).await,
+            Err(Error::Authorization(_))
+        ));
+        reviewer.roles = vec![Role::new(RoleType::SuperAdmin, "Super Admin".to_string())];
+
+        let pending = service
+            .list_approvals(&reviewer, Some(ApprovalStatus::PendingApproval))
+            .await
+            .unwrap();
+        assert!(pending
+            .iter()
+            .any(|approval| approval.tenant_id == response.tenant_id));
+
+        let approval = service
+            .approve(&reviewer, response.tenant_id)
+            .await
+            .unwrap();
+        assert_eq!(approval.status, ApprovalStatus::Approved);
+        assert_eq!(approval.reviewed_by, Some(reviewer.id));
+        assert!(
+            tenants
+                .get_tenant(response.tenant_id.0)
+                .await
+                .unwrap()
+                .unwrap()
+                .active
+        );
+        assert_eq!(
+            mailer.messages.lock().unwrap().last().unwrap().subject,
+            "Acme has been approved"
+        );
+        assert!(auth.authenticate(credentials).await.is_ok());
+
+        // Decisions are final
+        assert!(matches!(
+            service
+                .reject(&reviewer, response.tenant_id, Some("Spam".to_string()))
+                .await,
+            Err(Error::Conflict(_))
+        ));
+        assert!(matches!(
+            service.approve(&reviewer, TenantId::new()).await,
+            Err(Error::NotFound(_))
+        ));
+    }
+}
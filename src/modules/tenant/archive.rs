@@ -0,0 +1,340 @@
+use async_trait::async_trait;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    modules::tenant::models::Tenant,
+    shared::{
+        error::{Error, Result},
+        types::TenantId,
+    },
+};
+
+/// Version of the archive format, raised whenever archives change incompatibly
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+/// Tables holding a tenant's data with the condition selecting its rows, `$1`
+/// being the tenant ID
+///
+/// Tables are listed in the order they are restored in, every table after the
+/// ones it references. Sessions and refresh tokens are not archived.
+pub const ARCHIVED_TABLES: &[(&str, &str)] = &[
+    ("tenants", "id = $1"),
+    ("users", "tenant_id = $1"),
+    ("roles", "tenant_id = $1"),
+    (
+        "permissions",
+        "role_id IN (SELECT id FROM roles WHERE tenant_id = $1)",
+    ),
+    ("role_parents", "tenant_id = $1"),
+    ("user_roles", "tenant_id = $1"),
+    ("user_emails", "tenant_id = $1"),
+    ("mfa_backup_codes", "tenant_id = $1"),
+    ("user_token_versions", "tenant_id = $1"),
+    ("user_tombstones", "tenant_id = $1"),
+    ("sso_providers", "tenant_id = $1"),
+    ("sso_user_mappings", "tenant_id = $1"),
+    ("sso_link_requests", "tenant_id = $1"),
+    ("sso_tokens", "tenant_id = $1"),
+    (
+        "sso_used_assertions",
+        "provider_id IN (SELECT id FROM sso_providers WHERE tenant_id = $1)",
+    ),
+    ("projects", "tenant_id = $1"),
+    ("project_members", "tenant_id = $1"),
+    ("resource_owners", "tenant_id = $1"),
+    ("audit_log", "tenant_id = $1"),
+    ("tenant_settings", "tenant_id = $1"),
+    ("tenant_domains", "tenant_id = $1"),
+    ("tenant_slug_history", "tenant_id = $1"),
+    ("tenant_approvals", "tenant_id = $1"),
+    ("tenant_invitations", "tenant_id = $1"),
+    ("signup_verifications", "tenant_id = $1"),
+    ("data_exports", "tenant_id = $1"),
+    ("data_erasures", "tenant_id = $1"),
+    ("api_keys", "tenant_id = $1"),
+    ("token_funnel", "tenant_id = $1"),
+    ("session_archive", "tenant_id = $1"),
+];
+
+/// Extension point for the object storage tenant archives are kept in
+#[async_trait]
+pub trait ArchiveStore: Send + Sync + std::fmt::Debug {
+    /// Stores an object, replacing any object with the same key
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()>;
+
+    /// Gets an object, failing with a not found error if there is none
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+
+    /// Deletes an object; deleting a missing object succeeds
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// Archive store keeping objects as files below a directory, e.g. a mounted bucket
+#[derive(Debug, Clone)]
+pub struct FileArchiveStore {
+    root: PathBuf,
+}
+
+impl FileArchiveStore {
+    /// Creates a store keeping objects below the given directory
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Gets the path of an object, rejecting keys that would leave the root directory
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(key);
+        if key.is_empty()
+            || !relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(Error::InvalidInput(format!(
+                "Invalid archive object key: {}",
+                key
+            )));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl ArchiveStore for FileArchiveStore {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(|e| {
+                Error::Internal(format!("Failed to create archive directory: {}", e))
+            })?;
+        }
+        // Write to a temporary file first so readers never see a partial object
+        let partial = path.with_extension("partial");
+        tokio::fs::write(&partial, data)
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to write archive {}: {}", key, e)))?;
+        tokio::fs::rename(&partial, &path)
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to write archive {}: {}", key, e)))
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(data) => Ok(data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(Error::NotFound(format!("Archive {} not found", key)))
+            },
+            Err(e) => Err(Error::Internal(format!(
+                "Failed to read archive {}: {}",
+                key, e
+            ))),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(Error::Internal(format!(
+                "Failed to delete archive {}: {}",
+                key, e
+            ))),
+        }
+    }
+}
+
+/// Rows of all archived tables of a tenant, stored as gzipped JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TenantArchive {
+    pub format_version: u32,
+    pub tenant_id: TenantId,
+    pub archived_at: OffsetDateTime,
+    /// Rows by table name, each row an object of its columns
+    pub tables: BTreeMap<String, Vec<Value>>,
+}
+
+impl TenantArchive {
+    /// Creates an empty archive of a tenant
+    pub fn new(tenant_id: TenantId) -> Self {
+        Self {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            tenant_id,
+            archived_at: OffsetDateTime::now_utc(),
+            tables: BTreeMap::new(),
+        }
+    }
+
+    /// Gets the number of archived rows by table
+    pub fn row_counts(&self) -> BTreeMap<String, i64> {
+        self.tables
+            .iter()
+            .map(|(table, rows)| (table.clone(), rows.len() as i64))
+            .collect()
+    }
+
+    /// Serializes and compresses the archive
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let json = serde_json::to_vec(self)
+            .map_err(|e| Error::Internal(format!("Failed to serialize tenant archive: {}", e)))?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(&json)
+            .and_then(|_| encoder.finish())
+            .map_err(|e| Error::Internal(format!("Failed to compress tenant archive: {}", e)))
+    }
+
+    /// Decompresses and parses an archive, rejecting archives of other format versions
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut json = Vec::new();
+        GzDecoder::new(data)
+            .read_to_end(&mut json)
+            .map_err(|e| Error::Internal(format!("Failed to decompress tenant archive: {}", e)))?;
+        let archive: Self = serde_json::from_slice(&json)
+            .map_err(|e| Error::Internal(format!("Failed to parse tenant archive: {}", e)))?;
+        if archive.format_version != ARCHIVE_FORMAT_VERSION {
+            return Err(Error::Internal(format!(
+                "Unsupported tenant archive format version {}",
+                archive.format_version
+            )));
+        }
+        Ok(archive)
+    }
+}
+
+/// Record of a tenant archived to cold storage, kept after its data left the database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub id: Uuid,
+    pub tenant_id: TenantId,
+    pub tenant_name: String,
+    pub tenant_domain: String,
+    /// Key of the archive in the archive store
+    pub object_key: String,
+    /// Hex encoded SHA-256 digest of the stored archive
+    pub checksum: String,
+    pub size_bytes: i64,
+    /// Number of archived rows by table
+    pub row_counts: BTreeMap<String, i64>,
+    pub archived_at: OffsetDateTime,
+    /// When the tenant was restored from the archive, `None` while it is archived
+    pub restored_at: Option<OffsetDateTime>,
+}
+
+impl ArchiveManifest {
+    /// Creates the manifest of an archive of a tenant, given its encoded data
+    pub fn new(tenant: &Tenant, archive: &TenantArchive, data: &[u8]) -> Self {
+        let id = Uuid::new_v4();
+        Self {
+            id,
+            tenant_id: tenant.id,
+            tenant_name: tenant.name.clone(),
+            tenant_domain: tenant.domain.clone(),
+            object_key: format!("tenants/{}/{}.json.gz", tenant.id.0, id),
+            checksum: checksum(data),
+            size_bytes: data.len() as i64,
+            row_counts: archive.row_counts(),
+            archived_at: archive.archived_at,
+            restored_at: None,
+        }
+    }
+
+    /// Decodes the archive data of this manifest after verifying its checksum
+    pub fn open(&self, data: &[u8]) -> Result<TenantArchive> {
+        if checksum(data) != self.checksum {
+            return Err(Error::Internal(format!(
+                "Checksum mismatch of archive {}",
+                self.object_key
+            )));
+        }
+        let archive = TenantArchive::decode(data)?;
+        if archive.tenant_id != self.tenant_id {
+            return Err(Error::Internal(format!(
+                "Archive {} belongs to another tenant",
+                self.object_key
+            )));
+        }
+        Ok(archive)
+    }
+}
+
+/// Computes the hex encoded SHA-256 digest of archive data
+pub fn checksum(data: &[u8]) -> String {
+    digest::digest(&digest::SHA256, data)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_archive_round_trip() {
+        let tenant = Tenant::new("Acme".to_string(), "acme.com".to_string());
+        let mut archive = TenantArchive::new(tenant.id);
+        archive.tables.insert(
+            "users".to_string(),
+            vec![json!({ "id": Uuid::new_v4(), "email": "admin@acme.com" })],
+        );
+        archive.tables.insert("roles".to_string(), Vec::new());
+
+        let data = archive.encode().unwrap();
+        let manifest = ArchiveManifest::new(&tenant, &archive, &data);
+        assert_eq!(manifest.size_bytes, data.len() as i64);
+        assert_eq!(manifest.row_counts["users"], 1);
+        assert_eq!(manifest.row_counts["roles"], 0);
+        assert!(manifest
+            .object_key
+            .starts_with(&format!("tenants/{}/", tenant.id.0)));
+        assert_eq!(manifest.open(&data).unwrap(), archive);
+
+        // Tampered archives are rejected
+        let mut tampered = data.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(manifest.open(&tampered).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_archive_store() {
+        let root = std::env::temp_dir().join(format!("acci-archives-{}", Uuid::new_v4()));
+        let store = FileArchiveStore::new(&root);
+
+        store
+            .put("tenants/acme/archive.json.gz", b"data".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get("tenants/acme/archive.json.gz").await.unwrap(),
+            b"data"
+        );
+        store.delete("tenants/acme/archive.json.gz").await.unwrap();
+        assert!(matches!(
+            store.get("tenants/acme/archive.json.gz").await,
+            Err(Error::NotFound(_))
+        ));
+        assert!(store.delete("tenants/acme/archive.json.gz").await.is_ok());
+
+        // Keys cannot escape the root directory
+        for key in ["", "../secrets", "/etc/passwd", "tenants/../../secrets"] {
+            assert!(matches!(
+                store.put(key, Vec::new()).await,
+                Err(Error::InvalidInput(_))
+            ));
+        }
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
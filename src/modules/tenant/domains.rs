@@ -0,0 +1,237 @@
+use async_trait::async_trait;
+use hickory_resolver::{error::ResolveErrorKind, TokioAsyncResolver};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use url::{Host, Url};
+
+use crate::shared::{
+    error::{Error, Result},
+    types::TenantId,
+};
+
+/// Label prepended to a claimed domain to get the name of its TXT challenge record
+const CHALLENGE_LABEL: &str = "_acci-challenge";
+
+/// Prefix of the TXT challenge record value, followed by the claim token
+const CHALLENGE_VALUE_PREFIX: &str = "acci-domain-verification=";
+
+/// Maximum length of a domain name
+const MAX_DOMAIN_LENGTH: usize = 253;
+
+/// Maximum length of a single domain label
+const MAX_LABEL_LENGTH: usize = 63;
+
+/// Custom domain claimed by a tenant
+///
+/// The domain is only routed to the tenant once the TXT challenge proved the
+/// tenant controls its DNS zone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantDomain {
+    pub tenant_id: TenantId,
+    pub domain: String,
+    /// Token the TXT challenge record has to contain
+    pub token: String,
+    pub verified_at: Option<OffsetDateTime>,
+    /// When the challenge record was last looked up
+    pub last_checked_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+impl TenantDomain {
+    /// Creates a new, unverified claim of a domain with a fresh token
+    pub fn new(tenant_id: TenantId, domain: String) -> Self {
+        Self {
+            tenant_id,
+            domain,
+            token: rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(32)
+                .map(char::from)
+                .collect(),
+            verified_at: None,
+            last_checked_at: None,
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// Checks if the tenant proved it controls the domain
+    pub fn is_verified(&self) -> bool {
+        self.verified_at.is_some()
+    }
+
+    /// Name of the TXT record the tenant has to create, e.g. `_acci-challenge.login.acme.com`
+    pub fn challenge_name(&self) -> String {
+        format!("{}.{}", CHALLENGE_LABEL, self.domain)
+    }
+
+    /// Value of the TXT record the tenant has to create
+    pub fn challenge_value(&self) -> String {
+        format!("{}{}", CHALLENGE_VALUE_PREFIX, self.token)
+    }
+
+    /// Checks if one of the TXT records found for the challenge name answers the challenge
+    pub fn is_answered_by(&self, records: &[String]) -> bool {
+        let expected = self.challenge_value();
+        records.iter().any(|record| record.trim() == expected)
+    }
+}
+
+/// Normalizes a domain and ensures it is a valid hostname
+///
+/// Domains are lowercased without a trailing dot or port, and internationalized
+/// names are converted to punycode. Pasted URLs are reduced to their host.
+pub fn normalize_domain(domain: &str) -> Result<String> {
+    let input = domain.trim();
+    let host = match input.split_once("://") {
+        Some(_) => Url::parse(input)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .ok_or_else(|| Error::Validation(format!("Invalid domain {}", input)))?,
+        None => strip_port(input).to_string(),
+    };
+    let domain = match Host::parse(host.trim_end_matches('.')) {
+        Ok(Host::Domain(domain)) => domain,
+        Ok(Host::Ipv4(_) | Host::Ipv6(_)) => {
+            return Err(Error::Validation(format!(
+                "Invalid domain {}, IP addresses are not supported",
+                input
+            )))
+        },
+        Err(_) => return Err(Error::Validation(format!("Invalid domain {}", input))),
+    };
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    let valid_labels = labels.iter().all(|label| {
+        !label.is_empty()
+            && label.len() <= MAX_LABEL_LENGTH
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    });
+    if domain.len() > MAX_DOMAIN_LENGTH || labels.len() < 2 || !valid_labels {
+        return Err(Error::Validation(format!("Invalid domain {}", input)));
+    }
+    Ok(domain)
+}
+
+/// Strips a trailing port from a host, e.g. `acme.com:8443`
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port)) if !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
+
+/// Extension point for looking up the TXT records domain challenges are answered with
+#[async_trait]
+pub trait TxtResolver: Send + Sync + std::fmt::Debug {
+    /// Gets the TXT records of a name, empty if there are none
+    async fn lookup_txt(&self, name: &str) -> Result<Vec<String>>;
+}
+
+/// Resolver querying the name servers of the system configuration
+#[derive(Clone)]
+pub struct DnsTxtResolver {
+    resolver: TokioAsyncResolver,
+}
+
+impl DnsTxtResolver {
+    /// Creates a resolver from `/etc/resolv.conf` or the platform's equivalent
+    pub fn from_system_conf() -> Result<Self> {
+        let resolver = TokioAsyncResolver::tokio_from_system_conf()
+            .map_err(|e| Error::Internal(format!("Failed to read the DNS configuration: {}", e)))?;
+        Ok(Self { resolver })
+    }
+}
+
+impl std::fmt::Debug for DnsTxtResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DnsTxtResolver").finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl TxtResolver for DnsTxtResolver {
+    async fn lookup_txt(&self, name: &str) -> Result<Vec<String>> {
+        match self.resolver.txt_lookup(format!("{}.", name)).await {
+            Ok(lookup) => Ok(lookup
+                .iter()
+                .map(|txt| {
+                    txt.txt_data()
+                        .iter()
+                        .map(|data| String::from_utf8_lossy(data))
+                        .collect()
+                })
+                .collect()),
+            Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => Ok(Vec::new()),
+            Err(e) => Err(Error::Unavailable(format!(
+                "Failed to look up TXT records of {}: {}",
+                name, e
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_domain() {
+        assert_eq!(
+            normalize_domain(" Login.Acme.com. ").unwrap(),
+            "login.acme.com"
+        );
+        assert_eq!(
+            normalize_domain("acme-corp.co.uk").unwrap(),
+            "acme-corp.co.uk"
+        );
+        assert!(normalize_domain("localhost").is_err());
+        assert!(normalize_domain("acme..com").is_err());
+        assert!(normalize_domain("-acme.com").is_err());
+        assert!(normalize_domain("acme.com/login").is_err());
+        assert!(normalize_domain(&format!("{}.com", "a".repeat(MAX_LABEL_LENGTH + 1))).is_err());
+
+        // Schemes, ports and paths of pasted URLs are dropped
+        assert_eq!(
+            normalize_domain("https://Login.Acme.com:8443/sign-in").unwrap(),
+            "login.acme.com"
+        );
+        assert_eq!(normalize_domain("acme.com:8443").unwrap(), "acme.com");
+        // Internationalized names are stored as punycode
+        assert_eq!(normalize_domain("Bücher.de").unwrap(), "xn--bcher-kva.de");
+        assert_eq!(
+            normalize_domain("xn--bcher-kva.de").unwrap(),
+            "xn--bcher-kva.de"
+        );
+        assert!(normalize_domain("192.168.1.1").is_err());
+        assert!(normalize_domain("https://").is_err());
+        assert!(normalize_domain("acme_corp.com").is_err());
+    }
+
+    #[test]
+    fn test_domain_challenge() {
+        let claim = TenantDomain::new(TenantId::new(), "login.acme.com".to_string());
+        assert_eq!(claim.token.len(), 32);
+        assert!(!claim.is_verified());
+        assert_eq!(claim.challenge_name(), "_acci-challenge.login.acme.com");
+        assert_eq!(
+            claim.challenge_value(),
+            format!("acci-domain-verification={}", claim.token)
+        );
+
+        assert!(claim.is_answered_by(&[
+            "v=spf1 -all".to_string(),
+            format!("{} ", claim.challenge_value()),
+        ]));
+        assert!(!claim.is_answered_by(&[]));
+        assert!(!claim.is_answered_by(&[format!("{}x", claim.challenge_value())]));
+
+        // Every claim gets its own token, so records of another tenant do not answer it
+        let other = TenantDomain::new(TenantId::new(), "login.acme.com".to_string());
+        assert!(!claim.is_answered_by(&[other.challenge_value()]));
+    }
+}
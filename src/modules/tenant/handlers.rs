@@ -1,40 +1,190 @@
 use crate::shared::error::Error;
 use axum::http::StatusCode;
 use axum::{
-    extract::{Path, State},
-    response::IntoResponse,
-    routing::{get, post, put},
+    extract::{FromRef, Path, Query, State},
+    response::{IntoResponse, Redirect},
+    routing::{delete, get, post, put},
     Json, Router,
 };
+use std::sync::Arc;
 use time;
 use uuid::Uuid;
 
 use crate::{
+    modules::identity::{
+        auth::AuthenticationService,
+        handlers::AuthenticatedUser,
+        rbac::{action, ensure_tenant_boundary, resource, RbacService, RequirePermission},
+    },
     modules::tenant::{
-        models::{Tenant, TenantRequest, TenantResponse},
+        invitations::TenantInvitation,
+        models::{
+            AdminInvitationResponse, DomainCheckQuery, DomainCheckResponse,
+            InvitationAcceptRequest, InvitationAcceptResponse, SlugResolution,
+            TemplateCaptureRequest, Tenant, TenantDomainRequest, TenantDomainResponse,
+            TenantListQuery, TenantPlanRequest, TenantRequest, TenantResponse,
+            TenantSuspensionRequest,
+        },
+        plans::TenantPlan,
         service::TenantService,
+        templates::TenantTemplate,
     },
     shared::{error::Result, types::TenantId},
 };
 
-/// Creates a new tenant
+/// Shared state of the tenant handlers
+#[derive(Clone, FromRef)]
+pub struct TenantState {
+    pub auth: Arc<AuthenticationService>,
+    pub rbac: Arc<RbacService>,
+    pub tenants: TenantService,
+}
+
+/// Creates a new tenant, optionally from a template and inviting its first admin
 pub async fn create_tenant(
     State(service): State<TenantService>,
-    Json(request): Json<TenantRequest>,
+    RequirePermission(actor, _): RequirePermission<action::Create, resource::Tenants>,
+    Json(mut request): Json<TenantRequest>,
+) -> Result<impl IntoResponse> {
+    TenantService::ensure_super_admin(&actor, "create")?;
+    let admin_email = request.admin_email.take();
+    let response = match (request.template_id.take(), admin_email) {
+        (Some(template_id), admin_email) => {
+            let (tenant, invitation) = service
+                .create_tenant_from_template(request.into(), template_id, admin_email.as_deref())
+                .await?;
+            created_response(tenant, invitation)
+        },
+        (None, Some(admin_email)) => {
+            let (tenant, invitation) = service
+                .create_tenant_with_admin(request.into(), &admin_email)
+                .await?;
+            created_response(tenant, Some(invitation))
+        },
+        (None, None) => TenantResponse::from(service.create_tenant(request.into()).await?),
+    };
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Creates a new tenant with the configuration of an existing one, but none of its users
+pub async fn clone_tenant(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Create, resource::Tenants>,
+    Path(id): Path<String>,
+    Json(mut request): Json<TenantRequest>,
+) -> Result<impl IntoResponse> {
+    TenantService::ensure_super_admin(&actor, "clone")?;
+    let id = Uuid::parse_str(&id)
+        .map_err(|e| crate::shared::error::Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+
+    let admin_email = request.admin_email.take();
+    let (tenant, invitation) = service
+        .clone_tenant(id, request.into(), admin_email.as_deref())
+        .await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(created_response(tenant, invitation)),
+    ))
+}
+
+/// Builds the response for a created tenant with the invitation of its first admin
+fn created_response(tenant: Tenant, invitation: Option<TenantInvitation>) -> TenantResponse {
+    let mut response = TenantResponse::from(tenant);
+    response.admin_invitation = invitation.map(AdminInvitationResponse::from);
+    response
+}
+
+/// Stores a template new tenants can be created from
+pub async fn create_tenant_template(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Create, resource::Tenants>,
+    Json(template): Json<TenantTemplate>,
+) -> Result<impl IntoResponse> {
+    TenantService::ensure_super_admin(&actor, "manage templates of")?;
+    let template = service.create_template(template).await?;
+    Ok((StatusCode::CREATED, Json(template)))
+}
+
+/// Stores the configuration of a tenant as a template
+pub async fn capture_tenant_template(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Create, resource::Tenants>,
+    Path(id): Path<String>,
+    Json(request): Json<TemplateCaptureRequest>,
+) -> Result<impl IntoResponse> {
+    TenantService::ensure_super_admin(&actor, "capture templates of")?;
+    let id = Uuid::parse_str(&id)
+        .map_err(|e| crate::shared::error::Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+
+    let template = service.capture_template(id, request.name).await?;
+    Ok((StatusCode::CREATED, Json(template)))
+}
+
+/// Lists all tenant templates
+pub async fn list_tenant_templates(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Read, resource::Tenants>,
+) -> Result<impl IntoResponse> {
+    TenantService::ensure_super_admin(&actor, "manage templates of")?;
+    let templates = service.list_templates().await?;
+    Ok((StatusCode::OK, Json(templates)))
+}
+
+/// Gets a tenant template by ID
+pub async fn get_tenant_template(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Read, resource::Tenants>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    TenantService::ensure_super_admin(&actor, "manage templates of")?;
+    let id = Uuid::parse_str(&id)
+        .map_err(|e| crate::shared::error::Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+
+    let template = service.get_template(id).await?;
+    Ok((StatusCode::OK, Json(template)))
+}
+
+/// Deletes a tenant template
+pub async fn delete_tenant_template(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Delete, resource::Tenants>,
+    Path(id): Path<String>,
 ) -> Result<impl IntoResponse> {
-    let tenant = service.create_tenant(request.into()).await?;
-    Ok((StatusCode::CREATED, Json(TenantResponse::from(tenant))))
+    TenantService::ensure_super_admin(&actor, "manage templates of")?;
+    let id = Uuid::parse_str(&id)
+        .map_err(|e| crate::shared::error::Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+
+    service.delete_template(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Accepts the invitation of a tenant's first admin, setting the admin's password
+pub async fn accept_invitation(
+    State(service): State<TenantService>,
+    Json(request): Json<InvitationAcceptRequest>,
+) -> Result<impl IntoResponse> {
+    let invitation = service
+        .accept_invitation(&request.token, &request.password)
+        .await?;
+    Ok((
+        StatusCode::OK,
+        Json(InvitationAcceptResponse {
+            tenant_id: invitation.tenant_id,
+            email: invitation.email,
+        }),
+    ))
 }
 
-/// Gets a tenant by ID
+/// Gets a tenant by ID, reporting tenants the actor does not administer as not found
 pub async fn get_tenant(
     State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Read, resource::Tenants>,
     Path(id): Path<String>,
 ) -> Result<impl IntoResponse> {
     let id = Uuid::parse_str(&id)
         .map_err(|e| crate::shared::error::Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
 
-    match service.get_tenant(id).await? {
+    match service.get_managed_tenant(&actor, id).await? {
         Some(t) => Ok((StatusCode::OK, Json(t))),
         None => Ok((
             StatusCode::NOT_FOUND,
@@ -42,9 +192,15 @@ pub async fn get_tenant(
                 id: TenantId(uuid::Uuid::nil()),
                 name: String::new(),
                 domain: String::new(),
+                slug: None,
+                region: None,
                 active: false,
                 created_at: time::OffsetDateTime::now_utc(),
                 updated_at: time::OffsetDateTime::now_utc(),
+                deleted_at: None,
+                suspended_at: None,
+                suspension_reason: None,
+                plan: TenantPlan::default(),
             }),
         )),
     }
@@ -53,45 +209,338 @@ pub async fn get_tenant(
 /// Updates a tenant
 pub async fn update_tenant(
     State(service): State<TenantService>,
+    AuthenticatedUser(actor): AuthenticatedUser,
     Path(id): Path<String>,
     Json(request): Json<TenantRequest>,
 ) -> Result<impl IntoResponse> {
     let id = Uuid::parse_str(&id)
         .map_err(|e| crate::shared::error::Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
 
-    let mut tenant: Tenant = request.into();
-    tenant.id = TenantId(id);
-
-    let updated = service.update_tenant(tenant).await?;
+    let updated = service.update_managed_tenant(&actor, id, request).await?;
     Ok((StatusCode::OK, Json(TenantResponse::from(updated))))
 }
 
-/// Lists all tenants
-pub async fn list_tenants(State(service): State<TenantService>) -> Result<impl IntoResponse> {
-    let tenants = service.list_tenants().await?;
+/// Suspends a tenant, denying its users access until it is reactivated
+pub async fn suspend_tenant(
+    State(service): State<TenantService>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(id): Path<String>,
+    Json(request): Json<TenantSuspensionRequest>,
+) -> Result<impl IntoResponse> {
+    let id = Uuid::parse_str(&id)
+        .map_err(|e| crate::shared::error::Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+
+    let tenant = service
+        .suspend_managed_tenant(&actor, id, request.reason)
+        .await?;
+    Ok((StatusCode::OK, Json(TenantResponse::from(tenant))))
+}
+
+/// Reactivates a suspended tenant
+pub async fn reactivate_tenant(
+    State(service): State<TenantService>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let id = Uuid::parse_str(&id)
+        .map_err(|e| crate::shared::error::Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+
+    let tenant = service.reactivate_managed_tenant(&actor, id).await?;
+    Ok((StatusCode::OK, Json(TenantResponse::from(tenant))))
+}
+
+/// Archives an inactive, suspended or deleted tenant to cold storage
+pub async fn archive_tenant(
+    State(service): State<TenantService>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let id = Uuid::parse_str(&id)
+        .map_err(|e| crate::shared::error::Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+
+    let manifest = service.archive_managed_tenant(&actor, id).await?;
+    Ok((StatusCode::CREATED, Json(manifest)))
+}
+
+/// Lists the archived tenants that can be restored
+pub async fn list_tenant_archives(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Read, resource::Tenants>,
+) -> Result<impl IntoResponse> {
+    TenantService::ensure_super_admin(&actor, "list archived")?;
+    let archives = service.list_archives().await?;
+    Ok((StatusCode::OK, Json(archives)))
+}
+
+/// Restores an archived tenant from cold storage
+pub async fn restore_archived_tenant(
+    State(service): State<TenantService>,
+    AuthenticatedUser(actor): AuthenticatedUser,
+    Path(tenant_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let tenant_id = Uuid::parse_str(&tenant_id)
+        .map_err(|e| crate::shared::error::Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+
+    let tenant = service.restore_managed_archive(&actor, tenant_id).await?;
+    Ok((StatusCode::OK, Json(TenantResponse::from(tenant))))
+}
+
+/// Checks if a domain is valid and available before creating a tenant with it
+pub async fn check_tenant_domain(
+    State(service): State<TenantService>,
+    Query(query): Query<DomainCheckQuery>,
+) -> Result<impl IntoResponse> {
+    let (domain, available) = service.check_tenant_domain(&query.domain).await?;
     Ok((
         StatusCode::OK,
-        Json(
-            tenants
-                .into_iter()
-                .map(TenantResponse::from)
-                .collect::<Vec<_>>(),
-        ),
+        Json(DomainCheckResponse { domain, available }),
+    ))
+}
+
+/// Moves a tenant to another plan
+pub async fn set_tenant_plan(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Update, resource::Tenants>,
+    Path(id): Path<String>,
+    Json(request): Json<TenantPlanRequest>,
+) -> Result<impl IntoResponse> {
+    TenantService::ensure_super_admin(&actor, "change the plan of")?;
+    let id = Uuid::parse_str(&id)
+        .map_err(|e| crate::shared::error::Error::InvalidInput(format!("Invalid UUID: {}", e)))?;
+
+    let tenant = service.set_plan(id, request.plan).await?;
+    Ok((StatusCode::OK, Json(TenantResponse::from(tenant))))
+}
+
+/// Gets the settings of a tenant
+pub async fn get_tenant_settings(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Read, resource::Tenants>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let tenant_id = parse_tenant_id(&id)?;
+    ensure_tenant_boundary(&actor, tenant_id)?;
+    ensure_tenant_exists(&service, tenant_id).await?;
+
+    let settings = service.settings(tenant_id).await?;
+    Ok((StatusCode::OK, Json(settings)))
+}
+
+/// Gets a setting of a tenant
+pub async fn get_tenant_setting(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Read, resource::Tenants>,
+    Path((id, key)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let tenant_id = parse_tenant_id(&id)?;
+    ensure_tenant_boundary(&actor, tenant_id)?;
+    ensure_tenant_exists(&service, tenant_id).await?;
+
+    let settings = service.settings(tenant_id).await?;
+    let value = settings
+        .get_value(&key)
+        .cloned()
+        .ok_or_else(|| Error::NotFound("Setting not found".to_string()))?;
+    Ok((StatusCode::OK, Json(value)))
+}
+
+/// Sets a setting of a tenant to the JSON value of the request body
+pub async fn set_tenant_setting(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Update, resource::Tenants>,
+    Path((id, key)): Path<(String, String)>,
+    Json(value): Json<serde_json::Value>,
+) -> Result<impl IntoResponse> {
+    let tenant_id = parse_tenant_id(&id)?;
+    ensure_tenant_boundary(&actor, tenant_id)?;
+
+    service.set_setting(tenant_id, &key, value.clone()).await?;
+    Ok((StatusCode::OK, Json(value)))
+}
+
+/// Removes a setting of a tenant
+pub async fn delete_tenant_setting(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Update, resource::Tenants>,
+    Path((id, key)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let tenant_id = parse_tenant_id(&id)?;
+    ensure_tenant_boundary(&actor, tenant_id)?;
+
+    service.remove_setting(tenant_id, &key).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lists the custom domains of a tenant with their TXT challenges
+pub async fn list_tenant_domains(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Read, resource::Tenants>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let tenant_id = parse_tenant_id(&id)?;
+    ensure_tenant_boundary(&actor, tenant_id)?;
+    ensure_tenant_exists(&service, tenant_id).await?;
+
+    let domains = service.list_domains(tenant_id).await?;
+    let domains: Vec<TenantDomainResponse> = domains.into_iter().map(Into::into).collect();
+    Ok((StatusCode::OK, Json(domains)))
+}
+
+/// Claims a custom domain for a tenant, returning the TXT challenge to answer
+pub async fn claim_tenant_domain(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Update, resource::Tenants>,
+    Path(id): Path<String>,
+    Json(request): Json<TenantDomainRequest>,
+) -> Result<impl IntoResponse> {
+    let tenant_id = parse_tenant_id(&id)?;
+    ensure_tenant_boundary(&actor, tenant_id)?;
+
+    let domain = service.claim_domain(tenant_id, &request.domain).await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(TenantDomainResponse::from(domain)),
     ))
 }
 
+/// Verifies a custom domain of a tenant by looking up its TXT challenge right away
+pub async fn verify_tenant_domain(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Update, resource::Tenants>,
+    Path((id, domain)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let tenant_id = parse_tenant_id(&id)?;
+    ensure_tenant_boundary(&actor, tenant_id)?;
+
+    let domain = service.verify_domain(tenant_id, &domain).await?;
+    Ok((StatusCode::OK, Json(TenantDomainResponse::from(domain))))
+}
+
+/// Removes a custom domain of a tenant
+pub async fn delete_tenant_domain(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Update, resource::Tenants>,
+    Path((id, domain)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let tenant_id = parse_tenant_id(&id)?;
+    ensure_tenant_boundary(&actor, tenant_id)?;
+
+    service.remove_domain(tenant_id, &domain).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Parses the tenant ID of a request path
+fn parse_tenant_id(id: &str) -> Result<TenantId> {
+    Uuid::parse_str(id)
+        .map(TenantId)
+        .map_err(|e| Error::InvalidInput(format!("Invalid UUID: {}", e)))
+}
+
+/// Ensures a tenant exists, so its settings or domains are not reported empty
+async fn ensure_tenant_exists(service: &TenantService, tenant_id: TenantId) -> Result<()> {
+    match service.get_tenant(tenant_id.0).await? {
+        Some(_) => Ok(()),
+        None => Err(Error::NotFound("Tenant not found".to_string())),
+    }
+}
+
+/// Lists a page of tenants, filtered by status or a name or domain substring and sorted
+pub async fn list_tenants(
+    State(service): State<TenantService>,
+    RequirePermission(actor, _): RequirePermission<action::Read, resource::Tenants>,
+    Query(query): Query<TenantListQuery>,
+) -> Result<impl IntoResponse> {
+    TenantService::ensure_super_admin(&actor, "list")?;
+    let page = service.list_tenants_page(query).await?;
+    Ok((StatusCode::OK, Json(page)))
+}
+
+/// Resolves the tenant of a vanity URL like `/t/acme`
+///
+/// Previous slugs of renamed tenants permanently redirect to the current slug, relative
+/// to the requested URL so that the redirect works under any API prefix.
+pub async fn resolve_tenant_slug(
+    State(service): State<TenantService>,
+    Path(slug): Path<String>,
+) -> Result<impl IntoResponse> {
+    match service.resolve_slug(&slug).await? {
+        Some(SlugResolution::Renamed(Tenant {
+            slug: Some(current),
+            ..
+        })) => Ok(Redirect::permanent(&current).into_response()),
+        Some(SlugResolution::Current(tenant)) | Some(SlugResolution::Renamed(tenant)) => {
+            Ok((StatusCode::OK, Json(TenantResponse::from(tenant))).into_response())
+        },
+        None => Err(Error::NotFound("Tenant not found".to_string())),
+    }
+}
+
 /// Creates the tenant module router
-pub fn router(service: TenantService) -> Router {
+///
+/// Tenant administration requires a bearer token; only accepting invitations,
+/// resolving vanity URLs and checking domains before signing up are public.
+pub fn router(state: TenantState) -> Router {
     Router::new()
         .route("/tenants", post(create_tenant).get(list_tenants))
-        .route("/tenants/:id", get(get_tenant).put(update_tenant))
-        .with_state(service)
+        .route("/tenants/check-domain", get(check_tenant_domain))
+        .route("/tenants/:tenant_id", get(get_tenant).put(update_tenant))
+        .route("/tenants/:tenant_id/suspend", post(suspend_tenant))
+        .route("/tenants/:tenant_id/reactivate", post(reactivate_tenant))
+        .route("/tenants/:tenant_id/archive", post(archive_tenant))
+        .route("/tenant-archives", get(list_tenant_archives))
+        .route(
+            "/tenant-archives/:tenant_id/restore",
+            post(restore_archived_tenant),
+        )
+        .route("/tenants/:tenant_id/plan", put(set_tenant_plan))
+        .route("/tenants/:tenant_id/clone", post(clone_tenant))
+        .route(
+            "/tenants/:tenant_id/template",
+            post(capture_tenant_template),
+        )
+        .route(
+            "/tenant-templates",
+            get(list_tenant_templates).post(create_tenant_template),
+        )
+        .route(
+            "/tenant-templates/:id",
+            get(get_tenant_template).delete(delete_tenant_template),
+        )
+        .route("/tenants/:tenant_id/settings", get(get_tenant_settings))
+        .route(
+            "/tenants/:tenant_id/settings/:key",
+            get(get_tenant_setting)
+                .put(set_tenant_setting)
+                .delete(delete_tenant_setting),
+        )
+        .route(
+            "/tenants/:tenant_id/domains",
+            get(list_tenant_domains).post(claim_tenant_domain),
+        )
+        .route(
+            "/tenants/:tenant_id/domains/:domain",
+            delete(delete_tenant_domain),
+        )
+        .route(
+            "/tenants/:tenant_id/domains/:domain/verify",
+            post(verify_tenant_domain),
+        )
+        .route("/invitations/accept", post(accept_invitation))
+        .route("/t/:slug", get(resolve_tenant_slug))
+        .with_state(state)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::core::database::tests::create_test_db;
+    use crate::modules::identity::{
+        models::{Permission, PermissionAction, User},
+        rbac::create_admin_role,
+        repository::UserRepository,
+        session::{InMemorySessionStore, Session, SessionStore},
+    };
+    use crate::modules::tenant::repository::TenantRepository;
     use axum::{
         body::Body,
         http::{Request, StatusCode},
@@ -99,54 +548,129 @@ mod tests {
     use serde_json::json;
     use tower::ServiceExt;
 
+    /// Creates the tenant router and the token of a tenant admin who may read tenants
+    async fn tenant_admin_router(db: crate::core::database::Database) -> Result<(Router, String)> {
+        let service = TenantService::new(TenantRepository::new(db.get_pool()));
+        let tenant = service
+            .create_tenant(Tenant::new(
+                "Acme".to_string(),
+                format!("{}.example.com", Uuid::new_v4()),
+            ))
+            .await?;
+        let users = UserRepository::new(db.get_pool());
+        let mut admin = User::new(
+            tenant.id,
+            "admin@example.com".to_string(),
+            "hash".to_string(),
+        );
+        let mut role = create_admin_role();
+        role.permissions.push(Permission::new(
+            "Read Tenant".to_string(),
+            PermissionAction::Read,
+            "tenants".to_string(),
+        ));
+        admin.roles.push(role);
+        let admin = users.create_user(admin).await?;
+
+        let sessions = InMemorySessionStore::new();
+        let token = Uuid::new_v4().to_string();
+        sessions
+            .store_session(&Session::new(
+                admin.id,
+                tenant.id,
+                token.clone(),
+                time::Duration::hours(1),
+            ))
+            .await?;
+        let app = router(TenantState {
+            auth: Arc::new(AuthenticationService::new(users, Box::new(sessions))),
+            rbac: Arc::new(RbacService::new()),
+            tenants: service,
+        });
+        Ok((app, token))
+    }
+
     #[tokio::test]
     async fn test_create_tenant() -> Result<()> {
         let (db, _container) = create_test_db().await?;
-        let repository = crate::modules::tenant::repository::TenantRepository::new(db.get_pool());
-        let service = TenantService::new(repository);
-        let app = router(service).into_service();
+        let (app, token) = tenant_admin_router(db).await?;
+        let request = |token: Option<&str>| {
+            let mut builder = Request::builder()
+                .method("POST")
+                .uri("/tenants")
+                .header("Content-Type", "application/json");
+            if let Some(token) = token {
+                builder = builder.header("Authorization", format!("Bearer {}", token));
+            }
+            builder
+                .body(Body::from(
+                    json!({
+                        "name": "Test Tenant",
+                        "domain": "test.example.com"
+                    })
+                    .to_string(),
+                ))
+                .unwrap()
+        };
 
-        let response = app
-            .oneshot(
-                Request::builder()
-                    .method("POST")
-                    .uri("/tenants")
-                    .header("Content-Type", "application/json")
-                    .body(Body::from(
-                        json!({
-                            "name": "Test Tenant",
-                            "domain": "test.example.com"
-                        })
-                        .to_string(),
-                    ))
-                    .unwrap(),
-            )
-            .await
-            .unwrap();
+        let response = app.clone().oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 
-        assert_eq!(response.status(), StatusCode::CREATED);
+        // Only super admins create tenants
+        let response = app.oneshot(request(Some(&token))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
         Ok(())
     }
 
     #[tokio::test]
     async fn test_get_tenant() -> Result<()> {
         let (db, _container) = create_test_db().await?;
-        let repository = crate::modules::tenant::repository::TenantRepository::new(db.get_pool());
-        let service = TenantService::new(repository);
-        let app = router(service).into_service();
+        let (app, token) = tenant_admin_router(db).await?;
+        let request = |token: Option<&str>| {
+            let mut builder = Request::builder()
+                .method("GET")
+                .uri("/tenants/00000000-0000-0000-0000-000000000000");
+            if let Some(token) = token {
+                builder = builder.header("Authorization", format!("Bearer {}", token));
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+
+        let response = app.clone().oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Tenant admins cannot see other tenants
+        let response = app.oneshot(request(Some(&token))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_tenant_slug() -> Result<()> {
+        let (db, _container) = create_test_db().await?;
+        let service = TenantService::new(TenantRepository::new(db.get_pool()));
+        let (app, _) = tenant_admin_router(db).await?;
+        let mut globex = service
+            .create_tenant(Tenant::new(
+                "Globex".to_string(),
+                format!("{}.example.com", Uuid::new_v4()),
+            ))
+            .await?;
+        globex.slug = Some("globex-corp".to_string());
+        service.update_tenant(globex).await?;
+        let request = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
 
         let response = app
-            .oneshot(
-                Request::builder()
-                    .method("GET")
-                    .uri("/tenants/00000000-0000-0000-0000-000000000000")
-                    .body(Body::empty())
-                    .unwrap(),
-            )
+            .clone()
+            .oneshot(request("/t/globex-corp"))
             .await
             .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
 
-        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        // Previous slugs redirect relative to the requested URL, wherever the API is nested
+        let response = app.oneshot(request("/t/globex")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()["Location"], "globex-corp");
         Ok(())
     }
 }
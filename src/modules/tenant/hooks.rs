@@ -0,0 +1,220 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ring::hmac;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::{
+    core::database::DatabaseRouter,
+    modules::tenant::{
+        models::{Tenant, TenantResponse},
+        repository::TenantRepository,
+    },
+    shared::{
+        cache::{CacheInvalidation, CacheInvalidationBus},
+        error::{Error, Result},
+    },
+};
+
+/// Header carrying the HMAC-SHA256 signature of a webhook payload
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// Tenant lifecycle event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TenantEvent {
+    #[serde(rename = "tenant.created")]
+    Created,
+    #[serde(rename = "tenant.updated")]
+    Updated,
+    /// The tenant was deleted, but can still be restored
+    #[serde(rename = "tenant.deleted")]
+    Deleted,
+    #[serde(rename = "tenant.restored")]
+    Restored,
+    /// The tenant and its data were permanently removed
+    #[serde(rename = "tenant.purged")]
+    Purged,
+    /// The tenant was suspended, its users are denied access until it is reactivated
+    #[serde(rename = "tenant.suspended")]
+    Suspended,
+    #[serde(rename = "tenant.reactivated")]
+    Reactivated,
+    /// The tenant moved to another plan, changing the capabilities it can use
+    #[serde(rename = "tenant.plan_changed")]
+    PlanChanged,
+    /// The tenant's data was moved to cold storage and removed from the database
+    #[serde(rename = "tenant.archived")]
+    Archived,
+    /// The tenant's data was restored from cold storage
+    #[serde(rename = "tenant.unarchived")]
+    Unarchived,
+}
+
+/// Extension point for provisioning downstream resources alongside tenants
+#[async_trait]
+pub trait TenantHook: Send + Sync + std::fmt::Debug {
+    /// Runs after a tenant change was persisted; errors are returned to the caller
+    async fn on_tenant_event(&self, event: TenantEvent, tenant: &Tenant) -> Result<()>;
+}
+
+/// Payload delivered to tenant webhooks
+#[derive(Debug, Serialize)]
+pub struct TenantEventPayload {
+    pub event: TenantEvent,
+    pub tenant: TenantResponse,
+    pub occurred_at: OffsetDateTime,
+}
+
+/// Hook delivering tenant events to an external HTTP endpoint
+#[derive(Debug, Clone)]
+pub struct TenantWebhook {
+    url: String,
+    secret: Option<String>,
+    client: reqwest::Client,
+}
+
+impl TenantWebhook {
+    /// Creates a new webhook; payloads are signed when a secret is given
+    pub fn new(url: String, secret: Option<String>) -> Self {
+        Self {
+            url,
+            secret,
+            client: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Computes the base64 encoded HMAC-SHA256 signature of a payload
+pub fn sign_payload(secret: &str, payload: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    BASE64.encode(hmac::sign(&key, payload).as_ref())
+}
+
+#[async_trait]
+impl TenantHook for TenantWebhook {
+    async fn on_tenant_event(&self, event: TenantEvent, tenant: &Tenant) -> Result<()> {
+        let payload = serde_json::to_vec(&TenantEventPayload {
+            event,
+            tenant: TenantResponse::from(tenant.clone()),
+            occurred_at: OffsetDateTime::now_utc(),
+        })
+        .map_err(|e| Error::Internal(format!("Failed to serialize webhook payload: {}", e)))?;
+
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, sign_payload(secret, &payload));
+        }
+
+        let response = request
+            .body(payload)
+            .send()
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to deliver webhook: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(Error::Internal(format!(
+                "Webhook {} responded with status {}",
+                self.url,
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Broadcasts tenant changes so caches of all instances drop the tenant's entries
+#[async_trait]
+impl TenantHook for CacheInvalidationBus {
+    async fn on_tenant_event(&self, event: TenantEvent, tenant: &Tenant) -> Result<()> {
+        if event != TenantEvent::Created {
+            self.publish(CacheInvalidation::Tenant {
+                tenant_id: tenant.id,
+            })
+            .await;
+        }
+        Ok(())
+    }
+}
+
+/// Replicates tenants into the database of their region, whose tables reference them
+///
+/// The tenant catalog is kept in the home database; regional databases only hold
+/// their own tenants.
+#[derive(Debug, Clone)]
+pub struct RegionalTenantReplica {
+    router: DatabaseRouter,
+}
+
+impl RegionalTenantReplica {
+    /// Creates a new hook replicating into the databases of the given router
+    pub fn new(router: DatabaseRouter) -> Self {
+        Self { router }
+    }
+}
+
+#[async_trait]
+impl TenantHook for RegionalTenantReplica {
+    async fn on_tenant_event(&self, event: TenantEvent, tenant: &Tenant) -> Result<()> {
+        let Some(region) = tenant.region.as_deref() else {
+            return Ok(());
+        };
+        let repository = TenantRepository::new(self.router.region(Some(region))?.get_pool());
+        match event {
+            TenantEvent::Created => repository.create_tenant(tenant.clone()).await.map(|_| ()),
+            TenantEvent::Updated => repository.update_tenant(tenant.clone()).await.map(|_| ()),
+            TenantEvent::Deleted => repository.soft_delete_tenant(tenant.id.0).await.map(|_| ()),
+            TenantEvent::Restored => repository.restore_tenant(tenant.id.0).await.map(|_| ()),
+            TenantEvent::Purged => repository.delete_tenant(tenant.id.0).await,
+            TenantEvent::Suspended => repository
+                .suspend_tenant(tenant.id.0, tenant.suspension_reason.as_deref())
+                .await
+                .map(|_| ()),
+            TenantEvent::Reactivated => repository.reactivate_tenant(tenant.id.0).await.map(|_| ()),
+            TenantEvent::PlanChanged => repository
+                .set_plan(tenant.id.0, tenant.plan)
+                .await
+                .map(|_| ()),
+            // Only tenants without a region can be archived
+            TenantEvent::Archived | TenantEvent::Unarchived => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_payload() {
+        let signature = sign_payload("secret", b"{}");
+        assert_eq!(signature, sign_payload("secret", b"{}"));
+        assert_ne!(signature, sign_payload("other", b"{}"));
+        assert_ne!(signature, sign_payload("secret", b"[]"));
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        assert_eq!(
+            serde_json::to_string(&TenantEvent::Created).unwrap(),
+            "\"tenant.created\""
+        );
+        assert_eq!(
+            serde_json::to_string(&TenantEvent::Deleted).unwrap(),
+            "\"tenant.deleted\""
+        );
+        assert_eq!(
+            serde_json::to_string(&TenantEvent::Purged).unwrap(),
+            "\"tenant.purged\""
+        );
+        assert_eq!(
+            serde_json::to_string(&TenantEvent::Suspended).unwrap(),
+            "\"tenant.suspended\""
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    modules::signup::models::hash_token,
+    shared::types::{TenantId, UserId},
+};
+
+/// Invitation of the admin a tenant was created with
+///
+/// The admin stays inactive until they accept the invitation by choosing a password.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantInvitation {
+    pub id: Uuid,
+    pub tenant_id: TenantId,
+    pub user_id: UserId,
+    pub email: String,
+    pub token_hash: String,
+    pub expires_at: OffsetDateTime,
+    pub created_at: OffsetDateTime,
+}
+
+impl TenantInvitation {
+    /// Creates a new invitation expiring after the given time
+    pub fn new(
+        tenant_id: TenantId,
+        user_id: UserId,
+        email: String,
+        token: &str,
+        ttl: Duration,
+    ) -> Self {
+        let now = OffsetDateTime::now_utc();
+        Self {
+            id: Uuid::new_v4(),
+            tenant_id,
+            user_id,
+            email,
+            token_hash: hash_token(token),
+            expires_at: now + ttl,
+            created_at: now,
+        }
+    }
+
+    /// Checks if the invitation expired
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= OffsetDateTime::now_utc()
+    }
+}
+
+/// Generates the secret token of an invitation link
+pub fn generate_invitation_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(48)
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenant_invitation() {
+        let token = generate_invitation_token();
+        assert_eq!(token.len(), 48);
+        assert_ne!(token, generate_invitation_token());
+
+        let invitation = TenantInvitation::new(
+            TenantId::new(),
+            UserId::new(),
+            "admin@acme.com".to_string(),
+            &token,
+            Duration::hours(72),
+        );
+        assert!(!invitation.is_expired());
+        assert_eq!(invitation.token_hash, hash_token(&token));
+
+        let expired = TenantInvitation::new(
+            TenantId::new(),
+            UserId::new(),
+            "admin@acme.com".to_string(),
+            &token,
+            Duration::hours(-1),
+        );
+        assert!(expired.is_expired());
+    }
+}
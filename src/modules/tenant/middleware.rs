@@ -0,0 +1,344 @@
+use async_trait::async_trait;
+use axum::{
+    body::Body,
+    extract::FromRequestParts,
+    http::{header::HOST, request::Parts, HeaderMap, Request},
+    response::{IntoResponse, Response},
+};
+use futures::future::BoxFuture;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+use uuid::Uuid;
+
+use crate::{
+    modules::tenant::{
+        domains::normalize_domain,
+        models::{SlugResolution, Tenant},
+        plans::{ensure_capability, Capability},
+        service::TenantService,
+    },
+    shared::error::{Error, Result},
+};
+
+/// Header API clients name their tenant with instead of the host
+pub const TENANT_ID_HEADER: &str = "x-tenant-id";
+
+/// Tenant a request was made for, resolved by the [`TenantResolutionLayer`]
+#[derive(Debug, Clone)]
+pub struct CurrentTenant(pub Tenant);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CurrentTenant
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self> {
+        parts
+            .extensions
+            .get::<CurrentTenant>()
+            .cloned()
+            .ok_or_else(|| Error::Internal("Tenant of the request was not resolved".to_string()))
+    }
+}
+
+/// Resolves the tenant of a request from its `X-Tenant-ID` or `Host` header
+#[derive(Debug, Clone)]
+pub struct TenantResolver {
+    service: TenantService,
+    /// Parent domain of tenant subdomains, e.g. `acci.example.com` for `acme.acci.example.com`
+    base_domain: Option<String>,
+}
+
+impl TenantResolver {
+    /// Creates a resolver looking tenants up by their domains
+    pub fn new(service: TenantService) -> Self {
+        Self {
+            service,
+            base_domain: None,
+        }
+    }
+
+    /// Also resolves subdomains of the base domain by the slug of their tenant
+    pub fn with_base_domain(mut self, base_domain: String) -> Self {
+        self.base_domain = Some(base_domain.trim_end_matches('.').to_lowercase());
+        self
+    }
+
+    /// Resolves the active tenant of a request
+    ///
+    /// An `X-Tenant-ID` header takes precedence over the host, which is looked up as
+    /// a subdomain of the base domain, a verified custom domain or a tenant's domain.
+    pub async fn resolve(&self, headers: &HeaderMap) -> Result<Tenant> {
+        let tenant = match headers.get(TENANT_ID_HEADER) {
+            Some(value) => {
+                let id = value
+                    .to_str()
+                    .ok()
+                    .and_then(|value| Uuid::parse_str(value.trim()).ok())
+                    .ok_or_else(|| {
+                        Error::InvalidInput(format!("Invalid {} header", TENANT_ID_HEADER))
+                    })?;
+                self.service.get_tenant(id).await?
+            },
+            None => {
+                let host = host_name(headers).ok_or_else(|| {
+                    Error::InvalidInput("Missing or invalid Host header".to_string())
+                })?;
+                self.resolve_host(&host).await?
+            },
+        };
+        let tenant = tenant.ok_or_else(|| Error::NotFound("Unknown tenant".to_string()))?;
+
+        if !tenant.active {
+            return Err(Error::Authorization("Tenant is inactive".to_string()));
+        }
+        match (&tenant.suspended_at, &tenant.suspension_reason) {
+            (None, _) => Ok(tenant),
+            (Some(_), Some(reason)) => Err(Error::TenantSuspended(format!(
+                "Tenant is suspended: {}",
+                reason
+            ))),
+            (Some(_), None) => Err(Error::TenantSuspended("Tenant is suspended".to_string())),
+        }
+    }
+
+    /// Looks up the tenant of a host name
+    async fn resolve_host(&self, host: &str) -> Result<Option<Tenant>> {
+        if let Some(slug) = self
+            .base_domain
+            .as_deref()
+            .and_then(|base_domain| subdomain_slug(host, base_domain))
+        {
+            return Ok(self
+                .service
+                .resolve_slug(slug)
+                .await?
+                .map(|resolution| match resolution {
+                    SlugResolution::Current(tenant) | SlugResolution::Renamed(tenant) => tenant,
+                }));
+        }
+        if let Some(tenant) = self.service.resolve_domain(host).await? {
+            return Ok(Some(tenant));
+        }
+        self.service.get_tenant_by_domain(host).await
+    }
+}
+
+/// Gets the normalized host name of a request without its port
+fn host_name(headers: &HeaderMap) -> Option<String> {
+    let host = headers.get(HOST)?.to_str().ok()?;
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+    normalize_domain(host).ok()
+}
+
+/// Gets the tenant slug of a direct subdomain of the base domain
+fn subdomain_slug<'a>(host: &'a str, base_domain: &str) -> Option<&'a str> {
+    let slug = host.strip_suffix(base_domain)?.strip_suffix('.')?;
+    (!slug.is_empty() && !slug.contains('.')).then_some(slug)
+}
+
+/// Layer resolving the tenant of every request, rejecting requests for unknown or inactive tenants
+///
+/// Handlers get the tenant with the [`CurrentTenant`] extractor.
+#[derive(Debug, Clone)]
+pub struct TenantResolutionLayer {
+    resolver: TenantResolver,
+}
+
+impl TenantResolutionLayer {
+    /// Creates a layer resolving tenants with the given resolver
+    pub fn new(resolver: TenantResolver) -> Self {
+        Self { resolver }
+    }
+}
+
+impl<S> Layer<S> for TenantResolutionLayer {
+    type Service = TenantResolution<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TenantResolution {
+            inner,
+            resolver: self.resolver.clone(),
+        }
+    }
+}
+
+/// Service resolving the tenant of a request before passing it on
+#[derive(Debug, Clone)]
+pub struct TenantResolution<S> {
+    inner: S,
+    resolver: TenantResolver,
+}
+
+impl<S> Service<Request<Body>> for TenantResolution<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, std::result::Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<Body>) -> Self::Future {
+        // Keep the inner service that was polled ready for this request
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let resolver = self.resolver.clone();
+        Box::pin(async move {
+            match resolver.resolve(request.headers()).await {
+                Ok(tenant) => {
+                    request.extensions_mut().insert(CurrentTenant(tenant));
+                    inner.call(request).await
+                },
+                Err(e) => Ok(e.into_response()),
+            }
+        })
+    }
+}
+
+/// Layer restricting routes to tenants whose plan includes a capability
+///
+/// Must run within the [`TenantResolutionLayer`], whose resolved tenant it checks;
+/// tenants on lower plans get an upgrade required error.
+#[derive(Debug, Clone, Copy)]
+pub struct PlanGateLayer {
+    capability: Capability,
+}
+
+impl PlanGateLayer {
+    /// Creates a layer admitting tenants whose plan includes the capability
+    pub fn new(capability: Capability) -> Self {
+        Self { capability }
+    }
+}
+
+impl<S> Layer<S> for PlanGateLayer {
+    type Service = PlanGate<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PlanGate {
+            inner,
+            capability: self.capability,
+        }
+    }
+}
+
+/// Service passing on requests of tenants whose plan includes a capability
+#[derive(Debug, Clone)]
+pub struct PlanGate<S> {
+    inner: S,
+    capability: Capability,
+}
+
+impl<S> Service<Request<Body>> for PlanGate<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, std::result::Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let allowed = match request.extensions().get::<CurrentTenant>() {
+            Some(CurrentTenant(tenant)) => ensure_capability(tenant, self.capability),
+            None => Err(Error::Internal(
+                "Tenant of the request was not resolved".to_string(),
+            )),
+        };
+        if let Err(e) = allowed {
+            return Box::pin(async move { Ok(e.into_response()) });
+        }
+        // Keep the inner service that was polled ready for this request
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move { inner.call(request).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tenant::plans::TenantPlan;
+    use axum::{
+        http::{HeaderValue, StatusCode},
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_host_name() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(host_name(&headers), None);
+
+        headers.insert(HOST, HeaderValue::from_static("Login.Acme.com:8443"));
+        assert_eq!(host_name(&headers).as_deref(), Some("login.acme.com"));
+        headers.insert(HOST, HeaderValue::from_static("acme.com."));
+        assert_eq!(host_name(&headers).as_deref(), Some("acme.com"));
+        headers.insert(HOST, HeaderValue::from_static("[::1]:8080"));
+        assert_eq!(host_name(&headers), None);
+    }
+
+    #[test]
+    fn test_subdomain_slug() {
+        let base_domain = "acci.example.com";
+        assert_eq!(
+            subdomain_slug("acme.acci.example.com", base_domain),
+            Some("acme")
+        );
+        assert_eq!(subdomain_slug("acci.example.com", base_domain), None);
+        assert_eq!(
+            subdomain_slug("login.acme.acci.example.com", base_domain),
+            None
+        );
+        assert_eq!(
+            subdomain_slug("acme.notacci.example.com", base_domain),
+            None
+        );
+        assert_eq!(subdomain_slug("acme.example.com", base_domain), None);
+    }
+
+    #[tokio::test]
+    async fn test_plan_gate() {
+        let app = Router::new()
+            .route("/sso", get(|| async { "ok" }))
+            .layer(PlanGateLayer::new(Capability::Sso));
+        let request = |plan: TenantPlan| {
+            let mut tenant = Tenant::new("Acme".to_string(), "acme.com".to_string());
+            tenant.plan = plan;
+            let mut request = Request::builder().uri("/sso").body(Body::empty()).unwrap();
+            request.extensions_mut().insert(CurrentTenant(tenant));
+            request
+        };
+
+        let response = app
+            .clone()
+            .oneshot(request(TenantPlan::Free))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+        let response = app.clone().oneshot(request(TenantPlan::Pro)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Without the resolution layer there is no tenant to check
+        let response = app
+            .oneshot(Request::builder().uri("/sso").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
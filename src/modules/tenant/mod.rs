@@ -1,9 +1,22 @@
+pub mod archive;
+pub mod domains;
 mod handlers;
+pub mod hooks;
+pub mod invitations;
+pub mod middleware;
 pub mod models;
+pub mod plans;
 pub mod repository;
 pub mod service;
+pub mod settings;
+pub mod slug;
+pub mod templates;
 
-use crate::{core::database::Database, shared::error::Result, shared::types::TenantId};
+use crate::{
+    core::{bootstrap::SsoProviderSpec, config::TenantConfig, database::Database},
+    modules::identity::{rbac::RbacService, AuthenticationService},
+    shared::error::Result,
+};
 use axum::Router;
 
 /// Tenant module for managing tenants
@@ -20,52 +33,224 @@ impl TenantModule {
         }
     }
 
-    /// Gets the router for this module
-    pub fn router(&self) -> Result<Router> {
-        Ok(handlers::router(self.service.clone()))
+    /// Sets the data residency regions tenants can be assigned to
+    pub fn with_regions(mut self, regions: Vec<String>) -> Self {
+        self.service = self.service.with_regions(regions);
+        self
+    }
+
+    /// Sets the SSO provider every provisioned tenant starts with
+    pub fn with_default_sso_provider(mut self, provider: SsoProviderSpec) -> Self {
+        self.service = self.service.with_default_sso_provider(provider);
+        self
+    }
+
+    /// Caches tenant settings for `ttl` and distributes their changes to all instances via the bus
+    pub fn with_settings_cache(
+        mut self,
+        ttl: std::time::Duration,
+        invalidation: crate::shared::cache::CacheInvalidationBus,
+    ) -> Self {
+        self.service = self.service.with_settings_cache(ttl, invalidation);
+        self
+    }
+
+    /// Sets the resolver custom domain challenges are looked up with
+    pub fn with_txt_resolver(mut self, resolver: std::sync::Arc<dyn domains::TxtResolver>) -> Self {
+        self.service = self.service.with_txt_resolver(resolver);
+        self
+    }
+
+    /// Uses the given mailer for admin invitations
+    pub fn with_mailer(mut self, mailer: std::sync::Arc<dyn crate::shared::mail::Mailer>) -> Self {
+        self.service = self.service.with_mailer(mailer);
+        self
+    }
+
+    /// Sets the link and lifetime of admin invitations from the configuration
+    pub fn with_invitations(mut self, config: &TenantConfig) -> Self {
+        self.service = self.service.with_invitations(
+            config.invitation_url.clone(),
+            time::Duration::hours(config.invitation_ttl_hours),
+        );
+        self
+    }
+
+    /// Enables archiving tenants to the given object storage
+    pub fn with_archive_store(mut self, store: std::sync::Arc<dyn archive::ArchiveStore>) -> Self {
+        self.service = self.service.with_archive_store(store);
+        self
+    }
+
+    /// Archives tenants below the configured directory, if any
+    pub fn with_archives(self, config: &TenantConfig) -> Self {
+        match &config.archive_dir {
+            Some(dir) => {
+                self.with_archive_store(std::sync::Arc::new(archive::FileArchiveStore::new(dir)))
+            },
+            None => self,
+        }
+    }
+
+    /// Delivers tenant lifecycle events to the configured webhooks
+    pub fn with_webhooks(mut self, config: &TenantConfig) -> Self {
+        for webhook in &config.webhooks {
+            self.register_hook(std::sync::Arc::new(hooks::TenantWebhook::new(
+                webhook.url.clone(),
+                webhook.secret.clone(),
+            )));
+        }
+        self
+    }
+
+    /// Registers a hook that is notified about tenant lifecycle changes
+    pub fn register_hook(&mut self, hook: std::sync::Arc<dyn hooks::TenantHook>) {
+        self.service.register_hook(hook);
+    }
+
+    /// Purges deleted tenants once their retention period ended
+    ///
+    /// Must be called within the Tokio runtime.
+    pub fn spawn_purge_worker(&self, config: &TenantConfig) -> tokio::task::JoinHandle<()> {
+        self.service.clone().spawn_purge_worker(
+            time::Duration::days(config.deleted_retention_days.into()),
+            std::time::Duration::from_secs(config.purge_interval_secs),
+        )
+    }
+
+    /// Verifies pending custom domains once their TXT challenge is answered
+    ///
+    /// Must be called within the Tokio runtime.
+    pub fn spawn_domain_verification_worker(
+        &self,
+        config: &TenantConfig,
+    ) -> tokio::task::JoinHandle<()> {
+        self.service.clone().spawn_domain_verification_worker(
+            std::time::Duration::from_secs(config.domain_verification_interval_secs),
+            time::Duration::days(config.domain_claim_ttl_days.into()),
+        )
+    }
+
+    /// Creates the layer resolving the tenant of each request from its host or `X-Tenant-ID` header
+    pub fn resolution_layer(&self, config: &TenantConfig) -> middleware::TenantResolutionLayer {
+        let mut resolver = middleware::TenantResolver::new(self.service.clone());
+        if let Some(base_domain) = &config.base_domain {
+            resolver = resolver.with_base_domain(base_domain.clone());
+        }
+        middleware::TenantResolutionLayer::new(resolver)
+    }
+
+    /// Creates the layer restricting routes to tenants whose plan includes the capability
+    ///
+    /// Apply it to feature routers below the [`resolution_layer`](Self::resolution_layer), e.g.
+    /// `sso_router.layer(module.plan_gate(Capability::Sso))`.
+    pub fn plan_gate(&self, capability: plans::Capability) -> middleware::PlanGateLayer {
+        middleware::PlanGateLayer::new(capability)
+    }
+
+    /// Gets the router for this module, authenticating its users with the identity module
+    pub fn router(
+        &self,
+        auth: std::sync::Arc<AuthenticationService>,
+        rbac: std::sync::Arc<RbacService>,
+    ) -> Result<Router> {
+        Ok(handlers::router(handlers::TenantState {
+            auth,
+            rbac,
+            tenants: self.service.clone(),
+        }))
     }
 }
 
 /// Creates a router for the tenant module
-pub fn router(db: Database) -> Result<Router> {
+pub fn router(
+    db: Database,
+    auth: std::sync::Arc<AuthenticationService>,
+    rbac: std::sync::Arc<RbacService>,
+) -> Result<Router> {
     let module = TenantModule::new(db);
-    module.router()
+    module.router(auth, rbac)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::core::database::tests::create_test_db;
+    use crate::modules::identity::{
+        models::User,
+        rbac::create_super_admin_role,
+        repository::UserRepository,
+        session::{InMemorySessionStore, Session, SessionStore},
+    };
     use axum::{
         body::Body,
         http::{Request, StatusCode},
     };
     use serde_json::json;
+    use std::sync::Arc;
     use tower::ServiceExt;
 
+    /// Creates the tenant router and the bearer token of a super admin session
+    async fn super_admin_router(db: Database) -> Result<(Router, String)> {
+        let module = TenantModule::new(db.clone());
+        let tenant = module
+            .service
+            .create_tenant(models::Tenant::new(
+                "Operator".to_string(),
+                format!("{}.example.com", uuid::Uuid::new_v4()),
+            ))
+            .await?;
+        let users = UserRepository::new(db.get_pool());
+        let mut admin = User::new(
+            tenant.id,
+            "operator@example.com".to_string(),
+            "hash".to_string(),
+        );
+        admin.roles.push(create_super_admin_role());
+        let admin = users.create_user(admin).await?;
+
+        let sessions = InMemorySessionStore::new();
+        let token = uuid::Uuid::new_v4().to_string();
+        sessions
+            .store_session(&Session::new(
+                admin.id,
+                tenant.id,
+                token.clone(),
+                time::Duration::hours(1),
+            ))
+            .await?;
+        let auth = Arc::new(AuthenticationService::new(users, Box::new(sessions)));
+        Ok((module.router(auth, Arc::new(RbacService::new()))?, token))
+    }
+
     #[tokio::test]
     async fn test_create_tenant() -> Result<()> {
         let (db, _container) = create_test_db().await?;
-        let app = router(db)?.into_service();
+        let (app, token) = super_admin_router(db).await?;
+        let request = |token: Option<&str>| {
+            let mut builder = Request::builder()
+                .method("POST")
+                .uri("/tenants")
+                .header("Content-Type", "application/json");
+            if let Some(token) = token {
+                builder = builder.header("Authorization", format!("Bearer {}", token));
+            }
+            builder
+                .body(Body::from(
+                    json!({
+                        "name": "Test Tenant",
+                        "domain": "test.example.com"
+                    })
+                    .to_string(),
+                ))
+                .unwrap()
+        };
 
-        let response = app
-            .oneshot(
-                Request::builder()
-                    .method("POST")
-                    .uri("/tenants")
-                    .header("Content-Type", "application/json")
-                    .body(Body::from(
-                        json!({
-                            "name": "Test Tenant",
-                            "domain": "test.example.com"
-                        })
-                        .to_string(),
-                    ))
-                    .unwrap(),
-            )
-            .await
-            .unwrap();
+        // Tenant administration requires authentication
+        let response = app.clone().oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 
+        let response = app.oneshot(request(Some(&token))).await.unwrap();
         assert_eq!(response.status(), StatusCode::CREATED);
         Ok(())
     }
@@ -73,20 +258,104 @@ mod tests {
     #[tokio::test]
     async fn test_get_tenant() -> Result<()> {
         let (db, _container) = create_test_db().await?;
-        let app = router(db)?.into_service();
+        let (app, token) = super_admin_router(db).await?;
+        let request = |token: Option<&str>| {
+            let mut builder = Request::builder()
+                .method("GET")
+                .uri("/tenants/00000000-0000-0000-0000-000000000000");
+            if let Some(token) = token {
+                builder = builder.header("Authorization", format!("Bearer {}", token));
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+
+        let response = app.clone().oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 
+        let response = app.clone().oneshot(request(Some(&token))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Checking a domain before signing up stays public
         let response = app
             .oneshot(
                 Request::builder()
-                    .method("GET")
-                    .uri("/tenants/00000000-0000-0000-0000-000000000000")
+                    .uri("/tenants/check-domain?domain=free.example.com")
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
+        assert_ne!(response.status(), StatusCode::UNAUTHORIZED);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tenant_resolution() -> Result<()> {
+        let (db, _container) = create_test_db().await?;
+        let module = TenantModule::new(db);
+        let tenant = module
+            .service
+            .create_tenant(models::Tenant::new(
+                "Acme".to_string(),
+                format!("{}.example.com", uuid::Uuid::new_v4()),
+            ))
+            .await?;
+        let config = TenantConfig {
+            base_domain: Some("acci.example.com".to_string()),
+            ..TenantConfig::default()
+        };
+        let app = Router::new()
+            .route(
+                "/whoami",
+                axum::routing::get(
+                    |middleware::CurrentTenant(tenant): middleware::CurrentTenant| async move {
+                        tenant.id.0.to_string()
+                    },
+                ),
+            )
+            .layer(module.resolution_layer(&config));
+
+        let request = |header: &str, value: &str| {
+            Request::builder()
+                .uri("/whoami")
+                .header(header, value)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let read_body = |response: axum::response::Response| async move {
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            String::from_utf8(bytes.to_vec()).unwrap()
+        };
+
+        // By the tenant's domain, its subdomain of the base domain or the tenant ID header
+        for (header, value) in [
+            ("Host", format!("{}:8080", tenant.domain)),
+            ("Host", "acme.acci.example.com".to_string()),
+            (middleware::TENANT_ID_HEADER, tenant.id.0.to_string()),
+        ] {
+            let response = app.clone().oneshot(request(header, &value)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(read_body(response).await, tenant.id.0.to_string());
+        }
 
+        let response = app
+            .clone()
+            .oneshot(request("Host", "unknown.example.com"))
+            .await
+            .unwrap();
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app
+            .clone()
+            .oneshot(request(middleware::TENANT_ID_HEADER, "not-a-uuid"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        module.service.suspend_tenant(tenant.id.0, None).await?;
+        let response = app.oneshot(request("Host", &tenant.domain)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
         Ok(())
     }
 }
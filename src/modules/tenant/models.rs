@@ -2,7 +2,13 @@ use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
-use crate::shared::types::TenantId;
+use crate::{
+    modules::{
+        identity::models::User,
+        tenant::{domains::TenantDomain, invitations::TenantInvitation, plans::TenantPlan},
+    },
+    shared::types::TenantId,
+};
 
 /// Tenant model
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,9 +16,26 @@ pub struct Tenant {
     pub id: TenantId,
     pub name: String,
     pub domain: String,
+    /// URL-safe vanity slug, e.g. for login URLs like `/t/acme`
+    #[serde(default)]
+    pub slug: Option<String>,
+    /// Data residency region, e.g. `eu`; `None` keeps the tenant's data in the home region
+    #[serde(default)]
+    pub region: Option<String>,
     pub active: bool,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
+    /// When the tenant was deleted; it can be restored until it is purged
+    #[serde(default)]
+    pub deleted_at: Option<OffsetDateTime>,
+    /// When the tenant was suspended; its users cannot access it until it is reactivated
+    #[serde(default)]
+    pub suspended_at: Option<OffsetDateTime>,
+    #[serde(default)]
+    pub suspension_reason: Option<String>,
+    /// Plan deciding which capabilities the tenant can use
+    #[serde(default)]
+    pub plan: TenantPlan,
 }
 
 impl Tenant {
@@ -22,29 +45,139 @@ impl Tenant {
             id: TenantId::new(),
             name,
             domain,
+            slug: None,
+            region: None,
             active: true,
             created_at: OffsetDateTime::now_utc(),
             updated_at: OffsetDateTime::now_utc(),
+            deleted_at: None,
+            suspended_at: None,
+            suspension_reason: None,
+            plan: TenantPlan::default(),
         }
     }
+
+    /// Checks if the tenant was deleted and awaits being purged
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// Checks if the tenant is suspended
+    ///
+    /// Unlike inactive tenants, suspended tenants are known to their users, who
+    /// are told why their access is denied.
+    pub fn is_suspended(&self) -> bool {
+        self.suspended_at.is_some()
+    }
 }
 
 /// Tenant request model
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TenantRequest {
     pub name: String,
     pub domain: Option<String>,
+    /// Vanity slug, derived from the name on creation and kept on updates if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slug: Option<String>,
+    /// Data residency region; fixed once the tenant is created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Email address of the first admin, who is invited on creation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_email: Option<String>,
+    /// Plan on creation, the free plan if not given; changed with its own endpoint afterwards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<TenantPlan>,
+    /// Template the tenant is created from, whose plan replaces the given one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_id: Option<Uuid>,
 }
 
 /// Tenant response model
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TenantResponse {
     pub id: Uuid,
     pub name: String,
     pub domain: Option<String>,
+    pub slug: Option<String>,
+    pub region: Option<String>,
     pub active: bool,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<OffsetDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suspended_at: Option<OffsetDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suspension_reason: Option<String>,
+    pub plan: TenantPlan,
+    /// Invitation sent to the first admin if the tenant was created with one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_invitation: Option<AdminInvitationResponse>,
+}
+
+/// Invitation of a tenant's first admin
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminInvitationResponse {
+    pub email: String,
+    pub expires_at: OffsetDateTime,
+}
+
+impl From<TenantInvitation> for AdminInvitationResponse {
+    fn from(invitation: TenantInvitation) -> Self {
+        Self {
+            email: invitation.email,
+            expires_at: invitation.expires_at,
+        }
+    }
+}
+
+/// Request accepting an admin invitation
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InvitationAcceptRequest {
+    pub token: String,
+    pub password: String,
+}
+
+/// Admin whose invitation was accepted, ready to log in
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InvitationAcceptResponse {
+    pub tenant_id: TenantId,
+    pub email: String,
+}
+
+/// Query of the pre-flight check of a tenant domain
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DomainCheckQuery {
+    pub domain: String,
+}
+
+/// Result of the pre-flight check of a tenant domain
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DomainCheckResponse {
+    /// Domain as it would be stored
+    pub domain: String,
+    pub available: bool,
+}
+
+/// Request capturing a tenant's configuration as a template
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TemplateCaptureRequest {
+    pub name: String,
+}
+
+/// Plan change request model
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TenantPlanRequest {
+    pub plan: TenantPlan,
+}
+
+/// Tenant suspension request model
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TenantSuspensionRequest {
+    /// Reason shown to the tenant's users when their access is denied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
 }
 
 impl From<Tenant> for TenantResponse {
@@ -53,9 +186,16 @@ impl From<Tenant> for TenantResponse {
             id: tenant.id.0,
             name: tenant.name,
             domain: Some(tenant.domain),
+            slug: tenant.slug,
+            region: tenant.region,
             active: tenant.active,
             created_at: tenant.created_at,
             updated_at: tenant.updated_at,
+            deleted_at: tenant.deleted_at,
+            suspended_at: tenant.suspended_at,
+            suspension_reason: tenant.suspension_reason,
+            plan: tenant.plan,
+            admin_invitation: None,
         }
     }
 }
@@ -67,13 +207,138 @@ impl From<TenantRequest> for Tenant {
             id: TenantId::new(),
             name: request.name,
             domain: request.domain.unwrap_or_default(),
+            slug: request.slug,
+            region: request.region,
             active: true,
             created_at: now,
             updated_at: now,
+            deleted_at: None,
+            suspended_at: None,
+            suspension_reason: None,
+            plan: request.plan.unwrap_or_default(),
+        }
+    }
+}
+
+/// Attribute tenant lists can be sorted by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TenantSort {
+    Name,
+    Domain,
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl TenantSort {
+    /// Gets the column the tenants are sorted by
+    pub fn column(&self) -> &'static str {
+        match self {
+            TenantSort::Name => "name",
+            TenantSort::Domain => "domain",
+            TenantSort::CreatedAt => "created_at",
+            TenantSort::UpdatedAt => "updated_at",
         }
     }
 }
 
+/// Direction tenant lists are sorted in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Query parameters of the tenant list
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TenantListQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    /// Only active or inactive tenants; both if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active: Option<bool>,
+    /// Case-insensitive substring of the name or domain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+    /// Newest tenants first if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<TenantSort>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<SortOrder>,
+}
+
+/// Page of the tenant list
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TenantPage {
+    pub tenants: Vec<TenantResponse>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+}
+
+/// Tenant onboarded with its first admin, ready for the admin to log in
+#[derive(Debug, Clone)]
+pub struct TenantOnboarding {
+    pub tenant: Tenant,
+    /// Admin holding the tenant's admin role
+    pub admin: User,
+    /// ID of the tenant's default SSO provider, if one is configured
+    pub sso_provider_id: Option<Uuid>,
+}
+
+/// Custom domain claim request model
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TenantDomainRequest {
+    pub domain: String,
+}
+
+/// Custom domain response model
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TenantDomainResponse {
+    pub domain: String,
+    pub verified: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified_at: Option<OffsetDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_checked_at: Option<OffsetDateTime>,
+    /// TXT record the tenant has to create to verify the domain
+    pub challenge: DomainChallenge,
+}
+
+/// TXT record proving a tenant controls a domain
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DomainChallenge {
+    pub name: String,
+    pub value: String,
+}
+
+impl From<TenantDomain> for TenantDomainResponse {
+    fn from(domain: TenantDomain) -> Self {
+        Self {
+            verified: domain.is_verified(),
+            challenge: DomainChallenge {
+                name: domain.challenge_name(),
+                value: domain.challenge_value(),
+            },
+            domain: domain.domain,
+            verified_at: domain.verified_at,
+            last_checked_at: domain.last_checked_at,
+        }
+    }
+}
+
+/// Tenant found by a vanity slug
+#[derive(Debug, Clone)]
+pub enum SlugResolution {
+    /// The slug is the tenant's current one
+    Current(Tenant),
+    /// The slug is a previous one of the tenant, which was renamed since
+    Renamed(Tenant),
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -87,6 +352,8 @@ mod tests {
         assert_eq!(tenant.name, name);
         assert_eq!(tenant.domain, domain);
         assert!(tenant.active);
+        assert!(!tenant.is_deleted());
+        assert!(!tenant.is_suspended());
     }
 
     #[test]
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    modules::tenant::models::Tenant,
+    shared::error::{Error, Result},
+};
+
+/// Subscription plan of a tenant, deciding which capabilities it can use
+///
+/// Plans are ordered, each one including the capabilities of the plans below it.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TenantPlan {
+    #[default]
+    Free,
+    Pro,
+    Enterprise,
+}
+
+impl TenantPlan {
+    /// Checks if the plan includes a capability
+    pub fn allows(self, capability: Capability) -> bool {
+        self >= capability.minimum_plan()
+    }
+}
+
+impl std::fmt::Display for TenantPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TenantPlan::Free => write!(f, "free"),
+            TenantPlan::Pro => write!(f, "pro"),
+            TenantPlan::Enterprise => write!(f, "enterprise"),
+        }
+    }
+}
+
+impl std::str::FromStr for TenantPlan {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "free" => Ok(TenantPlan::Free),
+            "pro" => Ok(TenantPlan::Pro),
+            "enterprise" => Ok(TenantPlan::Enterprise),
+            _ => Err(Error::InvalidInput(format!("Invalid plan: {}", s))),
+        }
+    }
+}
+
+/// Feature restricted to tenants on higher plans
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Logins through SAML or OIDC identity providers
+    Sso,
+    /// User and group provisioning through SCIM
+    Scim,
+    /// Roles besides the built-in admin and user roles
+    CustomRoles,
+}
+
+impl Capability {
+    /// Gets the lowest plan including the capability
+    pub fn minimum_plan(self) -> TenantPlan {
+        match self {
+            Capability::Sso | Capability::CustomRoles => TenantPlan::Pro,
+            Capability::Scim => TenantPlan::Enterprise,
+        }
+    }
+}
+
+impl std::fmt::Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Capability::Sso => write!(f, "SSO"),
+            Capability::Scim => write!(f, "SCIM"),
+            Capability::CustomRoles => write!(f, "Custom roles"),
+        }
+    }
+}
+
+/// Ensures a tenant's plan includes a capability, asking for an upgrade otherwise
+pub fn ensure_capability(tenant: &Tenant, capability: Capability) -> Result<()> {
+    if tenant.plan.allows(capability) {
+        return Ok(());
+    }
+    Err(Error::UpgradeRequired(format!(
+        "{} requires the {} plan, the tenant is on the {} plan",
+        capability,
+        capability.minimum_plan(),
+        tenant.plan
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_capabilities() {
+        assert!(!TenantPlan::Free.allows(Capability::Sso));
+        assert!(TenantPlan::Pro.allows(Capability::Sso));
+        assert!(TenantPlan::Pro.allows(Capability::CustomRoles));
+        assert!(!TenantPlan::Pro.allows(Capability::Scim));
+        assert!(TenantPlan::Enterprise.allows(Capability::Scim));
+
+        for plan in [TenantPlan::Free, TenantPlan::Pro, TenantPlan::Enterprise] {
+            assert_eq!(plan.to_string().parse::<TenantPlan>().unwrap(), plan);
+        }
+        assert!("premium".parse::<TenantPlan>().is_err());
+    }
+
+    #[test]
+    fn test_ensure_capability() {
+        let mut tenant = Tenant::new("Acme".to_string(), "acme.com".to_string());
+        match ensure_capability(&tenant, Capability::Scim) {
+            Err(Error::UpgradeRequired(message)) => assert_eq!(
+                message,
+                "SCIM requires the enterprise plan, the tenant is on the free plan"
+            ),
+            other => panic!("Expected an upgrade required error, got {:?}", other),
+        }
+
+        tenant.plan = TenantPlan::Enterprise;
+        assert!(ensure_capability(&tenant, Capability::Scim).is_ok());
+    }
+}
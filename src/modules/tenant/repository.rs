@@ -1,25 +1,296 @@
-use sqlx::{Pool, Postgres as PgPool};
-use std::time::Duration;
-use time::{OffsetDateTime, PrimitiveDateTime};
+use sqlx::{PgConnection, Pool, Postgres as PgPool, Transaction};
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 use crate::{
     core::database::Database,
-    modules::tenant::models::Tenant,
+    modules::tenant::{
+        archive::ArchiveManifest,
+        domains::TenantDomain,
+        invitations::TenantInvitation,
+        models::{SortOrder, Tenant, TenantSort},
+        plans::TenantPlan,
+        settings::TenantSettings,
+        templates::TenantTemplate,
+    },
     shared::{
         error::{Error, Result},
-        types::TenantId,
+        types::{TenantId, UserId},
     },
 };
 
-/// Helper function to convert Option<OffsetDateTime> to Option<PrimitiveDateTime>
-fn to_primitive_datetime(dt: OffsetDateTime) -> PrimitiveDateTime {
-    PrimitiveDateTime::new(dt.date(), dt.time())
+/// Reads a stored plan; the column is constrained to known plans, anything else gets the free plan
+fn to_plan(plan: &str) -> TenantPlan {
+    plan.parse().unwrap_or_default()
+}
+
+/// Serializes a JSON column of a template
+fn to_json<T: serde::Serialize>(value: &T) -> Result<serde_json::Value> {
+    serde_json::to_value(value)
+        .map_err(|e| Error::Internal(format!("Failed to serialize tenant template: {}", e)))
+}
+
+/// Reads a stored JSON column of a template
+fn from_json<T: serde::de::DeserializeOwned>(value: serde_json::Value) -> Result<T> {
+    serde_json::from_value(value)
+        .map_err(|e| Error::Internal(format!("Invalid stored tenant template: {}", e)))
+}
+
+/// Reads the stored row counts of an archive manifest
+fn from_row_counts(value: serde_json::Value) -> Result<std::collections::BTreeMap<String, i64>> {
+    serde_json::from_value(value)
+        .map_err(|e| Error::Internal(format!("Invalid stored archive row counts: {}", e)))
+}
+
+/// Escapes the wildcards of a `LIKE` pattern, so the text only matches itself
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Inserts a tenant
+///
+/// Runs on a connection so callers can create tenants within their own transaction.
+pub(crate) async fn insert_tenant(conn: &mut PgConnection, tenant: &Tenant) -> Result<Tenant> {
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO tenants (id, name, domain, slug, region, active, created_at, updated_at, plan)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING id, name, domain, slug, region, active, created_at, updated_at, deleted_at,
+            suspended_at, suspension_reason, plan
+        "#,
+        tenant.id.0 as uuid::Uuid,
+        tenant.name,
+        tenant.domain,
+        tenant.slug,
+        tenant.region,
+        tenant.active,
+        tenant.created_at,
+        tenant.updated_at,
+        tenant.plan.to_string(),
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(Tenant {
+        id: tenant.id,
+        name: row.name,
+        domain: row.domain,
+        slug: row.slug,
+        region: row.region,
+        active: row.active,
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+        deleted_at: row.deleted_at,
+        suspended_at: row.suspended_at,
+        suspension_reason: row.suspension_reason,
+        plan: to_plan(&row.plan),
+    })
+}
+
+/// Sets a setting of a tenant, replacing its previous value
+///
+/// Runs on a connection so tenants can be provisioned with their settings in one transaction.
+pub(crate) async fn upsert_setting(
+    conn: &mut PgConnection,
+    tenant_id: TenantId,
+    key: &str,
+    value: &serde_json::Value,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO tenant_settings (tenant_id, key, value, updated_at)
+        VALUES ($1, $2, $3, NOW())
+        ON CONFLICT (tenant_id, key) DO UPDATE SET value = EXCLUDED.value, updated_at = NOW()
+        "#,
+        tenant_id.0 as uuid::Uuid,
+        key,
+        value,
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Inserts the invitation of a tenant admin
+///
+/// Runs on a connection so tenants can be created with their invited admin in one transaction.
+pub(crate) async fn insert_invitation(
+    conn: &mut PgConnection,
+    invitation: &TenantInvitation,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO tenant_invitations (id, tenant_id, user_id, email, token_hash, expires_at, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        invitation.id,
+        invitation.tenant_id.0 as uuid::Uuid,
+        invitation.user_id.0 as uuid::Uuid,
+        invitation.email,
+        invitation.token_hash,
+        invitation.expires_at,
+        invitation.created_at,
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Gets a tenant, deleted or not, locking it until the transaction ends
+///
+/// Runs on a connection so tenants can be archived within one transaction.
+pub(crate) async fn lock_tenant(conn: &mut PgConnection, id: Uuid) -> Result<Option<Tenant>> {
+    let row = sqlx::query!(
+        r#"
+        SELECT id, name, domain, slug, region, active, created_at, updated_at, deleted_at,
+            suspended_at, suspension_reason, plan
+        FROM tenants
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(row.map(|r| Tenant {
+        id: TenantId(r.id),
+        name: r.name,
+        domain: r.domain,
+        slug: r.slug,
+        region: r.region,
+        active: r.active,
+        created_at: r.created_at,
+        updated_at: r.updated_at,
+        deleted_at: r.deleted_at,
+        suspended_at: r.suspended_at,
+        suspension_reason: r.suspension_reason,
+        plan: to_plan(&r.plan),
+    }))
+}
+
+/// Exports the rows of an archived table selected by the condition, `$1` being the tenant ID
+///
+/// Table and condition come from [`ARCHIVED_TABLES`](crate::modules::tenant::archive::ARCHIVED_TABLES),
+/// never from input.
+pub(crate) async fn export_tenant_rows(
+    conn: &mut PgConnection,
+    table: &str,
+    selector: &str,
+    tenant_id: TenantId,
+) -> Result<Vec<serde_json::Value>> {
+    let rows: serde_json::Value = sqlx::query_scalar(&format!(
+        "SELECT COALESCE(jsonb_agg(to_jsonb(t)), '[]'::jsonb) FROM {} t WHERE {}",
+        table, selector
+    ))
+    .bind(tenant_id.0)
+    .fetch_one(&mut *conn)
+    .await?;
+    serde_json::from_value(rows)
+        .map_err(|e| Error::Internal(format!("Failed to export {}: {}", table, e)))
+}
+
+/// Imports exported rows into an archived table
+///
+/// Rows whose key is taken again, e.g. by a tenant that claimed the domain
+/// meanwhile, fail with a conflict.
+pub(crate) async fn import_tenant_rows(
+    conn: &mut PgConnection,
+    table: &str,
+    rows: &[serde_json::Value],
+) -> Result<()> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(&format!(
+        "INSERT INTO {0} SELECT * FROM jsonb_populate_recordset(NULL::{0}, $1)",
+        table
+    ))
+    .bind(serde_json::Value::Array(rows.to_vec()))
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => Error::Conflict(format!(
+            "Archived {} conflict with existing data: {}",
+            table,
+            db.message()
+        )),
+        e => e.into(),
+    })?;
+    Ok(())
+}
+
+/// Removes a tenant and all its data from the database
+///
+/// Runs on a connection so tenants can be archived within one transaction.
+pub(crate) async fn delete_tenant_data(conn: &mut PgConnection, tenant_id: TenantId) -> Result<()> {
+    // Users must go first, the tenant's other tables cascade from it
+    sqlx::query!(
+        "DELETE FROM users WHERE tenant_id = $1",
+        tenant_id.0 as Uuid
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!("DELETE FROM tenants WHERE id = $1", tenant_id.0 as Uuid)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!(
+        "DELETE FROM token_funnel WHERE tenant_id = $1",
+        tenant_id.0 as Uuid
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Inserts the manifest of a tenant archive
+///
+/// Runs on a connection so the manifest is only kept if the tenant's data was removed.
+pub(crate) async fn insert_archive_manifest(
+    conn: &mut PgConnection,
+    manifest: &ArchiveManifest,
+) -> Result<()> {
+    let row_counts = serde_json::to_value(&manifest.row_counts)
+        .map_err(|e| Error::Internal(format!("Failed to serialize archive row counts: {}", e)))?;
+    sqlx::query!(
+        r#"
+        INSERT INTO tenant_archives (
+            id, tenant_id, tenant_name, tenant_domain, object_key, checksum, size_bytes,
+            row_counts, archived_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        "#,
+        manifest.id,
+        manifest.tenant_id.0 as Uuid,
+        manifest.tenant_name,
+        manifest.tenant_domain,
+        manifest.object_key,
+        manifest.checksum,
+        manifest.size_bytes,
+        row_counts,
+        manifest.archived_at,
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
 }
 
-/// Helper function to convert Option<PrimitiveDateTime> to Option<OffsetDateTime>
-fn to_offset_datetime(dt: PrimitiveDateTime) -> OffsetDateTime {
-    dt.assume_utc()
+/// Marks an archive as restored, returning false if it already was
+///
+/// Runs on a connection so the archive is only marked if the tenant's data was restored.
+pub(crate) async fn mark_archive_restored(conn: &mut PgConnection, id: Uuid) -> Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE tenant_archives SET restored_at = NOW()
+        WHERE id = $1 AND restored_at IS NULL
+        "#,
+        id,
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(result.rows_affected() > 0)
 }
 
 /// Repository for tenant management
@@ -34,41 +305,30 @@ impl TenantRepository {
         Self { pool }
     }
 
+    /// Gets the connection pool, e.g. to read the tenant's roles
+    pub fn get_pool(&self) -> &Pool<PgPool> {
+        &self.pool
+    }
+
     /// Creates a new tenant
     pub async fn create_tenant(&self, tenant: Tenant) -> Result<Tenant> {
-        let row = sqlx::query!(
-            r#"
-            INSERT INTO tenants (id, name, domain, active, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6)
-            RETURNING id, name, domain, active, created_at, updated_at
-            "#,
-            tenant.id.0 as uuid::Uuid,
-            tenant.name,
-            tenant.domain,
-            tenant.active,
-            to_primitive_datetime(tenant.created_at),
-            to_primitive_datetime(tenant.updated_at),
-        )
-        .fetch_one(&self.pool)
-        .await?;
+        let mut conn = self.pool.acquire().await?;
+        insert_tenant(&mut conn, &tenant).await
+    }
 
-        Ok(Tenant {
-            id: tenant.id,
-            name: row.name,
-            domain: row.domain.expect("Domain should not be null"),
-            active: row.active,
-            created_at: to_offset_datetime(row.created_at),
-            updated_at: to_offset_datetime(row.updated_at),
-        })
+    /// Begins a transaction, e.g. to create a tenant together with its first users
+    pub(crate) async fn begin(&self) -> Result<Transaction<'static, PgPool>> {
+        Ok(self.pool.begin().await?)
     }
 
     /// Gets a tenant by ID
     pub async fn get_tenant(&self, id: uuid::Uuid) -> Result<Option<Tenant>> {
         let row = sqlx::query!(
             r#"
-            SELECT id, name, domain, active, created_at, updated_at
+            SELECT id, name, domain, slug, region, active, created_at, updated_at, deleted_at,
+                suspended_at, suspension_reason, plan
             FROM tenants
-            WHERE id = $1
+            WHERE id = $1 AND deleted_at IS NULL
             "#,
             id
         )
@@ -78,10 +338,16 @@ impl TenantRepository {
         Ok(row.map(|r| Tenant {
             id: TenantId(r.id),
             name: r.name,
-            domain: r.domain.expect("Domain should not be null"),
+            domain: r.domain,
+            slug: r.slug,
+            region: r.region,
             active: r.active,
-            created_at: to_offset_datetime(r.created_at),
-            updated_at: to_offset_datetime(r.updated_at),
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+            deleted_at: r.deleted_at,
+            suspended_at: r.suspended_at,
+            suspension_reason: r.suspension_reason,
+            plan: to_plan(&r.plan),
         }))
     }
 
@@ -89,9 +355,10 @@ impl TenantRepository {
     pub async fn get_tenant_by_domain(&self, domain: &str) -> Result<Tenant> {
         let row = sqlx::query!(
             r#"
-            SELECT id, name, domain, active, created_at, updated_at
+            SELECT id, name, domain, slug, region, active, created_at, updated_at, deleted_at,
+                suspended_at, suspension_reason, plan
             FROM tenants
-            WHERE domain = $1
+            WHERE domain = $1 AND deleted_at IS NULL
             "#,
             domain
         )
@@ -101,47 +368,196 @@ impl TenantRepository {
         Ok(Tenant {
             id: TenantId(row.id),
             name: row.name,
-            domain: row.domain.expect("Domain should not be null"),
+            domain: row.domain,
+            slug: row.slug,
+            region: row.region,
             active: row.active,
-            created_at: to_offset_datetime(row.created_at),
-            updated_at: to_offset_datetime(row.updated_at),
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            deleted_at: row.deleted_at,
+            suspended_at: row.suspended_at,
+            suspension_reason: row.suspension_reason,
+            plan: to_plan(&row.plan),
         })
     }
 
     /// Updates a tenant
+    ///
+    /// A replaced slug is kept in the slug history so it keeps resolving to the tenant,
+    /// unless the tenant takes it back.
+    /// The region is kept as it is fixed once the tenant is created.
     pub async fn update_tenant(&self, tenant: Tenant) -> Result<Tenant> {
+        let mut tx = self.pool.begin().await?;
+
+        let previous = sqlx::query_scalar!(
+            r#"SELECT slug FROM tenants WHERE id = $1 FOR UPDATE"#,
+            tenant.id.0 as uuid::Uuid,
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .flatten();
+
         let row = sqlx::query!(
             r#"
             UPDATE tenants
-            SET name = $1, domain = $2, active = $3, updated_at = $4
-            WHERE id = $5
-            RETURNING id, name, domain, active, created_at, updated_at
+            SET name = $1, domain = $2, slug = $3, active = $4, updated_at = $5
+            WHERE id = $6 AND deleted_at IS NULL
+            RETURNING id, name, domain, slug, region, active, created_at, updated_at, deleted_at,
+                suspended_at, suspension_reason, plan
             "#,
             tenant.name,
             tenant.domain,
+            tenant.slug,
             tenant.active,
-            to_primitive_datetime(tenant.updated_at),
+            tenant.updated_at,
             tenant.id.0 as uuid::Uuid,
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        if previous != row.slug {
+            if let Some(slug) = &row.slug {
+                sqlx::query!(
+                    r#"DELETE FROM tenant_slug_history WHERE slug = $1 AND tenant_id = $2"#,
+                    slug,
+                    tenant.id.0 as uuid::Uuid,
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            if let Some(slug) = &previous {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO tenant_slug_history (slug, tenant_id, replaced_at)
+                    VALUES ($1, $2, NOW())
+                    ON CONFLICT (slug) DO UPDATE SET replaced_at = NOW()
+                    "#,
+                    slug,
+                    tenant.id.0 as uuid::Uuid,
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+
         Ok(Tenant {
             id: tenant.id,
             name: row.name,
-            domain: row.domain.expect("Domain should not be null"),
+            domain: row.domain,
+            slug: row.slug,
+            region: row.region,
             active: row.active,
-            created_at: to_offset_datetime(row.created_at),
-            updated_at: to_offset_datetime(row.updated_at),
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            deleted_at: row.deleted_at,
+            suspended_at: row.suspended_at,
+            suspension_reason: row.suspension_reason,
+            plan: to_plan(&row.plan),
         })
     }
 
+    /// Gets a tenant by its current slug
+    pub async fn get_tenant_by_slug(&self, slug: &str) -> Result<Option<Tenant>> {
+        let row = sqlx::query!(
+            r#"
+            SELECT id, name, domain, slug, region, active, created_at, updated_at, deleted_at,
+                suspended_at, suspension_reason, plan
+            FROM tenants
+            WHERE slug = $1 AND deleted_at IS NULL
+            "#,
+            slug
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| Tenant {
+            id: TenantId(r.id),
+            name: r.name,
+            domain: r.domain,
+            slug: r.slug,
+            region: r.region,
+            active: r.active,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+            deleted_at: r.deleted_at,
+            suspended_at: r.suspended_at,
+            suspension_reason: r.suspension_reason,
+            plan: to_plan(&r.plan),
+        }))
+    }
+
+    /// Gets the tenant a previous slug belonged to
+    pub async fn get_tenant_by_previous_slug(&self, slug: &str) -> Result<Option<Tenant>> {
+        let row = sqlx::query!(
+            r#"
+            SELECT t.id, t.name, t.domain, t.slug, t.region, t.active, t.created_at, t.updated_at,
+                t.deleted_at, t.suspended_at, t.suspension_reason, t.plan
+            FROM tenant_slug_history h
+            JOIN tenants t ON t.id = h.tenant_id
+            WHERE h.slug = $1 AND t.deleted_at IS NULL
+            "#,
+            slug
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| Tenant {
+            id: TenantId(r.id),
+            name: r.name,
+            domain: r.domain,
+            slug: r.slug,
+            region: r.region,
+            active: r.active,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+            deleted_at: r.deleted_at,
+            suspended_at: r.suspended_at,
+            suspension_reason: r.suspension_reason,
+            plan: to_plan(&r.plan),
+        }))
+    }
+
+    /// Gets the tenant holding a slug, currently or as a previous slug
+    pub async fn get_slug_owner(&self, slug: &str) -> Result<Option<TenantId>> {
+        let owner = sqlx::query_scalar!(
+            r#"
+            SELECT id AS "id!" FROM tenants WHERE slug = $1
+            UNION ALL
+            SELECT tenant_id FROM tenant_slug_history WHERE slug = $1
+            LIMIT 1
+            "#,
+            slug
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(owner.map(TenantId))
+    }
+
+    /// Gets the tenant holding a normalized domain, deleted or not
+    pub async fn get_domain_owner(&self, domain: &str) -> Result<Option<TenantId>> {
+        let owner = sqlx::query_scalar!(
+            r#"
+            SELECT id FROM tenants WHERE lower(rtrim(domain, '.')) = $1
+            "#,
+            domain
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(owner.map(TenantId))
+    }
+
     /// Lists all tenants
     pub async fn list_tenants(&self) -> Result<Vec<Tenant>> {
         let rows = sqlx::query!(
             r#"
-            SELECT id, name, domain, active, created_at, updated_at
+            SELECT id, name, domain, slug, region, active, created_at, updated_at, deleted_at,
+                suspended_at, suspension_reason, plan
             FROM tenants
+            WHERE deleted_at IS NULL
             ORDER BY created_at DESC
             "#
         )
@@ -153,15 +569,844 @@ impl TenantRepository {
             .map(|r| Tenant {
                 id: TenantId(r.id),
                 name: r.name,
-                domain: r.domain.expect("Domain should not be null"),
+                domain: r.domain,
+                slug: r.slug,
+                region: r.region,
+                active: r.active,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+                deleted_at: r.deleted_at,
+                suspended_at: r.suspended_at,
+                suspension_reason: r.suspension_reason,
+                plan: to_plan(&r.plan),
+            })
+            .collect())
+    }
+
+    /// Lists a page of the tenants matching the filters, with the total number of matches
+    ///
+    /// The search matches a case-insensitive substring of the name or domain.
+    pub async fn list_tenants_page(
+        &self,
+        active: Option<bool>,
+        search: Option<&str>,
+        sort: TenantSort,
+        order: SortOrder,
+        offset: i64,
+        limit: i64,
+    ) -> Result<(Vec<Tenant>, i64)> {
+        let pattern = search.map(|search| format!("%{}%", escape_like(search)));
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, name, domain, slug, region, active, created_at, updated_at, deleted_at,
+                suspended_at, suspension_reason, plan, COUNT(*) OVER () AS "total!"
+            FROM tenants
+            WHERE deleted_at IS NULL
+                AND ($1::BOOLEAN IS NULL OR active = $1)
+                AND ($2::TEXT IS NULL OR name ILIKE $2 OR domain ILIKE $2)
+            ORDER BY
+                CASE WHEN $3::TEXT = 'name' AND $4::BOOLEAN THEN name END ASC,
+                CASE WHEN $3 = 'name' AND NOT $4 THEN name END DESC,
+                CASE WHEN $3 = 'domain' AND $4 THEN domain END ASC,
+                CASE WHEN $3 = 'domain' AND NOT $4 THEN domain END DESC,
+                CASE WHEN $3 = 'created_at' AND $4 THEN created_at END ASC,
+                CASE WHEN $3 = 'created_at' AND NOT $4 THEN created_at END DESC,
+                CASE WHEN $3 = 'updated_at' AND $4 THEN updated_at END ASC,
+                CASE WHEN $3 = 'updated_at' AND NOT $4 THEN updated_at END DESC,
+                id
+            OFFSET $5
+            LIMIT $6
+            "#,
+            active,
+            pattern,
+            sort.column(),
+            order == SortOrder::Asc,
+            offset,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        // Pages past the end carry no rows to read the total from
+        let total = match rows.first() {
+            Some(r) => r.total,
+            None => {
+                sqlx::query_scalar!(
+                    r#"
+                    SELECT COUNT(*) AS "count!"
+                    FROM tenants
+                    WHERE deleted_at IS NULL
+                        AND ($1::BOOLEAN IS NULL OR active = $1)
+                        AND ($2::TEXT IS NULL OR name ILIKE $2 OR domain ILIKE $2)
+                    "#,
+                    active,
+                    pattern,
+                )
+                .fetch_one(&self.pool)
+                .await?
+            },
+        };
+
+        let tenants = rows
+            .into_iter()
+            .map(|r| Tenant {
+                id: TenantId(r.id),
+                name: r.name,
+                domain: r.domain,
+                slug: r.slug,
+                region: r.region,
+                active: r.active,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+                deleted_at: r.deleted_at,
+                suspended_at: r.suspended_at,
+                suspension_reason: r.suspension_reason,
+                plan: to_plan(&r.plan),
+            })
+            .collect();
+        Ok((tenants, total))
+    }
+
+    /// Lists the deleted tenants awaiting being purged, most recently deleted first
+    pub async fn list_deleted_tenants(&self) -> Result<Vec<Tenant>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, name, domain, slug, region, active, created_at, updated_at, deleted_at,
+                suspended_at, suspension_reason, plan
+            FROM tenants
+            WHERE deleted_at IS NOT NULL
+            ORDER BY deleted_at DESC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| Tenant {
+                id: TenantId(r.id),
+                name: r.name,
+                domain: r.domain,
+                slug: r.slug,
+                region: r.region,
                 active: r.active,
-                created_at: to_offset_datetime(r.created_at),
-                updated_at: to_offset_datetime(r.updated_at),
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+                deleted_at: r.deleted_at,
+                suspended_at: r.suspended_at,
+                suspension_reason: r.suspension_reason,
+                plan: to_plan(&r.plan),
             })
             .collect())
     }
 
-    /// Deletes a tenant
+    /// Marks a tenant as deleted, returning it unless it was deleted before
+    pub async fn soft_delete_tenant(&self, id: uuid::Uuid) -> Result<Option<Tenant>> {
+        let row = sqlx::query!(
+            r#"
+            UPDATE tenants
+            SET deleted_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND deleted_at IS NULL
+            RETURNING id, name, domain, slug, region, active, created_at, updated_at, deleted_at,
+                suspended_at, suspension_reason, plan
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| Tenant {
+            id: TenantId(r.id),
+            name: r.name,
+            domain: r.domain,
+            slug: r.slug,
+            region: r.region,
+            active: r.active,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+            deleted_at: r.deleted_at,
+            suspended_at: r.suspended_at,
+            suspension_reason: r.suspension_reason,
+            plan: to_plan(&r.plan),
+        }))
+    }
+
+    /// Restores a deleted tenant, returning it unless it was not deleted
+    pub async fn restore_tenant(&self, id: uuid::Uuid) -> Result<Option<Tenant>> {
+        let row = sqlx::query!(
+            r#"
+            UPDATE tenants
+            SET deleted_at = NULL, updated_at = NOW()
+            WHERE id = $1 AND deleted_at IS NOT NULL
+            RETURNING id, name, domain, slug, region, active, created_at, updated_at, deleted_at,
+                suspended_at, suspension_reason, plan
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| Tenant {
+            id: TenantId(r.id),
+            name: r.name,
+            domain: r.domain,
+            slug: r.slug,
+            region: r.region,
+            active: r.active,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+            deleted_at: r.deleted_at,
+            suspended_at: r.suspended_at,
+            suspension_reason: r.suspension_reason,
+            plan: to_plan(&r.plan),
+        }))
+    }
+
+    /// Suspends a tenant, returning it unless it was not found
+    ///
+    /// Suspending an already suspended tenant keeps when it was suspended.
+    pub async fn suspend_tenant(
+        &self,
+        id: uuid::Uuid,
+        reason: Option<&str>,
+    ) -> Result<Option<Tenant>> {
+        let row = sqlx::query!(
+            r#"
+            UPDATE tenants
+            SET suspended_at = COALESCE(suspended_at, NOW()), suspension_reason = $2,
+                updated_at = NOW()
+            WHERE id = $1 AND deleted_at IS NULL
+            RETURNING id, name, domain, slug, region, active, created_at, updated_at, deleted_at,
+                suspended_at, suspension_reason, plan
+            "#,
+            id,
+            reason
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| Tenant {
+            id: TenantId(r.id),
+            name: r.name,
+            domain: r.domain,
+            slug: r.slug,
+            region: r.region,
+            active: r.active,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+            deleted_at: r.deleted_at,
+            suspended_at: r.suspended_at,
+            suspension_reason: r.suspension_reason,
+            plan: to_plan(&r.plan),
+        }))
+    }
+
+    /// Lifts the suspension of a tenant, returning it unless it was not suspended
+    pub async fn reactivate_tenant(&self, id: uuid::Uuid) -> Result<Option<Tenant>> {
+        let row = sqlx::query!(
+            r#"
+            UPDATE tenants
+            SET suspended_at = NULL, suspension_reason = NULL, updated_at = NOW()
+            WHERE id = $1 AND suspended_at IS NOT NULL AND deleted_at IS NULL
+            RETURNING id, name, domain, slug, region, active, created_at, updated_at, deleted_at,
+                suspended_at, suspension_reason, plan
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| Tenant {
+            id: TenantId(r.id),
+            name: r.name,
+            domain: r.domain,
+            slug: r.slug,
+            region: r.region,
+            active: r.active,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+            deleted_at: r.deleted_at,
+            suspended_at: r.suspended_at,
+            suspension_reason: r.suspension_reason,
+            plan: to_plan(&r.plan),
+        }))
+    }
+
+    /// Moves a tenant to another plan
+    pub async fn set_plan(&self, id: uuid::Uuid, plan: TenantPlan) -> Result<Option<Tenant>> {
+        let row = sqlx::query!(
+            r#"
+            UPDATE tenants
+            SET plan = $2, updated_at = NOW()
+            WHERE id = $1 AND deleted_at IS NULL
+            RETURNING id, name, domain, slug, region, active, created_at, updated_at, deleted_at,
+                suspended_at, suspension_reason, plan
+            "#,
+            id,
+            plan.to_string(),
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| Tenant {
+            id: TenantId(r.id),
+            name: r.name,
+            domain: r.domain,
+            slug: r.slug,
+            region: r.region,
+            active: r.active,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+            deleted_at: r.deleted_at,
+            suspended_at: r.suspended_at,
+            suspension_reason: r.suspension_reason,
+            plan: to_plan(&r.plan),
+        }))
+    }
+
+    /// Permanently deletes the tenants deleted before the given time, returning them
+    pub async fn purge_deleted_tenants(&self, before: OffsetDateTime) -> Result<Vec<Tenant>> {
+        let rows = sqlx::query!(
+            r#"
+            DELETE FROM tenants
+            WHERE deleted_at <= $1
+            RETURNING id, name, domain, slug, region, active, created_at, updated_at, deleted_at,
+                suspended_at, suspension_reason, plan
+            "#,
+            before
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| Tenant {
+                id: TenantId(r.id),
+                name: r.name,
+                domain: r.domain,
+                slug: r.slug,
+                region: r.region,
+                active: r.active,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+                deleted_at: r.deleted_at,
+                suspended_at: r.suspended_at,
+                suspension_reason: r.suspension_reason,
+                plan: to_plan(&r.plan),
+            })
+            .collect())
+    }
+
+    /// Gets the settings of a tenant
+    pub async fn get_settings(&self, tenant_id: TenantId) -> Result<TenantSettings> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT key, value
+            FROM tenant_settings
+            WHERE tenant_id = $1
+            "#,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(TenantSettings::new(
+            rows.into_iter().map(|r| (r.key, r.value)).collect(),
+        ))
+    }
+
+    /// Sets a setting of a tenant, replacing its previous value
+    pub async fn set_setting(
+        &self,
+        tenant_id: TenantId,
+        key: &str,
+        value: &serde_json::Value,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        upsert_setting(&mut conn, tenant_id, key, value).await
+    }
+
+    /// Removes a setting of a tenant, returning whether it was set
+    pub async fn delete_setting(&self, tenant_id: TenantId, key: &str) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM tenant_settings
+            WHERE tenant_id = $1 AND key = $2
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            key,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Claims a domain for a tenant, returning the existing claim if the tenant claimed it before
+    pub async fn claim_domain(&self, claim: &TenantDomain) -> Result<TenantDomain> {
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO tenant_domains (tenant_id, domain, token, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (tenant_id, domain) DO UPDATE SET domain = EXCLUDED.domain
+            RETURNING tenant_id, domain, token, verified_at, last_checked_at, created_at
+            "#,
+            claim.tenant_id.0 as uuid::Uuid,
+            claim.domain,
+            claim.token,
+            claim.created_at,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(TenantDomain {
+            tenant_id: TenantId(row.tenant_id),
+            domain: row.domain,
+            token: row.token,
+            verified_at: row.verified_at,
+            last_checked_at: row.last_checked_at,
+            created_at: row.created_at,
+        })
+    }
+
+    /// Gets a domain claimed by a tenant
+    pub async fn get_domain(
+        &self,
+        tenant_id: TenantId,
+        domain: &str,
+    ) -> Result<Option<TenantDomain>> {
+        let row = sqlx::query!(
+            r#"
+            SELECT tenant_id, domain, token, verified_at, last_checked_at, created_at
+            FROM tenant_domains
+            WHERE tenant_id = $1 AND domain = $2
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            domain,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| TenantDomain {
+            tenant_id: TenantId(r.tenant_id),
+            domain: r.domain,
+            token: r.token,
+            verified_at: r.verified_at,
+            last_checked_at: r.last_checked_at,
+            created_at: r.created_at,
+        }))
+    }
+
+    /// Lists the domains claimed by a tenant
+    pub async fn list_domains(&self, tenant_id: TenantId) -> Result<Vec<TenantDomain>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT tenant_id, domain, token, verified_at, last_checked_at, created_at
+            FROM tenant_domains
+            WHERE tenant_id = $1
+            ORDER BY domain
+            "#,
+            tenant_id.0 as uuid::Uuid,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| TenantDomain {
+                tenant_id: TenantId(r.tenant_id),
+                domain: r.domain,
+                token: r.token,
+                verified_at: r.verified_at,
+                last_checked_at: r.last_checked_at,
+                created_at: r.created_at,
+            })
+            .collect())
+    }
+
+    /// Lists unverified claims not checked since the given time, least recently checked first
+    pub async fn list_pending_domains(
+        &self,
+        checked_before: OffsetDateTime,
+        limit: i64,
+    ) -> Result<Vec<TenantDomain>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT tenant_id, domain, token, verified_at, last_checked_at, created_at
+            FROM tenant_domains
+            WHERE verified_at IS NULL AND (last_checked_at IS NULL OR last_checked_at < $1)
+            ORDER BY last_checked_at NULLS FIRST
+            LIMIT $2
+            "#,
+            checked_before,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| TenantDomain {
+                tenant_id: TenantId(r.tenant_id),
+                domain: r.domain,
+                token: r.token,
+                verified_at: r.verified_at,
+                last_checked_at: r.last_checked_at,
+                created_at: r.created_at,
+            })
+            .collect())
+    }
+
+    /// Gets the tenant that verified a domain
+    pub async fn get_verified_domain_owner(&self, domain: &str) -> Result<Option<TenantId>> {
+        let owner = sqlx::query_scalar!(
+            r#"
+            SELECT tenant_id
+            FROM tenant_domains
+            WHERE domain = $1 AND verified_at IS NOT NULL
+            "#,
+            domain,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(owner.map(TenantId))
+    }
+
+    /// Records that the challenge of an unverified claim was looked up without success
+    pub async fn mark_domain_checked(&self, tenant_id: TenantId, domain: &str) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE tenant_domains
+            SET last_checked_at = NOW()
+            WHERE tenant_id = $1 AND domain = $2 AND verified_at IS NULL
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            domain,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Marks a claim verified, returning it unless it was not pending
+    ///
+    /// The pending claims of other tenants for the same domain are dropped. Fails if
+    /// another tenant verified the domain in the meantime.
+    pub async fn mark_domain_verified(
+        &self,
+        tenant_id: TenantId,
+        domain: &str,
+    ) -> Result<Option<TenantDomain>> {
+        let mut tx = self.pool.begin().await?;
+
+        let row = sqlx::query!(
+            r#"
+            UPDATE tenant_domains
+            SET verified_at = NOW(), last_checked_at = NOW()
+            WHERE tenant_id = $1 AND domain = $2 AND verified_at IS NULL
+            RETURNING tenant_id, domain, token, verified_at, last_checked_at, created_at
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            domain,
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if row.is_some() {
+            sqlx::query!(
+                r#"
+                DELETE FROM tenant_domains
+                WHERE domain = $1 AND tenant_id <> $2 AND verified_at IS NULL
+                "#,
+                domain,
+                tenant_id.0 as uuid::Uuid,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(row.map(|r| TenantDomain {
+            tenant_id: TenantId(r.tenant_id),
+            domain: r.domain,
+            token: r.token,
+            verified_at: r.verified_at,
+            last_checked_at: r.last_checked_at,
+            created_at: r.created_at,
+        }))
+    }
+
+    /// Removes a domain claimed by a tenant, returning whether it was claimed
+    pub async fn delete_domain(&self, tenant_id: TenantId, domain: &str) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM tenant_domains
+            WHERE tenant_id = $1 AND domain = $2
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            domain,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Removes the unverified claims made before the given time, returning how many were removed
+    pub async fn delete_stale_domain_claims(&self, before: OffsetDateTime) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM tenant_domains
+            WHERE verified_at IS NULL AND created_at < $1
+            "#,
+            before,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Gets an invitation by the hash of its token
+    pub async fn get_invitation(&self, token_hash: &str) -> Result<Option<TenantInvitation>> {
+        let result = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, user_id, email, token_hash, expires_at, created_at
+            FROM tenant_invitations
+            WHERE token_hash = $1
+            "#,
+            token_hash,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(|r| TenantInvitation {
+            id: r.id,
+            tenant_id: TenantId(r.tenant_id),
+            user_id: UserId(r.user_id),
+            email: r.email,
+            token_hash: r.token_hash,
+            expires_at: r.expires_at,
+            created_at: r.created_at,
+        }))
+    }
+
+    /// Sets the password of an invited admin, activating the admin and verifying their email
+    ///
+    /// Returns false if the invitation was already accepted.
+    pub async fn accept_invitation(
+        &self,
+        invitation: &TenantInvitation,
+        password_hash: &str,
+    ) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let claimed = sqlx::query!(
+            r#"
+            DELETE FROM tenant_invitations
+            WHERE id = $1
+            "#,
+            invitation.id,
+        )
+        .execute(&mut *tx)
+        .await?;
+        if claimed.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $1, active = TRUE, updated_at = NOW()
+            WHERE id = $2 AND tenant_id = $3
+            "#,
+            password_hash,
+            invitation.user_id.0 as uuid::Uuid,
+            invitation.tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE user_emails
+            SET verified = TRUE, verified_at = NOW()
+            WHERE user_id = $1 AND tenant_id = $2 AND is_primary
+            "#,
+            invitation.user_id.0 as uuid::Uuid,
+            invitation.tenant_id.0 as uuid::Uuid,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Stores a tenant template
+    pub async fn create_template(&self, template: &TenantTemplate) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO tenant_templates (id, name, plan, roles, settings, features, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            template.id,
+            template.name,
+            template.plan.to_string(),
+            to_json(&template.roles)?,
+            to_json(&template.settings)?,
+            to_json(&template.features)?,
+            template.created_at,
+            template.updated_at,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Gets a tenant template by ID
+    pub async fn get_template(&self, id: Uuid) -> Result<Option<TenantTemplate>> {
+        let row = sqlx::query!(
+            r#"
+            SELECT id, name, plan, roles, settings, features, created_at, updated_at
+            FROM tenant_templates
+            WHERE id = $1
+            "#,
+            id,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|r| {
+            Ok(TenantTemplate {
+                id: r.id,
+                name: r.name,
+                plan: to_plan(&r.plan),
+                roles: from_json(r.roles)?,
+                settings: from_json(r.settings)?,
+                features: from_json(r.features)?,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+            })
+        })
+        .transpose()
+    }
+
+    /// Checks if a template with the name exists
+    pub async fn template_name_exists(&self, name: &str) -> Result<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM tenant_templates WHERE name = $1) AS "exists!""#,
+            name,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(exists)
+    }
+
+    /// Lists all tenant templates by name
+    pub async fn list_templates(&self) -> Result<Vec<TenantTemplate>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, name, plan, roles, settings, features, created_at, updated_at
+            FROM tenant_templates
+            ORDER BY name
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|r| {
+                Ok(TenantTemplate {
+                    id: r.id,
+                    name: r.name,
+                    plan: to_plan(&r.plan),
+                    roles: from_json(r.roles)?,
+                    settings: from_json(r.settings)?,
+                    features: from_json(r.features)?,
+                    created_at: r.created_at,
+                    updated_at: r.updated_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Gets the archive a tenant can be restored from
+    pub async fn get_archive(&self, tenant_id: Uuid) -> Result<Option<ArchiveManifest>> {
+        let row = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, tenant_name, tenant_domain, object_key, checksum, size_bytes,
+                row_counts, archived_at, restored_at
+            FROM tenant_archives
+            WHERE tenant_id = $1 AND restored_at IS NULL
+            "#,
+            tenant_id,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|r| {
+            Ok(ArchiveManifest {
+                id: r.id,
+                tenant_id: TenantId(r.tenant_id),
+                tenant_name: r.tenant_name,
+                tenant_domain: r.tenant_domain,
+                object_key: r.object_key,
+                checksum: r.checksum,
+                size_bytes: r.size_bytes,
+                row_counts: from_row_counts(r.row_counts)?,
+                archived_at: r.archived_at,
+                restored_at: r.restored_at,
+            })
+        })
+        .transpose()
+    }
+
+    /// Lists the archives of tenants that were not restored, newest first
+    pub async fn list_archives(&self) -> Result<Vec<ArchiveManifest>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, tenant_id, tenant_name, tenant_domain, object_key, checksum, size_bytes,
+                row_counts, archived_at, restored_at
+            FROM tenant_archives
+            WHERE restored_at IS NULL
+            ORDER BY archived_at DESC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|r| {
+                Ok(ArchiveManifest {
+                    id: r.id,
+                    tenant_id: TenantId(r.tenant_id),
+                    tenant_name: r.tenant_name,
+                    tenant_domain: r.tenant_domain,
+                    object_key: r.object_key,
+                    checksum: r.checksum,
+                    size_bytes: r.size_bytes,
+                    row_counts: from_row_counts(r.row_counts)?,
+                    archived_at: r.archived_at,
+                    restored_at: r.restored_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Deletes a tenant template, returning false if it did not exist
+    pub async fn delete_template(&self, id: Uuid) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM tenant_templates
+            WHERE id = $1
+            "#,
+            id,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Deletes a tenant permanently, together with all its data
     pub async fn delete_tenant(&self, id: uuid::Uuid) -> Result<()> {
         sqlx::query!(
             r#"
@@ -187,6 +1432,14 @@ impl Default for TenantRepository {
 mod tests {
     use super::*;
     use crate::core::database::tests::create_test_db;
+    use std::time::Duration;
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("acme"), "acme");
+        assert_eq!(escape_like("100%_off"), "100\\%\\_off");
+        assert_eq!(escape_like("a\\b"), "a\\\\b");
+    }
 
     #[tokio::test]
     async fn test_tenant_crud() {
@@ -198,9 +1451,15 @@ mod tests {
             id: TenantId(Uuid::new_v4()),
             name: "Test Tenant".to_string(),
             domain: format!("{}.example.com", Uuid::new_v4()),
+            slug: None,
+            region: None,
             active: true,
             created_at: OffsetDateTime::now_utc(),
             updated_at: OffsetDateTime::now_utc(),
+            deleted_at: None,
+            suspended_at: None,
+            suspension_reason: None,
+            plan: TenantPlan::default(),
         };
 
         let mut retries = 3;
@@ -237,9 +1496,87 @@ mod tests {
         let updated = repository.update_tenant(updated_tenant).await.unwrap();
         assert_eq!(updated.name, "Updated Tenant");
 
+        // Deleted tenants are hidden from lookups until they are restored
+        let deleted = repository
+            .soft_delete_tenant(tenant.id.0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(deleted.is_deleted());
+        assert!(repository.get_tenant(tenant.id.0).await.unwrap().is_none());
+        assert!(repository.list_tenants().await.unwrap().is_empty());
+        assert!(repository
+            .soft_delete_tenant(tenant.id.0)
+            .await
+            .unwrap()
+            .is_none());
+        let restored = repository
+            .restore_tenant(tenant.id.0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!restored.is_deleted());
+        assert!(repository.get_tenant(tenant.id.0).await.unwrap().is_some());
+
+        // Suspended tenants stay visible, and reactivating them clears the reason
+        let suspended = repository
+            .suspend_tenant(tenant.id.0, Some("Unpaid invoices"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(suspended.is_suspended());
+        assert_eq!(
+            suspended.suspension_reason.as_deref(),
+            Some("Unpaid invoices")
+        );
+        assert!(repository
+            .get_tenant(tenant.id.0)
+            .await
+            .unwrap()
+            .unwrap()
+            .is_suspended());
+        let reactivated = repository
+            .reactivate_tenant(tenant.id.0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!reactivated.is_suspended());
+        assert!(reactivated.suspension_reason.is_none());
+        assert!(repository
+            .reactivate_tenant(tenant.id.0)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Purging only removes tenants deleted before the cutoff
+        repository.soft_delete_tenant(tenant.id.0).await.unwrap();
+        let cutoff = OffsetDateTime::now_utc() - time::Duration::days(1);
+        assert!(repository
+            .purge_deleted_tenants(cutoff)
+            .await
+            .unwrap()
+            .is_empty());
+        let purged = repository
+            .purge_deleted_tenants(OffsetDateTime::now_utc())
+            .await
+            .unwrap();
+        assert_eq!(purged.len(), 1);
+        assert!(repository
+            .restore_tenant(tenant.id.0)
+            .await
+            .unwrap()
+            .is_none());
+
         // Test delete_tenant
-        repository.delete_tenant(tenant.id.0).await.unwrap();
-        let deleted = repository.get_tenant(tenant.id.0).await.unwrap();
+        let other = repository
+            .create_tenant(Tenant::new(
+                "Other Tenant".to_string(),
+                format!("{}.example.com", Uuid::new_v4()),
+            ))
+            .await
+            .unwrap();
+        repository.delete_tenant(other.id.0).await.unwrap();
+        let deleted = repository.get_tenant(other.id.0).await.unwrap();
         assert!(deleted.is_none());
     }
 }
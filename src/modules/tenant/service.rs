@@ -1,26 +1,500 @@
 use crate::{
-    modules::tenant::{models::Tenant, repository::TenantRepository},
-    shared::error::Result,
+    core::bootstrap::{create_sso_provider, SsoProviderSpec},
+    modules::{
+        identity::{
+            auth::AuthenticationService,
+            auth_policy::{AuthPolicy, AuthPolicyOverrides, AUTH_POLICY_SETTING},
+            models::{PermissionAction, Role, RoleType, User, UserEmail},
+            rbac::{
+                create_admin_role, create_user_role, ensure_tenant_boundary, has_permission,
+                is_super_admin,
+            },
+            repository::{insert_role, insert_user, RoleRepository},
+        },
+        signup::{models::hash_token, service::MIN_PASSWORD_LENGTH},
+        tenant::{
+            archive::{ArchiveManifest, ArchiveStore, TenantArchive, ARCHIVED_TABLES},
+            domains::{normalize_domain, TenantDomain, TxtResolver},
+            hooks::{TenantEvent, TenantHook},
+            invitations::{generate_invitation_token, TenantInvitation},
+            models::{
+                SlugResolution, Tenant, TenantListQuery, TenantOnboarding, TenantPage,
+                TenantRequest, TenantResponse,
+            },
+            plans::{ensure_capability, Capability, TenantPlan},
+            repository::{
+                delete_tenant_data, export_tenant_rows, import_tenant_rows,
+                insert_archive_manifest, insert_invitation, insert_tenant, lock_tenant,
+                mark_archive_restored, upsert_setting, TenantRepository,
+            },
+            settings::{
+                validate_setting_key, TenantSettings, TenantSettingsCache, FEATURE_FLAGS_SETTING,
+            },
+            slug::{slugify, validate_slug},
+            templates::{parse_feature_flags, TenantTemplate},
+        },
+    },
+    shared::{
+        cache::{CacheInvalidation, CacheInvalidationBus},
+        error::{Error, Result},
+        mail::{EmailMessage, LogMailer, Mailer},
+        types::TenantId,
+    },
 };
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use std::time::Duration;
 use time::OffsetDateTime;
+use tokio::task::JoinHandle;
 use uuid::Uuid;
 
+/// Default number of tenants per page of the tenant list
+const DEFAULT_PAGE_SIZE: i64 = 50;
+
+/// Maximum number of tenants per page of the tenant list
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// How long settings are cached unless configured otherwise
+const DEFAULT_SETTINGS_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Maximum number of pending domain claims checked per verification run
+const DOMAIN_VERIFICATION_BATCH_SIZE: i64 = 100;
+
+/// Link sent to invited admins unless configured otherwise; the token is appended
+const DEFAULT_INVITATION_URL: &str = "http://localhost:3000/invitations/accept?token=";
+
+/// How long invitations are valid unless configured otherwise
+const DEFAULT_INVITATION_TTL: time::Duration = time::Duration::hours(72);
+
 /// Service for tenant management
 #[derive(Debug, Clone)]
 pub struct TenantService {
     repository: TenantRepository,
+    hooks: Vec<Arc<dyn TenantHook>>,
+    /// Data residency regions tenants can be assigned to
+    regions: Vec<String>,
+    /// SSO provider every provisioned tenant starts with
+    default_sso_provider: Option<SsoProviderSpec>,
+    /// Settings every provisioned tenant starts with
+    default_settings: BTreeMap<String, Value>,
+    settings_cache: TenantSettingsCache,
+    /// Bus distributing settings changes to the caches of other instances
+    invalidation: Option<CacheInvalidationBus>,
+    /// Resolver looking up the TXT challenges of custom domains
+    txt_resolver: Option<Arc<dyn TxtResolver>>,
+    mailer: Arc<dyn Mailer>,
+    /// Link sent to invited admins; the invitation token is appended
+    invitation_url: String,
+    invitation_ttl: time::Duration,
+    /// Object storage archived tenants are moved to
+    archive_store: Option<Arc<dyn ArchiveStore>>,
 }
 
 impl TenantService {
     /// Creates a new TenantService instance
     pub fn new(repository: TenantRepository) -> Self {
-        Self { repository }
+        Self {
+            repository,
+            hooks: Vec::new(),
+            regions: Vec::new(),
+            default_sso_provider: None,
+            default_settings: BTreeMap::new(),
+            settings_cache: TenantSettingsCache::new(DEFAULT_SETTINGS_CACHE_TTL),
+            invalidation: None,
+            txt_resolver: None,
+            mailer: Arc::new(LogMailer),
+            invitation_url: DEFAULT_INVITATION_URL.to_string(),
+            invitation_ttl: DEFAULT_INVITATION_TTL,
+            archive_store: None,
+        }
+    }
+
+    /// Sets the data residency regions tenants can be assigned to
+    pub fn with_regions(mut self, regions: Vec<String>) -> Self {
+        self.regions = regions;
+        self
+    }
+
+    /// Sets the SSO provider every provisioned tenant starts with, e.g. a shared identity provider
+    pub fn with_default_sso_provider(mut self, provider: SsoProviderSpec) -> Self {
+        self.default_sso_provider = Some(provider);
+        self
+    }
+
+    /// Sets the settings every provisioned tenant starts with
+    pub fn with_default_settings(mut self, settings: BTreeMap<String, Value>) -> Self {
+        self.default_settings = settings;
+        self
+    }
+
+    /// Caches settings for `ttl` and distributes their changes to all instances via the bus
+    pub fn with_settings_cache(
+        mut self,
+        ttl: Duration,
+        invalidation: CacheInvalidationBus,
+    ) -> Self {
+        self.settings_cache = TenantSettingsCache::new(ttl);
+        invalidation.register(Arc::new(self.settings_cache.clone()));
+        self.invalidation = Some(invalidation);
+        self
+    }
+
+    /// Sets the resolver custom domain challenges are looked up with
+    pub fn with_txt_resolver(mut self, resolver: Arc<dyn TxtResolver>) -> Self {
+        self.txt_resolver = Some(resolver);
+        self
+    }
+
+    /// Uses the given mailer for admin invitations
+    pub fn with_mailer(mut self, mailer: Arc<dyn Mailer>) -> Self {
+        self.mailer = mailer;
+        self
+    }
+
+    /// Sets the link sent to invited admins, to which the token is appended, and how long it is valid
+    pub fn with_invitations(mut self, url: String, ttl: time::Duration) -> Self {
+        self.invitation_url = url;
+        self.invitation_ttl = ttl;
+        self
+    }
+
+    /// Enables archiving tenants to the given object storage
+    pub fn with_archive_store(mut self, store: Arc<dyn ArchiveStore>) -> Self {
+        self.archive_store = Some(store);
+        self
+    }
+
+    /// Registers a hook that is notified about tenant lifecycle changes
+    pub fn register_hook(&mut self, hook: Arc<dyn TenantHook>) {
+        self.hooks.push(hook);
+    }
+
+    /// Notifies all registered hooks about a tenant change
+    async fn notify(&self, event: TenantEvent, tenant: &Tenant) -> Result<()> {
+        for hook in &self.hooks {
+            hook.on_tenant_event(event, tenant).await?;
+        }
+        Ok(())
     }
 
     /// Creates a new tenant
-    pub async fn create_tenant(&self, tenant: Tenant) -> Result<Tenant> {
-        self.repository.create_tenant(tenant).await
+    ///
+    /// Without a slug, one is derived from the name if it is still available. The
+    /// region must be one of the configured regions.
+    pub async fn create_tenant(&self, mut tenant: Tenant) -> Result<Tenant> {
+        self.prepare_tenant(&mut tenant).await?;
+        let tenant = self.repository.create_tenant(tenant).await?;
+
+        // Do not keep tenants whose downstream provisioning failed
+        if let Err(e) = self.notify(TenantEvent::Created, &tenant).await {
+            self.repository.delete_tenant(tenant.id.0).await?;
+            return Err(e);
+        }
+
+        Ok(tenant)
+    }
+
+    /// Creates a new tenant together with its first admin, who is invited by email
+    ///
+    /// The tenant's built-in roles are seeded and the admin is created inactive,
+    /// holding the admin role, until they accept the invitation by choosing a
+    /// password. Nothing is kept if the invitation cannot be sent.
+    pub async fn create_tenant_with_admin(
+        &self,
+        tenant: Tenant,
+        admin_email: &str,
+    ) -> Result<(Tenant, TenantInvitation)> {
+        let (tenant, invitation) = self
+            .create_configured_tenant(
+                tenant,
+                vec![create_user_role()],
+                &BTreeMap::new(),
+                Some(admin_email),
+            )
+            .await?;
+        let invitation = invitation
+            .ok_or_else(|| Error::Internal("Admin of the tenant was not invited".to_string()))?;
+        Ok((tenant, invitation))
+    }
+
+    /// Creates a new tenant from a template, optionally inviting its first admin
+    ///
+    /// The tenant gets the template's plan, roles, settings and feature flags. An
+    /// invited admin holds the template's admin role, or the built-in one if the
+    /// template has none.
+    pub async fn create_tenant_from_template(
+        &self,
+        mut tenant: Tenant,
+        template_id: Uuid,
+        admin_email: Option<&str>,
+    ) -> Result<(Tenant, Option<TenantInvitation>)> {
+        let template = self.get_template(template_id).await?;
+        tenant.plan = template.plan;
+        self.create_configured_tenant(
+            tenant,
+            template.instantiate_roles()?,
+            &template.tenant_settings(),
+            admin_email,
+        )
+        .await
+    }
+
+    /// Creates a new tenant with the plan, roles, settings and feature flags of an
+    /// existing one, but none of its users
+    pub async fn clone_tenant(
+        &self,
+        source_id: Uuid,
+        mut tenant: Tenant,
+        admin_email: Option<&str>,
+    ) -> Result<(Tenant, Option<TenantInvitation>)> {
+        let template = self
+            .template_of(source_id, format!("Clone of {}", source_id))
+            .await?;
+        tenant.plan = template.plan;
+        self.create_configured_tenant(
+            tenant,
+            template.instantiate_roles()?,
+            &template.tenant_settings(),
+            admin_email,
+        )
+        .await
+    }
+
+    /// Creates a tenant with its roles and settings in a single transaction, inviting its admin
+    ///
+    /// Hooks are notified and the invitation is sent before the transaction commits,
+    /// so nothing is kept if either fails.
+    async fn create_configured_tenant(
+        &self,
+        mut tenant: Tenant,
+        roles: Vec<Role>,
+        settings: &BTreeMap<String, Value>,
+        admin_email: Option<&str>,
+    ) -> Result<(Tenant, Option<TenantInvitation>)> {
+        for (key, value) in settings {
+            validate_setting(key, value)?;
+        }
+        let admin_email = admin_email.map(str::trim);
+        if let Some(email) = admin_email {
+            UserEmail::validate(email)?;
+        }
+        self.prepare_tenant(&mut tenant).await?;
+
+        let token = generate_invitation_token();
+        let mut admin = None;
+        let mut invitation = None;
+        if let Some(email) = admin_email {
+            // The admin cannot log in before choosing a password, so any unknown one will do
+            let mut user = User::new(
+                tenant.id,
+                email.to_string(),
+                AuthenticationService::hash_password(&generate_invitation_token())?,
+            );
+            user.active = false;
+            user.roles.push(
+                roles
+                    .iter()
+                    .find(|role| role.role_type == RoleType::Admin)
+                    .cloned()
+                    .unwrap_or_else(create_admin_role),
+            );
+            invitation = Some(TenantInvitation::new(
+                tenant.id,
+                user.id,
+                user.email.clone(),
+                &token,
+                self.invitation_ttl,
+            ));
+            admin = Some(user);
+        }
+
+        let mut tx = self.repository.begin().await?;
+        let tenant = insert_tenant(&mut tx, &tenant).await?;
+        for role in &roles {
+            insert_role(&mut tx, tenant.id, role).await?;
+        }
+        for (key, value) in settings {
+            upsert_setting(&mut tx, tenant.id, key, value).await?;
+        }
+        if let (Some(admin), Some(invitation)) = (&admin, &invitation) {
+            insert_user(&mut tx, admin).await?;
+            insert_invitation(&mut tx, invitation).await?;
+        }
+        self.notify(TenantEvent::Created, &tenant).await?;
+        if let Some(invitation) = &invitation {
+            self.mailer
+                .send(&EmailMessage {
+                    to: invitation.email.clone(),
+                    subject: format!("You have been invited to administer {}", tenant.name),
+                    body: format!(
+                        "{} has been set up for you. Choose your password to get started:\n\n{}{}\n\nThe link expires at {}.",
+                        tenant.name, self.invitation_url, token, invitation.expires_at
+                    ),
+                })
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok((tenant, invitation))
+    }
+
+    /// Stores a template new tenants can be created from
+    pub async fn create_template(&self, template: TenantTemplate) -> Result<TenantTemplate> {
+        template.validate()?;
+        for (key, value) in &template.tenant_settings() {
+            validate_setting(key, value)?;
+        }
+        if self.repository.template_name_exists(&template.name).await? {
+            return Err(Error::Conflict(format!(
+                "Template {} already exists",
+                template.name
+            )));
+        }
+        self.repository.create_template(&template).await?;
+        Ok(template)
+    }
+
+    /// Stores the configuration of an existing tenant as a template
+    pub async fn capture_template(&self, tenant_id: Uuid, name: String) -> Result<TenantTemplate> {
+        let template = self.template_of(tenant_id, name).await?;
+        self.create_template(template).await
+    }
+
+    /// Captures the plan, roles, settings and feature flags of a tenant
+    async fn template_of(&self, tenant_id: Uuid, name: String) -> Result<TenantTemplate> {
+        let tenant = self
+            .get_tenant(tenant_id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Tenant not found".to_string()))?;
+        let roles = RoleRepository::new(self.repository.get_pool().clone())
+            .list_roles(tenant.id)
+            .await?;
+        let settings = self.repository.get_settings(tenant.id).await?;
+        TenantTemplate::capture(name, tenant.plan, &roles, settings.values().clone())
+    }
+
+    /// Gets a template by ID
+    pub async fn get_template(&self, id: Uuid) -> Result<TenantTemplate> {
+        self.repository
+            .get_template(id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Template not found".to_string()))
+    }
+
+    /// Lists all templates by name
+    pub async fn list_templates(&self) -> Result<Vec<TenantTemplate>> {
+        self.repository.list_templates().await
+    }
+
+    /// Deletes a template; tenants created from it keep their configuration
+    pub async fn delete_template(&self, id: Uuid) -> Result<()> {
+        if !self.repository.delete_template(id).await? {
+            return Err(Error::NotFound("Template not found".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Accepts an admin invitation, setting the admin's password and activating the admin
+    ///
+    /// The password has to meet the authentication policy of the tenant.
+    pub async fn accept_invitation(&self, token: &str, password: &str) -> Result<TenantInvitation> {
+        let invitation = self
+            .repository
+            .get_invitation(&hash_token(token))
+            .await?
+            .filter(|invitation| !invitation.is_expired())
+            .ok_or_else(|| Error::NotFound("Invalid or expired invitation".to_string()))?;
+
+        let policy = AuthPolicy::default()
+            .for_tenant(self, invitation.tenant_id)
+            .await?;
+        policy.password.validate(password)?;
+        let password_hash = AuthenticationService::hash_password(password)?;
+
+        if !self
+            .repository
+            .accept_invitation(&invitation, &password_hash)
+            .await?
+        {
+            return Err(Error::NotFound("Invalid or expired invitation".to_string()));
+        }
+        Ok(invitation)
+    }
+
+    /// Onboards a new tenant with its first admin in a single transaction
+    ///
+    /// Creates the tenant with its built-in roles and default settings, the admin
+    /// holding the admin role and, if configured, the default SSO provider. Hooks are notified before the
+    /// transaction commits, so a failed downstream provisioning leaves nothing behind.
+    pub async fn provision(
+        &self,
+        name: &str,
+        domain: &str,
+        admin_email: &str,
+        admin_password: &str,
+    ) -> Result<TenantOnboarding> {
+        validate_onboarding(name, admin_email, admin_password)?;
+        let mut tenant = Tenant::new(name.trim().to_string(), domain.to_string());
+        self.prepare_tenant(&mut tenant).await?;
+        let mut admin = User::new(
+            tenant.id,
+            admin_email.trim().to_string(),
+            AuthenticationService::hash_password(admin_password)?,
+        );
+        admin.roles.push(create_admin_role());
+
+        for (key, value) in &self.default_settings {
+            validate_setting(key, value)?;
+        }
+
+        let mut tx = self.repository.begin().await?;
+        let tenant = insert_tenant(&mut tx, &tenant).await?;
+        for (key, value) in &self.default_settings {
+            upsert_setting(&mut tx, tenant.id, key, value).await?;
+        }
+        // The user role is seeded for later members; the admin role is created with the admin
+        insert_role(&mut tx, tenant.id, &create_user_role()).await?;
+        let mut created = insert_user(&mut tx, &admin).await?;
+        created.roles = admin.roles;
+        let sso_provider_id = match &self.default_sso_provider {
+            Some(provider) => create_sso_provider(&mut tx, tenant.id, provider).await?,
+            None => None,
+        };
+        self.notify(TenantEvent::Created, &tenant).await?;
+        tx.commit().await?;
+
+        Ok(TenantOnboarding {
+            tenant,
+            admin: created,
+            sso_provider_id,
+        })
+    }
+
+    /// Validates the domain and region of a new tenant and derives its slug from the name if not given
+    ///
+    /// The domain is normalized. A derived slug is only set if it is still available.
+    async fn prepare_tenant(&self, tenant: &mut Tenant) -> Result<()> {
+        tenant.domain = normalize_domain(&tenant.domain)?;
+        self.ensure_domain_available(&tenant.domain, tenant.id)
+            .await?;
+        if let Some(region) = &tenant.region {
+            if !self.regions.contains(region) {
+                return Err(Error::Validation(format!("Unknown region {}", region)));
+            }
+        }
+        match &tenant.slug {
+            Some(slug) => self.ensure_slug_available(slug, tenant.id).await?,
+            None => {
+                if let Some(slug) = slugify(&tenant.name) {
+                    if self.repository.get_slug_owner(&slug).await?.is_none() {
+                        tenant.slug = Some(slug);
+                    }
+                }
+            },
+        }
+        Ok(())
     }
 
     /// Gets a tenant by ID
@@ -28,9 +502,83 @@ impl TenantService {
         self.repository.get_tenant(id).await
     }
 
-    /// Updates a tenant
-    pub async fn update_tenant(&self, tenant: Tenant) -> Result<Tenant> {
-        self.repository.update_tenant(tenant).await
+    /// Gets a tenant by domain
+    pub async fn get_tenant_by_domain(&self, domain: &str) -> Result<Option<Tenant>> {
+        let Ok(domain) = normalize_domain(domain) else {
+            return Ok(None);
+        };
+        match self.repository.get_tenant_by_domain(&domain).await {
+            Ok(tenant) => Ok(Some(tenant)),
+            Err(Error::NotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Updates a tenant; old slugs of renamed tenants keep resolving to them
+    pub async fn update_tenant(&self, mut tenant: Tenant) -> Result<Tenant> {
+        tenant.domain = normalize_domain(&tenant.domain)?;
+        self.ensure_domain_available(&tenant.domain, tenant.id)
+            .await?;
+        if let Some(slug) = &tenant.slug {
+            self.ensure_slug_available(slug, tenant.id).await?;
+        }
+        let tenant = self.repository.update_tenant(tenant).await?;
+        self.notify(TenantEvent::Updated, &tenant).await?;
+        Ok(tenant)
+    }
+
+    /// Resolves a vanity slug to its active tenant, also if it is a previous slug
+    pub async fn resolve_slug(&self, slug: &str) -> Result<Option<SlugResolution>> {
+        let slug = slug.to_lowercase();
+        let resolution = match self.repository.get_tenant_by_slug(&slug).await? {
+            Some(tenant) => Some(SlugResolution::Current(tenant)),
+            None => self
+                .repository
+                .get_tenant_by_previous_slug(&slug)
+                .await?
+                .map(SlugResolution::Renamed),
+        };
+        Ok(resolution.filter(|resolution| match resolution {
+            SlugResolution::Current(tenant) | SlugResolution::Renamed(tenant) => tenant.active,
+        }))
+    }
+
+    /// Checks if a domain is valid and available for a new tenant, returning it normalized
+    pub async fn check_tenant_domain(&self, domain: &str) -> Result<(String, bool)> {
+        let domain = normalize_domain(domain)?;
+        let available = match self.ensure_domain_available(&domain, TenantId::new()).await {
+            Ok(()) => true,
+            Err(Error::Conflict(_)) => false,
+            Err(e) => return Err(e),
+        };
+        Ok((domain, available))
+    }
+
+    /// Ensures a normalized domain is neither another tenant's domain, even a deleted
+    /// one's, nor a custom domain another tenant verified
+    async fn ensure_domain_available(&self, domain: &str, tenant_id: TenantId) -> Result<()> {
+        let owner = match self.repository.get_domain_owner(domain).await? {
+            Some(owner) => Some(owner),
+            None => self.repository.get_verified_domain_owner(domain).await?,
+        };
+        match owner {
+            Some(owner) if owner != tenant_id => Err(Error::Conflict(format!(
+                "Domain {} is already taken",
+                domain
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Ensures a slug is valid and not held by another tenant, also not as a previous slug
+    async fn ensure_slug_available(&self, slug: &str, tenant_id: TenantId) -> Result<()> {
+        validate_slug(slug)?;
+        match self.repository.get_slug_owner(slug).await? {
+            Some(owner) if owner != tenant_id => {
+                Err(Error::Validation(format!("Slug {} is already taken", slug)))
+            },
+            _ => Ok(()),
+        }
     }
 
     /// Lists all tenants
@@ -38,19 +586,652 @@ impl TenantService {
         self.repository.list_tenants().await
     }
 
-    /// Deletes a tenant
+    /// Lists a page of the tenants matching the query's filters, newest first unless sorted otherwise
+    pub async fn list_tenants_page(&self, query: TenantListQuery) -> Result<TenantPage> {
+        let page = query.page.unwrap_or(1).max(1);
+        let per_page = query
+            .per_page
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let search = query
+            .search
+            .as_deref()
+            .map(str::trim)
+            .filter(|search| !search.is_empty());
+
+        let (tenants, total) = self
+            .repository
+            .list_tenants_page(
+                query.active,
+                search,
+                query.sort.unwrap_or_default(),
+                query.order.unwrap_or_default(),
+                (page - 1) * per_page,
+                per_page,
+            )
+            .await?;
+        Ok(TenantPage {
+            tenants: tenants.into_iter().map(TenantResponse::from).collect(),
+            page,
+            per_page,
+            total,
+        })
+    }
+
+    /// Gets the settings of a tenant, empty for unknown tenants
+    pub async fn settings(&self, tenant_id: TenantId) -> Result<TenantSettings> {
+        if let Some(settings) = self.settings_cache.get(tenant_id) {
+            return Ok(settings);
+        }
+        let settings = self.repository.get_settings(tenant_id).await?;
+        self.settings_cache.insert(tenant_id, settings.clone());
+        Ok(settings)
+    }
+
+    /// Gets a setting of a tenant with the type the caller expects, `None` if it is not set
+    pub async fn setting<T: DeserializeOwned>(
+        &self,
+        tenant_id: TenantId,
+        key: &str,
+    ) -> Result<Option<T>> {
+        self.settings(tenant_id).await?.get(key)
+    }
+
+    /// Sets a setting of a tenant, replacing its previous value
+    ///
+    /// Settings with a known meaning, like the authentication policy overrides, are validated.
+    pub async fn set_setting(&self, tenant_id: TenantId, key: &str, value: Value) -> Result<()> {
+        validate_setting(key, &value)?;
+        if self.get_tenant(tenant_id.0).await?.is_none() {
+            return Err(Error::NotFound("Tenant not found".to_string()));
+        }
+        self.repository.set_setting(tenant_id, key, &value).await?;
+        self.invalidate_settings(tenant_id).await;
+        Ok(())
+    }
+
+    /// Removes a setting of a tenant, so features fall back to their defaults
+    pub async fn remove_setting(&self, tenant_id: TenantId, key: &str) -> Result<()> {
+        if !self.repository.delete_setting(tenant_id, key).await? {
+            return Err(Error::NotFound("Setting not found".to_string()));
+        }
+        self.invalidate_settings(tenant_id).await;
+        Ok(())
+    }
+
+    /// Drops the cached settings of a tenant on this and, with a bus, all other instances
+    async fn invalidate_settings(&self, tenant_id: TenantId) {
+        match &self.invalidation {
+            Some(bus) => {
+                bus.publish(CacheInvalidation::TenantSettings { tenant_id })
+                    .await
+            },
+            None => self.settings_cache.remove(tenant_id),
+        }
+    }
+
+    /// Claims a custom domain for a tenant, which has to answer its TXT challenge to verify it
+    ///
+    /// Claiming a domain again returns the existing claim. Unverified claims do not
+    /// block other tenants, but a verified domain cannot be claimed by anyone else.
+    pub async fn claim_domain(&self, tenant_id: TenantId, domain: &str) -> Result<TenantDomain> {
+        let domain = normalize_domain(domain)?;
+        if self.get_tenant(tenant_id.0).await?.is_none() {
+            return Err(Error::NotFound("Tenant not found".to_string()));
+        }
+        match self.repository.get_verified_domain_owner(&domain).await? {
+            Some(owner) if owner != tenant_id => Err(Error::Conflict(format!(
+                "Domain {} is already verified by another tenant",
+                domain
+            ))),
+            _ => {
+                self.repository
+                    .claim_domain(&TenantDomain::new(tenant_id, domain))
+                    .await
+            },
+        }
+    }
+
+    /// Lists the custom domains claimed by a tenant
+    pub async fn list_domains(&self, tenant_id: TenantId) -> Result<Vec<TenantDomain>> {
+        self.repository.list_domains(tenant_id).await
+    }
+
+    /// Removes a custom domain of a tenant, which stops routing to it if it was verified
+    pub async fn remove_domain(&self, tenant_id: TenantId, domain: &str) -> Result<()> {
+        let domain = normalize_domain(domain)?;
+        if !self.repository.delete_domain(tenant_id, &domain).await? {
+            return Err(Error::NotFound("Domain not found".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Looks up the TXT challenge of a claimed domain and marks the domain verified if it is answered
+    pub async fn verify_domain(&self, tenant_id: TenantId, domain: &str) -> Result<TenantDomain> {
+        let domain = normalize_domain(domain)?;
+        let claim = self
+            .repository
+            .get_domain(tenant_id, &domain)
+            .await?
+            .ok_or_else(|| Error::NotFound("Domain not found".to_string()))?;
+        if claim.is_verified() {
+            return Ok(claim);
+        }
+
+        if !self.check_domain(&claim).await? {
+            return Err(Error::Validation(format!(
+                "TXT record {} with value {} not found",
+                claim.challenge_name(),
+                claim.challenge_value()
+            )));
+        }
+        self.repository
+            .mark_domain_verified(tenant_id, &domain)
+            .await?
+            .ok_or_else(|| Error::NotFound("Domain not found".to_string()))
+    }
+
+    /// Checks if the TXT challenge of an unverified claim is answered, recording the lookup
+    ///
+    /// Fails if another tenant verified the domain first.
+    async fn check_domain(&self, claim: &TenantDomain) -> Result<bool> {
+        let resolver = self.txt_resolver.as_ref().ok_or_else(|| {
+            Error::Unavailable("Domain verification is not configured".to_string())
+        })?;
+        if let Some(owner) = self
+            .repository
+            .get_verified_domain_owner(&claim.domain)
+            .await?
+        {
+            if owner != claim.tenant_id {
+                return Err(Error::Conflict(format!(
+                    "Domain {} is already verified by another tenant",
+                    claim.domain
+                )));
+            }
+        }
+
+        let records = resolver.lookup_txt(&claim.challenge_name()).await?;
+        let answered = claim.is_answered_by(&records);
+        if !answered {
+            self.repository
+                .mark_domain_checked(claim.tenant_id, &claim.domain)
+                .await?;
+        }
+        Ok(answered)
+    }
+
+    /// Verifies the pending domain claims whose challenge was not looked up within `interval`
+    ///
+    /// Claims left unverified for longer than `claim_ttl` are dropped, so abandoned
+    /// claims do not keep being looked up. Returns the number of verified domains.
+    pub async fn verify_pending_domains(
+        &self,
+        interval: Duration,
+        claim_ttl: time::Duration,
+    ) -> Result<usize> {
+        let now = OffsetDateTime::now_utc();
+        let dropped = self
+            .repository
+            .delete_stale_domain_claims(now - claim_ttl)
+            .await?;
+        if dropped > 0 {
+            tracing::info!("Dropped {} unverified domain claims", dropped);
+        }
+
+        let pending = self
+            .repository
+            .list_pending_domains(now - interval, DOMAIN_VERIFICATION_BATCH_SIZE)
+            .await?;
+        let mut verified = 0;
+        for claim in pending {
+            match self.check_domain(&claim).await {
+                Ok(true) => {
+                    if self
+                        .repository
+                        .mark_domain_verified(claim.tenant_id, &claim.domain)
+                        .await?
+                        .is_some()
+                    {
+                        tracing::info!(
+                            "Verified domain {} of tenant {}",
+                            claim.domain,
+                            claim.tenant_id.0
+                        );
+                        verified += 1;
+                    }
+                },
+                Ok(false) => {},
+                Err(e) => tracing::warn!("Failed to verify domain {}: {}", claim.domain, e),
+            }
+        }
+        Ok(verified)
+    }
+
+    /// Periodically looks up the TXT challenges of pending domain claims
+    pub fn spawn_domain_verification_worker(
+        self,
+        interval: Duration,
+        claim_ttl: time::Duration,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.verify_pending_domains(interval, claim_ttl).await {
+                    tracing::error!("Failed to verify pending domains: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Resolves a verified custom domain to its active tenant
+    ///
+    /// Claimed domains are only routed to their tenant once they are verified.
+    pub async fn resolve_domain(&self, domain: &str) -> Result<Option<Tenant>> {
+        let Ok(domain) = normalize_domain(domain) else {
+            return Ok(None);
+        };
+        let Some(owner) = self.repository.get_verified_domain_owner(&domain).await? else {
+            return Ok(None);
+        };
+        Ok(self
+            .get_tenant(owner.0)
+            .await?
+            .filter(|tenant| tenant.active))
+    }
+
+    /// Deletes a tenant, which can be restored until it is purged
+    ///
+    /// Deleted tenants are hidden from lookups and their users can neither log
+    /// in nor use their sessions.
     pub async fn delete_tenant(&self, id: &str) -> Result<()> {
         let id = uuid::Uuid::parse_str(id).map_err(|e| {
             crate::shared::error::Error::InvalidInput(format!("Invalid UUID: {}", e))
         })?;
-        self.repository.delete_tenant(id).await
+        if let Some(tenant) = self.repository.soft_delete_tenant(id).await? {
+            self.notify(TenantEvent::Deleted, &tenant).await?;
+        }
+        Ok(())
+    }
+
+    /// Permanently removes a tenant that was never activated, e.g. of an abandoned signup,
+    /// freeing its domain and slug for new tenants
+    ///
+    /// Active tenants are deleted instead, which keeps them restorable.
+    pub async fn discard_tenant(&self, id: Uuid) -> Result<()> {
+        let Some(tenant) = self.repository.get_tenant(id).await? else {
+            return Ok(());
+        };
+        if tenant.active {
+            return Err(Error::Conflict(
+                "Active tenants cannot be discarded".to_string(),
+            ));
+        }
+        self.repository.delete_tenant(id).await?;
+        self.notify(TenantEvent::Purged, &tenant).await
+    }
+
+    /// Suspends a tenant, denying its users access until it is reactivated
+    ///
+    /// The reason is shown to the tenant's users; suspending a suspended tenant
+    /// only replaces the reason.
+    pub async fn suspend_tenant(&self, id: Uuid, reason: Option<String>) -> Result<Tenant> {
+        let tenant = self
+            .repository
+            .suspend_tenant(id, reason.as_deref())
+            .await?
+            .ok_or_else(|| Error::NotFound("Tenant not found".to_string()))?;
+        self.notify(TenantEvent::Suspended, &tenant).await?;
+        Ok(tenant)
+    }
+
+    /// Reactivates a suspended tenant, whose users can access it again with their sessions
+    pub async fn reactivate_tenant(&self, id: Uuid) -> Result<Tenant> {
+        let tenant = self
+            .repository
+            .reactivate_tenant(id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Suspended tenant not found".to_string()))?;
+        self.notify(TenantEvent::Reactivated, &tenant).await?;
+        Ok(tenant)
+    }
+
+    /// Moves a tenant to another plan
+    pub async fn set_plan(&self, id: Uuid, plan: TenantPlan) -> Result<Tenant> {
+        let tenant = self
+            .repository
+            .set_plan(id, plan)
+            .await?
+            .ok_or_else(|| Error::NotFound("Tenant not found".to_string()))?;
+        self.notify(TenantEvent::PlanChanged, &tenant).await?;
+        Ok(tenant)
+    }
+
+    /// Ensures a tenant's plan includes a capability, asking for an upgrade otherwise
+    pub async fn ensure_capability(
+        &self,
+        tenant_id: TenantId,
+        capability: Capability,
+    ) -> Result<()> {
+        let tenant = self
+            .get_tenant(tenant_id.0)
+            .await?
+            .ok_or_else(|| Error::NotFound("Tenant not found".to_string()))?;
+        ensure_capability(&tenant, capability)
+    }
+
+    /// Lists the deleted tenants that can still be restored
+    pub async fn list_deleted_tenants(&self) -> Result<Vec<Tenant>> {
+        self.repository.list_deleted_tenants().await
+    }
+
+    /// Restores a deleted tenant that was not purged yet
+    pub async fn restore_tenant(&self, id: Uuid) -> Result<Tenant> {
+        let tenant = self
+            .repository
+            .restore_tenant(id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Deleted tenant not found".to_string()))?;
+        self.notify(TenantEvent::Restored, &tenant).await?;
+        Ok(tenant)
+    }
+
+    /// Permanently removes the tenants deleted longer than the retention period ago,
+    /// returning how many were purged
+    ///
+    /// A hook failing for a purged tenant does not stop the others from being notified.
+    pub async fn purge_deleted_tenants(&self, retention: time::Duration) -> Result<usize> {
+        let purged = self
+            .repository
+            .purge_deleted_tenants(OffsetDateTime::now_utc() - retention)
+            .await?;
+        for tenant in &purged {
+            if let Err(e) = self.notify(TenantEvent::Purged, tenant).await {
+                tracing::error!(
+                    "Failed to notify hooks about purged tenant {}: {}",
+                    tenant.id.0,
+                    e
+                );
+            }
+        }
+        Ok(purged.len())
+    }
+
+    /// Archives an inactive, suspended or deleted tenant to cold storage
+    ///
+    /// The tenant's data is exported to the archive store and removed from the
+    /// database in one transaction, leaving a manifest the tenant can be restored
+    /// from. Sessions and refresh tokens are not archived.
+    pub async fn archive_tenant(&self, id: Uuid) -> Result<ArchiveManifest> {
+        let store = self.archive_store()?;
+        let mut tx = self.repository.begin().await?;
+        let tenant = lock_tenant(&mut tx, id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Tenant not found".to_string()))?;
+        if tenant.active && tenant.suspended_at.is_none() && tenant.deleted_at.is_none() {
+            return Err(Error::Conflict(
+                "Only inactive, suspended or deleted tenants can be archived".to_string(),
+            ));
+        }
+        if tenant.region.is_some() {
+            return Err(Error::InvalidInput(
+                "Tenants with a data residency region cannot be archived".to_string(),
+            ));
+        }
+
+        let mut archive = TenantArchive::new(tenant.id);
+        for (table, selector) in ARCHIVED_TABLES {
+            let rows = export_tenant_rows(&mut tx, table, selector, tenant.id).await?;
+            archive.tables.insert(table.to_string(), rows);
+        }
+        let data = archive.encode()?;
+        let manifest = ArchiveManifest::new(&tenant, &archive, &data);
+        store.put(&manifest.object_key, data).await?;
+
+        let removed: Result<()> = async {
+            delete_tenant_data(&mut tx, tenant.id).await?;
+            insert_archive_manifest(&mut tx, &manifest).await?;
+            tx.commit().await?;
+            Ok(())
+        }
+        .await;
+        if let Err(e) = removed {
+            // The tenant stays in the database, so its archive is not needed
+            if let Err(e) = store.delete(&manifest.object_key).await {
+                tracing::error!(
+                    "Failed to delete unused archive {}: {}",
+                    manifest.object_key,
+                    e
+                );
+            }
+            return Err(e);
+        }
+
+        self.invalidate_settings(tenant.id).await;
+        self.notify(TenantEvent::Archived, &tenant).await?;
+        Ok(manifest)
+    }
+
+    /// Lists the archived tenants that can be restored, newest first
+    pub async fn list_archives(&self) -> Result<Vec<ArchiveManifest>> {
+        self.repository.list_archives().await
+    }
+
+    /// Restores an archived tenant from cold storage
+    ///
+    /// The tenant comes back in the state it was archived in, e.g. still suspended.
+    /// Fails with a conflict if its ID, domain or slug were taken meanwhile.
+    pub async fn restore_archived_tenant(&self, tenant_id: Uuid) -> Result<Tenant> {
+        let store = self.archive_store()?;
+        let manifest = self
+            .repository
+            .get_archive(tenant_id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Archived tenant not found".to_string()))?;
+        let archive = manifest.open(&store.get(&manifest.object_key).await?)?;
+
+        let mut tx = self.repository.begin().await?;
+        for (table, _) in ARCHIVED_TABLES {
+            if let Some(rows) = archive.tables.get(*table) {
+                import_tenant_rows(&mut tx, table, rows).await?;
+            }
+        }
+        if !mark_archive_restored(&mut tx, manifest.id).await? {
+            return Err(Error::Conflict("Tenant was already restored".to_string()));
+        }
+        let tenant = lock_tenant(&mut tx, tenant_id)
+            .await?
+            .ok_or_else(|| Error::Internal("Archive holds no tenant".to_string()))?;
+        tx.commit().await?;
+
+        self.notify(TenantEvent::Unarchived, &tenant).await?;
+        Ok(tenant)
+    }
+
+    /// Gets the store archived tenants are kept in
+    fn archive_store(&self) -> Result<&Arc<dyn ArchiveStore>> {
+        self.archive_store
+            .as_ref()
+            .ok_or_else(|| Error::Internal("Tenant archiving is not enabled".to_string()))
+    }
+
+    /// Periodically purges the tenants deleted longer than the retention period ago
+    pub fn spawn_purge_worker(
+        self,
+        retention: time::Duration,
+        interval: Duration,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match self.purge_deleted_tenants(retention).await {
+                    Ok(0) => {},
+                    Ok(purged) => tracing::info!("Purged {} deleted tenants", purged),
+                    Err(e) => tracing::error!("Failed to purge deleted tenants: {}", e),
+                }
+            }
+        })
     }
+
+    /// Gets a tenant the actor administers, hiding other tenants
+    pub async fn get_managed_tenant(&self, actor: &User, id: Uuid) -> Result<Option<Tenant>> {
+        if ensure_tenant_boundary(actor, TenantId(id)).is_err() {
+            return Ok(None);
+        }
+        self.get_tenant(id).await
+    }
+
+    /// Lists the tenants the actor administers, all of them only for super admins
+    pub async fn list_managed_tenants(&self, actor: &User) -> Result<Vec<Tenant>> {
+        if is_super_admin(actor) {
+            return self.list_tenants().await;
+        }
+        Ok(self
+            .get_tenant(actor.tenant_id.0)
+            .await?
+            .into_iter()
+            .collect())
+    }
+
+    /// Updates the name, domain and slug of a tenant on behalf of one of its admins
+    ///
+    /// The other attributes are left as they are; e.g. whether the tenant is active
+    /// is only changed through its approval or suspension.
+    pub async fn update_managed_tenant(
+        &self,
+        actor: &User,
+        id: Uuid,
+        request: TenantRequest,
+    ) -> Result<Tenant> {
+        Self::ensure_manageable(actor, TenantId(id), PermissionAction::Update)?;
+        let mut tenant = self
+            .get_tenant(id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Tenant not found".to_string()))?;
+        tenant.name = request.name;
+        if let Some(domain) = request.domain {
+            tenant.domain = domain;
+        }
+        if let Some(slug) = request.slug {
+            tenant.slug = Some(slug);
+        }
+        tenant.updated_at = OffsetDateTime::now_utc();
+        self.update_tenant(tenant).await
+    }
+
+    /// Deletes a tenant on behalf of one of its admins
+    pub async fn delete_managed_tenant(&self, actor: &User, id: Uuid) -> Result<()> {
+        Self::ensure_manageable(actor, TenantId(id), PermissionAction::Delete)?;
+        self.delete_tenant(&id.to_string()).await
+    }
+
+    /// Restores a deleted tenant on behalf of a super admin
+    ///
+    /// The admins of a deleted tenant can no longer log in, so only super admins
+    /// can restore it.
+    pub async fn restore_managed_tenant(&self, actor: &User, id: Uuid) -> Result<Tenant> {
+        Self::ensure_super_admin(actor, "restore")?;
+        self.restore_tenant(id).await
+    }
+
+    /// Suspends a tenant on behalf of a super admin
+    pub async fn suspend_managed_tenant(
+        &self,
+        actor: &User,
+        id: Uuid,
+        reason: Option<String>,
+    ) -> Result<Tenant> {
+        Self::ensure_super_admin(actor, "suspend")?;
+        self.suspend_tenant(id, reason).await
+    }
+
+    /// Reactivates a suspended tenant on behalf of a super admin
+    ///
+    /// The admins of a suspended tenant cannot access it, so only super admins
+    /// can reactivate it.
+    pub async fn reactivate_managed_tenant(&self, actor: &User, id: Uuid) -> Result<Tenant> {
+        Self::ensure_super_admin(actor, "reactivate")?;
+        self.reactivate_tenant(id).await
+    }
+
+    /// Archives a tenant to cold storage on behalf of a super admin
+    pub async fn archive_managed_tenant(&self, actor: &User, id: Uuid) -> Result<ArchiveManifest> {
+        Self::ensure_super_admin(actor, "archive")?;
+        self.archive_tenant(id).await
+    }
+
+    /// Restores an archived tenant on behalf of a super admin
+    pub async fn restore_managed_archive(&self, actor: &User, tenant_id: Uuid) -> Result<Tenant> {
+        Self::ensure_super_admin(actor, "restore archived")?;
+        self.restore_archived_tenant(tenant_id).await
+    }
+
+    /// Ensures the actor is a super admin, who alone may change a tenant's lifecycle
+    /// or administer tenants other than their own
+    pub(crate) fn ensure_super_admin(actor: &User, action: &str) -> Result<()> {
+        if !is_super_admin(actor) {
+            return Err(Error::Authorization(format!(
+                "Only super admins can {} tenants",
+                action
+            )));
+        }
+        Ok(())
+    }
+
+    /// Ensures the actor may change a tenant, which is confined to their own one
+    fn ensure_manageable(
+        actor: &User,
+        tenant_id: TenantId,
+        action: PermissionAction,
+    ) -> Result<()> {
+        ensure_tenant_boundary(actor, tenant_id)?;
+        if !has_permission(actor, action, "tenants") {
+            return Err(Error::Authorization(format!(
+                "Missing permission to {} tenants",
+                action
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Ensures a setting has a valid key and, if its meaning is known, a valid value
+fn validate_setting(key: &str, value: &Value) -> Result<()> {
+    validate_setting_key(key)?;
+    if key == AUTH_POLICY_SETTING {
+        AuthPolicyOverrides::from_setting(value)?;
+    }
+    if key == FEATURE_FLAGS_SETTING {
+        parse_feature_flags(value)?;
+    }
+    Ok(())
+}
+
+/// Validates the tenant name and admin of an onboarding; the domain is validated with the tenant
+fn validate_onboarding(name: &str, email: &str, password: &str) -> Result<()> {
+    if name.trim().is_empty() {
+        return Err(Error::Validation(
+            "Tenant name must not be empty".to_string(),
+        ));
+    }
+    UserEmail::validate(email)?;
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(Error::Validation(format!(
+            "Password must have at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::core::database::tests::create_test_db;
+    use crate::modules::identity::{models::Permission, repository::UserRepository};
+    use crate::modules::tenant::models::{SortOrder, TenantSort};
 
     #[tokio::test]
     async fn test_tenant_crud() {
@@ -105,4 +1286,853 @@ mod tests {
         let deleted = service.get_tenant(tenant.id.0).await.unwrap();
         assert!(deleted.is_none());
     }
+
+    #[derive(Debug, Default)]
+    struct RecordingHook {
+        events: std::sync::Mutex<Vec<(TenantEvent, Uuid)>>,
+        fail_on_create: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl TenantHook for RecordingHook {
+        async fn on_tenant_event(&self, event: TenantEvent, tenant: &Tenant) -> Result<()> {
+            if self.fail_on_create && event == TenantEvent::Created {
+                return Err(crate::shared::error::Error::Internal(
+                    "Provisioning failed".to_string(),
+                ));
+            }
+            self.events.lock().unwrap().push((event, tenant.id.0));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tenant_hooks() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let mut service = TenantService::new(TenantRepository::new(db.get_pool()));
+        let hook = Arc::new(RecordingHook::default());
+        service.register_hook(hook.clone());
+
+        let tenant = Tenant::new(
+            "Hooked Tenant".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        let created = service.create_tenant(tenant.clone()).await.unwrap();
+        let mut updated = created.clone();
+        updated.name = "Renamed Tenant".to_string();
+        service.update_tenant(updated).await.unwrap();
+        let suspended = service
+            .suspend_tenant(tenant.id.0, Some("Unpaid invoices".to_string()))
+            .await
+            .unwrap();
+        assert!(suspended.is_suspended());
+        let reactivated = service.reactivate_tenant(tenant.id.0).await.unwrap();
+        assert!(!reactivated.is_suspended());
+        assert!(matches!(
+            service.reactivate_tenant(tenant.id.0).await,
+            Err(Error::NotFound(_))
+        ));
+        service
+            .delete_tenant(&tenant.id.0.to_string())
+            .await
+            .unwrap();
+        let restored = service.restore_tenant(tenant.id.0).await.unwrap();
+        assert!(!restored.is_deleted());
+        service
+            .delete_tenant(&tenant.id.0.to_string())
+            .await
+            .unwrap();
+        assert!(service
+            .list_deleted_tenants()
+            .await
+            .unwrap()
+            .iter()
+            .any(|deleted| deleted.id == tenant.id));
+        assert_eq!(
+            service
+                .purge_deleted_tenants(time::Duration::days(30))
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            service
+                .purge_deleted_tenants(time::Duration::ZERO)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(matches!(
+            service.restore_tenant(tenant.id.0).await,
+            Err(Error::NotFound(_))
+        ));
+
+        assert_eq!(
+            *hook.events.lock().unwrap(),
+            vec![
+                (TenantEvent::Created, tenant.id.0),
+                (TenantEvent::Updated, tenant.id.0),
+                (TenantEvent::Suspended, tenant.id.0),
+                (TenantEvent::Reactivated, tenant.id.0),
+                (TenantEvent::Deleted, tenant.id.0),
+                (TenantEvent::Restored, tenant.id.0),
+                (TenantEvent::Deleted, tenant.id.0),
+                (TenantEvent::Purged, tenant.id.0),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_provisioning_removes_tenant() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let mut service = TenantService::new(TenantRepository::new(db.get_pool()));
+        service.register_hook(Arc::new(RecordingHook {
+            fail_on_create: true,
+            ..Default::default()
+        }));
+
+        let tenant = Tenant::new(
+            "Failing Tenant".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        assert!(service.create_tenant(tenant.clone()).await.is_err());
+        assert!(service.get_tenant(tenant.id.0).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tenant_slugs() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let service = TenantService::new(TenantRepository::new(db.get_pool()));
+
+        // Slugs are derived from names unless given
+        let acme = service
+            .create_tenant(Tenant::new(
+                "Acme Gärten".to_string(),
+                format!("{}.example.com", Uuid::new_v4()),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(acme.slug.as_deref(), Some("acme-gaerten"));
+
+        let mut other = Tenant::new(
+            "Other".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        other.slug = Some("acme-gaerten".to_string());
+        let result = service.create_tenant(other.clone()).await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+        other.slug = Some("login".to_string());
+        let result = service.create_tenant(other.clone()).await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        // Renaming keeps the old slug resolving to the tenant and reserved for it
+        let mut renamed = acme.clone();
+        renamed.slug = Some("acme".to_string());
+        service.update_tenant(renamed).await.unwrap();
+        assert!(matches!(
+            service.resolve_slug("acme").await.unwrap(),
+            Some(SlugResolution::Current(t)) if t.id == acme.id
+        ));
+        assert!(matches!(
+            service.resolve_slug("acme-gaerten").await.unwrap(),
+            Some(SlugResolution::Renamed(t)) if t.slug.as_deref() == Some("acme")
+        ));
+        other.slug = Some("acme-gaerten".to_string());
+        let result = service.create_tenant(other).await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        // Taking a previous slug back makes it current again
+        let mut reverted = acme.clone();
+        reverted.slug = Some("acme-gaerten".to_string());
+        service.update_tenant(reverted).await.unwrap();
+        assert!(matches!(
+            service.resolve_slug("acme-gaerten").await.unwrap(),
+            Some(SlugResolution::Current(_))
+        ));
+        assert!(matches!(
+            service.resolve_slug("acme").await.unwrap(),
+            Some(SlugResolution::Renamed(_))
+        ));
+        assert!(service.resolve_slug("unknown").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_managed_tenant() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let service = TenantService::new(TenantRepository::new(db.get_pool()));
+        let mut tenant = Tenant::new(
+            "Acme".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        tenant.active = false;
+        let tenant = service.create_tenant(tenant).await.unwrap();
+
+        let mut admin = User::new(tenant.id, "admin@acme.com".to_string(), String::new());
+        let mut role = create_admin_role();
+        role.permissions.push(Permission::new(
+            "Update Tenant".to_string(),
+            PermissionAction::Update,
+            "tenants".to_string(),
+        ));
+        admin.roles.push(role);
+
+        // Admins edit the name, domain and slug, but cannot activate their tenant
+        let request = TenantRequest {
+            name: "Acme Corp".to_string(),
+            domain: None,
+            slug: Some("acme-corp".to_string()),
+            region: None,
+            admin_email: None,
+            plan: None,
+            template_id: None,
+        };
+        let updated = service
+            .update_managed_tenant(&admin, tenant.id.0, request)
+            .await
+            .unwrap();
+        assert_eq!(updated.name, "Acme Corp");
+        assert_eq!(updated.domain, tenant.domain);
+        assert_eq!(updated.slug.as_deref(), Some("acme-corp"));
+        assert!(!updated.active);
+
+        // Updates without a slug keep the current one
+        let request = TenantRequest {
+            name: "Acme".to_string(),
+            domain: None,
+            slug: None,
+            region: None,
+            admin_email: None,
+            plan: None,
+            template_id: None,
+        };
+        let updated = service
+            .update_managed_tenant(&admin, tenant.id.0, request)
+            .await
+            .unwrap();
+        assert_eq!(updated.slug.as_deref(), Some("acme-corp"));
+    }
+
+    #[test]
+    fn test_validate_onboarding() {
+        assert!(validate_onboarding("Acme", "admin@acme.com", "password123").is_ok());
+        assert!(validate_onboarding(" ", "admin@acme.com", "password123").is_err());
+        assert!(validate_onboarding("Acme", "admin", "password123").is_err());
+        assert!(validate_onboarding("Acme", "admin@acme.com", "short").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_provision() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let mut service = TenantService::new(TenantRepository::new(db.get_pool()))
+            .with_default_sso_provider(SsoProviderSpec {
+                name: "Okta".to_string(),
+                provider_type: "oidc".to_string(),
+                client_id: "acme".to_string(),
+                client_secret: "secret".to_string(),
+                metadata_url: None,
+                issuer: Some("https://acme.okta.com".to_string()),
+            });
+        let hook = Arc::new(RecordingHook::default());
+        service.register_hook(hook.clone());
+
+        let domain = format!("{}.example.com", Uuid::new_v4());
+        let onboarding = service
+            .provision("Acme", &domain, "admin@acme.com", "password123")
+            .await
+            .unwrap();
+        assert_eq!(onboarding.tenant.slug.as_deref(), Some("acme"));
+        assert_eq!(onboarding.admin.tenant_id, onboarding.tenant.id);
+        assert!(onboarding
+            .admin
+            .roles
+            .iter()
+            .any(|role| role.name == "Admin"));
+        assert!(onboarding.sso_provider_id.is_some());
+        assert_eq!(
+            *hook.events.lock().unwrap(),
+            vec![(TenantEvent::Created, onboarding.tenant.id.0)]
+        );
+
+        let admin = crate::modules::identity::repository::UserRepository::new(db.get_pool())
+            .get_user_by_email("admin@acme.com", onboarding.tenant.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(admin.roles.iter().any(|role| role.name == "Admin"));
+
+        // Domains are compared normalized
+        let result = service
+            .provision(
+                "Acme",
+                &format!("https://{}/", domain.to_uppercase()),
+                "admin@acme.com",
+                "password123",
+            )
+            .await;
+        assert!(matches!(result, Err(Error::Conflict(_))));
+        let result = service
+            .provision("Acme", "acme", "admin@acme.com", "password123")
+            .await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        let (normalized, available) = service
+            .check_tenant_domain(&format!("{}.", domain.to_uppercase()))
+            .await
+            .unwrap();
+        assert_eq!(normalized, domain);
+        assert!(!available);
+        let (_, available) = service
+            .check_tenant_domain(&format!("{}.example.com", Uuid::new_v4()))
+            .await
+            .unwrap();
+        assert!(available);
+    }
+
+    #[tokio::test]
+    async fn test_failed_provisioning_rolls_back_onboarding() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let mut service = TenantService::new(TenantRepository::new(db.get_pool()));
+        service.register_hook(Arc::new(RecordingHook {
+            fail_on_create: true,
+            ..Default::default()
+        }));
+
+        let domain = format!("{}.example.com", Uuid::new_v4());
+        let result = service
+            .provision("Acme", &domain, "admin@acme.com", "password123")
+            .await;
+        assert!(result.is_err());
+        assert!(service
+            .get_tenant_by_domain(&domain)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tenant_list_paging() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let service = TenantService::new(TenantRepository::new(db.get_pool()));
+
+        for name in ["Acme", "Globex", "Initech"] {
+            service
+                .create_tenant(Tenant::new(
+                    name.to_string(),
+                    format!("{}.example.com", name.to_lowercase()),
+                ))
+                .await
+                .unwrap();
+        }
+        let mut inactive = Tenant::new("Acme Labs".to_string(), "labs.example.com".to_string());
+        inactive.active = false;
+        service.create_tenant(inactive).await.unwrap();
+
+        let page = service
+            .list_tenants_page(TenantListQuery {
+                per_page: Some(2),
+                sort: Some(TenantSort::Name),
+                order: Some(SortOrder::Asc),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 4);
+        let names: Vec<_> = page.tenants.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["Acme", "Acme Labs"]);
+
+        // Filters apply to the total, and wildcards in the search match literally
+        let page = service
+            .list_tenants_page(TenantListQuery {
+                active: Some(true),
+                search: Some("ACME".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.tenants[0].name, "Acme");
+        let page = service
+            .list_tenants_page(TenantListQuery {
+                search: Some("%".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 0);
+
+        // Pages past the end are empty but still report the total
+        let page = service
+            .list_tenants_page(TenantListQuery {
+                page: Some(3),
+                per_page: Some(2),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(page.tenants.is_empty());
+        assert_eq!(page.total, 4);
+    }
+
+    #[tokio::test]
+    async fn test_tenant_settings() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let service =
+            TenantService::new(TenantRepository::new(db.get_pool())).with_default_settings(
+                BTreeMap::from([("session.lifetime_secs".to_string(), serde_json::json!(3600))]),
+            );
+
+        let onboarding = service
+            .provision(
+                "Acme",
+                &format!("{}.example.com", Uuid::new_v4()),
+                "admin@acme.com",
+                "password123",
+            )
+            .await
+            .unwrap();
+        let tenant_id = onboarding.tenant.id;
+        assert_eq!(
+            service
+                .setting::<u64>(tenant_id, "session.lifetime_secs")
+                .await
+                .unwrap(),
+            Some(3600)
+        );
+
+        // Changes replace cached settings right away
+        service
+            .set_setting(tenant_id, "session.lifetime_secs", serde_json::json!(600))
+            .await
+            .unwrap();
+        assert_eq!(
+            service
+                .setting::<u64>(tenant_id, "session.lifetime_secs")
+                .await
+                .unwrap(),
+            Some(600)
+        );
+        service
+            .remove_setting(tenant_id, "session.lifetime_secs")
+            .await
+            .unwrap();
+        assert!(service.settings(tenant_id).await.unwrap().is_empty());
+        assert!(matches!(
+            service
+                .remove_setting(tenant_id, "session.lifetime_secs")
+                .await,
+            Err(Error::NotFound(_))
+        ));
+
+        let result = service
+            .set_setting(tenant_id, "Invalid Key", serde_json::json!(true))
+            .await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+        let result = service
+            .set_setting(TenantId::new(), "branding", serde_json::json!({}))
+            .await;
+        assert!(matches!(result, Err(Error::NotFound(_))));
+    }
+
+    /// Resolver answering lookups from in-memory TXT records
+    #[derive(Debug, Default)]
+    struct StaticTxtResolver {
+        records: std::sync::Mutex<Vec<(String, String)>>,
+    }
+
+    impl StaticTxtResolver {
+        fn publish(&self, claim: &TenantDomain) {
+            self.records
+                .lock()
+                .unwrap()
+                .push((claim.challenge_name(), claim.challenge_value()));
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl TxtResolver for StaticTxtResolver {
+        async fn lookup_txt(&self, name: &str) -> Result<Vec<String>> {
+            Ok(self
+                .records
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(record_name, _)| record_name == name)
+                .map(|(_, value)| value.clone())
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_domain_verification() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let resolver = Arc::new(StaticTxtResolver::default());
+        let service = TenantService::new(TenantRepository::new(db.get_pool()))
+            .with_txt_resolver(resolver.clone());
+
+        let owner = service
+            .create_tenant(Tenant::new(
+                "Acme".to_string(),
+                format!("{}.example.com", Uuid::new_v4()),
+            ))
+            .await
+            .unwrap();
+        let squatter = service
+            .create_tenant(Tenant::new(
+                "Squatter".to_string(),
+                format!("{}.example.com", Uuid::new_v4()),
+            ))
+            .await
+            .unwrap();
+
+        // Both tenants can claim the domain, but neither gets it routed before verifying
+        let claim = service
+            .claim_domain(owner.id, "Login.Acme.com")
+            .await
+            .unwrap();
+        assert_eq!(claim.domain, "login.acme.com");
+        assert!(!claim.is_verified());
+        let squatter_claim = service
+            .claim_domain(squatter.id, "login.acme.com")
+            .await
+            .unwrap();
+        assert_ne!(squatter_claim.token, claim.token);
+        let again = service
+            .claim_domain(owner.id, "login.acme.com")
+            .await
+            .unwrap();
+        assert_eq!(again.token, claim.token);
+        assert!(service
+            .resolve_domain("login.acme.com")
+            .await
+            .unwrap()
+            .is_none());
+
+        // Without the TXT record, verification fails
+        let result = service.verify_domain(squatter.id, "login.acme.com").await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        // Once the owner publishes its challenge, the worker verifies the domain
+        resolver.publish(&claim);
+        let verified = service
+            .verify_pending_domains(Duration::ZERO, time::Duration::days(7))
+            .await
+            .unwrap();
+        assert_eq!(verified, 1);
+        let resolved = service
+            .resolve_domain("login.acme.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(resolved.id, owner.id);
+
+        // The squatter's pending claim is dropped and the domain cannot be claimed again
+        assert!(service.list_domains(squatter.id).await.unwrap().is_empty());
+        let result = service.claim_domain(squatter.id, "login.acme.com").await;
+        assert!(matches!(result, Err(Error::Conflict(_))));
+
+        service
+            .remove_domain(owner.id, "login.acme.com")
+            .await
+            .unwrap();
+        assert!(service
+            .resolve_domain("login.acme.com")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[derive(Debug, Default)]
+    struct RecordingMailer {
+        messages: std::sync::Mutex<Vec<EmailMessage>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Mailer for RecordingMailer {
+        async fn send(&self, message: &EmailMessage) -> Result<()> {
+            self.messages.lock().unwrap().push(message.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_tenant_with_admin() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let mailer = Arc::new(RecordingMailer::default());
+        let service = TenantService::new(TenantRepository::new(db.get_pool()))
+            .with_mailer(mailer.clone())
+            .with_invitations(
+                "https://acci.example.com/invite?token=".to_string(),
+                time::Duration::hours(1),
+            );
+        let users = UserRepository::new(db.get_pool());
+
+        let (tenant, invitation) = service
+            .create_tenant_with_admin(
+                Tenant::new(
+                    "Acme".to_string(),
+                    format!("{}.example.com", Uuid::new_v4()),
+                ),
+                " admin@acme.com ",
+            )
+            .await
+            .unwrap();
+        assert_eq!(invitation.email, "admin@acme.com");
+
+        // The admin holds the admin role but cannot log in before accepting
+        let admin = users
+            .get_user_by_email("admin@acme.com", tenant.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!admin.active);
+        assert!(admin
+            .roles
+            .iter()
+            .any(|role| role.role_type == RoleType::Admin));
+
+        let message = mailer.messages.lock().unwrap().pop().unwrap();
+        assert_eq!(message.to, "admin@acme.com");
+        let token = message
+            .body
+            .split("https://acci.example.com/invite?token=")
+            .nth(1)
+            .and_then(|rest| rest.split_whitespace().next())
+            .unwrap()
+            .to_string();
+
+        let result = service.accept_invitation(&token, "short").await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+        let accepted = service
+            .accept_invitation(&token, "correct horse battery")
+            .await
+            .unwrap();
+        assert_eq!(accepted.user_id, admin.id);
+
+        let admin = users.get_user_by_id(admin.id).await.unwrap().unwrap();
+        assert!(admin.active);
+        assert!(AuthenticationService::verify_password(
+            "correct horse battery",
+            &admin.password_hash
+        )
+        .unwrap());
+
+        // Invitations can only be accepted once
+        let result = service.accept_invitation(&token, "another password").await;
+        assert!(matches!(result, Err(Error::NotFound(_))));
+
+        let result = service
+            .create_tenant_with_admin(
+                Tenant::new(
+                    "Acme".to_string(),
+                    format!("{}.example.com", Uuid::new_v4()),
+                ),
+                "not an email",
+            )
+            .await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_tenant_templates() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let service = TenantService::new(TenantRepository::new(db.get_pool()))
+            .with_mailer(Arc::new(RecordingMailer::default()));
+        let roles = RoleRepository::new(db.get_pool());
+        let users = UserRepository::new(db.get_pool());
+
+        let mut template: TenantTemplate = serde_json::from_value(serde_json::json!({
+            "name": format!("Agency {}", Uuid::new_v4()),
+            "plan": "pro",
+            "roles": [
+                { "name": "Admin", "role_type": "Admin", "parents": ["Editor"] },
+                {
+                    "name": "Editor",
+                    "permissions": [{ "name": "Edit Projects", "action": "Update", "resource": "projects" }]
+                }
+            ],
+            "settings": { "locale": "de" },
+            "features": { "beta_dashboard": true }
+        }))
+        .unwrap();
+        template = service.create_template(template).await.unwrap();
+        let result = service.create_template(template.clone()).await;
+        assert!(matches!(result, Err(Error::Conflict(_))));
+
+        let (tenant, invitation) = service
+            .create_tenant_from_template(
+                Tenant::new(
+                    "Acme".to_string(),
+                    format!("{}.example.com", Uuid::new_v4()),
+                ),
+                template.id,
+                Some("admin@acme.com"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(tenant.plan, TenantPlan::Pro);
+        let settings = service.settings(tenant.id).await.unwrap();
+        assert!(settings.is_feature_enabled("beta_dashboard"));
+        assert_eq!(
+            settings.get::<String>("locale").unwrap().as_deref(),
+            Some("de")
+        );
+
+        // The invited admin holds the template's admin role, which inherits from the editor role
+        let tenant_roles = roles.list_roles(tenant.id).await.unwrap();
+        assert_eq!(tenant_roles.len(), 2);
+        let admin = users
+            .get_user_by_id(invitation.unwrap().user_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(admin.roles.len(), 1);
+        assert!(admin.roles[0]
+            .inherited_permissions
+            .iter()
+            .any(|p| p.resource == "projects"));
+
+        // Clones get the configuration of their source, but none of its users
+        let (clone, invitation) = service
+            .clone_tenant(
+                tenant.id.0,
+                Tenant::new(
+                    "Acme Clone".to_string(),
+                    format!("{}.example.com", Uuid::new_v4()),
+                ),
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(invitation.is_none());
+        assert_eq!(clone.plan, TenantPlan::Pro);
+        assert_eq!(service.settings(clone.id).await.unwrap(), settings);
+        let mut names: Vec<String> = roles
+            .list_roles(clone.id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|role| role.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["Admin", "Editor"]);
+        let (_, total) = users
+            .list_tenant_users(clone.id, None, 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(total, 0);
+
+        service.delete_template(template.id).await.unwrap();
+        let result = service.get_template(template.id).await;
+        assert!(matches!(result, Err(Error::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_tenant_archiving() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let root = std::env::temp_dir().join(format!("acci-archives-{}", Uuid::new_v4()));
+        let mut service = TenantService::new(TenantRepository::new(db.get_pool()))
+            .with_mailer(Arc::new(RecordingMailer::default()));
+        let users = UserRepository::new(db.get_pool());
+
+        let (tenant, _) = service
+            .create_tenant_with_admin(
+                Tenant::new(
+                    "Churned".to_string(),
+                    format!("{}.example.com", Uuid::new_v4()),
+                ),
+                "admin@churned.com",
+            )
+            .await
+            .unwrap();
+        service
+            .set_setting(tenant.id, "locale", serde_json::json!("de"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            service.archive_tenant(tenant.id.0).await,
+            Err(Error::Internal(_))
+        ));
+
+        service = service.with_archive_store(Arc::new(
+            crate::modules::tenant::archive::FileArchiveStore::new(&root),
+        ));
+        let hook = Arc::new(RecordingHook::default());
+        service.register_hook(hook.clone());
+
+        // Active tenants are still in use
+        assert!(matches!(
+            service.archive_tenant(tenant.id.0).await,
+            Err(Error::Conflict(_))
+        ));
+        service.suspend_tenant(tenant.id.0, None).await.unwrap();
+
+        let manifest = service.archive_tenant(tenant.id.0).await.unwrap();
+        assert_eq!(manifest.tenant_domain, tenant.domain);
+        assert_eq!(manifest.row_counts["users"], 1);
+        assert_eq!(manifest.row_counts["tenant_settings"], 1);
+        assert!(root.join(&manifest.object_key).exists());
+        assert!(service.get_tenant(tenant.id.0).await.unwrap().is_none());
+        assert!(users
+            .get_user_by_email("admin@churned.com", tenant.id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(service
+            .list_archives()
+            .await
+            .unwrap()
+            .iter()
+            .any(|archive| archive.id == manifest.id));
+
+        // The tenant comes back as it was archived, still suspended
+        let restored = service.restore_archived_tenant(tenant.id.0).await.unwrap();
+        assert!(restored.is_suspended());
+        assert!(users
+            .get_user_by_email("admin@churned.com", tenant.id)
+            .await
+            .unwrap()
+            .is_some());
+        let locale: Option<String> = service.setting(tenant.id, "locale").await.unwrap();
+        assert_eq!(locale.as_deref(), Some("de"));
+        assert!(matches!(
+            service.restore_archived_tenant(tenant.id.0).await,
+            Err(Error::NotFound(_))
+        ));
+
+        assert_eq!(
+            *hook.events.lock().unwrap(),
+            vec![
+                (TenantEvent::Suspended, tenant.id.0),
+                (TenantEvent::Archived, tenant.id.0),
+                (TenantEvent::Unarchived, tenant.id.0),
+            ]
+        );
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_tenant_regions() {
+        let (db, _container) = create_test_db().await.unwrap();
+        let service = TenantService::new(TenantRepository::new(db.get_pool()))
+            .with_regions(vec!["eu".to_string()]);
+
+        let mut tenant = Tenant::new(
+            "EU Tenant".to_string(),
+            format!("{}.example.com", Uuid::new_v4()),
+        );
+        tenant.region = Some("us".to_string());
+        let result = service.create_tenant(tenant.clone()).await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        tenant.region = Some("eu".to_string());
+        let created = service.create_tenant(tenant).await.unwrap();
+        assert_eq!(created.region.as_deref(), Some("eu"));
+
+        // Updates keep the region
+        let mut updated = created.clone();
+        updated.region = None;
+        updated.name = "Renamed EU Tenant".to_string();
+        let updated = service.update_tenant(updated).await.unwrap();
+        assert_eq!(updated.region.as_deref(), Some("eu"));
+    }
 }
@@ -0,0 +1,207 @@
+use moka::sync::Cache;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::shared::{
+    cache::{CacheInvalidation, CacheInvalidationHandler},
+    error::{Error, Result},
+    types::TenantId,
+};
+
+/// Maximum length of a setting key
+const MAX_KEY_LENGTH: usize = 100;
+
+/// Tenants whose settings are kept per local cache
+const MAX_CACHED_TENANTS: u64 = 10_000;
+
+/// Key of the setting holding a tenant's feature flags, e.g. `{"beta_dashboard": true}`
+pub const FEATURE_FLAGS_SETTING: &str = "features";
+
+/// Settings of a tenant, e.g. password policy overrides, session lifetimes and branding
+///
+/// Values are stored as JSON and read with the type the feature using them expects.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct TenantSettings {
+    values: BTreeMap<String, Value>,
+}
+
+impl TenantSettings {
+    /// Creates settings from their stored values
+    pub fn new(values: BTreeMap<String, Value>) -> Self {
+        Self { values }
+    }
+
+    /// Gets a setting, `None` if the tenant did not set it
+    ///
+    /// Fails if the stored value does not have the requested type.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.values
+            .get(key)
+            .map(|value| {
+                serde_json::from_value(value.clone()).map_err(|e| {
+                    Error::Validation(format!("Invalid value of setting {}: {}", key, e))
+                })
+            })
+            .transpose()
+    }
+
+    /// Gets the raw JSON value of a setting
+    pub fn get_value(&self, key: &str) -> Option<&Value> {
+        self.values.get(key)
+    }
+
+    /// Gets all settings by key
+    pub fn values(&self) -> &BTreeMap<String, Value> {
+        &self.values
+    }
+
+    /// Checks if a feature flag is set for the tenant; unknown features are disabled
+    pub fn is_feature_enabled(&self, feature: &str) -> bool {
+        self.values
+            .get(FEATURE_FLAGS_SETTING)
+            .and_then(|flags| flags.get(feature))
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+    }
+
+    /// Checks if the tenant set no settings at all
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// Ensures a setting key is lowercase ASCII, digits, `_`, `-` or `.`, e.g. `branding.logo_url`
+pub fn validate_setting_key(key: &str) -> Result<()> {
+    let valid_chars = key
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-' | '.'));
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH || !valid_chars {
+        return Err(Error::Validation(format!(
+            "Invalid setting key {}: use up to {} lowercase letters, digits, '_', '-' or '.'",
+            key, MAX_KEY_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+/// Local cache of tenant settings
+///
+/// Clones share their entries, so the cache registered for invalidations is the
+/// one the tenant service reads.
+#[derive(Debug, Clone)]
+pub struct TenantSettingsCache {
+    settings: Cache<TenantId, TenantSettings>,
+}
+
+impl TenantSettingsCache {
+    /// Creates a cache keeping settings for at most `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            settings: Cache::builder()
+                .max_capacity(MAX_CACHED_TENANTS)
+                .time_to_live(ttl)
+                .build(),
+        }
+    }
+
+    /// Gets the cached settings of a tenant
+    pub fn get(&self, tenant_id: TenantId) -> Option<TenantSettings> {
+        self.settings.get(&tenant_id)
+    }
+
+    /// Caches the settings of a tenant
+    pub fn insert(&self, tenant_id: TenantId, settings: TenantSettings) {
+        self.settings.insert(tenant_id, settings);
+    }
+
+    /// Drops the cached settings of a tenant
+    pub fn remove(&self, tenant_id: TenantId) {
+        self.settings.invalidate(&tenant_id);
+    }
+}
+
+impl CacheInvalidationHandler for TenantSettingsCache {
+    fn invalidate(&self, invalidation: &CacheInvalidation) {
+        match *invalidation {
+            CacheInvalidation::Tenant { tenant_id }
+            | CacheInvalidation::TenantSettings { tenant_id } => self.remove(tenant_id),
+            CacheInvalidation::User { .. }
+            | CacheInvalidation::Permissions
+            | CacheInvalidation::Session { .. } => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Branding {
+        logo_url: String,
+    }
+
+    #[test]
+    fn test_typed_settings() {
+        let settings = TenantSettings::new(BTreeMap::from([
+            ("session.lifetime_secs".to_string(), json!(3600)),
+            (
+                "branding".to_string(),
+                json!({ "logo_url": "https://acme.example.com/logo.png" }),
+            ),
+        ]));
+
+        assert_eq!(
+            settings.get::<u64>("session.lifetime_secs").unwrap(),
+            Some(3600)
+        );
+        assert_eq!(
+            settings.get::<Branding>("branding").unwrap(),
+            Some(Branding {
+                logo_url: "https://acme.example.com/logo.png".to_string()
+            })
+        );
+        assert_eq!(settings.get::<u64>("unknown").unwrap(), None);
+        assert!(matches!(
+            settings.get::<String>("session.lifetime_secs"),
+            Err(Error::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_feature_flags() {
+        let settings = TenantSettings::new(BTreeMap::from([(
+            FEATURE_FLAGS_SETTING.to_string(),
+            json!({ "beta_dashboard": true, "exports": false }),
+        )]));
+        assert!(settings.is_feature_enabled("beta_dashboard"));
+        assert!(!settings.is_feature_enabled("exports"));
+        assert!(!settings.is_feature_enabled("unknown"));
+        assert!(!TenantSettings::default().is_feature_enabled("beta_dashboard"));
+    }
+
+    #[test]
+    fn test_validate_setting_key() {
+        assert!(validate_setting_key("branding.logo_url").is_ok());
+        assert!(validate_setting_key("password-policy.min_length").is_ok());
+        assert!(validate_setting_key("").is_err());
+        assert!(validate_setting_key("Branding").is_err());
+        assert!(validate_setting_key("branding/logo").is_err());
+        assert!(validate_setting_key(&"a".repeat(MAX_KEY_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_settings_cache_invalidation() {
+        let cache = TenantSettingsCache::new(Duration::from_secs(60));
+        let tenant_id = TenantId::new();
+        cache.insert(tenant_id, TenantSettings::default());
+        cache.invalidate(&CacheInvalidation::Permissions);
+        assert!(cache.get(tenant_id).is_some());
+        cache.invalidate(&CacheInvalidation::TenantSettings { tenant_id });
+        assert!(cache.get(tenant_id).is_none());
+    }
+}
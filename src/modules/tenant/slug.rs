@@ -0,0 +1,156 @@
+use crate::shared::error::{Error, Result};
+
+/// Minimum length of a tenant slug
+pub const MIN_SLUG_LENGTH: usize = 3;
+
+/// Maximum length of a tenant slug, a DNS label so slugs can become subdomains
+pub const MAX_SLUG_LENGTH: usize = 63;
+
+/// Slugs that would shadow routes or look official
+const RESERVED_SLUGS: &[&str] = &[
+    "admin",
+    "api",
+    "app",
+    "assets",
+    "auth",
+    "billing",
+    "health",
+    "help",
+    "login",
+    "logout",
+    "mail",
+    "permissions",
+    "root",
+    "signup",
+    "sso",
+    "static",
+    "status",
+    "support",
+    "system",
+    "tenants",
+    "www",
+];
+
+/// Checks whether a slug is reserved
+pub fn is_reserved(slug: &str) -> bool {
+    RESERVED_SLUGS.contains(&slug)
+}
+
+/// Ensures a slug is URL-safe and not reserved
+///
+/// Slugs consist of lowercase ASCII letters, digits and single hyphens between them.
+pub fn validate_slug(slug: &str) -> Result<()> {
+    if slug.len() < MIN_SLUG_LENGTH || slug.len() > MAX_SLUG_LENGTH {
+        return Err(Error::Validation(format!(
+            "Slug must be between {} and {} characters long",
+            MIN_SLUG_LENGTH, MAX_SLUG_LENGTH
+        )));
+    }
+    if !slug
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        || slug.starts_with('-')
+        || slug.ends_with('-')
+        || slug.contains("--")
+    {
+        return Err(Error::Validation(
+            "Slug may only contain lowercase letters, digits and single hyphens between them"
+                .to_string(),
+        ));
+    }
+    if is_reserved(slug) {
+        return Err(Error::Validation(format!("Slug {} is reserved", slug)));
+    }
+    Ok(())
+}
+
+/// Derives a slug from a tenant name in any language
+///
+/// Latin letters with diacritics are transliterated, other characters separate
+/// words. Returns `None` if too little of the name is representable.
+pub fn slugify(name: &str) -> Option<String> {
+    let mut slug = String::new();
+    for c in name.chars().flat_map(char::to_lowercase) {
+        match transliterate(c) {
+            Some(ascii) => slug.push_str(ascii),
+            None if c.is_ascii_alphanumeric() => slug.push(c),
+            None => {
+                if !slug.is_empty() && !slug.ends_with('-') {
+                    slug.push('-');
+                }
+            },
+        }
+    }
+
+    let mut slug = slug.trim_end_matches('-').to_string();
+    if slug.len() > MAX_SLUG_LENGTH {
+        slug.truncate(MAX_SLUG_LENGTH);
+        slug = slug.trim_end_matches('-').to_string();
+    }
+    validate_slug(&slug).ok().map(|_| slug)
+}
+
+/// Transliterates common lowercase Latin letters with diacritics to ASCII
+fn transliterate(c: char) -> Option<&'static str> {
+    let ascii = match c {
+        'à' | 'á' | 'â' | 'ã' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'ä' | 'æ' => "ae",
+        'ç' | 'ć' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => "e",
+        'ğ' => "g",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'į' | 'ı' => "i",
+        'ł' => "l",
+        'ñ' | 'ń' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ō' | 'ő' => "o",
+        'ö' | 'ø' | 'œ' => "oe",
+        'ř' => "r",
+        'ś' | 'š' | 'ş' | 'ș' => "s",
+        'ß' => "ss",
+        'ť' | 'ț' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ū' | 'ů' | 'ű' | 'ų' => "u",
+        'ü' => "ue",
+        'ý' | 'ÿ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    };
+    Some(ascii)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_slug() {
+        assert!(validate_slug("acme").is_ok());
+        assert!(validate_slug("acme-corp-2").is_ok());
+        assert!(validate_slug("ac").is_err());
+        assert!(validate_slug("Acme").is_err());
+        assert!(validate_slug("-acme").is_err());
+        assert!(validate_slug("ac--me").is_err());
+        assert!(validate_slug("acme/login").is_err());
+        assert!(validate_slug(&"a".repeat(MAX_SLUG_LENGTH + 1)).is_err());
+        assert!(matches!(validate_slug("admin"), Err(Error::Validation(_))));
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Acme Corp.").as_deref(), Some("acme-corp"));
+        assert_eq!(
+            slugify("Müller & Söhne GmbH").as_deref(),
+            Some("mueller-soehne-gmbh")
+        );
+        assert_eq!(slugify("  Café Crème  ").as_deref(), Some("cafe-creme"));
+        assert_eq!(slugify("Łódź Straße").as_deref(), Some("lodz-strasse"));
+        // Scripts without transliteration only separate words
+        assert_eq!(slugify("東京 Labs").as_deref(), Some("labs"));
+        assert_eq!(slugify("東京"), None);
+        assert_eq!(slugify("Admin"), None);
+        assert_eq!(
+            slugify(&"x".repeat(100)).map(|s| s.len()),
+            Some(MAX_SLUG_LENGTH)
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    modules::{
+        identity::models::{Permission, PermissionRequest, Role, RoleType},
+        tenant::{
+            plans::{Capability, TenantPlan},
+            settings::FEATURE_FLAGS_SETTING,
+        },
+    },
+    shared::error::{Error, Result},
+};
+
+/// Configuration new tenants can be created with, e.g. for mass onboarding
+///
+/// Templates hold no users; tenants created from them start with the template's
+/// plan, roles, settings and feature flags.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantTemplate {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub name: String,
+    #[serde(default)]
+    pub plan: TenantPlan,
+    #[serde(default)]
+    pub roles: Vec<TemplateRole>,
+    #[serde(default)]
+    pub settings: BTreeMap<String, Value>,
+    /// Feature flags by name, stored in the tenant's `features` setting
+    #[serde(default)]
+    pub features: BTreeMap<String, bool>,
+    #[serde(default = "OffsetDateTime::now_utc")]
+    pub created_at: OffsetDateTime,
+    #[serde(default = "OffsetDateTime::now_utc")]
+    pub updated_at: OffsetDateTime,
+}
+
+/// Role created in every tenant of a template
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateRole {
+    pub name: String,
+    #[serde(default = "custom_role_type")]
+    pub role_type: RoleType,
+    #[serde(default)]
+    pub permissions: Vec<PermissionRequest>,
+    /// Names of the template's roles whose permissions this role inherits
+    #[serde(default)]
+    pub parents: Vec<String>,
+}
+
+/// Template roles are custom roles unless they say otherwise
+fn custom_role_type() -> RoleType {
+    RoleType::Custom
+}
+
+impl TenantTemplate {
+    /// Creates an empty template
+    pub fn new(name: String, plan: TenantPlan) -> Self {
+        let now = OffsetDateTime::now_utc();
+        Self {
+            id: Uuid::new_v4(),
+            name,
+            plan,
+            roles: Vec::new(),
+            settings: BTreeMap::new(),
+            features: BTreeMap::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Captures the configuration of a tenant from its roles and settings
+    pub fn capture(
+        name: String,
+        plan: TenantPlan,
+        roles: &[Role],
+        mut settings: BTreeMap<String, Value>,
+    ) -> Result<Self> {
+        let names: HashMap<Uuid, &str> = roles
+            .iter()
+            .map(|role| (role.id, role.name.as_str()))
+            .collect();
+        let features = match settings.remove(FEATURE_FLAGS_SETTING) {
+            Some(value) => parse_feature_flags(&value)?,
+            None => BTreeMap::new(),
+        };
+
+        let mut template = Self::new(name, plan);
+        template.roles = roles
+            .iter()
+            .map(|role| TemplateRole {
+                name: role.name.clone(),
+                role_type: role.role_type,
+                permissions: role
+                    .permissions
+                    .iter()
+                    .map(|p| PermissionRequest {
+                        name: p.name.clone(),
+                        action: p.action,
+                        resource: p.resource.clone(),
+                        scope: p.scope,
+                        effect: p.effect,
+                    })
+                    .collect(),
+                parents: role
+                    .parent_ids
+                    .iter()
+                    .filter_map(|id| names.get(id).map(|name| name.to_string()))
+                    .collect(),
+            })
+            .collect();
+        template.settings = settings;
+        template.features = features;
+        Ok(template)
+    }
+
+    /// Ensures the template can be instantiated and its plan includes its custom roles
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(Error::Validation(
+                "Template name must not be empty".to_string(),
+            ));
+        }
+        if self.settings.contains_key(FEATURE_FLAGS_SETTING) {
+            return Err(Error::Validation(format!(
+                "Set feature flags with the template's features instead of its {} setting",
+                FEATURE_FLAGS_SETTING
+            )));
+        }
+        if self
+            .roles
+            .iter()
+            .any(|role| role.role_type == RoleType::SuperAdmin)
+        {
+            return Err(Error::Validation(
+                "Templates cannot create super admin roles".to_string(),
+            ));
+        }
+        if self
+            .roles
+            .iter()
+            .any(|role| role.role_type == RoleType::Custom)
+            && !self.plan.allows(Capability::CustomRoles)
+        {
+            return Err(Error::UpgradeRequired(format!(
+                "{} requires the {} plan, the template is on the {} plan",
+                Capability::CustomRoles,
+                Capability::CustomRoles.minimum_plan(),
+                self.plan
+            )));
+        }
+        self.instantiate_roles().map(|_| ())
+    }
+
+    /// Creates the template's roles with fresh IDs, every role after its parents
+    pub fn instantiate_roles(&self) -> Result<Vec<Role>> {
+        let mut ids = HashMap::with_capacity(self.roles.len());
+        for role in &self.roles {
+            if ids.insert(role.name.as_str(), Uuid::new_v4()).is_some() {
+                return Err(Error::Validation(format!(
+                    "Template has more than one role named {}",
+                    role.name
+                )));
+            }
+        }
+
+        let mut created: HashSet<&str> = HashSet::with_capacity(self.roles.len());
+        let mut roles = Vec::with_capacity(self.roles.len());
+        while roles.len() < self.roles.len() {
+            let ready: Vec<&TemplateRole> = self
+                .roles
+                .iter()
+                .filter(|role| !created.contains(role.name.as_str()))
+                .filter(|role| {
+                    role.parents
+                        .iter()
+                        .all(|parent| created.contains(parent.as_str()))
+                })
+                .collect();
+            if ready.is_empty() {
+                return Err(Error::Validation(
+                    "Template roles have unknown or circular parents".to_string(),
+                ));
+            }
+            for role in ready {
+                created.insert(role.name.as_str());
+                roles.push(Role {
+                    id: ids[role.name.as_str()],
+                    role_type: role.role_type,
+                    name: role.name.clone(),
+                    permissions: role
+                        .permissions
+                        .iter()
+                        .map(|p| Permission {
+                            id: Uuid::new_v4(),
+                            name: p.name.clone(),
+                            action: p.action,
+                            resource: p.resource.clone(),
+                            scope: p.scope,
+                            effect: p.effect,
+                        })
+                        .collect(),
+                    parent_ids: role
+                        .parents
+                        .iter()
+                        .map(|parent| ids[parent.as_str()])
+                        .collect(),
+                    inherited_permissions: Vec::new(),
+                });
+            }
+        }
+        Ok(roles)
+    }
+
+    /// Gets the settings of a new tenant, including the feature flags
+    pub fn tenant_settings(&self) -> BTreeMap<String, Value> {
+        let mut settings = self.settings.clone();
+        if !self.features.is_empty() {
+            settings.insert(
+                FEATURE_FLAGS_SETTING.to_string(),
+                serde_json::to_value(&self.features).unwrap_or_default(),
+            );
+        }
+        settings
+    }
+}
+
+/// Parses the value of a `features` setting, an object of feature names and flags
+pub fn parse_feature_flags(value: &Value) -> Result<BTreeMap<String, bool>> {
+    serde_json::from_value(value.clone()).map_err(|e| {
+        Error::Validation(format!(
+            "Invalid {} setting, expected flags by feature name: {}",
+            FEATURE_FLAGS_SETTING, e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::identity::models::PermissionAction;
+    use serde_json::json;
+
+    fn role(name: &str, parents: &[&str]) -> TemplateRole {
+        TemplateRole {
+            name: name.to_string(),
+            role_type: RoleType::Custom,
+            permissions: vec![PermissionRequest {
+                name: format!("Read {}", name),
+                action: PermissionAction::Read,
+                resource: name.to_string(),
+                scope: Default::default(),
+                effect: Default::default(),
+            }],
+            parents: parents.iter().map(|parent| parent.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_instantiate_roles() {
+        let mut template = TenantTemplate::new("Agency".to_string(), TenantPlan::Pro);
+        template.roles = vec![
+            role("Editor", &["Viewer"]),
+            role("Viewer", &[]),
+            role("Owner", &["Editor", "Viewer"]),
+        ];
+        assert!(template.validate().is_ok());
+
+        let roles = template.instantiate_roles().unwrap();
+        let names: Vec<&str> = roles.iter().map(|role| role.name.as_str()).collect();
+        assert_eq!(names, ["Viewer", "Editor", "Owner"]);
+        assert_eq!(roles[1].parent_ids, [roles[0].id]);
+        assert_eq!(roles[2].parent_ids, [roles[1].id, roles[0].id]);
+
+        // Every tenant gets its own role and permission IDs
+        let again = template.instantiate_roles().unwrap();
+        assert_ne!(again[0].id, roles[0].id);
+        assert_ne!(again[0].permissions[0].id, roles[0].permissions[0].id);
+
+        // Capturing the roles again yields the same template roles
+        let captured =
+            TenantTemplate::capture("Copy".to_string(), TenantPlan::Pro, &roles, BTreeMap::new())
+                .unwrap();
+        assert_eq!(captured.roles[2].parents, ["Editor", "Viewer"]);
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        let mut template = TenantTemplate::new("Agency".to_string(), TenantPlan::Pro);
+        template.roles = vec![role("Editor", &["Owner"]), role("Owner", &["Editor"])];
+        assert!(matches!(template.validate(), Err(Error::Validation(_))));
+
+        template.roles = vec![role("Editor", &["Viewer"])];
+        assert!(matches!(template.validate(), Err(Error::Validation(_))));
+
+        template.roles = vec![role("Editor", &[]), role("Editor", &[])];
+        assert!(matches!(template.validate(), Err(Error::Validation(_))));
+
+        // Custom roles need a plan including them
+        template.roles = vec![role("Editor", &[])];
+        template.plan = TenantPlan::Free;
+        assert!(matches!(
+            template.validate(),
+            Err(Error::UpgradeRequired(_))
+        ));
+    }
+
+    #[test]
+    fn test_feature_flags() {
+        let mut template = TenantTemplate::new("Agency".to_string(), TenantPlan::Free);
+        template.settings.insert("locale".to_string(), json!("de"));
+        template.features.insert("beta_dashboard".to_string(), true);
+
+        let settings = template.tenant_settings();
+        assert_eq!(settings["features"], json!({ "beta_dashboard": true }));
+        assert_eq!(settings["locale"], json!("de"));
+
+        let captured =
+            TenantTemplate::capture("Copy".to_string(), TenantPlan::Free, &[], settings).unwrap();
+        assert_eq!(captured.features, template.features);
+        assert!(!captured.settings.contains_key("features"));
+
+        assert!(parse_feature_flags(&json!({ "beta_dashboard": "yes" })).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::shared::types::{TenantId, UserId};
+
+/// Number of events buffered per subscriber before slow subscribers miss events
+const STREAM_CAPACITY: usize = 1024;
+
+/// Kind of an audit event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditCategory {
+    /// A change of stored data, e.g. an updated user or role
+    Audit,
+    /// A security relevant occurrence, e.g. a failed login
+    Security,
+}
+
+/// Event recorded in the audit log and streamed to subscribers of its tenant
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub id: Uuid,
+    pub tenant_id: TenantId,
+    pub category: AuditCategory,
+    pub action: String,
+    /// User who performed the action, if known
+    pub user_id: Option<UserId>,
+    pub table_name: String,
+    pub record_id: String,
+    pub details: Option<serde_json::Value>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+impl AuditEvent {
+    /// Creates an event about a record of a tenant
+    pub fn new(
+        tenant_id: TenantId,
+        category: AuditCategory,
+        action: &str,
+        table_name: &str,
+        record_id: impl ToString,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            tenant_id,
+            category,
+            action: action.to_string(),
+            user_id: None,
+            table_name: table_name.to_string(),
+            record_id: record_id.to_string(),
+            details: None,
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// Sets the user who performed the action
+    pub fn with_user(mut self, user_id: UserId) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    /// Sets additional information about the event
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Adds a piece of information about the event to its details
+    ///
+    /// Values that cannot be serialized are left out rather than failing the event.
+    pub fn with_detail(mut self, key: &str, value: impl Serialize) -> Self {
+        let Ok(value) = serde_json::to_value(value) else {
+            return self;
+        };
+        let details = self
+            .details
+            .get_or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+        if let Some(details) = details.as_object_mut() {
+            details.insert(key.to_string(), value);
+        }
+        self
+    }
+}
+
+/// Stream distributing audit events of this process to live subscribers, e.g. security dashboards
+///
+/// Clones share their subscribers; publishing without subscribers drops the event.
+#[derive(Debug, Clone)]
+pub struct AuditStream {
+    sender: broadcast::Sender<AuditEvent>,
+}
+
+impl Default for AuditStream {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(STREAM_CAPACITY);
+        Self { sender }
+    }
+}
+
+impl AuditStream {
+    /// Creates a stream without subscribers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends an event to all subscribers of its tenant
+    pub fn publish(&self, event: AuditEvent) {
+        // Fails only if nobody is subscribed
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribes to the events of a tenant published from now on
+    pub fn subscribe(&self, tenant_id: TenantId) -> AuditSubscription {
+        AuditSubscription {
+            tenant_id,
+            receiver: self.sender.subscribe(),
+        }
+    }
+}
+
+/// Receiver of the audit events of one tenant
+#[derive(Debug)]
+pub struct AuditSubscription {
+    tenant_id: TenantId,
+    receiver: broadcast::Receiver<AuditEvent>,
+}
+
+impl AuditSubscription {
+    /// Waits for the next event of the tenant; returns `None` once the stream is closed
+    ///
+    /// Events missed because the subscriber fell behind are skipped with a warning.
+    pub async fn next(&mut self) -> Option<AuditEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if event.tenant_id == self.tenant_id => return Some(event),
+                Ok(_) => {},
+                Err(RecvError::Lagged(missed)) => {
+                    warn!(
+                        "Audit subscriber of tenant {} missed {} events",
+                        self.tenant_id.0, missed
+                    );
+                },
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_audit_stream_tenant_isolation() {
+        let stream = AuditStream::new();
+        let tenant_id = TenantId::new();
+        let mut subscription = stream.subscribe(tenant_id);
+
+        let other = AuditEvent::new(
+            TenantId::new(),
+            AuditCategory::Audit,
+            "user_updated",
+            "users",
+            1,
+        );
+        let event = AuditEvent::new(
+            tenant_id,
+            AuditCategory::Security,
+            "login_failed",
+            "users",
+            2,
+        )
+        .with_detail("reason", "invalid_credentials")
+        .with_detail("retry_after", 30);
+        assert_eq!(
+            event.details,
+            Some(serde_json::json!({ "reason": "invalid_credentials", "retry_after": 30 }))
+        );
+        stream.publish(other);
+        stream.publish(event.clone());
+
+        assert_eq!(subscription.next().await, Some(event));
+
+        drop(stream);
+        assert_eq!(subscription.next().await, None);
+    }
+}
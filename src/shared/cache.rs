@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::shared::{
+    error::{Error, Result},
+    types::{TenantId, UserId},
+};
+
+/// Redis channel invalidation messages are broadcast on
+pub const INVALIDATION_CHANNEL: &str = "acci:cache-invalidation";
+
+/// Delay before the listener reconnects after losing its Redis connection
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Cached data that became stale
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CacheInvalidation {
+    /// A user, its roles or its permissions changed
+    User { user_id: UserId },
+    /// A tenant changed or was deleted
+    Tenant { tenant_id: TenantId },
+    /// Settings of a tenant changed
+    TenantSettings { tenant_id: TenantId },
+    /// Role definitions changed, affecting the permissions of any user
+    Permissions,
+    /// A session was revoked
+    Session { session_id: Uuid },
+}
+
+/// Cache dropping entries when notified about stale data
+pub trait CacheInvalidationHandler: Send + Sync + std::fmt::Debug {
+    /// Drops the entries affected by an invalidation
+    fn invalidate(&self, invalidation: &CacheInvalidation);
+}
+
+/// Message published to other instances
+#[derive(Debug, Serialize, Deserialize)]
+struct InvalidationMessage {
+    origin: Uuid,
+    invalidation: CacheInvalidation,
+}
+
+/// Bus distributing cache invalidations to local caches and, with Redis, to all other instances
+///
+/// Without Redis only the caches of this process are invalidated, so other
+/// instances serve stale entries until their TTL expires.
+#[derive(Debug, Clone)]
+pub struct CacheInvalidationBus {
+    instance_id: Uuid,
+    handlers: Arc<RwLock<Vec<Arc<dyn CacheInvalidationHandler>>>>,
+    client: Option<redis::Client>,
+}
+
+impl Default for CacheInvalidationBus {
+    fn default() -> Self {
+        Self {
+            instance_id: Uuid::new_v4(),
+            handlers: Arc::new(RwLock::new(Vec::new())),
+            client: None,
+        }
+    }
+}
+
+impl CacheInvalidationBus {
+    /// Creates a bus invalidating only the caches of this process
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a bus broadcasting invalidations via Redis pub/sub
+    pub fn with_redis(redis_url: &str) -> Result<Self> {
+        Ok(Self {
+            client: Some(redis::Client::open(redis_url)?),
+            ..Self::default()
+        })
+    }
+
+    /// Registers a cache to invalidate
+    pub fn register(&self, handler: Arc<dyn CacheInvalidationHandler>) {
+        if let Ok(mut handlers) = self.handlers.write() {
+            handlers.push(handler);
+        }
+    }
+
+    /// Invalidates local caches and broadcasts the invalidation to other instances
+    ///
+    /// Broadcast failures are only logged since the change itself was already stored.
+    pub async fn publish(&self, invalidation: CacheInvalidation) {
+        self.dispatch(&invalidation);
+
+        let Some(client) = &self.client else {
+            return;
+        };
+        if let Err(e) = self.broadcast(client, invalidation).await {
+            warn!("Failed to broadcast cache invalidation: {}", e);
+        }
+    }
+
+    /// Publishes an invalidation on the Redis channel
+    async fn broadcast(
+        &self,
+        client: &redis::Client,
+        invalidation: CacheInvalidation,
+    ) -> Result<()> {
+        let message = serde_json::to_string(&InvalidationMessage {
+            origin: self.instance_id,
+            invalidation,
+        })
+        .map_err(|e| Error::Internal(format!("Failed to serialize invalidation: {}", e)))?;
+
+        let mut conn = client.get_async_connection().await?;
+        redis::cmd("PUBLISH")
+            .arg(INVALIDATION_CHANNEL)
+            .arg(message)
+            .query_async::<_, i64>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Invalidates the local caches
+    fn dispatch(&self, invalidation: &CacheInvalidation) {
+        if let Ok(handlers) = self.handlers.read() {
+            for handler in handlers.iter() {
+                handler.invalidate(invalidation);
+            }
+        }
+    }
+
+    /// Applies a message received from the channel, ignoring messages of this instance
+    fn receive(&self, payload: &str) {
+        match serde_json::from_str::<InvalidationMessage>(payload) {
+            Ok(message) if message.origin != self.instance_id => {
+                debug!("Applying cache invalidation {:?}", message.invalidation);
+                self.dispatch(&message.invalidation);
+            },
+            Ok(_) => {},
+            Err(e) => warn!("Ignoring malformed cache invalidation: {}", e),
+        }
+    }
+
+    /// Starts a background thread applying invalidations broadcast by other instances
+    ///
+    /// The listener reconnects when it loses its connection; does nothing without Redis.
+    pub fn spawn_listener(&self) -> Result<()> {
+        let Some(client) = self.client.clone() else {
+            return Ok(());
+        };
+        let bus = self.clone();
+        std::thread::Builder::new()
+            .name("cache-invalidation".to_string())
+            .spawn(move || loop {
+                if let Err(e) = bus.listen(&client) {
+                    warn!("Cache invalidation listener disconnected: {}", e);
+                }
+                // Entries cached while disconnected may have missed invalidations
+                bus.dispatch(&CacheInvalidation::Permissions);
+                std::thread::sleep(RECONNECT_DELAY);
+            })
+            .map_err(|e| Error::Internal(format!("Failed to start listener: {}", e)))?;
+        Ok(())
+    }
+
+    /// Subscribes to the channel and applies messages until the connection fails
+    fn listen(&self, client: &redis::Client) -> Result<()> {
+        let mut conn = client.get_connection()?;
+        let mut pubsub = conn.as_pubsub();
+        pubsub.subscribe(INVALIDATION_CHANNEL)?;
+        loop {
+            let payload: String = pubsub.get_message()?.get_payload()?;
+            self.receive(&payload);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Default)]
+    struct RecordingCache {
+        invalidations: Mutex<Vec<CacheInvalidation>>,
+    }
+
+    impl CacheInvalidationHandler for RecordingCache {
+        fn invalidate(&self, invalidation: &CacheInvalidation) {
+            self.invalidations.lock().unwrap().push(*invalidation);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalidation_dispatch() {
+        let bus = CacheInvalidationBus::new();
+        let cache = Arc::new(RecordingCache::default());
+        bus.register(cache.clone());
+
+        let user_id = UserId::new();
+        bus.publish(CacheInvalidation::User { user_id }).await;
+
+        // Messages of other instances are applied, echoes of our own are not
+        let other = serde_json::to_string(&InvalidationMessage {
+            origin: Uuid::new_v4(),
+            invalidation: CacheInvalidation::Permissions,
+        })
+        .unwrap();
+        let own = serde_json::to_string(&InvalidationMessage {
+            origin: bus.instance_id,
+            invalidation: CacheInvalidation::Permissions,
+        })
+        .unwrap();
+        bus.receive(&other);
+        bus.receive(&own);
+        bus.receive("not json");
+
+        assert_eq!(
+            *cache.invalidations.lock().unwrap(),
+            vec![
+                CacheInvalidation::User { user_id },
+                CacheInvalidation::Permissions
+            ]
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use serde::Serialize;
+use std::{
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// State of a circuit breaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Calls reach the backing service
+    Closed,
+    /// The backing service failed repeatedly, calls are short-circuited
+    Open,
+    /// The reset timeout elapsed, a trial call decides whether to close again
+    HalfOpen,
+}
+
+/// Snapshot of the counters of a circuit breaker
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CircuitBreakerMetrics {
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub total_failures: u64,
+    pub short_circuited_calls: u64,
+    pub times_opened: u64,
+}
+
+/// Circuit breaker guarding calls to an unreliable backing service
+///
+/// Opens after `failure_threshold` consecutive failures and lets a trial call
+/// through once `reset_timeout` elapsed; clones share their state.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    opened_at: Arc<Mutex<Option<Instant>>>,
+    consecutive_failures: Arc<AtomicU32>,
+    total_failures: Arc<AtomicU64>,
+    short_circuited_calls: Arc<AtomicU64>,
+    times_opened: Arc<AtomicU64>,
+}
+
+impl CircuitBreaker {
+    /// Creates a closed circuit breaker
+    pub fn new(failure_threshold: u32, reset_timeout: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            reset_timeout,
+            opened_at: Arc::new(Mutex::new(None)),
+            consecutive_failures: Arc::new(AtomicU32::new(0)),
+            total_failures: Arc::new(AtomicU64::new(0)),
+            short_circuited_calls: Arc::new(AtomicU64::new(0)),
+            times_opened: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Gets the current state
+    pub fn state(&self) -> CircuitState {
+        match *self.opened_at.lock().unwrap_or_else(|e| e.into_inner()) {
+            None => CircuitState::Closed,
+            Some(opened_at) if opened_at.elapsed() >= self.reset_timeout => CircuitState::HalfOpen,
+            Some(_) => CircuitState::Open,
+        }
+    }
+
+    /// Checks if a call may reach the backing service, counting short-circuited calls
+    pub fn allow(&self) -> bool {
+        if self.state() == CircuitState::Open {
+            self.short_circuited_calls.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Records a successful call, closing the circuit
+    pub fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+        *self.opened_at.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Records a failed call, opening the circuit once the threshold is reached
+    pub fn record_failure(&self) {
+        self.total_failures.fetch_add(1, Ordering::Relaxed);
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut opened_at = self.opened_at.lock().unwrap_or_else(|e| e.into_inner());
+        // A failed trial call restarts the reset timeout
+        if failures >= self.failure_threshold || opened_at.is_some() {
+            if opened_at.is_none() {
+                self.times_opened.fetch_add(1, Ordering::Relaxed);
+            }
+            *opened_at = Some(Instant::now());
+        }
+    }
+
+    /// Gets a snapshot of the counters
+    pub fn metrics(&self) -> CircuitBreakerMetrics {
+        CircuitBreakerMetrics {
+            state: self.state(),
+            consecutive_failures: self.consecutive_failures.load(Ordering::Relaxed),
+            total_failures: self.total_failures.load(Ordering::Relaxed),
+            short_circuited_calls: self.short_circuited_calls.load(Ordering::Relaxed),
+            times_opened: self.times_opened.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new(2, Duration::from_millis(100));
+        assert!(breaker.allow());
+
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.allow());
+
+        // A failed trial call opens the circuit again
+        std::thread::sleep(Duration::from_millis(150));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.allow());
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(150));
+        breaker.record_success();
+        assert_eq!(
+            breaker.metrics(),
+            CircuitBreakerMetrics {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                total_failures: 3,
+                short_circuited_calls: 1,
+                times_opened: 1,
+            }
+        );
+    }
+}
@@ -1,12 +1,25 @@
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
+    Json,
 };
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// Result type for the application
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Throttling details of a rejected login, so clients can render a countdown
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThrottleInfo {
+    #[serde(rename = "error")]
+    pub message: String,
+    /// Seconds until the client may try again
+    pub retry_after: u64,
+    /// Failed attempts left before the account is locked out
+    pub attempts_remaining: u32,
+}
+
 /// Error type for the application
 #[derive(Debug, Error)]
 pub enum Error {
@@ -37,18 +50,100 @@ pub enum Error {
     /// Validation error
     #[error("Validation error: {0}")]
     Validation(String),
+
+    /// Conflict error, e.g. a unique attribute is already taken
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    /// Rate limit error, the caller sent too many requests
+    #[error("Too many requests: {0}")]
+    RateLimited(String),
+
+    /// Unavailable error, a backing service is down and no fallback is configured
+    #[error("Service unavailable: {0}")]
+    Unavailable(String),
+
+    /// Tenant suspended error, the caller's tenant is suspended and cannot be accessed
+    #[error("Tenant suspended: {0}")]
+    TenantSuspended(String),
+
+    /// Upgrade required error, the caller's tenant plan does not include the feature
+    #[error("Upgrade required: {0}")]
+    UpgradeRequired(String),
+
+    /// Throttled error, the caller made too many attempts and has to wait
+    #[error("Too many attempts: {}", .0.message)]
+    Throttled(ThrottleInfo),
+
+    /// Locked out error, too many failed attempts locked the account temporarily
+    #[error("Locked out: {}", .0.message)]
+    LockedOut(ThrottleInfo),
+}
+
+impl Error {
+    /// Gets the HTTP status code for this error
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Error::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::Authentication(_) => StatusCode::UNAUTHORIZED,
+            Error::Authorization(_) => StatusCode::FORBIDDEN,
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            Error::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::Validation(_) => StatusCode::BAD_REQUEST,
+            Error::Conflict(_) => StatusCode::CONFLICT,
+            Error::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            Error::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Error::TenantSuspended(_) => StatusCode::FORBIDDEN,
+            Error::UpgradeRequired(_) => StatusCode::PAYMENT_REQUIRED,
+            Error::Throttled(_) => StatusCode::TOO_MANY_REQUESTS,
+            Error::LockedOut(_) => StatusCode::LOCKED,
+        }
+    }
+
+    /// Gets the message of this error
+    pub fn message(&self) -> &str {
+        match self {
+            Error::Database(msg)
+            | Error::Authentication(msg)
+            | Error::Authorization(msg)
+            | Error::NotFound(msg)
+            | Error::InvalidInput(msg)
+            | Error::Internal(msg)
+            | Error::Validation(msg)
+            | Error::Conflict(msg)
+            | Error::RateLimited(msg)
+            | Error::Unavailable(msg)
+            | Error::TenantSuspended(msg)
+            | Error::UpgradeRequired(msg) => msg,
+            Error::Throttled(info) | Error::LockedOut(info) => &info.message,
+        }
+    }
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
-        let (status, message) = match self {
-            Error::Database(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-            Error::Authentication(msg) => (StatusCode::UNAUTHORIZED, msg),
-            Error::Authorization(msg) => (StatusCode::FORBIDDEN, msg),
-            Error::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
-            Error::InvalidInput(msg) => (StatusCode::BAD_REQUEST, msg),
-            Error::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-            Error::Validation(msg) => (StatusCode::BAD_REQUEST, msg),
+        let status = self.status_code();
+        let message = match self {
+            // Throttling details are sent as JSON with a Retry-After header
+            Error::Throttled(info) | Error::LockedOut(info) => {
+                let retry_after = HeaderValue::from(info.retry_after);
+                let mut response = (status, Json(info)).into_response();
+                response.headers_mut().insert(RETRY_AFTER, retry_after);
+                return response;
+            },
+            Error::Database(msg)
+            | Error::Authentication(msg)
+            | Error::Authorization(msg)
+            | Error::NotFound(msg)
+            | Error::InvalidInput(msg)
+            | Error::Internal(msg)
+            | Error::Validation(msg)
+            | Error::Conflict(msg)
+            | Error::RateLimited(msg)
+            | Error::Unavailable(msg)
+            | Error::TenantSuspended(msg)
+            | Error::UpgradeRequired(msg) => msg,
         };
 
         (status, message).into_response()
@@ -122,6 +217,32 @@ mod tests {
 
         let error = Error::Validation("test error".to_string());
         assert_eq!(error.to_string(), "Validation error: test error");
+
+        let error = Error::Conflict("test error".to_string());
+        assert_eq!(error.to_string(), "Conflict: test error");
+
+        let error = Error::RateLimited("test error".to_string());
+        assert_eq!(error.to_string(), "Too many requests: test error");
+
+        let error = Error::Unavailable("test error".to_string());
+        assert_eq!(error.to_string(), "Service unavailable: test error");
+
+        let error = Error::TenantSuspended("test error".to_string());
+        assert_eq!(error.to_string(), "Tenant suspended: test error");
+
+        let error = Error::UpgradeRequired("test error".to_string());
+        assert_eq!(error.to_string(), "Upgrade required: test error");
+
+        let info = ThrottleInfo {
+            message: "test error".to_string(),
+            retry_after: 30,
+            attempts_remaining: 0,
+        };
+        let error = Error::Throttled(info.clone());
+        assert_eq!(error.to_string(), "Too many attempts: test error");
+
+        let error = Error::LockedOut(info);
+        assert_eq!(error.to_string(), "Locked out: test error");
     }
 
     #[test]
@@ -153,5 +274,40 @@ mod tests {
         let error = Error::Validation("test error".to_string());
         let response = error.into_response();
         assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let error = Error::Conflict("test error".to_string());
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let error = Error::RateLimited("test error".to_string());
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let error = Error::Unavailable("test error".to_string());
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let error = Error::TenantSuspended("test error".to_string());
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let error = Error::UpgradeRequired("test error".to_string());
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+
+        let info = ThrottleInfo {
+            message: "test error".to_string(),
+            retry_after: 30,
+            attempts_remaining: 2,
+        };
+        let response = Error::Throttled(info.clone()).into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "30");
+
+        let error = Error::LockedOut(info);
+        assert_eq!(error.message(), "test error");
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::LOCKED);
+        assert_eq!(response.headers()[RETRY_AFTER], "30");
     }
 }
\ No newline at end of file
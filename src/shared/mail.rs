@@ -0,0 +1,64 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::shared::{
+    error::Result,
+    sli::{Dependency, SliTracker},
+};
+
+/// Plain text email sent to a single recipient
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Extension point for delivering transactional emails
+#[async_trait]
+pub trait Mailer: Send + Sync + std::fmt::Debug {
+    /// Sends an email; errors are returned to the caller
+    async fn send(&self, message: &EmailMessage) -> Result<()>;
+}
+
+/// Mailer logging the emails it drops, for development
+///
+/// Only recipient and subject are logged; bodies carry verification and reset
+/// tokens that must not end up in log storage.
+#[derive(Debug, Clone, Default)]
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<()> {
+        tracing::info!(
+            to = %message.to,
+            subject = %message.subject,
+            "Email not delivered, no mail transport configured"
+        );
+        Ok(())
+    }
+}
+
+/// Mailer recording the latency and outcome of the deliveries of another mailer
+#[derive(Debug, Clone)]
+pub struct TrackedMailer {
+    inner: Arc<dyn Mailer>,
+    sli: SliTracker,
+}
+
+impl TrackedMailer {
+    /// Wraps a mailer, recording its deliveries in the SLIs of the mailer dependency
+    pub fn new(inner: Arc<dyn Mailer>, sli: SliTracker) -> Self {
+        Self { inner, sli }
+    }
+}
+
+#[async_trait]
+impl Mailer for TrackedMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<()> {
+        self.sli
+            .observe(Dependency::Mailer, self.inner.send(message))
+            .await
+    }
+}
@@ -1,3 +1,12 @@
+pub mod audit;
+pub mod cache;
+pub mod circuit_breaker;
 pub mod error;
+pub mod mail;
+pub mod quota;
+pub mod rate_limit;
+pub mod secrets;
+pub mod sli;
+pub mod token_funnel;
 pub mod traits;
-pub mod types;
+pub mod types;
\ No newline at end of file
@@ -0,0 +1,212 @@
+use axum::{
+    http::{header::RETRY_AFTER, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use time::{Duration, OffsetDateTime, Time};
+
+use crate::shared::error::Result;
+
+/// Prefix of the Redis keys holding quota counters
+const QUOTA_KEY_PREFIX: &str = "acci:quota";
+
+/// Header with the number of requests allowed in the current window
+pub static LIMIT_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+
+/// Header with the number of requests left in the current window
+pub static REMAINING_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+
+/// Header with the Unix timestamp at which the current window ends
+pub static RESET_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+/// Request quota of an API key; limits apply per UTC day, `None` means unlimited
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKeyQuota {
+    /// Requests per day across all endpoints
+    pub requests_per_day: Option<u32>,
+    /// Requests per day to single endpoints, keyed by method and route,
+    /// e.g. `GET /tenants/:tenant_id/users`
+    #[serde(default)]
+    pub endpoint_budgets: HashMap<String, u32>,
+}
+
+impl ApiKeyQuota {
+    /// Checks if the key may send unlimited requests to the endpoint
+    pub fn is_unlimited(&self, endpoint: &str) -> bool {
+        self.requests_per_day.is_none() && !self.endpoint_budgets.contains_key(endpoint)
+    }
+}
+
+/// Usage of the most exhausted limit applying to a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaUsage {
+    pub limit: u32,
+    pub remaining: u32,
+    pub exceeded: bool,
+    pub reset_at: OffsetDateTime,
+}
+
+impl QuotaUsage {
+    /// Computes the usage of a limit after `count` requests in the current window
+    fn new(limit: u32, count: u64, reset_at: OffsetDateTime) -> Self {
+        Self {
+            limit,
+            remaining: u64::from(limit).saturating_sub(count) as u32,
+            exceeded: count > u64::from(limit),
+            reset_at,
+        }
+    }
+
+    /// Gets the usage of whichever limit is closer to being exhausted
+    fn tightest(self, other: Self) -> Self {
+        if other.exceeded != self.exceeded {
+            return if other.exceeded { other } else { self };
+        }
+        if other.remaining < self.remaining {
+            other
+        } else {
+            self
+        }
+    }
+
+    /// Adds the quota headers to a response
+    pub fn apply_headers(&self, headers: &mut HeaderMap) {
+        headers.insert(LIMIT_HEADER.clone(), HeaderValue::from(self.limit));
+        headers.insert(REMAINING_HEADER.clone(), HeaderValue::from(self.remaining));
+        headers.insert(
+            RESET_HEADER.clone(),
+            HeaderValue::from(self.reset_at.unix_timestamp()),
+        );
+    }
+}
+
+impl IntoResponse for QuotaUsage {
+    /// Rejects a request exceeding its quota with 429 and the quota headers
+    fn into_response(self) -> Response {
+        let retry_after = (self.reset_at - OffsetDateTime::now_utc())
+            .whole_seconds()
+            .max(0);
+        let mut response = (
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many requests: API key quota exceeded",
+        )
+            .into_response();
+        self.apply_headers(response.headers_mut());
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        response
+    }
+}
+
+/// Tracker counting the requests of API keys in Redis, shared by all instances
+#[derive(Debug, Clone)]
+pub struct QuotaTracker {
+    client: redis::Client,
+}
+
+impl QuotaTracker {
+    /// Creates a tracker storing its counters in the given Redis
+    pub fn new(redis_url: &str) -> Result<Self> {
+        Ok(Self {
+            client: redis::Client::open(redis_url)?,
+        })
+    }
+
+    /// Counts a request of an API key to an endpoint
+    ///
+    /// Returns `None` if no limit applies; otherwise the caller rejects the
+    /// request if the returned usage is exceeded and sends its headers either way.
+    pub async fn consume(
+        &self,
+        key_id: &str,
+        endpoint: &str,
+        quota: &ApiKeyQuota,
+    ) -> Result<Option<QuotaUsage>> {
+        if quota.is_unlimited(endpoint) {
+            return Ok(None);
+        }
+
+        let now = OffsetDateTime::now_utc();
+        let (window, reset_at) = day_window(now);
+        let ttl = (reset_at - now).whole_seconds().max(1);
+        let daily_key = format!("{}:{}:{}", QUOTA_KEY_PREFIX, key_id, window);
+        let endpoint_key = format!("{}:{}:{}:{}", QUOTA_KEY_PREFIX, key_id, window, endpoint);
+
+        let mut conn = self.client.get_async_connection().await?;
+        let (daily, per_endpoint): (u64, u64) = redis::pipe()
+            .atomic()
+            .incr(&daily_key, 1)
+            .expire(&daily_key, ttl)
+            .ignore()
+            .incr(&endpoint_key, 1)
+            .expire(&endpoint_key, ttl)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+
+        let daily = quota
+            .requests_per_day
+            .map(|limit| QuotaUsage::new(limit, daily, reset_at));
+        let per_endpoint = quota
+            .endpoint_budgets
+            .get(endpoint)
+            .map(|&limit| QuotaUsage::new(limit, per_endpoint, reset_at));
+        Ok(match (daily, per_endpoint) {
+            (Some(daily), Some(per_endpoint)) => Some(daily.tightest(per_endpoint)),
+            (daily, per_endpoint) => daily.or(per_endpoint),
+        })
+    }
+}
+
+/// Gets the UTC day a point in time falls into and the start of the next day
+fn day_window(now: OffsetDateTime) -> (String, OffsetDateTime) {
+    let day = now.date();
+    let reset_at = (day + Duration::days(1))
+        .with_time(Time::MIDNIGHT)
+        .assume_utc();
+    (day.to_string(), reset_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_usage() {
+        // 2025-01-28 13:45 UTC
+        let (window, reset_at) =
+            day_window(OffsetDateTime::from_unix_timestamp(1_738_071_900).unwrap());
+        assert_eq!(window, "2025-01-28");
+        assert_eq!(reset_at.unix_timestamp(), 1_738_108_800);
+
+        let daily = QuotaUsage::new(1000, 10, reset_at);
+        assert_eq!(daily.remaining, 990);
+        assert!(!daily.exceeded);
+
+        // The endpoint budget is the tighter limit once nearly used up
+        let per_endpoint = QuotaUsage::new(5, 5, reset_at);
+        assert_eq!(daily.tightest(per_endpoint), per_endpoint);
+        let exceeded = QuotaUsage::new(5, 6, reset_at);
+        assert!(exceeded.exceeded);
+        assert_eq!(exceeded.remaining, 0);
+        assert_eq!(exceeded.tightest(daily), exceeded);
+
+        let response = exceeded.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[&LIMIT_HEADER], "5");
+        assert_eq!(response.headers()[&REMAINING_HEADER], "0");
+        assert_eq!(
+            response.headers()[&RESET_HEADER],
+            reset_at.unix_timestamp().to_string()
+        );
+
+        let quota = ApiKeyQuota {
+            requests_per_day: None,
+            endpoint_budgets: HashMap::from([("GET /tenants/:tenant_id/users".to_string(), 100)]),
+        };
+        assert!(!quota.is_unlimited("GET /tenants/:tenant_id/users"));
+        assert!(quota.is_unlimited("GET /tenants/:tenant_id/roles"));
+    }
+}
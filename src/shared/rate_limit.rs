@@ -0,0 +1,69 @@
+use moka::sync::Cache;
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::shared::error::{Error, Result};
+
+/// Fixed window rate limiter keyed by an arbitrary client key, e.g. an IP address
+///
+/// The window of a key starts with its first attempt; counters are kept in
+/// memory and thus per process.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    max_attempts: u32,
+    attempts: Cache<String, Arc<AtomicU32>>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter allowing `max_attempts` per key within `window`
+    pub fn new(max_attempts: u32, window: Duration) -> Self {
+        Self {
+            max_attempts,
+            attempts: Cache::builder()
+                .max_capacity(100_000)
+                .time_to_live(window)
+                .build(),
+        }
+    }
+
+    /// Records an attempt and fails once the key exceeded its attempts in the current window
+    pub fn check(&self, key: &str) -> Result<()> {
+        let counter = self
+            .attempts
+            .get_with(key.to_string(), || Arc::new(AtomicU32::new(0)));
+        if counter.fetch_add(1, Ordering::SeqCst) >= self.max_attempts {
+            return Err(Error::RateLimited(
+                "Too many attempts, please try again later".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(2, Duration::from_millis(200));
+        assert!(limiter.check("10.0.0.1").is_ok());
+        assert!(limiter.check("10.0.0.1").is_ok());
+        assert!(matches!(
+            limiter.check("10.0.0.1"),
+            Err(Error::RateLimited(_))
+        ));
+
+        // Keys are limited independently
+        assert!(limiter.check("10.0.0.2").is_ok());
+
+        // A new window starts once the previous one ended
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(limiter.check("10.0.0.1").is_ok());
+    }
+}
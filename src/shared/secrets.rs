@@ -0,0 +1,220 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use crate::{
+    core::config::SecretsConfig,
+    shared::error::{Error, Result},
+};
+
+/// Time a secret store has to answer
+const SECRET_STORE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Prefix of the environment variables `env:` references may read
+///
+/// Keeps references from exposing other settings of the process, like the database URL.
+const SECRET_ENV_PREFIX: &str = "ACCI_SECRET_";
+
+/// Extension point resolving references to secrets kept outside the database
+///
+/// References start with the scheme of their store, e.g. `env:ACCI_SECRET_OKTA`,
+/// `vault:secret/data/sso/okta#client_secret` or an AWS Secrets Manager ARN.
+#[async_trait]
+pub trait SecretResolver: Send + Sync + std::fmt::Debug {
+    /// Resolves a reference to the secret's value
+    async fn resolve(&self, reference: &str) -> Result<String>;
+}
+
+/// Resolver reading `env:<NAME>` references from environment variables
+///
+/// Only variables starting with `ACCI_SECRET_` can be read.
+#[derive(Debug, Clone, Default)]
+pub struct EnvSecretResolver;
+
+#[async_trait]
+impl SecretResolver for EnvSecretResolver {
+    async fn resolve(&self, reference: &str) -> Result<String> {
+        let name = reference
+            .strip_prefix("env:")
+            .ok_or_else(|| Error::InvalidInput(format!("Not an env reference: {}", reference)))?;
+        if !name.starts_with(SECRET_ENV_PREFIX) {
+            return Err(Error::InvalidInput(format!(
+                "Secret variables must start with {}",
+                SECRET_ENV_PREFIX
+            )));
+        }
+        std::env::var(name).map_err(|_| Error::NotFound(format!("Secret {} is not set", reference)))
+    }
+}
+
+/// Resolver reading `vault:<path>#<field>` references from a HashiCorp Vault KV v2 engine
+///
+/// The path includes the mount and `data` segment, e.g. `secret/data/sso/okta`.
+#[derive(Debug, Clone)]
+pub struct VaultSecretResolver {
+    address: String,
+    token: String,
+    client: reqwest::Client,
+}
+
+#[derive(Debug, Deserialize)]
+struct VaultResponse {
+    data: VaultData,
+}
+
+#[derive(Debug, Deserialize)]
+struct VaultData {
+    data: HashMap<String, serde_json::Value>,
+}
+
+impl VaultSecretResolver {
+    /// Creates a resolver for the Vault server at `address`
+    pub fn new(address: &str, token: String) -> Self {
+        Self {
+            address: address.trim_end_matches('/').to_string(),
+            token,
+            client: reqwest::Client::builder()
+                .timeout(SECRET_STORE_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl SecretResolver for VaultSecretResolver {
+    async fn resolve(&self, reference: &str) -> Result<String> {
+        let (path, field) = parse_vault_reference(reference)?;
+        let response = self
+            .client
+            .get(format!("{}/v1/{}", self.address, path))
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Error::Unavailable(format!("Failed to read secret from Vault: {}", e)))?;
+        let response: VaultResponse = response
+            .json()
+            .await
+            .map_err(|e| Error::Internal(format!("Invalid Vault response: {}", e)))?;
+        match response.data.data.get(field) {
+            Some(serde_json::Value::String(value)) => Ok(value.clone()),
+            _ => Err(Error::NotFound(format!("Secret {} not found", reference))),
+        }
+    }
+}
+
+/// Splits a Vault reference into its path and field
+fn parse_vault_reference(reference: &str) -> Result<(&str, &str)> {
+    reference
+        .strip_prefix("vault:")
+        .and_then(|reference| reference.split_once('#'))
+        .filter(|(path, field)| !path.is_empty() && !field.is_empty())
+        .map(|(path, field)| (path.trim_start_matches('/'), field))
+        .ok_or_else(|| {
+            Error::InvalidInput(format!(
+                "Vault references must look like vault:<path>#<field>, got {}",
+                reference
+            ))
+        })
+}
+
+/// Resolver handing references to the resolver registered for their scheme
+///
+/// AWS Secrets Manager ARNs have the scheme `arn`; deployments using it
+/// register a resolver for it.
+#[derive(Debug, Clone, Default)]
+pub struct SchemeSecretResolver {
+    resolvers: HashMap<String, Arc<dyn SecretResolver>>,
+}
+
+impl SchemeSecretResolver {
+    /// Creates a resolver without any schemes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a resolver for environment variables and, if configured, Vault
+    pub fn from_config(config: &SecretsConfig) -> Self {
+        let resolver = Self::new().with_scheme("env", Arc::new(EnvSecretResolver));
+        match (&config.vault_address, &config.vault_token) {
+            (Some(address), Some(token)) => resolver.with_scheme(
+                "vault",
+                Arc::new(VaultSecretResolver::new(address, token.clone())),
+            ),
+            _ => resolver,
+        }
+    }
+
+    /// Resolves references with the given scheme through `resolver`
+    pub fn with_scheme(mut self, scheme: &str, resolver: Arc<dyn SecretResolver>) -> Self {
+        self.resolvers.insert(scheme.to_string(), resolver);
+        self
+    }
+
+    /// Checks if references of the scheme of `reference` can be resolved
+    pub fn supports(&self, reference: &str) -> bool {
+        reference
+            .split_once(':')
+            .is_some_and(|(scheme, _)| self.resolvers.contains_key(scheme))
+    }
+}
+
+#[async_trait]
+impl SecretResolver for SchemeSecretResolver {
+    async fn resolve(&self, reference: &str) -> Result<String> {
+        let resolver = reference
+            .split_once(':')
+            .and_then(|(scheme, _)| self.resolvers.get(scheme))
+            .ok_or_else(|| {
+                Error::InvalidInput(format!("Unsupported secret reference {}", reference))
+            })?;
+        resolver.resolve(reference).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scheme_secret_resolver() {
+        std::env::set_var("ACCI_SECRET_TEST_SSO", "s3cret");
+        std::env::set_var("ACCI_TEST_SSO_SECRET", "s3cret");
+        let resolver = SchemeSecretResolver::from_config(&SecretsConfig::default());
+
+        assert!(resolver.supports("env:ACCI_SECRET_TEST_SSO"));
+        assert!(!resolver.supports("vault:secret/data/sso#client_secret"));
+        assert!(!resolver.supports("plain-secret"));
+        assert_eq!(
+            resolver.resolve("env:ACCI_SECRET_TEST_SSO").await.unwrap(),
+            "s3cret"
+        );
+        assert!(matches!(
+            resolver.resolve("env:ACCI_SECRET_TEST_MISSING").await,
+            Err(Error::NotFound(_))
+        ));
+        // Other variables of the process cannot be read
+        assert!(matches!(
+            resolver.resolve("env:ACCI_TEST_SSO_SECRET").await,
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            resolver
+                .resolve("arn:aws:secretsmanager:eu-west-1:123456789012:secret:sso")
+                .await,
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_vault_reference() {
+        assert_eq!(
+            parse_vault_reference("vault:secret/data/sso/okta#client_secret").unwrap(),
+            ("secret/data/sso/okta", "client_secret")
+        );
+        assert!(parse_vault_reference("vault:secret/data/sso/okta").is_err());
+        assert!(parse_vault_reference("vault:#client_secret").is_err());
+        assert!(parse_vault_reference("env:OKTA_SECRET").is_err());
+    }
+}
@@ -0,0 +1,366 @@
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Samples kept per dependency, bounding memory under heavy traffic
+const MAX_SAMPLES: usize = 10_000;
+
+/// Weight of a critical dependency in the composite health score
+const CRITICAL_WEIGHT: u32 = 3;
+
+/// External dependency whose calls are tracked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dependency {
+    Postgres,
+    Redis,
+    Mailer,
+    /// SAML and OIDC identity providers
+    IdentityProvider,
+}
+
+impl Dependency {
+    /// All tracked dependencies
+    pub const ALL: [Dependency; 4] = [
+        Dependency::Postgres,
+        Dependency::Redis,
+        Dependency::Mailer,
+        Dependency::IdentityProvider,
+    ];
+
+    /// Name of the dependency in reports and metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Dependency::Postgres => "postgres",
+            Dependency::Redis => "redis",
+            Dependency::Mailer => "mailer",
+            Dependency::IdentityProvider => "identity_provider",
+        }
+    }
+}
+
+impl std::fmt::Display for Dependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Limits above which a dependency counts as degraded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SliThresholds {
+    /// Share of failed calls, between 0 and 1
+    pub max_error_rate: f64,
+    pub max_p99: Duration,
+    /// Calls needed within the window before a dependency can be degraded
+    pub min_samples: usize,
+}
+
+impl Default for SliThresholds {
+    fn default() -> Self {
+        Self {
+            max_error_rate: 0.05,
+            max_p99: Duration::from_secs(1),
+            min_samples: 20,
+        }
+    }
+}
+
+/// Status of a dependency within the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyStatus {
+    Healthy,
+    Degraded,
+    /// Too few calls to judge
+    Unknown,
+}
+
+/// Error rate and latency of the calls to a dependency within the window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DependencySli {
+    pub dependency: Dependency,
+    pub critical: bool,
+    pub calls: usize,
+    pub error_rate: f64,
+    pub p99_ms: u64,
+    pub status: DependencyStatus,
+}
+
+/// Composite health of the tracked dependencies
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthReport {
+    /// Weighted share of dependencies that are not degraded, from 0 to 100
+    pub score: u8,
+    /// Whether a critical dependency is degraded, which fails readiness
+    pub degraded: bool,
+    pub dependencies: Vec<DependencySli>,
+}
+
+/// Outcome of a single call
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Instant,
+    latency: Duration,
+    success: bool,
+}
+
+/// Tracks error rates and p99 latencies of calls to dependencies over a sliding window
+///
+/// Postgres and Redis are critical by default: while one of them is degraded
+/// the instance reports not-ready. Clones share their samples.
+#[derive(Debug, Clone)]
+pub struct SliTracker {
+    window: Duration,
+    thresholds: SliThresholds,
+    critical: Vec<Dependency>,
+    samples: Arc<Mutex<HashMap<Dependency, VecDeque<Sample>>>>,
+}
+
+impl Default for SliTracker {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(300), SliThresholds::default())
+    }
+}
+
+impl SliTracker {
+    /// Creates a tracker judging the calls of the last `window`
+    pub fn new(window: Duration, thresholds: SliThresholds) -> Self {
+        Self {
+            window,
+            thresholds,
+            critical: vec![Dependency::Postgres, Dependency::Redis],
+            samples: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Sets the dependencies whose degradation fails readiness
+    pub fn with_critical(mut self, critical: Vec<Dependency>) -> Self {
+        self.critical = critical;
+        self
+    }
+
+    /// Records the outcome of a call to a dependency
+    pub fn record(&self, dependency: Dependency, latency: Duration, success: bool) {
+        let now = Instant::now();
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        let samples = samples.entry(dependency).or_default();
+        Self::prune(samples, now, self.window);
+        if samples.len() >= MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(Sample {
+            at: now,
+            latency,
+            success,
+        });
+    }
+
+    /// Runs a call to a dependency and records its latency and outcome
+    pub async fn observe<T, E, F>(&self, dependency: Dependency, call: F) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        let started = Instant::now();
+        let result = call.await;
+        self.record(dependency, started.elapsed(), result.is_ok());
+        result
+    }
+
+    /// Computes the SLIs of a dependency within the window
+    pub fn sli(&self, dependency: Dependency) -> DependencySli {
+        let now = Instant::now();
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        let samples = samples.entry(dependency).or_default();
+        Self::prune(samples, now, self.window);
+
+        let calls = samples.len();
+        let failures = samples.iter().filter(|s| !s.success).count();
+        let error_rate = if calls == 0 {
+            0.0
+        } else {
+            failures as f64 / calls as f64
+        };
+        let mut latencies: Vec<Duration> = samples.iter().map(|s| s.latency).collect();
+        latencies.sort_unstable();
+        let p99 = match latencies.len() {
+            0 => Duration::ZERO,
+            n => latencies[(n * 99).div_ceil(100) - 1],
+        };
+
+        let status = if calls < self.thresholds.min_samples.max(1) {
+            DependencyStatus::Unknown
+        } else if error_rate > self.thresholds.max_error_rate || p99 > self.thresholds.max_p99 {
+            DependencyStatus::Degraded
+        } else {
+            DependencyStatus::Healthy
+        };
+
+        DependencySli {
+            dependency,
+            critical: self.critical.contains(&dependency),
+            calls,
+            error_rate,
+            p99_ms: p99.as_millis() as u64,
+            status,
+        }
+    }
+
+    /// Computes the composite health of all dependencies
+    ///
+    /// Critical dependencies weigh more in the score; dependencies without enough
+    /// calls count as healthy.
+    pub fn report(&self) -> HealthReport {
+        let dependencies: Vec<DependencySli> =
+            Dependency::ALL.iter().map(|d| self.sli(*d)).collect();
+
+        let weight = |sli: &DependencySli| if sli.critical { CRITICAL_WEIGHT } else { 1 };
+        let total: u32 = dependencies.iter().map(weight).sum();
+        let healthy: u32 = dependencies
+            .iter()
+            .filter(|sli| sli.status != DependencyStatus::Degraded)
+            .map(weight)
+            .sum();
+
+        HealthReport {
+            score: (healthy * 100 / total.max(1)) as u8,
+            degraded: dependencies
+                .iter()
+                .any(|sli| sli.critical && sli.status == DependencyStatus::Degraded),
+            dependencies,
+        }
+    }
+
+    /// Checks whether a critical dependency is degraded
+    pub fn is_degraded(&self) -> bool {
+        self.critical
+            .iter()
+            .any(|d| self.sli(*d).status == DependencyStatus::Degraded)
+    }
+
+    /// Renders the SLIs in the Prometheus text exposition format
+    pub fn render_metrics(&self) -> String {
+        let report = self.report();
+        let mut out = String::new();
+        let _ = writeln!(out, "# TYPE acci_health_score gauge");
+        let _ = writeln!(out, "acci_health_score {}", report.score);
+        for (name, kind) in [
+            ("acci_dependency_calls", "gauge"),
+            ("acci_dependency_error_rate", "gauge"),
+            ("acci_dependency_p99_seconds", "gauge"),
+            ("acci_dependency_degraded", "gauge"),
+        ] {
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for sli in &report.dependencies {
+                let value = match name {
+                    "acci_dependency_calls" => sli.calls as f64,
+                    "acci_dependency_error_rate" => sli.error_rate,
+                    "acci_dependency_p99_seconds" => sli.p99_ms as f64 / 1000.0,
+                    _ => (sli.status == DependencyStatus::Degraded) as u8 as f64,
+                };
+                let _ = writeln!(
+                    out,
+                    "{}{{dependency=\"{}\"}} {}",
+                    name, sli.dependency, value
+                );
+            }
+        }
+        out
+    }
+
+    /// Drops the samples that left the window
+    fn prune(samples: &mut VecDeque<Sample>, now: Instant, window: Duration) {
+        while samples
+            .front()
+            .is_some_and(|s| now.duration_since(s.at) > window)
+        {
+            samples.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> SliTracker {
+        SliTracker::new(
+            Duration::from_secs(60),
+            SliThresholds {
+                max_error_rate: 0.1,
+                max_p99: Duration::from_millis(500),
+                min_samples: 10,
+            },
+        )
+    }
+
+    #[test]
+    fn test_error_rate_and_p99() {
+        let tracker = tracker();
+        for i in 0..100 {
+            tracker.record(
+                Dependency::Postgres,
+                Duration::from_millis(if i == 99 { 900 } else { 10 }),
+                i % 20 != 0,
+            );
+        }
+
+        let sli = tracker.sli(Dependency::Postgres);
+        assert_eq!(sli.calls, 100);
+        assert!((sli.error_rate - 0.05).abs() < f64::EPSILON);
+        assert_eq!(sli.p99_ms, 10);
+        assert_eq!(sli.status, DependencyStatus::Healthy);
+        assert!(sli.critical);
+
+        // Too few calls to judge
+        tracker.record(Dependency::Mailer, Duration::from_secs(5), false);
+        assert_eq!(
+            tracker.sli(Dependency::Mailer).status,
+            DependencyStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn test_degraded_dependencies() {
+        let tracker = tracker();
+        for _ in 0..10 {
+            tracker.record(Dependency::Mailer, Duration::from_secs(2), true);
+        }
+
+        // Non-critical dependencies only lower the score
+        let report = tracker.report();
+        assert!(!report.degraded);
+        assert!(!tracker.is_degraded());
+        assert_eq!(report.score, 87);
+
+        for _ in 0..10 {
+            tracker.record(Dependency::Redis, Duration::from_millis(1), false);
+        }
+        let report = tracker.report();
+        assert!(report.degraded);
+        assert!(tracker.is_degraded());
+        assert_eq!(report.score, 50);
+
+        let metrics = tracker.render_metrics();
+        assert!(metrics.contains("acci_health_score 50"));
+        assert!(metrics.contains("acci_dependency_degraded{dependency=\"redis\"} 1"));
+        assert!(metrics.contains("acci_dependency_error_rate{dependency=\"redis\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn test_observe() {
+        let tracker = tracker();
+        let ok: Result<u8, ()> = tracker.observe(Dependency::Redis, async { Ok(1) }).await;
+        assert_eq!(ok, Ok(1));
+        let err: Result<u8, ()> = tracker.observe(Dependency::Redis, async { Err(()) }).await;
+        assert!(err.is_err());
+
+        let sli = tracker.sli(Dependency::Redis);
+        assert_eq!(sli.calls, 2);
+        assert!((sli.error_rate - 0.5).abs() < f64::EPSILON);
+    }
+}
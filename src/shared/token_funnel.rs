@@ -0,0 +1,157 @@
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+
+use crate::shared::{error::Result, types::TenantId};
+
+/// Token based flow whose funnel is tracked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenFlow {
+    /// Email verification of a public signup
+    SignupVerification,
+}
+
+impl std::fmt::Display for TokenFlow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenFlow::SignupVerification => write!(f, "signup_verification"),
+        }
+    }
+}
+
+/// Step of a token's lifecycle counted in the funnel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenOutcome {
+    Issued,
+    Completed,
+    /// The token expired unused and was purged
+    Expired,
+}
+
+/// Issuance and completion counters of a flow within a tenant
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenFunnel {
+    pub tenant_id: TenantId,
+    pub flow: String,
+    pub issued: i64,
+    pub completed: i64,
+    pub expired: i64,
+    /// Share of issued tokens that were completed, `None` before the first issuance
+    pub completion_rate: Option<f64>,
+}
+
+impl TokenFunnel {
+    /// Creates the funnel of the given counters
+    pub fn new(
+        tenant_id: TenantId,
+        flow: String,
+        issued: i64,
+        completed: i64,
+        expired: i64,
+    ) -> Self {
+        let completion_rate = (issued > 0).then(|| completed as f64 / issued as f64);
+        Self {
+            tenant_id,
+            flow,
+            issued,
+            completed,
+            expired,
+            completion_rate,
+        }
+    }
+}
+
+/// Repository of the token funnel counters
+#[derive(Debug, Clone)]
+pub struct TokenFunnelRepository {
+    pool: Pool<Postgres>,
+}
+
+impl TokenFunnelRepository {
+    /// Creates a new TokenFunnelRepository instance
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    /// Counts tokens of a flow reaching a step of their lifecycle
+    pub async fn record(
+        &self,
+        tenant_id: TenantId,
+        flow: TokenFlow,
+        outcome: TokenOutcome,
+        count: i64,
+    ) -> Result<()> {
+        let (issued, completed, expired) = match outcome {
+            TokenOutcome::Issued => (count, 0, 0),
+            TokenOutcome::Completed => (0, count, 0),
+            TokenOutcome::Expired => (0, 0, count),
+        };
+        sqlx::query!(
+            r#"
+            INSERT INTO token_funnel (tenant_id, flow, issued, completed, expired)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (tenant_id, flow) DO UPDATE
+            SET issued = token_funnel.issued + EXCLUDED.issued,
+                completed = token_funnel.completed + EXCLUDED.completed,
+                expired = token_funnel.expired + EXCLUDED.expired,
+                updated_at = NOW()
+            "#,
+            tenant_id.0 as uuid::Uuid,
+            flow.to_string(),
+            issued,
+            completed,
+            expired,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Lists the funnels of all flows, optionally of a single tenant
+    pub async fn list(&self, tenant_id: Option<TenantId>) -> Result<Vec<TokenFunnel>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT tenant_id, flow, issued, completed, expired
+            FROM token_funnel
+            WHERE $1::uuid IS NULL OR tenant_id = $1
+            ORDER BY tenant_id, flow
+            "#,
+            tenant_id.map(|id| id.0),
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                TokenFunnel::new(
+                    TenantId(r.tenant_id),
+                    r.flow,
+                    r.issued,
+                    r.completed,
+                    r.expired,
+                )
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_funnel_completion_rate() {
+        let funnel = TokenFunnel::new(
+            TenantId::new(),
+            TokenFlow::SignupVerification.to_string(),
+            4,
+            3,
+            1,
+        );
+        assert_eq!(funnel.flow, "signup_verification");
+        assert_eq!(funnel.completion_rate, Some(0.75));
+
+        let empty = TokenFunnel::new(TenantId::new(), funnel.flow, 0, 0, 0);
+        assert_eq!(empty.completion_rate, None);
+    }
+}
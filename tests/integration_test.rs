@@ -1,10 +1,17 @@
 use acci_rust::{
     core::{
-        config::{Config, DatabaseConfig, RedisConfig, ServerConfig},
+        config::{
+            Config, DatabaseConfig, EmailConfig, LoginThrottleConfig, ModulesConfig, PolicyConfig,
+            ProbeConfig, RedisConfig, SecretsConfig, ServerConfig, SessionConfig,
+            SessionFallbackConfig, SessionStoreKind, SignupConfig, SsoConfig, TenantConfig,
+        },
         Core,
     },
     modules::identity::{
-        models::{Credentials, Permission, PermissionAction, Role, RoleType, User},
+        models::{
+            Credentials, Permission, PermissionAction, PermissionEffect, PermissionScope, Role,
+            RoleType, User,
+        },
         AuthenticationService, IdentityModule,
     },
     shared::{
@@ -22,6 +29,8 @@ async fn test_core_initialization() -> Result<()> {
             host: "127.0.0.1".to_string(),
             port: 3000,
             cors_allowed_origins: vec!["http://localhost:3000".to_string()],
+            api_prefix: "/api".to_string(),
+            tls: None,
         },
         database: DatabaseConfig {
             host: "localhost".to_string(),
@@ -34,7 +43,21 @@ async fn test_core_initialization() -> Result<()> {
         },
         redis: RedisConfig {
             url: "redis://localhost:6379".to_string(),
+            fallback: SessionFallbackConfig::default(),
+            ..RedisConfig::default_dev()
         },
+        modules: ModulesConfig::default(),
+        signup: SignupConfig::default(),
+        emails: EmailConfig::default(),
+        sso: SsoConfig::default(),
+        secrets: SecretsConfig::default(),
+        policy: PolicyConfig::default(),
+        probes: ProbeConfig::default(),
+        login_throttle: LoginThrottleConfig::default(),
+        sessions: SessionConfig::default(),
+        tenants: TenantConfig::default(),
+        regions: Vec::new(),
+        bootstrap_file: None,
     };
 
     let _core = Core::new(config).await?;
@@ -48,6 +71,8 @@ async fn test_user_authentication() -> Result<()> {
             host: "127.0.0.1".to_string(),
             port: 3000,
             cors_allowed_origins: vec!["http://localhost:3000".to_string()],
+            api_prefix: "/api".to_string(),
+            tls: None,
         },
         database: DatabaseConfig {
             host: "localhost".to_string(),
@@ -60,7 +85,21 @@ async fn test_user_authentication() -> Result<()> {
         },
         redis: RedisConfig {
             url: "redis://localhost:6379".to_string(),
+            fallback: SessionFallbackConfig::default(),
+            ..RedisConfig::default_dev()
         },
+        modules: ModulesConfig::default(),
+        signup: SignupConfig::default(),
+        emails: EmailConfig::default(),
+        sso: SsoConfig::default(),
+        secrets: SecretsConfig::default(),
+        policy: PolicyConfig::default(),
+        probes: ProbeConfig::default(),
+        login_throttle: LoginThrottleConfig::default(),
+        sessions: SessionConfig::default(),
+        tenants: TenantConfig::default(),
+        regions: Vec::new(),
+        bootstrap_file: None,
     };
 
     let _core = Core::new(config).await?;
@@ -89,6 +128,8 @@ async fn create_test_identity_module() -> Result<(IdentityModule, Authentication
             host: "127.0.0.1".to_string(),
             port: 3000,
             cors_allowed_origins: vec!["http://localhost:3000".to_string()],
+            api_prefix: "/api".to_string(),
+            tls: None,
         },
         database: DatabaseConfig {
             host: "localhost".to_string(),
@@ -101,11 +142,28 @@ async fn create_test_identity_module() -> Result<(IdentityModule, Authentication
         },
         redis: RedisConfig {
             url: "redis://localhost:6379".to_string(),
+            fallback: SessionFallbackConfig::default(),
+            ..RedisConfig::default_dev()
+        },
+        modules: ModulesConfig::default(),
+        signup: SignupConfig::default(),
+        emails: EmailConfig::default(),
+        sso: SsoConfig::default(),
+        secrets: SecretsConfig::default(),
+        policy: PolicyConfig::default(),
+        probes: ProbeConfig::default(),
+        login_throttle: LoginThrottleConfig::default(),
+        sessions: SessionConfig {
+            store: SessionStoreKind::Memory,
+            ..SessionConfig::default()
         },
+        tenants: TenantConfig::default(),
+        regions: Vec::new(),
+        bootstrap_file: None,
     };
 
-    let core = Core::new(config).await?;
-    acci_rust::modules::identity::create_identity_module(core.database).await
+    let core = Core::new(config.clone()).await?;
+    acci_rust::modules::identity::create_identity_module(&core.databases, &config).await
 }
 
 async fn create_test_user(identity_module: &IdentityModule) -> Result<User> {
@@ -121,6 +179,8 @@ async fn create_test_user(identity_module: &IdentityModule) -> Result<User> {
                 name: "Create User".to_string(),
                 action: PermissionAction::Create,
                 resource: "users".to_string(),
+                scope: PermissionScope::All,
+                effect: PermissionEffect::Allow,
             }];
             role
         }],
@@ -130,6 +190,7 @@ async fn create_test_user(identity_module: &IdentityModule) -> Result<User> {
         updated_at: OffsetDateTime::now_utc(),
         mfa_enabled: false,
         mfa_secret: None,
+        token_scopes: None,
     };
 
     identity_module.create_user(&user).await
@@ -142,13 +203,13 @@ async fn test_user_permissions() -> Result<()> {
 
     // Test permission check
     let has_permission = identity_module
-        .check_permission(&user, PermissionAction::Create, "users")
+        .check_permission(&user, PermissionAction::Create, "users", None)
         .await?;
     assert!(has_permission);
 
     // Test permission does not exist
     let has_permission = identity_module
-        .check_permission(&user, PermissionAction::Delete, "users")
+        .check_permission(&user, PermissionAction::Delete, "users", None)
         .await?;
     assert!(!has_permission);
 